@@ -0,0 +1,442 @@
+//! # BGZF-Framed Files
+//!
+//! This module writes and reads VBINSEQ files framed in BGZF (blocked gzip), the container
+//! format used by htslib. It requires the `bgzf` feature.
+//!
+//! A BGZF-framed file is a regular VBINSEQ file compressed with BGZF, so generic tooling
+//! such as `bgzip -d` restores the plain VBINSEQ file. In addition, every record block starts
+//! a new BGZF frame, so each block can be located by the virtual offset of its first frame.
+//! These virtual offsets are recorded in the reserved bytes of the `BlockRange`s of the
+//! index (see `BlockRange::virtual_offset`), which allows tools to decompress and slice the
+//! container block by block.
+//!
+//! Since BGZF compresses the whole container, the record blocks are typically written
+//! without ZSTD compression.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use std::fs::File;
+//! use vbinseq::bgzf::{BgzfReader, BgzfWriter};
+//! use vbinseq::{VBinseqHeader, VBinseqWriterBuilder};
+//!
+//! // Write a BGZF-framed file and its index
+//! let mut bgzf = BgzfWriter::new(File::create("reads.vbq.gz").unwrap());
+//! let mut writer = VBinseqWriterBuilder::default()
+//!     .header(VBinseqHeader::new(false, false, false))
+//!     .build(&mut bgzf)
+//!     .unwrap();
+//! writer.write_nucleotides(0, b"ACGTACGT").unwrap();
+//! writer.finish().unwrap();
+//! drop(writer);
+//! let index = bgzf.finish().unwrap();
+//! index.save_to_path("reads.vbq.gz.vqi").unwrap();
+//!
+//! // Read the last block directly
+//! let mut reader = BgzfReader::new(File::open("reads.vbq.gz").unwrap()).unwrap();
+//! let mut block = reader.new_block();
+//! let last = index.ranges().last().unwrap();
+//! reader.read_block_at(last, &mut block).unwrap();
+//! ```
+
+use std::io::{self, Read, Seek, Write};
+use std::sync::Arc;
+
+use byteorder::{ByteOrder, LittleEndian};
+use noodles_bgzf as bgzf;
+
+use crate::error::{IndexError, ReadError, Result};
+use crate::footer::FOOTER_MAGIC;
+use crate::header::{SIZE_BLOCK_HEADER, SIZE_HEADER};
+use crate::index::{IndexHeader, INDEX_MAGIC};
+use crate::reader::RecordBlock;
+use crate::sections::HeaderSections;
+use crate::{BlockHeader, BlockIndex, BlockRange, VBinseqHeader};
+
+/// Position of the `BgzfWriter` in the VBINSEQ byte stream
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FrameState {
+    /// Reading the file header
+    Header,
+
+    /// Reading a block header (or the footer)
+    BlockHeader,
+
+    /// Reading the data of a block (or the header sections) with the given number of
+    /// remaining bytes
+    BlockData(u64),
+
+    /// Reading the bytes following the last block (the embedded index and the footer)
+    Trailer,
+}
+
+/// Writer framing a VBINSEQ byte stream in BGZF
+///
+/// `BgzfWriter` is used as the destination of a `VBinseqWriter`. It compresses the stream
+/// with BGZF and starts a new BGZF frame at every record block, recording the virtual offset
+/// of every block. `finish` completes the BGZF stream and returns the index of the blocks.
+pub struct BgzfWriter<W: Write> {
+    /// The BGZF writer
+    inner: bgzf::io::Writer<W>,
+
+    /// Position in the VBINSEQ byte stream
+    state: FrameState,
+
+    /// Buffered bytes of the current file or block header
+    pending: Vec<u8>,
+
+    /// Number of uncompressed bytes written
+    position: u64,
+
+    /// Number of records in the blocks written so far
+    n_records: u64,
+
+    /// Ranges of the blocks written so far
+    ranges: Vec<BlockRange>,
+}
+impl<W: Write> BgzfWriter<W> {
+    /// Creates a new BGZF writer
+    ///
+    /// # Parameters
+    ///
+    /// * `inner` - The destination of the BGZF stream
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner: bgzf::io::Writer::new(inner),
+            state: FrameState::Header,
+            pending: Vec::with_capacity(SIZE_HEADER),
+            position: 0,
+            n_records: 0,
+            ranges: Vec::new(),
+        }
+    }
+
+    /// Finishes the BGZF stream and returns the index of the written blocks
+    ///
+    /// The block ranges of the index record the virtual offsets of their blocks. The index
+    /// is sized to the compressed stream, so it can be saved next to the BGZF-framed file.
+    ///
+    /// # Errors
+    ///
+    /// * I/O errors from writing the remaining frames
+    pub fn finish(&mut self) -> Result<BlockIndex> {
+        self.write_pending()?;
+        self.inner.try_finish()?;
+
+        let mut index = BlockIndex::new(IndexHeader::new(self.inner.position()));
+        for range in &self.ranges {
+            index.add_range(*range);
+        }
+        Ok(index)
+    }
+
+    /// Writes the buffered header bytes to the BGZF stream
+    fn write_pending(&mut self) -> io::Result<()> {
+        self.inner.write_all(&self.pending)?;
+        self.position += self.pending.len() as u64;
+        self.pending.clear();
+        Ok(())
+    }
+
+    /// Handles a completely buffered file or block header
+    fn complete_pending(&mut self) -> io::Result<()> {
+        if self.state == FrameState::Header {
+            let mut header_bytes = [0u8; SIZE_HEADER];
+            header_bytes.copy_from_slice(&self.pending);
+            let header = VBinseqHeader::from_bytes(&header_bytes)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            self.write_pending()?;
+
+            // The header sections share the frame of the header
+            let sections_size = header.sections_size() as u64;
+            self.state = FrameState::BlockData(sections_size);
+            if sections_size == 0 {
+                self.complete_block()?;
+            }
+            return Ok(());
+        }
+
+        if matches!(
+            LittleEndian::read_u64(&self.pending[0..8]),
+            FOOTER_MAGIC | INDEX_MAGIC
+        ) {
+            self.state = FrameState::Trailer;
+            return self.write_pending();
+        }
+        let mut header_bytes = [0u8; SIZE_BLOCK_HEADER];
+        header_bytes.copy_from_slice(&self.pending);
+        let block_header = BlockHeader::from_bytes(&header_bytes)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        let mut range = BlockRange::new(
+            self.position,
+            block_header.size,
+            block_header.records,
+            self.n_records,
+        );
+        range.set_virtual_offset(u64::from(self.inner.virtual_position()));
+        self.ranges.push(range);
+        self.n_records += u64::from(block_header.records);
+
+        self.write_pending()?;
+        self.state = FrameState::BlockData(block_header.size);
+        if block_header.size == 0 {
+            self.complete_block()?;
+        }
+        Ok(())
+    }
+
+    /// Ends the frame of a completely written block
+    fn complete_block(&mut self) -> io::Result<()> {
+        self.inner.flush()?;
+        self.state = FrameState::BlockHeader;
+        Ok(())
+    }
+}
+impl<W: Write> Write for BgzfWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut rest = buf;
+        while !rest.is_empty() {
+            match self.state {
+                FrameState::Header | FrameState::BlockHeader => {
+                    let size = if self.state == FrameState::Header {
+                        SIZE_HEADER
+                    } else {
+                        SIZE_BLOCK_HEADER
+                    };
+                    let take = (size - self.pending.len()).min(rest.len());
+                    self.pending.extend_from_slice(&rest[..take]);
+                    rest = &rest[take..];
+                    if self.pending.len() == size {
+                        self.complete_pending()?;
+                    }
+                }
+                FrameState::BlockData(remaining) => {
+                    let take = remaining.min(rest.len() as u64) as usize;
+                    self.inner.write_all(&rest[..take])?;
+                    self.position += take as u64;
+                    rest = &rest[take..];
+                    if take as u64 == remaining {
+                        self.complete_block()?;
+                    } else {
+                        self.state = FrameState::BlockData(remaining - take as u64);
+                    }
+                }
+                FrameState::Trailer => {
+                    self.inner.write_all(rest)?;
+                    self.position += rest.len() as u64;
+                    rest = &[];
+                }
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        // Frames are only ended at block boundaries
+        Ok(())
+    }
+}
+
+/// Reader for BGZF-framed VBINSEQ files
+///
+/// Blocks are read sequentially with `read_block_into`, or directly by their virtual offset
+/// with `read_block_at`.
+pub struct BgzfReader<R: Read> {
+    /// The BGZF reader
+    inner: bgzf::io::Reader<R>,
+
+    /// Parsed header information from the file
+    header: VBinseqHeader,
+
+    /// Parsed header sections of the file
+    sections: HeaderSections,
+
+    /// Compression dictionaries of the file (see the `dictionary` module)
+    dictionaries: Arc<[Vec<u8>]>,
+
+    /// Reusable buffer for the data of a block
+    buffer: Vec<u8>,
+
+    /// Total number of records read so far
+    total: u64,
+
+    /// Whether the last block was read
+    done: bool,
+}
+impl<R: Read> BgzfReader<R> {
+    /// Creates a new reader over a BGZF-framed VBINSEQ file
+    ///
+    /// # Errors
+    ///
+    /// * I/O errors if the BGZF stream cannot be decompressed
+    /// * Header validation errors if the stream doesn't start with a valid VBINSEQ header
+    /// * `HeaderError::InvalidSection` if the header sections cannot be parsed
+    pub fn new(inner: R) -> Result<Self> {
+        let mut inner = bgzf::io::Reader::new(inner);
+        let mut header_bytes = [0u8; SIZE_HEADER];
+        inner.read_exact(&mut header_bytes)?;
+        let header = VBinseqHeader::from_bytes(&header_bytes)?;
+        let sections = HeaderSections::from_reader(&mut inner, &header)?;
+        let dictionaries = sections.dictionaries.clone().into();
+        Ok(Self {
+            inner,
+            header,
+            sections,
+            dictionaries,
+            buffer: Vec::new(),
+            total: 0,
+            done: false,
+        })
+    }
+
+    /// Returns a copy of the file's header information
+    pub fn header(&self) -> VBinseqHeader {
+        self.header
+    }
+
+    /// Returns the header sections of the file (e.g. the read-group table)
+    pub fn sections(&self) -> &HeaderSections {
+        &self.sections
+    }
+
+    /// Creates a new empty record block with the appropriate size for this file
+    pub fn new_block(&self) -> RecordBlock {
+        RecordBlock::new(self.header.block() as usize)
+    }
+
+    /// Fills an existing RecordBlock with the next block of records
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(true)` - If a block was successfully read
+    /// * `Ok(false)` - If the end of the file was reached (no more blocks)
+    /// * `Err(_)` - If an error occurred during reading
+    pub fn read_block_into(&mut self, block: &mut RecordBlock) -> Result<bool> {
+        block.clear();
+        if self.done {
+            return Ok(false);
+        }
+
+        // Read the next block header (the stream ends with the blocks or the footer)
+        let mut header_bytes = [0u8; SIZE_BLOCK_HEADER];
+        let n_read = read_full(&mut self.inner, &mut header_bytes)?;
+        if n_read == 0
+            || matches!(
+                LittleEndian::read_u64(&header_bytes[0..8]),
+                FOOTER_MAGIC | INDEX_MAGIC
+            )
+        {
+            self.done = true;
+            return Ok(false);
+        }
+        if n_read < SIZE_BLOCK_HEADER {
+            return Err(ReadError::UnexpectedEndOfFile(n_read).into());
+        }
+        let block_header = BlockHeader::from_bytes(&header_bytes)?;
+
+        // Read the block contents
+        self.buffer.resize(block_header.size as usize, 0);
+        self.inner.read_exact(&mut self.buffer)?;
+        block.set_dictionaries(&self.dictionaries);
+        block.ingest_block(&block_header, &self.buffer, &self.header)?;
+        block.update_index(self.total);
+        self.total += u64::from(block_header.records);
+
+        Ok(true)
+    }
+}
+impl<R: Read + Seek> BgzfReader<R> {
+    /// Reads the block of a range directly from its virtual offset
+    ///
+    /// Subsequent calls to `read_block_into` continue with the following block.
+    ///
+    /// # Parameters
+    ///
+    /// * `range` - The range of the block (from the index of the BGZF-framed file)
+    /// * `block` - The block to fill
+    ///
+    /// # Errors
+    ///
+    /// * `IndexError::MissingVirtualOffset` - If the range has no virtual offset
+    /// * I/O and format errors from reading the block
+    pub fn read_block_at(&mut self, range: &BlockRange, block: &mut RecordBlock) -> Result<bool> {
+        let Some(virtual_offset) = range.virtual_offset() else {
+            return Err(IndexError::MissingVirtualOffset(range.start_offset).into());
+        };
+        self.inner
+            .seek(bgzf::VirtualPosition::from(virtual_offset))?;
+        self.total = range.cumulative_records;
+        self.done = false;
+        self.read_block_into(block)
+    }
+}
+
+/// Reads until `buf` is full or the end of the stream, returning the number of bytes read
+fn read_full<R: Read>(reader: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+    let mut n_read = 0;
+    while n_read < buf.len() {
+        match reader.read(&mut buf[n_read..]) {
+            Ok(0) => break,
+            Ok(n) => n_read += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(n_read)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::conformance::TestVector;
+    use crate::MemoryReader;
+
+    #[test]
+    fn test_bgzf_round_trip() -> Result<()> {
+        let mut vector = TestVector::generate(true, false, true);
+        vector.header.set_footer(true);
+
+        let mut bgzf = BgzfWriter::new(Vec::new());
+        vector.write_vbq(&mut bgzf)?;
+        let index = bgzf.finish()?;
+        let framed = bgzf.inner.get_ref().clone();
+        assert!(index.n_blocks() > 1);
+
+        // Decompressing the container restores the plain file
+        let mut plain = Vec::new();
+        bgzf::io::Reader::new(&framed[..]).read_to_end(&mut plain)?;
+        let mut expected = Vec::new();
+        vector.write_vbq(&mut expected)?;
+        assert_eq!(plain, expected);
+        let plain_index = MemoryReader::new(plain)?.build_index()?;
+        for (range, plain_range) in index.ranges().iter().zip(plain_index.ranges()) {
+            assert_eq!(range.start_offset, plain_range.start_offset);
+            assert_eq!(range.cumulative_records, plain_range.cumulative_records);
+        }
+
+        // Sequential reading
+        let mut reader = BgzfReader::new(&framed[..])?;
+        let mut block = reader.new_block();
+        let mut n_records = 0;
+        while reader.read_block_into(&mut block)? {
+            n_records += block.n_records();
+        }
+        assert_eq!(n_records, vector.records.len());
+
+        // Random access by virtual offset
+        let mut reader = BgzfReader::new(Cursor::new(&framed))?;
+        let last = index.ranges().last().unwrap();
+        assert!(reader.read_block_at(last, &mut block)?);
+        let record = block.iter().next().unwrap();
+        let expected_record = &vector.records[last.cumulative_records as usize];
+        let mut sequence = Vec::new();
+        record.decode_s(&mut sequence)?;
+        assert_eq!(record.index(), last.cumulative_records);
+        assert_eq!(sequence, expected_record.sequence);
+        assert!(!reader.read_block_into(&mut block)?);
+        Ok(())
+    }
+}
@@ -0,0 +1,45 @@
+//! `vbq cat` - decodes records to FASTQ or FASTA
+
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use clap::Args;
+use vbinseq::{MmapReader, OwnedRecord};
+
+use crate::fastx::{FastxWriter, Format};
+use crate::output_writer;
+
+#[derive(Args)]
+pub struct CatArgs {
+    /// Input VBINSEQ file
+    input: PathBuf,
+
+    /// Output file [default: stdout]
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Output format
+    #[arg(short, long, value_enum, default_value_t = Format::Fastq)]
+    format: Format,
+}
+
+/// Decodes every record of the input, writing paired records as interleaved mates
+pub fn run(args: &CatArgs) -> Result<()> {
+    let mut reader = MmapReader::new(&args.input)
+        .with_context(|| format!("Failed to open {}", args.input.display()))?;
+    let mut block = reader.new_block();
+    let mut writer = FastxWriter::new(
+        output_writer(args.output.as_deref())?,
+        args.format,
+        reader.header().is_homopolymer(),
+    );
+
+    let mut record = OwnedRecord::default();
+    while reader.read_block_into(&mut block)? {
+        for ref_record in block.iter() {
+            record.fill(&ref_record)?;
+            writer.write_record(&record)?;
+        }
+    }
+    writer.finish()
+}
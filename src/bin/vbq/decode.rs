@@ -0,0 +1,55 @@
+//! `vbq decode` - converts VBINSEQ to FASTQ or FASTA as a filter
+
+use std::fs::File;
+use std::io::{stdin, BufReader, Read};
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use clap::Args;
+use vbinseq::{OwnedRecord, StreamReader};
+
+use crate::fastx::{FastxWriter, Format};
+use crate::output_writer;
+
+#[derive(Args)]
+pub struct DecodeArgs {
+    /// Input VBINSEQ file [default: stdin]
+    input: Option<PathBuf>,
+
+    /// Output file [default: stdout]
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Output format
+    #[arg(short, long, value_enum, default_value_t = Format::Fastq)]
+    format: Format,
+}
+
+/// Decodes a VBINSEQ stream, writing paired records as interleaved mates
+///
+/// Unlike `cat`, the input does not need to be a regular file (e.g. a pipe), which is
+/// decoded block by block as it arrives.
+pub fn run(args: &DecodeArgs) -> Result<()> {
+    let input: Box<dyn Read> = match &args.input {
+        Some(path) => Box::new(
+            File::open(path).with_context(|| format!("Failed to open {}", path.display()))?,
+        ),
+        None => Box::new(stdin().lock()),
+    };
+    let mut reader = StreamReader::new(BufReader::new(input))?;
+    let mut block = reader.new_block();
+    let mut writer = FastxWriter::new(
+        output_writer(args.output.as_deref())?,
+        args.format,
+        reader.header().is_homopolymer(),
+    );
+
+    let mut record = OwnedRecord::default();
+    while reader.read_block_into(&mut block)? {
+        for ref_record in block.iter() {
+            record.fill(&ref_record)?;
+            writer.write_record(&record)?;
+        }
+    }
+    writer.finish()
+}
@@ -0,0 +1,49 @@
+//! `vbq digest` - prints content digests of decoded records
+
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use clap::Args;
+use vbinseq::digest::{digest_fastq, digest_fastq_paired, digest_file};
+
+#[derive(Args)]
+pub struct DigestArgs {
+    /// Input VBINSEQ file (or FASTQ file with --fastq)
+    input: PathBuf,
+
+    /// Read the input as uncompressed FASTQ
+    #[arg(long)]
+    fastq: bool,
+
+    /// FASTQ file with the mates of the records of the input
+    #[arg(long, requires = "fastq")]
+    mate: Option<PathBuf>,
+
+    /// Exclude quality scores from the digests
+    #[arg(long)]
+    no_quality: bool,
+}
+
+fn open(path: &Path) -> Result<BufReader<File>> {
+    File::open(path)
+        .map(BufReader::new)
+        .with_context(|| format!("Failed to open {}", path.display()))
+}
+
+/// Prints the digests of the input
+///
+/// Running this on a VBINSEQ file and on the FASTQ file it was converted from shows
+/// whether the conversion preserved the records.
+pub fn run(args: &DigestArgs) -> Result<()> {
+    let quality = !args.no_quality;
+    let digest = match (args.fastq, &args.mate) {
+        (false, _) => digest_file(&args.input, quality)
+            .with_context(|| format!("Failed to read {}", args.input.display()))?,
+        (true, None) => digest_fastq(open(&args.input)?, quality)?,
+        (true, Some(mate)) => digest_fastq_paired(open(&args.input)?, open(mate)?, quality)?,
+    };
+    println!("{digest}");
+    Ok(())
+}
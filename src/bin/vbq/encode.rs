@@ -0,0 +1,243 @@
+//! `vbq encode` - converts FASTQ to VBINSEQ as a streaming filter
+
+use std::fs::File;
+use std::io::{stdin, Read};
+use std::path::PathBuf;
+
+use anyhow::{bail, ensure, Context, Result};
+use clap::{Args, ValueEnum};
+use seq_io::fastq::{self, Record};
+use vbinseq::header::BLOCK_SIZE;
+use vbinseq::{
+    Codec, OwnedRecord, ParallelVBinseqWriter, Policy, QualityBins, VBinseqHeader,
+    VBinseqWriterBuilder,
+};
+
+use crate::output_writer;
+
+/// Handling of sequences with invalid nucleotides (see `vbinseq::Policy`)
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum PolicyArg {
+    /// Skip the record
+    Ignore,
+    /// Stop with an error
+    Break,
+    /// Stop with an error reporting the positions of the invalid nucleotides
+    Report,
+    /// Replace invalid nucleotides with random nucleotides
+    Random,
+    /// Replace invalid nucleotides with A
+    A,
+    /// Replace invalid nucleotides with C
+    C,
+    /// Replace invalid nucleotides with G
+    G,
+    /// Replace invalid nucleotides with T
+    T,
+}
+impl From<PolicyArg> for Policy {
+    fn from(policy: PolicyArg) -> Self {
+        match policy {
+            PolicyArg::Ignore => Policy::IgnoreSequence,
+            PolicyArg::Break => Policy::BreakOnInvalid,
+            PolicyArg::Report => Policy::ReportPositions,
+            PolicyArg::Random => Policy::RandomDraw,
+            PolicyArg::A => Policy::SetToA,
+            PolicyArg::C => Policy::SetToC,
+            PolicyArg::G => Policy::SetToG,
+            PolicyArg::T => Policy::SetToT,
+        }
+    }
+}
+
+/// Codec of the compressed blocks (see `vbinseq::Codec`)
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum CodecArg {
+    /// Zstd, balancing speed and compression ratio
+    Zstd,
+    /// LZ4, the fastest (requires the `lz4` feature)
+    Lz4,
+    /// XZ, the most compact (requires the `xz` feature)
+    Xz,
+}
+impl From<CodecArg> for Codec {
+    fn from(codec: CodecArg) -> Self {
+        match codec {
+            CodecArg::Zstd => Codec::Zstd,
+            CodecArg::Lz4 => Codec::Lz4,
+            CodecArg::Xz => Codec::Xz,
+        }
+    }
+}
+
+#[derive(Args)]
+pub struct EncodeArgs {
+    /// Input FASTQ file [default: stdin]
+    input: Option<PathBuf>,
+
+    /// Output VBINSEQ file [default: stdout]
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Treat consecutive records as the mates of paired records
+    #[arg(short, long)]
+    interleaved: bool,
+
+    /// Do not store quality scores
+    #[arg(long)]
+    no_quality: bool,
+
+    /// Do not compress blocks
+    #[arg(short, long)]
+    uncompressed: bool,
+
+    /// Codec of the compressed blocks
+    #[arg(long, value_enum, default_value_t = CodecArg::Zstd, conflicts_with = "uncompressed")]
+    codec: CodecArg,
+
+    /// Virtual block size in bytes
+    #[arg(short, long, default_value_t = BLOCK_SIZE)]
+    block_size: u64,
+
+    /// Use settings suited to long reads (16MB blocks, run-length quality transform, and a
+    /// higher compression level)
+    #[arg(short, long, conflicts_with_all = ["block_size", "interleaved"])]
+    long_reads: bool,
+
+    /// Store N and the IUPAC ambiguity codes exactly (4 bits per nucleotide)
+    #[arg(long)]
+    iupac: bool,
+
+    /// Group the sequences and quality scores of every block for better compression
+    #[arg(long)]
+    columnar: bool,
+
+    /// Bin quality scores to Illumina's 8 levels before storage (lossy)
+    #[arg(long, conflicts_with = "no_quality")]
+    bin_quality: bool,
+
+    /// Handling of sequences with invalid nucleotides
+    #[arg(short, long, value_enum, default_value_t = PolicyArg::Ignore)]
+    policy: PolicyArg,
+
+    /// Number of threads encoding and compressing blocks
+    #[arg(short, long, default_value_t = 1)]
+    threads: usize,
+
+    /// Store the block index in the output instead of requiring a .vqi index file
+    #[arg(long)]
+    embed_index: bool,
+
+    /// Metadata entry stored in the output header (can be repeated)
+    #[arg(short, long, value_name = "KEY=VALUE", value_parser = parse_metadata)]
+    metadata: Vec<(String, String)>,
+}
+
+/// Parses a `KEY=VALUE` metadata entry
+fn parse_metadata(entry: &str) -> Result<(String, String)> {
+    let Some((key, value)) = entry.split_once('=') else {
+        bail!("expected KEY=VALUE, found {entry:?}");
+    };
+    Ok((key.to_string(), value.to_string()))
+}
+
+/// Encodes FASTQ records into a VBINSEQ stream
+///
+/// Records are written as they are parsed, so the conversion can sit between other tools
+/// in a pipeline. Record names are not stored.
+pub fn run(args: &EncodeArgs) -> Result<()> {
+    let input: Box<dyn Read> = match &args.input {
+        Some(path) => Box::new(
+            File::open(path).with_context(|| format!("Failed to open {}", path.display()))?,
+        ),
+        None => Box::new(stdin().lock()),
+    };
+    let mut reader = fastq::Reader::new(input);
+
+    let mut builder = VBinseqWriterBuilder::default();
+    let mut header = if args.long_reads {
+        builder = builder.long_read_profile();
+        let mut header = VBinseqHeader::long_read_profile();
+        header.set_qual(!args.no_quality);
+        header.set_compressed(!args.uncompressed);
+        header
+    } else {
+        VBinseqHeader::with_capacity(
+            args.block_size,
+            !args.no_quality,
+            !args.uncompressed,
+            args.interleaved,
+        )
+    };
+    if !args.uncompressed {
+        header.set_codec(args.codec.into());
+    }
+    header.set_embedded_index(args.embed_index);
+    header.set_iupac(args.iupac);
+    header.set_columnar(args.columnar);
+    if args.bin_quality {
+        builder = builder.quality_bins(QualityBins::illumina());
+    }
+    for (key, value) in &args.metadata {
+        builder = builder.metadata(key, value);
+    }
+    let writer = builder
+        .header(header)
+        .policy(args.policy.into())
+        .build(output_writer(args.output.as_deref())?)?;
+
+    let (n_records, n_skipped) = if args.threads > 1 {
+        let mut writer = ParallelVBinseqWriter::new(writer, args.threads)?;
+        let n_records = encode_records(&mut reader, args.interleaved, |record| {
+            writer.write_record(record)
+        })?;
+        writer.finish()?;
+        (n_records, writer.skipped().total)
+    } else {
+        let mut writer = writer;
+        let n_records = encode_records(&mut reader, args.interleaved, |record| {
+            writer.write_record(record).map(drop)
+        })?;
+        writer.finish()?;
+        (n_records, writer.skipped().total)
+    };
+    eprintln!(
+        "Encoded {} records ({n_skipped} skipped)",
+        n_records - n_skipped
+    );
+    Ok(())
+}
+
+/// Parses FASTQ records and passes them to a write function
+///
+/// Returns the number of records (or pairs of interleaved records) parsed.
+fn encode_records<R: Read>(
+    reader: &mut fastq::Reader<R>,
+    interleaved: bool,
+    mut write: impl FnMut(&OwnedRecord) -> vbinseq::Result<()>,
+) -> Result<u64> {
+    let mut n_records = 0u64;
+    while let Some(primary) = reader.next() {
+        let mut record = OwnedRecord::from(&primary?);
+        if interleaved {
+            let Some(extended) = reader.next() else {
+                bail!("Interleaved input has an odd number of records");
+            };
+            let extended = extended?;
+            record = OwnedRecord::new_paired(
+                0,
+                record.seq().to_vec(),
+                extended.seq().to_vec(),
+                record.squal().to_vec(),
+                extended.qual().to_vec(),
+            );
+        }
+        ensure!(
+            !record.seq().is_empty(),
+            "Record {n_records} has an empty sequence"
+        );
+        write(&record)?;
+        n_records += 1;
+    }
+    Ok(n_records)
+}
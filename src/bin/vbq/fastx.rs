@@ -0,0 +1,184 @@
+//! FASTQ and FASTA output shared by the subcommands that decode records
+
+use std::io::Write;
+
+use anyhow::Result;
+use clap::ValueEnum;
+use vbinseq::homopolymer::expand;
+use vbinseq::OwnedRecord;
+
+/// Quality score written for records without quality scores
+const DEFAULT_QUALITY: u8 = b'?';
+
+/// Text format of the decoded records
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    Fastq,
+    Fasta,
+}
+
+/// Writes decoded records as FASTQ or FASTA
+///
+/// Records have no names, so the record index is used as the name. The segments of paired
+/// records (and records of more segments) are written as interleaved mates, and sequences without quality scores are given
+/// `DEFAULT_QUALITY` scores in FASTQ output. Sequences of homopolymer-compressed files
+/// are expanded and have no quality scores.
+pub struct FastxWriter {
+    inner: Box<dyn Write>,
+    format: Format,
+
+    /// Whether sequences are homopolymer-compressed and need to be expanded
+    homopolymer: bool,
+
+    ebuf: Vec<u8>,
+    qbuf: Vec<u8>,
+}
+impl FastxWriter {
+    pub fn new(inner: Box<dyn Write>, format: Format, homopolymer: bool) -> Self {
+        Self {
+            inner,
+            format,
+            homopolymer,
+            ebuf: Vec::new(),
+            qbuf: Vec::new(),
+        }
+    }
+
+    /// Writes every segment of a record
+    pub fn write_record(&mut self, record: &OwnedRecord) -> Result<()> {
+        for segment in 0..record.n_segments() {
+            let (sequence, quality) = (record.segment(segment), record.segment_qual(segment));
+            self.write_mate(record.index(), sequence, quality)?;
+        }
+        Ok(())
+    }
+
+    /// Flushes the underlying writer
+    pub fn finish(&mut self) -> Result<()> {
+        self.inner.flush()?;
+        Ok(())
+    }
+
+    /// Writes a sequence of a record
+    ///
+    /// The bytes are the quality scores, or the run lengths of homopolymer-compressed
+    /// sequences.
+    fn write_mate(&mut self, index: u64, sequence: &[u8], bytes: &[u8]) -> Result<()> {
+        if !self.homopolymer {
+            return self.write_sequence(index, sequence, bytes);
+        }
+        let mut expanded = std::mem::take(&mut self.ebuf);
+        expanded.clear();
+        expand(sequence, bytes, &mut expanded)?;
+        let status = self.write_sequence(index, &expanded, &[]);
+        self.ebuf = expanded;
+        status
+    }
+
+    fn write_sequence(&mut self, index: u64, sequence: &[u8], quality: &[u8]) -> Result<()> {
+        match self.format {
+            Format::Fasta => {
+                writeln!(self.inner, ">{index}")?;
+                self.inner.write_all(sequence)?;
+                self.inner.write_all(b"\n")?;
+            }
+            Format::Fastq => {
+                let quality = if quality.is_empty() {
+                    self.qbuf.clear();
+                    self.qbuf.resize(sequence.len(), DEFAULT_QUALITY);
+                    self.qbuf.as_slice()
+                } else {
+                    quality
+                };
+                writeln!(self.inner, "@{index}")?;
+                self.inner.write_all(sequence)?;
+                self.inner.write_all(b"\n+\n")?;
+                self.inner.write_all(quality)?;
+                self.inner.write_all(b"\n")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::raw::tests::write_file;
+    use std::fs::File;
+    use vbinseq::sections::HeaderSections;
+    use vbinseq::{MmapReader, VBinseqHeader};
+
+    /// Writes the records of a file with a `FastxWriter` and returns the output
+    fn fastx(input: &std::path::Path, format: Format) -> Result<String> {
+        let output = input.with_extension("fastx");
+        let mut reader = MmapReader::new(input)?;
+        let homopolymer = reader.header().is_homopolymer();
+        let mut writer = FastxWriter::new(Box::new(File::create(&output)?), format, homopolymer);
+        let mut block = reader.new_block();
+        let mut record = OwnedRecord::default();
+        while reader.read_block_into(&mut block)? {
+            for ref_record in block.iter() {
+                record.fill(&ref_record)?;
+                writer.write_record(&record)?;
+            }
+        }
+        writer.finish()?;
+        drop(writer);
+        Ok(std::fs::read_to_string(&output)?)
+    }
+
+    #[test]
+    fn test_homopolymer() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("vbq_fastx_{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let input = dir.join("homopolymer.vbq");
+        let mut header = VBinseqHeader::with_capacity(1024, false, true, true);
+        header.set_homopolymer(true);
+        write_file(&input, header, HeaderSections::default(), 0, 4)?;
+
+        // Sequences are expanded and written without their run lengths
+        let (mut fastq, mut fasta) = (String::new(), String::new());
+        for index in 0..4 {
+            for mate in 0..2 {
+                let sequence = "ACGGTTAC".repeat(1 + (index + mate) % 4);
+                let quality = "?".repeat(sequence.len());
+                fastq += &format!("@{index}\n{sequence}\n+\n{quality}\n");
+                fasta += &format!(">{index}\n{sequence}\n");
+            }
+        }
+        assert_eq!(fastx(&input, Format::Fastq)?, fastq);
+        assert_eq!(fastx(&input, Format::Fasta)?, fasta);
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_segments() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("vbq_fastx_segments_{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let input = dir.join("segments.vbq");
+        for qual in [false, true] {
+            let mut header = VBinseqHeader::with_capacity(4096, qual, true, true);
+            header.set_segments(4)?;
+            write_file(&input, header, HeaderSections::default(), 0, 3)?;
+
+            // Every segment is written as an interleaved mate
+            let (mut fastq, mut fasta) = (String::new(), String::new());
+            for index in 0..3 {
+                for segment in 0..4 {
+                    let sequence = "ACGGTTAC".repeat(1 + (index + segment) % 4);
+                    let quality = if qual { "F" } else { "?" }.repeat(sequence.len());
+                    fastq += &format!("@{index}\n{sequence}\n+\n{quality}\n");
+                    fasta += &format!(">{index}\n{sequence}\n");
+                }
+            }
+            assert_eq!(fastx(&input, Format::Fastq)?, fastq);
+            assert_eq!(fastx(&input, Format::Fasta)?, fasta);
+        }
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,140 @@
+//! `vbq grep` - selects records by subsequence or flag
+
+use std::path::PathBuf;
+
+use anyhow::{ensure, Context, Result};
+use clap::{ArgGroup, Args};
+use vbinseq::{MmapReader, OwnedRecord};
+
+use crate::sink::{Format, Sink};
+
+#[derive(Args)]
+#[command(group(ArgGroup::new("predicate").required(true).multiple(true).args(["pattern", "flag"])))]
+pub struct GrepArgs {
+    /// Input VBINSEQ file
+    input: PathBuf,
+
+    /// Subsequence to search for in either mate (can be repeated)
+    #[arg(short = 'e', long)]
+    pattern: Vec<String>,
+
+    /// Also search for the reverse complement of the patterns
+    #[arg(short, long)]
+    revcomp: bool,
+
+    /// Flag value required after applying the flag mask
+    #[arg(long)]
+    flag: Option<u64>,
+
+    /// Bits of the flag compared to the flag value [default: all bits]
+    #[arg(long, requires = "flag")]
+    flag_mask: Option<u64>,
+
+    /// Select the records that do not match
+    #[arg(short = 'v', long)]
+    invert: bool,
+
+    /// Output file [default: stdout]
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Output format (VBINSEQ output keeps the header of the input)
+    #[arg(short, long, value_enum, default_value_t = Format::Fastq)]
+    format: Format,
+}
+
+/// Returns the reverse complement of a nucleotide sequence
+fn reverse_complement(sequence: &[u8]) -> Vec<u8> {
+    sequence
+        .iter()
+        .rev()
+        .map(|base| match base {
+            b'A' => b'T',
+            b'C' => b'G',
+            b'G' => b'C',
+            _ => b'A',
+        })
+        .collect()
+}
+
+/// Returns true if `pattern` occurs in `sequence`
+fn contains(sequence: &[u8], pattern: &[u8]) -> bool {
+    sequence
+        .windows(pattern.len())
+        .any(|window| window == pattern)
+}
+
+/// Normalizes the search patterns, adding their reverse complements if requested
+fn build_patterns(args: &GrepArgs) -> Result<Vec<Vec<u8>>> {
+    let mut patterns = Vec::new();
+    for pattern in &args.pattern {
+        let pattern = pattern.to_ascii_uppercase().into_bytes();
+        ensure!(!pattern.is_empty(), "Patterns must not be empty");
+        ensure!(
+            pattern.iter().all(|base| b"ACGT".contains(base)),
+            "Patterns may only contain the nucleotides A, C, G, and T"
+        );
+        if args.revcomp {
+            patterns.push(reverse_complement(&pattern));
+        }
+        patterns.push(pattern);
+    }
+    Ok(patterns)
+}
+
+/// Writes the records of the input that match the predicates
+///
+/// A record matches if its flag matches (when a flag is given) and either mate contains
+/// one of the patterns (when patterns are given). Flags are checked before decoding, so
+/// records failing the flag predicate are only decoded if they are selected by `--invert`.
+pub fn run(args: &GrepArgs) -> Result<()> {
+    let patterns = build_patterns(args)?;
+    let mask = args.flag_mask.unwrap_or(u64::MAX);
+
+    let mut reader = MmapReader::new(&args.input)
+        .with_context(|| format!("Failed to open {}", args.input.display()))?;
+    let mut block = reader.new_block();
+    let mut sink = Sink::new(args.output.as_deref(), args.format, &reader)?;
+
+    let mut record = OwnedRecord::default();
+    let mut n_selected = 0u64;
+    while reader.read_block_into(&mut block)? {
+        for ref_record in block.iter() {
+            let flag_match = args
+                .flag
+                .is_none_or(|flag| ref_record.flag() & mask == flag);
+            let selected = if !flag_match {
+                args.invert
+            } else {
+                record.fill(&ref_record)?;
+                let pattern_match = patterns.is_empty()
+                    || patterns.iter().any(|pattern| {
+                        contains(record.seq(), pattern) || contains(record.xseq(), pattern)
+                    });
+                pattern_match != args.invert
+            };
+            if selected {
+                if !flag_match {
+                    record.fill(&ref_record)?;
+                }
+                sink.write_record(&record)?;
+                n_selected += 1;
+            }
+        }
+    }
+    sink.finish()?;
+    eprintln!("Selected {n_selected} records");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reverse_complement() {
+        assert_eq!(reverse_complement(b"AACGT"), b"ACGTT");
+        assert!(contains(b"TTACGTT", b"ACG"));
+        assert!(!contains(b"AC", b"ACG"));
+    }
+}
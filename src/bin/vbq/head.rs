@@ -0,0 +1,37 @@
+//! `vbq head` - writes the first records of a file
+
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use clap::Args;
+use vbinseq::{BlockIndex, MmapReader};
+
+use crate::sink::{write_selected, Format, Sink};
+
+#[derive(Args)]
+pub struct HeadArgs {
+    /// Input VBINSEQ file
+    input: PathBuf,
+
+    /// Number of records to write
+    #[arg(short = 'n', long, default_value_t = 10)]
+    count: u64,
+
+    /// Output file [default: stdout]
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Output format (VBINSEQ output keeps the header of the input)
+    #[arg(short, long, value_enum, default_value_t = Format::Fastq)]
+    format: Format,
+}
+
+/// Writes the first records of the input, reading only the blocks that hold them
+pub fn run(args: &HeadArgs) -> Result<()> {
+    let mut reader = MmapReader::new(&args.input)
+        .with_context(|| format!("Failed to open {}", args.input.display()))?;
+    let index = BlockIndex::from_vbq(&args.input)?;
+    let mut sink = Sink::new(args.output.as_deref(), args.format, &reader)?;
+    write_selected(&mut reader, &index, 0..args.count, &mut sink)?;
+    sink.finish()
+}
@@ -0,0 +1,63 @@
+//! `vbq index` - builds the block index of a file
+
+use std::io::Write;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use clap::Args;
+use vbinseq::BlockIndex;
+
+use crate::output_writer;
+use crate::raw::index_path;
+
+#[derive(Args)]
+pub struct IndexArgs {
+    /// Input VBINSEQ file
+    input: PathBuf,
+
+    /// Output index file [default: <INPUT>.vqi]
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Print the block ranges to stdout as TSV instead of writing the index
+    #[arg(short, long)]
+    print: bool,
+
+    /// Also store the offset of every record in its block, for single-record seeks
+    #[arg(short, long)]
+    records: bool,
+}
+
+/// Builds the block index of the input and saves it next to the file
+pub fn run(args: &IndexArgs) -> Result<()> {
+    let mut index = BlockIndex::from_vbq(&args.input)
+        .with_context(|| format!("Failed to index {}", args.input.display()))?;
+    if args.records {
+        let bytes = std::fs::read(&args.input)
+            .with_context(|| format!("Failed to read {}", args.input.display()))?;
+        index
+            .add_record_offsets(&bytes)
+            .with_context(|| format!("Failed to index records of {}", args.input.display()))?;
+    }
+    if args.print {
+        let mut writer = output_writer(None)?;
+        writeln!(writer, "offset\tlen\trecords\tcumulative_records")?;
+        for range in index.ranges() {
+            writeln!(
+                writer,
+                "{}\t{}\t{}\t{}",
+                range.start_offset, range.len, range.block_records, range.cumulative_records
+            )?;
+        }
+        writer.flush()?;
+        return Ok(());
+    }
+
+    let path = args
+        .output
+        .clone()
+        .unwrap_or_else(|| index_path(&args.input));
+    index.save_to_path(&path)?;
+    eprintln!("Indexed {} blocks to {}", index.n_blocks(), path.display());
+    Ok(())
+}
@@ -0,0 +1,134 @@
+//! # vbq
+//!
+//! Command line utilities for inspecting and converting VBINSEQ files.
+//!
+//! The binary requires the `cli` feature:
+//!
+//! ```bash
+//! cargo install vbinseq --features cli
+//! vbq stats reads.vbq
+//! vbq cat reads.vbq --format fasta > reads.fasta
+//! vbq index reads.vbq
+//! vbq grep reads.vbq -e ACGTACGT --revcomp > hits.fastq
+//! vbq head reads.vbq -n 100
+//! vbq sample reads.vbq --fraction 0.01 --seed 7 > subset.fastq
+//! vbq split reads.vbq --n-shards 4
+//! vbq repack filtered.vbq -o packed.vbq
+//! vbq simulate -o simulated.vbq -n 100000 --paired --seed 7
+//! zcat reads.fastq.gz | vbq encode | vbq decode | head
+//! vbq merge reads.0.vbq reads.1.vbq reads.2.vbq reads.3.vbq -o merged.vbq
+//! ```
+
+mod cat;
+mod decode;
+mod digest;
+mod encode;
+mod fastx;
+mod grep;
+mod head;
+mod index;
+mod merge;
+mod raw;
+mod repack;
+mod sample;
+mod simulate;
+mod sink;
+mod split;
+mod stats;
+
+use std::fs::File;
+use std::io::{self, stdout, BufWriter, Write};
+use std::path::Path;
+
+use anyhow::Result;
+use clap::{Parser, Subcommand};
+
+#[derive(Parser)]
+#[command(name = "vbq", version, about = "Inspect and convert VBINSEQ files")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Decode the records of a file to FASTQ or FASTA
+    Cat(cat::CatArgs),
+
+    /// Print a summary of the header and blocks of a file
+    Stats(stats::StatsArgs),
+
+    /// Build the block index (.vqi) of a file
+    Index(index::IndexArgs),
+
+    /// Split a file into shards at block boundaries without re-encoding
+    Split(split::SplitArgs),
+
+    /// Concatenate files without re-encoding and rebuild the index
+    Merge(merge::MergeArgs),
+
+    /// Pack the records of a file into full blocks, optionally of another size
+    Repack(repack::RepackArgs),
+
+    /// Select records containing a subsequence or matching a flag
+    Grep(grep::GrepArgs),
+
+    /// Write the first records of a file
+    Head(head::HeadArgs),
+
+    /// Write a seeded random sample of the records of a file
+    Sample(sample::SampleArgs),
+
+    /// Write a file of synthetic records for benchmarks and fixtures
+    Simulate(simulate::SimulateArgs),
+
+    /// Convert FASTQ to VBINSEQ (stdin to stdout by default)
+    Encode(encode::EncodeArgs),
+
+    /// Convert VBINSEQ to FASTQ or FASTA (stdin to stdout by default)
+    Decode(decode::DecodeArgs),
+
+    /// Print content digests of the decoded records of a VBINSEQ or FASTQ file
+    Digest(digest::DigestArgs),
+}
+
+/// Opens a buffered output, writing to stdout if no path is given
+pub(crate) fn output_writer(path: Option<&Path>) -> Result<Box<dyn Write>> {
+    Ok(match path {
+        Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+        None => Box::new(BufWriter::new(stdout().lock())),
+    })
+}
+
+/// Returns true if the error was caused by the reader of stdout closing the pipe
+fn is_broken_pipe(error: &anyhow::Error) -> bool {
+    error.chain().any(|cause| {
+        cause
+            .downcast_ref::<io::Error>()
+            .is_some_and(|e| e.kind() == io::ErrorKind::BrokenPipe)
+    })
+}
+
+fn main() -> Result<()> {
+    let result = match Cli::parse().command {
+        Command::Cat(args) => cat::run(&args),
+        Command::Stats(args) => stats::run(&args),
+        Command::Index(args) => index::run(&args),
+        Command::Split(args) => split::run(&args),
+        Command::Merge(args) => merge::run(&args),
+        Command::Repack(args) => repack::run(&args),
+        Command::Grep(args) => grep::run(&args),
+        Command::Head(args) => head::run(&args),
+        Command::Sample(args) => sample::run(&args),
+        Command::Simulate(args) => simulate::run(&args),
+        Command::Encode(args) => encode::run(&args),
+        Command::Decode(args) => decode::run(&args),
+        Command::Digest(args) => digest::run(&args),
+    };
+
+    // Piping into `head` and friends closes stdout early, which is not an error
+    match result {
+        Err(error) if is_broken_pipe(&error) => Ok(()),
+        result => result,
+    }
+}
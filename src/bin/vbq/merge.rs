@@ -0,0 +1,123 @@
+//! `vbq merge` - concatenates files without re-encoding
+
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use clap::Args;
+use vbinseq::MmapReader;
+
+use crate::raw::{ensure_compatible, RawWriter};
+
+#[derive(Args)]
+pub struct MergeArgs {
+    /// Input VBINSEQ files, merged in the given order
+    #[arg(required = true)]
+    inputs: Vec<PathBuf>,
+
+    /// Output VBINSEQ file
+    #[arg(short, long)]
+    output: PathBuf,
+}
+
+/// Concatenates the blocks of the inputs into a single file and rebuilds its index
+///
+/// The output uses the header and sections of the first input. All inputs must share
+/// the layout of its records (see `ensure_compatible`).
+pub fn run(args: &MergeArgs) -> Result<()> {
+    let mut sources = Vec::with_capacity(args.inputs.len());
+    for path in &args.inputs {
+        let source =
+            MmapReader::new(path).with_context(|| format!("Failed to open {}", path.display()))?;
+        if let Some(first) = sources.first() {
+            ensure_compatible(first, &source, path)?;
+        }
+        sources.push(source);
+    }
+    let Some(first) = sources.first() else {
+        unreachable!("clap requires at least one input");
+    };
+
+    let mut writer = RawWriter::create(&args.output, first)
+        .with_context(|| format!("Failed to create {}", args.output.display()))?;
+    for source in &mut sources {
+        writer.copy_all(source)?;
+    }
+    let n_records = writer.n_records();
+    let index = writer.finish()?;
+    eprintln!(
+        "Merged {} files into {} ({} blocks, {} records)",
+        args.inputs.len(),
+        args.output.display(),
+        index.n_blocks(),
+        n_records
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::raw::tests::{read_records, sections, write_file};
+    use vbinseq::footer::verify;
+    use vbinseq::sections::HeaderSections;
+    use vbinseq::VBinseqHeader;
+
+    #[test]
+    fn test_merge() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("vbq_merge_{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+
+        // Sections and embedded indexes are kept
+        let mut header = VBinseqHeader::with_capacity(1024, true, true, true);
+        header.set_footer(true);
+        header.set_embedded_index(true);
+        let inputs = vec![dir.join("a.vbq"), dir.join("b.vbq")];
+        write_file(&inputs[0], header, sections(), 0, 200)?;
+        write_file(&inputs[1], header, sections(), 200, 100)?;
+        let output = dir.join("merged.vbq");
+        let args = MergeArgs {
+            inputs: inputs.clone(),
+            output: output.clone(),
+        };
+        run(&args)?;
+
+        assert!(verify(&output)?);
+        let reader = MmapReader::new(&output)?;
+        assert_eq!(reader.header(), MmapReader::new(&inputs[0])?.header());
+        assert_eq!(reader.sections()?, sections());
+        let mut expected = read_records(&inputs[0])?;
+        expected.extend(read_records(&inputs[1])?);
+        let records = read_records(&output)?;
+        assert_eq!(records.len(), 300);
+        for (record, expected) in records.iter().zip(&expected) {
+            assert_eq!(
+                (record.flag(), record.seq()),
+                (expected.flag(), expected.seq())
+            );
+            assert_eq!(record.squal(), expected.squal());
+        }
+
+        // Inputs with another record layout are rejected
+        let mut iupac = header;
+        iupac.set_iupac(true);
+        write_file(&inputs[1], iupac, sections(), 200, 100)?;
+        assert!(run(&args).is_err());
+        let bins_only = HeaderSections {
+            quality_bins: sections().quality_bins,
+            ..HeaderSections::default()
+        };
+        write_file(&inputs[1], header, bins_only, 200, 100)?;
+        assert!(run(&args).is_ok());
+        write_file(&inputs[1], header, HeaderSections::default(), 200, 100)?;
+        assert!(run(&args).is_err());
+        let dictionaries = HeaderSections {
+            dictionaries: vec![b"ACGGTTAC".repeat(64)],
+            ..sections()
+        };
+        write_file(&inputs[1], header, dictionaries, 200, 100)?;
+        assert!(run(&args).is_err());
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
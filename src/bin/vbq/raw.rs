@@ -0,0 +1,230 @@
+//! Raw block copying shared by the `split` and `merge` subcommands
+//!
+//! Blocks are copied verbatim (still compressed) from their source files with
+//! `VBinseqWriter::write_raw_block`, so no record is decoded or re-encoded. Outputs keep
+//! the header sections of their input, and files whose header announces a footer (and an
+//! embedded index) get fresh ones, which only requires decompressing the copied blocks.
+
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, ensure, Result};
+use vbinseq::header::{FLAG_EMBEDDED_INDEX, FLAG_FOOTER};
+use vbinseq::{BlockIndex, MmapReader, VBinseqWriter, VBinseqWriterBuilder};
+
+/// Extension flags that are rebuilt for the output rather than copied with the blocks
+const REBUILT_FLAGS: u32 = FLAG_FOOTER | FLAG_EMBEDDED_INDEX;
+
+/// Returns the index file path of a VBINSEQ file (`<path>.vqi`)
+pub fn index_path(path: &Path) -> PathBuf {
+    let mut index_path = path.as_os_str().to_owned();
+    index_path.push(".vqi");
+    index_path.into()
+}
+
+/// Checks that the blocks of two files can be stored in the same file
+///
+/// Blocks are interpreted with the header and sections of the file holding them, so the
+/// block size, quality, segment count, codec, quality transform, and all extension flags
+/// but the footer and embedded index have to match, along with the compression
+/// dictionaries and quality bins of the files.
+pub fn ensure_compatible(first: &MmapReader, other: &MmapReader, path: &Path) -> Result<()> {
+    let (first_header, other_header) = (first.header(), other.header());
+    ensure!(
+        first_header.block() == other_header.block()
+            && first_header.qual() == other_header.qual()
+            && first_header.segments() == other_header.segments()
+            && first_header.codec() == other_header.codec()
+            && first_header.quality_transform() == other_header.quality_transform()
+            && first_header.flags() & !REBUILT_FLAGS == other_header.flags() & !REBUILT_FLAGS,
+        "{} is incompatible with the first input (block size, quality, segments, compression, \
+         quality transform, or extension flags differ)",
+        path.display()
+    );
+    let (first_sections, other_sections) = (first.sections()?, other.sections()?);
+    ensure!(
+        first_sections.dictionaries == other_sections.dictionaries
+            && first_sections.quality_bins == other_sections.quality_bins,
+        "{} is incompatible with the first input (compression dictionaries or quality bins \
+         differ)",
+        path.display()
+    );
+    Ok(())
+}
+
+/// Writes a VBINSEQ file from raw blocks of other files
+pub struct RawWriter {
+    path: PathBuf,
+    writer: VBinseqWriter<BufWriter<File>>,
+    n_records: u64,
+}
+impl RawWriter {
+    /// Creates the output file and writes the header and sections of a source file
+    pub fn create(path: &Path, source: &MmapReader) -> Result<Self> {
+        let writer = VBinseqWriterBuilder::default()
+            .header(source.header())
+            .sections(source.sections()?)
+            .build(BufWriter::new(File::create(path)?))?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            writer,
+            n_records: 0,
+        })
+    }
+
+    /// Copies the next `n_blocks` blocks of a source file
+    pub fn copy_blocks(&mut self, source: &mut MmapReader, n_blocks: usize) -> Result<()> {
+        for _ in 0..n_blocks {
+            let Some(block) = source.next_raw_block()? else {
+                bail!("Unexpected end of file while copying blocks");
+            };
+            self.n_records += block.header.records as u64;
+            self.writer.write_raw_block(&block)?;
+        }
+        Ok(())
+    }
+
+    /// Copies all remaining blocks of a source file
+    pub fn copy_all(&mut self, source: &mut MmapReader) -> Result<()> {
+        while let Some(block) = source.next_raw_block()? {
+            self.n_records += block.header.records as u64;
+            self.writer.write_raw_block(&block)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the number of records copied so far
+    pub fn n_records(&self) -> u64 {
+        self.n_records
+    }
+
+    /// Completes the file, writing its footer if needed, and rebuilds its index
+    pub fn finish(mut self) -> Result<BlockIndex> {
+        self.writer.finish()?;
+        drop(self.writer);
+
+        let index = BlockIndex::from_vbq(&self.path)?;
+        index.save_to_path(index_path(&self.path))?;
+        Ok(index)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use vbinseq::read_group::ReadGroup;
+    use vbinseq::sections::HeaderSections;
+    use vbinseq::{OwnedRecord, QualityBins, VBinseqHeader};
+
+    /// Returns the sections of the test files: read groups, metadata, and quality bins
+    pub(crate) fn sections() -> HeaderSections {
+        HeaderSections {
+            read_groups: vec![ReadGroup::new("lane1").with_tag("SM", "sample1")],
+            metadata: vec![("sample".to_string(), "sample1".to_string())],
+            quality_bins: Some(QualityBins::illumina()),
+            ..HeaderSections::default()
+        }
+    }
+
+    /// Writes `n_records` records matching the layout of `header`, flagged from `first` on
+    pub(crate) fn write_file(
+        path: &Path,
+        header: VBinseqHeader,
+        sections: HeaderSections,
+        first: u64,
+        n_records: u64,
+    ) -> Result<()> {
+        let mut writer = VBinseqWriterBuilder::default()
+            .header(header)
+            .sections(sections)
+            .build(BufWriter::new(File::create(path)?))?;
+        for flag in first..first + n_records {
+            let sequences: Vec<_> = (0..header.segments())
+                .map(|i| b"ACGGTTAC".repeat(1 + (flag as usize + i) % 4))
+                .collect();
+            let qualities = if header.qual() {
+                sequences.iter().map(|seq| vec![b'F'; seq.len()]).collect()
+            } else {
+                Vec::new()
+            };
+            writer.write_record(&OwnedRecord::new_segments(flag, sequences, qualities))?;
+        }
+        writer.finish()?;
+        Ok(())
+    }
+
+    /// Reads the records of a file
+    pub(crate) fn read_records(path: &Path) -> Result<Vec<OwnedRecord>> {
+        let mut reader = MmapReader::new(path)?;
+        let mut block = reader.new_block();
+        let mut records = Vec::new();
+        while reader.read_block_into(&mut block)? {
+            for record in block.iter() {
+                records.push(OwnedRecord::try_from(&record)?);
+            }
+        }
+        Ok(records)
+    }
+
+    #[test]
+    fn test_ensure_compatible() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("vbq_compatible_{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let mut base = VBinseqHeader::with_capacity(1024, false, true, true);
+        base.set_footer(true);
+        let first = dir.join("first.vbq");
+        write_file(&first, base, HeaderSections::default(), 0, 10)?;
+        let first = MmapReader::new(&first)?;
+
+        // Footers and embedded indexes are rebuilt for the output
+        let mut indexed = base;
+        indexed.set_embedded_index(true);
+        let mut plain = base;
+        plain.set_footer(false);
+
+        // Everything describing the stored records has to match
+        let mut homopolymer = base;
+        homopolymer.set_homopolymer(true);
+        let mut iupac = base;
+        iupac.set_iupac(true);
+        let mut columnar = base;
+        columnar.set_columnar(true);
+        let mut segments = base;
+        segments.set_segments(3)?;
+        let mut aux = base;
+        aux.set_aux(true);
+        let mut block_checksum = base;
+        block_checksum.set_block_checksum(true);
+        let dictionaries = HeaderSections {
+            dictionaries: vec![b"ACGGTTAC".repeat(64)],
+            ..HeaderSections::default()
+        };
+
+        let cases = [
+            (indexed, HeaderSections::default(), true),
+            (plain, HeaderSections::default(), true),
+            (base, HeaderSections::default(), true),
+            (homopolymer, HeaderSections::default(), false),
+            (iupac, HeaderSections::default(), false),
+            (columnar, HeaderSections::default(), false),
+            (segments, HeaderSections::default(), false),
+            (aux, HeaderSections::default(), false),
+            (block_checksum, HeaderSections::default(), false),
+            (base, dictionaries, false),
+        ];
+        for (i, (header, sections, compatible)) in cases.into_iter().enumerate() {
+            let path = dir.join(format!("other{i}.vbq"));
+            write_file(&path, header, sections, 0, 10)?;
+            let other = MmapReader::new(&path)?;
+            assert_eq!(
+                ensure_compatible(&first, &other, &path).is_ok(),
+                compatible,
+                "{header}"
+            );
+        }
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,32 @@
+//! `vbq repack` - packs the records of a file into full blocks
+
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use clap::Args;
+use vbinseq::repack::{repack, RepackOptions};
+
+#[derive(Args)]
+pub struct RepackArgs {
+    /// Input VBINSEQ file
+    input: PathBuf,
+
+    /// Output VBINSEQ file
+    #[arg(short, long)]
+    output: PathBuf,
+
+    /// Block size of the output in bytes [default: block size of the input]
+    #[arg(short, long)]
+    block_size: Option<u64>,
+}
+
+/// Rewrites the input with full blocks and prints the fill statistics before and after
+pub fn run(args: &RepackArgs) -> Result<()> {
+    let options = RepackOptions {
+        block_size: args.block_size,
+    };
+    let report = repack(&args.input, &args.output, &options)
+        .with_context(|| format!("Failed to repack {}", args.input.display()))?;
+    println!("Before:\n{}\n\nAfter:\n{}", report.before, report.after);
+    Ok(())
+}
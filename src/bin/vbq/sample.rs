@@ -0,0 +1,157 @@
+//! `vbq sample` - writes a random subset of the records of a file
+
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+
+use anyhow::{ensure, Context, Result};
+use clap::{ArgGroup, Args};
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
+use vbinseq::{BlockIndex, MmapReader};
+
+use crate::sink::{write_selected, Format, Sink};
+
+#[derive(Args)]
+#[command(group(ArgGroup::new("size").required(true).args(["count", "fraction"])))]
+pub struct SampleArgs {
+    /// Input VBINSEQ file
+    input: PathBuf,
+
+    /// Number of records to sample
+    #[arg(short = 'n', long)]
+    count: Option<u64>,
+
+    /// Probability of sampling each record
+    #[arg(short = 'p', long)]
+    fraction: Option<f64>,
+
+    /// Seed of the random number generator
+    #[arg(short, long, default_value_t = 42)]
+    seed: u64,
+
+    /// Output file [default: stdout]
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Output format (VBINSEQ output keeps the header of the input)
+    #[arg(short, long, value_enum, default_value_t = Format::Fastq)]
+    format: Format,
+}
+
+/// Chooses `count` distinct indices below `n_records` uniformly (Floyd's algorithm)
+fn sample_count(rng: &mut SmallRng, count: u64, n_records: u64) -> BTreeSet<u64> {
+    let count = count.min(n_records);
+    let mut chosen = BTreeSet::new();
+    for upper in (n_records - count)..n_records {
+        let candidate = rng.gen_range(0..=upper);
+        if !chosen.insert(candidate) {
+            chosen.insert(upper);
+        }
+    }
+    chosen
+}
+
+/// Indices below `n_records` each chosen independently with a fixed probability
+///
+/// The gaps between chosen indices are drawn from a geometric distribution, so the
+/// cost is proportional to the number of chosen indices rather than to `n_records`.
+struct Bernoulli {
+    rng: SmallRng,
+    log_q: f64,
+    next: u64,
+    n_records: u64,
+}
+impl Bernoulli {
+    fn new(rng: SmallRng, fraction: f64, n_records: u64) -> Self {
+        Self {
+            rng,
+            log_q: (1.0 - fraction).ln(),
+            next: 0,
+            // Nothing is ever chosen with a fraction of 0
+            n_records: if fraction > 0.0 { n_records } else { 0 },
+        }
+    }
+}
+impl Iterator for Bernoulli {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<u64> {
+        // 1 - u lies in (0, 1], so the logarithm is finite
+        let u: f64 = self.rng.gen();
+        let gap = ((1.0 - u).ln() / self.log_q).floor();
+        let index = self.next.checked_add(gap as u64)?;
+        if index >= self.n_records {
+            self.next = self.n_records;
+            return None;
+        }
+        self.next = index + 1;
+        Some(index)
+    }
+}
+
+/// Writes a seeded random sample of the records of the input, in file order
+///
+/// The number of records is taken from the index, and only the blocks holding sampled
+/// records are decoded.
+pub fn run(args: &SampleArgs) -> Result<()> {
+    if let Some(fraction) = args.fraction {
+        ensure!(
+            (0.0..=1.0).contains(&fraction),
+            "The fraction must be between 0 and 1"
+        );
+    }
+
+    let mut reader = MmapReader::new(&args.input)
+        .with_context(|| format!("Failed to open {}", args.input.display()))?;
+    let index = BlockIndex::from_vbq(&args.input)?;
+    let n_records: u64 = index
+        .ranges()
+        .iter()
+        .map(|range| range.block_records as u64)
+        .sum();
+    let mut sink = Sink::new(args.output.as_deref(), args.format, &reader)?;
+
+    let mut rng = SmallRng::seed_from_u64(args.seed);
+    let n_written = match (args.count, args.fraction) {
+        (Some(count), _) => {
+            let selected = sample_count(&mut rng, count, n_records);
+            write_selected(&mut reader, &index, selected.into_iter(), &mut sink)?
+        }
+        (None, Some(fraction)) => {
+            let selected = Bernoulli::new(rng, fraction, n_records);
+            write_selected(&mut reader, &index, selected, &mut sink)?
+        }
+        (None, None) => unreachable!("clap requires a count or a fraction"),
+    };
+    sink.finish()?;
+    eprintln!("Sampled {n_written} of {n_records} records");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sample_count() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let chosen = sample_count(&mut rng, 10, 100);
+        assert_eq!(chosen.len(), 10);
+        assert!(chosen.iter().all(|&index| index < 100));
+        assert_eq!(sample_count(&mut rng, 10, 5).len(), 5);
+    }
+
+    #[test]
+    fn test_sample_fraction() {
+        let all: Vec<u64> = Bernoulli::new(SmallRng::seed_from_u64(0), 1.0, 50).collect();
+        assert_eq!(all, (0..50).collect::<Vec<_>>());
+        assert_eq!(
+            Bernoulli::new(SmallRng::seed_from_u64(0), 0.0, 50).count(),
+            0
+        );
+
+        let some: Vec<u64> = Bernoulli::new(SmallRng::seed_from_u64(0), 0.1, 10_000).collect();
+        assert!(some.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!((500..1500).contains(&some.len()));
+    }
+}
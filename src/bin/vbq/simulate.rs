@@ -0,0 +1,71 @@
+//! `vbq simulate` - writes a file of synthetic records
+
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use clap::Args;
+use vbinseq::simulate::{simulate, LengthDistribution, SimulationOptions};
+
+#[derive(Args)]
+pub struct SimulateArgs {
+    /// Output VBINSEQ file
+    #[arg(short, long)]
+    output: PathBuf,
+
+    /// Number of records to simulate
+    #[arg(short = 'n', long, default_value_t = 10_000)]
+    count: u64,
+
+    /// Read length (the shortest length with --max-length)
+    #[arg(short, long, default_value_t = 150)]
+    length: usize,
+
+    /// Longest read length, drawing lengths uniformly from --length to this length
+    #[arg(long)]
+    max_length: Option<usize>,
+
+    /// Simulate paired records
+    #[arg(long)]
+    paired: bool,
+
+    /// Probability of a nucleotide being substituted
+    #[arg(short, long, default_value_t = 0.001)]
+    error_rate: f64,
+
+    /// Probability of a nucleotide being called as N
+    #[arg(long, default_value_t = 0.0001)]
+    n_rate: f64,
+
+    /// Write records without quality scores
+    #[arg(long)]
+    no_quality: bool,
+
+    /// Seed of the random number generator
+    #[arg(short, long, default_value_t = 42)]
+    seed: u64,
+}
+
+/// Writes the simulated records and prints their number
+pub fn run(args: &SimulateArgs) -> Result<()> {
+    let defaults = SimulationOptions::default();
+    let options = SimulationOptions {
+        n_records: args.count,
+        length: match args.max_length {
+            Some(max) => LengthDistribution::Uniform {
+                min: args.length,
+                max,
+            },
+            None => LengthDistribution::Fixed(args.length),
+        },
+        paired: args.paired,
+        error_rate: args.error_rate,
+        n_rate: args.n_rate,
+        quality: defaults.quality.filter(|_| !args.no_quality),
+        seed: args.seed,
+        ..defaults
+    };
+    let n_written = simulate(&args.output, &options)
+        .with_context(|| format!("Failed to simulate {}", args.output.display()))?;
+    println!("Simulated {n_written} records");
+    Ok(())
+}
@@ -0,0 +1,133 @@
+//! Output of selected records shared by the filtering subcommands
+
+use std::io::Write;
+use std::path::Path;
+
+use anyhow::Result;
+use clap::ValueEnum;
+use vbinseq::{BlockIndex, MmapReader, OwnedRecord, VBinseqWriter, VBinseqWriterBuilder};
+
+use crate::fastx::{self, FastxWriter};
+use crate::output_writer;
+
+/// Format of the selected records
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    Fastq,
+    Fasta,
+    Vbq,
+}
+
+/// Destination of the selected records
+pub enum Sink {
+    Fastx(FastxWriter),
+    Vbq(Box<VBinseqWriter<Box<dyn Write>>>),
+}
+impl Sink {
+    /// Opens the output, writing to stdout if no path is given
+    ///
+    /// VBINSEQ output keeps the header and sections of the input.
+    pub fn new(path: Option<&Path>, format: Format, input: &MmapReader) -> Result<Self> {
+        let output = output_writer(path)?;
+        let homopolymer = input.header().is_homopolymer();
+        Ok(match format {
+            Format::Fastq => {
+                Self::Fastx(FastxWriter::new(output, fastx::Format::Fastq, homopolymer))
+            }
+            Format::Fasta => {
+                Self::Fastx(FastxWriter::new(output, fastx::Format::Fasta, homopolymer))
+            }
+            Format::Vbq => Self::Vbq(Box::new(
+                VBinseqWriterBuilder::default()
+                    .header(input.header())
+                    .sections(input.sections()?)
+                    .build(output)?,
+            )),
+        })
+    }
+
+    pub fn write_record(&mut self, record: &OwnedRecord) -> Result<()> {
+        match self {
+            Self::Fastx(writer) => writer.write_record(record),
+            Self::Vbq(writer) => {
+                writer.write_record(record)?;
+                Ok(())
+            }
+        }
+    }
+
+    pub fn finish(&mut self) -> Result<()> {
+        match self {
+            Self::Fastx(writer) => writer.finish(),
+            Self::Vbq(writer) => Ok(writer.finish()?),
+        }
+    }
+}
+
+/// Writes the records with the given indices, which must be strictly increasing
+///
+/// Blocks without a selected record are skipped using the index, so they are neither
+/// decompressed nor decoded. Returns the number of records written.
+pub fn write_selected(
+    reader: &mut MmapReader,
+    index: &BlockIndex,
+    selected: impl Iterator<Item = u64>,
+    sink: &mut Sink,
+) -> Result<u64> {
+    let mut selected = selected.peekable();
+    let mut block = reader.new_block();
+    let mut record = OwnedRecord::default();
+    let mut n_written = 0;
+    for range in index.ranges() {
+        let Some(&next) = selected.peek() else {
+            break;
+        };
+        let end = range.cumulative_records + range.block_records as u64;
+        if next >= end {
+            continue;
+        }
+
+        reader.read_block_at(range, &mut block)?;
+        for ref_record in block.iter() {
+            if selected.next_if_eq(&ref_record.index()).is_some() {
+                record.fill(&ref_record)?;
+                sink.write_record(&record)?;
+                n_written += 1;
+            }
+        }
+    }
+    Ok(n_written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::raw::tests::{read_records, sections, write_file};
+    use vbinseq::VBinseqHeader;
+
+    #[test]
+    fn test_vbq_sections() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("vbq_sink_{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let (input, output) = (dir.join("reads.vbq"), dir.join("selected.vbq"));
+        let header = VBinseqHeader::with_capacity(1024, true, true, false);
+        write_file(&input, header, sections(), 0, 100)?;
+
+        // VBINSEQ output keeps the sections of the input
+        let mut reader = MmapReader::new(&input)?;
+        let index = BlockIndex::from_vbq(&input)?;
+        let mut sink = Sink::new(Some(&output), Format::Vbq, &reader)?;
+        let selected = (0..100).step_by(7);
+        assert_eq!(
+            write_selected(&mut reader, &index, selected, &mut sink)?,
+            15
+        );
+        sink.finish()?;
+        drop(sink);
+        assert_eq!(MmapReader::new(&output)?.sections()?, sections());
+        assert_eq!(read_records(&output)?[1].flag(), 7);
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,141 @@
+//! `vbq split` - splits a file into shards at block boundaries
+
+use std::path::PathBuf;
+
+use anyhow::{ensure, Context, Result};
+use clap::Args;
+use vbinseq::{BlockIndex, MmapReader};
+
+use crate::raw::RawWriter;
+
+#[derive(Args)]
+pub struct SplitArgs {
+    /// Input VBINSEQ file
+    input: PathBuf,
+
+    /// Number of shards to write
+    #[arg(short, long)]
+    n_shards: usize,
+
+    /// Prefix of the shard files [default: <INPUT> without its extension]
+    #[arg(short, long)]
+    prefix: Option<PathBuf>,
+}
+
+/// Returns the shard receiving a block, balancing the number of records per shard
+///
+/// `records_before` is the number of records in all previous blocks.
+fn shard_of(records_before: u64, n_records: u64, n_shards: usize) -> usize {
+    if n_records == 0 {
+        return 0;
+    }
+    let shard = records_before as u128 * n_shards as u128 / n_records as u128;
+    (shard as usize).min(n_shards - 1)
+}
+
+/// Splits the input into `n_shards` files of consecutive blocks
+///
+/// Blocks are copied verbatim, so shards are written at I/O speed. Shards hold roughly the
+/// same number of records, but a shard is empty if the input has fewer blocks than shards.
+/// Each shard is written as `<PREFIX>.<N>.vbq` along with its index.
+pub fn run(args: &SplitArgs) -> Result<()> {
+    ensure!(args.n_shards > 0, "The number of shards must be positive");
+
+    let mut source = MmapReader::new(&args.input)
+        .with_context(|| format!("Failed to open {}", args.input.display()))?;
+    let index = BlockIndex::from_vbq(&args.input)?;
+    let ranges = index.ranges();
+    let n_records: u64 = ranges.iter().map(|range| range.block_records as u64).sum();
+
+    let prefix = args
+        .prefix
+        .clone()
+        .unwrap_or_else(|| args.input.with_extension(""));
+    let width = (args.n_shards - 1).to_string().len();
+
+    let mut start = 0;
+    let mut records_before = 0;
+    for shard in 0..args.n_shards {
+        // Collect the consecutive blocks assigned to this shard
+        let mut end = start;
+        while end < ranges.len() && shard_of(records_before, n_records, args.n_shards) == shard {
+            records_before += ranges[end].block_records as u64;
+            end += 1;
+        }
+
+        let mut path = prefix.as_os_str().to_owned();
+        path.push(format!(".{shard:0width$}.vbq"));
+        let path = PathBuf::from(path);
+
+        let mut writer = RawWriter::create(&path, &source)
+            .with_context(|| format!("Failed to create {}", path.display()))?;
+        writer.copy_blocks(&mut source, end - start)?;
+        let shard_records = writer.n_records();
+        writer.finish()?;
+        eprintln!(
+            "Wrote {} blocks ({} records) to {}",
+            end - start,
+            shard_records,
+            path.display()
+        );
+        start = end;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::raw::tests::{read_records, sections, write_file};
+    use vbinseq::footer::verify;
+    use vbinseq::VBinseqHeader;
+
+    #[test]
+    fn test_shard_assignment() {
+        // 4 blocks of 10 records into 2 shards
+        let shards: Vec<usize> = (0..4).map(|i| shard_of(i * 10, 40, 2)).collect();
+        assert_eq!(shards, vec![0, 0, 1, 1]);
+
+        // More shards than blocks leaves some shards empty
+        let shards: Vec<usize> = (0..2).map(|i| shard_of(i * 10, 20, 4)).collect();
+        assert_eq!(shards, vec![0, 2]);
+
+        assert_eq!(shard_of(0, 0, 3), 0);
+    }
+
+    #[test]
+    fn test_split() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("vbq_split_cli_{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+
+        // Shards keep the sections of the input and get their own embedded index
+        let mut header = VBinseqHeader::with_capacity(1024, true, true, true);
+        header.set_footer(true);
+        header.set_embedded_index(true);
+        let input = dir.join("reads.vbq");
+        write_file(&input, header, sections(), 0, 500)?;
+        let args = SplitArgs {
+            input: input.clone(),
+            n_shards: 3,
+            prefix: None,
+        };
+        run(&args)?;
+
+        let source_header = MmapReader::new(&input)?.header();
+        let mut flags = Vec::new();
+        for shard in 0..3 {
+            let path = dir.join(format!("reads.{shard}.vbq"));
+            assert!(verify(&path)?);
+            let reader = MmapReader::new(&path)?;
+            assert_eq!(reader.header(), source_header);
+            assert_eq!(reader.sections()?, sections());
+            let records = read_records(&path)?;
+            assert!(!records.is_empty());
+            flags.extend(records.iter().map(|record| record.flag()));
+        }
+        assert_eq!(flags, (0..500).collect::<Vec<_>>());
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
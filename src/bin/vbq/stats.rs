@@ -0,0 +1,31 @@
+//! `vbq stats` - prints a summary of a file
+
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use clap::Args;
+use vbinseq::summary::FileSummary;
+use vbinseq::MmapReader;
+
+#[derive(Args)]
+pub struct StatsArgs {
+    /// Input VBINSEQ file
+    input: PathBuf,
+}
+
+/// Prints the header, block statistics, quality bins, and metadata of the input
+///
+/// Only the block headers are scanned, so this is cheap even for very large files.
+pub fn run(args: &StatsArgs) -> Result<()> {
+    let summary = FileSummary::from_path(&args.input)
+        .with_context(|| format!("Failed to read {}", args.input.display()))?;
+    println!("{summary}");
+    let sections = MmapReader::new(&args.input)?.sections()?;
+    if let Some(bins) = sections.quality_bins {
+        println!("Quality bins:    {} levels", bins.bins().len());
+    }
+    for (key, value) in sections.metadata {
+        println!("Metadata:        {key}={value}");
+    }
+    Ok(())
+}
@@ -0,0 +1,78 @@
+//! # Per-Record Checksums
+//!
+//! Files with the record checksum extension enabled (see `VBinseqHeader::set_record_crc`)
+//! end every record with a 2-byte CRC-16 of the record's preamble and data. The footer
+//! digest only tells whether a file is intact as a whole and a corrupt block only fails
+//! to decode as a whole, while the record checksums localize a bit flip to the
+//! individual reads it affects.
+//!
+//! The checksum is CRC-16/CCITT-FALSE (polynomial `0x1021`, initial value `0xFFFF`) over
+//! the stored record bytes (i.e. after any quality transform), stored little-endian
+//! after the record. Readers skip the checksums, and `validate::check` reports every
+//! record whose checksum does not match.
+//!
+//! # Example
+//!
+//! ```rust
+//! use vbinseq::checksum::crc16;
+//!
+//! assert_eq!(crc16(b"123456789"), 0x29B1);
+//! ```
+
+/// Size of the checksum stored after every record in bytes
+pub const SIZE_RECORD_CRC: usize = 2;
+
+/// Lookup table of CRC-16/CCITT-FALSE for every byte value
+const TABLE: [u16; 256] = {
+    let mut table = [0u16; 256];
+    let mut byte = 0;
+    while byte < 256 {
+        let mut crc = (byte as u16) << 8;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+            bit += 1;
+        }
+        table[byte] = crc;
+        byte += 1;
+    }
+    table
+};
+
+/// Computes the CRC-16/CCITT-FALSE checksum of a byte slice
+///
+/// # Parameters
+///
+/// * `bytes` - The bytes to checksum
+pub fn crc16(bytes: &[u8]) -> u16 {
+    bytes.iter().fold(0xFFFF, |crc, &byte| {
+        (crc << 8) ^ TABLE[usize::from((crc >> 8) as u8 ^ byte)]
+    })
+}
+
+/// Returns whether a stored record matches the checksum at its end
+///
+/// # Parameters
+///
+/// * `record` - The stored bytes of a record, including its checksum
+pub(crate) fn verify(record: &[u8]) -> bool {
+    match record.len().checked_sub(SIZE_RECORD_CRC) {
+        Some(end) => crc16(&record[..end]).to_le_bytes() == record[end..],
+        None => false,
+    }
+}
+
+/// Writes the checksum of a record into its last bytes
+///
+/// # Parameters
+///
+/// * `record` - The stored bytes of a record, ending with space for its checksum
+pub(crate) fn seal(record: &mut [u8]) {
+    let end = record.len() - SIZE_RECORD_CRC;
+    let crc = crc16(&record[..end]);
+    record[end..].copy_from_slice(&crc.to_le_bytes());
+}
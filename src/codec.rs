@@ -0,0 +1,203 @@
+//! # Block Codecs
+//!
+//! This module compresses and decompresses the data of a block with any of the codecs a
+//! file can select in its header (see `Codec`). Zstd is provided by the `compression`
+//! feature (enabled by default), LZ4 by the `lz4` feature and XZ by the `xz` feature.
+//! Codecs whose feature is not enabled fail with `HeaderError::UnsupportedCodec`.
+//!
+//! LZ4 favors speed over ratio, which suits pipelines where encoding and decoding time
+//! dominate, while XZ compresses slowly but compactly, which suits archival copies. Zstd
+//! sits in between and is the only codec supporting compression dictionaries (see the
+//! `dictionary` module) and multithreaded compression of a block.
+//!
+//! Writers and readers use these functions internally, so they are only needed to handle
+//! raw block data directly (see `RawBlock`).
+//!
+//! # Example
+//!
+//! ```rust
+//! use vbinseq::{codec, Codec};
+//!
+//! let data = b"ACGT".repeat(1024);
+//! let mut compressed = Vec::new();
+//! codec::compress(Codec::Zstd, 3, &data, &mut compressed).unwrap();
+//!
+//! let mut decompressed = vec![0; data.len()];
+//! let size = codec::decompress(Codec::Zstd, &compressed, &mut decompressed).unwrap();
+//! assert_eq!(&decompressed[..size], data.as_slice());
+//! ```
+
+use std::io;
+
+use crate::error::HeaderError;
+use crate::{Codec, Result};
+
+/// Highest XZ preset (compression levels are clamped to the presets)
+pub const MAX_XZ_LEVEL: i32 = 9;
+
+/// Compresses bytes with a codec
+///
+/// # Parameters
+///
+/// * `codec` - The codec to compress with
+/// * `level` - The compression level. This is the zstd level for zstd, and the preset
+///   (clamped to 0-9) for XZ. LZ4 has no levels, so it is ignored.
+/// * `src` - The bytes to compress
+/// * `dst` - Buffer replaced with the compressed bytes
+///
+/// # Errors
+///
+/// * `HeaderError::UnsupportedCodec` - If support for the codec is not compiled in
+/// * IO errors if the codec fails to compress the bytes
+pub fn compress(codec: Codec, level: i32, src: &[u8], dst: &mut Vec<u8>) -> Result<()> {
+    dst.clear();
+    match codec {
+        Codec::Uncompressed => dst.extend_from_slice(src),
+        #[cfg(feature = "compression")]
+        Codec::Zstd => {
+            dst.reserve(zstd::zstd_safe::compress_bound(src.len()));
+            zstd::bulk::Compressor::new(level)?.compress_to_buffer(src, dst)?;
+        }
+        #[cfg(feature = "lz4")]
+        Codec::Lz4 => {
+            dst.resize(lz4_flex::block::get_maximum_output_size(src.len()), 0);
+            let size = lz4_flex::block::compress_into(src, dst).map_err(invalid_data)?;
+            dst.truncate(size);
+        }
+        #[cfg(feature = "xz")]
+        Codec::Xz => {
+            use liblzma::stream::{Action, Check, Status, Stream};
+
+            // Blocks are checked by the format itself, so the stream has no check
+            let preset = level.clamp(0, MAX_XZ_LEVEL) as u32;
+            let mut stream =
+                Stream::new_easy_encoder(preset, Check::None).map_err(io::Error::from)?;
+            dst.reserve(src.len() / 2 + 64);
+            loop {
+                let consumed = stream.total_in() as usize;
+                match stream
+                    .process_vec(&src[consumed..], dst, Action::Finish)
+                    .map_err(io::Error::from)?
+                {
+                    Status::StreamEnd => break,
+                    _ => dst.reserve(dst.capacity().max(64)),
+                }
+            }
+        }
+        #[allow(unreachable_patterns)]
+        codec => {
+            let _ = level;
+            return Err(HeaderError::UnsupportedCodec(codec).into());
+        }
+    }
+    Ok(())
+}
+
+/// Decompresses bytes with a codec into a buffer
+///
+/// # Parameters
+///
+/// * `codec` - The codec the bytes were compressed with
+/// * `src` - The compressed bytes
+/// * `dst` - Buffer receiving the decompressed bytes, which must be large enough to hold
+///   all of them (e.g. the virtual block size)
+///
+/// # Returns
+///
+/// The number of decompressed bytes written to the start of `dst`
+///
+/// # Errors
+///
+/// * `HeaderError::UnsupportedCodec` - If support for the codec is not compiled in
+/// * IO errors if the bytes are corrupt, truncated, or do not fit into `dst`
+pub fn decompress(codec: Codec, src: &[u8], dst: &mut [u8]) -> Result<usize> {
+    match codec {
+        Codec::Uncompressed => {
+            let Some(dst) = dst.get_mut(..src.len()) else {
+                return Err(invalid_data("block exceeds the buffer"));
+            };
+            dst.copy_from_slice(src);
+            Ok(src.len())
+        }
+        #[cfg(feature = "compression")]
+        Codec::Zstd => Ok(zstd::bulk::Decompressor::new()?.decompress_to_buffer(src, dst)?),
+        #[cfg(feature = "lz4")]
+        Codec::Lz4 => lz4_flex::block::decompress_into(src, dst).map_err(invalid_data),
+        #[cfg(feature = "xz")]
+        Codec::Xz => {
+            use liblzma::stream::{Action, Status, Stream};
+
+            let mut stream = Stream::new_stream_decoder(u64::MAX, 0).map_err(io::Error::from)?;
+            match stream
+                .process(src, dst, Action::Finish)
+                .map_err(io::Error::from)?
+            {
+                Status::StreamEnd => Ok(stream.total_out() as usize),
+                _ => Err(invalid_data("xz block is truncated or exceeds the buffer")),
+            }
+        }
+        #[allow(unreachable_patterns)]
+        codec => Err(HeaderError::UnsupportedCodec(codec).into()),
+    }
+}
+
+/// Reports corrupt compressed data as an IO error, like the zstd decoder
+fn invalid_data<E>(e: E) -> crate::Error
+where
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    io::Error::new(io::ErrorKind::InvalidData, e).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Codecs whose feature is enabled
+    fn supported() -> Vec<Codec> {
+        [Codec::Uncompressed, Codec::Zstd, Codec::Lz4, Codec::Xz]
+            .into_iter()
+            .filter(|codec| codec.is_supported())
+            .collect()
+    }
+
+    #[test]
+    fn test_round_trip() -> Result<()> {
+        let data = b"ACGTTGCAAACCGGTT".repeat(4096);
+        for codec in supported() {
+            let mut compressed = Vec::new();
+            compress(codec, 3, &data, &mut compressed)?;
+            if codec != Codec::Uncompressed {
+                assert!(compressed.len() < data.len() / 4, "{codec}");
+            }
+            let mut decompressed = vec![0; data.len()];
+            let size = decompress(codec, &compressed, &mut decompressed)?;
+            assert_eq!(&decompressed[..size], data.as_slice(), "{codec}");
+
+            // Data exceeding the buffer is rejected
+            let mut small = vec![0; data.len() - 1];
+            assert!(
+                decompress(codec, &compressed, &mut small).is_err(),
+                "{codec}"
+            );
+            // Corrupt data is rejected
+            if codec != Codec::Uncompressed {
+                let truncated = &compressed[..compressed.len() / 2];
+                assert!(decompress(codec, truncated, &mut decompressed).is_err());
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_unsupported() {
+        for codec in [Codec::Zstd, Codec::Lz4, Codec::Xz] {
+            if codec.is_supported() {
+                continue;
+            }
+            let error = compress(codec, 3, b"ACGT", &mut Vec::new()).unwrap_err();
+            let feature = codec.feature().unwrap();
+            assert!(error.to_string().contains(feature), "{error}");
+        }
+    }
+}
@@ -0,0 +1,342 @@
+//! # Columnar Block Layout
+//!
+//! Records are normally stored one after the other, so the flags and lengths, the packed
+//! nucleotides, and the quality scores of the records alternate within a block. These
+//! parts have very different statistics, which hurts the ratio of the block compressor.
+//! Files with the columnar layout (see `VBinseqHeader::set_columnar`) instead store every
+//! block as four regions, followed by the padding of the block:
+//!
+//! 1. The preambles (flag and lengths) of all records
+//! 2. The encoded sequences of all records
+//! 3. The quality scores of all records
+//! 4. The trailers (auxiliary value and checksum) of all records
+//!
+//! Every region holds the bytes of the records in order and exactly as they are stored in
+//! the row layout, so blocks keep their size and can be converted between the layouts
+//! with the file header alone.
+//!
+//! Writers rearrange blocks right before compressing them, so record checksums, the
+//! footer digest, and record offsets (see `BlockIndex::add_record_offsets`) refer to the
+//! records in the row layout, while block checksums cover the stored (columnar) block.
+//! `RecordBlock` parses the regions directly, and all other readers of raw blocks restore
+//! the row layout first.
+//!
+//! # Example
+//!
+//! ```rust
+//! use vbinseq::{MemoryReader, VBinseqHeader, VBinseqWriterBuilder};
+//!
+//! let mut header = VBinseqHeader::new(true, true, false);
+//! header.set_columnar(true);
+//!
+//! let mut bytes = Vec::new();
+//! let mut writer = VBinseqWriterBuilder::default()
+//!     .header(header)
+//!     .build(&mut bytes)
+//!     .unwrap();
+//! writer.write_nucleotides_quality(0, b"ACGT", b"IIII").unwrap();
+//! writer.finish().unwrap();
+//! drop(writer);
+//!
+//! let mut reader = MemoryReader::new(bytes).unwrap();
+//! let mut block = reader.new_block();
+//! reader.read_block_into(&mut block).unwrap();
+//! assert_eq!(block.iter().next().unwrap().squal(), b"IIII");
+//! ```
+
+use byteorder::{ByteOrder, LittleEndian};
+
+use crate::error::{ReadError, Result};
+use crate::header::{BlockHeader, VBinseqHeader, RECORD_NO_QUALITY};
+use crate::reader::encoded_sequence_len;
+
+/// Layout of the records of a block
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Layout {
+    /// Number of lengths stored in the preamble of every record
+    stored_lengths: usize,
+    /// Length of all records of a fixed-length block
+    fixed_length: Option<u64>,
+    /// Whether records store base bytes (quality scores or run lengths)
+    base_bytes: bool,
+    /// Whether records without quality scores are marked (optional quality scores)
+    marks_quality: bool,
+    /// Whether sequences are 4-bit encoded (see the `iupac` module)
+    iupac: bool,
+    /// Size of the auxiliary value and checksum stored after the data of every record
+    trailer: usize,
+}
+impl Layout {
+    /// Returns the layout of the records of a file
+    ///
+    /// The length of the records of fixed-length files is set per block (see
+    /// `with_fixed_length`).
+    pub(crate) fn new(header: &VBinseqHeader) -> Self {
+        Self {
+            stored_lengths: header.record_lengths() / 8,
+            fixed_length: header.is_fixed_length().then_some(0),
+            base_bytes: header.has_base_bytes(),
+            marks_quality: header.marks_quality(),
+            iupac: header.is_iupac(),
+            trailer: header.record_trailer(),
+        }
+    }
+
+    /// Returns the layout of the records of a block
+    pub(crate) fn of_block(header: &VBinseqHeader, block_header: &BlockHeader) -> Self {
+        Self::new(header).with_fixed_length(block_header.fixed_length())
+    }
+
+    /// Sets the length of all records of a fixed-length block
+    ///
+    /// This has no effect on the layout of files with variable-length records.
+    pub(crate) fn with_fixed_length(mut self, length: u64) -> Self {
+        if self.fixed_length.is_some() {
+            self.fixed_length = Some(length);
+        }
+        self
+    }
+
+    /// Returns the size of the flag and lengths stored before the data of every record
+    pub(crate) fn preamble(&self) -> usize {
+        8 + 8 * self.stored_lengths
+    }
+
+    /// Returns the size of the auxiliary value and checksum stored after every record
+    pub(crate) fn trailer(&self) -> usize {
+        self.trailer
+    }
+
+    /// Appends the segment lengths of a record read from its preamble
+    ///
+    /// Returns whether the record stores base bytes (quality scores or run lengths).
+    pub(crate) fn read_lengths(&self, preamble: &[u8], lens: &mut Vec<u64>) -> bool {
+        if let Some(length) = self.fixed_length {
+            lens.push(length);
+            return self.base_bytes;
+        }
+        let start = lens.len();
+        lens.extend(
+            preamble[8..self.preamble()]
+                .chunks_exact(8)
+                .map(LittleEndian::read_u64),
+        );
+        if self.marks_quality {
+            let has_quality = lens[start] & RECORD_NO_QUALITY == 0;
+            lens[start] &= !RECORD_NO_QUALITY;
+            return has_quality;
+        }
+        self.base_bytes
+    }
+
+    /// Returns the size of the encoded sequences of a segment
+    pub(crate) fn sequence_size(&self, len: u64) -> usize {
+        encoded_sequence_len(len, self.iupac) * 8
+    }
+}
+
+/// Offsets of the regions of a block in the columnar layout
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Regions {
+    /// Offset of the encoded sequences (the end of the preambles)
+    pub(crate) sequences: usize,
+    /// Offset of the quality scores
+    pub(crate) qualities: usize,
+    /// Offset of the trailers
+    pub(crate) trailers: usize,
+    /// End of the records (the start of the padding)
+    pub(crate) end: usize,
+}
+impl Regions {
+    /// Finds the regions of a block from the preambles of its records
+    ///
+    /// Lengths are untrusted, so the records are checked to fit into the block.
+    ///
+    /// # Parameters
+    ///
+    /// * `block` - The decompressed bytes of the block
+    /// * `n_records` - The number of records declared by the block header
+    /// * `layout` - The layout of the records of the block
+    /// * `columnar` - Whether the block is in the columnar layout (otherwise the
+    ///   preambles are found by walking the records of the row layout)
+    ///
+    /// # Errors
+    ///
+    /// * `ReadError::RecordCountMismatch` - If the block cannot hold the preambles of all
+    ///   declared records
+    /// * `ReadError::InvalidRecordLength` - If the lengths of a record exceed the block
+    pub(crate) fn find(
+        block: &[u8],
+        n_records: usize,
+        layout: &Layout,
+        columnar: bool,
+    ) -> Result<Self> {
+        let preamble = layout.preamble();
+        let mut lens = Vec::new();
+        let (mut sequences, mut qualities) = (0usize, 0usize);
+        let mut end = 0usize;
+        for i in 0..n_records {
+            let start = if columnar { i * preamble } else { end };
+            let Some(bytes) = block.get(start..start + preamble) else {
+                return Err(ReadError::RecordCountMismatch(n_records as u32, i).into());
+            };
+            lens.clear();
+            let has_quality = layout.read_lengths(bytes, &mut lens);
+            let size = lens.iter().try_fold(0usize, |size, &len| {
+                let sequence = layout.sequence_size(len);
+                let quality = if has_quality {
+                    usize::try_from(len).ok()?
+                } else {
+                    0
+                };
+                sequences = sequences.checked_add(sequence)?;
+                qualities = qualities.checked_add(quality)?;
+                size.checked_add(sequence)?.checked_add(quality)
+            });
+            match size.and_then(|size| end.checked_add(preamble + size + layout.trailer())) {
+                Some(next) if next <= block.len() => end = next,
+                _ => {
+                    let xlen = lens.get(1).copied().unwrap_or_default();
+                    return Err(ReadError::InvalidRecordLength(start, lens[0], xlen).into());
+                }
+            }
+        }
+        let sequences_start = n_records * preamble;
+        Ok(Self {
+            sequences: sequences_start,
+            qualities: sequences_start + sequences,
+            trailers: sequences_start + sequences + qualities,
+            end,
+        })
+    }
+}
+
+/// Rearranges a block between the row and the columnar layout
+///
+/// The output has the size of the input, and the padding after the records is copied
+/// as-is.
+///
+/// # Parameters
+///
+/// * `block` - The decompressed bytes of the block
+/// * `n_records` - The number of records declared by the block header
+/// * `layout` - The layout of the records of the block
+/// * `columnar` - Whether `block` is in the columnar layout (it is converted to the row
+///   layout if so, and to the columnar layout otherwise)
+/// * `output` - The buffer to write the converted block to (cleared first)
+///
+/// # Errors
+///
+/// * `ReadError::RecordCountMismatch` - If the block cannot hold the preambles of all
+///   declared records
+/// * `ReadError::InvalidRecordLength` - If the lengths of a record exceed the block
+pub(crate) fn convert(
+    block: &[u8],
+    n_records: usize,
+    layout: &Layout,
+    columnar: bool,
+    output: &mut Vec<u8>,
+) -> Result<()> {
+    let regions = Regions::find(block, n_records, layout, columnar)?;
+    output.clear();
+    output.extend_from_slice(block);
+
+    // Offsets of the next record in the row layout and in every region
+    let preamble = layout.preamble();
+    let (mut row, mut preambles) = (0, 0);
+    let (mut sequences, mut qualities, mut trailers) =
+        (regions.sequences, regions.qualities, regions.trailers);
+    let mut move_part = |row: &mut usize, column: &mut usize, size: usize| {
+        let (from, to) = if columnar {
+            (*column, *row)
+        } else {
+            (*row, *column)
+        };
+        output[to..to + size].copy_from_slice(&block[from..from + size]);
+        *row += size;
+        *column += size;
+    };
+    let mut lens = Vec::new();
+    for _ in 0..n_records {
+        let start = if columnar { preambles } else { row };
+        lens.clear();
+        let has_quality = layout.read_lengths(&block[start..start + preamble], &mut lens);
+        move_part(&mut row, &mut preambles, preamble);
+        for &len in &lens {
+            move_part(&mut row, &mut sequences, layout.sequence_size(len));
+            if has_quality {
+                move_part(&mut row, &mut qualities, len as usize);
+            }
+        }
+        move_part(&mut row, &mut trailers, layout.trailer());
+    }
+    Ok(())
+}
+
+/// Restores the row layout of a columnar block
+///
+/// # Parameters
+///
+/// * `block` - The decompressed bytes of the block
+/// * `header` - The header of the file the block belongs to
+/// * `block_header` - The header of the block
+/// * `rows` - The buffer to write the block in the row layout to (cleared first)
+///
+/// # Errors
+///
+/// * `ReadError::RecordCountMismatch` - If the block cannot hold the preambles of all
+///   declared records
+/// * `ReadError::InvalidRecordLength` - If the lengths of a record exceed the block
+pub(crate) fn to_rows(
+    block: &[u8],
+    header: &VBinseqHeader,
+    block_header: &BlockHeader,
+    rows: &mut Vec<u8>,
+) -> Result<()> {
+    let layout = Layout::of_block(header, block_header);
+    convert(block, block_header.records as usize, &layout, true, rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+    use rand::rngs::SmallRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_convert() -> Result<()> {
+        let mut rng = SmallRng::seed_from_u64(24);
+        for mut header in testing::headers() {
+            header.set_aux(header.compressed());
+            header.set_record_crc(header.has_footer());
+            header.set_compressed(false);
+            let records = testing::random_records(&mut rng, &header, 50);
+            let bytes = testing::write_records(header, &records)?;
+
+            // Every block converts to the other layout and back
+            let mut reader = crate::MemoryReader::new(bytes)?;
+            let columnar = header.is_columnar();
+            let (mut converted, mut restored) = (Vec::new(), Vec::new());
+            while let Some(raw) = reader.next_raw_block()? {
+                let layout = Layout::of_block(&header, &raw.header);
+                let n_records = raw.header.records as usize;
+                convert(raw.data, n_records, &layout, columnar, &mut converted)?;
+                assert_eq!(converted.len(), raw.data.len());
+                convert(&converted, n_records, &layout, !columnar, &mut restored)?;
+                assert_eq!(restored, raw.data, "{}", header.summary());
+                let columns = if columnar { raw.data } else { &converted };
+
+                // The regions hold the parts of all records
+                let regions = Regions::find(columns, n_records, &layout, true)?;
+                assert_eq!(regions.sequences, n_records * layout.preamble());
+                assert_eq!(regions.trailers + n_records * layout.trailer(), regions.end);
+                assert!(columns[regions.end..].iter().all(|&byte| byte == 0));
+
+                // Blocks declaring more records than they hold are rejected
+                assert!(Regions::find(columns, n_records + 1000, &layout, true).is_err());
+            }
+        }
+        Ok(())
+    }
+}
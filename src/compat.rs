@@ -0,0 +1,16 @@
+//! # Ecosystem Compatibility
+//!
+//! This module provides adapters between VBINSEQ records and the record abstractions of
+//! other Rust sequence parsing libraries, so existing tools can consume VBINSEQ files with
+//! minimal changes. Each adapter is gated behind a feature named after the library:
+//!
+//! * `needletail` - Record conversions for [needletail](https://docs.rs/needletail)
+//! * `paraseq` - Record traits and parallel processors of [paraseq](https://docs.rs/paraseq)
+//! * `seq_io` - Record conversions for [seq_io](https://docs.rs/seq_io)
+
+#[cfg(feature = "needletail")]
+pub mod needletail;
+#[cfg(feature = "paraseq")]
+pub mod paraseq;
+#[cfg(feature = "seq_io")]
+pub mod seq_io;
@@ -0,0 +1,160 @@
+//! # needletail Adapters
+//!
+//! This module converts between VBINSEQ records and the records of
+//! [needletail](https://docs.rs/needletail). It requires the `needletail` feature.
+//!
+//! * needletail's `SequenceRecord` converts into an `OwnedRecord` (single-end, with a flag
+//!   of 0), and `write_fastx_records` writes every record of a needletail reader into a
+//!   VBINSEQ writer.
+//! * `OwnedRecord` implements needletail's `Sequence` trait over its primary sequence, so
+//!   needletail's k-mer utilities can be used on decoded VBINSEQ records. Records of
+//!   homopolymer-compressed files must be expanded first (see
+//!   `OwnedRecord::expand_homopolymers`).
+//! * The other segments of a record (e.g. the extended sequence of paired records) are
+//!   available through `OwnedRecord::segment`, whose byte slices also implement `Sequence`.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use std::fs::File;
+//! use vbinseq::compat::needletail::write_fastx_records;
+//! use vbinseq::{VBinseqHeader, VBinseqWriterBuilder};
+//!
+//! let mut reader = needletail::parse_fastx_file("reads.fastq").unwrap();
+//! let header = VBinseqHeader::new(true, true, false);
+//! let mut writer = VBinseqWriterBuilder::default()
+//!     .header(header)
+//!     .build(File::create("reads.vbq").unwrap())
+//!     .unwrap();
+//!
+//! let stats = write_fastx_records(reader.as_mut(), &mut writer).unwrap();
+//! writer.finish().unwrap();
+//! println!("Wrote {} records", stats.n_records);
+//! ```
+
+use std::io::Write;
+
+use needletail::parser::SequenceRecord;
+use needletail::{FastxReader, Sequence};
+
+use crate::convert::ConvertStats;
+use crate::{OwnedRecord, Result, VBinseqWriter};
+
+impl From<&SequenceRecord<'_>> for OwnedRecord {
+    fn from(record: &SequenceRecord<'_>) -> Self {
+        let quality = record.qual().map(<[u8]>::to_vec).unwrap_or_default();
+        Self::new(0, record.seq().into_owned(), quality)
+    }
+}
+
+impl<'a> Sequence<'a> for OwnedRecord {
+    fn sequence(&'a self) -> &'a [u8] {
+        debug_assert!(
+            !self.is_homopolymer(),
+            "homopolymer-compressed records must be expanded first"
+        );
+        self.seq()
+    }
+}
+
+/// Writes all records of a needletail reader into a VBINSEQ writer
+///
+/// Records are written with `VBinseqWriter::write_record`, so the quality scores are only
+/// kept if the writer is configured for quality scores. Records are single-end, so the
+/// writer must not be configured for paired-end reads.
+///
+/// # Parameters
+///
+/// * `reader` - The needletail reader to consume
+/// * `writer` - The destination of the records
+///
+/// # Returns
+///
+/// * `Ok(ConvertStats)` - The statistics of the conversion
+///
+/// # Errors
+///
+/// * `Error::NeedletailError` - If a record could not be parsed
+/// * Any error raised by the writer (e.g. `WriteError::PairedFlagSet`)
+pub fn write_fastx_records<W: Write>(
+    reader: &mut dyn FastxReader,
+    writer: &mut VBinseqWriter<W>,
+) -> Result<ConvertStats> {
+    let mut stats = ConvertStats::default();
+    while let Some(record) = reader.next() {
+        let record = OwnedRecord::from(&record?);
+        stats.n_sequences += 1;
+        stats.record(writer.write_record(&record)?);
+    }
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MemoryReader, VBinseqHeader, VBinseqWriterBuilder};
+
+    #[test]
+    fn test_needletail_records() -> Result<()> {
+        let fasta = b">a\nACGT\nACGT\n>b\nTTNN\n";
+        let mut reader = needletail::parse_fastx_reader(&fasta[..]).unwrap();
+
+        let mut bytes = Vec::new();
+        let header = VBinseqHeader::new(false, true, false);
+        let mut writer = VBinseqWriterBuilder::default()
+            .header(header)
+            .policy(crate::Policy::IgnoreSequence)
+            .build(&mut bytes)?;
+        let stats = write_fastx_records(reader.as_mut(), &mut writer)?;
+        writer.finish()?;
+        drop(writer);
+        assert_eq!(stats.n_sequences, 2);
+        assert_eq!(stats.n_records, 1);
+        assert_eq!(stats.n_skipped, 1);
+
+        let mut reader = MemoryReader::new(bytes)?;
+        let mut block = reader.new_block();
+        assert!(reader.read_block_into(&mut block)?);
+        let record = OwnedRecord::try_from(&block.iter().next().unwrap())?;
+        assert_eq!(record.seq(), b"ACGTACGT");
+        let kmers: Vec<&[u8]> = record.kmers(4).collect();
+        assert_eq!(kmers.len(), 5);
+        Ok(())
+    }
+
+    #[test]
+    fn test_segment_kmers() -> Result<()> {
+        let record = OwnedRecord::new_segments(
+            0,
+            vec![b"ACGTA".to_vec(), b"GGCC".to_vec(), b"TTAAT".to_vec()],
+            vec![],
+        );
+        let kmers: Vec<&[u8]> = (0..record.n_segments())
+            .flat_map(|segment| record.segment(segment).kmers(4))
+            .collect();
+        assert_eq!(kmers, [b"ACGT", b"CGTA", b"GGCC", b"TTAA", b"TAAT"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_homopolymer_kmers() -> Result<()> {
+        let mut bytes = Vec::new();
+        let mut header = VBinseqHeader::new(false, false, false);
+        header.set_homopolymer(true);
+        let mut writer = VBinseqWriterBuilder::default()
+            .header(header)
+            .build(&mut bytes)?;
+        writer.write_nucleotides(0, b"AAACGGGG")?;
+        writer.finish()?;
+        drop(writer);
+
+        let mut reader = MemoryReader::new(bytes)?;
+        let mut block = reader.new_block();
+        assert!(reader.read_block_into(&mut block)?);
+        let mut record = OwnedRecord::try_from(&block.iter().next().unwrap())?;
+        record.expand_homopolymers()?;
+        let kmers: Vec<&[u8]> = record.kmers(4).collect();
+        assert_eq!(kmers, [b"AAAC", b"AACG", b"ACGG", b"CGGG", b"GGGG"]);
+        Ok(())
+    }
+}
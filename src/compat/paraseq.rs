@@ -0,0 +1,421 @@
+//! # paraseq Adapters
+//!
+//! This module lets processors written against paraseq's FASTX abstractions consume
+//! VBINSEQ files. It requires the `paraseq` feature.
+//!
+//! * `ParaseqRecord` implements `paraseq::fastx::Record` over decoded sequences.
+//! * `ParaseqProcessor` wraps a `paraseq::parallel::ParallelProcessor` so it can be passed
+//!   to `MmapReader::process_parallel`. All segments of a record (e.g. both mates of paired
+//!   records) are processed as consecutive records.
+//! * `ParaseqPairedProcessor` wraps a `paraseq::parallel::PairedParallelProcessor` and
+//!   processes the primary and extended sequences of each record as a pair. Further
+//!   segments (e.g. index reads) are not processed, as pairs hold two reads.
+//!
+//! VBINSEQ records have no names, so the id of each record is its global index in the file.
+//! The processors expand homopolymer-compressed sequences, which have no quality scores.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use paraseq::fastx::Record;
+//! use paraseq::parallel::{ParallelProcessor, Result};
+//! use vbinseq::compat::paraseq::ParaseqProcessor;
+//! use vbinseq::MmapReader;
+//!
+//! #[derive(Clone, Default)]
+//! struct BaseCounter {
+//!     n_bases: usize,
+//! }
+//! impl ParallelProcessor for BaseCounter {
+//!     fn process_record<Rf: Record>(&mut self, record: Rf) -> Result<()> {
+//!         self.n_bases += record.seq().len();
+//!         Ok(())
+//!     }
+//! }
+//!
+//! let reader = MmapReader::new("example.vbq").unwrap();
+//! reader
+//!     .process_parallel(ParaseqProcessor::new(BaseCounter::default()), 4)
+//!     .unwrap();
+//! ```
+
+use std::io::Write;
+
+use paraseq::fastx::Record;
+use paraseq::parallel::{PairedParallelProcessor, ParallelProcessor};
+
+use crate::error::{ReadError, Result};
+use crate::{OwnedRecord, RefRecord};
+
+/// A decoded sequence implementing paraseq's `Record` trait
+///
+/// Each `ParaseqRecord` describes a single read: either the primary or the extended
+/// sequence of a VBINSEQ record.
+#[derive(Debug, Clone, Copy)]
+pub struct ParaseqRecord<'a> {
+    /// Name of the read
+    id: &'a [u8],
+
+    /// Decoded nucleotide sequence
+    seq: &'a [u8],
+
+    /// Quality scores (`None` if the file has no quality scores)
+    qual: Option<&'a [u8]>,
+}
+impl<'a> ParaseqRecord<'a> {
+    /// Creates a new record from its components
+    pub fn new(id: &'a [u8], seq: &'a [u8], qual: Option<&'a [u8]>) -> Self {
+        Self { id, seq, qual }
+    }
+
+    /// Creates a record describing the primary sequence of `record`
+    ///
+    /// Homopolymer-compressed records must be expanded first (see
+    /// `OwnedRecord::expand_homopolymers`), as their run lengths are never described as
+    /// quality scores.
+    pub fn primary(id: &'a [u8], record: &'a OwnedRecord) -> Self {
+        let qual = has_quality(record).then_some(record.squal());
+        Self::new(id, record.seq(), qual)
+    }
+
+    /// Creates a record describing the extended sequence of `record`
+    ///
+    /// Returns `None` if the record is not paired. Homopolymer-compressed records must be
+    /// expanded first (see `primary`).
+    pub fn extended(id: &'a [u8], record: &'a OwnedRecord) -> Option<Self> {
+        if !record.is_paired() {
+            return None;
+        }
+        let qual = has_quality(record).then_some(record.xqual());
+        Some(Self::new(id, record.xseq(), qual))
+    }
+
+    /// Creates a record describing a segment of `record`
+    ///
+    /// Segments 0 and 1 are the primary and extended sequences. Returns `None` for segments
+    /// past the last segment of the record. Homopolymer-compressed records must be expanded
+    /// first (see `primary`).
+    pub fn segment(id: &'a [u8], record: &'a OwnedRecord, segment: usize) -> Option<Self> {
+        if segment >= record.n_segments() {
+            return None;
+        }
+        let qual = has_quality(record).then_some(record.segment_qual(segment));
+        Some(Self::new(id, record.segment(segment), qual))
+    }
+}
+impl Record for ParaseqRecord<'_> {
+    fn id(&self) -> &[u8] {
+        self.id
+    }
+
+    fn seq(&self) -> &[u8] {
+        self.seq
+    }
+
+    fn qual(&self) -> Option<&[u8]> {
+        self.qual
+    }
+}
+
+/// Checks if a record has quality scores (and not the run lengths of homopolymers)
+fn has_quality(record: &OwnedRecord) -> bool {
+    record.has_quality() && !record.is_homopolymer()
+}
+
+/// Decoding buffers shared by the processor adapters
+#[derive(Clone, Default)]
+struct DecodeBuffer {
+    /// The decoded record
+    record: OwnedRecord,
+
+    /// The id of the decoded record
+    id: Vec<u8>,
+}
+impl DecodeBuffer {
+    fn fill(&mut self, record: &RefRecord) -> Result<()> {
+        self.record.fill(record)?;
+        self.record.expand_homopolymers()?;
+        self.id.clear();
+        write!(self.id, "{}", record.index())?;
+        Ok(())
+    }
+}
+
+/// Adapter running a paraseq `ParallelProcessor` over VBINSEQ records
+///
+/// The primary sequence of every record is passed to the wrapped processor, followed by
+/// the extended sequence if the record is paired and by any further segment.
+#[derive(Clone)]
+pub struct ParaseqProcessor<P> {
+    /// The wrapped paraseq processor
+    inner: P,
+
+    /// Buffers of the decoded record
+    buffer: DecodeBuffer,
+}
+impl<P> ParaseqProcessor<P> {
+    /// Wraps a paraseq processor
+    pub fn new(inner: P) -> Self {
+        Self {
+            inner,
+            buffer: DecodeBuffer::default(),
+        }
+    }
+
+    /// Returns a reference to the wrapped processor
+    pub fn inner(&self) -> &P {
+        &self.inner
+    }
+
+    /// Consumes the adapter and returns the wrapped processor
+    pub fn into_inner(self) -> P {
+        self.inner
+    }
+}
+impl<P: ParallelProcessor> crate::ParallelProcessor for ParaseqProcessor<P> {
+    fn process_record(&mut self, record: RefRecord) -> Result<()> {
+        self.buffer.fill(&record)?;
+        let DecodeBuffer { record, id } = &self.buffer;
+        for segment in 0..record.n_segments() {
+            if let Some(read) = ParaseqRecord::segment(id, record, segment) {
+                self.inner.process_record(read)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn on_batch_complete(&mut self) -> Result<()> {
+        self.inner.on_batch_complete()?;
+        Ok(())
+    }
+
+    fn on_thread_complete(&mut self) -> Result<()> {
+        self.inner.on_thread_complete()?;
+        Ok(())
+    }
+
+    fn set_tid(&mut self, tid: usize) {
+        self.inner.set_thread_id(tid);
+    }
+}
+
+/// Adapter running a paraseq `PairedParallelProcessor` over paired VBINSEQ records
+///
+/// The primary and extended sequences of every record are passed to the wrapped processor
+/// as a pair. Further segments are not passed.
+///
+/// # Errors
+///
+/// Processing fails with `ReadError::UnpairedRecord` if a record has no extended sequence.
+#[derive(Clone)]
+pub struct ParaseqPairedProcessor<P> {
+    /// The wrapped paraseq processor
+    inner: P,
+
+    /// Buffers of the decoded record
+    buffer: DecodeBuffer,
+}
+impl<P> ParaseqPairedProcessor<P> {
+    /// Wraps a paired paraseq processor
+    pub fn new(inner: P) -> Self {
+        Self {
+            inner,
+            buffer: DecodeBuffer::default(),
+        }
+    }
+
+    /// Returns a reference to the wrapped processor
+    pub fn inner(&self) -> &P {
+        &self.inner
+    }
+
+    /// Consumes the adapter and returns the wrapped processor
+    pub fn into_inner(self) -> P {
+        self.inner
+    }
+}
+impl<P: PairedParallelProcessor> crate::ParallelProcessor for ParaseqPairedProcessor<P> {
+    fn process_record(&mut self, record: RefRecord) -> Result<()> {
+        self.buffer.fill(&record)?;
+        let DecodeBuffer { record, id } = &self.buffer;
+        let Some(extended) = ParaseqRecord::extended(id, record) else {
+            return Err(ReadError::UnpairedRecord(record.index()).into());
+        };
+        self.inner
+            .process_record_pair(ParaseqRecord::primary(id, record), extended)?;
+        Ok(())
+    }
+
+    fn on_batch_complete(&mut self) -> Result<()> {
+        self.inner.on_batch_complete()?;
+        Ok(())
+    }
+
+    fn on_thread_complete(&mut self) -> Result<()> {
+        self.inner.on_thread_complete()?;
+        Ok(())
+    }
+
+    fn set_tid(&mut self, tid: usize) {
+        self.inner.set_thread_id(tid);
+    }
+}
+
+#[cfg(all(test, feature = "mmap"))]
+mod tests {
+    use std::sync::Arc;
+
+    use parking_lot::Mutex;
+
+    use super::*;
+    use crate::conformance::TestVector;
+    use crate::MmapReader;
+
+    /// The (id, sequence, quality) of a processed record
+    type Read = (String, Vec<u8>, Option<Vec<u8>>);
+
+    /// Collects every processed record
+    #[derive(Clone, Default)]
+    struct Collector {
+        records: Arc<Mutex<Vec<Read>>>,
+        n_threads_complete: Arc<Mutex<usize>>,
+    }
+    impl Collector {
+        fn push<Rf: Record>(&self, record: &Rf) {
+            self.records.lock().push((
+                record.id_str().to_string(),
+                record.seq().to_vec(),
+                record.qual().map(<[u8]>::to_vec),
+            ));
+        }
+    }
+    impl ParallelProcessor for Collector {
+        fn process_record<Rf: Record>(&mut self, record: Rf) -> paraseq::parallel::Result<()> {
+            self.push(&record);
+            Ok(())
+        }
+
+        fn on_thread_complete(&mut self) -> paraseq::parallel::Result<()> {
+            *self.n_threads_complete.lock() += 1;
+            Ok(())
+        }
+    }
+    impl PairedParallelProcessor for Collector {
+        fn process_record_pair<Rf: Record>(
+            &mut self,
+            record1: Rf,
+            record2: Rf,
+        ) -> paraseq::parallel::Result<()> {
+            assert_eq!(record1.id(), record2.id());
+            self.push(&record1);
+            self.push(&record2);
+            Ok(())
+        }
+    }
+
+    fn write_vector(name: &str, vector: &TestVector) -> Result<std::path::PathBuf> {
+        let path =
+            std::env::temp_dir().join(format!("vbq_paraseq_{}_{}.vbq", name, std::process::id()));
+        vector.write_vbq(std::fs::File::create(&path)?)?;
+        Ok(path)
+    }
+
+    fn expected(vector: &TestVector) -> Vec<Read> {
+        let mut expected = Vec::new();
+        for (index, record) in vector.records.iter().enumerate() {
+            let quality = |q: &Vec<u8>| (!record.quality.is_empty()).then(|| q.clone());
+            expected.push((
+                index.to_string(),
+                record.sequence.clone(),
+                quality(&record.quality),
+            ));
+            if !record.extended.is_empty() {
+                expected.push((
+                    index.to_string(),
+                    record.extended.clone(),
+                    quality(&record.extended_quality),
+                ));
+            }
+            for (sequence, extra_quality) in &record.extra_segments {
+                expected.push((index.to_string(), sequence.clone(), quality(extra_quality)));
+            }
+        }
+        expected
+    }
+
+    #[test]
+    fn test_paraseq_processor() -> Result<()> {
+        let vector = TestVector::generate(true, true, true);
+        let path = write_vector("single", &vector)?;
+
+        let collector = Collector::default();
+        let reader = MmapReader::new(&path)?;
+        reader.process_parallel(ParaseqProcessor::new(collector.clone()), 2)?;
+
+        let mut records = collector.records.lock().clone();
+        records.sort_by_key(|(id, _, _)| id.parse::<usize>().unwrap());
+        assert_eq!(records, expected(&vector));
+        assert_eq!(*collector.n_threads_complete.lock(), 2);
+
+        std::fs::remove_file(&path)?;
+        std::fs::remove_file(format!("{}.vqi", path.display())).ok();
+        Ok(())
+    }
+
+    #[test]
+    fn test_segments() -> Result<()> {
+        let vector = TestVector::extension(crate::header::FLAG_SEGMENTS);
+        let path = write_vector("segments", &vector)?;
+
+        // Every segment is processed as a record
+        let collector = Collector::default();
+        let reader = MmapReader::new(&path)?;
+        reader.process_parallel(ParaseqProcessor::new(collector.clone()), 1)?;
+        assert_eq!(*collector.records.lock(), expected(&vector));
+        let n_segments = vector.header.segments() * vector.records.len();
+        assert_eq!(collector.records.lock().len(), n_segments);
+
+        std::fs::remove_file(&path)?;
+        std::fs::remove_file(format!("{}.vqi", path.display())).ok();
+        Ok(())
+    }
+
+    #[test]
+    fn test_homopolymer_records() -> Result<()> {
+        let vector = TestVector::extension(crate::header::FLAG_HOMOPOLYMER);
+        let path = write_vector("homopolymer", &vector)?;
+
+        // Sequences are expanded, and their run lengths are not passed as quality scores
+        let collector = Collector::default();
+        let reader = MmapReader::new(&path)?;
+        reader.process_parallel(ParaseqPairedProcessor::new(collector.clone()), 1)?;
+        assert_eq!(*collector.records.lock(), expected(&vector));
+
+        std::fs::remove_file(&path)?;
+        std::fs::remove_file(format!("{}.vqi", path.display())).ok();
+        Ok(())
+    }
+
+    #[test]
+    fn test_paraseq_paired_processor() -> Result<()> {
+        let paired = TestVector::generate(false, false, true);
+        let path = write_vector("paired", &paired)?;
+
+        let collector = Collector::default();
+        let reader = MmapReader::new(&path)?;
+        reader.process_parallel(ParaseqPairedProcessor::new(collector.clone()), 1)?;
+        assert_eq!(*collector.records.lock(), expected(&paired));
+
+        let single = TestVector::generate(false, false, false);
+        let single_path = write_vector("unpaired", &single)?;
+        let reader = MmapReader::new(&single_path)?;
+        let result = reader.process_parallel(ParaseqPairedProcessor::new(Collector::default()), 1);
+        assert!(result.is_err());
+
+        for path in [path, single_path] {
+            std::fs::remove_file(&path)?;
+            std::fs::remove_file(format!("{}.vqi", path.display())).ok();
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,278 @@
+//! # seq_io Adapters
+//!
+//! This module converts between VBINSEQ records and the FASTA/FASTQ records of
+//! [seq_io](https://docs.rs/seq_io). It requires the `seq_io` feature.
+//!
+//! * seq_io records convert into `OwnedRecord`s (single-end, with a flag of 0), which can be
+//!   written with `VBinseqWriter::write_record`.
+//! * `OwnedRecord`s convert into seq_io's owned FASTA and FASTQ records. The conversion
+//!   describes the primary sequence; `extended_fasta` and `extended_fastq` describe the
+//!   extended sequence of paired records, and `segment_fasta` and `segment_fastq` describe
+//!   any segment of a record.
+//!
+//! VBINSEQ records have no names, so the header of each converted record is its global
+//! index in the file. Records without quality scores are given `DEFAULT_QUALITY` scores
+//! when converted into FASTQ records. Homopolymer-compressed sequences are expanded, so
+//! they are also given `DEFAULT_QUALITY` scores (see `OwnedRecord::expand_homopolymers`).
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use std::fs::File;
+//! use seq_io::fastq::Reader;
+//! use vbinseq::{OwnedRecord, VBinseqHeader, VBinseqWriterBuilder};
+//!
+//! let mut reader = Reader::from_path("reads.fastq").unwrap();
+//! let header = VBinseqHeader::new(true, true, false);
+//! let mut writer = VBinseqWriterBuilder::default()
+//!     .header(header)
+//!     .build(File::create("reads.vbq").unwrap())
+//!     .unwrap();
+//!
+//! while let Some(record) = reader.next() {
+//!     let record = OwnedRecord::from(&record.unwrap());
+//!     writer.write_record(&record).unwrap();
+//! }
+//! writer.finish().unwrap();
+//! ```
+
+use seq_io::{fasta, fastq};
+
+use crate::{Error, OwnedRecord};
+
+/// Quality score assigned to records without quality scores when converted to FASTQ
+pub const DEFAULT_QUALITY: u8 = b'?';
+
+/// Returns the header of a converted record
+fn record_head(record: &OwnedRecord) -> Vec<u8> {
+    record.index().to_string().into_bytes()
+}
+
+/// Returns the quality scores of a converted record
+fn record_quality(sequence: &[u8], quality: &[u8]) -> Vec<u8> {
+    if quality.is_empty() {
+        vec![DEFAULT_QUALITY; sequence.len()]
+    } else {
+        quality.to_vec()
+    }
+}
+
+impl From<&fastq::RefRecord<'_>> for OwnedRecord {
+    fn from(record: &fastq::RefRecord<'_>) -> Self {
+        use fastq::Record;
+        Self::new(0, record.seq().to_vec(), record.qual().to_vec())
+    }
+}
+
+impl From<&fastq::OwnedRecord> for OwnedRecord {
+    fn from(record: &fastq::OwnedRecord) -> Self {
+        Self::new(0, record.seq.clone(), record.qual.clone())
+    }
+}
+
+impl From<&fasta::RefRecord<'_>> for OwnedRecord {
+    fn from(record: &fasta::RefRecord<'_>) -> Self {
+        Self::new(0, record.owned_seq(), Vec::new())
+    }
+}
+
+impl From<&fasta::OwnedRecord> for OwnedRecord {
+    fn from(record: &fasta::OwnedRecord) -> Self {
+        Self::new(0, record.seq.clone(), Vec::new())
+    }
+}
+
+impl TryFrom<&OwnedRecord> for fastq::OwnedRecord {
+    type Error = Error;
+
+    fn try_from(record: &OwnedRecord) -> crate::Result<Self> {
+        let record = record.expanded()?;
+        Ok(Self {
+            head: record_head(&record),
+            seq: record.seq().to_vec(),
+            qual: record_quality(record.seq(), record.squal()),
+        })
+    }
+}
+
+impl TryFrom<&OwnedRecord> for fasta::OwnedRecord {
+    type Error = Error;
+
+    fn try_from(record: &OwnedRecord) -> crate::Result<Self> {
+        let record = record.expanded()?;
+        Ok(Self {
+            head: record_head(&record),
+            seq: record.seq().to_vec(),
+        })
+    }
+}
+
+/// Converts the extended sequence of a record into a FASTQ record
+///
+/// Returns `None` if the record is not paired.
+///
+/// # Errors
+///
+/// * `ReadError::InvalidHomopolymerRuns` - If the run lengths of a homopolymer-compressed
+///   record do not match its sequence
+pub fn extended_fastq(record: &OwnedRecord) -> crate::Result<Option<fastq::OwnedRecord>> {
+    segment_fastq(record, 1)
+}
+
+/// Converts the extended sequence of a record into a FASTA record
+///
+/// Returns `None` if the record is not paired.
+///
+/// # Errors
+///
+/// * `ReadError::InvalidHomopolymerRuns` - If the run lengths of a homopolymer-compressed
+///   record do not match its sequence
+pub fn extended_fasta(record: &OwnedRecord) -> crate::Result<Option<fasta::OwnedRecord>> {
+    segment_fasta(record, 1)
+}
+
+/// Converts a segment of a record into a FASTQ record
+///
+/// Segments 0 and 1 are the primary and extended sequences. Returns `None` for segments past
+/// the last segment of the record.
+///
+/// # Errors
+///
+/// * `ReadError::InvalidHomopolymerRuns` - If the run lengths of a homopolymer-compressed
+///   record do not match its sequence
+pub fn segment_fastq(
+    record: &OwnedRecord,
+    segment: usize,
+) -> crate::Result<Option<fastq::OwnedRecord>> {
+    let record = record.expanded()?;
+    Ok((segment < record.n_segments()).then(|| fastq::OwnedRecord {
+        head: record_head(&record),
+        seq: record.segment(segment).to_vec(),
+        qual: record_quality(record.segment(segment), record.segment_qual(segment)),
+    }))
+}
+
+/// Converts a segment of a record into a FASTA record
+///
+/// Segments 0 and 1 are the primary and extended sequences. Returns `None` for segments past
+/// the last segment of the record.
+///
+/// # Errors
+///
+/// * `ReadError::InvalidHomopolymerRuns` - If the run lengths of a homopolymer-compressed
+///   record do not match its sequence
+pub fn segment_fasta(
+    record: &OwnedRecord,
+    segment: usize,
+) -> crate::Result<Option<fasta::OwnedRecord>> {
+    let record = record.expanded()?;
+    Ok((segment < record.n_segments()).then(|| fasta::OwnedRecord {
+        head: record_head(&record),
+        seq: record.segment(segment).to_vec(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MemoryReader, VBinseqHeader, VBinseqWriterBuilder};
+
+    #[test]
+    fn test_seq_io_round_trip() -> crate::Result<()> {
+        let fastq = b"@r1 desc\nACGTACGT\n+\nIIIIFFFF\n@r2\nTTGCA\n+\n!!!!!\n";
+        let mut reader = fastq::Reader::new(&fastq[..]);
+
+        let mut bytes = Vec::new();
+        let header = VBinseqHeader::new(true, false, false);
+        let mut writer = VBinseqWriterBuilder::default()
+            .header(header)
+            .build(&mut bytes)?;
+        while let Some(record) = reader.next() {
+            let record = record.expect("valid FASTQ record");
+            assert!(writer.write_record(&OwnedRecord::from(&record))?);
+        }
+        writer.finish()?;
+        drop(writer);
+
+        let mut reader = MemoryReader::new(bytes)?;
+        let mut block = reader.new_block();
+        let mut converted = Vec::new();
+        while reader.read_block_into(&mut block)? {
+            for record in block.iter() {
+                let owned = OwnedRecord::try_from(&record)?;
+                assert!(extended_fastq(&owned)?.is_none());
+                converted.push(fastq::OwnedRecord::try_from(&owned)?);
+            }
+        }
+        assert_eq!(converted.len(), 2);
+        assert_eq!(converted[0].head, b"0");
+        assert_eq!(converted[0].seq, b"ACGTACGT");
+        assert_eq!(converted[0].qual, b"IIIIFFFF");
+        assert_eq!(converted[1].head, b"1");
+        assert_eq!(converted[1].qual, b"!!!!!");
+        Ok(())
+    }
+
+    #[test]
+    fn test_seq_io_default_quality() -> crate::Result<()> {
+        let record = OwnedRecord::new_paired(0, b"ACGT".to_vec(), b"GG".to_vec(), vec![], vec![]);
+        let primary = fastq::OwnedRecord::try_from(&record)?;
+        assert_eq!(primary.qual, vec![DEFAULT_QUALITY; 4]);
+        let extended = extended_fastq(&record)?.unwrap();
+        assert_eq!(extended.seq, b"GG");
+        assert_eq!(extended.qual, vec![DEFAULT_QUALITY; 2]);
+        assert_eq!(extended_fasta(&record)?.unwrap().seq, b"GG");
+        Ok(())
+    }
+
+    #[test]
+    fn test_segments() -> crate::Result<()> {
+        let record = OwnedRecord::new_segments(
+            7,
+            vec![b"ACGT".to_vec(), b"GG".to_vec(), b"TTAC".to_vec()],
+            vec![b"IIII".to_vec(), b"FF".to_vec(), b"!!!!".to_vec()],
+        );
+        let fastq: Vec<_> = (0..record.n_segments())
+            .map(|segment| segment_fastq(&record, segment).map(Option::unwrap))
+            .collect::<crate::Result<_>>()?;
+        assert_eq!(fastq.len(), 3);
+        assert_eq!(fastq[0], fastq::OwnedRecord::try_from(&record)?);
+        assert_eq!(Some(fastq[1].clone()), extended_fastq(&record)?);
+        assert_eq!(fastq[2].seq, b"TTAC");
+        assert_eq!(fastq[2].qual, b"!!!!");
+        assert_eq!(segment_fasta(&record, 2)?.unwrap().seq, b"TTAC");
+        assert!(segment_fastq(&record, 3)?.is_none());
+        assert!(segment_fasta(&record, 3)?.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_homopolymer_records() -> crate::Result<()> {
+        let mut bytes = Vec::new();
+        let mut header = VBinseqHeader::new(false, false, true);
+        header.set_homopolymer(true);
+        let mut writer = VBinseqWriterBuilder::default()
+            .header(header)
+            .build(&mut bytes)?;
+        writer.write_nucleotides_paired(0, b"AAACGGGG", b"TTTA")?;
+        writer.finish()?;
+        drop(writer);
+
+        let mut reader = MemoryReader::new(bytes)?;
+        let mut block = reader.new_block();
+        assert!(reader.read_block_into(&mut block)?);
+        let record = OwnedRecord::try_from(&block.iter().next().unwrap())?;
+        assert!(record.is_homopolymer());
+
+        // Run lengths are not converted into quality scores
+        let primary = fastq::OwnedRecord::try_from(&record)?;
+        assert_eq!(primary.seq, b"AAACGGGG");
+        assert_eq!(primary.qual, vec![DEFAULT_QUALITY; 8]);
+        let extended = extended_fastq(&record)?.unwrap();
+        assert_eq!(extended.seq, b"TTTA");
+        assert_eq!(extended.qual, vec![DEFAULT_QUALITY; 4]);
+        assert_eq!(fasta::OwnedRecord::try_from(&record)?.seq, b"AAACGGGG");
+        assert_eq!(extended_fasta(&record)?.unwrap().seq, b"TTTA");
+        Ok(())
+    }
+}
@@ -0,0 +1,588 @@
+//! # Conformance Test Vectors
+//!
+//! This module deterministically generates small reference VBINSEQ files covering every
+//! combination of file features (quality scores, paired records, compression) along with
+//! edge-case sequence lengths around the 2-bit word boundaries. Every format extension
+//! (see `VBinseqHeader::flags`) and the header sections get a test vector of their own.
+//!
+//! Each test vector is written as a `.vbq` file together with a `.tsv` file describing
+//! the expected records, so that other implementations of the format can validate their
+//! readers against these golden files.
+//!
+//! The expected records file has one line per record with the following tab-separated
+//! columns (empty columns for absent data):
+//!
+//! 1. record index
+//! 2. flag
+//! 3. primary sequence
+//! 4. primary quality scores
+//! 5. extended sequence
+//! 6. extended quality scores
+//!
+//! Files with auxiliary values add a column with the auxiliary value of the record, and
+//! files with more than two segments add the sequence and quality scores of every further
+//! segment as two more columns. Sequences of homopolymer-compressed files are listed
+//! expanded, as they were before compression.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use vbinseq::conformance::write_test_vectors;
+//!
+//! // Write all test vectors into a directory
+//! let paths = write_test_vectors("./conformance").unwrap();
+//! println!("Wrote {} test vectors", paths.len());
+//! ```
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
+
+use crate::header::{
+    FLAG_AUX, FLAG_BLOCK_CHECKSUM, FLAG_CODEC_FALLBACK, FLAG_COLUMNAR, FLAG_EMBEDDED_INDEX,
+    FLAG_FIXED_LENGTH, FLAG_FOOTER, FLAG_HOMOPOLYMER, FLAG_IUPAC, FLAG_OPTIONAL_QUALITY,
+    FLAG_RECORD_CRC, FLAG_SEGMENTS,
+};
+use crate::homopolymer::expand;
+use crate::read_group::{with_read_group, ReadGroup};
+use crate::sections::HeaderSections;
+use crate::writer::RNG_SEED;
+use crate::{
+    iupac, OwnedRecord, QualityBins, RefRecord, Result, VBinseqHeader, VBinseqWriterBuilder,
+};
+
+/// Block size used for all test vectors (2KB)
+///
+/// This is small enough that every test vector spans multiple blocks.
+pub const VECTOR_BLOCK_SIZE: u64 = 2 * 1024;
+
+/// Sequence lengths exercised by every test vector
+///
+/// These cover the smallest possible records as well as lengths around the
+/// 32-nucleotide boundaries of the 2-bit encoding.
+pub const EDGE_CASE_LENGTHS: [usize; 12] = [1, 2, 31, 32, 33, 63, 64, 65, 96, 100, 150, 1000];
+
+/// Number of times the edge-case lengths are cycled in each test vector
+const LENGTH_CYCLES: usize = 4;
+
+/// Format extensions covered by a test vector of their own (see `TestVector::extension`)
+pub const EXTENSION_FLAGS: [u32; 12] = [
+    FLAG_FOOTER,
+    FLAG_HOMOPOLYMER,
+    FLAG_RECORD_CRC,
+    FLAG_AUX,
+    FLAG_FIXED_LENGTH,
+    FLAG_OPTIONAL_QUALITY,
+    FLAG_BLOCK_CHECKSUM,
+    FLAG_EMBEDDED_INDEX,
+    FLAG_SEGMENTS,
+    FLAG_IUPAC,
+    FLAG_COLUMNAR,
+    FLAG_CODEC_FALLBACK,
+];
+
+/// Length of the records of the fixed-length test vector
+const FIXED_LENGTH: usize = 100;
+
+/// Number of segments of the records of the segments test vector
+const VECTOR_SEGMENTS: usize = 3;
+
+/// Lengths of the segments after the extended sequence, which are short index reads
+const INDEX_LENGTHS: [usize; 5] = [1, 8, 10, 32, 33];
+
+/// A single record of a test vector
+#[derive(Debug, Clone, PartialEq)]
+pub struct VectorRecord {
+    /// Flag of the record
+    pub flag: u64,
+
+    /// Primary nucleotide sequence
+    pub sequence: Vec<u8>,
+
+    /// Quality scores of the primary sequence (empty if the file has no quality scores)
+    pub quality: Vec<u8>,
+
+    /// Extended nucleotide sequence (empty if the file is not paired)
+    pub extended: Vec<u8>,
+
+    /// Quality scores of the extended sequence (empty if not paired or no quality scores)
+    pub extended_quality: Vec<u8>,
+
+    /// Auxiliary value of the record (0 if the file has no auxiliary values)
+    pub aux: u64,
+
+    /// Sequences and quality scores of the segments after the extended sequence (empty
+    /// unless the file has more than two segments)
+    pub extra_segments: Vec<(Vec<u8>, Vec<u8>)>,
+}
+impl VectorRecord {
+    /// Decodes a record read from a test vector
+    ///
+    /// Sequences of homopolymer-compressed files are expanded, so the record compares
+    /// equal to the record of the test vector.
+    ///
+    /// # Parameters
+    ///
+    /// * `record` - The record to decode
+    /// * `header` - The header of the file the record was read from
+    pub fn from_record(record: &RefRecord, header: &VBinseqHeader) -> Result<Self> {
+        let owned = OwnedRecord::try_from(record)?;
+        let (mut sequence, mut extended) = (Vec::new(), Vec::new());
+        let (quality, extended_quality) = if header.is_homopolymer() {
+            expand(owned.seq(), owned.squal(), &mut sequence)?;
+            expand(owned.xseq(), owned.xqual(), &mut extended)?;
+            (Vec::new(), Vec::new())
+        } else {
+            sequence.extend_from_slice(owned.seq());
+            extended.extend_from_slice(owned.xseq());
+            (owned.squal().to_vec(), owned.xqual().to_vec())
+        };
+        let extra_segments = (2..header.segments())
+            .map(|i| (owned.segment(i).to_vec(), owned.segment_qual(i).to_vec()))
+            .collect();
+        Ok(Self {
+            flag: owned.flag(),
+            sequence,
+            quality,
+            extended,
+            extended_quality,
+            aux: owned.aux(),
+            extra_segments,
+        })
+    }
+
+    /// Returns the record as it is passed to the writer
+    fn to_owned_record(&self) -> OwnedRecord {
+        let record = if self.extra_segments.is_empty() {
+            OwnedRecord::new_paired(
+                self.flag,
+                self.sequence.clone(),
+                self.extended.clone(),
+                self.quality.clone(),
+                self.extended_quality.clone(),
+            )
+        } else {
+            let (extra, extra_quality): (Vec<_>, Vec<_>) =
+                self.extra_segments.iter().cloned().unzip();
+            let sequences = [self.sequence.clone(), self.extended.clone()];
+            let qualities = [self.quality.clone(), self.extended_quality.clone()];
+            OwnedRecord::new_segments(
+                self.flag,
+                sequences.into_iter().chain(extra).collect(),
+                qualities.into_iter().chain(extra_quality).collect(),
+            )
+        };
+        record.with_aux(self.aux)
+    }
+}
+
+/// A deterministic reference file and its expected records
+#[derive(Debug, Clone)]
+pub struct TestVector {
+    /// Name of the test vector (used as file stem)
+    pub name: String,
+
+    /// Header of the reference file
+    pub header: VBinseqHeader,
+
+    /// Header sections of the reference file
+    pub sections: HeaderSections,
+
+    /// Records stored in the reference file
+    pub records: Vec<VectorRecord>,
+}
+impl TestVector {
+    /// Generates a test vector for the given feature combination
+    ///
+    /// The records are generated from a fixed seed so every call produces identical data.
+    ///
+    /// # Parameters
+    ///
+    /// * `qual` - Whether the file includes quality scores
+    /// * `compressed` - Whether the blocks are ZSTD compressed
+    /// * `paired` - Whether records contain paired sequences
+    pub fn generate(qual: bool, compressed: bool, paired: bool) -> Self {
+        let header = VBinseqHeader::with_capacity(VECTOR_BLOCK_SIZE, qual, compressed, paired);
+        Self {
+            name: vector_name(qual, compressed, paired),
+            header,
+            sections: HeaderSections::default(),
+            records: generate_records(&header, b"ACGT"),
+        }
+    }
+
+    /// Generates a test vector for a format extension
+    ///
+    /// The vector stores uncompressed paired records with quality scores, unless the
+    /// extension requires otherwise: homopolymer-compressed records have no quality
+    /// scores, fixed-length records are single-end and all have the same length, and
+    /// every other record of the optional quality vector has no quality scores. IUPAC
+    /// sequences draw from all nucleotide codes, and the codec fallback vector is
+    /// compressed.
+    ///
+    /// # Parameters
+    ///
+    /// * `flag` - The extension flag (one of `EXTENSION_FLAGS`)
+    ///
+    /// # Panics
+    ///
+    /// If `flag` is not one of `EXTENSION_FLAGS`
+    pub fn extension(flag: u32) -> Self {
+        let mut header = VBinseqHeader::with_capacity(VECTOR_BLOCK_SIZE, true, false, true);
+        let name = match flag {
+            FLAG_FOOTER => {
+                header.set_footer(true);
+                "footer"
+            }
+            FLAG_HOMOPOLYMER => {
+                header.set_qual(false);
+                header.set_homopolymer(true);
+                "homopolymer"
+            }
+            FLAG_RECORD_CRC => {
+                header.set_record_crc(true);
+                "record_crc"
+            }
+            FLAG_AUX => {
+                header.set_aux(true);
+                "aux"
+            }
+            FLAG_FIXED_LENGTH => {
+                header.set_paired(false);
+                header.set_fixed_length(true);
+                "fixed_length"
+            }
+            FLAG_OPTIONAL_QUALITY => {
+                header.set_optional_quality(true);
+                "optional_quality"
+            }
+            FLAG_BLOCK_CHECKSUM => {
+                header.set_block_checksum(true);
+                "block_checksum"
+            }
+            FLAG_EMBEDDED_INDEX => {
+                header.set_footer(true);
+                header.set_embedded_index(true);
+                "embedded_index"
+            }
+            FLAG_SEGMENTS => {
+                header
+                    .set_segments(VECTOR_SEGMENTS)
+                    .expect("segment count is valid");
+                "segments"
+            }
+            FLAG_IUPAC => {
+                header.set_iupac(true);
+                "iupac"
+            }
+            FLAG_COLUMNAR => {
+                header.set_columnar(true);
+                "columnar"
+            }
+            FLAG_CODEC_FALLBACK => {
+                header.set_compressed(true);
+                header.set_codec_fallback(true);
+                "codec_fallback"
+            }
+            _ => panic!("No test vector for extension flag {flag:#x}"),
+        };
+        let alphabet = if header.is_iupac() {
+            &iupac::ALPHABET[1..]
+        } else {
+            b"ACGT"
+        };
+        Self {
+            name: format!("ext_{name}"),
+            header,
+            sections: HeaderSections::default(),
+            records: generate_records(&header, alphabet),
+        }
+    }
+
+    /// Generates a test vector with header sections
+    ///
+    /// The file stores a read-group table referenced by its records, key/value metadata,
+    /// and quality score bins, so the quality scores of the records are binned.
+    pub fn with_sections() -> Self {
+        let header = VBinseqHeader::with_capacity(VECTOR_BLOCK_SIZE, true, false, true);
+        let bins = QualityBins::illumina();
+        let mut records = generate_records(&header, b"ACGT");
+        for (i, record) in records.iter_mut().enumerate() {
+            record.flag = with_read_group(record.flag, (i % 2) as u16);
+            bins.apply(&mut record.quality);
+            bins.apply(&mut record.extended_quality);
+        }
+        let sections = HeaderSections {
+            read_groups: vec![
+                ReadGroup::new("lane1").with_tag("SM", "sample1"),
+                ReadGroup::new("lane2").with_tag("SM", "sample1"),
+            ],
+            metadata: vec![("source".to_string(), "conformance".to_string())],
+            quality_bins: Some(bins),
+            ..HeaderSections::default()
+        };
+        Self {
+            name: "sections".to_string(),
+            header,
+            sections,
+            records,
+        }
+    }
+
+    /// Generates a header-only test vector without any records
+    pub fn empty() -> Self {
+        Self {
+            name: "empty".to_string(),
+            header: VBinseqHeader::with_capacity(VECTOR_BLOCK_SIZE, false, false, false),
+            sections: HeaderSections::default(),
+            records: Vec::new(),
+        }
+    }
+
+    /// Writes the test vector as a VBINSEQ file
+    ///
+    /// # Parameters
+    ///
+    /// * `inner` - The destination of the VBINSEQ file
+    pub fn write_vbq<W: Write>(&self, inner: W) -> Result<()> {
+        let mut writer = VBinseqWriterBuilder::default()
+            .header(self.header)
+            .sections(self.sections.clone())
+            .build(inner)?;
+        for record in &self.records {
+            writer.write_record(&record.to_owned_record())?;
+        }
+        writer.finish()
+    }
+
+    /// Writes the expected records of the test vector as tab-separated values
+    ///
+    /// # Parameters
+    ///
+    /// * `writer` - The destination of the expected records
+    pub fn write_expected<W: Write>(&self, writer: &mut W) -> Result<()> {
+        for (index, record) in self.records.iter().enumerate() {
+            write!(writer, "{}\t{}\t", index, record.flag)?;
+            writer.write_all(&record.sequence)?;
+            writer.write_all(b"\t")?;
+            writer.write_all(&record.quality)?;
+            writer.write_all(b"\t")?;
+            writer.write_all(&record.extended)?;
+            writer.write_all(b"\t")?;
+            writer.write_all(&record.extended_quality)?;
+            if self.header.has_aux() {
+                write!(writer, "\t{}", record.aux)?;
+            }
+            for (sequence, quality) in &record.extra_segments {
+                writer.write_all(b"\t")?;
+                writer.write_all(sequence)?;
+                writer.write_all(b"\t")?;
+                writer.write_all(quality)?;
+            }
+            writer.write_all(b"\n")?;
+        }
+        Ok(())
+    }
+}
+
+/// Generates all test vectors
+///
+/// This covers every combination of quality scores, compression, and pairing, every
+/// format extension, and the header sections, as well as a header-only file.
+pub fn test_vectors() -> Vec<TestVector> {
+    let mut vectors = vec![TestVector::empty()];
+    for qual in [false, true] {
+        for compressed in [false, true] {
+            for paired in [false, true] {
+                vectors.push(TestVector::generate(qual, compressed, paired));
+            }
+        }
+    }
+    vectors.extend(EXTENSION_FLAGS.map(TestVector::extension));
+    vectors.push(TestVector::with_sections());
+    vectors
+}
+
+/// Writes all test vectors into a directory
+///
+/// For each test vector a `<name>.vbq` file and a `<name>.tsv` file with the
+/// expected records are written. The directory is created if it does not exist.
+///
+/// # Parameters
+///
+/// * `dir` - The directory to write the test vectors into
+///
+/// # Returns
+///
+/// The paths of the written `.vbq` files
+pub fn write_test_vectors<P: AsRef<Path>>(dir: P) -> Result<Vec<PathBuf>> {
+    std::fs::create_dir_all(&dir)?;
+    let mut paths = Vec::new();
+    for vector in test_vectors() {
+        let vbq_path = dir.as_ref().join(format!("{}.vbq", vector.name));
+        let tsv_path = dir.as_ref().join(format!("{}.tsv", vector.name));
+
+        vector.write_vbq(File::create(&vbq_path).map(BufWriter::new)?)?;
+
+        let mut tsv = File::create(&tsv_path).map(BufWriter::new)?;
+        vector.write_expected(&mut tsv)?;
+        tsv.flush()?;
+
+        paths.push(vbq_path);
+    }
+    Ok(paths)
+}
+
+/// Builds the name of a test vector from its feature combination
+fn vector_name(qual: bool, compressed: bool, paired: bool) -> String {
+    let mut name = if paired { "paired" } else { "single" }.to_string();
+    if qual {
+        name.push_str("_qual");
+    }
+    if compressed {
+        name.push_str("_zstd");
+    }
+    name
+}
+
+/// Generates the records of a test vector for a file header
+///
+/// The records cycle through the edge-case lengths, and their contents are drawn from a
+/// fixed seed so every call produces identical data.
+///
+/// # Parameters
+///
+/// * `header` - The header of the file the records are written to
+/// * `alphabet` - The nucleotides the sequences are drawn from
+fn generate_records(header: &VBinseqHeader, alphabet: &[u8]) -> Vec<VectorRecord> {
+    let mut rng = SmallRng::seed_from_u64(RNG_SEED);
+    let lengths = EDGE_CASE_LENGTHS.iter().cycle();
+    lengths
+        .clone()
+        .zip(lengths.skip(EDGE_CASE_LENGTHS.len() / 2))
+        .take(EDGE_CASE_LENGTHS.len() * LENGTH_CYCLES)
+        .enumerate()
+        .map(|(i, (&slen, &xlen))| {
+            let slen = if header.is_fixed_length() {
+                FIXED_LENGTH
+            } else {
+                slen
+            };
+            let xlen = if header.paired() { xlen } else { 0 };
+            let qual = header.qual() && !(header.has_optional_quality() && i % 2 == 1);
+            let mut record = VectorRecord {
+                flag: rng.gen(),
+                sequence: random_sequence(&mut rng, alphabet, slen),
+                quality: random_quality(&mut rng, if qual { slen } else { 0 }),
+                extended: random_sequence(&mut rng, alphabet, xlen),
+                extended_quality: random_quality(&mut rng, if qual { xlen } else { 0 }),
+                aux: 0,
+                extra_segments: Vec::new(),
+            };
+            if header.has_aux() {
+                record.aux = rng.gen();
+            }
+            for _ in 2..header.segments() {
+                let len = INDEX_LENGTHS[i % INDEX_LENGTHS.len()];
+                let sequence = random_sequence(&mut rng, alphabet, len);
+                let quality = random_quality(&mut rng, if qual { len } else { 0 });
+                record.extra_segments.push((sequence, quality));
+            }
+            record
+        })
+        .collect()
+}
+
+/// Generates a random nucleotide sequence of the given length
+fn random_sequence(rng: &mut SmallRng, alphabet: &[u8], len: usize) -> Vec<u8> {
+    (0..len)
+        .map(|_| alphabet[rng.gen_range(0..alphabet.len())])
+        .collect()
+}
+
+/// Generates random quality scores of the given length (Phred+33, Q0-Q41)
+fn random_quality(rng: &mut SmallRng, len: usize) -> Vec<u8> {
+    (0..len).map(|_| rng.gen_range(b'!'..=b'J')).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MemoryReader;
+
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn test_vectors_round_trip() -> crate::Result<()> {
+        let dir = std::env::temp_dir().join(format!("vbq_conformance_{}", std::process::id()));
+        let paths = write_test_vectors(&dir)?;
+
+        for (vector, path) in test_vectors().iter().zip(paths) {
+            let mut reader = crate::MmapReader::new(&path)?;
+            let mut block = reader.new_block();
+            let mut n_blocks = 0;
+            let mut records = Vec::new();
+            while reader.read_block_into(&mut block)? {
+                for record in block.iter() {
+                    records.push(VectorRecord::from_record(&record, &vector.header)?);
+                }
+                n_blocks += 1;
+            }
+            assert_eq!(reader.sections()?, vector.sections);
+            assert_eq!(records, vector.records, "mismatch in {}", vector.name);
+            assert!(vector.records.is_empty() || n_blocks > 1);
+        }
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_vectors_memory_reader() -> crate::Result<()> {
+        for vector in test_vectors() {
+            let mut bytes = Vec::new();
+            vector.write_vbq(&mut bytes)?;
+
+            let mut reader = MemoryReader::new(bytes)?;
+            let mut block = reader.new_block();
+            let mut n_blocks = 0;
+            let mut records = Vec::new();
+            while reader.read_block_into(&mut block)? {
+                n_blocks += 1;
+                for record in block.iter() {
+                    records.push(VectorRecord::from_record(&record, &vector.header)?);
+                }
+            }
+            assert_eq!(records, vector.records, "mismatch in {}", vector.name);
+            assert_eq!(reader.build_index()?.n_blocks(), n_blocks);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_vectors_block_access() -> crate::Result<()> {
+        for vector in test_vectors() {
+            let mut bytes = Vec::new();
+            vector.write_vbq(&mut bytes)?;
+
+            let mut reader = MemoryReader::new(bytes)?;
+            let index = reader.build_index()?;
+            let mut block = reader.new_block();
+            for range in index.ranges().iter().rev() {
+                assert!(reader.read_block_at(range, &mut block)?);
+                assert_eq!(block.n_records(), range.block_records as usize);
+                for (offset, record) in block.iter().enumerate() {
+                    let index = range.cumulative_records as usize + offset;
+                    assert_eq!(record.index(), index as u64);
+                    assert_eq!(
+                        VectorRecord::from_record(&record, &vector.header)?,
+                        vector.records[index]
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+}
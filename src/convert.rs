@@ -0,0 +1,763 @@
+//! # Format Conversion
+//!
+//! This module converts sequence data from other formats into VBINSEQ files.
+//!
+//! ## FASTA
+//!
+//! `fasta_to_vbq` encodes FASTA records (without quality scores) such as reference genomes
+//! or assemblies. Multi-line sequences are joined, lowercase (soft-masked) nucleotides are
+//! converted to uppercase, and invalid nucleotides are handled by the configured `Policy`.
+//!
+//! Records cannot span blocks, so sequences too long to fit into a single block are split
+//! into consecutive fragments of at most `FastaOptions::max_fragment_len` nucleotides. The
+//! flag of every record is the 0-based ordinal of the FASTA entry it came from, so the
+//! fragments of a contig can be reassembled by concatenating consecutive records sharing
+//! the same flag. Sequence names are not stored.
+//!
+//! ## FASTQ
+//!
+//! `encode_fastq` and `encode_fastq_paired` encode FASTQ records (requires the `seq_io`
+//! feature), either single-end, as interleaved mates, or as mates from two files.
+//! `decode_to_fastq_parallel` writes the records of a file back as FASTQ using multiple
+//! threads (requires the `mmap` feature). Record names are not stored, so decoded records
+//! are named by their index.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use std::fs::File;
+//! use std::io::BufWriter;
+//! use vbinseq::convert::{fasta_to_vbq, FastaOptions};
+//!
+//! let input = File::open("reference.fa").unwrap();
+//! let output = File::create("reference.vbq").map(BufWriter::new).unwrap();
+//!
+//! let stats = fasta_to_vbq(input, output, &FastaOptions::default()).unwrap();
+//! println!("Wrote {} records from {} sequences", stats.n_records, stats.n_sequences);
+//! ```
+
+use std::io::{BufRead, BufReader, Read, Write};
+#[cfg(feature = "mmap")]
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc, Mutex,
+};
+
+use crate::error::{ReadError, Result, WriteError};
+use crate::header::BLOCK_SIZE;
+use crate::writer::record_byte_size;
+#[cfg(feature = "mmap")]
+use crate::{homopolymer::expand, MmapReader, ParallelProcessor, RefRecord};
+use crate::{Policy, VBinseqHeader, VBinseqWriter, VBinseqWriterBuilder};
+
+/// Quality score written for records without quality scores
+pub const DEFAULT_QUALITY: u8 = b'?';
+
+/// Options for converting FASTA files
+#[derive(Debug, Clone, Copy)]
+pub struct FastaOptions {
+    /// Virtual block size of the output file in bytes
+    pub block_size: u64,
+
+    /// Whether the blocks of the output file are ZSTD compressed
+    pub compressed: bool,
+
+    /// Policy for handling invalid nucleotides
+    pub policy: Policy,
+}
+impl Default for FastaOptions {
+    /// Creates options with the default block size, compression enabled, and
+    /// invalid nucleotides replaced by random draws
+    ///
+    /// Without the `compression` feature blocks are stored uncompressed, and without the
+    /// `policy-rand` feature invalid nucleotides are replaced with A.
+    fn default() -> Self {
+        Self {
+            block_size: BLOCK_SIZE,
+            compressed: cfg!(feature = "compression"),
+            #[cfg(feature = "policy-rand")]
+            policy: Policy::RandomDraw,
+            #[cfg(not(feature = "policy-rand"))]
+            policy: Policy::SetToA,
+        }
+    }
+}
+impl FastaOptions {
+    /// Returns the maximum number of nucleotides stored in a single record
+    ///
+    /// This is the longest sequence that fits into a single block of the configured size.
+    pub fn max_fragment_len(&self) -> usize {
+        // Each 64-bit word holds 32 nucleotides
+        let words = (self.block_size as usize).saturating_sub(record_byte_size(0, 0)) / 8;
+        words * 32
+    }
+}
+
+/// Options for converting FASTQ files
+#[derive(Debug, Clone, Copy)]
+pub struct FastqOptions {
+    /// Virtual block size of the output file in bytes
+    pub block_size: u64,
+
+    /// Whether the blocks of the output file are ZSTD compressed
+    pub compressed: bool,
+
+    /// Whether quality scores are stored
+    pub quality: bool,
+
+    /// Whether consecutive records of a single input are the mates of paired records
+    ///
+    /// This is ignored by `encode_fastq_paired`, which always writes paired records.
+    pub interleaved: bool,
+
+    /// Policy for handling invalid nucleotides
+    pub policy: Policy,
+}
+impl Default for FastqOptions {
+    /// Creates options for single-end records with quality scores, the default block size,
+    /// and compression enabled (if the `compression` feature is enabled)
+    fn default() -> Self {
+        Self {
+            block_size: BLOCK_SIZE,
+            compressed: cfg!(feature = "compression"),
+            quality: true,
+            interleaved: false,
+            policy: Policy::default(),
+        }
+    }
+}
+
+/// Options for decoding files to FASTQ
+#[derive(Debug, Clone, Copy)]
+pub struct FastqDecodeOptions {
+    /// Number of worker threads
+    pub n_threads: usize,
+
+    /// Quality score written for records without quality scores
+    pub default_quality: u8,
+}
+impl Default for FastqDecodeOptions {
+    /// Creates options using all available cores and `DEFAULT_QUALITY` scores
+    fn default() -> Self {
+        Self {
+            n_threads: std::thread::available_parallelism().map_or(1, usize::from),
+            default_quality: DEFAULT_QUALITY,
+        }
+    }
+}
+
+/// Statistics of a conversion
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ConvertStats {
+    /// Number of input sequences
+    pub n_sequences: u64,
+
+    /// Number of records written
+    pub n_records: u64,
+
+    /// Number of records skipped (invalid nucleotides under `Policy::IgnoreSequence`,
+    /// or input sequences without any nucleotides)
+    pub n_skipped: u64,
+}
+impl ConvertStats {
+    /// Counts a record as written or skipped
+    pub(crate) fn record(&mut self, written: bool) {
+        if written {
+            self.n_records += 1;
+        } else {
+            self.n_skipped += 1;
+        }
+    }
+}
+
+/// Converts FASTA records into a VBINSEQ file
+///
+/// # Parameters
+///
+/// * `reader` - The source of the FASTA records (uncompressed)
+/// * `writer` - The destination of the VBINSEQ file
+/// * `options` - Options of the conversion
+///
+/// # Returns
+///
+/// * `Ok(ConvertStats)` - The statistics of the conversion
+///
+/// # Errors
+///
+/// * `ReadError::InvalidFastaLine` - If sequence data appears before the first FASTA header
+/// * `WriteError::RecordSizeExceedsMaximumBlockSize` - If the block size cannot hold any nucleotides
+/// * Any error raised by the writer (e.g. invalid nucleotides with `Policy::BreakOnInvalid`)
+/// * I/O errors from reading or writing
+pub fn fasta_to_vbq<R: Read, W: Write>(
+    reader: R,
+    writer: W,
+    options: &FastaOptions,
+) -> Result<ConvertStats> {
+    let header = VBinseqHeader::with_capacity(options.block_size, false, options.compressed, false);
+    let mut writer = VBinseqWriterBuilder::default()
+        .header(header)
+        .policy(options.policy)
+        .build(writer)?;
+
+    let max_len = options.max_fragment_len();
+    if max_len == 0 {
+        let record_size = record_byte_size(1, 0);
+        return Err(WriteError::RecordSizeExceedsMaximumBlockSize(
+            record_size,
+            options.block_size as usize,
+        )
+        .into());
+    }
+    let mut stats = ConvertStats::default();
+    let mut sequence = Vec::with_capacity(max_len);
+    let mut in_record = false;
+    let mut n_fragments = 0;
+
+    let mut reader = BufReader::new(reader);
+    let mut line = Vec::new();
+    let mut line_number = 0;
+    loop {
+        line.clear();
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            break;
+        }
+        line_number += 1;
+        let content = line.trim_ascii_end();
+        if content.first() == Some(&b'>') {
+            if in_record {
+                write_fragment(&mut writer, &mut stats, &mut sequence, &mut n_fragments)?;
+            }
+            stats.n_sequences += 1;
+            n_fragments = 0;
+            in_record = true;
+            continue;
+        }
+        if content.is_empty() {
+            continue;
+        }
+        if !in_record {
+            return Err(ReadError::InvalidFastaLine(line_number).into());
+        }
+
+        // Fill the sequence buffer, emitting fragments whenever it is full
+        let mut remaining = content;
+        while !remaining.is_empty() {
+            let take = remaining.len().min(max_len - sequence.len());
+            sequence.extend(remaining[..take].iter().map(u8::to_ascii_uppercase));
+            remaining = &remaining[take..];
+            if sequence.len() == max_len {
+                write_fragment(&mut writer, &mut stats, &mut sequence, &mut n_fragments)?;
+            }
+        }
+    }
+    if in_record {
+        write_fragment(&mut writer, &mut stats, &mut sequence, &mut n_fragments)?;
+    }
+    writer.finish()?;
+    Ok(stats)
+}
+
+/// Writes the buffered sequence as a record of the current FASTA entry
+///
+/// Nothing is written for an empty buffer, since records cannot be empty. Entries without
+/// any sequence data are counted as skipped.
+fn write_fragment<W: Write>(
+    writer: &mut VBinseqWriter<W>,
+    stats: &mut ConvertStats,
+    sequence: &mut Vec<u8>,
+    n_fragments: &mut u64,
+) -> Result<()> {
+    if sequence.is_empty() {
+        if *n_fragments == 0 {
+            stats.n_skipped += 1;
+        }
+        return Ok(());
+    }
+    let flag = stats.n_sequences - 1;
+    stats.record(writer.write_nucleotides(flag, sequence)?);
+    *n_fragments += 1;
+    sequence.clear();
+    Ok(())
+}
+
+/// Converts FASTQ records into a VBINSEQ file
+///
+/// Records are written with a flag of 0 and without their names. With
+/// `FastqOptions::interleaved`, consecutive records are written as the mates of a paired
+/// record. This requires the `seq_io` feature.
+///
+/// # Parameters
+///
+/// * `reader` - The source of the FASTQ records (uncompressed)
+/// * `writer` - The destination of the VBINSEQ file
+/// * `options` - Options of the conversion
+///
+/// # Returns
+///
+/// * `Ok(ConvertStats)` - The statistics of the conversion (`n_sequences` counts the input
+///   records, so mates are counted individually)
+///
+/// # Errors
+///
+/// * `Error::AnyhowError` - If a FASTQ record could not be parsed
+/// * `ReadError::InvalidMatePair` - If interleaved input has an odd number of records
+/// * Any error raised by the writer (e.g. invalid nucleotides with `Policy::BreakOnInvalid`)
+/// * I/O errors from writing
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use std::fs::File;
+/// use std::io::BufWriter;
+/// use vbinseq::convert::{encode_fastq, FastqOptions};
+///
+/// let input = File::open("reads.fq").unwrap();
+/// let output = File::create("reads.vbq").map(BufWriter::new).unwrap();
+///
+/// let stats = encode_fastq(input, output, &FastqOptions::default()).unwrap();
+/// println!("Wrote {} records ({} skipped)", stats.n_records, stats.n_skipped);
+/// ```
+#[cfg(feature = "seq_io")]
+pub fn encode_fastq<R: Read, W: Write>(
+    reader: R,
+    writer: W,
+    options: &FastqOptions,
+) -> Result<ConvertStats> {
+    use seq_io::fastq::Record;
+
+    let mut reader = seq_io::fastq::Reader::new(reader);
+    let mut writer = fastq_writer(writer, options, options.interleaved)?;
+    let mut stats = ConvertStats::default();
+    while let Some(primary) = reader.next() {
+        let primary = primary.map_err(anyhow::Error::from)?;
+        stats.n_sequences += 1;
+        if !options.interleaved {
+            let written = write_fastq_record(
+                &mut writer,
+                options.quality,
+                (primary.seq(), primary.qual()),
+                None,
+            )?;
+            stats.record(written);
+            continue;
+        }
+        // Release the borrow of the reader to parse the mate
+        let primary = primary.to_owned_record();
+        let Some(extended) = reader.next() else {
+            return Err(ReadError::InvalidMatePair(stats.n_sequences - 1).into());
+        };
+        let extended = extended.map_err(anyhow::Error::from)?;
+        stats.n_sequences += 1;
+        let written = write_fastq_record(
+            &mut writer,
+            options.quality,
+            (primary.seq(), primary.qual()),
+            Some((extended.seq(), extended.qual())),
+        )?;
+        stats.record(written);
+    }
+    writer.finish()?;
+    Ok(stats)
+}
+
+/// Converts paired FASTQ records from two files into a VBINSEQ file
+///
+/// The records of `extended` are the mates of the records of `primary`. Records are
+/// written with a flag of 0 and without their names. This requires the `seq_io` feature.
+///
+/// # Parameters
+///
+/// * `primary` - The source of the primary mates (uncompressed FASTQ)
+/// * `extended` - The source of the extended mates (uncompressed FASTQ)
+/// * `writer` - The destination of the VBINSEQ file
+/// * `options` - Options of the conversion (`interleaved` is ignored)
+///
+/// # Returns
+///
+/// * `Ok(ConvertStats)` - The statistics of the conversion (`n_sequences` counts the input
+///   records of both files)
+///
+/// # Errors
+///
+/// * `Error::AnyhowError` - If a FASTQ record could not be parsed
+/// * `ReadError::InvalidMatePair` - If the files hold different numbers of records
+/// * Any error raised by the writer
+/// * I/O errors from writing
+#[cfg(feature = "seq_io")]
+pub fn encode_fastq_paired<R: Read, S: Read, W: Write>(
+    primary: R,
+    extended: S,
+    writer: W,
+    options: &FastqOptions,
+) -> Result<ConvertStats> {
+    use seq_io::fastq::Record;
+
+    let mut primary = seq_io::fastq::Reader::new(primary);
+    let mut extended = seq_io::fastq::Reader::new(extended);
+    let mut writer = fastq_writer(writer, options, true)?;
+    let mut stats = ConvertStats::default();
+    loop {
+        match (primary.next(), extended.next()) {
+            (Some(r1), Some(r2)) => {
+                let r1 = r1.map_err(anyhow::Error::from)?;
+                let r2 = r2.map_err(anyhow::Error::from)?;
+                stats.n_sequences += 2;
+                let written = write_fastq_record(
+                    &mut writer,
+                    options.quality,
+                    (r1.seq(), r1.qual()),
+                    Some((r2.seq(), r2.qual())),
+                )?;
+                stats.record(written);
+            }
+            (None, None) => break,
+            _ => return Err(ReadError::InvalidMatePair(stats.n_sequences).into()),
+        }
+    }
+    writer.finish()?;
+    Ok(stats)
+}
+
+/// Builds the writer of a FASTQ conversion
+#[cfg(feature = "seq_io")]
+fn fastq_writer<W: Write>(
+    writer: W,
+    options: &FastqOptions,
+    paired: bool,
+) -> Result<VBinseqWriter<W>> {
+    let header = VBinseqHeader::with_capacity(
+        options.block_size,
+        options.quality,
+        options.compressed,
+        paired,
+    );
+    VBinseqWriterBuilder::default()
+        .header(header)
+        .policy(options.policy)
+        .build(writer)
+}
+
+/// Writes a FASTQ record (or pair of mates) given as sequence and quality scores
+///
+/// Records without nucleotides cannot be stored and are skipped.
+#[cfg(feature = "seq_io")]
+fn write_fastq_record<W: Write>(
+    writer: &mut VBinseqWriter<W>,
+    quality: bool,
+    primary: (&[u8], &[u8]),
+    extended: Option<(&[u8], &[u8])>,
+) -> Result<bool> {
+    if primary.0.is_empty() || extended.is_some_and(|(seq, _)| seq.is_empty()) {
+        return Ok(false);
+    }
+    match (extended, quality) {
+        (None, false) => writer.write_nucleotides(0, primary.0),
+        (None, true) => writer.write_nucleotides_quality(0, primary.0, primary.1),
+        (Some(extended), false) => writer.write_nucleotides_paired(0, primary.0, extended.0),
+        (Some(extended), true) => {
+            writer.write_nucleotides_quality_paired(0, primary.0, extended.0, primary.1, extended.1)
+        }
+    }
+}
+
+/// Decodes the records of a file to FASTQ using multiple threads
+///
+/// Every thread formats the records of a block into a local buffer, which is written to
+/// `writer` as a whole once the block is done. Records therefore stay in order within a
+/// block, but blocks are written in the order they finish. Records are named by their
+/// index, the segments of paired records (and records of more segments) are written as
+/// interleaved mates (`/1`, `/2`, and so on), and records without quality scores are given
+/// `FastqDecodeOptions::default_quality` scores.
+/// Homopolymer-compressed sequences are expanded.
+///
+/// This requires the `mmap` feature.
+///
+/// # Parameters
+///
+/// * `reader` - The reader of the file (consumed by the worker threads)
+/// * `writer` - The destination of the FASTQ records
+/// * `options` - Options of the decoding
+///
+/// # Returns
+///
+/// * `Ok(u64)` - The number of decoded records
+///
+/// # Errors
+///
+/// * Any error raised while reading or decoding blocks
+/// * I/O errors from writing
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use std::fs::File;
+/// use std::io::BufWriter;
+/// use vbinseq::convert::{decode_to_fastq_parallel, FastqDecodeOptions};
+/// use vbinseq::MmapReader;
+///
+/// let reader = MmapReader::new("reads.vbq").unwrap();
+/// let output = File::create("reads.fq").map(BufWriter::new).unwrap();
+///
+/// let n_records =
+///     decode_to_fastq_parallel(reader, output, &FastqDecodeOptions::default()).unwrap();
+/// println!("Decoded {n_records} records");
+/// ```
+#[cfg(feature = "mmap")]
+pub fn decode_to_fastq_parallel<W: Write + Send + 'static>(
+    reader: MmapReader,
+    writer: W,
+    options: &FastqDecodeOptions,
+) -> Result<u64> {
+    let writer = Arc::new(Mutex::new(writer));
+    let decoder = FastqDecoder {
+        default_quality: options.default_quality,
+        homopolymer: reader.header().is_homopolymer(),
+        buffer: Vec::new(),
+        dbuf: Vec::new(),
+        ebuf: Vec::new(),
+        qbuf: Vec::new(),
+        local_records: 0,
+        writer: Arc::clone(&writer),
+        n_records: Arc::new(AtomicU64::new(0)),
+    };
+    reader.process_parallel(decoder.clone(), options.n_threads)?;
+    writer
+        .lock()
+        .map_err(|_| anyhow::anyhow!("FASTQ writer was poisoned"))?
+        .flush()?;
+    Ok(decoder.n_records.load(Ordering::Relaxed))
+}
+
+/// Parallel processor writing records as FASTQ
+#[cfg(feature = "mmap")]
+struct FastqDecoder<W: Write> {
+    default_quality: u8,
+
+    /// Whether sequences are homopolymer-compressed and need to be expanded
+    homopolymer: bool,
+
+    /// Thread-local buffers
+    buffer: Vec<u8>,
+    dbuf: Vec<u8>,
+    ebuf: Vec<u8>,
+    qbuf: Vec<u8>,
+    local_records: u64,
+
+    /// Shared state
+    writer: Arc<Mutex<W>>,
+    n_records: Arc<AtomicU64>,
+}
+#[cfg(feature = "mmap")]
+impl<W: Write> Clone for FastqDecoder<W> {
+    fn clone(&self) -> Self {
+        Self {
+            default_quality: self.default_quality,
+            homopolymer: self.homopolymer,
+            buffer: Vec::new(),
+            dbuf: Vec::new(),
+            ebuf: Vec::new(),
+            qbuf: Vec::new(),
+            local_records: 0,
+            writer: Arc::clone(&self.writer),
+            n_records: Arc::clone(&self.n_records),
+        }
+    }
+}
+#[cfg(feature = "mmap")]
+impl<W: Write> FastqDecoder<W> {
+    /// Formats a sequence as a FASTQ entry in the local buffer
+    ///
+    /// The bytes are the quality scores, or the run lengths of homopolymer-compressed
+    /// sequences (which are expanded and written without quality scores).
+    fn write_entry(&mut self, name: std::fmt::Arguments, bytes: &[u8]) -> Result<()> {
+        let quality = if self.homopolymer {
+            self.ebuf.clear();
+            expand(&self.dbuf, bytes, &mut self.ebuf)?;
+            std::mem::swap(&mut self.dbuf, &mut self.ebuf);
+            &[]
+        } else {
+            bytes
+        };
+        let quality = if quality.is_empty() {
+            self.qbuf.clear();
+            self.qbuf.resize(self.dbuf.len(), self.default_quality);
+            self.qbuf.as_slice()
+        } else {
+            quality
+        };
+        writeln!(self.buffer, "@{name}")?;
+        self.buffer.extend_from_slice(&self.dbuf);
+        self.buffer.extend_from_slice(b"\n+\n");
+        self.buffer.extend_from_slice(quality);
+        self.buffer.push(b'\n');
+        Ok(())
+    }
+}
+#[cfg(feature = "mmap")]
+impl<W: Write + Send + 'static> ParallelProcessor for FastqDecoder<W> {
+    fn process_record(&mut self, record: RefRecord) -> Result<()> {
+        let index = record.index();
+        let n_segments = record.n_segments();
+        for segment in 0..n_segments {
+            self.dbuf.clear();
+            record.decode_segment(segment, &mut self.dbuf)?;
+            let quality = record.segment_qual(segment);
+            if n_segments > 1 {
+                self.write_entry(format_args!("{index}/{}", segment + 1), quality)?;
+            } else {
+                self.write_entry(format_args!("{index}"), quality)?;
+            }
+        }
+        self.local_records += 1;
+        Ok(())
+    }
+
+    fn on_batch_complete(&mut self) -> Result<()> {
+        self.writer
+            .lock()
+            .map_err(|_| anyhow::anyhow!("FASTQ writer was poisoned"))?
+            .write_all(&self.buffer)?;
+        self.n_records
+            .fetch_add(self.local_records, Ordering::Relaxed);
+        self.buffer.clear();
+        self.local_records = 0;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MemoryReader;
+
+    #[test]
+    fn test_fasta_to_vbq() -> Result<()> {
+        let contig = b"ACGTNacgtGGCCTTAA".repeat(40);
+        let mut fasta = b">chr1 description\n".to_vec();
+        for line in contig.chunks(60) {
+            fasta.extend_from_slice(line);
+            fasta.extend_from_slice(b"\r\n");
+        }
+        fasta.extend_from_slice(b">empty\n>short\nACGT\n\nTTTT\n");
+
+        let path = std::env::temp_dir().join(format!("vbq_fasta_{}.vbq", std::process::id()));
+        let options = FastaOptions {
+            block_size: 128,
+            policy: Policy::SetToA,
+            ..Default::default()
+        };
+        let stats = fasta_to_vbq(&fasta[..], std::fs::File::create(&path)?, &options)?;
+        assert_eq!(stats.n_sequences, 3);
+        assert_eq!(stats.n_skipped, 1);
+
+        // Reassemble the fragments of each entry by their flag
+        let mut sequences: Vec<Vec<u8>> = vec![Vec::new(); 3];
+        let mut reader = MemoryReader::new(std::fs::read(&path)?)?;
+        let mut block = reader.new_block();
+        let mut dbuf = Vec::new();
+        let mut n_records = 0;
+        while reader.read_block_into(&mut block)? {
+            for record in block.iter() {
+                dbuf.clear();
+                record.decode_s(&mut dbuf)?;
+                assert!(dbuf.len() <= options.max_fragment_len());
+                sequences[record.flag() as usize].extend_from_slice(&dbuf);
+                n_records += 1;
+            }
+        }
+        assert_eq!(n_records, stats.n_records);
+        let expected: Vec<u8> = contig
+            .to_ascii_uppercase()
+            .iter()
+            .map(|&n| if n == b'N' { b'A' } else { n })
+            .collect();
+        assert_eq!(sequences[0], expected);
+        assert!(sequences[1].is_empty());
+        assert_eq!(sequences[2], b"ACGTTTTT");
+
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(all(feature = "seq_io", feature = "mmap"))]
+    fn test_fastq_round_trip() -> Result<()> {
+        use crate::Error;
+
+        let fastq = b"@a/1\nACGTN\n+\nIIII#\n@a/2\nTTGG\n+\nFFFF\n\
+                      @b/1\n\n+\n\n@b/2\nAC\n+\nII\n@c/1\nGGG\n+\n!!!\n@c/2\nCA\n+\n##\n";
+        let path = std::env::temp_dir().join(format!("vbq_fastq_{}.vbq", std::process::id()));
+        let options = FastqOptions {
+            block_size: 128,
+            interleaved: true,
+            policy: Policy::SetToA,
+            ..Default::default()
+        };
+        let stats = encode_fastq(&fastq[..], std::fs::File::create(&path)?, &options)?;
+        assert_eq!(stats.n_sequences, 6);
+        assert_eq!(stats.n_records, 2);
+        assert_eq!(stats.n_skipped, 1);
+
+        // An odd number of interleaved records is missing a mate
+        let err = encode_fastq(&fastq[..19], std::io::sink(), &options).unwrap_err();
+        assert!(matches!(
+            err.root(),
+            Error::ReadError(ReadError::InvalidMatePair(0))
+        ));
+
+        let out_path = path.with_extension("fq");
+        let decode_options = FastqDecodeOptions {
+            n_threads: 2,
+            ..Default::default()
+        };
+        let reader = MmapReader::new(&path)?;
+        let output = std::fs::File::create(&out_path)?;
+        assert_eq!(
+            decode_to_fastq_parallel(reader, output, &decode_options)?,
+            2
+        );
+
+        // Both records fit into a single block, so they are written in order
+        let decoded = std::fs::read_to_string(&out_path)?;
+        assert_eq!(
+            decoded,
+            "@0/1\nACGTA\n+\nIIII#\n@0/2\nTTGG\n+\nFFFF\n@1/1\nGGG\n+\n!!!\n@1/2\nCA\n+\n##\n"
+        );
+
+        std::fs::remove_file(&path)?;
+        std::fs::remove_file(&out_path)?;
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "mmap")]
+    fn test_decode_segments() -> Result<()> {
+        use crate::{VBinseqHeader, VBinseqWriterBuilder};
+
+        let path = std::env::temp_dir().join(format!("vbq_fastq_seg_{}.vbq", std::process::id()));
+        let mut header = VBinseqHeader::new(true, false, false);
+        header.set_segments(3)?;
+        let mut writer = VBinseqWriterBuilder::default()
+            .header(header)
+            .build(std::fs::File::create(&path)?)?;
+        let sequences: [&[u8]; 3] = [b"ACGT", b"TTG", b"GGCCA"];
+        let qualities: [&[u8]; 3] = [b"IIII", b"FFF", b"#####"];
+        writer.write_segments(0, &sequences, &qualities)?;
+        writer.finish()?;
+        drop(writer);
+
+        // Every segment is written as a mate
+        let out_path = path.with_extension("fq");
+        let reader = MmapReader::new(&path)?;
+        let output = std::fs::File::create(&out_path)?;
+        decode_to_fastq_parallel(reader, output, &FastqDecodeOptions::default())?;
+        assert_eq!(
+            std::fs::read_to_string(&out_path)?,
+            "@0/1\nACGT\n+\nIIII\n@0/2\nTTG\n+\nFFF\n@0/3\nGGCCA\n+\n#####\n"
+        );
+
+        std::fs::remove_file(&path)?;
+        std::fs::remove_file(&out_path)?;
+        Ok(())
+    }
+}
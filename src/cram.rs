@@ -0,0 +1,476 @@
+//! # CRAM Interoperability
+//!
+//! This module converts between CRAM files and VBINSEQ files, allowing archives to migrate
+//! between the two formats without a lossy round trip through FASTQ. It requires the
+//! `cram` feature.
+//!
+//! ## Import
+//!
+//! `cram_to_vbq` stores every primary record of a CRAM file as a VBINSEQ record:
+//!
+//! * The SAM flags of the record are stored in the VBINSEQ flag
+//! * Reads stored reverse complemented are restored to their original orientation
+//! * Secondary and supplementary records are skipped, as their reads are already stored
+//!   by the primary record
+//! * Paired files expect mates as adjacent records (first segment followed by last segment)
+//!
+//! Read names are not stored, and records of aligned CRAM files are imported without
+//! their alignments. Aligned CRAM files that reference external sequences cannot be read.
+//!
+//! ## Export
+//!
+//! `vbq_to_cram` writes every VBINSEQ record as an unmapped CRAM record named by its
+//! record index. The segments of paired records (and records of more segments) are
+//! written as adjacent mates, where segments between the first and the last one have both
+//! segment flags (as in SAM). Homopolymer-compressed
+//! sequences are expanded. Reads without quality scores are written with missing quality
+//! scores (`0xFF`, as in BAM files), which `cram_to_vbq` imports as scores of 0.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use std::fs::File;
+//! use vbinseq::cram::{cram_to_vbq, vbq_to_cram, CramExportOptions, CramImportOptions};
+//!
+//! // Import unaligned reads from a CRAM file
+//! let input = File::open("reads.cram").unwrap();
+//! let output = File::create("reads.vbq").unwrap();
+//! cram_to_vbq(input, output, &CramImportOptions::default()).unwrap();
+//!
+//! // And export them back
+//! let output = File::create("roundtrip.cram").unwrap();
+//! vbq_to_cram("reads.vbq", output, &CramExportOptions::default()).unwrap();
+//! ```
+
+use std::io::{Read, Write};
+use std::path::Path;
+
+use noodles_cram as cram;
+use noodles_sam::{
+    self as sam,
+    alignment::{
+        io::Write as _,
+        record::Flags,
+        record_buf::{QualityScores, Sequence},
+        RecordBuf,
+    },
+};
+
+use crate::convert::ConvertStats;
+use crate::error::{ReadError, Result};
+use crate::header::BLOCK_SIZE;
+use crate::{MmapReader, OwnedRecord, Policy, VBinseqHeader, VBinseqWriter, VBinseqWriterBuilder};
+
+/// Offset of Phred scores in ASCII-encoded quality scores
+const PHRED_OFFSET: u8 = 33;
+
+/// Quality score of reads without quality scores (as in BAM files)
+const MISSING_QUALITY: u8 = 0xFF;
+
+/// Flags describing the alignment of a record, which do not apply to exported records
+const ALIGNMENT_FLAGS: Flags = Flags::PROPERLY_SEGMENTED
+    .union(Flags::REVERSE_COMPLEMENTED)
+    .union(Flags::MATE_REVERSE_COMPLEMENTED)
+    .union(Flags::SECONDARY)
+    .union(Flags::SUPPLEMENTARY);
+
+/// Flags describing the segments of a record, which are set from the pairing of exported records
+const SEGMENT_FLAGS: Flags = Flags::SEGMENTED
+    .union(Flags::UNMAPPED)
+    .union(Flags::MATE_UNMAPPED)
+    .union(Flags::FIRST_SEGMENT)
+    .union(Flags::LAST_SEGMENT);
+
+/// Options for importing CRAM files
+#[derive(Debug, Clone, Copy)]
+pub struct CramImportOptions {
+    /// Virtual block size of the output file in bytes
+    pub block_size: u64,
+
+    /// Whether the blocks of the output file are ZSTD compressed
+    pub compressed: bool,
+
+    /// Whether quality scores are stored
+    ///
+    /// Records without quality scores are stored with the lowest quality score (`!`).
+    pub quality: bool,
+
+    /// Whether adjacent mates are stored as paired records
+    pub paired: bool,
+
+    /// Policy for handling invalid nucleotides
+    pub policy: Policy,
+}
+impl Default for CramImportOptions {
+    /// Creates options for single-end reads with quality scores and compression
+    fn default() -> Self {
+        Self {
+            block_size: BLOCK_SIZE,
+            compressed: true,
+            quality: true,
+            paired: false,
+            policy: Policy::default(),
+        }
+    }
+}
+
+/// Options for exporting CRAM files
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CramExportOptions {
+    /// Whether the VBINSEQ flags are written as SAM flags
+    ///
+    /// This restores the flags of files imported with `cram_to_vbq` (e.g. QC failures and
+    /// duplicates). Alignment and segment flags are always derived from the exported record.
+    pub preserve_flags: bool,
+}
+
+/// A read of a CRAM record in its original orientation
+struct CramRead {
+    flags: Flags,
+    sequence: Vec<u8>,
+    quality: Vec<u8>,
+}
+impl CramRead {
+    fn from_record(record: &RecordBuf) -> Self {
+        let flags = record.flags();
+        let mut sequence = record.sequence().as_ref().to_vec();
+        let scores = record.quality_scores().as_ref();
+        let mut quality: Vec<u8> = scores
+            .iter()
+            .map(|q| q.saturating_add(PHRED_OFFSET))
+            .collect();
+        if quality.len() != sequence.len() || scores.iter().all(|&q| q == MISSING_QUALITY) {
+            quality = vec![PHRED_OFFSET; sequence.len()];
+        }
+        if flags.is_reverse_complemented() {
+            sequence.reverse();
+            sequence.iter_mut().for_each(|n| *n = complement(*n));
+            quality.reverse();
+        }
+        Self {
+            flags,
+            sequence,
+            quality,
+        }
+    }
+}
+
+/// Returns the complement of a nucleotide (unknown nucleotides are kept as-is)
+fn complement(nucleotide: u8) -> u8 {
+    match nucleotide {
+        b'A' => b'T',
+        b'C' => b'G',
+        b'G' => b'C',
+        b'T' => b'A',
+        b'a' => b't',
+        b'c' => b'g',
+        b'g' => b'c',
+        b't' => b'a',
+        other => other,
+    }
+}
+
+/// Converts the records of a CRAM file into a VBINSEQ file
+///
+/// # Parameters
+///
+/// * `reader` - The source of the CRAM file
+/// * `writer` - The destination of the VBINSEQ file
+/// * `options` - Options of the conversion
+///
+/// # Returns
+///
+/// * `Ok(ConvertStats)` - The statistics of the conversion (`n_sequences` counts the
+///   imported CRAM records)
+///
+/// # Errors
+///
+/// * `ReadError::InvalidMatePair` - If a paired import encounters a record without its mate
+/// * Any error raised by the writer
+/// * I/O errors from reading the CRAM file or writing the VBINSEQ file
+pub fn cram_to_vbq<R: Read, W: Write>(
+    reader: R,
+    writer: W,
+    options: &CramImportOptions,
+) -> Result<ConvertStats> {
+    let mut reader = cram::io::reader::Builder::default().build_from_reader(reader);
+    let sam_header = reader.read_header()?;
+
+    let header = VBinseqHeader::with_capacity(
+        options.block_size,
+        options.quality,
+        options.compressed,
+        options.paired,
+    );
+    let mut writer = VBinseqWriterBuilder::default()
+        .header(header)
+        .policy(options.policy)
+        .build(writer)?;
+
+    let mut stats = ConvertStats::default();
+    let mut first_mate: Option<CramRead> = None;
+    for result in reader.records(&sam_header) {
+        let record = result?;
+        let flags = record.flags();
+        if flags.is_secondary() || flags.is_supplementary() {
+            continue;
+        }
+        stats.n_sequences += 1;
+        let read = CramRead::from_record(&record);
+
+        if !options.paired {
+            let written = write_single(&mut writer, &read, options.quality)?;
+            stats.record(written);
+            continue;
+        }
+        match first_mate.take() {
+            None if flags.is_first_segment() => first_mate = Some(read),
+            Some(first) if flags.is_last_segment() => {
+                let written = write_paired(&mut writer, &first, &read, options.quality)?;
+                stats.record(written);
+            }
+            _ => return Err(ReadError::InvalidMatePair(stats.n_sequences - 1).into()),
+        }
+    }
+    if first_mate.is_some() {
+        return Err(ReadError::InvalidMatePair(stats.n_sequences - 1).into());
+    }
+    writer.finish()?;
+    Ok(stats)
+}
+
+/// Writes a single read as a record
+fn write_single<W: Write>(
+    writer: &mut VBinseqWriter<W>,
+    read: &CramRead,
+    quality: bool,
+) -> Result<bool> {
+    let flag = u64::from(read.flags.bits());
+    if quality {
+        writer.write_nucleotides_quality(flag, &read.sequence, &read.quality)
+    } else {
+        writer.write_nucleotides(flag, &read.sequence)
+    }
+}
+
+/// Writes a pair of mates as a paired record (using the flags of the first mate)
+fn write_paired<W: Write>(
+    writer: &mut VBinseqWriter<W>,
+    first: &CramRead,
+    last: &CramRead,
+    quality: bool,
+) -> Result<bool> {
+    let flag = u64::from(first.flags.bits());
+    if quality {
+        writer.write_nucleotides_quality_paired(
+            flag,
+            &first.sequence,
+            &last.sequence,
+            &first.quality,
+            &last.quality,
+        )
+    } else {
+        writer.write_nucleotides_paired(flag, &first.sequence, &last.sequence)
+    }
+}
+
+/// Converts the records of a VBINSEQ file into an unmapped CRAM file
+///
+/// # Parameters
+///
+/// * `path` - Path to the VBINSEQ file
+/// * `writer` - The destination of the CRAM file
+/// * `options` - Options of the conversion
+///
+/// # Returns
+///
+/// * `Ok(u64)` - The number of CRAM records written
+///
+/// # Errors
+///
+/// * Any error raised while reading or decoding the VBINSEQ file
+/// * I/O errors from writing the CRAM file
+pub fn vbq_to_cram<P: AsRef<Path>, W: Write>(
+    path: P,
+    writer: W,
+    options: &CramExportOptions,
+) -> Result<u64> {
+    let mut reader = MmapReader::new(path)?;
+    let segments = reader.header().segments();
+
+    let sam_header = sam::Header::default();
+    let mut writer = cram::io::writer::Builder::default().build_from_writer(writer);
+    writer.write_header(&sam_header)?;
+
+    let mut block = reader.new_block();
+    let mut record = OwnedRecord::default();
+    let mut n_records = 0;
+    while reader.read_block_into(&mut block)? {
+        for ref_record in block.iter() {
+            record.fill(&ref_record)?;
+            record.expand_homopolymers()?;
+            let base = if options.preserve_flags {
+                Flags::from_bits_truncate(record.flag() as u16) - ALIGNMENT_FLAGS - SEGMENT_FLAGS
+            } else {
+                Flags::empty()
+            } | Flags::UNMAPPED;
+            let name = record.index().to_string();
+
+            if segments == 1 {
+                let cram_record = build_record(&name, base, record.seq(), record.squal());
+                writer.write_alignment_record(&sam_header, &cram_record)?;
+                n_records += 1;
+                continue;
+            }
+
+            let base = base | Flags::SEGMENTED | Flags::MATE_UNMAPPED;
+            for segment in 0..segments {
+                let flags = match segment {
+                    0 => base | Flags::FIRST_SEGMENT,
+                    _ if segment == segments - 1 => base | Flags::LAST_SEGMENT,
+                    _ => base | Flags::FIRST_SEGMENT | Flags::LAST_SEGMENT,
+                };
+                let (sequence, quality) = (record.segment(segment), record.segment_qual(segment));
+                let cram_record = build_record(&name, flags, sequence, quality);
+                writer.write_alignment_record(&sam_header, &cram_record)?;
+                n_records += 1;
+            }
+        }
+    }
+    writer.try_finish(&sam_header)?;
+    Ok(n_records)
+}
+
+/// Builds an unmapped CRAM record
+///
+/// Reads without quality scores are given `MISSING_QUALITY` scores.
+fn build_record(name: &str, flags: Flags, sequence: &[u8], quality: &[u8]) -> RecordBuf {
+    let scores = if quality.is_empty() {
+        vec![MISSING_QUALITY; sequence.len()]
+    } else {
+        quality
+            .iter()
+            .map(|q| q.saturating_sub(PHRED_OFFSET))
+            .collect()
+    };
+    RecordBuf::builder()
+        .set_name(name)
+        .set_flags(flags)
+        .set_sequence(Sequence::from(sequence))
+        .set_quality_scores(QualityScores::from(scores))
+        .build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conformance::TestVector;
+
+    #[test]
+    fn test_cram_round_trip() -> Result<()> {
+        let dir = std::env::temp_dir();
+        let pid = std::process::id();
+        let source = dir.join(format!("vbq_cram_source_{pid}.vbq"));
+        let target = dir.join(format!("vbq_cram_target_{pid}.vbq"));
+
+        let vector = TestVector::generate(true, true, true);
+        vector.write_vbq(std::fs::File::create(&source)?)?;
+
+        let mut cram_bytes = Vec::new();
+        let options = CramExportOptions::default();
+        let n_records = vbq_to_cram(&source, &mut cram_bytes, &options)?;
+        assert_eq!(n_records, 2 * vector.records.len() as u64);
+
+        let options = CramImportOptions {
+            paired: true,
+            ..Default::default()
+        };
+        let stats = cram_to_vbq(&cram_bytes[..], std::fs::File::create(&target)?, &options)?;
+        assert_eq!(stats.n_records, vector.records.len() as u64);
+
+        let mut reader = MmapReader::new(&target)?;
+        let mut block = reader.new_block();
+        let mut expected = vector.records.iter();
+        let mut sbuf = Vec::new();
+        let mut xbuf = Vec::new();
+        while reader.read_block_into(&mut block)? {
+            for record in block.iter() {
+                let expected = expected.next().unwrap();
+                sbuf.clear();
+                xbuf.clear();
+                record.decode_s(&mut sbuf)?;
+                record.decode_x(&mut xbuf)?;
+                assert_eq!(sbuf, expected.sequence);
+                assert_eq!(xbuf, expected.extended);
+                assert_eq!(record.squal(), expected.quality);
+                assert_eq!(record.xqual(), expected.extended_quality);
+            }
+        }
+        assert!(expected.next().is_none());
+
+        std::fs::remove_file(&source)?;
+        std::fs::remove_file(&target)?;
+        Ok(())
+    }
+
+    /// Reads all records of a CRAM file
+    fn read_cram(bytes: &[u8]) -> Result<Vec<RecordBuf>> {
+        let mut reader = cram::io::reader::Builder::default().build_from_reader(bytes);
+        let sam_header = reader.read_header()?;
+        let records = reader
+            .records(&sam_header)
+            .collect::<std::io::Result<_>>()?;
+        Ok(records)
+    }
+
+    #[test]
+    fn test_homopolymer_export() -> Result<()> {
+        let path = std::env::temp_dir().join(format!("vbq_cram_hp_{}.vbq", std::process::id()));
+        let vector = TestVector::extension(crate::header::FLAG_HOMOPOLYMER);
+        vector.write_vbq(std::fs::File::create(&path)?)?;
+
+        let mut cram_bytes = Vec::new();
+        vbq_to_cram(&path, &mut cram_bytes, &CramExportOptions::default())?;
+        let records = read_cram(&cram_bytes)?;
+        assert_eq!(records.len(), 2 * vector.records.len());
+        for (mates, expected) in records.chunks(2).zip(&vector.records) {
+            assert_eq!(mates[0].sequence().as_ref(), expected.sequence);
+            assert_eq!(mates[1].sequence().as_ref(), expected.extended);
+            for mate in mates {
+                let scores = mate.quality_scores().as_ref();
+                assert!(scores.iter().all(|&q| q == MISSING_QUALITY));
+            }
+        }
+
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_segments_export() -> Result<()> {
+        let path = std::env::temp_dir().join(format!("vbq_cram_seg_{}.vbq", std::process::id()));
+        let vector = TestVector::extension(crate::header::FLAG_SEGMENTS);
+        vector.write_vbq(std::fs::File::create(&path)?)?;
+        let segments = vector.header.segments();
+        assert!(segments > 2);
+
+        let mut cram_bytes = Vec::new();
+        let n_records = vbq_to_cram(&path, &mut cram_bytes, &CramExportOptions::default())?;
+        assert_eq!(n_records, (segments * vector.records.len()) as u64);
+        let records = read_cram(&cram_bytes)?;
+        for (mates, expected) in records.chunks(segments).zip(&vector.records) {
+            let reads = [&expected.sequence, &expected.extended]
+                .into_iter()
+                .chain(expected.extra_segments.iter().map(|(sequence, _)| sequence));
+            for (segment, (mate, read)) in mates.iter().zip(reads).enumerate() {
+                assert_eq!(mate.sequence().as_ref(), read.as_slice());
+                let flags = mate.flags();
+                assert_eq!(flags.is_first_segment(), segment < segments - 1);
+                assert_eq!(flags.is_last_segment(), segment > 0);
+            }
+        }
+
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+}
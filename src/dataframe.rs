@@ -0,0 +1,282 @@
+//! # Polars DataFrame Conversion
+//!
+//! This module converts record blocks and files into Polars DataFrames for exploratory
+//! analysis (e.g. in notebooks). It requires the `polars` feature.
+//!
+//! Each row describes one record with the following columns:
+//!
+//! | Column         | Type    | Description                                                  |
+//! |----------------|---------|--------------------------------------------------------------|
+//! | `index`        | u64     | Global index of the record in the file                       |
+//! | `flag`         | u64     | Flag of the record                                           |
+//! | `slen`         | u64     | Length of the primary sequence                               |
+//! | `xlen`         | u64     | Length of the extended sequence (0 if not paired)            |
+//! | `gc`           | f64     | GC fraction of all segments                                  |
+//! | `mean_quality` | f64     | Mean Phred quality score (null without quality scores)       |
+//! | `sequence`     | str     | Primary sequence (only with `DataFrameOptions::sequences`)   |
+//! | `extended`     | str     | Extended sequence (only with `DataFrameOptions::sequences`)  |
+//! | `extra`        | str     | Further segments (only with `DataFrameOptions::sequences`)   |
+//!
+//! The mean quality covers all segments. The `extra` column holds the segments after the
+//! extended sequence separated by spaces, and is empty unless records have more than two
+//! segments.
+//!
+//! Homopolymer-compressed sequences are expanded, so lengths and sequences describe the
+//! original reads. Their run lengths are not quality scores, so their mean quality is null.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use vbinseq::dataframe::{file_to_dataframe, DataFrameOptions};
+//!
+//! let df = file_to_dataframe("example.vbq", &DataFrameOptions::default()).unwrap();
+//! println!("{df}");
+//! ```
+
+use std::path::Path;
+
+use polars::prelude::{Column, DataFrame};
+
+use crate::reader::RecordBlock;
+use crate::{MmapReader, OwnedRecord, RefRecord, Result};
+
+/// Offset of Phred scores in ASCII-encoded quality scores
+const PHRED_OFFSET: u8 = 33;
+
+/// Options for converting records into DataFrames
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DataFrameOptions {
+    /// Whether the decoded sequences are included as string columns
+    pub sequences: bool,
+}
+
+/// Accumulates the columns of a DataFrame record by record
+#[derive(Default)]
+struct ColumnBuilder {
+    index: Vec<u64>,
+    flag: Vec<u64>,
+    slen: Vec<u64>,
+    xlen: Vec<u64>,
+    gc: Vec<f64>,
+    mean_quality: Vec<Option<f64>>,
+    sequence: Vec<String>,
+    extended: Vec<String>,
+    extra: Vec<String>,
+    record: OwnedRecord,
+}
+impl ColumnBuilder {
+    fn push(&mut self, record: &RefRecord, options: &DataFrameOptions) -> Result<()> {
+        self.record.fill(record)?;
+        self.record.expand_homopolymers()?;
+        let record = &self.record;
+
+        let (mut n_bases, mut n_gc, mut n_quality, mut quality_sum) = (0, 0, 0, 0);
+        for segment in 0..record.n_segments() {
+            let sequence = record.segment(segment);
+            n_bases += sequence.len();
+            n_gc += sequence.iter().filter(|&&n| n == b'G' || n == b'C').count();
+            let quality = record.segment_qual(segment);
+            n_quality += quality.len();
+            quality_sum += quality
+                .iter()
+                .map(|&q| u64::from(q.saturating_sub(PHRED_OFFSET)))
+                .sum::<u64>();
+        }
+
+        self.index.push(record.index());
+        self.flag.push(record.flag());
+        self.slen.push(record.seq().len() as u64);
+        self.xlen.push(record.xseq().len() as u64);
+        self.gc.push(ratio(n_gc as u64, n_bases));
+        self.mean_quality.push(if record.has_quality() {
+            Some(ratio(quality_sum, n_quality))
+        } else {
+            None
+        });
+        if options.sequences {
+            self.sequence
+                .push(String::from_utf8_lossy(record.seq()).into_owned());
+            self.extended
+                .push(String::from_utf8_lossy(record.xseq()).into_owned());
+            let extra: Vec<_> = (2..record.n_segments())
+                .map(|segment| String::from_utf8_lossy(record.segment(segment)))
+                .collect();
+            self.extra.push(extra.join(" "));
+        }
+        Ok(())
+    }
+
+    fn push_block(&mut self, block: &RecordBlock, options: &DataFrameOptions) -> Result<()> {
+        for record in block.iter() {
+            self.push(&record, options)?;
+        }
+        Ok(())
+    }
+
+    fn build(self, options: &DataFrameOptions) -> Result<DataFrame> {
+        let mut columns = vec![
+            Column::new("index".into(), self.index),
+            Column::new("flag".into(), self.flag),
+            Column::new("slen".into(), self.slen),
+            Column::new("xlen".into(), self.xlen),
+            Column::new("gc".into(), self.gc),
+            Column::new("mean_quality".into(), self.mean_quality),
+        ];
+        if options.sequences {
+            columns.push(Column::new("sequence".into(), self.sequence));
+            columns.push(Column::new("extended".into(), self.extended));
+            columns.push(Column::new("extra".into(), self.extra));
+        }
+        Ok(DataFrame::new(columns)?)
+    }
+}
+
+/// Returns `numerator / denominator`, or 0 for an empty denominator
+fn ratio(numerator: u64, denominator: usize) -> f64 {
+    if denominator == 0 {
+        0.0
+    } else {
+        numerator as f64 / denominator as f64
+    }
+}
+
+/// Converts the records of a block into a DataFrame
+///
+/// # Parameters
+///
+/// * `block` - The block of records to convert
+/// * `options` - Options of the conversion
+///
+/// # Returns
+///
+/// * `Ok(DataFrame)` - A DataFrame with one row per record of the block
+/// * `Err(_)` - If a sequence could not be decoded
+pub fn block_to_dataframe(block: &RecordBlock, options: &DataFrameOptions) -> Result<DataFrame> {
+    let mut builder = ColumnBuilder::default();
+    builder.push_block(block, options)?;
+    builder.build(options)
+}
+
+/// Converts all records of a VBINSEQ file into a DataFrame
+///
+/// # Parameters
+///
+/// * `path` - Path to the VBINSEQ file
+/// * `options` - Options of the conversion
+///
+/// # Returns
+///
+/// * `Ok(DataFrame)` - A DataFrame with one row per record of the file
+/// * `Err(_)` - If the file could not be read or a sequence could not be decoded
+pub fn file_to_dataframe<P: AsRef<Path>>(path: P, options: &DataFrameOptions) -> Result<DataFrame> {
+    let mut reader = MmapReader::new(path)?;
+    let mut block = reader.new_block();
+    let mut builder = ColumnBuilder::default();
+    while reader.read_block_into(&mut block)? {
+        builder.push_block(&block, options)?;
+    }
+    builder.build(options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conformance::TestVector;
+
+    #[test]
+    fn test_file_to_dataframe() -> Result<()> {
+        let path = std::env::temp_dir().join(format!("vbq_dataframe_{}.vbq", std::process::id()));
+        let vector = TestVector::generate(true, false, true);
+        vector.write_vbq(std::fs::File::create(&path)?)?;
+
+        let options = DataFrameOptions { sequences: true };
+        let df = file_to_dataframe(&path, &options)?;
+        assert_eq!(df.height(), vector.records.len());
+        assert_eq!(df.width(), 9);
+
+        let first = &vector.records[0];
+        let sequence = df
+            .column("sequence")
+            .unwrap()
+            .str()
+            .unwrap()
+            .get(0)
+            .unwrap();
+        assert_eq!(sequence.as_bytes(), first.sequence);
+        let gc = df.column("gc").unwrap().f64().unwrap().get(0).unwrap();
+        let n_gc = first
+            .sequence
+            .iter()
+            .chain(&first.extended)
+            .filter(|&&n| n == b'G' || n == b'C')
+            .count();
+        let n_bases = first.sequence.len() + first.extended.len();
+        assert!((gc - n_gc as f64 / n_bases as f64).abs() < 1e-12);
+
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_segments_dataframe() -> Result<()> {
+        let path =
+            std::env::temp_dir().join(format!("vbq_dataframe_seg_{}.vbq", std::process::id()));
+        let vector = TestVector::extension(crate::header::FLAG_SEGMENTS);
+        vector.write_vbq(std::fs::File::create(&path)?)?;
+
+        let df = file_to_dataframe(&path, &DataFrameOptions { sequences: true })?;
+        assert_eq!(df.height(), vector.records.len());
+        let extra = df.column("extra").unwrap().str().unwrap();
+        let gc = df.column("gc").unwrap().f64().unwrap();
+        let mean_quality = df.column("mean_quality").unwrap().f64().unwrap();
+        for (i, record) in vector.records.iter().enumerate() {
+            let segments: Vec<_> = record.extra_segments.iter().map(|(s, _)| s).collect();
+            let expected: Vec<_> = segments
+                .iter()
+                .map(|s| String::from_utf8_lossy(s))
+                .collect();
+            assert_eq!(extra.get(i), Some(expected.join(" ").as_str()));
+
+            // GC fraction and mean quality cover every segment
+            let sequences = [&record.sequence, &record.extended]
+                .into_iter()
+                .chain(segments);
+            let qualities = [&record.quality, &record.extended_quality]
+                .into_iter()
+                .chain(record.extra_segments.iter().map(|(_, q)| q));
+            let bases: Vec<u8> = sequences.flatten().copied().collect();
+            let scores: Vec<u8> = qualities.flatten().copied().collect();
+            let n_gc = bases.iter().filter(|&&n| n == b'G' || n == b'C').count();
+            let sum: u64 = scores.iter().map(|&q| u64::from(q - PHRED_OFFSET)).sum();
+            assert!((gc.get(i).unwrap() - n_gc as f64 / bases.len() as f64).abs() < 1e-12);
+            let mean = sum as f64 / scores.len() as f64;
+            assert!((mean_quality.get(i).unwrap() - mean).abs() < 1e-12);
+        }
+
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_homopolymer_dataframe() -> Result<()> {
+        let path =
+            std::env::temp_dir().join(format!("vbq_dataframe_hp_{}.vbq", std::process::id()));
+        let vector = TestVector::extension(crate::header::FLAG_HOMOPOLYMER);
+        vector.write_vbq(std::fs::File::create(&path)?)?;
+
+        let df = file_to_dataframe(&path, &DataFrameOptions { sequences: true })?;
+        assert_eq!(df.height(), vector.records.len());
+        let sequences = df.column("sequence").unwrap().str().unwrap();
+        let extended = df.column("extended").unwrap().str().unwrap();
+        let slen = df.column("slen").unwrap().u64().unwrap();
+        for (i, record) in vector.records.iter().enumerate() {
+            assert_eq!(sequences.get(i).unwrap().as_bytes(), record.sequence);
+            assert_eq!(extended.get(i).unwrap().as_bytes(), record.extended);
+            assert_eq!(slen.get(i), Some(record.sequence.len() as u64));
+        }
+        assert_eq!(df.column("mean_quality").unwrap().null_count(), df.height());
+
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+}
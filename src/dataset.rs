@@ -0,0 +1,710 @@
+//! # Datasets of Many Shards
+//!
+//! This module groups many VBINSEQ files (shards) into a single logical dataset.
+//!
+//! A `Manifest` lists the shards of a dataset with their record counts and content digests
+//! (see `footer::content_digest`), along with free-form key/value metadata. Manifests are
+//! stored as text with one tab-separated entry per line:
+//!
+//! | Line                                      | Description                                |
+//! |-------------------------------------------|--------------------------------------------|
+//! | `vbq-manifest`, `1`                       | Format identifier and version (first line) |
+//! | `meta`, key, value                        | Metadata entry                             |
+//! | `shard`, path, record count, digest (hex) | Shard of the dataset                       |
+//!
+//! Relative shard paths are resolved against the directory of the manifest.
+//!
+//! A `Dataset` presents the shards of a manifest as one collection: records are numbered
+//! with global indices across all shards (in manifest order), blocks can be read
+//! sequentially with a `DatasetReader`, and all records can be processed in parallel.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use vbinseq::dataset::{Dataset, Manifest};
+//!
+//! // Describe the shards in a manifest
+//! let mut manifest = Manifest::from_shards(["part_0.vbq", "part_1.vbq"]).unwrap();
+//! manifest.set_metadata("sample", "S1").unwrap();
+//! manifest.save_to_path("dataset.vbqm").unwrap();
+//!
+//! // Read all shards as one collection
+//! let dataset = Dataset::open("dataset.vbqm").unwrap();
+//! let mut reader = dataset.reader();
+//! let mut block = reader.new_block();
+//! while reader.read_block_into(&mut block).unwrap() {
+//!     for record in block.iter() {
+//!         println!("Record {}", record.index());
+//!     }
+//! }
+//! ```
+
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::dictionary;
+use crate::error::{DatasetError, ReadError, Result};
+use crate::footer::compute_footer;
+use crate::header::{BLOCK_SIZE, SIZE_HEADER};
+use crate::reader::{
+    load_file, parse_file_layout, process_block, read_next_block, FileBytes, RecordBlock,
+};
+use crate::{BlockIndex, ParallelProcessor, VBinseqHeader};
+
+/// First line of a manifest (format identifier and version)
+pub const MANIFEST_HEADER: &str = "vbq-manifest\t1";
+
+/// A VBINSEQ file of a dataset
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Shard {
+    /// Path of the shard (relative paths are resolved against the manifest directory)
+    pub path: PathBuf,
+
+    /// Number of records in the shard
+    pub n_records: u64,
+
+    /// Content digest of the shard (see `footer::content_digest`)
+    pub digest: u128,
+}
+impl Shard {
+    /// Describes a shard by scanning its file
+    ///
+    /// The record count and content digest are computed from the record blocks.
+    ///
+    /// # Parameters
+    ///
+    /// * `path` - Path to the VBINSEQ file (stored as given)
+    ///
+    /// # Errors
+    ///
+    /// * I/O errors if the file cannot be read
+    /// * Format errors if the file is not a valid VBINSEQ file
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let (n_records, digest) = scan_shard(path.as_ref())?;
+        Ok(Self {
+            path: path.as_ref().to_path_buf(),
+            n_records,
+            digest,
+        })
+    }
+}
+
+/// List of the shards of a dataset with its metadata
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Manifest {
+    /// Shards of the dataset in order
+    shards: Vec<Shard>,
+
+    /// Key/value metadata in insertion order
+    metadata: Vec<(String, String)>,
+}
+impl Manifest {
+    /// Creates an empty manifest
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a manifest by scanning the given shards
+    ///
+    /// # Parameters
+    ///
+    /// * `paths` - Paths of the shards in dataset order
+    ///
+    /// # Errors
+    ///
+    /// * The errors of `Shard::from_path` and `Manifest::add_shard`
+    pub fn from_shards<I, P>(paths: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<Path>,
+    {
+        let mut manifest = Self::new();
+        for path in paths {
+            manifest.add_shard(Shard::from_path(path)?)?;
+        }
+        Ok(manifest)
+    }
+
+    /// Appends a shard to the manifest
+    ///
+    /// # Errors
+    ///
+    /// * `DatasetError::InvalidManifestField` - If the path is not valid UTF-8 or contains
+    ///   a tab or line break
+    pub fn add_shard(&mut self, shard: Shard) -> Result<()> {
+        match shard.path.to_str() {
+            Some(path) => validate_field(path)?,
+            None => {
+                return Err(DatasetError::InvalidManifestField(
+                    shard.path.to_string_lossy().to_string(),
+                )
+                .into())
+            }
+        }
+        self.shards.push(shard);
+        Ok(())
+    }
+
+    /// Sets a metadata entry, replacing any previous value of the key
+    ///
+    /// # Errors
+    ///
+    /// * `DatasetError::InvalidManifestField` - If the key or value contains a tab or line break
+    pub fn set_metadata(&mut self, key: &str, value: &str) -> Result<()> {
+        validate_field(key)?;
+        validate_field(value)?;
+        match self.metadata.iter_mut().find(|(k, _)| k == key) {
+            Some(entry) => entry.1 = value.to_string(),
+            None => self.metadata.push((key.to_string(), value.to_string())),
+        }
+        Ok(())
+    }
+
+    /// Returns the value of a metadata entry
+    pub fn metadata(&self, key: &str) -> Option<&str> {
+        self.metadata
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Returns all metadata entries in insertion order
+    pub fn metadata_entries(&self) -> &[(String, String)] {
+        &self.metadata
+    }
+
+    /// Returns the shards of the manifest
+    pub fn shards(&self) -> &[Shard] {
+        &self.shards
+    }
+
+    /// Returns the total number of records over all shards
+    pub fn n_records(&self) -> u64 {
+        self.shards.iter().map(|shard| shard.n_records).sum()
+    }
+
+    /// Writes the manifest in its text format
+    pub fn write<W: Write>(&self, writer: &mut W) -> Result<()> {
+        writeln!(writer, "{MANIFEST_HEADER}")?;
+        for (key, value) in &self.metadata {
+            writeln!(writer, "meta\t{key}\t{value}")?;
+        }
+        for shard in &self.shards {
+            writeln!(
+                writer,
+                "shard\t{}\t{}\t{:032x}",
+                shard.path.display(),
+                shard.n_records,
+                shard.digest
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Saves the manifest to a path
+    pub fn save_to_path<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let mut writer = File::create(path).map(BufWriter::new)?;
+        self.write(&mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Reads a manifest from its text format
+    ///
+    /// # Errors
+    ///
+    /// * `DatasetError::InvalidManifestHeader` - If the first line is not `MANIFEST_HEADER`
+    /// * `DatasetError::InvalidManifestLine` - If a line cannot be parsed
+    pub fn from_reader<R: Read>(reader: R) -> Result<Self> {
+        let mut lines = BufReader::new(reader).lines();
+        let first = lines.next().transpose()?.unwrap_or_default();
+        if first.trim_end_matches('\r') != MANIFEST_HEADER {
+            return Err(DatasetError::InvalidManifestHeader(first).into());
+        }
+
+        let mut manifest = Self::new();
+        for (line_number, line) in (2..).zip(lines) {
+            let line = line?;
+            let line = line.trim_end_matches('\r');
+            if line.is_empty() {
+                continue;
+            }
+            let invalid = || DatasetError::InvalidManifestLine(line_number);
+            let fields: Vec<&str> = line.split('\t').collect();
+            match fields.as_slice() {
+                ["meta", key, value] => manifest.set_metadata(key, value)?,
+                ["shard", path, n_records, digest] => manifest.add_shard(Shard {
+                    path: PathBuf::from(path),
+                    n_records: n_records.parse().map_err(|_| invalid())?,
+                    digest: u128::from_str_radix(digest, 16).map_err(|_| invalid())?,
+                })?,
+                _ => return Err(invalid().into()),
+            }
+        }
+        Ok(manifest)
+    }
+
+    /// Reads a manifest from a path
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::from_reader(File::open(path)?)
+    }
+}
+
+/// Checks that a manifest field can be stored in the text format
+fn validate_field(field: &str) -> Result<()> {
+    if field.contains(['\t', '\n', '\r']) {
+        return Err(DatasetError::InvalidManifestField(field.to_string()).into());
+    }
+    Ok(())
+}
+
+/// Computes the record count and content digest of a shard
+fn scan_shard(path: &Path) -> Result<(u64, u128)> {
+    let bytes = load_file(path)?;
+    let (header, _, _) = parse_file_layout(&bytes)?;
+    let footer = compute_footer(&bytes, &header)?;
+    Ok((footer.n_records, footer.digest))
+}
+
+/// Reads the header of a shard without loading the file
+fn read_shard_header(path: &Path) -> Result<VBinseqHeader> {
+    let mut header_bytes = [0u8; SIZE_HEADER];
+    let mut file = File::open(path)?;
+    let n_read = file.read(&mut header_bytes)?;
+    if n_read < SIZE_HEADER {
+        file.read_exact(&mut header_bytes[n_read..])
+            .map_err(|_| ReadError::UnexpectedEndOfFile(n_read))?;
+    }
+    VBinseqHeader::from_bytes(&header_bytes)
+}
+
+/// A collection of shards presented as one logical VBINSEQ file
+///
+/// Records are numbered with global indices: the records of each shard follow the records
+/// of all preceding shards of the manifest. The record counts of the manifest define the
+/// global indices, so they are expected to match the shards (see `Dataset::verify`).
+///
+/// All shards must share the block size, quality, and paired flags of the first shard.
+pub struct Dataset {
+    /// The manifest of the dataset
+    manifest: Manifest,
+
+    /// Resolved paths of the shards
+    paths: Vec<PathBuf>,
+
+    /// Global index of the first record of each shard
+    offsets: Vec<u64>,
+
+    /// Header of the first shard (`None` for datasets without shards)
+    header: Option<VBinseqHeader>,
+}
+impl Dataset {
+    /// Opens the dataset described by a manifest file
+    ///
+    /// Relative shard paths are resolved against the directory of the manifest.
+    ///
+    /// # Errors
+    ///
+    /// * The errors of `Manifest::from_path` and `Dataset::new`
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let manifest = Manifest::from_path(&path)?;
+        let root = path.as_ref().parent().unwrap_or(Path::new(""));
+        Self::new(manifest, root)
+    }
+
+    /// Creates a dataset from a manifest
+    ///
+    /// # Parameters
+    ///
+    /// * `manifest` - The manifest describing the shards
+    /// * `root` - Directory against which relative shard paths are resolved
+    ///
+    /// # Errors
+    ///
+    /// * I/O and header validation errors if a shard header cannot be read
+    /// * `DatasetError::IncompatibleShard` - If the shards have incompatible headers
+    pub fn new<P: AsRef<Path>>(manifest: Manifest, root: P) -> Result<Self> {
+        let paths: Vec<PathBuf> = manifest
+            .shards()
+            .iter()
+            .map(|shard| root.as_ref().join(&shard.path))
+            .collect();
+
+        let mut header: Option<VBinseqHeader> = None;
+        for path in &paths {
+            let shard_header = read_shard_header(path)?;
+            match header {
+                None => header = Some(shard_header),
+                Some(first)
+                    if first.block() != shard_header.block()
+                        || first.qual() != shard_header.qual()
+                        || first.segments() != shard_header.segments() =>
+                {
+                    return Err(DatasetError::IncompatibleShard(path.display().to_string()).into())
+                }
+                Some(_) => {}
+            }
+        }
+
+        let offsets = manifest
+            .shards()
+            .iter()
+            .scan(0, |total, shard| {
+                let offset = *total;
+                *total += shard.n_records;
+                Some(offset)
+            })
+            .collect();
+
+        Ok(Self {
+            manifest,
+            paths,
+            offsets,
+            header,
+        })
+    }
+
+    /// Returns the manifest of the dataset
+    pub fn manifest(&self) -> &Manifest {
+        &self.manifest
+    }
+
+    /// Returns the header of the first shard (`None` for datasets without shards)
+    pub fn header(&self) -> Option<VBinseqHeader> {
+        self.header
+    }
+
+    /// Returns the number of shards
+    pub fn n_shards(&self) -> usize {
+        self.paths.len()
+    }
+
+    /// Returns the total number of records over all shards
+    pub fn n_records(&self) -> u64 {
+        self.manifest.n_records()
+    }
+
+    /// Returns the resolved path of a shard
+    pub fn shard_path(&self, shard: usize) -> Option<&Path> {
+        self.paths.get(shard).map(PathBuf::as_path)
+    }
+
+    /// Locates a record by its global index
+    ///
+    /// # Returns
+    ///
+    /// * `Some((shard, index))` - The shard holding the record and its index within the shard
+    /// * `None` - If the index is beyond the last record
+    pub fn locate(&self, index: u64) -> Option<(usize, u64)> {
+        if index >= self.n_records() {
+            return None;
+        }
+        let shard = self.offsets.partition_point(|&offset| offset <= index) - 1;
+        // Skip empty shards sharing the same offset
+        let shard = (shard..self.n_shards())
+            .find(|&s| index < self.offsets[s] + self.manifest.shards[s].n_records)?;
+        Some((shard, index - self.offsets[shard]))
+    }
+
+    /// Creates a reader over the blocks of all shards in order
+    pub fn reader(&self) -> DatasetReader<'_> {
+        DatasetReader {
+            dataset: self,
+            shard: 0,
+            cursor: None,
+            total: 0,
+        }
+    }
+
+    /// Verifies the shards against the record counts and digests of the manifest
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(true)` - If every shard matches the manifest
+    /// * `Ok(false)` - If any shard differs from the manifest
+    pub fn verify(&self) -> Result<bool> {
+        for (path, shard) in self.paths.iter().zip(self.manifest.shards()) {
+            if scan_shard(path)? != (shard.n_records, shard.digest) {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Processes all records of the dataset in parallel
+    ///
+    /// The blocks of all shards are divided evenly across the threads, and every record is
+    /// passed to the processor with its global index. This behaves like
+    /// `MmapReader::process_parallel` over the concatenation of the shards.
+    ///
+    /// # Parameters
+    ///
+    /// * `processor` - The processor cloned into every thread
+    /// * `num_threads` - The number of threads to use
+    pub fn process_parallel<P: ParallelProcessor + Clone + 'static>(
+        &self,
+        processor: P,
+        num_threads: usize,
+    ) -> Result<()> {
+        // Load every shard and list the blocks of the dataset
+        let mut files = Vec::with_capacity(self.n_shards());
+        let mut headers = Vec::with_capacity(self.n_shards());
+        let mut dictionaries = Vec::with_capacity(self.n_shards());
+        let mut blocks = Vec::new();
+        for (shard, path) in self.paths.iter().enumerate() {
+            let bytes = load_file(path)?;
+            let (header, _, _) = parse_file_layout(&bytes)?;
+            let index = BlockIndex::from_bytes(&bytes)?;
+            blocks.extend(index.ranges().iter().map(|range| (shard, *range)));
+            dictionaries.push(dictionary::load(&bytes, &header)?);
+            files.push(bytes);
+            headers.push(header);
+        }
+        if blocks.is_empty() {
+            return Ok(()); // Nothing to process
+        }
+
+        // Create shared resources
+        let blocks_per_thread = blocks.len().div_ceil(num_threads);
+        let files: Arc<Vec<FileBytes>> = Arc::new(files);
+        let headers = Arc::new(headers);
+        let dictionaries = Arc::new(dictionaries);
+        let blocks = Arc::new(blocks);
+        let offsets = Arc::new(self.offsets.clone());
+
+        // Spawn worker threads
+        let mut handles = Vec::new();
+        for thread_id in 0..num_threads {
+            let start_block = thread_id * blocks_per_thread;
+            let end_block = std::cmp::min((thread_id + 1) * blocks_per_thread, blocks.len());
+            if start_block >= end_block {
+                continue;
+            }
+
+            let files = Arc::clone(&files);
+            let headers = Arc::clone(&headers);
+            let dictionaries = Arc::clone(&dictionaries);
+            let blocks = Arc::clone(&blocks);
+            let offsets = Arc::clone(&offsets);
+            let mut proc = processor.clone();
+            proc.set_tid(thread_id);
+
+            let handle = std::thread::spawn(move || -> Result<()> {
+                let mut record_block = RecordBlock::new(headers[0].block() as usize);
+                for (shard, range) in &blocks[start_block..end_block] {
+                    record_block.set_dictionaries(&dictionaries[*shard]);
+                    process_block(
+                        &files[*shard],
+                        &headers[*shard],
+                        range,
+                        offsets[*shard] + range.cumulative_records,
+                        &mut record_block,
+                        &mut proc,
+                    )?;
+                }
+                proc.on_thread_complete()
+            });
+            handles.push(handle);
+        }
+
+        for handle in handles {
+            handle.join().unwrap()?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A shard opened by a `DatasetReader`
+struct ShardCursor {
+    /// Contents of the shard
+    bytes: FileBytes,
+
+    /// Header of the shard
+    header: VBinseqHeader,
+
+    /// Compression dictionaries of the shard
+    dictionaries: Arc<[Vec<u8>]>,
+
+    /// Position where the record blocks of the shard end
+    end: usize,
+
+    /// Current position in the shard
+    pos: usize,
+}
+
+/// Sequential reader over the blocks of all shards of a dataset
+///
+/// Shards are opened one at a time in manifest order, and the records of every block carry
+/// their global index in the dataset.
+pub struct DatasetReader<'a> {
+    /// The dataset being read
+    dataset: &'a Dataset,
+
+    /// Index of the current shard
+    shard: usize,
+
+    /// The currently opened shard
+    cursor: Option<ShardCursor>,
+
+    /// Global index of the next record
+    total: u64,
+}
+impl DatasetReader<'_> {
+    /// Creates a new empty record block with the appropriate size for this dataset
+    pub fn new_block(&self) -> RecordBlock {
+        let block_size = self
+            .dataset
+            .header
+            .map_or(BLOCK_SIZE, |header| header.block());
+        RecordBlock::new(block_size as usize)
+    }
+
+    /// Fills an existing RecordBlock with the next block of records
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(true)` - If a block was successfully read
+    /// * `Ok(false)` - If the end of the last shard was reached (no more blocks)
+    /// * `Err(_)` - If an error occurred during reading
+    pub fn read_block_into(&mut self, block: &mut RecordBlock) -> Result<bool> {
+        loop {
+            if self.cursor.is_none() {
+                let Some(path) = self.dataset.shard_path(self.shard) else {
+                    block.clear();
+                    return Ok(false);
+                };
+                let bytes = load_file(path)?;
+                let (header, _, end) = parse_file_layout(&bytes)?;
+                let dictionaries = dictionary::load(&bytes, &header)?;
+                self.total = self.dataset.offsets[self.shard];
+                self.cursor = Some(ShardCursor {
+                    bytes,
+                    header,
+                    dictionaries,
+                    end,
+                    pos: header.data_offset(),
+                });
+            }
+
+            let cursor = self.cursor.as_mut().unwrap();
+            block.set_dictionaries(&cursor.dictionaries);
+            if read_next_block(
+                &cursor.bytes,
+                cursor.end,
+                &cursor.header,
+                &mut cursor.pos,
+                &mut self.total,
+                block,
+            )? {
+                return Ok(true);
+            }
+            self.cursor = None;
+            self.shard += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    use super::*;
+    use crate::conformance::TestVector;
+    use crate::RefRecord;
+
+    #[derive(Clone, Default)]
+    struct IndexSum {
+        sum: Arc<AtomicU64>,
+        count: Arc<AtomicU64>,
+    }
+    impl ParallelProcessor for IndexSum {
+        fn process_record(&mut self, record: RefRecord) -> Result<()> {
+            self.sum.fetch_add(record.index(), Ordering::Relaxed);
+            self.count.fetch_add(1, Ordering::Relaxed);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_dataset() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("vbq_dataset_{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let vectors = [
+            TestVector::generate(true, true, false),
+            TestVector::empty(),
+            TestVector::generate(true, false, false),
+        ];
+        let mut names = Vec::new();
+        for (i, vector) in vectors.iter().enumerate() {
+            let name = format!("shard_{i}.vbq");
+            let mut header = vector.header;
+            header.set_block(vectors[0].header.block())?;
+            header.set_qual(true);
+            let vector = TestVector {
+                header,
+                ..vector.clone()
+            };
+            vector.write_vbq(File::create(dir.join(&name))?)?;
+            names.push(name);
+        }
+
+        // Scan the shards (relative to the manifest directory) and round-trip the manifest
+        let mut manifest = Manifest::new();
+        for name in &names {
+            let mut shard = Shard::from_path(dir.join(name))?;
+            shard.path = PathBuf::from(name);
+            manifest.add_shard(shard)?;
+        }
+        manifest.set_metadata("sample", "S1")?;
+        assert!(manifest.set_metadata("bad", "a\tb").is_err());
+        let manifest_path = dir.join("dataset.vbqm");
+        manifest.save_to_path(&manifest_path)?;
+        assert_eq!(Manifest::from_path(&manifest_path)?, manifest);
+
+        let dataset = Dataset::open(&manifest_path)?;
+        let n_records = (vectors[0].records.len() + vectors[2].records.len()) as u64;
+        assert_eq!(dataset.n_records(), n_records);
+        assert_eq!(dataset.manifest().metadata("sample"), Some("S1"));
+        assert!(dataset.verify()?);
+        let first = vectors[0].records.len() as u64;
+        assert_eq!(dataset.locate(first - 1), Some((0, first - 1)));
+        assert_eq!(dataset.locate(first), Some((2, 0)));
+        assert_eq!(dataset.locate(n_records), None);
+
+        // Sequential reading with global indices
+        let mut reader = dataset.reader();
+        let mut block = reader.new_block();
+        let mut expected_index = 0;
+        let mut sequence = Vec::new();
+        let expected: Vec<_> = vectors[0]
+            .records
+            .iter()
+            .chain(&vectors[2].records)
+            .collect();
+        while reader.read_block_into(&mut block)? {
+            for record in block.iter() {
+                assert_eq!(record.index(), expected_index);
+                sequence.clear();
+                record.decode_s(&mut sequence)?;
+                assert_eq!(sequence, expected[expected_index as usize].sequence);
+                expected_index += 1;
+            }
+        }
+        assert_eq!(expected_index, n_records);
+
+        // Parallel processing with global indices
+        let processor = IndexSum::default();
+        dataset.process_parallel(processor.clone(), 3)?;
+        assert_eq!(processor.count.load(Ordering::Relaxed), n_records);
+        assert_eq!(
+            processor.sum.load(Ordering::Relaxed),
+            n_records * (n_records - 1) / 2
+        );
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
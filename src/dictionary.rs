@@ -0,0 +1,301 @@
+//! # Compression Dictionaries
+//!
+//! Small zstd blocks compress considerably better with a dictionary trained on similar
+//! data. Files can carry several dictionaries (e.g. one trained on short reads and one on
+//! long reads of a heterogeneous dataset), and every compressed block records which one
+//! it was compressed with, so each block uses the dictionary that suits it best.
+//!
+//! The dictionaries are stored in the header sections of the file (see the `sections`
+//! module), and blocks reference a dictionary by its position in the list (see
+//! `BlockHeader::dictionary`). A file holds at most 256 dictionaries. Readers load the
+//! dictionaries of the file when they are opened.
+//!
+//! Writers of files with dictionaries pick the dictionary of every compressed block with
+//! the selector passed to `VBinseqWriterBuilder::dictionary_selector`, or by default
+//! compress the block with every dictionary (and without one) and keep the smallest
+//! result. Dictionaries can be trained with `train` on any bytes, or with
+//! `train_from_file` on the records of an existing file.
+//!
+//! # Example
+//!
+//! ```rust
+//! use vbinseq::sections::HeaderSections;
+//! use vbinseq::{MemoryReader, VBinseqHeader, VBinseqWriterBuilder};
+//!
+//! // Raw bytes (e.g. the records of a similar file) can serve as a dictionary
+//! let mut sample = Vec::new();
+//! let mut writer = VBinseqWriterBuilder::default()
+//!     .header(VBinseqHeader::with_capacity(1024, false, false, false))
+//!     .build(&mut sample)
+//!     .unwrap();
+//! writer.write_nucleotides(0, b"ACGTTGCAACGTTGCA").unwrap();
+//! writer.finish().unwrap();
+//! drop(writer);
+//!
+//! let sections = HeaderSections {
+//!     dictionaries: vec![sample],
+//!     ..HeaderSections::default()
+//! };
+//! let mut bytes = Vec::new();
+//! let mut writer = VBinseqWriterBuilder::default()
+//!     .header(VBinseqHeader::with_capacity(1024, false, true, false))
+//!     .sections(sections)
+//!     .build(&mut bytes)
+//!     .unwrap();
+//! writer.write_nucleotides(0, b"ACGTTGCAACGTTGCA").unwrap();
+//! writer.finish().unwrap();
+//! drop(writer);
+//!
+//! let mut reader = MemoryReader::new(bytes).unwrap();
+//! let mut block = reader.new_block();
+//! assert!(reader.read_block_into(&mut block).unwrap());
+//! assert_eq!(block.n_records(), 1);
+//! ```
+
+use std::sync::Arc;
+
+use crate::error::Result;
+use crate::sections::{push_bytes, HeaderSections, SectionCursor};
+use crate::VBinseqHeader;
+
+/// Maximum number of dictionaries of a file
+pub const MAX_DICTIONARIES: usize = 256;
+
+/// Trains a zstd dictionary on samples of the data to compress
+///
+/// Dictionaries are most effective when trained on many small samples resembling the
+/// blocks they compress, e.g. the stored records of similar files (see
+/// `train_from_file`).
+///
+/// # Parameters
+///
+/// * `samples` - The samples to train on
+/// * `max_size` - Maximum size of the dictionary in bytes (e.g. 100 KiB)
+///
+/// # Errors
+///
+/// * I/O errors if zstd fails to train a dictionary, e.g. if there are too few samples
+#[cfg(feature = "compression")]
+pub fn train<S: AsRef<[u8]>>(samples: &[S], max_size: usize) -> Result<Vec<u8>> {
+    Ok(zstd::dict::from_samples(samples, max_size)?)
+}
+
+/// Trains a zstd dictionary on the stored records of a file
+///
+/// Every record of the file (as stored in its blocks) is used as a sample.
+///
+/// # Parameters
+///
+/// * `bytes` - The full contents of the file
+/// * `max_size` - Maximum size of the dictionary in bytes (e.g. 100 KiB)
+///
+/// # Errors
+///
+/// * Header and read errors if the file cannot be parsed
+/// * I/O errors if zstd fails to train a dictionary, e.g. if there are too few records
+#[cfg(feature = "compression")]
+pub fn train_from_file(bytes: &[u8], max_size: usize) -> Result<Vec<u8>> {
+    let (header, _, end) = crate::reader::parse_file_layout(bytes)?;
+    let dictionaries = load(bytes, &header)?;
+    let mut samples = Vec::new();
+    let mut dbuf = Vec::new();
+    let (mut pos, mut total) = (header.data_offset(), 0);
+    while let Some(raw) =
+        crate::reader::read_next_raw_block(bytes, end, &header, &mut pos, &mut total)?
+    {
+        let records =
+            crate::footer::block_records(&raw.header, raw.data, &header, &dictionaries, &mut dbuf)?;
+        samples.extend(records.into_iter().map(<[u8]>::to_vec));
+    }
+    train(&samples, max_size)
+}
+
+/// Loads the dictionaries of a file held in memory
+///
+/// Files without header sections have no dictionaries, so nothing is parsed.
+///
+/// # Parameters
+///
+/// * `bytes` - The full contents of the file (e.g. a memory map)
+/// * `header` - The header of the file
+pub(crate) fn load(bytes: &[u8], header: &VBinseqHeader) -> Result<Arc<[Vec<u8>]>> {
+    if header.sections_size() == 0 {
+        return Ok(Arc::from([]));
+    }
+    let sections = HeaderSections::from_file_bytes(bytes, header)?;
+    Ok(sections.dictionaries.into())
+}
+
+/// Returns the dictionary a block was compressed with
+///
+/// # Errors
+///
+/// * `ReadError::MissingDictionary` - If the block references a dictionary the file lacks
+#[cfg(feature = "compression")]
+pub(crate) fn block_dictionary<'a>(
+    block_header: &crate::BlockHeader,
+    dictionaries: &'a [Vec<u8>],
+) -> Result<Option<&'a [u8]>> {
+    match block_header.dictionary() {
+        None => Ok(None),
+        Some(id) => match dictionaries.get(usize::from(id)) {
+            Some(dictionary) => Ok(Some(dictionary)),
+            None => Err(crate::error::ReadError::MissingDictionary(id).into()),
+        },
+    }
+}
+
+/// Encodes dictionaries as the payload of their section
+///
+/// The section is stored as the u32 number of dictionaries followed by every dictionary
+/// prefixed with its u32 size.
+pub(crate) fn encode_table(dictionaries: &[Vec<u8>], payload: &mut Vec<u8>) {
+    payload.extend_from_slice(&(dictionaries.len() as u32).to_le_bytes());
+    for dictionary in dictionaries {
+        push_bytes(payload, dictionary);
+    }
+}
+
+/// Decodes dictionaries from the payload of their section
+///
+/// Returns `None` if the payload is invalid.
+pub(crate) fn decode_table(payload: &[u8]) -> Option<Vec<Vec<u8>>> {
+    let mut cursor = SectionCursor::new(payload);
+    let n_dictionaries = cursor.u32()?;
+    let mut dictionaries = Vec::new();
+    for _ in 0..n_dictionaries {
+        dictionaries.push(cursor.bytes()?.to_vec());
+    }
+    cursor.is_done().then_some(dictionaries)
+}
+
+#[cfg(all(test, feature = "mmap"))]
+mod tests {
+    use rand::rngs::SmallRng;
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::error::WriteError;
+    use crate::testing::{random_records, read_records, write_records};
+    use crate::writer::DictionarySelector;
+    use crate::{validate, Error, MemoryReader, OwnedRecord, VBinseqWriterBuilder};
+
+    /// Writes records to a file with dictionaries
+    fn write_with(
+        header: VBinseqHeader,
+        sections: &HeaderSections,
+        records: &[OwnedRecord],
+        selector: Option<DictionarySelector>,
+    ) -> Result<Vec<u8>> {
+        let mut builder = VBinseqWriterBuilder::default()
+            .header(header)
+            .sections(sections.clone());
+        if let Some(selector) = selector {
+            builder = builder.dictionary_selector(move |block| selector(block));
+        }
+        let mut bytes = Vec::new();
+        let mut writer = builder.build(&mut bytes)?;
+        for record in records {
+            writer.write_record(record)?;
+        }
+        writer.finish()?;
+        drop(writer);
+        Ok(bytes)
+    }
+
+    /// Returns the dictionary of every block of a file
+    fn block_dictionaries(bytes: Vec<u8>) -> Result<Vec<Option<u8>>> {
+        let mut reader = MemoryReader::new(bytes)?;
+        let mut dictionaries = Vec::new();
+        while let Some(raw) = reader.next_raw_block()? {
+            dictionaries.push(raw.header.dictionary());
+        }
+        Ok(dictionaries)
+    }
+
+    #[test]
+    fn test_dictionaries() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("vbq_dictionary_{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join("reads.vbq");
+
+        let mut header = VBinseqHeader::with_capacity(4096, true, true, true);
+        header.set_footer(true);
+        let rng = &mut SmallRng::seed_from_u64(11);
+        let training = write_records(header, &random_records(rng, &header, 2000))?;
+        let sections = HeaderSections {
+            dictionaries: vec![b"ACGT".repeat(64), train_from_file(&training, 8 << 10)?],
+            ..HeaderSections::default()
+        };
+        assert_eq!(HeaderSections::from_bytes(&sections.to_bytes())?, sections);
+
+        // Blocks use the selected dictionary and are read back by every reader
+        let records = random_records(rng, &header, 500);
+        let plain = write_records(header, &records)?;
+        let expected = read_records(plain.clone())?;
+        let bytes = write_with(header, &sections, &records, Some(Arc::new(|_| Some(1))))?;
+        assert!(block_dictionaries(bytes.clone())?
+            .iter()
+            .all(|&dictionary| dictionary == Some(1)));
+        assert_eq!(read_records(bytes.clone())?, expected);
+        std::fs::write(&path, &bytes)?;
+        assert!(validate::check(&path)?.is_valid());
+        let mut reader = crate::MmapReader::new(&path)?;
+        let mut block = reader.new_block();
+        let mut n_records = 0;
+        while reader.read_block_into(&mut block)? {
+            n_records += block.n_records();
+        }
+        assert_eq!(n_records, records.len());
+
+        // Without a selector, no block is larger than without dictionaries
+        let best = write_with(header, &sections, &records, None)?;
+        assert!(best.len() - sections.to_bytes().len() <= plain.len());
+        assert_eq!(read_records(best)?, expected);
+
+        // Selected dictionaries must exist, also in files receiving raw blocks
+        let error = write_with(header, &sections, &records, Some(Arc::new(|_| Some(2))));
+        assert!(matches!(
+            error,
+            Err(Error::WriteError(WriteError::UnknownDictionary(2, 2)))
+        ));
+        let mut source = MemoryReader::new(bytes)?;
+        let raw = source.next_raw_block()?.unwrap();
+        let mut writer = VBinseqWriterBuilder::default()
+            .header(header)
+            .build(Vec::new())?;
+        assert!(matches!(
+            writer.write_raw_block(&raw),
+            Err(Error::WriteError(WriteError::UnknownDictionary(1, 0)))
+        ));
+
+        // Files receiving raw blocks must hold the same dictionary under its number
+        let other = HeaderSections {
+            dictionaries: vec![sections.dictionaries[0].clone(), b"TGCA".repeat(64)],
+            ..HeaderSections::default()
+        };
+        let mut writer = VBinseqWriterBuilder::default()
+            .header(header)
+            .sections(other)
+            .build(Vec::new())?;
+        assert!(matches!(
+            writer.write_raw_block(&raw),
+            Err(Error::WriteError(WriteError::DictionaryMismatch(1)))
+        ));
+        let mut copy = Vec::new();
+        let mut writer = VBinseqWriterBuilder::default()
+            .header(header)
+            .sections(sections.clone())
+            .build(&mut copy)?;
+        writer.write_raw_block(&raw)?;
+        while let Some(raw) = source.next_raw_block()? {
+            writer.write_raw_block(&raw)?;
+        }
+        writer.finish()?;
+        drop(writer);
+        assert_eq!(read_records(copy)?, expected);
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
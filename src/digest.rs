@@ -0,0 +1,421 @@
+//! # Record Content Digests
+//!
+//! This module computes digests over the decoded content of records (nucleotides and,
+//! optionally, quality scores), so the records of a VBINSEQ file can be compared to those
+//! of the FASTQ file it was converted from.
+//!
+//! Two digests are computed in a single pass:
+//!
+//! * `ordered` depends on the order of the records, and proves that two files hold the
+//!   same records in the same order.
+//! * `unordered` is independent of the order of the records (a multiset digest), and
+//!   proves that two files hold the same records, e.g. after parallel conversion.
+//!
+//! Each record is hashed with XXH3-128 over the length-prefixed primary sequence, primary
+//! quality scores, extended sequence, and extended quality scores, followed by the
+//! sequence and quality scores of any further segment. The ordered digest is
+//! the XXH3-128 hash of the sequence of record hashes, and the unordered digest is their
+//! wrapping sum. Record flags and FASTQ record names are not included.
+//!
+//! Unlike the footer digest (see `footer`), which covers the encoded record bytes, these
+//! digests only depend on the decoded text of the records. Sequences are hashed as
+//! given: VBINSEQ files decode to uppercase `ACGT`, so FASTQ sequences with lowercase or
+//! invalid nucleotides only match if they were converted without modification.
+//! Homopolymer-compressed sequences are hashed expanded, without quality scores.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use vbinseq::digest;
+//!
+//! let vbq = digest::digest_file("reads.vbq", true).unwrap();
+//! println!("{vbq}");
+//! ```
+
+use std::fmt;
+#[cfg(feature = "seq_io")]
+use std::io::Read;
+#[cfg(feature = "mmap")]
+use std::path::Path;
+
+use xxhash_rust::xxh3::{Xxh3, Xxh3Default};
+
+use crate::reader::RecordBlock;
+use crate::{OwnedRecord, Result};
+
+/// Digests of the decoded content of a set of records
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ContentDigest {
+    /// Number of records
+    pub n_records: u64,
+
+    /// Digest depending on the order of the records
+    pub ordered: u128,
+
+    /// Digest independent of the order of the records
+    pub unordered: u128,
+}
+impl fmt::Display for ContentDigest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Records:          {}", self.n_records)?;
+        writeln!(f, "Ordered digest:   {:032x}", self.ordered)?;
+        write!(f, "Unordered digest: {:032x}", self.unordered)
+    }
+}
+
+/// Running digests over decoded records
+#[derive(Clone)]
+pub struct RecordHasher {
+    /// Whether quality scores are included in the record hashes
+    quality: bool,
+    /// Hasher of a single record
+    record: Xxh3,
+    /// Streaming hasher over the record hashes
+    ordered: Xxh3Default,
+    /// Wrapping sum of the record hashes
+    unordered: u128,
+    /// Number of records seen
+    n_records: u64,
+}
+impl RecordHasher {
+    /// Creates a new hasher
+    ///
+    /// # Parameters
+    ///
+    /// * `quality` - Whether quality scores are included in the digests. Files without
+    ///   quality scores can only be compared to other files with this set to `false`.
+    pub fn new(quality: bool) -> Self {
+        Self {
+            quality,
+            record: Xxh3::new(),
+            ordered: Xxh3Default::new(),
+            unordered: 0,
+            n_records: 0,
+        }
+    }
+
+    /// Adds a record given as decoded text
+    ///
+    /// Single-end records have an empty extended sequence and extended quality scores.
+    /// Quality scores are ignored if the hasher does not include them.
+    pub fn update(&mut self, sequence: &[u8], quality: &[u8], extended: &[u8], xquality: &[u8]) {
+        self.update_segments(&[sequence, extended], &[quality, xquality]);
+    }
+
+    /// Adds a record of any number of segments given as decoded text
+    ///
+    /// Records of one or two segments hash the same as with `update`: a missing extended
+    /// sequence is hashed as an empty sequence. Missing quality scores are hashed as empty
+    /// quality scores, and all quality scores are ignored if the hasher does not include
+    /// them.
+    pub fn update_segments(&mut self, sequences: &[&[u8]], qualities: &[&[u8]]) {
+        self.record.reset();
+        for segment in 0..sequences.len().max(2) {
+            let sequence = sequences.get(segment).copied().unwrap_or_default();
+            let quality = match qualities.get(segment) {
+                Some(quality) if self.quality => quality,
+                _ => &[][..],
+            };
+            for field in [sequence, quality] {
+                self.record.update(&(field.len() as u64).to_le_bytes());
+                self.record.update(field);
+            }
+        }
+        let digest = self.record.digest128();
+        self.ordered.update(&digest.to_le_bytes());
+        self.unordered = self.unordered.wrapping_add(digest);
+        self.n_records += 1;
+    }
+
+    /// Adds a decoded record
+    ///
+    /// Homopolymer-compressed records are expanded first (see
+    /// `OwnedRecord::expand_homopolymers`).
+    ///
+    /// # Errors
+    ///
+    /// * `ReadError::InvalidHomopolymerRuns` - If the run lengths do not match a sequence
+    pub fn update_record(&mut self, record: &OwnedRecord) -> Result<()> {
+        let record = record.expanded()?;
+        let segments = 0..record.n_segments();
+        let sequences: Vec<&[u8]> = segments.clone().map(|i| record.segment(i)).collect();
+        let qualities: Vec<&[u8]> = segments.map(|i| record.segment_qual(i)).collect();
+        self.update_segments(&sequences, &qualities);
+        Ok(())
+    }
+
+    /// Decodes and adds all records of a block
+    ///
+    /// # Errors
+    ///
+    /// * Decoding errors of the records
+    pub fn update_block(&mut self, block: &RecordBlock) -> Result<()> {
+        let mut record = OwnedRecord::default();
+        for ref_record in block.iter() {
+            record.fill(&ref_record)?;
+            record.expand_homopolymers()?;
+            self.update_record(&record)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the digests of all records seen so far
+    pub fn digest(&self) -> ContentDigest {
+        ContentDigest {
+            n_records: self.n_records,
+            ordered: self.ordered.digest128(),
+            unordered: self.unordered,
+        }
+    }
+}
+
+/// Computes the content digests of the records of a VBINSEQ file
+///
+/// # Parameters
+///
+/// * `path` - Path to the VBINSEQ file
+/// * `quality` - Whether quality scores are included in the digests
+///
+/// # Returns
+///
+/// * `Ok(ContentDigest)` - The digests of the records of the file
+/// * `Err(_)` - If the file could not be read or has an invalid format
+#[cfg(feature = "mmap")]
+pub fn digest_file<P: AsRef<Path>>(path: P, quality: bool) -> Result<ContentDigest> {
+    let mut reader = crate::MmapReader::new(path)?;
+    let mut block = reader.new_block();
+    let mut hasher = RecordHasher::new(quality);
+    while reader.read_block_into(&mut block)? {
+        hasher.update_block(&block)?;
+    }
+    Ok(hasher.digest())
+}
+
+/// Computes the content digests of single-end FASTQ records
+///
+/// This requires the `seq_io` feature.
+///
+/// # Parameters
+///
+/// * `reader` - The source of the FASTQ records (uncompressed)
+/// * `quality` - Whether quality scores are included in the digests
+///
+/// # Errors
+///
+/// * `Error::AnyhowError` - If a FASTQ record could not be parsed
+#[cfg(feature = "seq_io")]
+pub fn digest_fastq<R: Read>(reader: R, quality: bool) -> Result<ContentDigest> {
+    use seq_io::fastq::Record;
+
+    let mut reader = seq_io::fastq::Reader::new(reader);
+    let mut hasher = RecordHasher::new(quality);
+    while let Some(record) = reader.next() {
+        let record = record.map_err(anyhow::Error::from)?;
+        hasher.update(record.seq(), record.qual(), &[], &[]);
+    }
+    Ok(hasher.digest())
+}
+
+/// Computes the content digests of paired FASTQ records from two files
+///
+/// The records of `extended` are the mates of the records of `primary`. This requires the
+/// `seq_io` feature.
+///
+/// # Errors
+///
+/// * `Error::AnyhowError` - If a FASTQ record could not be parsed or the files hold
+///   different numbers of records
+#[cfg(feature = "seq_io")]
+pub fn digest_fastq_paired<R: Read, S: Read>(
+    primary: R,
+    extended: S,
+    quality: bool,
+) -> Result<ContentDigest> {
+    use seq_io::fastq::Record;
+
+    let mut primary = seq_io::fastq::Reader::new(primary);
+    let mut extended = seq_io::fastq::Reader::new(extended);
+    let mut hasher = RecordHasher::new(quality);
+    loop {
+        match (primary.next(), extended.next()) {
+            (Some(r1), Some(r2)) => {
+                let r1 = r1.map_err(anyhow::Error::from)?;
+                let r2 = r2.map_err(anyhow::Error::from)?;
+                hasher.update(r1.seq(), r1.qual(), r2.seq(), r2.qual());
+            }
+            (None, None) => break,
+            _ => {
+                return Err(anyhow::anyhow!("Paired FASTQ files differ in length").into());
+            }
+        }
+    }
+    Ok(hasher.digest())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MemoryReader, VBinseqHeader, VBinseqWriterBuilder};
+
+    fn records() -> Vec<OwnedRecord> {
+        vec![
+            OwnedRecord::new(0, b"ACGT".to_vec(), b"IIII".to_vec()),
+            OwnedRecord::new(0, b"TTGCA".to_vec(), b"!!!!!".to_vec()),
+            OwnedRecord::new(0, b"GG".to_vec(), b"FF".to_vec()),
+        ]
+    }
+
+    #[test]
+    fn test_ordered_and_unordered() -> Result<()> {
+        let mut forward = RecordHasher::new(true);
+        for record in &records() {
+            forward.update_record(record)?;
+        }
+        let mut reverse = RecordHasher::new(true);
+        for record in records().iter().rev() {
+            reverse.update_record(record)?;
+        }
+
+        let (forward, reverse) = (forward.digest(), reverse.digest());
+        assert_eq!(forward.n_records, 3);
+        assert_eq!(forward.unordered, reverse.unordered);
+        assert_ne!(forward.ordered, reverse.ordered);
+        Ok(())
+    }
+
+    #[test]
+    fn test_quality_and_framing() {
+        let mut with_quality = RecordHasher::new(true);
+        with_quality.update(b"ACGT", b"IIII", b"", b"");
+        let mut other_quality = RecordHasher::new(true);
+        other_quality.update(b"ACGT", b"IIIF", b"", b"");
+        assert_ne!(with_quality.digest(), other_quality.digest());
+
+        let mut without_quality = RecordHasher::new(false);
+        without_quality.update(b"ACGT", b"IIII", b"", b"");
+        let mut other_quality = RecordHasher::new(false);
+        other_quality.update(b"ACGT", b"IIIF", b"", b"");
+        assert_eq!(without_quality.digest(), other_quality.digest());
+
+        // Moving nucleotides between mates changes the digest
+        let mut split = RecordHasher::new(false);
+        split.update(b"AC", b"", b"GT", b"");
+        assert_ne!(split.digest(), without_quality.digest());
+    }
+
+    #[test]
+    fn test_vbq_matches_source() -> Result<()> {
+        let mut bytes = Vec::new();
+        let header = VBinseqHeader::with_capacity(256, true, true, false);
+        let mut writer = VBinseqWriterBuilder::default()
+            .header(header)
+            .build(&mut bytes)?;
+        let mut source = RecordHasher::new(true);
+        for record in records().iter().cycle().take(100) {
+            writer.write_record(record)?;
+            source.update_record(record)?;
+        }
+        writer.finish()?;
+        drop(writer);
+
+        let mut reader = MemoryReader::new(bytes)?;
+        let mut block = reader.new_block();
+        let mut hasher = RecordHasher::new(true);
+        while reader.read_block_into(&mut block)? {
+            hasher.update_block(&block)?;
+        }
+        assert_eq!(hasher.digest(), source.digest());
+        Ok(())
+    }
+
+    #[test]
+    fn test_segments() -> Result<()> {
+        let record = |index: &[u8]| {
+            let sequences = vec![b"ACGT".to_vec(), b"GG".to_vec(), index.to_vec()];
+            let qualities = vec![b"IIII".to_vec(), b"FF".to_vec(), vec![b'#'; index.len()]];
+            OwnedRecord::new_segments(0, sequences, qualities)
+        };
+        let (first, second) = (record(b"AACG"), record(b"AACT"));
+
+        // Records differing only in a further segment have different digests
+        let mut source = RecordHasher::new(true);
+        source.update_record(&first)?;
+        let mut other = RecordHasher::new(true);
+        other.update_record(&second)?;
+        assert_ne!(source.digest(), other.digest());
+        source.update_record(&second)?;
+
+        // Records of two segments hash as with `update`
+        let paired = OwnedRecord::new_paired(0, b"AC".to_vec(), b"G".to_vec(), vec![], vec![]);
+        let mut hasher = RecordHasher::new(true);
+        hasher.update_record(&paired)?;
+        let mut expected = RecordHasher::new(true);
+        expected.update(b"AC", b"", b"G", b"");
+        assert_eq!(hasher.digest(), expected.digest());
+
+        let mut bytes = Vec::new();
+        let mut header = VBinseqHeader::new(true, false, false);
+        header.set_segments(3)?;
+        let mut writer = VBinseqWriterBuilder::default()
+            .header(header)
+            .build(&mut bytes)?;
+        writer.write_record(&first)?;
+        writer.write_record(&second)?;
+        writer.finish()?;
+        drop(writer);
+
+        let mut reader = MemoryReader::new(bytes)?;
+        let mut block = reader.new_block();
+        assert!(reader.read_block_into(&mut block)?);
+        let mut hasher = RecordHasher::new(true);
+        hasher.update_block(&block)?;
+        assert_eq!(hasher.digest(), source.digest());
+        Ok(())
+    }
+
+    #[test]
+    fn test_homopolymer_records() -> Result<()> {
+        let mut bytes = Vec::new();
+        let mut header = VBinseqHeader::new(false, false, true);
+        header.set_homopolymer(true);
+        let mut writer = VBinseqWriterBuilder::default()
+            .header(header)
+            .build(&mut bytes)?;
+        writer.write_nucleotides_paired(0, b"AAACGGGG", b"TTTA")?;
+        writer.finish()?;
+        drop(writer);
+
+        // The expanded sequences are hashed without the run lengths
+        let mut source = RecordHasher::new(true);
+        source.update(b"AAACGGGG", b"", b"TTTA", b"");
+        let mut reader = MemoryReader::new(bytes)?;
+        let mut block = reader.new_block();
+        assert!(reader.read_block_into(&mut block)?);
+        let mut hasher = RecordHasher::new(true);
+        hasher.update_block(&block)?;
+        assert_eq!(hasher.digest(), source.digest());
+
+        let record = OwnedRecord::try_from(&block.iter().next().unwrap())?;
+        let mut hasher = RecordHasher::new(true);
+        hasher.update_record(&record)?;
+        assert_eq!(hasher.digest(), source.digest());
+        Ok(())
+    }
+
+    #[cfg(feature = "seq_io")]
+    #[test]
+    fn test_digest_fastq() -> Result<()> {
+        let r1 = b"@a\nACGT\n+\nIIII\n@b\nTTGCA\n+\n!!!!!\n";
+        let r2 = b"@a\nGG\n+\nFF\n@b\nC\n+\nI\n";
+        let single = digest_fastq(&r1[..], true)?;
+        let mut hasher = RecordHasher::new(true);
+        hasher.update(b"ACGT", b"IIII", b"", b"");
+        hasher.update(b"TTGCA", b"!!!!!", b"", b"");
+        assert_eq!(single, hasher.digest());
+
+        let paired = digest_fastq_paired(&r1[..], &r2[..], false)?;
+        assert_eq!(paired.n_records, 2);
+        assert!(digest_fastq_paired(&r1[..], &r2[..16], false).is_err());
+        Ok(())
+    }
+}
@@ -11,6 +11,13 @@
 //! * `WriteError` - Errors that can occur during writing operations
 //! * `ReadError` - Errors that can occur during reading operations
 //! * `IndexError` - Errors related to file indexing
+//! * `DatasetError` - Errors related to dataset manifests
+//!
+//! Errors raised while reading blocks are wrapped in `Error::Context`, which records the
+//! file, block, byte offset, and record where they occurred (see `ErrorContext`).
+
+use std::fmt;
+use std::path::PathBuf;
 
 use crate::VBinseqHeader;
 
@@ -55,6 +62,10 @@ pub enum Error {
     #[error("Error processing Index: {0}")]
     IndexError(#[from] IndexError),
 
+    /// Errors related to dataset manifests
+    #[error("Error processing dataset: {0}")]
+    DatasetError(#[from] DatasetError),
+
     /// Standard I/O errors
     #[error("Error with IO: {0}")]
     IoError(#[from] std::io::Error),
@@ -67,9 +78,38 @@ pub enum Error {
     #[error("Bitnuc error: {0}")]
     BitnucError(#[from] bitnuc::NucleotideError),
 
+    /// Errors from building Polars DataFrames
+    #[cfg(feature = "polars")]
+    #[error("Polars error: {0}")]
+    PolarsError(#[from] polars::error::PolarsError),
+
+    /// Errors raised by wrapped paraseq processors
+    #[cfg(feature = "paraseq")]
+    #[error("Paraseq error: {0}")]
+    ParaseqError(#[from] paraseq::parallel::ProcessError),
+
+    /// Errors raised while parsing records with needletail
+    #[cfg(feature = "needletail")]
+    #[error("Needletail error: {0}")]
+    NeedletailError(#[from] needletail::errors::ParseError),
+
+    /// Errors from serializing records to JSON
+    #[cfg(feature = "serde")]
+    #[error("JSON error: {0}")]
+    JsonError(#[from] serde_json::Error),
+
     /// Generic errors for other unexpected situations
     #[error("Generic error: {0}")]
     AnyhowError(#[from] anyhow::Error),
+
+    /// Errors annotated with the location in a file where they occurred
+    #[error("{source} ({context})")]
+    Context {
+        /// Where the error occurred
+        context: ErrorContext,
+        /// The error that occurred
+        source: Box<Error>,
+    },
 }
 impl Error {
     /// Checks if the error is an index mismatch error
@@ -82,11 +122,124 @@ impl Error {
     /// * `true` if the error is an `IndexError::ByteSizeMismatch`
     /// * `false` for all other error types
     pub fn is_index_mismatch(&self) -> bool {
-        match self {
+        match self.root() {
             Self::IndexError(err) => err.is_mismatch(),
             _ => false,
         }
     }
+
+    /// Returns the underlying error without its context
+    ///
+    /// This is useful to match on the kind of an error regardless of whether it was
+    /// annotated with the location where it occurred.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use vbinseq::error::ReadError;
+    /// use vbinseq::{Error, MmapReader};
+    ///
+    /// match MmapReader::new("example.vbq") {
+    ///     Err(e) if matches!(e.root(), Error::ReadError(ReadError::EmptyFile)) => {
+    ///         println!("The file is empty");
+    ///     }
+    ///     _ => {}
+    /// }
+    /// ```
+    pub fn root(&self) -> &Error {
+        match self {
+            Self::Context { source, .. } => source.root(),
+            _ => self,
+        }
+    }
+
+    /// Returns the location where the error occurred (if known)
+    pub fn context(&self) -> Option<&ErrorContext> {
+        match self {
+            Self::Context { context, .. } => Some(context),
+            _ => None,
+        }
+    }
+
+    /// Annotates the error with the location where it occurred
+    ///
+    /// If the error already has a context, only the fields missing from it are filled in,
+    /// so the innermost (most precise) location is kept.
+    pub fn with_context(self, context: ErrorContext) -> Self {
+        match self {
+            Self::Context {
+                context: inner,
+                source,
+            } => Self::Context {
+                context: inner.or(context),
+                source,
+            },
+            source => Self::Context {
+                context,
+                source: Box::new(source),
+            },
+        }
+    }
+}
+
+/// Location in a VBINSEQ file where an error occurred
+///
+/// All fields are optional, as not every location is known wherever an error is raised
+/// (e.g. there is no path for files read from memory).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ErrorContext {
+    /// Path of the file
+    pub path: Option<PathBuf>,
+
+    /// Index of the block (0-based)
+    pub block: Option<usize>,
+
+    /// Byte offset of the block header in the file
+    pub offset: Option<u64>,
+
+    /// Index of the record being read when the error occurred (0-based, across the file)
+    pub record: Option<u64>,
+}
+impl ErrorContext {
+    /// Creates a context for a file
+    pub fn for_path<P: Into<PathBuf>>(path: P) -> Self {
+        Self {
+            path: Some(path.into()),
+            ..Default::default()
+        }
+    }
+
+    /// Fills the fields missing from this context with those of another
+    fn or(self, other: Self) -> Self {
+        Self {
+            path: self.path.or(other.path),
+            block: self.block.or(other.block),
+            offset: self.offset.or(other.offset),
+            record: self.record.or(other.record),
+        }
+    }
+}
+impl fmt::Display for ErrorContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+        if let Some(path) = &self.path {
+            parts.push(format!("file {}", path.display()));
+        }
+        if let Some(block) = self.block {
+            parts.push(format!("block {block}"));
+        }
+        if let Some(offset) = self.offset {
+            parts.push(format!("offset {offset}"));
+        }
+        if let Some(record) = self.record {
+            parts.push(format!("record {record}"));
+        }
+        if parts.is_empty() {
+            write!(f, "unknown location")
+        } else {
+            write!(f, "{}", parts.join(", "))
+        }
+    }
 }
 
 /// Errors that can occur during write operations to VBINSEQ files
@@ -121,6 +274,26 @@ pub enum WriteError {
     #[error("Invalid nucleotides found in sequence: {0}")]
     InvalidNucleotideSequence(String),
 
+    /// When invalid nucleotide characters are found in a sequence (with their positions)
+    ///
+    /// The parameter holds the 0-based positions of the invalid nucleotides
+    #[error("Invalid nucleotides found at positions {0:?}")]
+    InvalidNucleotidePositions(Vec<usize>),
+
+    /// When a pre-encoded sequence does not have the number of words its length requires
+    ///
+    /// The first parameter is the sequence length, the second is the number of words
+    #[error("Encoded sequence of {0} nucleotides cannot be stored in {1} 64-bit words")]
+    InvalidEncodedLength(u64, usize),
+
+    /// When the quality scores of a record differ in length from its sequence
+    ///
+    /// The first parameter is the index the record would have had in the file, the second
+    /// is its flag, the third is the sequence length, and the fourth is the number of
+    /// quality scores
+    #[error("Record {0} (flag {1}) has a sequence of length {2} but {3} quality scores")]
+    QualityLengthMismatch(u64, u64, usize, usize),
+
     /// When a header is not provided to the writer builder
     #[error("Missing header in writer builder")]
     MissingHeader,
@@ -138,6 +311,85 @@ pub enum WriteError {
     /// The first parameter is the expected header, the second is the found header
     #[error("Incompatible headers found in VBinseqWriter::ingest. Found ({1:?}) Expected ({0:?})")]
     IncompatibleHeaders(VBinseqHeader, VBinseqHeader),
+
+    /// When trying to write to a writer that has already been finished
+    #[error("Cannot write to a VBinseqWriter after it has been finished")]
+    WriterFinished,
+
+    /// When a record references a read group missing from the read-group table
+    ///
+    /// The first parameter is the referenced read group, the second is the number of
+    /// read groups in the table
+    #[error("Record references read group {0} but the file has {1} read groups")]
+    UnknownReadGroup(u16, usize),
+
+    /// When configuring direct I/O with a sector size that is not a power of two
+    ///
+    /// The parameter is the configured sector size
+    #[error("Sector size must be a power of two, found {0}")]
+    InvalidSectorSize(usize),
+
+    /// When ingesting a writer in order with a sequence number that was already used
+    ///
+    /// The parameter is the sequence number
+    #[error("Sequence number {0} was already ingested or is pending")]
+    DuplicateSequence(u64),
+
+    /// When finishing a writer while writers ingested in order wait for a missing sequence number
+    ///
+    /// The first parameter is the missing sequence number, the second is the number of
+    /// pending writers
+    #[error("Sequence number {0} was never ingested, {1} writers are pending")]
+    MissingSequence(u64, usize),
+
+    /// When setting an auxiliary value but the header specifies none are stored
+    #[error("Aux flag not set in header but trying to write auxiliary values.")]
+    AuxFlagNotSet,
+
+    /// When writing more compression dictionaries than a block header can reference
+    ///
+    /// The parameter is the number of dictionaries
+    #[error("At most 256 compression dictionaries are supported, found {0}")]
+    TooManyDictionaries(usize),
+
+    /// When copying a block stored with a codec the receiving file cannot hold
+    ///
+    /// The first parameter is the codec of the block, the second is the codec of the file
+    #[error("Block stored with codec {0} cannot be copied into a file with codec {1}")]
+    CodecMismatch(crate::Codec, crate::Codec),
+
+    /// When a dictionary selector picks a dictionary missing from the header sections
+    ///
+    /// The first parameter is the selected dictionary, the second is the number of
+    /// dictionaries
+    #[error("Selected dictionary {0} but the file has {1} dictionaries")]
+    UnknownDictionary(usize, usize),
+
+    /// When copying a block compressed with a dictionary that differs from the dictionary
+    /// of the same number in the receiving file
+    ///
+    /// The parameter is the dictionary of the block
+    #[error("Dictionary {0} of the block differs from dictionary {0} of the file")]
+    DictionaryMismatch(usize),
+
+    /// When writing a record whose length differs from the length of a fixed-length file
+    ///
+    /// The first parameter is the length of the file, the second is the length of the record
+    #[error("Fixed-length file has records of length {0}, found a record of length {1}")]
+    FixedLengthMismatch(u64, u64),
+
+    /// When a record has a different number of segments than the records of the file
+    ///
+    /// The first parameter is the number of segments of the file, the second is the number
+    /// of sequences (or quality scores) of the record
+    #[error("File has records of {0} segments, found a record of {1} segments")]
+    SegmentCountMismatch(usize, usize),
+
+    /// When simulation options cannot generate records
+    ///
+    /// The parameter describes the invalid option
+    #[error("Invalid simulation options: {0}")]
+    InvalidSimulationOptions(String),
 }
 
 /// Errors related to parsing and validating VBINSEQ file headers
@@ -157,9 +409,103 @@ pub enum HeaderError {
     #[error("Invalid format version: {0}")]
     InvalidFormatVersion(u8),
 
+    /// When the block size of a header is invalid (zero)
+    ///
+    /// The parameter is the rejected block size
+    #[error("Invalid block size: {0}")]
+    InvalidBlockSize(u64),
+
     /// When the reserved bytes section of the header is invalid
     #[error("Invalid reserved bytes")]
     InvalidReservedBytes,
+
+    /// When a block header records a codec unknown to this library
+    ///
+    /// The parameter is the unknown codec byte
+    #[error("Invalid codec: {0}")]
+    InvalidCodec(u8),
+
+    /// When a codec is known but support for it was not compiled in
+    ///
+    /// The parameter is the codec (see `Codec::feature` for the feature it requires)
+    #[error(
+        "Codec {codec} is not supported by this build (enable the `{feature}` feature)",
+        codec = .0,
+        feature = .0.feature().unwrap_or_default()
+    )]
+    UnsupportedCodec(crate::Codec),
+
+    /// When trying to claim the application region with the reserved placeholder id
+    ///
+    /// The parameter is the rejected application id
+    #[error("Invalid application id: {0:#06x} is reserved for unclaimed headers")]
+    InvalidAppId(u16),
+
+    /// When application data does not fit in the header's application region
+    ///
+    /// The first parameter is the size of the data, the second is the maximum size
+    #[error("Application data of {0} bytes exceeds the maximum of {1} bytes")]
+    AppDataTooLarge(usize, usize),
+
+    /// When the header uses format extensions unknown to this library
+    ///
+    /// The parameter is the set of unknown extension flags
+    #[error("Unsupported format extension flags: {0:#x}")]
+    UnsupportedFlags(u32),
+
+    /// When the header records an unknown quality score transform
+    ///
+    /// The parameter is the byte found in the header
+    #[error("Invalid quality transform: {0}")]
+    InvalidQualityTransform(u8),
+
+    /// When a header enables both quality scores and homopolymer compression
+    ///
+    /// Run lengths are stored in place of quality scores, so the two are exclusive
+    #[error("Homopolymer-compressed files cannot store quality scores")]
+    HomopolymerWithQuality,
+
+    /// When a header enables fixed-length records for records of varying layout
+    ///
+    /// Mates, homopolymer-compressed sequences and optional quality scores vary the layout
+    /// of records, so records cannot omit their lengths. Block checksums are stored in the
+    /// bytes of the block header holding the record length.
+    #[error("Fixed-length records cannot be paired, homopolymer-compressed, lack qualities or have block checksums")]
+    InvalidFixedLength,
+
+    /// When a header has an invalid number of segments per record
+    ///
+    /// The parameter is the number of segments. Records have 1 to `MAX_SEGMENTS` segments,
+    /// files counting them in the header at least 3, and homopolymer-compressed files at
+    /// most 2.
+    #[error("Invalid number of segments per record: {0}")]
+    InvalidSegments(usize),
+
+    /// When quality score bins are invalid
+    ///
+    /// The parameter holds the lower bound and representative score of every bin. Bins must
+    /// start at score 0, have increasing lower bounds, and representative scores of at most
+    /// 93.
+    #[error("Invalid quality bins: {0:?}")]
+    InvalidQualityBins(Vec<(u8, u8)>),
+
+    /// When a header embeds the block index but has no footer
+    ///
+    /// The footer records the size of the embedded index, so readers could not find it
+    #[error("Files with an embedded index must have a footer")]
+    EmbeddedIndexWithoutFooter,
+
+    /// When the header sections are too large to be recorded in the header
+    ///
+    /// The first parameter is the size of the sections, the second is the maximum size
+    #[error("Header sections of {0} bytes exceed the maximum of {1} bytes")]
+    SectionsTooLarge(usize, usize),
+
+    /// When the header sections following the header cannot be parsed
+    ///
+    /// The parameter is the offset of the invalid section within the sections
+    #[error("Invalid header section at offset {0}")]
+    InvalidSection(usize),
 }
 
 /// Errors related to VBINSEQ file indexing
@@ -185,6 +531,69 @@ pub enum IndexError {
     /// The first parameter is the actual file size, the second is the expected size
     #[error("Mismatch in size between upstream size: {0} and expected index size {1}")]
     ByteSizeMismatch(u64, u64),
+
+    /// When a block range has no BGZF virtual offset
+    ///
+    /// The parameter is the start offset of the block in the uncompressed file
+    #[error("Block at offset {0} has no BGZF virtual offset")]
+    MissingVirtualOffset(u64),
+
+    /// When a range of blocks extends beyond the blocks of a file
+    ///
+    /// The first two parameters are the start and end of the range, the third is the
+    /// number of blocks in the file
+    #[error("Block range {0}..{1} is out of bounds for a file with {2} blocks")]
+    BlockRangeOutOfBounds(usize, usize, usize),
+
+    /// When an index is required but no index file exists
+    ///
+    /// The parameter is the path of the missing index file
+    #[error("Index file not found: {0}")]
+    MissingIndexFile(String),
+
+    /// When the record counts of the index disagree with the file
+    ///
+    /// The first parameter describes what was counted, the second is the count of the
+    /// index, the third is the count of the file (from its block headers or footer)
+    #[error("Index has {1} {0} but the file has {2}")]
+    RecordCountMismatch(String, u64, u64),
+
+    /// When a block range of the index doesn't point to a block header of the file
+    ///
+    /// The parameter is the start offset of the block range
+    #[error("Index has a block at offset {0} which is not a block of the file")]
+    InvalidBlockOffset(u64),
+
+    /// When a byte range doesn't start and end at block boundaries of the file
+    ///
+    /// The first parameter is the offset of the range, the second is its length
+    #[error("Byte range of {1} bytes at offset {0} is not aligned to the blocks of the file")]
+    UnalignedRange(u64, u64),
+
+    /// When a record index is required but the index has no record offsets
+    ///
+    /// This is a mismatch, so the index is rebuilt with record offsets unless the index
+    /// policy requires a valid index file (see `MmapReader::set_record_index`).
+    #[error("Index has no record offsets")]
+    MissingRecordOffsets,
+
+    /// When a record starts beyond the offsets a record index can store
+    ///
+    /// The parameter is the offset of the record in its decompressed block
+    #[error("Record offset {0} exceeds the 32-bit offsets of record indexes")]
+    RecordOffsetOverflow(u64),
+
+    /// When the block index is loaded from a file without an embedded index
+    ///
+    /// See `VBinseqHeader::set_embedded_index`
+    #[error("File has no embedded index")]
+    MissingEmbeddedIndex,
+
+    /// When the embedded index of a file is missing or corrupt
+    ///
+    /// The parameter is the position of the footer recording the size of the index
+    #[error("Embedded index before the footer at position {0} is missing or corrupt")]
+    InvalidEmbeddedIndex(u64),
 }
 
 impl IndexError {
@@ -194,13 +603,50 @@ impl IndexError {
     ///
     /// # Returns
     ///
-    /// * `true` for `ByteSizeMismatch` errors
-    /// * `true` for any other error type (this behavior is likely a bug and should be fixed)
+    /// * `true` for `ByteSizeMismatch`, `RecordCountMismatch`, and `InvalidBlockOffset`
+    ///   errors, i.e. the index was built for another version of the file, and for
+    ///   `MissingRecordOffsets` errors, i.e. the index was built without record offsets
+    /// * `false` for any other error type (e.g. a corrupt index file)
     pub fn is_mismatch(&self) -> bool {
-        matches!(self, Self::ByteSizeMismatch(_, _) | _) // Note: this appears to always return true regardless of error type
+        matches!(
+            self,
+            Self::ByteSizeMismatch(_, _)
+                | Self::RecordCountMismatch(_, _, _)
+                | Self::InvalidBlockOffset(_)
+                | Self::MissingRecordOffsets
+        )
     }
 }
 
+/// Errors related to dataset manifests and their shards
+#[derive(thiserror::Error, Debug)]
+pub enum DatasetError {
+    /// When the first line of a manifest is not a supported manifest header
+    ///
+    /// The parameter is the first line of the manifest
+    #[error("Invalid or unsupported manifest header: {0}")]
+    InvalidManifestHeader(String),
+
+    /// When a line of a manifest cannot be parsed
+    ///
+    /// The parameter is the 1-based line number
+    #[error("Invalid manifest line: {0}")]
+    InvalidManifestLine(usize),
+
+    /// When a metadata key, value, or shard path cannot be stored in a manifest
+    /// (it contains a tab or a line break)
+    ///
+    /// The parameter is the offending field
+    #[error("Manifest fields cannot contain tabs or line breaks: {0:?}")]
+    InvalidManifestField(String),
+
+    /// When the header of a shard is incompatible with the first shard of the dataset
+    ///
+    /// The parameter is the path of the incompatible shard
+    #[error("Shard {0} is incompatible with the first shard (block size, quality, or paired flags differ)")]
+    IncompatibleShard(String),
+}
+
 /// Errors that can occur during read operations from VBINSEQ files
 ///
 /// These errors typically occur when there are issues with the file format or
@@ -211,6 +657,10 @@ pub enum ReadError {
     #[error("Unexpected file metadata")]
     InvalidFileType,
 
+    /// When the file is empty (zero bytes), i.e. not even a header was written
+    #[error("File is empty (expected a VBINSEQ header)")]
+    EmptyFile,
+
     /// When a block header contains an invalid magic number
     ///
     /// The first parameter is the invalid magic number, the second is the position in the file
@@ -222,4 +672,130 @@ pub enum ReadError {
     /// The parameter is the position in the file where the read was attempted
     #[error("Unable to find an expected full block at position {0}")]
     UnexpectedEndOfFile(usize),
+
+    /// When the number of records parsed from a block differs from its block header
+    ///
+    /// The first parameter is the declared record count, the second is the number of
+    /// records parsed from the block data
+    #[error("Block header declares {0} records but {1} records were parsed (the quality or paired flags of the file header may not match the data)")]
+    RecordCountMismatch(u32, usize),
+
+    /// When a block contains data after its last record other than zero padding
+    ///
+    /// The parameter is the offset of the unexpected data within the (decompressed) block
+    #[error("Unexpected data at offset {0} of a block after its last record (the quality flag of the file header may not match the data)")]
+    UnexpectedBlockData(usize),
+
+    /// When the lengths of a record exceed the data remaining in its block
+    ///
+    /// The first parameter is the offset of the record within the (decompressed) block,
+    /// the second and third are its primary and extended sequence lengths
+    #[error(
+        "Record at offset {0} of a block has lengths ({1}, {2}) exceeding the remaining block data"
+    )]
+    InvalidRecordLength(usize, u64, u64),
+
+    /// When the footer contains an invalid magic number
+    ///
+    /// The first parameter is the invalid magic number, the second is the position in the file
+    #[error("Unexpected Footer Magic Number found: {0} at position {1}")]
+    InvalidFooterMagicNumber(u64, usize),
+
+    /// When an operation requires a footer but the file was written without one
+    #[error("File does not have a footer")]
+    MissingFooter,
+
+    /// When a block does not match the checksum recorded in its block header
+    ///
+    /// The first parameter is the recorded checksum, the second is the checksum of the
+    /// (decompressed) block data
+    #[error("Block checksum mismatch: expected {0:#018x} but found {1:#018x}")]
+    BlockChecksumMismatch(u64, u64),
+
+    /// When a record extends beyond the bounds of its block
+    ///
+    /// The parameter is the position of the record within the decoded block
+    #[error("Record at block position {0} extends beyond the block")]
+    TruncatedRecord(usize),
+
+    /// When a FASTA file contains sequence data before the first header line
+    ///
+    /// The parameter is the line number of the offending line
+    #[error("Sequence data without a preceding FASTA header at line {0}")]
+    InvalidFastaLine(usize),
+
+    /// When a paired conversion encounters a record that is not part of a mate pair
+    ///
+    /// The parameter is the 0-based ordinal of the offending input record
+    #[error("Input record {0} is not part of an adjacent mate pair")]
+    InvalidMatePair(u64),
+
+    /// When the run lengths of a homopolymer-compressed sequence do not match it
+    ///
+    /// The first parameter is the number of nucleotides, the second is the number of runs
+    #[error("Homopolymer-compressed sequence of {0} nucleotides has {1} invalid run lengths")]
+    InvalidHomopolymerRuns(usize, usize),
+
+    /// When a 4-bit encoded sequence has fewer words than its length requires
+    ///
+    /// The first parameter is the number of nucleotides, the second is the number of words
+    #[error("4-bit sequence of {0} nucleotides cannot be decoded from {1} 64-bit words")]
+    InvalidIupacSequence(usize, usize),
+
+    /// A paired sequence was required but the record has no extended sequence
+    ///
+    /// The parameter is the global index of the record
+    #[error("Record {0} has no extended sequence")]
+    UnpairedRecord(u64),
+
+    /// The encoded sequence buffer of a record does not match the sequence length
+    ///
+    /// The first parameter is the global index of the record, the second is the sequence
+    /// length, and the third is the number of encoded 64-bit words
+    #[error("Record {0} has a sequence of {1} nucleotides but {2} encoded words")]
+    SequenceBufferMismatch(u64, u64, usize),
+
+    /// The quality scores of a record do not match its sequence length
+    ///
+    /// The first parameter is the global index of the record, the second is the number of
+    /// quality scores, and the third is the sequence length
+    #[error("Record {0} has {1} quality scores for a sequence of {2} nucleotides")]
+    QualityLengthMismatch(u64, usize, u64),
+
+    /// A record of a file without paired records has an extended sequence
+    ///
+    /// The parameter is the global index of the record
+    #[error("Record {0} has an extended sequence but the file is not paired")]
+    UnexpectedExtendedSequence(u64),
+
+    /// A record flag was rejected by a flag check
+    ///
+    /// The first parameter is the global index of the record, the second is its flag
+    #[error("Record {0} has an invalid flag: {1}")]
+    InvalidFlag(u64, u64),
+
+    /// A block was compressed with a dictionary missing from the header sections
+    ///
+    /// The parameter is the dictionary recorded in the block header
+    #[error("Block was compressed with dictionary {0}, which is missing from the file")]
+    MissingDictionary(u8),
+
+    /// When a tombstone file doesn't start with the expected magic number
+    ///
+    /// The parameter is the invalid magic number that was found
+    #[error("Invalid tombstone file magic number: {0}")]
+    InvalidTombstoneMagic(u64),
+
+    /// When a tombstone file was written for another version of its file
+    ///
+    /// The first parameter is the size of the file, the second is the size recorded in
+    /// the tombstone file
+    #[error("Tombstones were recorded for a file of {1} bytes but the file has {0} bytes")]
+    StaleTombstones(u64, u64),
+
+    /// When a record index is past the last record of a file
+    ///
+    /// The first parameter is the record index, the second is the number of records
+    #[error("Record {0} is out of range for a file with {1} records")]
+    RecordOutOfRange(u64, u64),
 }
@@ -0,0 +1,152 @@
+//! # Record Filtering
+//!
+//! This module copies the records of a VBINSEQ file selected by their flags into a new
+//! file. It requires the `mmap` feature.
+//!
+//! Selection only looks at the record preambles, and selected records are re-emitted in
+//! their 2-bit encoded form (see `VBinseqWriter::write_encoded`), so no sequence is ever
+//! decoded to ASCII and re-encoded. The selected records are packed into fresh blocks of
+//! the block size of the input.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use vbinseq::filter;
+//!
+//! // Keep the records with the lowest flag bit set
+//! let n_selected = filter::by_flag("reads.vbq", "selected.vbq", |flag| flag & 1 == 1).unwrap();
+//! println!("Selected {n_selected} records");
+//! ```
+
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+use crate::{MmapReader, Result, VBinseqWriterBuilder};
+
+/// Copies the records whose flag matches a predicate into a new file
+///
+/// The output has the header of the input (including its block size and whether it has a
+/// footer) and holds the selected records in their original order.
+///
+/// # Parameters
+///
+/// * `path` - Path to the VBINSEQ file to copy the records from
+/// * `output` - Path of the file to write
+/// * `predicate` - Called with the flag of every record, returns true to select the record
+///
+/// # Returns
+///
+/// The number of selected records
+///
+/// # Errors
+///
+/// * I/O errors from reading the input or writing the output
+/// * Parsing errors if the input has an invalid format
+pub fn by_flag<P, Q, F>(path: P, output: Q, mut predicate: F) -> Result<u64>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+    F: FnMut(u64) -> bool,
+{
+    let mut reader = MmapReader::new(path)?;
+    let header = reader.header();
+    let mut writer = VBinseqWriterBuilder::default()
+        .header(header)
+        .sections(reader.sections()?)
+        .build(File::create(output).map(BufWriter::new)?)?;
+
+    let mut block = reader.new_block();
+    let mut n_selected = 0;
+    while reader.read_block_into(&mut block)? {
+        for record in block.iter().filter(|record| predicate(record.flag())) {
+            if header.segments() > 2 {
+                writer.write_stored(&record)?;
+            } else if header.paired() {
+                writer.write_encoded_paired(
+                    record.flag(),
+                    record.slen(),
+                    record.sbuf(),
+                    record.squal(),
+                    record.xlen(),
+                    record.xbuf(),
+                    record.xqual(),
+                )?;
+            } else {
+                writer.write_encoded(
+                    record.flag(),
+                    record.slen(),
+                    record.sbuf(),
+                    record.squal(),
+                )?;
+            }
+            n_selected += 1;
+        }
+    }
+    writer.finish()?;
+    Ok(n_selected)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MemoryReader, OwnedRecord, VBinseqHeader};
+
+    /// Reads all records of a file
+    fn read_records(path: &Path) -> Result<Vec<OwnedRecord>> {
+        let mut reader = MemoryReader::new(std::fs::read(path)?)?;
+        let mut block = reader.new_block();
+        let mut records = Vec::new();
+        while reader.read_block_into(&mut block)? {
+            for ref_record in block.iter() {
+                let mut record = OwnedRecord::default();
+                record.fill(&ref_record)?;
+                records.push(record);
+            }
+        }
+        Ok(records)
+    }
+
+    #[test]
+    fn test_by_flag() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("vbq_filter_{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join("reads.vbq");
+        let output = dir.join("selected.vbq");
+
+        let header = VBinseqHeader::with_capacity(512, true, true, true);
+        let mut writer = VBinseqWriterBuilder::default()
+            .header(header)
+            .build(File::create(&path)?)?;
+        for flag in 0..1000 {
+            let sequence = b"ACGTTGCAGT".repeat(1 + flag as usize % 7);
+            let mate = b"TTGCA".repeat(1 + flag as usize % 3);
+            writer.write_nucleotides_quality_paired(
+                flag,
+                &sequence,
+                &mate,
+                &vec![b'A' + (flag % 20) as u8; sequence.len()],
+                &vec![b'I'; mate.len()],
+            )?;
+        }
+        writer.finish()?;
+        drop(writer);
+
+        let n_selected = by_flag(&path, &output, |flag| flag % 3 == 0)?;
+        assert_eq!(n_selected, 334);
+
+        let records = read_records(&path)?;
+        let selected = read_records(&output)?;
+        assert_eq!(selected.len(), 334);
+        for (record, expected) in selected.iter().zip(records.iter().step_by(3)) {
+            assert_eq!(record.flag(), expected.flag());
+            assert_eq!(record.seq(), expected.seq());
+            assert_eq!(record.xseq(), expected.xseq());
+            assert_eq!(record.squal(), expected.squal());
+            assert_eq!(record.xqual(), expected.xqual());
+        }
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
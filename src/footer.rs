@@ -0,0 +1,681 @@
+//! # File Footer
+//!
+//! Files whose header has the footer extension enabled (see `VBinseqHeader::set_footer`)
+//! end with a fixed-size footer written by the writer at `finish()`. The footer records the
+//! number of blocks and records in the file along with a digest of its logical content.
+//!
+//! The content digest only depends on the records and their order, not on how the blocks
+//! are stored: re-compressing a file (or storing some blocks uncompressed) does not change
+//! its digest. This allows archives to be audited without comparing files byte-for-byte.
+//!
+//! The digest is the XXH3-128 hash of the sequence of per-record XXH3-128 digests (as
+//! little-endian bytes), where each record digest covers the uncompressed bytes of the
+//! record (flag, lengths, encoded sequences, and quality scores). Block padding is not
+//! included.
+//!
+//! ## Footer Layout (64 bytes)
+//!
+//! | Offset | Size | Field       | Description                          |
+//! |--------|------|-------------|--------------------------------------|
+//! | 0      | 8    | magic       | "VBQFOOTR"                           |
+//! | 8      | 8    | n_blocks    | Number of record blocks              |
+//! | 16     | 8    | n_records   | Number of records                    |
+//! | 24     | 16   | digest      | Content digest (XXH3-128)            |
+//! | 40     | 24   | reserved    | Reserved for future extensions       |
+//!
+//! Files with an embedded index (see `VBinseqHeader::set_embedded_index`) store the index
+//! between the last record block and the footer, and its size in bytes in the first 8
+//! reserved bytes (little-endian). Since the size is relative to the footer, the index is
+//! found at the end of a file even when it is the last member of a concatenated stream.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use vbinseq::footer;
+//!
+//! // Recompute the content digest and compare it to the one stored in the footer
+//! if footer::verify("example.vbq").unwrap() {
+//!     println!("Content digest matches");
+//! }
+//! ```
+
+use std::io::Write;
+use std::path::Path;
+
+use byteorder::{ByteOrder, LittleEndian};
+use xxhash_rust::xxh3::{xxh3_128, Xxh3Default};
+
+use crate::error::{IndexError, ReadError, Result};
+use crate::header::{BlockHeader, Codec, RECORD_NO_QUALITY, SIZE_BLOCK_HEADER, SIZE_HEADER};
+use crate::reader::load_file;
+use crate::VBinseqHeader;
+use crate::{codec, columnar, dictionary};
+
+/// Magic number for footer identification: "VBQFOOTR" in ASCII (0x52544F4F46514256)
+pub(crate) const FOOTER_MAGIC: u64 = 0x52544F4F46514256;
+
+/// Size of the footer in bytes (64 bytes)
+pub const SIZE_FOOTER: usize = 64;
+
+/// Reserved bytes for future use in the footer (24 bytes)
+///
+/// These bytes are set to a placeholder value (42) and reserved for future extensions.
+pub const RESERVED_BYTES_FOOTER: [u8; 24] = [42; 24];
+
+/// Footer of a VBINSEQ file
+///
+/// The footer is the last `SIZE_FOOTER` bytes of files whose header has the footer
+/// extension enabled.
+///
+/// # Fields
+///
+/// * `magic` - Magic number to validate the footer ("VBQFOOTR", 8 bytes)
+/// * `n_blocks` - Number of record blocks in the file (8 bytes)
+/// * `n_records` - Number of records in the file (8 bytes)
+/// * `digest` - Digest of the logical content of the file (16 bytes)
+/// * `reserved` - Reserved bytes for future extensions (24 bytes)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Footer {
+    /// Magic number to identify the footer ("VBQFOOTR")
+    pub magic: u64,
+
+    /// Number of record blocks in the file
+    pub n_blocks: u64,
+
+    /// Number of records in the file
+    pub n_records: u64,
+
+    /// XXH3-128 digest of the logical content of the file
+    pub digest: u128,
+
+    /// Reserved bytes for future extensions
+    pub reserved: [u8; 24],
+}
+impl Footer {
+    /// Creates a new footer
+    ///
+    /// # Parameters
+    ///
+    /// * `n_blocks` - Number of record blocks in the file
+    /// * `n_records` - Number of records in the file
+    /// * `digest` - Content digest of the file
+    pub fn new(n_blocks: u64, n_records: u64, digest: u128) -> Self {
+        Self {
+            magic: FOOTER_MAGIC,
+            n_blocks,
+            n_records,
+            digest,
+            reserved: RESERVED_BYTES_FOOTER,
+        }
+    }
+
+    /// Creates a footer from a 64-byte buffer
+    ///
+    /// # Parameters
+    ///
+    /// * `buffer` - A 64-byte array containing the footer data
+    /// * `pos` - Position of the footer in the file (used for error reporting)
+    ///
+    /// # Errors
+    ///
+    /// * `ReadError::InvalidFooterMagicNumber` - If the magic number doesn't match "VBQFOOTR"
+    pub fn from_bytes(buffer: &[u8; SIZE_FOOTER], pos: usize) -> Result<Self> {
+        let magic = LittleEndian::read_u64(&buffer[0..8]);
+        if magic != FOOTER_MAGIC {
+            return Err(ReadError::InvalidFooterMagicNumber(magic, pos).into());
+        }
+        let mut reserved = RESERVED_BYTES_FOOTER;
+        reserved.copy_from_slice(&buffer[40..]);
+        Ok(Self {
+            magic,
+            n_blocks: LittleEndian::read_u64(&buffer[8..16]),
+            n_records: LittleEndian::read_u64(&buffer[16..24]),
+            digest: LittleEndian::read_u128(&buffer[24..40]),
+            reserved,
+        })
+    }
+
+    /// Writes the footer to a writer
+    ///
+    /// # Parameters
+    ///
+    /// * `writer` - Any type that implements the `Write` trait
+    pub fn write_bytes<W: Write>(&self, writer: &mut W) -> Result<()> {
+        let mut buffer = [0u8; SIZE_FOOTER];
+        LittleEndian::write_u64(&mut buffer[0..8], self.magic);
+        LittleEndian::write_u64(&mut buffer[8..16], self.n_blocks);
+        LittleEndian::write_u64(&mut buffer[16..24], self.n_records);
+        LittleEndian::write_u128(&mut buffer[24..40], self.digest);
+        buffer[40..].copy_from_slice(&self.reserved);
+        writer.write_all(&buffer)?;
+        Ok(())
+    }
+
+    /// Returns the size of the embedded index preceding the footer in bytes
+    ///
+    /// Returns `None` if the file has no embedded index (see
+    /// `VBinseqHeader::set_embedded_index`).
+    pub fn index_size(&self) -> Option<u64> {
+        (self.reserved[..8] != RESERVED_BYTES_FOOTER[..8])
+            .then(|| LittleEndian::read_u64(&self.reserved[..8]))
+    }
+
+    /// Records the size of the embedded index preceding the footer
+    ///
+    /// # Parameters
+    ///
+    /// * `size` - The size of the embedded index in bytes
+    pub(crate) fn set_index_size(&mut self, size: u64) {
+        LittleEndian::write_u64(&mut self.reserved[..8], size);
+    }
+
+    /// Returns whether two footers describe the same file content
+    ///
+    /// Only the counts and the content digest are compared.
+    pub fn matches(&self, other: &Footer) -> bool {
+        self.n_blocks == other.n_blocks
+            && self.n_records == other.n_records
+            && self.digest == other.digest
+    }
+
+    /// Reads the footer from the full contents of a VBINSEQ file
+    ///
+    /// # Parameters
+    ///
+    /// * `bytes` - The full contents of the file (e.g. a memory map)
+    /// * `header` - The header of the file
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Some(Footer))` - If the header announces a footer and it is valid
+    /// * `Ok(None)` - If the file was written without a footer
+    ///
+    /// # Errors
+    ///
+    /// * `ReadError::UnexpectedEndOfFile` - If the file is too small to hold a footer
+    /// * `ReadError::InvalidFooterMagicNumber` - If the footer is missing or corrupted
+    pub fn from_file_bytes(bytes: &[u8], header: &VBinseqHeader) -> Result<Option<Self>> {
+        if !header.has_footer() {
+            return Ok(None);
+        }
+        if bytes.len() < header.data_offset() + SIZE_FOOTER {
+            return Err(ReadError::UnexpectedEndOfFile(bytes.len()).into());
+        }
+        let pos = bytes.len() - SIZE_FOOTER;
+        let mut footer_bytes = [0u8; SIZE_FOOTER];
+        footer_bytes.copy_from_slice(&bytes[pos..]);
+        Self::from_bytes(&footer_bytes, pos).map(Some)
+    }
+}
+
+/// Returns the position in the file where the record blocks end
+///
+/// This is the start of the footer if the file has one, or the end of the file otherwise.
+pub(crate) fn data_end(bytes: &[u8], header: &VBinseqHeader) -> Result<usize> {
+    match Footer::from_file_bytes(bytes, header)? {
+        Some(footer) if header.has_embedded_index() => {
+            let pos = bytes.len() - SIZE_FOOTER;
+            footer
+                .index_size()
+                .and_then(|size| pos.checked_sub(usize::try_from(size).ok()?))
+                .filter(|&start| start >= header.data_offset())
+                .ok_or_else(|| IndexError::InvalidEmbeddedIndex(pos as u64).into())
+        }
+        Some(_) => Ok(bytes.len() - SIZE_FOOTER),
+        None => Ok(bytes.len()),
+    }
+}
+
+/// Running content digest of a VBINSEQ file
+///
+/// Writers feed the records of every block they flush into the hasher. Headless writers
+/// retain the record digests instead of hashing them, so that the writer ingesting their
+/// blocks can hash them in file order.
+#[derive(Clone)]
+pub(crate) struct ContentHasher {
+    /// Streaming hasher over the record digests
+    hasher: Xxh3Default,
+    /// Record digests retained for ingestion
+    retained: Vec<u128>,
+    /// Whether record digests are retained instead of hashed
+    retain: bool,
+    /// Number of blocks seen
+    n_blocks: u64,
+    /// Number of records seen
+    n_records: u64,
+    /// Stored size and number of records of every block seen
+    /// None unless the blocks are indexed (for an embedded index)
+    blocks: Option<Vec<(u64, u32)>>,
+}
+impl ContentHasher {
+    pub(crate) fn new(retain: bool) -> Self {
+        Self {
+            hasher: Xxh3Default::new(),
+            retained: Vec::new(),
+            retain,
+            n_blocks: 0,
+            n_records: 0,
+            blocks: None,
+        }
+    }
+
+    /// Creates the hasher of a file written with a header, if the file has a footer
+    ///
+    /// Files with an embedded index also have the size of every block recorded.
+    pub(crate) fn for_header(header: &VBinseqHeader, retain: bool) -> Option<Self> {
+        header.has_footer().then(|| Self {
+            blocks: header.has_embedded_index().then(Vec::new),
+            ..Self::new(retain)
+        })
+    }
+
+    /// Adds the records of a block to the digest
+    ///
+    /// The stored size of the block is set with `set_block_size` once it is known.
+    pub(crate) fn update_block<'a>(&mut self, records: impl IntoIterator<Item = &'a [u8]>) {
+        let mut n_records = 0;
+        for record in records {
+            self.push(xxh3_128(record));
+            n_records += 1;
+        }
+        self.n_records += u64::from(n_records);
+        self.n_blocks += 1;
+        if let Some(blocks) = &mut self.blocks {
+            blocks.push((0, n_records));
+        }
+    }
+
+    /// Sets the stored size of the last block added to the digest
+    pub(crate) fn set_block_size(&mut self, size: u64) {
+        if let Some((block_size, _)) = self.blocks.as_mut().and_then(|blocks| blocks.last_mut()) {
+            *block_size = size;
+        }
+    }
+
+    /// Takes over the blocks seen by another hasher
+    pub(crate) fn absorb(&mut self, other: &mut Self) {
+        for digest in std::mem::take(&mut other.retained) {
+            self.push(digest);
+        }
+        self.n_blocks += std::mem::take(&mut other.n_blocks);
+        self.n_records += std::mem::take(&mut other.n_records);
+        if let (Some(blocks), Some(other_blocks)) = (&mut self.blocks, &mut other.blocks) {
+            blocks.append(other_blocks);
+        }
+    }
+
+    /// Returns the stored size and number of records of every block seen
+    ///
+    /// Returns `None` unless the hasher records the blocks (see `for_header`).
+    pub(crate) fn blocks(&self) -> Option<&[(u64, u32)]> {
+        self.blocks.as_deref()
+    }
+
+    /// Builds the footer describing all blocks seen so far
+    pub(crate) fn footer(&self) -> Footer {
+        Footer::new(self.n_blocks, self.n_records, self.hasher.digest128())
+    }
+
+    fn push(&mut self, digest: u128) {
+        if self.retain {
+            self.retained.push(digest);
+        } else {
+            self.hasher.update(&digest.to_le_bytes());
+        }
+    }
+}
+
+/// Returns the size in bytes of the record starting at `pos` of a decoded block
+///
+/// Records of fixed-length files omit their lengths, which are taken from the block header.
+pub(crate) fn record_size(
+    bytes: &[u8],
+    pos: usize,
+    header: &VBinseqHeader,
+    block_header: &BlockHeader,
+) -> Result<usize> {
+    let preamble = 8 + header.record_lengths();
+    if pos + preamble > bytes.len() {
+        return Err(ReadError::TruncatedRecord(pos).into());
+    }
+    let mut has_base_bytes = header.has_base_bytes();
+    let (mut words, mut bases) = (0u64, 0u64);
+    let per_word = if header.is_iupac() { 16 } else { 32 };
+    let mut add_segment = |len: u64| {
+        words = words.saturating_add(len.div_ceil(per_word));
+        bases = bases.saturating_add(len);
+    };
+    if header.is_fixed_length() {
+        add_segment(block_header.fixed_length());
+    } else {
+        let lens = bytes[pos + 8..pos + preamble].chunks_exact(8);
+        for (i, mut len) in lens.map(LittleEndian::read_u64).enumerate() {
+            if i == 0 && header.marks_quality() {
+                has_base_bytes = len & RECORD_NO_QUALITY == 0;
+                len &= !RECORD_NO_QUALITY;
+            }
+            add_segment(len);
+        }
+    }
+    let mut size =
+        ((preamble + header.record_trailer()) as u64).saturating_add(words.saturating_mul(8));
+    if has_base_bytes {
+        size = size.saturating_add(bases);
+    }
+    if pos as u64 + size > bytes.len() as u64 {
+        return Err(ReadError::TruncatedRecord(pos).into());
+    }
+    Ok(size as usize)
+}
+
+/// Recomputes the footer of a file from its record blocks
+///
+/// # Parameters
+///
+/// * `bytes` - The full contents of the file (e.g. a memory map)
+/// * `header` - The header of the file
+pub(crate) fn compute_footer(bytes: &[u8], header: &VBinseqHeader) -> Result<Footer> {
+    let end = data_end(bytes, header)?;
+    let dictionaries = dictionary::load(bytes, header)?;
+    let mut hasher = ContentHasher::new(false);
+    let mut dbuf = Vec::with_capacity(header.block() as usize);
+    let mut pos = header.data_offset();
+    while pos < end {
+        if pos + SIZE_BLOCK_HEADER > end {
+            return Err(ReadError::UnexpectedEndOfFile(pos).into());
+        }
+        let mut header_bytes = [0u8; SIZE_BLOCK_HEADER];
+        header_bytes.copy_from_slice(&bytes[pos..pos + SIZE_BLOCK_HEADER]);
+        let block_header = BlockHeader::from_bytes(&header_bytes)?;
+        pos += SIZE_BLOCK_HEADER;
+
+        let size = block_header.size as usize;
+        if pos + size > end {
+            return Err(ReadError::UnexpectedEndOfFile(pos).into());
+        }
+        hash_block(
+            &mut hasher,
+            &block_header,
+            &bytes[pos..pos + size],
+            header,
+            &dictionaries,
+            &mut dbuf,
+        )?;
+        pos += size;
+    }
+    Ok(hasher.footer())
+}
+
+/// Adds the records of a stored block to a content digest
+///
+/// # Parameters
+///
+/// * `hasher` - The content digest to update
+/// * `block_header` - The header of the block
+/// * `data` - The stored (possibly compressed) bytes of the block
+/// * `header` - The header of the file the block belongs to
+/// * `dictionaries` - The compression dictionaries of the file
+/// * `dbuf` - Reusable buffer for decompressing the block
+pub(crate) fn hash_block(
+    hasher: &mut ContentHasher,
+    block_header: &BlockHeader,
+    data: &[u8],
+    header: &VBinseqHeader,
+    dictionaries: &[Vec<u8>],
+    dbuf: &mut Vec<u8>,
+) -> Result<()> {
+    hasher.update_block(block_records(
+        block_header,
+        data,
+        header,
+        dictionaries,
+        dbuf,
+    )?);
+    hasher.set_block_size(block_header.size);
+    Ok(())
+}
+
+/// Splits a stored block into the stored bytes of its records
+///
+/// # Parameters
+///
+/// * `block_header` - The header of the block
+/// * `data` - The stored (possibly compressed) bytes of the block
+/// * `header` - The header of the file the block belongs to
+/// * `dictionaries` - The compression dictionaries of the file
+/// * `dbuf` - Reusable buffer for decompressing the block
+pub(crate) fn block_records<'a>(
+    block_header: &BlockHeader,
+    data: &'a [u8],
+    header: &VBinseqHeader,
+    dictionaries: &[Vec<u8>],
+    dbuf: &'a mut Vec<u8>,
+) -> Result<Vec<&'a [u8]>> {
+    let block = decode_block(block_header, data, header, dictionaries, dbuf)?;
+    let mut records = Vec::with_capacity(block_header.records as usize);
+    let mut rpos = 0;
+    for _ in 0..block_header.records {
+        let rsize = record_size(block, rpos, header, block_header)?;
+        records.push(&block[rpos..rpos + rsize]);
+        rpos += rsize;
+    }
+    Ok(records)
+}
+
+/// Returns the decompressed bytes of a block
+///
+/// Uncompressed blocks are returned as-is, compressed blocks are decompressed into `dbuf`.
+/// Blocks of columnar files are returned in the row layout (see the `columnar` module).
+///
+/// # Parameters
+///
+/// * `block_header` - The header of the block
+/// * `data` - The stored (possibly compressed) bytes of the block
+/// * `header` - The header of the file the block belongs to
+/// * `dictionaries` - The compression dictionaries of the file
+/// * `dbuf` - Reusable buffer for decompressing the block
+pub(crate) fn decode_block<'a>(
+    block_header: &BlockHeader,
+    data: &'a [u8],
+    header: &VBinseqHeader,
+    dictionaries: &[Vec<u8>],
+    dbuf: &'a mut Vec<u8>,
+) -> Result<&'a [u8]> {
+    let codec = block_header.codec()?.unwrap_or(header.codec());
+    if codec != Codec::Uncompressed {
+        decompress_block(codec, block_header, data, header, dictionaries, dbuf)?;
+    }
+    if header.is_columnar() {
+        let block = if codec == Codec::Uncompressed {
+            data
+        } else {
+            dbuf.as_slice()
+        };
+        let mut rows = Vec::new();
+        columnar::to_rows(block, header, block_header, &mut rows)?;
+        *dbuf = rows;
+    } else if codec == Codec::Uncompressed {
+        return Ok(data);
+    }
+    Ok(dbuf.as_slice())
+}
+
+/// Decompresses the bytes of a compressed block into `dbuf`
+fn decompress_block(
+    codec: Codec,
+    block_header: &BlockHeader,
+    data: &[u8],
+    header: &VBinseqHeader,
+    dictionaries: &[Vec<u8>],
+    dbuf: &mut Vec<u8>,
+) -> Result<()> {
+    match codec {
+        #[cfg(feature = "compression")]
+        Codec::Zstd => {
+            dbuf.clear();
+            match dictionary::block_dictionary(block_header, dictionaries)? {
+                Some(dictionary) => {
+                    let mut decoder = zstd::stream::Decoder::with_dictionary(data, dictionary)?;
+                    std::io::Read::read_to_end(&mut decoder, dbuf)?;
+                }
+                None => zstd::stream::copy_decode(data, &mut *dbuf)?,
+            }
+        }
+        codec => {
+            #[cfg(not(feature = "compression"))]
+            let _ = (block_header, dictionaries);
+            dbuf.resize(header.block() as usize, 0);
+            let size = codec::decompress(codec, data, dbuf)?;
+            dbuf.truncate(size);
+        }
+    }
+    Ok(())
+}
+
+/// Recomputes the content digest of a VBINSEQ file
+///
+/// This works for files with or without a footer, so it can be used to compare the
+/// content of files written with different settings.
+///
+/// # Parameters
+///
+/// * `path` - Path to the VBINSEQ file
+///
+/// # Returns
+///
+/// * `Ok(u128)` - The content digest of the file
+/// * `Err(_)` - If the file could not be read or has an invalid format
+pub fn content_digest<P: AsRef<Path>>(path: P) -> Result<u128> {
+    let mmap = load_file(path)?;
+    let header = read_header(&mmap)?;
+    Ok(compute_footer(&mmap, &header)?.digest)
+}
+
+/// Verifies the content of a VBINSEQ file against its footer
+///
+/// The content digest and the block and record counts are recomputed from the record
+/// blocks and compared to the values recorded in the footer.
+///
+/// # Parameters
+///
+/// * `path` - Path to the VBINSEQ file
+///
+/// # Returns
+///
+/// * `Ok(true)` - If the content matches the footer
+/// * `Ok(false)` - If the content differs from the footer
+///
+/// # Errors
+///
+/// * `ReadError::MissingFooter` - If the file was written without a footer
+/// * Any error encountered while reading the file
+pub fn verify<P: AsRef<Path>>(path: P) -> Result<bool> {
+    let mmap = load_file(path)?;
+    let header = read_header(&mmap)?;
+    let Some(footer) = Footer::from_file_bytes(&mmap, &header)? else {
+        return Err(ReadError::MissingFooter.into());
+    };
+    Ok(compute_footer(&mmap, &header)?.matches(&footer))
+}
+
+/// Parses the file header from the full contents of a file
+pub(crate) fn read_header(bytes: &[u8]) -> Result<VBinseqHeader> {
+    if bytes.len() < SIZE_HEADER {
+        return Err(ReadError::UnexpectedEndOfFile(bytes.len()).into());
+    }
+    let mut header_bytes = [0u8; SIZE_HEADER];
+    header_bytes.copy_from_slice(&bytes[..SIZE_HEADER]);
+    VBinseqHeader::from_bytes(&header_bytes)
+}
+
+#[cfg(all(test, feature = "mmap"))]
+mod tests {
+    use super::*;
+    use crate::{MmapReader, VBinseqWriterBuilder};
+    use std::fs::File;
+
+    fn write_file(path: &Path, compressed: bool, footer: bool) -> Result<()> {
+        let mut header = VBinseqHeader::with_capacity(1024, true, compressed, false);
+        header.set_footer(footer);
+        let mut writer = VBinseqWriterBuilder::default()
+            .header(header)
+            .build(File::create(path)?)?;
+        for i in 0..200 {
+            let sequence = b"ACGTTGCA".repeat(1 + i % 7);
+            let quality = vec![b'I'; sequence.len()];
+            writer.write_nucleotides_quality(i as u64, &sequence, &quality)?;
+        }
+        writer.finish()
+    }
+
+    #[test]
+    fn test_footer_digest() -> Result<()> {
+        let dir = std::env::temp_dir();
+        let pid = std::process::id();
+        let plain = dir.join(format!("vbq_footer_plain_{pid}.vbq"));
+        let zstd = dir.join(format!("vbq_footer_zstd_{pid}.vbq"));
+        let legacy = dir.join(format!("vbq_footer_legacy_{pid}.vbq"));
+        write_file(&plain, false, true)?;
+        write_file(&zstd, true, true)?;
+        write_file(&legacy, false, false)?;
+
+        // The digest is independent of compression and of the presence of a footer
+        assert!(verify(&plain)?);
+        assert!(verify(&zstd)?);
+        assert_eq!(content_digest(&plain)?, content_digest(&zstd)?);
+        assert_eq!(content_digest(&plain)?, content_digest(&legacy)?);
+        assert!(verify(&legacy).is_err());
+
+        // Readers stop at the footer
+        let mut reader = MmapReader::new(&zstd)?;
+        let footer = reader.footer().unwrap();
+        assert_eq!(footer.n_records, 200);
+        let mut block = reader.new_block();
+        let mut n_records = 0;
+        while reader.read_block_into(&mut block)? {
+            n_records += block.n_records();
+        }
+        assert_eq!(n_records, 200);
+
+        // Corrupting the content is detected
+        let mut bytes = std::fs::read(&plain)?;
+        bytes[SIZE_HEADER + SIZE_BLOCK_HEADER] ^= 1;
+        std::fs::write(&plain, bytes)?;
+        assert!(!verify(&plain)?);
+
+        for path in [plain, zstd, legacy] {
+            std::fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_footer_digest_ingest() -> Result<()> {
+        let path =
+            std::env::temp_dir().join(format!("vbq_footer_ingest_{}.vbq", std::process::id()));
+        let mut header = VBinseqHeader::with_capacity(256, false, true, false);
+        header.set_footer(true);
+        let mut writer = VBinseqWriterBuilder::default()
+            .header(header)
+            .build(File::create(&path)?)?;
+        let mut thread_writer = VBinseqWriterBuilder::default()
+            .header(header)
+            .headless(true)
+            .build(Vec::new())?;
+        for i in 0..100 {
+            writer.write_nucleotides(i, b"ACGTACGTACGTACGTACGT")?;
+            thread_writer.write_nucleotides(i, b"TTTTGGGGCCCCAAAA")?;
+            if i % 30 == 0 {
+                writer.ingest(&mut thread_writer)?;
+            }
+        }
+        writer.ingest(&mut thread_writer)?;
+        writer.finish()?;
+
+        let reader = MmapReader::new(&path)?;
+        assert_eq!(reader.footer().unwrap().n_records, 200);
+        assert!(verify(&path)?);
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,475 @@
+//! # Fuzzing Generators
+//!
+//! This module generates headers, block ranges, and raw blocks for fuzz and property
+//! tests of the parsers, with `arbitrary::Arbitrary` implementations behind the
+//! `arbitrary` feature and `proptest` strategies behind the `proptest` feature.
+//!
+//! Generated values are mostly well-formed, so inputs get past the magic numbers and into
+//! the parsers, but they include malformed values on purpose: headers combine
+//! incompatible format extensions, block headers carry corrupted magic numbers,
+//! reserved bytes, or sizes that don't match their payload, and payloads are arbitrary
+//! bytes. Parsers are expected to reject such inputs with an error, never to panic.
+//!
+//! `FuzzFile` assembles a header and raw blocks into the bytes of a complete file, which
+//! can be passed to `MemoryReader`. Sizes are bounded (see `MAX_BLOCK_SIZE`,
+//! `MAX_PAYLOAD_SIZE`, and `MAX_BLOCKS`) so that generated inputs stay fast to parse.
+//!
+//! # Example
+//!
+//! A fuzz target (e.g. with `cargo fuzz`) checking that reading arbitrary files never
+//! panics:
+//!
+//! ```rust,ignore
+//! #![no_main]
+//! use libfuzzer_sys::fuzz_target;
+//! use vbinseq::{fuzz::FuzzFile, MemoryReader};
+//!
+//! fuzz_target!(|file: FuzzFile| {
+//!     if let Ok(mut reader) = MemoryReader::new(file.to_bytes()) {
+//!         let mut block = reader.new_block();
+//!         while let Ok(true) = reader.read_block_into(&mut block) {}
+//!     }
+//! });
+//! ```
+
+use crate::header::{APP_DATA_SIZE, APP_ID_UNCLAIMED};
+#[cfg(feature = "arbitrary")]
+use crate::RawBlock;
+use crate::{BlockHeader, BlockRange, Codec, QualityTransform, VBinseqHeader};
+
+/// Quality transforms of generated file headers
+const QUALITY_TRANSFORMS: [QualityTransform; 3] = [
+    QualityTransform::None,
+    QualityTransform::RunLength,
+    QualityTransform::Delta,
+];
+
+/// Largest block size of generated file headers (in bytes)
+pub const MAX_BLOCK_SIZE: u64 = 1 << 20;
+
+/// Largest payload of generated blocks (in bytes)
+pub const MAX_PAYLOAD_SIZE: usize = 4096;
+
+/// Largest number of blocks of generated files
+pub const MAX_BLOCKS: usize = 8;
+
+/// A file header followed by raw blocks, for feeding whole files to the readers
+#[derive(Debug, Clone, PartialEq)]
+pub struct FuzzFile {
+    /// Header of the file
+    pub header: VBinseqHeader,
+
+    /// Headers and stored bytes of the blocks of the file
+    pub blocks: Vec<(BlockHeader, Vec<u8>)>,
+}
+impl FuzzFile {
+    /// Returns the bytes of the file
+    ///
+    /// The file has no header sections, footer, or index, even if its header claims one.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        self.header
+            .write_bytes(&mut bytes)
+            .expect("Writing to a Vec cannot fail");
+        for (header, payload) in &self.blocks {
+            header
+                .write_bytes(&mut bytes)
+                .expect("Writing to a Vec cannot fail");
+            bytes.extend_from_slice(payload);
+        }
+        bytes
+    }
+}
+
+/// Choices shared by the generators of file headers
+#[derive(Debug)]
+struct HeaderChoices {
+    /// Block size of the header
+    block: u64,
+    /// Format extensions to enable (one bit each)
+    features: u16,
+    /// Selects the quality transform
+    transform: u8,
+    /// Application id and data to stamp the header with
+    app: Option<(u16, [u8; APP_DATA_SIZE])>,
+}
+impl HeaderChoices {
+    /// Builds the header through its setters, so it can always be written
+    fn build(self) -> VBinseqHeader {
+        let bit = |i: u16| self.features & (1 << i) != 0;
+        let mut header = VBinseqHeader::with_capacity(self.block, bit(0), bit(1), bit(2));
+        header.set_footer(bit(3));
+        header.set_homopolymer(bit(4));
+        header.set_record_crc(bit(5));
+        header.set_aux(bit(6));
+        header.set_fixed_length(bit(7));
+        header.set_optional_quality(bit(8));
+        header.set_quality_transform(
+            QUALITY_TRANSFORMS[usize::from(self.transform) % QUALITY_TRANSFORMS.len()],
+        );
+        if let Some((app_id, data)) = self.app.filter(|(id, _)| *id != APP_ID_UNCLAIMED) {
+            header
+                .set_app_data(app_id, &data)
+                .expect("Application id is claimed and data fits");
+        }
+        header
+    }
+}
+
+/// Choices shared by the generators of block headers
+#[derive(Debug)]
+struct BlockChoices {
+    /// Size of the block (only used by blocks without a payload to match)
+    size: u64,
+    /// Number of records of the block
+    records: u32,
+    /// Selects which fields are corrupted
+    selector: u8,
+    /// Magic number of blocks with a corrupted magic number
+    magic: u64,
+    /// Reserved bytes (used as-is by blocks with corrupted reserved bytes)
+    reserved: [u8; 12],
+}
+impl BlockChoices {
+    /// Builds a block header for a payload of the given size
+    ///
+    /// One in 16 headers has a corrupted magic number, one in 4 has arbitrary reserved
+    /// bytes, and one in 8 has a size that doesn't match the payload.
+    fn build(self, payload_size: Option<usize>) -> BlockHeader {
+        let size = match payload_size {
+            Some(size) if self.selector % 8 != 2 => size as u64,
+            _ => self.size,
+        };
+        let mut header = BlockHeader::new(size, self.records);
+        if self.selector.is_multiple_of(16) {
+            header.magic = self.magic;
+        }
+        if self.selector % 4 == 1 {
+            header.reserved = self.reserved;
+        } else {
+            let [codec, empty, dictionary, id, ..] = self.reserved;
+            header.set_codec(if codec % 2 == 0 {
+                Codec::Uncompressed
+            } else {
+                Codec::Zstd
+            });
+            header.set_empty_records(empty % 2 == 1);
+            header.set_dictionary((dictionary % 4 == 1).then_some(id));
+        }
+        header
+    }
+}
+
+/// Returns the stored bytes of a payload in a file with the given header
+///
+/// Payloads of compressed files are compressed (unless compression is disabled), so the
+/// parsers of records in compressed blocks are reached as well.
+fn stored_payload(header: &VBinseqHeader, payload: Vec<u8>) -> Vec<u8> {
+    #[cfg(feature = "compression")]
+    if header.compressed() {
+        return zstd::bulk::compress(&payload, 1).unwrap_or(payload);
+    }
+    #[cfg(not(feature = "compression"))]
+    let _ = header;
+    payload
+}
+
+#[cfg(feature = "arbitrary")]
+mod arbitrary_impls {
+    use arbitrary::{Arbitrary, Result, Unstructured};
+
+    use super::*;
+
+    impl<'a> Arbitrary<'a> for HeaderChoices {
+        fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+            Ok(Self {
+                block: u.int_in_range(1..=MAX_BLOCK_SIZE)?,
+                features: u.arbitrary()?,
+                transform: u.arbitrary()?,
+                app: u.arbitrary()?,
+            })
+        }
+    }
+
+    impl<'a> Arbitrary<'a> for BlockChoices {
+        fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+            Ok(Self {
+                size: u.arbitrary()?,
+                records: u.arbitrary()?,
+                selector: u.arbitrary()?,
+                magic: u.arbitrary()?,
+                reserved: u.arbitrary()?,
+            })
+        }
+    }
+
+    impl<'a> Arbitrary<'a> for VBinseqHeader {
+        fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+            Ok(HeaderChoices::arbitrary(u)?.build())
+        }
+    }
+
+    impl<'a> Arbitrary<'a> for BlockHeader {
+        fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+            Ok(BlockChoices::arbitrary(u)?.build(None))
+        }
+    }
+
+    impl<'a> Arbitrary<'a> for BlockRange {
+        fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+            let mut range = BlockRange::new(
+                u.arbitrary()?,
+                u.arbitrary()?,
+                u.arbitrary()?,
+                u.arbitrary()?,
+            );
+            range.reservation = u.arbitrary()?;
+            Ok(range)
+        }
+    }
+
+    impl<'a> Arbitrary<'a> for RawBlock<'a> {
+        fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+            let file_header = VBinseqHeader::arbitrary(u)?;
+            let choices = BlockChoices::arbitrary(u)?;
+            let len = u.int_in_range(0..=MAX_PAYLOAD_SIZE)?.min(u.len());
+            let data = u.bytes(len)?;
+            Ok(RawBlock {
+                header: choices.build(Some(len)),
+                file_header,
+                dictionaries: &[],
+                data,
+            })
+        }
+    }
+
+    impl<'a> Arbitrary<'a> for FuzzFile {
+        fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+            let header = VBinseqHeader::arbitrary(u)?;
+            let n_blocks = u.int_in_range(0..=MAX_BLOCKS)?;
+            let mut blocks = Vec::with_capacity(n_blocks);
+            for _ in 0..n_blocks {
+                let choices = BlockChoices::arbitrary(u)?;
+                let len = u.int_in_range(0..=MAX_PAYLOAD_SIZE)?.min(u.len());
+                let payload = stored_payload(&header, u.bytes(len)?.to_vec());
+                blocks.push((choices.build(Some(payload.len())), payload));
+            }
+            Ok(Self { header, blocks })
+        }
+    }
+}
+
+#[cfg(feature = "proptest")]
+mod proptest_impls {
+    use proptest::arbitrary::{any, Arbitrary};
+    use proptest::collection::vec;
+    use proptest::strategy::{BoxedStrategy, Strategy};
+
+    use super::*;
+
+    /// Strategy for the choices of file headers
+    fn header_choices() -> impl Strategy<Value = HeaderChoices> {
+        (
+            1..=MAX_BLOCK_SIZE,
+            any::<u16>(),
+            any::<u8>(),
+            any::<Option<(u16, [u8; APP_DATA_SIZE])>>(),
+        )
+            .prop_map(|(block, features, transform, app)| HeaderChoices {
+                block,
+                features,
+                transform,
+                app,
+            })
+    }
+
+    /// Strategy for the choices of block headers
+    fn block_choices() -> impl Strategy<Value = BlockChoices> {
+        (
+            any::<u64>(),
+            any::<u32>(),
+            any::<u8>(),
+            any::<u64>(),
+            any::<[u8; 12]>(),
+        )
+            .prop_map(|(size, records, selector, magic, reserved)| BlockChoices {
+                size,
+                records,
+                selector,
+                magic,
+                reserved,
+            })
+    }
+
+    impl Arbitrary for VBinseqHeader {
+        type Parameters = ();
+        type Strategy = BoxedStrategy<Self>;
+
+        fn arbitrary_with((): ()) -> Self::Strategy {
+            header_choices().prop_map(HeaderChoices::build).boxed()
+        }
+    }
+
+    impl Arbitrary for BlockHeader {
+        type Parameters = ();
+        type Strategy = BoxedStrategy<Self>;
+
+        fn arbitrary_with((): ()) -> Self::Strategy {
+            block_choices()
+                .prop_map(|choices| choices.build(None))
+                .boxed()
+        }
+    }
+
+    impl Arbitrary for BlockRange {
+        type Parameters = ();
+        type Strategy = BoxedStrategy<Self>;
+
+        fn arbitrary_with((): ()) -> Self::Strategy {
+            (
+                any::<u64>(),
+                any::<u64>(),
+                any::<u32>(),
+                any::<u64>(),
+                any::<[u8; 8]>(),
+            )
+                .prop_map(
+                    |(start_offset, len, block_records, cumulative, reservation)| {
+                        let mut range =
+                            BlockRange::new(start_offset, len, block_records, cumulative);
+                        range.reservation = reservation;
+                        range
+                    },
+                )
+                .boxed()
+        }
+    }
+
+    impl Arbitrary for FuzzFile {
+        type Parameters = ();
+        type Strategy = BoxedStrategy<Self>;
+
+        fn arbitrary_with((): ()) -> Self::Strategy {
+            let block = (block_choices(), vec(any::<u8>(), 0..=MAX_PAYLOAD_SIZE));
+            (any::<VBinseqHeader>(), vec(block, 0..=MAX_BLOCKS))
+                .prop_map(|(header, blocks)| {
+                    let blocks = blocks
+                        .into_iter()
+                        .map(|(choices, payload)| {
+                            let payload = stored_payload(&header, payload);
+                            (choices.build(Some(payload.len())), payload)
+                        })
+                        .collect();
+                    FuzzFile { header, blocks }
+                })
+                .boxed()
+        }
+    }
+
+    /// Strategy for raw block payloads with the headers of their block and file
+    ///
+    /// `RawBlock` borrows its payload, so the strategy yields its parts.
+    pub fn raw_block_parts() -> impl Strategy<Value = (BlockHeader, VBinseqHeader, Vec<u8>)> {
+        (
+            block_choices(),
+            any::<VBinseqHeader>(),
+            vec(any::<u8>(), 0..=MAX_PAYLOAD_SIZE),
+        )
+            .prop_map(|(choices, file_header, data)| {
+                (choices.build(Some(data.len())), file_header, data)
+            })
+    }
+}
+#[cfg(feature = "proptest")]
+pub use proptest_impls::raw_block_parts;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::header::{SIZE_BLOCK_HEADER, SIZE_HEADER};
+    use crate::MemoryReader;
+
+    /// Reads every block and record of a file, which must not panic
+    fn read_all(bytes: Vec<u8>) {
+        let Ok(mut reader) = MemoryReader::new(bytes) else {
+            return;
+        };
+        let mut block = reader.new_block();
+        let mut dbuf = Vec::new();
+        while let Ok(true) = reader.read_block_into(&mut block) {
+            for record in block.iter() {
+                let _ = record.decode_s(&mut dbuf);
+                let _ = record.decode_x(&mut dbuf);
+            }
+        }
+    }
+
+    /// Parses a file header and block header from the written bytes, which must not panic
+    fn parse_headers(header: &VBinseqHeader, block_header: &BlockHeader) {
+        let mut bytes = Vec::new();
+        header.write_bytes(&mut bytes).unwrap();
+        block_header.write_bytes(&mut bytes).unwrap();
+        let _ = VBinseqHeader::from_bytes(bytes[..SIZE_HEADER].try_into().unwrap());
+        let _ = BlockHeader::from_bytes(
+            bytes[SIZE_HEADER..][..SIZE_BLOCK_HEADER]
+                .try_into()
+                .unwrap(),
+        );
+    }
+
+    #[cfg(feature = "arbitrary")]
+    #[test]
+    fn test_arbitrary_files() {
+        use arbitrary::{Arbitrary, Unstructured};
+        use rand::rngs::SmallRng;
+        use rand::{Rng, SeedableRng};
+
+        let mut rng = SmallRng::seed_from_u64(22);
+        let mut entropy = vec![0u8; 4 * MAX_PAYLOAD_SIZE];
+        for _ in 0..500 {
+            rng.fill(&mut entropy[..]);
+            let u = &mut Unstructured::new(&entropy);
+            let file = FuzzFile::arbitrary(u).unwrap();
+            assert!(file.blocks.len() <= MAX_BLOCKS);
+            read_all(file.to_bytes());
+            parse_headers(
+                &VBinseqHeader::arbitrary(u).unwrap(),
+                &BlockHeader::arbitrary(u).unwrap(),
+            );
+            let range = BlockRange::arbitrary(u).unwrap();
+            let mut bytes = Vec::new();
+            range.write_bytes(&mut bytes).unwrap();
+            let parsed = BlockRange::from_bytes(&bytes);
+            assert_eq!(parsed.start_offset, range.start_offset);
+            assert_eq!(parsed.len, range.len);
+            let raw = RawBlock::arbitrary(u).unwrap();
+            assert!(raw.data.len() <= MAX_PAYLOAD_SIZE);
+        }
+    }
+
+    #[cfg(feature = "proptest")]
+    mod strategies {
+        use proptest::prelude::*;
+
+        use super::*;
+
+        proptest! {
+            #[test]
+            fn test_proptest_files(file in any::<FuzzFile>()) {
+                read_all(file.to_bytes());
+            }
+
+            #[test]
+            fn test_proptest_headers(header in any::<VBinseqHeader>(), block in any::<BlockHeader>()) {
+                prop_assert!(header.block() <= MAX_BLOCK_SIZE);
+                parse_headers(&header, &block);
+            }
+
+            #[test]
+            fn test_proptest_raw_blocks((header, file_header, data) in raw_block_parts()) {
+                prop_assert!(data.len() <= MAX_PAYLOAD_SIZE);
+                parse_headers(&file_header, &header);
+            }
+        }
+    }
+}
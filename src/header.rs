@@ -12,27 +12,115 @@
 //!
 //! Both headers are fixed-size and include magic numbers to validate file integrity.
 
+use std::fmt;
 use std::io::{Read, Write};
 
 use byteorder::{ByteOrder, LittleEndian};
 
+use crate::checksum::SIZE_RECORD_CRC;
 use crate::error::{HeaderError, ReadError, Result};
+use crate::QualityTransform;
 
 /// Magic number for file identification: "VSEQ" in ASCII (0x51455356)
 ///
 /// This constant is used in the file header to identify VBINSEQ formatted files.
-const MAGIC: u32 = 0x51455356;
+pub(crate) const MAGIC: u32 = 0x51455356;
 
 /// Magic number for block identification: "BLOCKSEQ" in ASCII (0x5145534B434F4C42)
 ///
 /// This constant is used in block headers to validate block integrity.
-const BLOCK_MAGIC: u64 = 0x5145534B434F4C42;
+pub(crate) const BLOCK_MAGIC: u64 = 0x5145534B434F4C42;
 
 /// Current format version number
 ///
 /// This should be incremented when making backwards-incompatible changes to the format.
 const FORMAT: u8 = 1;
 
+/// Format version of files using format extensions
+///
+/// Files of this version interpret the first 8 reserved bytes of the header as extension
+/// fields (see `VBinseqHeader::flags`). Readers that only understand format 1 reject these
+/// files instead of misreading them, while files without extensions are still written as
+/// format 1.
+const FORMAT_EXTENDED: u8 = 2;
+
+/// Size of the extension fields at the start of the header's reserved bytes
+const EXTENSION_SIZE: usize = 8;
+
+/// Offset of the quality transform within the extension fields (after the flags)
+const QUALITY_TRANSFORM_OFFSET: usize = 4;
+
+/// Offset of the size of the header sections within the extension fields (a u24 after
+/// the quality transform)
+const SECTIONS_SIZE_OFFSET: usize = 5;
+
+/// Maximum size of the header sections in bytes (see the `sections` module)
+pub const MAX_SECTIONS_SIZE: usize = (1 << 24) - 1;
+
+/// Extension flag: the file ends with a footer (see the `footer` module)
+pub const FLAG_FOOTER: u32 = 1 << 0;
+
+/// Extension flag: sequences are homopolymer-compressed (see the `homopolymer` module)
+pub const FLAG_HOMOPOLYMER: u32 = 1 << 1;
+
+/// Extension flag: every record ends with a checksum (see the `checksum` module)
+pub const FLAG_RECORD_CRC: u32 = 1 << 2;
+
+/// Extension flag: every record stores a u64 auxiliary value
+pub const FLAG_AUX: u32 = 1 << 3;
+
+/// Extension flag: records omit their lengths, which are recorded per block
+pub const FLAG_FIXED_LENGTH: u32 = 1 << 4;
+
+/// Extension flag: records may omit their quality scores
+pub const FLAG_OPTIONAL_QUALITY: u32 = 1 << 5;
+
+/// Extension flag: every block header stores a checksum of the uncompressed block
+pub const FLAG_BLOCK_CHECKSUM: u32 = 1 << 6;
+
+/// Extension flag: the block index is stored before the footer (see `BlockIndex::from_embedded`)
+pub const FLAG_EMBEDDED_INDEX: u32 = 1 << 7;
+
+/// Extension flag: records have more than two segments, counted by the paired byte
+pub const FLAG_SEGMENTS: u32 = 1 << 8;
+
+/// Extension flag: sequences are 4-bit encoded IUPAC codes (see the `iupac` module)
+pub const FLAG_IUPAC: u32 = 1 << 9;
+
+/// Extension flag: blocks group the parts of their records into regions (see the
+/// `columnar` module)
+pub const FLAG_COLUMNAR: u32 = 1 << 10;
+
+/// Extension flag: blocks of a compressed file may be stored uncompressed (see
+/// `VBinseqWriterBuilder::compression_fallback`)
+pub const FLAG_CODEC_FALLBACK: u32 = 1 << 11;
+
+/// All extension flags understood by this library
+const KNOWN_FLAGS: u32 = FLAG_FOOTER
+    | FLAG_HOMOPOLYMER
+    | FLAG_RECORD_CRC
+    | FLAG_AUX
+    | FLAG_FIXED_LENGTH
+    | FLAG_OPTIONAL_QUALITY
+    | FLAG_BLOCK_CHECKSUM
+    | FLAG_EMBEDDED_INDEX
+    | FLAG_SEGMENTS
+    | FLAG_IUPAC
+    | FLAG_COLUMNAR
+    | FLAG_CODEC_FALLBACK;
+
+/// Maximum number of segments of every record (see `VBinseqHeader::set_segments`)
+pub const MAX_SEGMENTS: usize = u8::MAX as usize;
+
+/// Bit of the stored primary length marking records without quality scores
+///
+/// This is only set in files with optional quality scores (see
+/// `VBinseqHeader::set_optional_quality`).
+pub(crate) const RECORD_NO_QUALITY: u64 = 1 << 63;
+
+/// Size of the auxiliary value of a record in bytes
+pub const SIZE_AUX: usize = 8;
+
 /// Size of the file header in bytes (32 bytes)
 ///
 /// The file header has a fixed size to simplify parsing.
@@ -49,16 +137,144 @@ pub const SIZE_BLOCK_HEADER: usize = 32;
 /// A larger block size can improve compression ratio but reduces random access granularity.
 pub const BLOCK_SIZE: u64 = 128 * 1024;
 
+/// Block size of the long-read profile in bytes: 16MB
+///
+/// Every record must fit in a single block, and nanopore and PacBio reads reach hundreds
+/// of kilobases (several megabases for ultra-long reads).
+pub const LONG_READ_BLOCK_SIZE: u64 = 16 * 1024 * 1024;
+
 /// Reserved bytes for future use in the file header (16 bytes)
 ///
 /// These bytes are set to a placeholder value (42) and reserved for future extensions.
 pub const RESERVED_BYTES: [u8; 16] = [42; 16];
 
+/// Offset of the application region within the header's reserved bytes
+///
+/// The last 8 reserved bytes are set aside for applications: a 2-byte application id
+/// followed by `APP_DATA_SIZE` bytes of application-defined data.
+const APP_REGION_OFFSET: usize = 8;
+
+/// Number of application-defined data bytes available in the header (6 bytes)
+pub const APP_DATA_SIZE: usize = 6;
+
+/// Application id of an unclaimed application region
+///
+/// This corresponds to the placeholder value of the reserved bytes (42, 42), so headers
+/// written without application data are read back as unclaimed.
+pub const APP_ID_UNCLAIMED: u16 = 0x2A2A;
+
 /// Reserved bytes for future use in block headers (12 bytes)
 ///
 /// These bytes are set to a placeholder value (42) and reserved for future extensions.
 pub const RESERVED_BYTES_BLOCK: [u8; 12] = [42; 12];
 
+/// Placeholder codec byte of block headers written before codecs were recorded per block
+const CODEC_UNSPECIFIED: u8 = 42;
+
+/// Second reserved byte of block headers holding zero-length records
+const BLOCK_EMPTY_RECORDS: u8 = 1;
+
+/// Marks blocks compressed with a dictionary (stored in the third reserved byte)
+const BLOCK_DICTIONARY: u8 = 1;
+
+/// Offset of the record length of fixed-length files within the block reserved bytes
+const BLOCK_FIXED_LENGTH_OFFSET: usize = 4;
+
+/// Offset of the block checksum within the block reserved bytes
+///
+/// This overlaps the record length of fixed-length files, which cannot have checksums.
+const BLOCK_CHECKSUM_OFFSET: usize = 4;
+
+/// Codec used to store the data of a block
+///
+/// Each block header records the codec its data was written with in the first of its
+/// reserved bytes, so files mixing codecs (e.g. incompressible blocks stored raw in an
+/// otherwise compressed file) are self-describing at block granularity.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Codec {
+    /// Block data is stored as-is
+    Uncompressed,
+
+    /// Block data is ZSTD compressed
+    Zstd,
+
+    /// Block data is LZ4 compressed (requires the `lz4` feature)
+    ///
+    /// Fastest to write and read, at a lower compression ratio than zstd.
+    Lz4,
+
+    /// Block data is XZ (LZMA2) compressed (requires the `xz` feature)
+    ///
+    /// Slow to write but compact, suited to archival copies.
+    Xz,
+}
+impl Codec {
+    /// Returns the byte representation of the codec
+    pub fn as_byte(self) -> u8 {
+        match self {
+            Self::Uncompressed => 0,
+            Self::Zstd => 1,
+            Self::Lz4 => 2,
+            Self::Xz => 3,
+        }
+    }
+
+    /// Returns the cargo feature providing support for the codec
+    ///
+    /// Files written without compression need no feature.
+    pub fn feature(self) -> Option<&'static str> {
+        match self {
+            Self::Uncompressed => None,
+            Self::Zstd => Some("compression"),
+            Self::Lz4 => Some("lz4"),
+            Self::Xz => Some("xz"),
+        }
+    }
+
+    /// Returns whether files compressed with the codec are written as format 2
+    ///
+    /// Format 1 readers decompress every block of a compressed file with zstd, so only
+    /// zstd and uncompressed files can be read by them.
+    fn needs_extended_format(self) -> bool {
+        matches!(self, Self::Lz4 | Self::Xz)
+    }
+
+    /// Returns whether support for the codec is compiled in
+    pub fn is_supported(self) -> bool {
+        match self {
+            Self::Uncompressed => true,
+            Self::Zstd => cfg!(feature = "compression"),
+            Self::Lz4 => cfg!(feature = "lz4"),
+            Self::Xz => cfg!(feature = "xz"),
+        }
+    }
+
+    /// Parses a codec from its byte representation
+    ///
+    /// # Errors
+    ///
+    /// * `HeaderError::InvalidCodec` - If the byte does not correspond to a known codec
+    pub fn from_byte(byte: u8) -> Result<Self> {
+        match byte {
+            0 => Ok(Self::Uncompressed),
+            1 => Ok(Self::Zstd),
+            2 => Ok(Self::Lz4),
+            3 => Ok(Self::Xz),
+            _ => Err(HeaderError::InvalidCodec(byte).into()),
+        }
+    }
+}
+impl fmt::Display for Codec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Uncompressed => write!(f, "uncompressed"),
+            Self::Zstd => write!(f, "zstd"),
+            Self::Lz4 => write!(f, "lz4"),
+            Self::Xz => write!(f, "xz"),
+        }
+    }
+}
+
 /// File header for VBINSEQ files
 ///
 /// This structure represents the 32-byte header that appears at the beginning of every
@@ -66,51 +282,62 @@ pub const RESERVED_BYTES_BLOCK: [u8; 12] = [42; 12];
 /// whether quality scores are included, whether blocks are compressed, and whether
 /// records contain paired sequences.
 ///
+/// The fields are private so that every header can be written as-is: the block size is
+/// nonzero, the format version is known, and the format version matches the extension
+/// flags in use. They are accessed through methods of the same name and changed through
+/// validating setters.
+///
 /// # Fields
 ///
 /// * `magic` - Magic number to validate file format ("VSEQ", 4 bytes)
 /// * `format` - Version number of the file format (1 byte)
 /// * `block` - Size of each block in bytes (8 bytes)
 /// * `qual` - Whether quality scores are included (1 byte boolean)
-/// * `compressed` - Whether blocks are ZSTD compressed (1 byte boolean)
-/// * `paired` - Whether records contain paired sequences (1 byte boolean)
+/// * `codec` - Codec of the blocks (1 byte, 0 for uncompressed and 1 for ZSTD)
+/// * `paired` - Whether records contain paired sequences (1 byte boolean), or the number of
+///   segments of every record in files with more than two segments
 /// * `reserved` - Reserved bytes for future extensions (16 bytes)
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub struct VBinseqHeader {
     /// Magic number to identify the file format ("VSEQ")
     ///
     /// Always set to 0x51455356 (4 bytes)
-    pub magic: u32,
+    magic: u32,
 
     /// Version of the file format
     ///
-    /// Currently set to 1 (1 byte)
-    pub format: u8,
+    /// Set to 1, or 2 if format extensions are used (1 byte)
+    format: u8,
 
     /// Block size in bytes
     ///
     /// This is the virtual (uncompressed) size of each record block (8 bytes)
-    pub block: u64,
+    block: u64,
 
     /// Whether quality scores are included with sequences
     ///
     /// If true, quality scores are stored for each nucleotide (1 byte)
-    pub qual: bool,
+    qual: bool,
 
-    /// Whether internal blocks are compressed with ZSTD
+    /// Codec the blocks are compressed with
     ///
-    /// If true, blocks are compressed individually (1 byte)
-    pub compressed: bool,
+    /// Blocks are compressed individually, unless this is `Codec::Uncompressed`. The byte
+    /// values of uncompressed and ZSTD files match the former boolean flag (1 byte)
+    codec: Codec,
 
-    /// Whether records contain paired sequences
+    /// Number of segments (sequences) of every record
     ///
-    /// If true, each record has both primary and extended sequences (1 byte)
-    pub paired: bool,
+    /// This is 1 for single-end and 2 for paired records, which is stored as the paired
+    /// boolean. Larger counts are stored as-is in the same byte and require the
+    /// `FLAG_SEGMENTS` extension (1 byte)
+    segments: u8,
 
     /// Reserved bytes for future format extensions
     ///
-    /// Currently filled with placeholder values (16 bytes)
-    pub reserved: [u8; 16],
+    /// Currently filled with placeholder values (16 bytes).
+    /// In format 2 the first 8 bytes hold the extension fields (see `flags`).
+    /// The last 8 bytes form the application region (see `set_app_data`).
+    reserved: [u8; 16],
 }
 impl Default for VBinseqHeader {
     /// Creates a default header with default block size and all features disabled
@@ -162,18 +389,51 @@ impl VBinseqHeader {
     /// // Create header with a 256KB block size, with quality scores and compression
     /// let header = VBinseqHeader::with_capacity(256 * 1024, true, true, false);
     /// ```
+    ///
+    /// # Panics
+    ///
+    /// If `block` is zero
     pub fn with_capacity(block: u64, qual: bool, compressed: bool, paired: bool) -> Self {
+        assert!(block > 0, "Block size must be nonzero");
         Self {
             magic: MAGIC,
             format: FORMAT,
             block,
             qual,
-            compressed,
-            paired,
+            codec: if compressed {
+                Codec::Zstd
+            } else {
+                Codec::Uncompressed
+            },
+            segments: if paired { 2 } else { 1 },
             reserved: RESERVED_BYTES,
         }
     }
 
+    /// Creates a header suited to long reads (e.g. nanopore or PacBio)
+    ///
+    /// The header uses blocks of `LONG_READ_BLOCK_SIZE` bytes, stores quality scores,
+    /// compresses blocks, and applies the run-length quality transform, as long-read
+    /// qualities repeat often. Records are not paired. Use
+    /// `VBinseqWriterBuilder::long_read_profile` to also apply the matching writer
+    /// settings.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use vbinseq::header::LONG_READ_BLOCK_SIZE;
+    /// use vbinseq::{QualityTransform, VBinseqHeader};
+    ///
+    /// let header = VBinseqHeader::long_read_profile();
+    /// assert_eq!(header.block(), LONG_READ_BLOCK_SIZE);
+    /// assert_eq!(header.quality_transform(), QualityTransform::RunLength);
+    /// ```
+    pub fn long_read_profile() -> Self {
+        let mut header = Self::with_capacity(LONG_READ_BLOCK_SIZE, true, true, false);
+        header.set_quality_transform(QualityTransform::RunLength);
+        header
+    }
+
     /// Creates a header from a 32-byte buffer
     ///
     /// This function parses a raw byte buffer into a `VBinseqHeader` structure,
@@ -191,33 +451,68 @@ impl VBinseqHeader {
     ///
     /// * `HeaderError::InvalidMagicNumber` - If the magic number doesn't match "VSEQ"
     /// * `HeaderError::InvalidFormatVersion` - If the format version is unsupported
+    /// * `HeaderError::InvalidBlockSize` - If the block size is zero
+    /// * `HeaderError::InvalidCodec` - If the codec byte is unknown
     /// * `HeaderError::InvalidReservedBytes` - If the reserved bytes section is invalid
+    /// * `HeaderError::UnsupportedFlags` - If the header uses unknown format extensions
+    /// * `HeaderError::InvalidQualityTransform` - If the quality transform is unknown
+    /// * `HeaderError::HomopolymerWithQuality` - If the header enables both quality scores
+    ///   and homopolymer compression
+    /// * `HeaderError::InvalidFixedLength` - If the header enables fixed-length records
+    ///   for paired or homopolymer-compressed records, or with optional quality scores
+    /// * `HeaderError::InvalidSegments` - If the header has more than two segments without
+    ///   counting at least three, or with homopolymer compression
     pub fn from_bytes(buffer: &[u8; SIZE_HEADER]) -> Result<Self> {
         let magic = LittleEndian::read_u32(&buffer[0..4]);
         if magic != MAGIC {
             return Err(HeaderError::InvalidMagicNumber(magic).into());
         }
         let format = buffer[4];
-        if format != FORMAT {
+        if format != FORMAT && format != FORMAT_EXTENDED {
             return Err(HeaderError::InvalidFormatVersion(format).into());
         }
         let block = LittleEndian::read_u64(&buffer[5..13]);
+        if block == 0 {
+            return Err(HeaderError::InvalidBlockSize(block).into());
+        }
         let qual = buffer[13] != 0;
-        let compressed = buffer[14] != 0;
-        let paired = buffer[15] != 0;
+        let codec = Codec::from_byte(buffer[14])?;
+        let segments = buffer[15];
         let reserved = match buffer[16..32].try_into() {
             Ok(reserved) => reserved,
             Err(_) => return Err(HeaderError::InvalidReservedBytes.into()),
         };
-        Ok(Self {
+        let mut header = Self {
             magic,
             format,
             block,
             qual,
-            compressed,
+            codec,
             reserved,
-            paired,
-        })
+            segments,
+        };
+        let unknown = header.flags() & !KNOWN_FLAGS;
+        if unknown != 0 {
+            return Err(HeaderError::UnsupportedFlags(unknown).into());
+        }
+        if header.flags() & FLAG_SEGMENTS == 0 {
+            header.segments = if segments != 0 { 2 } else { 1 };
+        } else if segments < 3 || header.is_homopolymer() {
+            return Err(HeaderError::InvalidSegments(segments as usize).into());
+        }
+        if header.format == FORMAT_EXTENDED {
+            QualityTransform::from_byte(header.reserved[QUALITY_TRANSFORM_OFFSET])?;
+        }
+        if header.qual && header.is_homopolymer() {
+            return Err(HeaderError::HomopolymerWithQuality.into());
+        }
+        if header.is_fixed_length() && !header.supports_fixed_length() {
+            return Err(HeaderError::InvalidFixedLength.into());
+        }
+        if header.has_embedded_index() && !header.has_footer() {
+            return Err(HeaderError::EmbeddedIndexWithoutFooter.into());
+        }
+        Ok(header)
     }
 
     /// Writes the header to a writer
@@ -242,8 +537,14 @@ impl VBinseqHeader {
         buffer[4] = self.format;
         LittleEndian::write_u64(&mut buffer[5..13], self.block);
         buffer[13] = if self.qual { 1 } else { 0 };
-        buffer[14] = if self.compressed { 1 } else { 0 };
-        buffer[15] = if self.paired { 1 } else { 0 }; // Fixed bug: was using self.compressed
+        buffer[14] = self.codec.as_byte();
+        buffer[15] = if self.segments > 2 {
+            self.segments
+        } else if self.paired() {
+            1
+        } else {
+            0
+        };
         buffer[16..32].copy_from_slice(&self.reserved);
         writer.write_all(&buffer)?;
         Ok(())
@@ -271,116 +572,1208 @@ impl VBinseqHeader {
         reader.read_exact(&mut buffer)?;
         Self::from_bytes(&buffer)
     }
-}
 
-/// Block header for VBINSEQ block data
-///
-/// Each block in a VBINSEQ file is preceded by a 32-byte block header that contains
-/// information about the block including its size and the number of records it contains.
-///
-/// # Fields
-///
-/// * `magic` - Magic number to validate block integrity ("BLOCKSEQ", 8 bytes)
-/// * `size` - Actual size of the block in bytes (8 bytes)
-/// * `records` - Number of records in the block (4 bytes)
-/// * `reserved` - Reserved bytes for future extensions (12 bytes)
-#[derive(Clone, Copy, Debug)]
-pub struct BlockHeader {
-    /// Magic number to identify the block ("BLOCKSEQ")
-    ///
-    /// Always set to 0x5145534B434F4C42 (8 bytes)
-    pub magic: u64,
+    /// Returns the magic number of the header ("VSEQ")
+    pub fn magic(&self) -> u32 {
+        self.magic
+    }
 
-    /// Actual size of the block in bytes
+    /// Returns the version of the file format
     ///
-    /// This can differ from the virtual block size in the file header
-    /// when compression is enabled (8 bytes)
-    pub size: u64,
+    /// This is 1, or 2 if format extensions are used (see `flags`).
+    pub fn format(&self) -> u8 {
+        self.format
+    }
 
-    /// Number of records stored in this block
-    ///
-    /// Used to iterate through records efficiently (4 bytes)
-    pub records: u32,
+    /// Returns the virtual (uncompressed) size of each record block in bytes
+    pub fn block(&self) -> u64 {
+        self.block
+    }
 
-    /// Reserved bytes for future extensions
-    ///
-    /// Currently filled with placeholder values (12 bytes)
-    pub reserved: [u8; 12],
-}
-impl BlockHeader {
-    /// Creates a new block header
+    /// Sets the virtual (uncompressed) size of each record block in bytes
     ///
-    /// # Parameters
+    /// # Errors
     ///
-    /// * `size` - The actual size of the block in bytes (can be compressed size)
-    /// * `records` - The number of records contained in the block
+    /// * `HeaderError::InvalidBlockSize` - If `block` is zero
     ///
     /// # Example
     ///
     /// ```rust
-    /// use vbinseq::BlockHeader;
+    /// use vbinseq::VBinseqHeader;
     ///
-    /// // Create a block header for a block with 1024 bytes and 100 records
-    /// let header = BlockHeader::new(1024, 100);
+    /// let mut header = VBinseqHeader::default();
+    /// header.set_block(64 * 1024).unwrap();
+    ///
+    /// assert_eq!(header.block(), 64 * 1024);
+    /// assert!(header.set_block(0).is_err());
     /// ```
-    pub fn new(size: u64, records: u32) -> Self {
-        Self {
-            magic: BLOCK_MAGIC,
-            size,
-            records,
-            reserved: RESERVED_BYTES_BLOCK,
+    pub fn set_block(&mut self, block: u64) -> Result<()> {
+        if block == 0 {
+            return Err(HeaderError::InvalidBlockSize(block).into());
         }
+        self.block = block;
+        Ok(())
     }
 
-    /// Writes the block header to a writer
+    /// Returns whether quality scores are included with sequences
+    pub fn qual(&self) -> bool {
+        self.qual
+    }
+
+    /// Sets whether quality scores are included with sequences
+    pub fn set_qual(&mut self, qual: bool) {
+        self.qual = qual;
+    }
+
+    /// Returns whether blocks are compressed (with any codec)
+    pub fn compressed(&self) -> bool {
+        self.codec != Codec::Uncompressed
+    }
+
+    /// Sets whether blocks are compressed
     ///
-    /// This function serializes the block header structure into a 32-byte buffer and writes
-    /// it to the provided writer.
+    /// Enabling compression keeps the codec of an already compressed header, and selects
+    /// zstd otherwise. Use `set_codec` to pick another codec.
+    pub fn set_compressed(&mut self, compressed: bool) {
+        if !compressed {
+            self.set_codec(Codec::Uncompressed);
+        } else if self.codec == Codec::Uncompressed {
+            self.set_codec(Codec::Zstd);
+        }
+    }
+
+    /// Returns whether records contain paired sequences
     ///
-    /// # Parameters
+    /// This is also the case for records with more than two segments (see `segments`).
+    pub fn paired(&self) -> bool {
+        self.segments > 1
+    }
+
+    /// Sets whether records contain paired sequences
     ///
-    /// * `writer` - Any type that implements the `Write` trait
+    /// This replaces any other number of segments (see `set_segments`).
+    pub fn set_paired(&mut self, paired: bool) {
+        self.segments = if paired { 2 } else { 1 };
+        self.set_flag(FLAG_SEGMENTS, false);
+    }
+
+    /// Returns the number of segments (sequences) of every record
     ///
-    /// # Returns
+    /// This is 1 for single-end records and 2 for paired records.
+    pub fn segments(&self) -> usize {
+        self.segments as usize
+    }
+
+    /// Sets the number of segments (sequences) of every record
     ///
-    /// * `Result<()>` - Success if the header was written
+    /// Assays with more than two reads per fragment (e.g. R1, R2, I1 and I2, or linked
+    /// reads) store all of them in one record. The first two segments are the primary and
+    /// extended sequences of the record, and all segments are read with
+    /// `RefRecord::segment_buf` and related methods. Files with 1 or 2 segments are
+    /// single-end or paired files (see `set_paired`), while more segments upgrade the
+    /// header to format 2 and store the count in place of the paired boolean, so readers
+    /// unaware of segments reject these files. A homopolymer-compressed file holds at
+    /// most two segments per record, which the writer checks on creation.
     ///
     /// # Errors
     ///
-    /// * IO errors if writing to the writer fails
-    pub fn write_bytes<W: Write>(&self, writer: &mut W) -> Result<()> {
-        let mut buffer = [0u8; SIZE_BLOCK_HEADER];
-        LittleEndian::write_u64(&mut buffer[0..8], self.magic);
-        LittleEndian::write_u64(&mut buffer[8..16], self.size);
-        LittleEndian::write_u32(&mut buffer[16..20], self.records);
-        buffer[20..].copy_from_slice(&self.reserved);
-        writer.write_all(&buffer)?;
+    /// * `HeaderError::InvalidSegments` - If `segments` is 0 or exceeds `MAX_SEGMENTS`
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use vbinseq::VBinseqHeader;
+    ///
+    /// // R1, R2, I1 and I2 reads
+    /// let mut header = VBinseqHeader::new(true, true, false);
+    /// header.set_segments(4).unwrap();
+    ///
+    /// assert_eq!(header.segments(), 4);
+    /// assert!(header.paired());
+    /// assert_eq!(header.format(), 2);
+    /// assert!(header.set_segments(0).is_err());
+    /// ```
+    pub fn set_segments(&mut self, segments: usize) -> Result<()> {
+        if segments == 0 || segments > MAX_SEGMENTS {
+            return Err(HeaderError::InvalidSegments(segments).into());
+        }
+        self.segments = segments as u8;
+        self.set_flag(FLAG_SEGMENTS, segments > 2);
         Ok(())
     }
 
-    /// Creates a block header from a 32-byte buffer
+    /// Returns the reserved bytes of the header
     ///
-    /// This function parses a raw byte buffer into a `BlockHeader` structure,
-    /// validating the magic number.
+    /// In format 2 the first 8 bytes hold the extension fields (see `flags`), and the last
+    /// 8 bytes form the application region (see `set_app_data`). Both are changed through
+    /// their dedicated setters.
+    pub fn reserved(&self) -> &[u8; 16] {
+        &self.reserved
+    }
+
+    /// Returns the codec used for the blocks of the file
     ///
-    /// # Parameters
+    /// Individual blocks may override this codec in their block header
+    /// (see `BlockHeader::codec`).
+    pub fn codec(&self) -> Codec {
+        self.codec
+    }
+
+    /// Sets the codec used for the blocks of the file
     ///
-    /// * `buffer` - A 32-byte array containing the block header data
+    /// Writers reject codecs whose feature is not enabled (see `Codec::feature`). Like
+    /// format extension flags, codecs other than zstd upgrade compressed headers to
+    /// format 2, so readers unaware of them reject these files instead of decompressing
+    /// their blocks with zstd.
     ///
-    /// # Returns
+    /// # Example
     ///
-    /// * `Result<Self>` - A valid block header if parsing was successful
+    /// ```rust
+    /// use vbinseq::{Codec, VBinseqHeader};
     ///
-    /// # Errors
+    /// let mut header = VBinseqHeader::new(true, true, false);
+    /// header.set_codec(Codec::Lz4);
+    /// assert!(header.compressed());
+    /// assert_eq!(header.codec(), Codec::Lz4);
+    /// assert_eq!(header.format(), 2);
+    /// ```
+    pub fn set_codec(&mut self, codec: Codec) {
+        self.codec = codec;
+        if codec.needs_extended_format() {
+            self.extend();
+        } else if self.format == FORMAT_EXTENDED {
+            self.shrink();
+        }
+    }
+
+    /// Returns the format extension flags of the header
     ///
-    /// * `ReadError::InvalidBlockMagicNumber` - If the magic number doesn't match "BLOCKSEQ"
-    pub fn from_bytes(buffer: &[u8; SIZE_BLOCK_HEADER]) -> Result<Self> {
-        let magic = LittleEndian::read_u64(&buffer[0..8]);
-        if magic != BLOCK_MAGIC {
-            return Err(ReadError::InvalidBlockMagicNumber(magic, 0).into());
+    /// Format 1 headers have no extensions and always return 0.
+    pub fn flags(&self) -> u32 {
+        if self.format == FORMAT_EXTENDED {
+            LittleEndian::read_u32(&self.reserved[0..4])
+        } else {
+            0
         }
-        let size = LittleEndian::read_u64(&buffer[8..16]);
-        let records = LittleEndian::read_u32(&buffer[16..20]);
-        Ok(Self::new(size, records))
+    }
+
+    /// Enables or disables a format extension flag
+    ///
+    /// Enabling a flag upgrades the header to format 2, and disabling the last
+    /// extension downgrades it back to format 1 so that plain files stay readable
+    /// by older readers.
+    fn set_flag(&mut self, flag: u32, enabled: bool) {
+        if self.format != FORMAT_EXTENDED && !enabled {
+            return;
+        }
+        self.extend();
+        let flags = if enabled {
+            self.flags() | flag
+        } else {
+            self.flags() & !flag
+        };
+        LittleEndian::write_u32(&mut self.reserved[0..4], flags);
+        self.shrink();
+    }
+
+    /// Upgrades the header to format 2, initializing the extension fields
+    fn extend(&mut self) {
+        if self.format != FORMAT_EXTENDED {
+            self.format = FORMAT_EXTENDED;
+            self.reserved[..EXTENSION_SIZE].fill(0);
+        }
+    }
+
+    /// Downgrades the header to format 1 if no extension (or codec requiring format 2) is
+    /// in use
+    fn shrink(&mut self) {
+        if !self.codec.needs_extended_format()
+            && self.reserved[..EXTENSION_SIZE]
+                .iter()
+                .all(|&byte| byte == 0)
+        {
+            self.format = FORMAT;
+            self.reserved[..EXTENSION_SIZE].copy_from_slice(&RESERVED_BYTES[..EXTENSION_SIZE]);
+        }
+    }
+
+    /// Returns whether the file ends with a footer
+    pub fn has_footer(&self) -> bool {
+        self.flags() & FLAG_FOOTER != 0
+    }
+
+    /// Sets whether the file ends with a footer
+    ///
+    /// When enabled, the writer appends a footer with the number of blocks and records
+    /// and a digest of the file contents at `finish()` (see the `footer` module).
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use vbinseq::VBinseqHeader;
+    ///
+    /// let mut header = VBinseqHeader::default();
+    /// header.set_footer(true);
+    ///
+    /// assert!(header.has_footer());
+    /// assert_eq!(header.format(), 2);
+    /// ```
+    pub fn set_footer(&mut self, footer: bool) {
+        self.set_flag(FLAG_FOOTER, footer);
+    }
+
+    /// Returns whether sequences are homopolymer-compressed
+    pub fn is_homopolymer(&self) -> bool {
+        self.flags() & FLAG_HOMOPOLYMER != 0
+    }
+
+    /// Sets whether sequences are homopolymer-compressed
+    ///
+    /// When enabled, records store one nucleotide per homopolymer run along with the run
+    /// lengths, which take the place of quality scores (see the `homopolymer` module).
+    /// Quality scores must therefore be disabled, which the writer checks on creation.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use vbinseq::VBinseqHeader;
+    ///
+    /// let mut header = VBinseqHeader::new(false, true, false);
+    /// header.set_homopolymer(true);
+    ///
+    /// assert!(header.is_homopolymer());
+    /// assert_eq!(header.format(), 2);
+    /// ```
+    pub fn set_homopolymer(&mut self, homopolymer: bool) {
+        self.set_flag(FLAG_HOMOPOLYMER, homopolymer);
+    }
+
+    /// Returns whether sequences are 4-bit encoded IUPAC codes
+    pub fn is_iupac(&self) -> bool {
+        self.flags() & FLAG_IUPAC != 0
+    }
+
+    /// Sets whether sequences are 4-bit encoded IUPAC codes
+    ///
+    /// The 2-bit encoding only stores `ACGT`, so other nucleotides are rewritten or
+    /// rejected by the `Policy` of the writer. When enabled, sequences are encoded with 4
+    /// bits per nucleotide instead, which stores `N` and all ambiguity codes exactly at
+    /// twice the size (see the `iupac` module). Readers decode both encodings transparently.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use vbinseq::VBinseqHeader;
+    ///
+    /// let mut header = VBinseqHeader::default();
+    /// header.set_iupac(true);
+    ///
+    /// assert!(header.is_iupac());
+    /// assert_eq!(header.format(), 2);
+    /// ```
+    pub fn set_iupac(&mut self, iupac: bool) {
+        self.set_flag(FLAG_IUPAC, iupac);
+    }
+
+    /// Returns whether blocks use the columnar layout
+    pub fn is_columnar(&self) -> bool {
+        self.flags() & FLAG_COLUMNAR != 0
+    }
+
+    /// Sets whether blocks use the columnar layout
+    ///
+    /// Records are normally stored one after the other, interleaving the flags and
+    /// lengths, the encoded sequences, and the quality scores of the records. With the
+    /// columnar layout, every block stores the preambles, the sequences, the quality
+    /// scores, and the trailers of all its records as separate regions, which compress
+    /// better (see the `columnar` module). Readers handle both layouts transparently.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use vbinseq::VBinseqHeader;
+    ///
+    /// let mut header = VBinseqHeader::default();
+    /// header.set_columnar(true);
+    ///
+    /// assert!(header.is_columnar());
+    /// assert_eq!(header.format(), 2);
+    /// ```
+    pub fn set_columnar(&mut self, columnar: bool) {
+        self.set_flag(FLAG_COLUMNAR, columnar);
+    }
+
+    /// Returns whether blocks of a compressed file may be stored uncompressed
+    pub fn has_codec_fallback(&self) -> bool {
+        self.flags() & FLAG_CODEC_FALLBACK != 0
+    }
+
+    /// Sets whether blocks of a compressed file may be stored uncompressed
+    ///
+    /// Blocks record their codec in their block header, but readers of format 1 files
+    /// decompress every block of a compressed file. The writer sets this flag when
+    /// incompressible blocks may be stored as-is (see
+    /// `VBinseqWriterBuilder::compression_fallback`), so these readers reject such files
+    /// instead of misreading them.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use vbinseq::VBinseqHeader;
+    ///
+    /// let mut header = VBinseqHeader::new(true, true, false);
+    /// header.set_codec_fallback(true);
+    ///
+    /// assert!(header.has_codec_fallback());
+    /// assert_eq!(header.format(), 2);
+    /// ```
+    pub fn set_codec_fallback(&mut self, fallback: bool) {
+        self.set_flag(FLAG_CODEC_FALLBACK, fallback);
+    }
+
+    /// Returns whether every record ends with a checksum
+    pub fn has_record_crc(&self) -> bool {
+        self.flags() & FLAG_RECORD_CRC != 0
+    }
+
+    /// Sets whether every record ends with a checksum
+    ///
+    /// When enabled, the writer stores a CRC-16 of every record after it, so validation
+    /// can report corrupt records individually (see the `checksum` module).
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use vbinseq::VBinseqHeader;
+    ///
+    /// let mut header = VBinseqHeader::default();
+    /// header.set_record_crc(true);
+    ///
+    /// assert!(header.has_record_crc());
+    /// assert_eq!(header.format(), 2);
+    /// ```
+    pub fn set_record_crc(&mut self, record_crc: bool) {
+        self.set_flag(FLAG_RECORD_CRC, record_crc);
+    }
+
+    /// Returns whether every record stores a u64 auxiliary value
+    pub fn has_aux(&self) -> bool {
+        self.flags() & FLAG_AUX != 0
+    }
+
+    /// Sets whether every record stores a u64 auxiliary value
+    ///
+    /// The auxiliary value holds numeric instrument metadata separate from the flag, e.g.
+    /// a timestamp, a well position, or the channel id of nanopore reads. It is stored
+    /// after the quality scores of the record (before its checksum) and is read with
+    /// `RefRecord::aux`. Writers set it with `VBinseqWriter::set_record_aux`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use vbinseq::VBinseqHeader;
+    ///
+    /// let mut header = VBinseqHeader::default();
+    /// header.set_aux(true);
+    ///
+    /// assert!(header.has_aux());
+    /// assert_eq!(header.format(), 2);
+    /// ```
+    pub fn set_aux(&mut self, aux: bool) {
+        self.set_flag(FLAG_AUX, aux);
+    }
+
+    /// Returns whether records omit their lengths
+    pub fn is_fixed_length(&self) -> bool {
+        self.flags() & FLAG_FIXED_LENGTH != 0
+    }
+
+    /// Sets whether records omit their lengths
+    ///
+    /// This is a fast path for files of uniform short reads: all records have the same
+    /// length, which the writer takes from the first record and records in every block
+    /// header (see `BlockHeader::fixed_length`) instead of storing it with every record.
+    /// Records are then 16 bytes smaller and readers parse blocks with a constant stride.
+    /// Only unpaired files without homopolymer compression or block checksums can have
+    /// fixed-length records, which the writer checks on creation.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use vbinseq::VBinseqHeader;
+    ///
+    /// let mut header = VBinseqHeader::new(true, true, false);
+    /// header.set_fixed_length(true);
+    ///
+    /// assert!(header.is_fixed_length());
+    /// assert_eq!(header.format(), 2);
+    /// ```
+    pub fn set_fixed_length(&mut self, fixed_length: bool) {
+        self.set_flag(FLAG_FIXED_LENGTH, fixed_length);
+    }
+
+    /// Returns whether the records of the file can omit their lengths
+    ///
+    /// Mates, homopolymer-compressed sequences and optional quality scores all vary the
+    /// layout of records, which fixed-length records cannot express. Block checksums take
+    /// the place of the record length in the block headers.
+    pub(crate) fn supports_fixed_length(&self) -> bool {
+        !self.paired()
+            && !self.is_homopolymer()
+            && !self.has_optional_quality()
+            && !self.has_block_checksum()
+    }
+
+    /// Returns whether every block header stores a checksum of the uncompressed block
+    pub fn has_block_checksum(&self) -> bool {
+        self.flags() & FLAG_BLOCK_CHECKSUM != 0
+    }
+
+    /// Sets whether every block header stores a checksum of the uncompressed block
+    ///
+    /// When enabled, the writer stores the XXH3 64-bit hash of every block (records and
+    /// padding, before compression) in its block header (see `BlockHeader::checksum`).
+    /// Readers verify it when requested (e.g. `MmapReader::set_verify_checksums`), so
+    /// silently corrupted blocks fail to read instead of decoding to garbage. Fixed-length
+    /// files cannot have block checksums.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use vbinseq::VBinseqHeader;
+    ///
+    /// let mut header = VBinseqHeader::default();
+    /// header.set_block_checksum(true);
+    ///
+    /// assert!(header.has_block_checksum());
+    /// assert_eq!(header.format(), 2);
+    /// ```
+    pub fn set_block_checksum(&mut self, checksum: bool) {
+        self.set_flag(FLAG_BLOCK_CHECKSUM, checksum);
+    }
+
+    /// Returns whether the block index is stored in the file, before the footer
+    pub fn has_embedded_index(&self) -> bool {
+        self.flags() & FLAG_EMBEDDED_INDEX != 0
+    }
+
+    /// Sets whether the block index is stored in the file, before the footer
+    ///
+    /// When enabled, the writer stores the block ranges of the file between the last block
+    /// and the footer at `finish()`, so readers load the index from the file itself instead
+    /// of a `.vqi` index file (see `BlockIndex::from_embedded`). This suits object stores,
+    /// where writing a second object next to the file is awkward. The footer records the
+    /// size of the index, so enabling the embedded index also enables the footer.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use vbinseq::VBinseqHeader;
+    ///
+    /// let mut header = VBinseqHeader::default();
+    /// header.set_embedded_index(true);
+    ///
+    /// assert!(header.has_embedded_index());
+    /// assert!(header.has_footer());
+    /// ```
+    pub fn set_embedded_index(&mut self, embedded: bool) {
+        if embedded {
+            self.set_footer(true);
+        }
+        self.set_flag(FLAG_EMBEDDED_INDEX, embedded);
+    }
+
+    /// Returns whether records may omit their quality scores
+    pub fn has_optional_quality(&self) -> bool {
+        self.flags() & FLAG_OPTIONAL_QUALITY != 0
+    }
+
+    /// Sets whether records may omit their quality scores
+    ///
+    /// Files with quality scores otherwise require them for every record. With optional
+    /// quality scores, records written without them (e.g. with
+    /// `VBinseqWriter::write_nucleotides`) are marked as such, so datasets merged from
+    /// sources with and without quality scores need no placeholder scores. Their
+    /// `RefRecord::squal` is empty. This only applies to files with quality scores.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use vbinseq::VBinseqHeader;
+    ///
+    /// let mut header = VBinseqHeader::new(true, true, false);
+    /// header.set_optional_quality(true);
+    ///
+    /// assert!(header.has_optional_quality());
+    /// assert_eq!(header.format(), 2);
+    /// ```
+    pub fn set_optional_quality(&mut self, optional: bool) {
+        self.set_flag(FLAG_OPTIONAL_QUALITY, optional);
+    }
+
+    /// Returns whether records are marked when they have no quality scores
+    pub(crate) fn marks_quality(&self) -> bool {
+        self.qual && self.has_optional_quality()
+    }
+
+    /// Returns the number of lengths stored with every record
+    ///
+    /// Records store the lengths of two segments, even if they are not paired, or the
+    /// lengths of all their segments if they have more.
+    pub(crate) fn length_fields(&self) -> usize {
+        self.segments().max(2)
+    }
+
+    /// Returns the number of bytes of the lengths stored with every record
+    ///
+    /// Records of fixed-length files omit their lengths.
+    pub(crate) fn record_lengths(&self) -> usize {
+        if self.is_fixed_length() {
+            0
+        } else {
+            8 * self.length_fields()
+        }
+    }
+
+    /// Returns the number of bytes of the auxiliary value of every record
+    pub(crate) fn record_aux(&self) -> usize {
+        if self.has_aux() {
+            SIZE_AUX
+        } else {
+            0
+        }
+    }
+
+    /// Returns the number of bytes stored after the data of every record
+    ///
+    /// These are the auxiliary value and the checksum.
+    pub(crate) fn record_trailer(&self) -> usize {
+        let crc = if self.has_record_crc() {
+            SIZE_RECORD_CRC
+        } else {
+            0
+        };
+        self.record_aux() + crc
+    }
+
+    /// Returns whether records store one byte per stored nucleotide
+    ///
+    /// These are quality scores, or run lengths in homopolymer-compressed files.
+    pub(crate) fn has_base_bytes(&self) -> bool {
+        self.qual || self.is_homopolymer()
+    }
+
+    /// Returns the transform applied to quality scores before block compression
+    ///
+    /// Format 1 headers always return `QualityTransform::None`.
+    pub fn quality_transform(&self) -> QualityTransform {
+        if self.format == FORMAT_EXTENDED {
+            QualityTransform::from_byte(self.reserved[QUALITY_TRANSFORM_OFFSET])
+                .expect("quality transform is validated")
+        } else {
+            QualityTransform::None
+        }
+    }
+
+    /// Sets the transform applied to quality scores before block compression
+    ///
+    /// The writer transforms the quality scores of every record and readers restore
+    /// them, so the transform only changes how well blocks compress (see the `quality`
+    /// module). Like format extension flags, a transform other than
+    /// `QualityTransform::None` upgrades the header to format 2.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use vbinseq::{QualityTransform, VBinseqHeader};
+    ///
+    /// let mut header = VBinseqHeader::new(true, true, false);
+    /// header.set_quality_transform(QualityTransform::RunLength);
+    ///
+    /// assert_eq!(header.quality_transform(), QualityTransform::RunLength);
+    /// assert_eq!(header.format(), 2);
+    /// ```
+    pub fn set_quality_transform(&mut self, transform: QualityTransform) {
+        if self.format != FORMAT_EXTENDED && transform == QualityTransform::None {
+            return;
+        }
+        self.extend();
+        self.reserved[QUALITY_TRANSFORM_OFFSET] = transform.as_byte();
+        self.shrink();
+    }
+
+    /// Returns the size of the header sections following the header in bytes
+    ///
+    /// Files can store variable-length sections (e.g. a read-group table) between the
+    /// header and the first record block (see the `sections` module). Format 1 headers
+    /// have no sections and always return 0.
+    pub fn sections_size(&self) -> usize {
+        if self.format == FORMAT_EXTENDED {
+            let bytes = &self.reserved[SECTIONS_SIZE_OFFSET..EXTENSION_SIZE];
+            LittleEndian::read_u24(bytes) as usize
+        } else {
+            0
+        }
+    }
+
+    /// Sets the size of the header sections following the header
+    ///
+    /// The writer sets the size from the sections it writes, so this does not need to be
+    /// called by users. Like format extension flags, a nonzero size upgrades the header
+    /// to format 2.
+    ///
+    /// # Errors
+    ///
+    /// * `HeaderError::SectionsTooLarge` - If the size exceeds `MAX_SECTIONS_SIZE`
+    pub(crate) fn set_sections_size(&mut self, size: usize) -> Result<()> {
+        if size > MAX_SECTIONS_SIZE {
+            return Err(HeaderError::SectionsTooLarge(size, MAX_SECTIONS_SIZE).into());
+        }
+        if self.format != FORMAT_EXTENDED && size == 0 {
+            return Ok(());
+        }
+        self.extend();
+        LittleEndian::write_u24(
+            &mut self.reserved[SECTIONS_SIZE_OFFSET..EXTENSION_SIZE],
+            size as u32,
+        );
+        self.shrink();
+        Ok(())
+    }
+
+    /// Returns the offset of the first record block in the file
+    ///
+    /// This is the size of the header plus the size of the header sections.
+    pub fn data_offset(&self) -> usize {
+        SIZE_HEADER + self.sections_size()
+    }
+
+    /// Stamps the header with application-specific data
+    ///
+    /// A small region of the header's reserved bytes is available to applications
+    /// to mark files with custom markers (e.g. a pipeline version) without changing
+    /// the format. The region is claimed by an application id so that applications
+    /// can recognize their own markers and ignore those of others.
+    ///
+    /// Data shorter than `APP_DATA_SIZE` bytes is zero-padded.
+    ///
+    /// # Parameters
+    ///
+    /// * `app_id` - Identifier of the application claiming the region
+    /// * `data` - Up to `APP_DATA_SIZE` bytes of application-defined data
+    ///
+    /// # Errors
+    ///
+    /// * `HeaderError::InvalidAppId` - If `app_id` is `APP_ID_UNCLAIMED`
+    /// * `HeaderError::AppDataTooLarge` - If `data` is longer than `APP_DATA_SIZE` bytes
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use vbinseq::VBinseqHeader;
+    ///
+    /// let mut header = VBinseqHeader::default();
+    /// header.set_app_data(0x0101, b"v2.1").unwrap();
+    ///
+    /// assert_eq!(header.app_id(), Some(0x0101));
+    /// assert_eq!(header.app_data(0x0101), Some(&b"v2.1\0\0"[..]));
+    /// assert_eq!(header.app_data(0x0202), None);
+    /// ```
+    pub fn set_app_data(&mut self, app_id: u16, data: &[u8]) -> Result<()> {
+        if app_id == APP_ID_UNCLAIMED {
+            return Err(HeaderError::InvalidAppId(app_id).into());
+        }
+        if data.len() > APP_DATA_SIZE {
+            return Err(HeaderError::AppDataTooLarge(data.len(), APP_DATA_SIZE).into());
+        }
+        let region = &mut self.reserved[APP_REGION_OFFSET..];
+        LittleEndian::write_u16(&mut region[0..2], app_id);
+        region[2..].fill(0);
+        region[2..2 + data.len()].copy_from_slice(data);
+        Ok(())
+    }
+
+    /// Returns the id of the application that claimed the application region
+    ///
+    /// Returns `None` if the region is unclaimed.
+    pub fn app_id(&self) -> Option<u16> {
+        let app_id = LittleEndian::read_u16(&self.reserved[APP_REGION_OFFSET..]);
+        if app_id == APP_ID_UNCLAIMED {
+            None
+        } else {
+            Some(app_id)
+        }
+    }
+
+    /// Returns the application-defined data if the region is claimed by `app_id`
+    ///
+    /// Returns `None` if the region is unclaimed or claimed by another application.
+    pub fn app_data(&self, app_id: u16) -> Option<&[u8]> {
+        if self.app_id() == Some(app_id) {
+            Some(&self.reserved[APP_REGION_OFFSET + 2..])
+        } else {
+            None
+        }
+    }
+
+    /// Releases the application region, restoring its placeholder bytes
+    pub fn clear_app_data(&mut self) {
+        self.reserved[APP_REGION_OFFSET..].copy_from_slice(&RESERVED_BYTES[APP_REGION_OFFSET..]);
+    }
+
+    /// Returns a compact single-line summary of the header
+    ///
+    /// This is useful for logging where the multi-line `Display` output is too verbose.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use vbinseq::VBinseqHeader;
+    ///
+    /// let header = VBinseqHeader::new(true, true, false);
+    /// assert_eq!(
+    ///     header.summary(),
+    ///     "VBQ v1 block=131072 qual=yes compressed=yes paired=no"
+    /// );
+    /// ```
+    pub fn summary(&self) -> String {
+        format!(
+            "VBQ v{} block={} qual={} compressed={} paired={}",
+            self.format,
+            self.block,
+            yes_no(self.qual),
+            yes_no(self.compressed()),
+            yes_no(self.paired()),
+        )
+    }
+}
+impl fmt::Display for VBinseqHeader {
+    /// Formats the header as a human-readable multi-line description
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Format version:  {}", self.format)?;
+        writeln!(f, "Block size:      {} bytes", self.block)?;
+        writeln!(f, "Quality scores:  {}", yes_no(self.qual))?;
+        match self.codec {
+            Codec::Uncompressed => writeln!(f, "Compressed:      no")?,
+            codec => writeln!(f, "Compressed:      yes ({codec})")?,
+        }
+        write!(f, "Paired:          {}", yes_no(self.paired()))?;
+        if self.segments > 2 {
+            write!(f, "\nSegments:        {}", self.segments)?;
+        }
+        if self.has_footer() {
+            write!(f, "\nFooter:          yes")?;
+        }
+        if self.is_homopolymer() {
+            write!(f, "\nHomopolymer:     yes")?;
+        }
+        if self.has_record_crc() {
+            write!(f, "\nRecord CRC:      yes")?;
+        }
+        if self.has_aux() {
+            write!(f, "\nAux values:      yes")?;
+        }
+        if self.is_fixed_length() {
+            write!(f, "\nFixed length:    yes")?;
+        }
+        if self.has_optional_quality() {
+            write!(f, "\nOptional qual:   yes")?;
+        }
+        if self.has_block_checksum() {
+            write!(f, "\nBlock checksum:  yes")?;
+        }
+        if self.has_embedded_index() {
+            write!(f, "\nEmbedded index:  yes")?;
+        }
+        if self.is_iupac() {
+            write!(f, "\nIUPAC:           yes")?;
+        }
+        if self.is_columnar() {
+            write!(f, "\nColumnar:        yes")?;
+        }
+        if self.has_codec_fallback() {
+            write!(f, "\nCodec fallback:  yes")?;
+        }
+        if self.quality_transform() != QualityTransform::None {
+            write!(f, "\nQual transform:  {}", self.quality_transform())?;
+        }
+        if self.sections_size() > 0 {
+            write!(f, "\nHeader sections: {} bytes", self.sections_size())?;
+        }
+        if let Some(app_id) = self.app_id() {
+            write!(f, "\nApplication id:  {app_id:#06x}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Renders a boolean flag as "yes" or "no" for human-readable output
+fn yes_no(flag: bool) -> &'static str {
+    if flag {
+        "yes"
+    } else {
+        "no"
+    }
+}
+
+/// Block header for VBINSEQ block data
+///
+/// Each block in a VBINSEQ file is preceded by a 32-byte block header that contains
+/// information about the block including its size and the number of records it contains.
+///
+/// # Fields
+///
+/// * `magic` - Magic number to validate block integrity ("BLOCKSEQ", 8 bytes)
+/// * `size` - Actual size of the block in bytes (8 bytes)
+/// * `records` - Number of records in the block (4 bytes)
+/// * `reserved` - Reserved bytes for future extensions (12 bytes), the first of which
+///   records the codec of the block data and the second of which marks blocks holding
+///   zero-length records
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BlockHeader {
+    /// Magic number to identify the block ("BLOCKSEQ")
+    ///
+    /// Always set to 0x5145534B434F4C42 (8 bytes)
+    pub magic: u64,
+
+    /// Actual size of the block in bytes
+    ///
+    /// This can differ from the virtual block size in the file header
+    /// when compression is enabled (8 bytes)
+    pub size: u64,
+
+    /// Number of records stored in this block
+    ///
+    /// Used to iterate through records efficiently (4 bytes)
+    pub records: u32,
+
+    /// Reserved bytes for future extensions
+    ///
+    /// The first byte records the codec of the block (see `codec`), the second marks
+    /// blocks holding zero-length records (see `has_empty_records`), the third and fourth
+    /// record the compression dictionary of the block (see `dictionary`),
+    /// the remaining bytes are filled with placeholder values (12 bytes)
+    pub reserved: [u8; 12],
+}
+impl BlockHeader {
+    /// Creates a new block header
+    ///
+    /// # Parameters
+    ///
+    /// * `size` - The actual size of the block in bytes (can be compressed size)
+    /// * `records` - The number of records contained in the block
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use vbinseq::BlockHeader;
+    ///
+    /// // Create a block header for a block with 1024 bytes and 100 records
+    /// let header = BlockHeader::new(1024, 100);
+    /// ```
+    pub fn new(size: u64, records: u32) -> Self {
+        Self {
+            magic: BLOCK_MAGIC,
+            size,
+            records,
+            reserved: RESERVED_BYTES_BLOCK,
+        }
+    }
+
+    /// Writes the block header to a writer
+    ///
+    /// This function serializes the block header structure into a 32-byte buffer and writes
+    /// it to the provided writer.
+    ///
+    /// # Parameters
+    ///
+    /// * `writer` - Any type that implements the `Write` trait
+    ///
+    /// # Returns
+    ///
+    /// * `Result<()>` - Success if the header was written
+    ///
+    /// # Errors
+    ///
+    /// * IO errors if writing to the writer fails
+    pub fn write_bytes<W: Write>(&self, writer: &mut W) -> Result<()> {
+        let mut buffer = [0u8; SIZE_BLOCK_HEADER];
+        LittleEndian::write_u64(&mut buffer[0..8], self.magic);
+        LittleEndian::write_u64(&mut buffer[8..16], self.size);
+        LittleEndian::write_u32(&mut buffer[16..20], self.records);
+        buffer[20..].copy_from_slice(&self.reserved);
+        writer.write_all(&buffer)?;
+        Ok(())
+    }
+
+    /// Creates a block header from a 32-byte buffer
+    ///
+    /// This function parses a raw byte buffer into a `BlockHeader` structure,
+    /// validating the magic number.
+    ///
+    /// # Parameters
+    ///
+    /// * `buffer` - A 32-byte array containing the block header data
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Self>` - A valid block header if parsing was successful
+    ///
+    /// # Errors
+    ///
+    /// * `ReadError::InvalidBlockMagicNumber` - If the magic number doesn't match "BLOCKSEQ"
+    pub fn from_bytes(buffer: &[u8; SIZE_BLOCK_HEADER]) -> Result<Self> {
+        let magic = LittleEndian::read_u64(&buffer[0..8]);
+        if magic != BLOCK_MAGIC {
+            return Err(ReadError::InvalidBlockMagicNumber(magic, 0).into());
+        }
+        let size = LittleEndian::read_u64(&buffer[8..16]);
+        let records = LittleEndian::read_u32(&buffer[16..20]);
+        let mut header = Self::new(size, records);
+        header.reserved.copy_from_slice(&buffer[20..]);
+        Ok(header)
+    }
+
+    /// Returns the codec recorded for this block
+    ///
+    /// Blocks written before codecs were recorded per block return `None`,
+    /// in which case the codec of the file header applies.
+    ///
+    /// # Errors
+    ///
+    /// * `HeaderError::InvalidCodec` - If the recorded codec is unknown
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use vbinseq::{BlockHeader, Codec};
+    ///
+    /// let mut header = BlockHeader::new(1024, 100);
+    /// assert_eq!(header.codec().unwrap(), None);
+    ///
+    /// header.set_codec(Codec::Zstd);
+    /// assert_eq!(header.codec().unwrap(), Some(Codec::Zstd));
+    /// ```
+    pub fn codec(&self) -> Result<Option<Codec>> {
+        match self.reserved[0] {
+            CODEC_UNSPECIFIED => Ok(None),
+            byte => Codec::from_byte(byte).map(Some),
+        }
+    }
+
+    /// Records the codec used for this block
+    pub fn set_codec(&mut self, codec: Codec) {
+        self.reserved[0] = codec.as_byte();
+    }
+
+    /// Returns whether the block may hold records with an empty primary sequence
+    ///
+    /// Readers parse exactly `records` records of every block. In blocks without this
+    /// mark, a record with an empty primary sequence can only be the zero padding after
+    /// the last record, so finding one before `records` records were parsed means the
+    /// record count of the header is wrong.
+    pub fn has_empty_records(&self) -> bool {
+        self.reserved[1] == BLOCK_EMPTY_RECORDS
+    }
+
+    /// Marks whether the block holds records with an empty primary sequence
+    ///
+    /// The writer sets this for every block it writes with such records.
+    pub fn set_empty_records(&mut self, empty: bool) {
+        self.reserved[1] = if empty {
+            BLOCK_EMPTY_RECORDS
+        } else {
+            RESERVED_BYTES_BLOCK[1]
+        };
+    }
+
+    /// Returns the compression dictionary used for this block
+    ///
+    /// This is the position of the dictionary in the header sections (see the
+    /// `dictionary` module), or `None` if the block was compressed without one.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use vbinseq::BlockHeader;
+    ///
+    /// let mut header = BlockHeader::new(1024, 100);
+    /// assert_eq!(header.dictionary(), None);
+    ///
+    /// header.set_dictionary(Some(2));
+    /// assert_eq!(header.dictionary(), Some(2));
+    /// ```
+    pub fn dictionary(&self) -> Option<u8> {
+        (self.reserved[2] == BLOCK_DICTIONARY).then_some(self.reserved[3])
+    }
+
+    /// Records the compression dictionary used for this block
+    pub fn set_dictionary(&mut self, dictionary: Option<u8>) {
+        match dictionary {
+            Some(id) => {
+                self.reserved[2] = BLOCK_DICTIONARY;
+                self.reserved[3] = id;
+            }
+            None => self.reserved[2..4].copy_from_slice(&RESERVED_BYTES_BLOCK[2..4]),
+        }
+    }
+
+    /// Returns the length of every record of the block in fixed-length files
+    ///
+    /// Records of fixed-length files (see `VBinseqHeader::set_fixed_length`) omit their
+    /// lengths, which are recorded in the block header instead. The value is meaningless
+    /// for blocks of other files.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use vbinseq::BlockHeader;
+    ///
+    /// let mut header = BlockHeader::new(1024, 100);
+    /// header.set_fixed_length(150);
+    /// assert_eq!(header.fixed_length(), 150);
+    /// ```
+    pub fn fixed_length(&self) -> u64 {
+        LittleEndian::read_u64(&self.reserved[BLOCK_FIXED_LENGTH_OFFSET..])
+    }
+
+    /// Records the length of every record of the block in fixed-length files
+    pub fn set_fixed_length(&mut self, length: u64) {
+        LittleEndian::write_u64(&mut self.reserved[BLOCK_FIXED_LENGTH_OFFSET..], length);
+    }
+
+    /// Returns the checksum of the uncompressed block in files with block checksums
+    ///
+    /// This is the XXH3 64-bit hash of the block data before compression, including the
+    /// padding after the last record (see `VBinseqHeader::set_block_checksum`). The value
+    /// is meaningless for blocks of other files.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use vbinseq::BlockHeader;
+    ///
+    /// let mut header = BlockHeader::new(1024, 100);
+    /// header.set_checksum(0x1234_5678_9abc_def0);
+    /// assert_eq!(header.checksum(), 0x1234_5678_9abc_def0);
+    /// ```
+    pub fn checksum(&self) -> u64 {
+        LittleEndian::read_u64(&self.reserved[BLOCK_CHECKSUM_OFFSET..])
+    }
+
+    /// Records the checksum of the uncompressed block
+    pub fn set_checksum(&mut self, checksum: u64) {
+        LittleEndian::write_u64(&mut self.reserved[BLOCK_CHECKSUM_OFFSET..], checksum);
+    }
+
+    /// Returns a compact single-line summary of the block header
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use vbinseq::BlockHeader;
+    ///
+    /// let header = BlockHeader::new(1024, 100);
+    /// assert_eq!(header.summary(), "block size=1024 records=100");
+    /// ```
+    pub fn summary(&self) -> String {
+        format!("block size={} records={}", self.size, self.records)
+    }
+}
+impl fmt::Display for BlockHeader {
+    /// Formats the block header as a human-readable multi-line description
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Block size:      {} bytes", self.size)?;
+        write!(f, "Records:         {}", self.records)?;
+        if let Ok(Some(codec)) = self.codec() {
+            write!(f, "\nCodec:           {codec}")?;
+        }
+        if let Some(dictionary) = self.dictionary() {
+            write!(f, "\nDictionary:      {dictionary}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_zero_block_size() -> Result<()> {
+        let mut header = VBinseqHeader::with_capacity(1024, true, false, true);
+        assert!(header.set_block(0).is_err());
+        assert_eq!(header.block(), 1024);
+
+        // Headers with a zero block size are rejected when read
+        let mut bytes = Vec::new();
+        header.write_bytes(&mut bytes)?;
+        bytes[5..13].fill(0);
+        let error = VBinseqHeader::from_bytes(bytes[..].try_into().unwrap()).unwrap_err();
+        assert!(matches!(
+            error,
+            crate::Error::HeaderError(HeaderError::InvalidBlockSize(0))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_segments() -> Result<()> {
+        let mut header = VBinseqHeader::with_capacity(1024, true, false, false);
+        assert_eq!((header.segments(), header.record_lengths()), (1, 16));
+        header.set_segments(5)?;
+        assert_eq!((header.segments(), header.record_lengths()), (5, 40));
+        assert!(header.set_segments(MAX_SEGMENTS + 1).is_err());
+
+        // The count replaces the paired byte and is read back
+        let mut bytes = Vec::new();
+        header.write_bytes(&mut bytes)?;
+        assert_eq!(bytes[15], 5);
+        let read = VBinseqHeader::from_bytes(bytes[..].try_into().unwrap())?;
+        assert_eq!(read, header);
+
+        // Counted headers must have more than two segments
+        bytes[15] = 2;
+        let error = VBinseqHeader::from_bytes(bytes[..].try_into().unwrap()).unwrap_err();
+        assert!(matches!(
+            error,
+            crate::Error::HeaderError(HeaderError::InvalidSegments(2))
+        ));
+
+        // Pairs keep the boolean paired byte
+        header.set_paired(true);
+        assert_eq!(header.segments(), 2);
+        assert_eq!(header.flags() & FLAG_SEGMENTS, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_codec_format() -> Result<()> {
+        for (codec, format) in [
+            (Codec::Uncompressed, 1),
+            (Codec::Zstd, 1),
+            (Codec::Lz4, 2),
+            (Codec::Xz, 2),
+        ] {
+            let mut header = VBinseqHeader::with_capacity(1024, true, false, false);
+            header.set_codec(codec);
+            assert_eq!(header.format(), format, "{codec}");
+
+            // The format byte is written and the header read back
+            let mut bytes = Vec::new();
+            header.write_bytes(&mut bytes)?;
+            assert_eq!((bytes[4], bytes[14]), (format, codec.as_byte()));
+            let read = VBinseqHeader::from_bytes(bytes[..].try_into().unwrap())?;
+            assert_eq!(read, header);
+            assert_eq!((read.format(), read.codec()), (format, codec));
+
+            // Extensions keep format 2 and switching back to zstd restores format 1
+            header.set_footer(true);
+            header.set_footer(false);
+            assert_eq!(header.format(), format);
+            header.set_codec(Codec::Zstd);
+            assert_eq!(header.format(), 1);
+        }
+        Ok(())
     }
 }
@@ -0,0 +1,149 @@
+//! # Homopolymer Compression
+//!
+//! Files with the homopolymer extension enabled (see `VBinseqHeader::set_homopolymer`)
+//! store homopolymer-compressed sequences: every run of a repeated nucleotide is stored as
+//! a single nucleotide along with the length of the run. Long-read analyses often work on
+//! compressed sequences natively, and long reads hold enough homopolymers that storing
+//! them compressed noticeably cuts the number of stored nucleotides.
+//!
+//! The run lengths take the place of the quality scores: records store one run length
+//! byte per stored nucleotide, which is returned by `RefRecord::squal` and
+//! `RefRecord::xqual`. Homopolymer-compressed files therefore cannot store quality scores.
+//! Runs longer than 255 nucleotides are split into several runs of the same nucleotide.
+//!
+//! The writer compresses sequences passed to `write_nucleotides` and
+//! `write_nucleotides_paired`. Readers return the compressed sequences, which can be
+//! expanded back into the original sequences with `expand`.
+//!
+//! # Example
+//!
+//! ```rust
+//! use vbinseq::homopolymer::{collapse, expand};
+//!
+//! let (mut sequence, mut runs) = (Vec::new(), Vec::new());
+//! collapse(b"AAACGGTTTT", &mut sequence, &mut runs);
+//! assert_eq!(sequence, b"ACGT");
+//! assert_eq!(runs, [3, 1, 2, 4]);
+//!
+//! let mut expanded = Vec::new();
+//! expand(&sequence, &runs, &mut expanded).unwrap();
+//! assert_eq!(expanded, b"AAACGGTTTT");
+//! ```
+
+use crate::error::{ReadError, Result};
+
+/// Homopolymer-compresses a sequence
+///
+/// Nucleotides are compared case-insensitively, and each run keeps the case of its first
+/// nucleotide. Both buffers are cleared first.
+///
+/// # Parameters
+///
+/// * `sequence` - The sequence to compress
+/// * `collapsed` - The buffer receiving one nucleotide per run
+/// * `runs` - The buffer receiving the length of every run (1 to 255)
+pub fn collapse(sequence: &[u8], collapsed: &mut Vec<u8>, runs: &mut Vec<u8>) {
+    collapsed.clear();
+    runs.clear();
+    for &nucleotide in sequence {
+        match (collapsed.last(), runs.last_mut()) {
+            (Some(last), Some(run)) if last.eq_ignore_ascii_case(&nucleotide) && *run < u8::MAX => {
+                *run += 1;
+            }
+            _ => {
+                collapsed.push(nucleotide);
+                runs.push(1);
+            }
+        }
+    }
+}
+
+/// Expands a homopolymer-compressed sequence into the original sequence
+///
+/// The expanded sequence is appended to `expanded`.
+///
+/// # Parameters
+///
+/// * `collapsed` - The decoded compressed sequence
+/// * `runs` - The run length of every nucleotide of `collapsed`
+/// * `expanded` - The buffer receiving the expanded sequence
+///
+/// # Errors
+///
+/// * `ReadError::InvalidHomopolymerRuns` - If the number of runs differs from the number
+///   of nucleotides, or a run has a length of 0
+pub fn expand(collapsed: &[u8], runs: &[u8], expanded: &mut Vec<u8>) -> Result<()> {
+    if collapsed.len() != runs.len() || runs.contains(&0) {
+        return Err(ReadError::InvalidHomopolymerRuns(collapsed.len(), runs.len()).into());
+    }
+    expanded.reserve(runs.iter().map(|&run| run as usize).sum());
+    for (&nucleotide, &run) in collapsed.iter().zip(runs) {
+        expanded.extend(std::iter::repeat_n(nucleotide, run as usize));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MemoryReader, OwnedRecord, VBinseqHeader, VBinseqWriterBuilder};
+
+    #[test]
+    fn test_homopolymer_file() -> Result<()> {
+        let long_run = vec![b'G'; 600];
+        let sequences: [&[u8]; 3] = [b"AAAcCCGTTTTTTTT", &long_run, b"ACGTNNNN"];
+
+        let mut header = VBinseqHeader::with_capacity(1024, false, false, true);
+        header.set_homopolymer(true);
+        let mut bytes = Vec::new();
+        let mut writer = VBinseqWriterBuilder::default()
+            .header(header)
+            .policy(crate::Policy::SetToA)
+            .build(&mut bytes)?;
+        for sequence in sequences {
+            assert!(writer.write_nucleotides_paired(0, sequence, b"TTGA")?);
+        }
+        writer.finish()?;
+        drop(writer);
+
+        let mut reader = MemoryReader::new(bytes)?;
+        assert!(reader.header().is_homopolymer());
+        let mut block = reader.new_block();
+        let (mut dbuf, mut expanded) = (Vec::new(), Vec::new());
+        let mut n_records = 0;
+        while reader.read_block_into(&mut block)? {
+            for record in block.iter() {
+                dbuf.clear();
+                record.decode_s(&mut dbuf)?;
+                assert_eq!(dbuf.len(), record.squal().len());
+                expanded.clear();
+                expand(&dbuf, record.squal(), &mut expanded)?;
+                let expected = sequences[n_records]
+                    .to_ascii_uppercase()
+                    .iter()
+                    .map(|&n| if n == b'N' { b'A' } else { n })
+                    .collect::<Vec<u8>>();
+                assert_eq!(expanded, expected);
+                assert_eq!(record.xqual(), [2, 1, 1]);
+
+                // Owned records are expanded without their run lengths
+                assert!(record.is_homopolymer());
+                let mut owned = OwnedRecord::try_from(&record)?;
+                owned.expand_homopolymers()?;
+                assert_eq!((owned.seq(), owned.xseq()), (&expected[..], &b"TTGA"[..]));
+                assert!(!owned.has_quality() && owned.xqual().is_empty());
+                n_records += 1;
+            }
+        }
+        assert_eq!(n_records, 3);
+
+        // Runs of a long homopolymer are split
+        let (mut collapsed, mut runs) = (Vec::new(), Vec::new());
+        collapse(&long_run, &mut collapsed, &mut runs);
+        assert_eq!(collapsed, b"GGG");
+        assert_eq!(runs, [255, 255, 90]);
+        assert!(expand(b"AC", &[1], &mut expanded).is_err());
+        assert!(expand(b"A", &[0], &mut expanded).is_err());
+        Ok(())
+    }
+}
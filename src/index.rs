@@ -1,15 +1,23 @@
+#[cfg(feature = "compression")]
+use std::fs::File;
+#[cfg(feature = "compression")]
+use std::io::{BufReader, BufWriter};
 use std::{
-    fs::File,
-    io::{BufReader, BufWriter, Read, Write},
+    io::{Read, Write},
+    ops::Range,
     path::Path,
 };
 
 use byteorder::{ByteOrder, LittleEndian};
+#[cfg(feature = "compression")]
 use zstd::{Decoder, Encoder};
 
 use crate::{
-    error::IndexError,
+    dictionary,
+    error::{ErrorContext, IndexError, ReadError},
+    footer::{block_records, data_end, read_header, SIZE_FOOTER},
     header::{SIZE_BLOCK_HEADER, SIZE_HEADER},
+    reader::load_file,
     BlockHeader, Result, VBinseqHeader,
 };
 
@@ -21,6 +29,41 @@ pub const INDEX_HEADER_SIZE: usize = 32;
 pub const INDEX_MAGIC: u64 = 0x5845444e49514256;
 /// Index Block Reservation
 pub const INDEX_RESERVATION: [u8; 8] = [42; 8];
+/// Marker of index headers followed by record offsets (VBQRECOF)
+pub const INDEX_RECORD_OFFSETS: [u8; 8] = *b"VBQRECOF";
+
+/// When readers rebuild the index of a file instead of loading its index file
+///
+/// Building an index scans the block headers of the whole file, which is expensive for
+/// large files on slow storage, so callers can choose when it may happen
+/// (see `MmapReader::set_index_policy`).
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use vbinseq::{IndexPolicy, MmapReader};
+///
+/// // Fail instead of rescanning the file if its index is missing or stale
+/// let mut reader = MmapReader::new("example.vbq").unwrap();
+/// reader.set_index_policy(IndexPolicy::RequireValid);
+/// let index = reader.load_index().unwrap();
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum IndexPolicy {
+    /// Only load the index file, returning an error if it is missing or does not match
+    /// the file
+    RequireValid,
+
+    /// Load the index file, rebuilding (and saving) the index if the index file is
+    /// missing or was built for another version of the file
+    ///
+    /// Other errors, such as a corrupt index file, are returned.
+    #[default]
+    RebuildOnMismatch,
+
+    /// Always rebuild (and save) the index, ignoring any existing index file
+    AlwaysRebuild,
+}
 
 /// Descriptor of the dimensions of a block in a VBINSEQ file
 ///
@@ -48,7 +91,7 @@ pub const INDEX_RESERVATION: [u8; 8] = [42; 8];
 /// println!("Block starts at byte {}", range.start_offset);
 /// println!("Block contains {} records", range.block_records);
 /// ```
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct BlockRange {
     /// File offset where the block starts (in bytes, including headers)
     ///
@@ -71,16 +114,21 @@ pub struct BlockRange {
     /// (4 bytes in serialized form)
     pub block_records: u32,
 
-    /// Cumulative number of records up to and including this block
+    /// Cumulative number of records before this block
     ///
-    /// This allows efficient determination of which block contains a specific record
-    /// by index without scanning through all previous blocks.
+    /// This is the (0-based) index of the first record of the block, which allows
+    /// efficient determination of which block contains a specific record by index
+    /// without scanning through all previous blocks.
     ///
-    /// (4 bytes in serialized form)
-    pub cumulative_records: u32,
+    /// (4 bytes in serialized form, holding the lower 32 bits of the count; the full count
+    /// is restored from the block record counts when an index is loaded)
+    pub cumulative_records: u64,
 
     /// Reserved bytes for future extensions
     ///
+    /// For BGZF-framed files these bytes hold the virtual offset of the block
+    /// (see `virtual_offset`).
+    ///
     /// (8 bytes in serialized form)
     pub reservation: [u8; 8],
 }
@@ -92,7 +140,7 @@ impl BlockRange {
     /// * `start_offset` - The byte offset in the file where this block starts
     /// * `len` - The length of the block data in bytes
     /// * `block_records` - The number of records contained in this block
-    /// * `cumulative_records` - The total number of records up to and including this block
+    /// * `cumulative_records` - The total number of records before this block
     ///
     /// # Returns
     ///
@@ -106,7 +154,7 @@ impl BlockRange {
     /// // Create a new block range for a block starting at byte 1024
     /// let range = BlockRange::new(1024, 8192, 1000, 5000);
     /// ```
-    pub fn new(start_offset: u64, len: u64, block_records: u32, cumulative_records: u32) -> Self {
+    pub fn new(start_offset: u64, len: u64, block_records: u32, cumulative_records: u64) -> Self {
         Self {
             start_offset,
             len,
@@ -123,7 +171,7 @@ impl BlockRange {
     /// - Bytes 0-7: start_offset (u64, little endian)
     /// - Bytes 8-15: len (u64, little endian)
     /// - Bytes 16-19: block_records (u32, little endian)
-    /// - Bytes 20-23: cumulative_records (u32, little endian)
+    /// - Bytes 20-23: cumulative_records (lower 32 bits, little endian)
     /// - Bytes 24-31: reservation (8 bytes)
     ///
     /// # Parameters
@@ -139,7 +187,7 @@ impl BlockRange {
         LittleEndian::write_u64(&mut buf[0..8], self.start_offset);
         LittleEndian::write_u64(&mut buf[8..16], self.len);
         LittleEndian::write_u32(&mut buf[16..20], self.block_records);
-        LittleEndian::write_u32(&mut buf[20..24], self.cumulative_records);
+        LittleEndian::write_u32(&mut buf[20..24], self.cumulative_records as u32);
         buf[24..].copy_from_slice(&self.reservation);
         writer.write_all(&buf)?;
         Ok(())
@@ -164,18 +212,45 @@ impl BlockRange {
     /// - Bytes 0-7: start_offset (u64, little endian)
     /// - Bytes 8-15: len (u64, little endian)
     /// - Bytes 16-19: block_records (u32, little endian)
-    /// - Bytes 20-23: cumulative_records (u32, little endian)
-    /// - Bytes 24-31: reservation (ignored, default value used)
+    /// - Bytes 20-23: cumulative_records (lower 32 bits, little endian)
+    /// - Bytes 24-31: reservation (8 bytes)
+    ///
+    /// As only the lower 32 bits of the cumulative record count are stored, counts of
+    /// files with more than `u32::MAX` records are truncated. `BlockIndex::from_path`
+    /// restores them from the record counts of the blocks.
     pub fn from_exact(buffer: &[u8; SIZE_BLOCK_RANGE]) -> Self {
+        let mut reservation = [0; 8];
+        reservation.copy_from_slice(&buffer[24..32]);
         Self {
             start_offset: LittleEndian::read_u64(&buffer[0..8]),
             len: LittleEndian::read_u64(&buffer[8..16]),
             block_records: LittleEndian::read_u32(&buffer[16..20]),
-            cumulative_records: LittleEndian::read_u32(&buffer[20..24]),
-            reservation: INDEX_RESERVATION,
+            cumulative_records: u64::from(LittleEndian::read_u32(&buffer[20..24])),
+            reservation,
         }
     }
 
+    /// Returns the BGZF virtual offset of the block
+    ///
+    /// The virtual offset is only recorded for blocks of BGZF-framed files
+    /// (see the `bgzf` module). Returns `None` if no virtual offset is recorded.
+    pub fn virtual_offset(&self) -> Option<u64> {
+        if self.reservation == INDEX_RESERVATION {
+            None
+        } else {
+            Some(LittleEndian::read_u64(&self.reservation))
+        }
+    }
+
+    /// Records the BGZF virtual offset of the block
+    ///
+    /// # Parameters
+    ///
+    /// * `virtual_offset` - The virtual offset of the BGZF frame the block starts in
+    pub fn set_virtual_offset(&mut self, virtual_offset: u64) {
+        LittleEndian::write_u64(&mut self.reservation, virtual_offset);
+    }
+
     /// Deserializes a `BlockRange` from a slice of bytes
     ///
     /// This is a convenience method that copies the first 32 bytes from the provided slice
@@ -223,6 +298,8 @@ pub struct IndexHeader {
 
     /// Reserved bytes for future extensions
     ///
+    /// Indexes with record offsets store `INDEX_RECORD_OFFSETS` and the number of blocks
+    /// here (see `BlockIndex::add_record_offsets`).
     /// (16 bytes in serialized form)
     reserved: [u8; INDEX_HEADER_SIZE - 16],
 }
@@ -263,22 +340,43 @@ impl IndexHeader {
     /// The header is expected to be 32 bytes with the following structure:
     /// - Bytes 0-7: magic number (u64, little endian, must be INDEX_MAGIC)
     /// - Bytes 8-15: file size in bytes (u64, little endian)
-    /// - Bytes 16-31: reserved for future extensions
+    /// - Bytes 16-31: reserved for future extensions (the record offsets marker and the
+    ///   number of blocks in indexes with record offsets)
     pub fn from_reader<R: Read>(reader: &mut R) -> Result<Self> {
         let mut buffer = [0; INDEX_HEADER_SIZE];
         reader.read_exact(&mut buffer)?;
         let magic = LittleEndian::read_u64(&buffer[0..8]);
         let bytes = LittleEndian::read_u64(&buffer[8..16]);
-        let _reserved = &buffer[16..INDEX_HEADER_SIZE]; // Not used but bytes pulled to validate size
         if magic != INDEX_MAGIC {
             return Err(IndexError::InvalidMagicNumber(magic).into());
         }
+        let mut reserved = [0; INDEX_HEADER_SIZE - 16];
+        reserved.copy_from_slice(&buffer[16..INDEX_HEADER_SIZE]);
         Ok(Self {
             magic,
             bytes,
-            reserved: [42; INDEX_HEADER_SIZE - 16],
+            reserved,
         })
     }
+
+    /// Returns the number of blocks of an index followed by record offsets
+    ///
+    /// Returns `None` if the index has no record offsets.
+    fn record_blocks(&self) -> Option<u64> {
+        (self.reserved[..8] == INDEX_RECORD_OFFSETS)
+            .then(|| LittleEndian::read_u64(&self.reserved[8..]))
+    }
+
+    /// Marks the index as followed by record offsets
+    ///
+    /// # Parameters
+    ///
+    /// * `n_blocks` - The number of block ranges preceding the record offsets
+    fn set_record_blocks(&mut self, n_blocks: u64) {
+        self.reserved[..8].copy_from_slice(&INDEX_RECORD_OFFSETS);
+        LittleEndian::write_u64(&mut self.reserved[8..], n_blocks);
+    }
+
     /// Serializes the index header to a binary format and writes it to the provided writer
     ///
     /// This method serializes the `IndexHeader` to a fixed-size 32-byte structure and
@@ -341,10 +439,15 @@ impl IndexHeader {
 #[derive(Debug, Clone)]
 pub struct BlockIndex {
     /// Header containing metadata about the indexed file
+    #[cfg_attr(not(feature = "compression"), allow(dead_code))]
     header: IndexHeader,
 
     /// Collection of block ranges, one for each block in the file
     ranges: Vec<BlockRange>,
+
+    /// Offset of every record in its decompressed block, by record index
+    /// None unless the index was built with record offsets
+    record_offsets: Option<Vec<u32>>,
 }
 impl BlockIndex {
     /// Creates a new empty block index with the specified header
@@ -360,6 +463,7 @@ impl BlockIndex {
         Self {
             header,
             ranges: Vec::default(),
+            record_offsets: None,
         }
     }
     /// Returns the number of blocks in the indexed file
@@ -386,7 +490,9 @@ impl BlockIndex {
     ///
     /// This writes the index header and all block ranges to a file, which can be loaded
     /// later to avoid rescanning the VBINSEQ file. The index is compressed to reduce
-    /// storage space.
+    /// storage space, so this requires the `compression` feature.
+    ///
+    /// Record offsets (see `add_record_offsets`) are saved after the block ranges.
     ///
     /// # Parameters
     ///
@@ -409,11 +515,19 @@ impl BlockIndex {
     /// // Save it for future use
     /// index.save_to_path(Path::new("example.vbq.vqi")).unwrap();
     /// ```
+    #[cfg(feature = "compression")]
     pub fn save_to_path<P: AsRef<Path>>(&self, path: P) -> Result<()> {
         let mut writer = File::create(path).map(BufWriter::new)?;
-        self.header.write_bytes(&mut writer)?;
+        let mut header = self.header;
+        if self.record_offsets.is_some() {
+            header.set_record_blocks(self.ranges.len() as u64);
+        }
+        header.write_bytes(&mut writer)?;
         let mut writer = Encoder::new(writer, 3)?.auto_finish();
         self.write_range(&mut writer)?;
+        for &offset in self.record_offsets.iter().flatten() {
+            writer.write_all(&offset.to_le_bytes())?;
+        }
         writer.flush()?;
         Ok(())
     }
@@ -447,7 +561,7 @@ impl BlockIndex {
     /// # Parameters
     ///
     /// * `range` - The block range to add to the index
-    fn add_range(&mut self, range: BlockRange) {
+    pub(crate) fn add_range(&mut self, range: BlockRange) {
         self.ranges.push(range);
     }
 
@@ -494,32 +608,66 @@ impl BlockIndex {
     /// This method uses memory mapping for efficiency, which allows the operating system
     /// to load only the needed portions of the file into memory as they are accessed.
     pub fn from_vbq<P: AsRef<Path>>(path: P) -> Result<Self> {
-        let file = File::open(path)?;
-        let mmap = unsafe { memmap2::Mmap::map(&file)? };
-        let file_size = mmap.len();
+        let locate = |e: crate::Error| e.with_context(ErrorContext::for_path(path.as_ref()));
+        let bytes = load_file(&path).map_err(locate)?;
+        Self::from_bytes(&bytes).map_err(locate)
+    }
 
-        // Read header from mapped memory (unused but checks for validity)
-        let _header = {
+    /// Creates a new index by scanning the block headers of a VBINSEQ file held in memory
+    ///
+    /// # Parameters
+    ///
+    /// * `bytes` - The full contents of a VBINSEQ file
+    ///
+    /// # Returns
+    ///
+    /// A new `BlockIndex` describing every block of the file
+    ///
+    /// # Errors
+    ///
+    /// * `ReadError::EmptyFile` if the buffer is empty
+    /// * `ReadError::UnexpectedEndOfFile` if the buffer is too small to hold a header
+    /// * Header validation errors from the file header or any block header
+    ///
+    /// A file holding only a header (and possibly a footer) yields an empty index.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let file_size = bytes.len();
+        if file_size == 0 {
+            return Err(ReadError::EmptyFile.into());
+        }
+        if file_size < SIZE_HEADER {
+            return Err(ReadError::UnexpectedEndOfFile(file_size).into());
+        }
+
+        // Read header from memory
+        let header = {
             let mut header_bytes = [0u8; SIZE_HEADER];
-            header_bytes.copy_from_slice(&mmap[..SIZE_HEADER]);
+            header_bytes.copy_from_slice(&bytes[..SIZE_HEADER]);
             VBinseqHeader::from_bytes(&header_bytes)?
         };
 
-        // Initialize position after the header
-        let mut pos = SIZE_HEADER;
+        // Initialize position after the header and its sections
+        let mut pos = header.data_offset();
 
         // Initialize the collection
         let index_header = IndexHeader::new(file_size as u64);
         let mut index = BlockIndex::new(index_header);
 
-        // Find all block headers
+        // Find all block headers (stopping at the footer if present)
+        let end = data_end(bytes, &header)?;
         let mut record_total = 0;
-        while pos < mmap.len() {
+        while pos < end {
+            if end - pos < SIZE_BLOCK_HEADER {
+                return Err(ReadError::UnexpectedEndOfFile(pos).into());
+            }
             let block_header = {
                 let mut header_bytes = [0u8; SIZE_BLOCK_HEADER];
-                header_bytes.copy_from_slice(&mmap[pos..pos + SIZE_BLOCK_HEADER]);
+                header_bytes.copy_from_slice(&bytes[pos..pos + SIZE_BLOCK_HEADER]);
                 BlockHeader::from_bytes(&header_bytes)?
             };
+            if block_header.size > (end - pos - SIZE_BLOCK_HEADER) as u64 {
+                return Err(ReadError::UnexpectedEndOfFile(pos).into());
+            }
             index.add_range(BlockRange::new(
                 pos as u64,
                 block_header.size,
@@ -527,13 +675,16 @@ impl BlockIndex {
                 record_total,
             ));
             pos += SIZE_BLOCK_HEADER + block_header.size as usize;
-            record_total += block_header.records;
+            record_total += u64::from(block_header.records);
         }
 
         Ok(index)
     }
 
     /// Reads an index from a path
+    ///
+    /// Index files are zstd compressed, so this requires the `compression` feature.
+    #[cfg(feature = "compression")]
     pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
         let upstream_file =
             if let Some(upstream) = path.as_ref().to_str().unwrap().strip_suffix(".vqi") {
@@ -544,9 +695,7 @@ impl BlockIndex {
                 )
                 .into());
             };
-        let upstream_handle = File::open(upstream_file)?;
-        let mmap = unsafe { memmap2::Mmap::map(&upstream_handle)? };
-        let file_size = mmap.len() as u64;
+        let file_size = File::open(upstream_file)?.metadata()?.len();
 
         let mut file_handle = File::open(path).map(BufReader::new)?;
         let index_header = IndexHeader::from_reader(&mut file_handle)?;
@@ -559,19 +708,267 @@ impl BlockIndex {
             decoder.read_to_end(&mut buffer)?;
             buffer
         };
+        Self::parse(index_header, &buffer)
+    }
+
+    /// Parses the block ranges (and record offsets) following an index header
+    fn parse(index_header: IndexHeader, buffer: &[u8]) -> Result<Self> {
+        // Record offsets follow the ranges of indexes with record offsets
+        let ranges_end = match index_header.record_blocks() {
+            Some(n_blocks) => (n_blocks as usize)
+                .checked_mul(SIZE_BLOCK_RANGE)
+                .filter(|&end| end <= buffer.len())
+                .ok_or_else(|| {
+                    IndexError::RecordCountMismatch(
+                        "blocks".to_string(),
+                        n_blocks,
+                        (buffer.len() / SIZE_BLOCK_RANGE) as u64,
+                    )
+                })?,
+            None => buffer.len(),
+        };
 
+        // Cumulative counts are stored as 32 bits, so they are recomputed in 64 bits
         let mut ranges = Self::new(index_header);
         let mut pos = 0;
-        while pos < buffer.len() {
+        let mut record_total = 0;
+        while pos < ranges_end {
             let bound = pos + SIZE_BLOCK_RANGE;
-            let range = BlockRange::from_bytes(&buffer[pos..bound]);
+            let mut range = BlockRange::from_bytes(&buffer[pos..bound]);
+            range.cumulative_records = record_total;
+            record_total += u64::from(range.block_records);
             ranges.add_range(range);
             pos += SIZE_BLOCK_RANGE;
         }
+        if index_header.record_blocks().is_some() {
+            let offsets = &buffer[ranges_end..];
+            if offsets.len() as u64 != 4 * record_total {
+                return Err(IndexError::RecordCountMismatch(
+                    "record offsets".to_string(),
+                    offsets.len() as u64 / 4,
+                    record_total,
+                )
+                .into());
+            }
+            let mut record_offsets = vec![0; offsets.len() / 4];
+            LittleEndian::read_u32_into(offsets, &mut record_offsets);
+            ranges.record_offsets = Some(record_offsets);
+        }
 
         Ok(ranges)
     }
 
+    /// Reads the index embedded in a VBINSEQ file held in memory
+    ///
+    /// Files written with an embedded index (see `VBinseqHeader::set_embedded_index`)
+    /// store their block ranges between the last block and the footer, so no index file
+    /// is needed. `MmapReader::load_index` loads embedded indexes automatically.
+    ///
+    /// # Parameters
+    ///
+    /// * `bytes` - The full contents of the VBINSEQ file
+    ///
+    /// # Errors
+    ///
+    /// * `IndexError::MissingEmbeddedIndex` if the file has no embedded index
+    /// * `IndexError::InvalidEmbeddedIndex` if the embedded index is corrupt
+    /// * Header and footer validation errors of the file
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use vbinseq::{BlockIndex, VBinseqHeader, VBinseqWriterBuilder};
+    ///
+    /// let mut header = VBinseqHeader::with_capacity(1024, false, false, false);
+    /// header.set_embedded_index(true);
+    /// let mut bytes = Vec::new();
+    /// let mut writer = VBinseqWriterBuilder::default()
+    ///     .header(header)
+    ///     .build(&mut bytes)
+    ///     .unwrap();
+    /// for flag in 0..100 {
+    ///     writer.write_nucleotides(flag, b"ACGTACGT").unwrap();
+    /// }
+    /// writer.close().unwrap();
+    ///
+    /// let index = BlockIndex::from_embedded(&bytes).unwrap();
+    /// assert_eq!(index.n_records(), 100);
+    /// ```
+    pub fn from_embedded(bytes: &[u8]) -> Result<Self> {
+        let header = read_header(bytes)?;
+        if !header.has_embedded_index() {
+            return Err(IndexError::MissingEmbeddedIndex.into());
+        }
+        let start = data_end(bytes, &header)?;
+        let footer = bytes.len() - SIZE_FOOTER;
+        let invalid = || IndexError::InvalidEmbeddedIndex(footer as u64);
+        let mut embedded = &bytes[start..footer];
+        let index_header = IndexHeader::from_reader(&mut embedded).map_err(|_| invalid())?;
+        if index_header.bytes != (footer - start) as u64 {
+            return Err(invalid().into());
+        }
+        let mut index = Self::parse(index_header, embedded).map_err(|_| invalid())?;
+        let blocks_end = index
+            .ranges
+            .last()
+            .map_or(header.data_offset() as u64, |range| {
+                range.start_offset + SIZE_BLOCK_HEADER as u64 + range.len
+            });
+        if blocks_end != start as u64 {
+            return Err(invalid().into());
+        }
+
+        // Saved copies of the index describe the whole file, like index files
+        index.header = IndexHeader::new(bytes.len() as u64);
+        Ok(index)
+    }
+
+    /// Builds the index of blocks written one after another
+    ///
+    /// The index header is left without a file size, as embedded indexes record their own
+    /// size instead (see `write_embedded`).
+    ///
+    /// # Parameters
+    ///
+    /// * `data_offset` - The position of the first block in the file
+    /// * `blocks` - The stored size and number of records of every block
+    pub(crate) fn from_block_sizes(data_offset: u64, blocks: &[(u64, u32)]) -> Self {
+        let mut index = Self::new(IndexHeader::new(0));
+        let (mut pos, mut record_total) = (data_offset, 0);
+        for &(size, records) in blocks {
+            index.add_range(BlockRange::new(pos, size, records, record_total));
+            pos += SIZE_BLOCK_HEADER as u64 + size;
+            record_total += u64::from(records);
+        }
+        index
+    }
+
+    /// Returns the size of the index embedded in a file in bytes
+    ///
+    /// This is the size of the index header, the block ranges, and the record offsets.
+    pub(crate) fn embedded_size(&self) -> u64 {
+        let offsets = self.record_offsets.as_ref().map_or(0, Vec::len);
+        (INDEX_HEADER_SIZE + SIZE_BLOCK_RANGE * self.ranges.len() + 4 * offsets) as u64
+    }
+
+    /// Writes the index as embedded in a file (see `from_embedded`)
+    ///
+    /// Unlike index files, embedded indexes are not compressed, and their header records
+    /// the size of the embedded index instead of the size of the file, so readers can skip
+    /// it between the members of a concatenated stream.
+    pub(crate) fn write_embedded<W: Write>(&self, writer: &mut W) -> Result<()> {
+        let mut header = IndexHeader::new(self.embedded_size());
+        if self.record_offsets.is_some() {
+            header.set_record_blocks(self.ranges.len() as u64);
+        }
+        header.write_bytes(writer)?;
+        self.write_range(writer)?;
+        for &offset in self.record_offsets.iter().flatten() {
+            writer.write_all(&offset.to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Adds the offsets of all records in their blocks to the index
+    ///
+    /// With record offsets, a single record can be read without parsing the records
+    /// preceding it in its block (see `MmapReader::seek_record`). Uncompressed blocks are
+    /// not even read beyond the record. Computing the offsets requires decompressing
+    /// every block of the file once. The offsets are saved with the index, which takes 4
+    /// bytes per record (before compression).
+    ///
+    /// # Parameters
+    ///
+    /// * `bytes` - The full contents of the indexed VBINSEQ file
+    ///
+    /// # Errors
+    ///
+    /// * `IndexError::InvalidBlockOffset` if a block range doesn't point to a block header
+    /// * `IndexError::RecordOffsetOverflow` if a record starts beyond the 32-bit offsets
+    /// * Read errors if a block is corrupt
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use vbinseq::BlockIndex;
+    ///
+    /// let bytes = std::fs::read("example.vbq").unwrap();
+    /// let mut index = BlockIndex::from_bytes(&bytes).unwrap();
+    /// index.add_record_offsets(&bytes).unwrap();
+    /// index.save_to_path("example.vbq.vqi").unwrap();
+    /// ```
+    pub fn add_record_offsets(&mut self, bytes: &[u8]) -> Result<()> {
+        let header = read_header(bytes)?;
+        let dictionaries = dictionary::load(bytes, &header)?;
+        let mut record_offsets = Vec::with_capacity(self.n_records() as usize);
+        let mut dbuf = Vec::new();
+        for range in &self.ranges {
+            let start = range.start_offset as usize;
+            let data = start
+                .checked_add(SIZE_BLOCK_HEADER)
+                .zip(usize::try_from(range.len).ok())
+                .and_then(|(data, len)| Some(data..data.checked_add(len)?))
+                .filter(|data| data.end <= bytes.len())
+                .ok_or(IndexError::InvalidBlockOffset(range.start_offset))?;
+            let block_header =
+                BlockHeader::from_bytes(bytes[start..data.start].try_into().unwrap())?;
+            let records = block_records(
+                &block_header,
+                &bytes[data],
+                &header,
+                &dictionaries,
+                &mut dbuf,
+            )?;
+            let mut offset = 0u64;
+            for record in records {
+                let stored =
+                    u32::try_from(offset).map_err(|_| IndexError::RecordOffsetOverflow(offset))?;
+                record_offsets.push(stored);
+                offset += record.len() as u64;
+            }
+        }
+        self.record_offsets = Some(record_offsets);
+        Ok(())
+    }
+
+    /// Returns whether the index holds the offsets of the records in their blocks
+    pub fn has_record_offsets(&self) -> bool {
+        self.record_offsets.is_some()
+    }
+
+    /// Returns the offsets of the records of a block in the decompressed block
+    ///
+    /// Returns `None` if the index has no record offsets (see `add_record_offsets`) or
+    /// the block doesn't exist.
+    ///
+    /// # Parameters
+    ///
+    /// * `block` - The position of the block in the index
+    pub fn record_offsets(&self, block: usize) -> Option<&[u32]> {
+        let range = self.ranges.get(block)?;
+        let start = range.cumulative_records as usize;
+        self.record_offsets
+            .as_ref()?
+            .get(start..start + range.block_records as usize)
+    }
+
+    /// Returns the position of the block holding a record
+    ///
+    /// Returns `None` if the record index is past the last record of the file.
+    ///
+    /// # Parameters
+    ///
+    /// * `record` - The (0-based) index of the record in the file
+    pub fn block_of_record(&self, record: u64) -> Option<usize> {
+        if record >= self.n_records() {
+            return None;
+        }
+        let next = self
+            .ranges
+            .partition_point(|range| range.cumulative_records <= record);
+        Some(next - 1)
+    }
+
     /// Get a reference to the internal ranges
     /// Returns a reference to the collection of block ranges
     ///
@@ -600,6 +997,86 @@ impl BlockIndex {
         &self.ranges
     }
 
+    /// Returns the total number of records in the indexed file
+    pub fn n_records(&self) -> u64 {
+        self.ranges.last().map_or(0, |range| {
+            range.cumulative_records + u64::from(range.block_records)
+        })
+    }
+
+    /// Splits the indexed file into ranges of consecutive blocks
+    ///
+    /// The blocks are divided into at most `n_parts` ranges holding about the same number of
+    /// records, e.g. to assign the blocks of a file to jobs processing them with
+    /// `MmapReader::process_parallel_range`.
+    ///
+    /// # Parameters
+    ///
+    /// * `n_parts` - Maximum number of ranges
+    ///
+    /// # Returns
+    ///
+    /// The positions of the blocks of every range in file order (empty for a file without
+    /// blocks)
+    pub fn split_blocks(&self, n_parts: usize) -> Vec<Range<usize>> {
+        if self.ranges.is_empty() || n_parts == 0 {
+            return Vec::new();
+        }
+        let n_records = self.n_records();
+        let mut starts = vec![0];
+        for part in 1..n_parts as u64 {
+            let target = n_records * part / n_parts as u64;
+            let start = self
+                .ranges
+                .partition_point(|range| range.cumulative_records < target);
+            if start > *starts.last().unwrap() && start < self.ranges.len() {
+                starts.push(start);
+            }
+        }
+        starts
+            .iter()
+            .zip(starts.iter().skip(1).chain([&self.ranges.len()]))
+            .map(|(&start, &end)| start..end)
+            .collect()
+    }
+
+    /// Splits the indexed file into byte ranges of consecutive blocks
+    ///
+    /// The blocks are divided into at most `n_parts` ranges holding about the same number of
+    /// records (see `split_blocks`), e.g. to assign the blocks of a file to distributed
+    /// workers. Each range is given as its offset in the file and its length (in bytes,
+    /// including block headers) and can be opened with `MmapReader::with_range`.
+    ///
+    /// # Parameters
+    ///
+    /// * `n_parts` - Maximum number of ranges
+    ///
+    /// # Returns
+    ///
+    /// The `(offset, length)` of every range in file order (empty for a file without blocks)
+    pub fn split_points(&self, n_parts: usize) -> Vec<(u64, u64)> {
+        let end = |range: &BlockRange| range.start_offset + SIZE_BLOCK_HEADER as u64 + range.len;
+        self.split_blocks(n_parts)
+            .into_iter()
+            .map(|blocks| {
+                let offset = self.ranges[blocks.start].start_offset;
+                (offset, end(&self.ranges[blocks.end - 1]) - offset)
+            })
+            .collect()
+    }
+
+    /// Returns the largest number of records in a single block
+    ///
+    /// This is the capacity needed to read any block of the file without growing the
+    /// buffers of a `RecordBlock` (see `RecordBlock::reserve`).
+    pub fn max_block_records(&self) -> usize {
+        self.ranges
+            .iter()
+            .map(|range| range.block_records as usize)
+            .max()
+            .unwrap_or(0)
+    }
+
     pub fn pprint(&self) {
         self.ranges.iter().for_each(|range| {
             println!(
@@ -609,3 +1086,88 @@ impl BlockIndex {
         })
     }
 }
+
+#[cfg(all(test, feature = "compression"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_large_cumulative_records() -> Result<()> {
+        let path = std::env::temp_dir().join(format!("vbq_index_{}.vbq", std::process::id()));
+        std::fs::write(&path, [0; 1024])?;
+        let index_path = path.with_extension("vbq.vqi");
+
+        // Blocks with more than u32::MAX records in total
+        let mut index = BlockIndex::new(IndexHeader::new(1024));
+        for i in 0..3 {
+            let cumulative_records = i * u64::from(u32::MAX);
+            index.add_range(BlockRange::new(i, 1, u32::MAX, cumulative_records));
+        }
+        index.save_to_path(&index_path)?;
+
+        let loaded = BlockIndex::from_path(&index_path)?;
+        let cumulative: Vec<u64> = loaded
+            .ranges()
+            .iter()
+            .map(|r| r.cumulative_records)
+            .collect();
+        assert_eq!(
+            cumulative,
+            [0, u64::from(u32::MAX), 2 * u64::from(u32::MAX)]
+        );
+        assert_eq!(loaded.ranges(), index.ranges());
+
+        std::fs::remove_file(&index_path)?;
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+    #[test]
+    fn test_record_offsets() -> Result<()> {
+        use crate::testing;
+        use rand::rngs::SmallRng;
+        use rand::SeedableRng;
+
+        let mut rng = SmallRng::seed_from_u64(5);
+        let path = std::env::temp_dir().join(format!("vbq_recof_{}.vbq", std::process::id()));
+        let index_path = path.with_extension("vbq.vqi");
+        let header = VBinseqHeader::with_capacity(testing::BLOCK_SIZES[0], true, true, true);
+        let records = testing::random_records(&mut rng, &header, 300);
+        let bytes = testing::write_records(header, &records)?;
+        std::fs::write(&path, &bytes)?;
+
+        let mut index = BlockIndex::from_bytes(&bytes)?;
+        assert!(index.n_blocks() > 1);
+        index.add_record_offsets(&bytes)?;
+        assert_eq!(index.block_of_record(0), Some(0));
+        assert_eq!(index.block_of_record(records.len() as u64), None);
+        for (position, range) in index.ranges().iter().enumerate() {
+            let offsets = index.record_offsets(position).unwrap();
+            assert_eq!(offsets.len(), range.block_records as usize);
+            assert_eq!(offsets.first(), Some(&0));
+            assert!(offsets.windows(2).all(|pair| pair[0] < pair[1]));
+            let last = range.cumulative_records + u64::from(range.block_records) - 1;
+            assert_eq!(index.block_of_record(last), Some(position));
+        }
+
+        // Record offsets survive saving and loading the index
+        index.save_to_path(&index_path)?;
+        let loaded = BlockIndex::from_path(&index_path)?;
+        assert!(loaded.has_record_offsets());
+        assert_eq!(loaded.ranges(), index.ranges());
+        for position in 0..index.n_blocks() {
+            assert_eq!(
+                loaded.record_offsets(position),
+                index.record_offsets(position)
+            );
+        }
+
+        // Truncated index files are rejected
+        let saved = std::fs::read(&index_path)?;
+        std::fs::write(&index_path, &saved[..saved.len() - 4])?;
+        assert!(BlockIndex::from_path(&index_path).is_err());
+
+        std::fs::remove_file(&index_path)?;
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,153 @@
+//! # IUPAC Encoding
+//!
+//! Files with the IUPAC extension enabled (see `VBinseqHeader::set_iupac`) store their
+//! sequences with 4 bits per nucleotide instead of 2, which keeps `N` and the ambiguity
+//! codes of the IUPAC alphabet (`RYSWKMBDHV`) that the 2-bit encoding cannot represent.
+//! Sequences round-trip exactly, except that lowercase nucleotides are stored in uppercase
+//! like in the 2-bit encoding. Other bytes (e.g. `-` or `.`) are invalid and handled by
+//! the `Policy` of the writer.
+//!
+//! Nucleotides use the 4-bit codes of SAM/BAM (`=ACMGRSVTWYHKDBN`, where each bit stands
+//! for one of `ACGT`), packed 16 per `u64` word with the first nucleotide in the lowest
+//! bits. Readers decode both encodings transparently (see `RefRecord::decode_s`), so these
+//! functions are only needed to handle encoded words directly (see `RefRecord::sbuf`).
+//!
+//! # Example
+//!
+//! ```rust
+//! use vbinseq::iupac::{decode, encode};
+//!
+//! let mut words = Vec::new();
+//! encode(b"ACGTNRYacgt", &mut words).unwrap();
+//! assert_eq!(words.len(), 1);
+//!
+//! let mut sequence = Vec::new();
+//! decode(&words, 11, &mut sequence).unwrap();
+//! assert_eq!(sequence, b"ACGTNRYACGT");
+//! ```
+
+use crate::error::{ReadError, Result, WriteError};
+
+/// Nucleotides stored in every 64-bit word
+pub const NUCLEOTIDES_PER_WORD: usize = 16;
+
+/// Nucleotide of every 4-bit code
+///
+/// Code 0 (`=`) is never written, as it is not a nucleotide.
+pub const ALPHABET: &[u8; 16] = b"=ACMGRSVTWYHKDBN";
+
+/// Marks bytes without a code in `CODES`
+const INVALID: u8 = u8::MAX;
+
+/// 4-bit code of every byte (upper- and lowercase nucleotides share their code)
+const CODES: [u8; 256] = {
+    let mut codes = [INVALID; 256];
+    let mut code = 1;
+    while code < ALPHABET.len() {
+        let nucleotide = ALPHABET[code];
+        codes[nucleotide as usize] = code as u8;
+        codes[nucleotide.to_ascii_lowercase() as usize] = code as u8;
+        code += 1;
+    }
+    codes
+};
+
+/// Returns whether a byte is an (upper- or lowercase) IUPAC nucleotide
+pub fn is_iupac(nucleotide: &u8) -> bool {
+    CODES[*nucleotide as usize] != INVALID
+}
+
+/// Returns the number of 64-bit words storing a sequence of the given length
+pub fn encoded_len(len: u64) -> usize {
+    len.div_ceil(NUCLEOTIDES_PER_WORD as u64) as usize
+}
+
+/// Encodes a sequence with 4 bits per nucleotide
+///
+/// The words are appended to the buffer. Empty sequences encode to no words.
+///
+/// # Parameters
+///
+/// * `sequence` - The nucleotides to encode
+/// * `ebuf` - The buffer receiving the encoded words
+///
+/// # Errors
+///
+/// * `WriteError::InvalidNucleotideSequence` - If the sequence has a byte that is not an
+///   IUPAC nucleotide (the buffer then holds part of the words)
+pub fn encode(sequence: &[u8], ebuf: &mut Vec<u64>) -> Result<()> {
+    ebuf.reserve(encoded_len(sequence.len() as u64));
+    for chunk in sequence.chunks(NUCLEOTIDES_PER_WORD) {
+        let mut word = 0;
+        for (i, &nucleotide) in chunk.iter().enumerate() {
+            let code = CODES[nucleotide as usize];
+            if code == INVALID {
+                let sequence = String::from_utf8_lossy(sequence).into_owned();
+                return Err(WriteError::InvalidNucleotideSequence(sequence).into());
+            }
+            word |= u64::from(code) << (4 * i);
+        }
+        ebuf.push(word);
+    }
+    Ok(())
+}
+
+/// Decodes a sequence stored with 4 bits per nucleotide
+///
+/// The nucleotides are appended to the buffer in uppercase.
+///
+/// # Parameters
+///
+/// * `ebuf` - The encoded words
+/// * `len` - The number of nucleotides to decode
+/// * `dbuf` - The buffer receiving the nucleotides
+///
+/// # Errors
+///
+/// * `ReadError::InvalidIupacSequence` - If `ebuf` has fewer words than `len` requires
+pub fn decode(ebuf: &[u64], len: usize, dbuf: &mut Vec<u8>) -> Result<()> {
+    if ebuf.len() < encoded_len(len as u64) {
+        return Err(ReadError::InvalidIupacSequence(len, ebuf.len()).into());
+    }
+    dbuf.reserve(len);
+    dbuf.extend((0..len).map(|i| {
+        let word = ebuf[i / NUCLEOTIDES_PER_WORD];
+        ALPHABET[(word >> (4 * (i % NUCLEOTIDES_PER_WORD))) as usize & 0xF]
+    }));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() -> Result<()> {
+        let sequence: Vec<u8> = b"ACGTNRYSWKMBDHV".repeat(7);
+        let mut words = Vec::new();
+        encode(&sequence, &mut words)?;
+        assert_eq!(words.len(), encoded_len(sequence.len() as u64));
+
+        let mut decoded = Vec::new();
+        decode(&words, sequence.len(), &mut decoded)?;
+        assert_eq!(decoded, sequence);
+
+        // Lowercase nucleotides are stored in uppercase
+        words.clear();
+        encode(b"acgtn", &mut words)?;
+        decoded.clear();
+        decode(&words, 5, &mut decoded)?;
+        assert_eq!(decoded, b"ACGTN");
+
+        // Other bytes are rejected, as are buffers that are too short
+        assert!(encode(b"ACG-T", &mut Vec::new()).is_err());
+        assert!(!is_iupac(&b'='));
+        assert!(matches!(
+            decode(&words, 17, &mut decoded),
+            Err(crate::Error::ReadError(ReadError::InvalidIupacSequence(
+                17, 1
+            )))
+        ));
+        Ok(())
+    }
+}
@@ -0,0 +1,299 @@
+//! # JSON Lines Export
+//!
+//! This module serializes decoded records as [JSON Lines](https://jsonlines.org), one JSON
+//! object per record, which is handy for piping small extracts into `jq` and ad-hoc
+//! scripts. It requires the `serde` feature.
+//!
+//! `OwnedRecord` implements serde's `Serialize` with the following fields:
+//!
+//! | Field      | Type   | Description                                                  |
+//! |------------|--------|--------------------------------------------------------------|
+//! | `index`    | number | Global index of the record in the file                       |
+//! | `flag`     | number | Flag of the record                                           |
+//! | `seq`      | string | Primary sequence                                             |
+//! | `qual`     | string | Quality scores of the primary sequence (if present)          |
+//! | `xseq`     | string | Extended sequence (only for paired records)                  |
+//! | `xqual`    | string | Quality scores of the extended sequence (if present)         |
+//! | `segments` | array  | Further segments (only for records of more than two segments) |
+//!
+//! Every further segment is an object with a `seq` field and, if present, a `qual` field.
+//!
+//! Homopolymer-compressed sequences are expanded, and their run lengths are not written
+//! as quality scores (see `OwnedRecord::expand_homopolymers`).
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use vbinseq::jsonl;
+//!
+//! // Write every record of a file to stdout as JSON Lines
+//! let n_records = jsonl::export("example.vbq", std::io::stdout().lock()).unwrap();
+//! eprintln!("Exported {n_records} records");
+//! ```
+
+use std::io::Write;
+#[cfg(feature = "mmap")]
+use std::path::Path;
+
+use serde::ser::{Error as _, Serialize, SerializeMap, Serializer};
+
+use crate::reader::RecordBlock;
+use crate::{OwnedRecord, Result};
+
+/// Serializes ASCII sequences and quality scores as JSON strings
+fn as_str(bytes: &[u8]) -> std::borrow::Cow<'_, str> {
+    String::from_utf8_lossy(bytes)
+}
+
+/// A segment after the extended sequence of a record
+struct Segment<'a> {
+    sequence: &'a [u8],
+    quality: &'a [u8],
+}
+impl Serialize for Segment<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("seq", &as_str(self.sequence))?;
+        if !self.quality.is_empty() {
+            map.serialize_entry("qual", &as_str(self.quality))?;
+        }
+        map.end()
+    }
+}
+
+impl Serialize for OwnedRecord {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let record = self.expanded().map_err(S::Error::custom)?;
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("index", &record.index())?;
+        map.serialize_entry("flag", &record.flag())?;
+        map.serialize_entry("seq", &as_str(record.seq()))?;
+        if !record.squal().is_empty() {
+            map.serialize_entry("qual", &as_str(record.squal()))?;
+        }
+        if record.is_paired() {
+            map.serialize_entry("xseq", &as_str(record.xseq()))?;
+            if !record.xqual().is_empty() {
+                map.serialize_entry("xqual", &as_str(record.xqual()))?;
+            }
+        }
+        if record.n_segments() > 2 {
+            let segments: Vec<Segment> = (2..record.n_segments())
+                .map(|segment| Segment {
+                    sequence: record.segment(segment),
+                    quality: record.segment_qual(segment),
+                })
+                .collect();
+            map.serialize_entry("segments", &segments)?;
+        }
+        map.end()
+    }
+}
+
+/// Writes decoded records as JSON Lines
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use vbinseq::jsonl::JsonlWriter;
+/// use vbinseq::MmapReader;
+///
+/// let mut reader = MmapReader::new("example.vbq").unwrap();
+/// let mut block = reader.new_block();
+/// let mut writer = JsonlWriter::new(std::io::stdout().lock());
+///
+/// // Only export the first block
+/// if reader.read_block_into(&mut block).unwrap() {
+///     writer.write_block(&block).unwrap();
+/// }
+/// writer.flush().unwrap();
+/// ```
+pub struct JsonlWriter<W: Write> {
+    /// Destination of the JSON lines
+    inner: W,
+    /// Reusable buffer for decoding records
+    record: OwnedRecord,
+}
+impl<W: Write> JsonlWriter<W> {
+    /// Creates a new writer over an output
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            record: OwnedRecord::default(),
+        }
+    }
+
+    /// Writes a single record as one line
+    ///
+    /// # Errors
+    ///
+    /// * `Error::JsonError` - If the record could not be serialized or written
+    pub fn write_record(&mut self, record: &OwnedRecord) -> Result<()> {
+        serde_json::to_writer(&mut self.inner, record)?;
+        self.inner.write_all(b"\n")?;
+        Ok(())
+    }
+
+    /// Decodes and writes all records of a block
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(usize)` - The number of records written
+    ///
+    /// # Errors
+    ///
+    /// * Decoding errors of the records
+    /// * `Error::JsonError` - If a record could not be serialized or written
+    pub fn write_block(&mut self, block: &RecordBlock) -> Result<usize> {
+        let mut record = std::mem::take(&mut self.record);
+        let mut n_records = 0;
+        for ref_record in block.iter() {
+            record.fill(&ref_record)?;
+            record.expand_homopolymers()?;
+            self.write_record(&record)?;
+            n_records += 1;
+        }
+        self.record = record;
+        Ok(n_records)
+    }
+
+    /// Flushes the underlying output
+    pub fn flush(&mut self) -> Result<()> {
+        self.inner.flush()?;
+        Ok(())
+    }
+
+    /// Returns the underlying output
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+/// Writes every record of a VBINSEQ file as JSON Lines
+///
+/// # Parameters
+///
+/// * `path` - Path to the VBINSEQ file
+/// * `writer` - Destination of the JSON lines
+///
+/// # Returns
+///
+/// * `Ok(u64)` - The number of records written
+/// * `Err(_)` - If the file could not be read or a record could not be written
+#[cfg(feature = "mmap")]
+pub fn export<P: AsRef<Path>, W: Write>(path: P, writer: W) -> Result<u64> {
+    let mut reader = crate::MmapReader::new(path)?;
+    let mut block = reader.new_block();
+    let mut writer = JsonlWriter::new(writer);
+    let mut n_records = 0;
+    while reader.read_block_into(&mut block)? {
+        n_records += writer.write_block(&block)? as u64;
+    }
+    writer.flush()?;
+    Ok(n_records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MemoryReader, VBinseqHeader, VBinseqWriterBuilder};
+
+    #[test]
+    fn test_record_json() -> Result<()> {
+        let single = OwnedRecord::new(3, b"ACGT".to_vec(), Vec::new());
+        assert_eq!(
+            serde_json::to_string(&single)?,
+            r#"{"index":0,"flag":3,"seq":"ACGT"}"#
+        );
+
+        let paired = OwnedRecord::new_paired(
+            1,
+            b"ACGT".to_vec(),
+            b"GG".to_vec(),
+            b"IIII".to_vec(),
+            b"FF".to_vec(),
+        );
+        assert_eq!(
+            serde_json::to_string(&paired)?,
+            r#"{"index":0,"flag":1,"seq":"ACGT","qual":"IIII","xseq":"GG","xqual":"FF"}"#
+        );
+
+        let sequences = vec![
+            b"AC".to_vec(),
+            b"G".to_vec(),
+            b"TT".to_vec(),
+            b"CAT".to_vec(),
+        ];
+        let qualities = vec![
+            b"II".to_vec(),
+            b"F".to_vec(),
+            b"++".to_vec(),
+            b"!!!".to_vec(),
+        ];
+        let segments = OwnedRecord::new_segments(2, sequences, qualities);
+        assert_eq!(
+            serde_json::to_string(&segments)?,
+            concat!(
+                r#"{"index":0,"flag":2,"seq":"AC","qual":"II","xseq":"G","xqual":"F","#,
+                r#""segments":[{"seq":"TT","qual":"++"},{"seq":"CAT","qual":"!!!"}]}"#
+            )
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_block() -> Result<()> {
+        let mut bytes = Vec::new();
+        let header = VBinseqHeader::new(true, false, false);
+        let mut writer = VBinseqWriterBuilder::default()
+            .header(header)
+            .build(&mut bytes)?;
+        writer.write_nucleotides_quality(0, b"ACGT", b"IIII")?;
+        writer.write_nucleotides_quality(7, b"TTGCA", b"!!!!!")?;
+        writer.finish()?;
+        drop(writer);
+
+        let mut reader = MemoryReader::new(bytes)?;
+        let mut block = reader.new_block();
+        let mut writer = JsonlWriter::new(Vec::new());
+        while reader.read_block_into(&mut block)? {
+            writer.write_block(&block)?;
+        }
+        let lines = String::from_utf8(writer.into_inner()).unwrap();
+        let lines: Vec<&str> = lines.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(
+            lines[1],
+            r#"{"index":1,"flag":7,"seq":"TTGCA","qual":"!!!!!"}"#
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_homopolymer_records() -> Result<()> {
+        let mut bytes = Vec::new();
+        let mut header = VBinseqHeader::new(false, false, true);
+        header.set_homopolymer(true);
+        let mut writer = VBinseqWriterBuilder::default()
+            .header(header)
+            .build(&mut bytes)?;
+        writer.write_nucleotides_paired(0, b"AAACGGGG", b"TTTA")?;
+        writer.finish()?;
+        drop(writer);
+
+        // Run lengths are not written as quality scores
+        let expected = r#"{"index":0,"flag":0,"seq":"AAACGGGG","xseq":"TTTA"}"#;
+        let mut reader = MemoryReader::new(bytes)?;
+        let mut block = reader.new_block();
+        assert!(reader.read_block_into(&mut block)?);
+        let record = OwnedRecord::try_from(&block.iter().next().unwrap())?;
+        assert!(record.is_homopolymer());
+        assert_eq!(serde_json::to_string(&record)?, expected);
+
+        let mut writer = JsonlWriter::new(Vec::new());
+        writer.write_block(&block)?;
+        let lines = String::from_utf8(writer.into_inner()).unwrap();
+        assert_eq!(lines.trim_end(), expected);
+        Ok(())
+    }
+}
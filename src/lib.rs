@@ -13,7 +13,8 @@
 //! * **Variable-length records** - Unlike fixed-size records, variable-length records can store sequences of any size
 //! * **Quality scores** - Optional quality score tracking for each nucleotide
 //! * **Paired sequences** - Support for paired-end sequencing data
-//! * **Parallel compression** - Support for ZSTD compression with parallel processing
+//! * **Parallel compression** - Support for ZSTD compression with parallel processing, or
+//!   LZ4 and XZ compression (see the `codec` module)
 //! * **Random access** - Efficient random access to record blocks
 //!
 //! ## Usage
@@ -84,23 +85,86 @@
 //!    - Block header (32 bytes)
 //!    - Block data (variable size, containing records)
 //!    - Block padding (to maintain fixed virtual block size)
+//! 3. An optional footer (64 bytes) with record counts and a content digest
 //!
 //! Each record contains a preamble with metadata and data containing encoded sequences and quality scores.
 //!
 //! See the README.md for detailed format specifications.
 
+#[cfg(feature = "bgzf")]
+pub mod bgzf;
+pub mod checksum;
+pub mod codec;
+pub mod columnar;
+pub mod compat;
+#[cfg(any(test, all(feature = "compression", feature = "policy-rand")))]
+pub mod conformance;
+pub mod convert;
+#[cfg(feature = "cram")]
+pub mod cram;
+#[cfg(feature = "polars")]
+pub mod dataframe;
+pub mod dataset;
+pub mod dictionary;
+pub mod digest;
+#[cfg(all(feature = "direct_io", target_os = "linux"))]
+pub mod direct;
 pub mod error;
+#[cfg(feature = "mmap")]
+pub mod filter;
+pub mod footer;
+#[cfg(any(feature = "arbitrary", feature = "proptest"))]
+pub mod fuzz;
 pub mod header;
+pub mod homopolymer;
 pub mod index;
+pub mod iupac;
+#[cfg(feature = "serde")]
+pub mod jsonl;
+pub mod merge;
+#[cfg(feature = "mmap")]
+pub mod mmap_writer;
 pub mod parallel;
 pub mod policy;
+pub mod quality;
+pub mod read_group;
 pub mod reader;
+pub mod recovery;
+#[cfg(feature = "mmap")]
+pub mod repack;
+pub mod scan;
+pub mod sections;
+#[cfg(feature = "policy-rand")]
+pub mod simulate;
+#[cfg(feature = "mmap")]
+pub mod split;
+#[cfg(feature = "async")]
+pub mod stream;
+pub mod summary;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+#[cfg(feature = "mmap")]
+pub mod tombstone;
+#[cfg(all(feature = "io_uring", target_os = "linux"))]
+pub mod uring;
+pub mod validate;
 pub mod writer;
 
 pub use error::{Error, Result};
-pub use header::{BlockHeader, VBinseqHeader};
-pub use index::{BlockIndex, BlockRange};
+pub use footer::Footer;
+pub use header::{BlockHeader, Codec, VBinseqHeader};
+pub use index::{BlockIndex, BlockRange, IndexPolicy};
+#[cfg(feature = "mmap")]
+pub use mmap_writer::MmapWriter;
 pub use parallel::ParallelProcessor;
 pub use policy::Policy;
-pub use reader::{MmapReader, RefRecord};
-pub use writer::{VBinseqWriter, VBinseqWriterBuilder};
+pub use quality::{QualityBins, QualityTransform};
+#[cfg(feature = "mmap")]
+pub use reader::{FollowOptions, MapOptions, MmapReader, Records};
+pub use reader::{MemoryReader, OwnedRecord, RawBlock, RefRecord, StreamReader};
+pub use recovery::{ParseMode, SkipCounts};
+pub use summary::{describe, FileSummary};
+pub use writer::{
+    BufferPool, ParallelVBinseqWriter, RecordInput, SkipReason, SkippedRecord, SkippedRecords,
+    SyncWriter, VBinseqWriter, VBinseqWriterBuilder,
+};
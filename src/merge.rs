@@ -0,0 +1,326 @@
+//! # Overlap Merging of Paired Reads
+//!
+//! The mates of a short fragment (e.g. an amplicon or a short insert) overlap: the end of
+//! the primary read covers the same nucleotides as the reverse complement of the end of
+//! the extended read. Such pairs are commonly merged into a single consensus read before
+//! analysis.
+//!
+//! A `Merger` detects the overlap of two mates and builds the consensus. Writers merge
+//! overlapping pairs written to paired files when configured with
+//! `VBinseqWriterBuilder::merge_overlaps`: a merged pair is written as a record with the
+//! consensus as its primary sequence, no extended sequence, and the flag bits of the
+//! `MergeOptions` set. Pairs without a sufficient overlap are written unchanged.
+//!
+//! # Consensus
+//!
+//! The overlap is searched from the longest to the shortest candidate, and the first
+//! candidate with at most `max_mismatch_rate` mismatching nucleotides is accepted. Within
+//! the overlap, agreeing nucleotides keep the higher quality score, while for disagreeing
+//! nucleotides the one with the higher quality score is kept (the primary nucleotide if
+//! the scores are equal or there are no quality scores). Nucleotides other than `ACGT`
+//! always count as mismatches and are replaced by the nucleotide of the other mate.
+//!
+//! # Example
+//!
+//! ```rust
+//! use vbinseq::merge::{MergeOptions, Merger};
+//!
+//! let mut merger = Merger::new(MergeOptions {
+//!     min_overlap: 4,
+//!     ..MergeOptions::default()
+//! });
+//!
+//! // The fragment ACGTTGCAAGGC sequenced from both ends
+//! assert!(merger.merge(b"ACGTTGCA", b"GCCTTGCA", &[], &[]));
+//! assert_eq!(merger.sequence(), b"ACGTTGCAAGGC");
+//! ```
+
+/// Options for merging overlapping mates
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MergeOptions {
+    /// Minimum number of overlapping nucleotides
+    pub min_overlap: usize,
+
+    /// Maximum fraction of mismatching nucleotides in the overlap
+    pub max_mismatch_rate: f64,
+
+    /// Bits set in the flag of merged records
+    pub flag: u64,
+}
+impl Default for MergeOptions {
+    fn default() -> Self {
+        Self {
+            min_overlap: 12,
+            max_mismatch_rate: 0.1,
+            flag: 1 << 63,
+        }
+    }
+}
+
+/// Merges overlapping mates into consensus reads
+///
+/// The merger holds reusable buffers, so a single merger should be used for all pairs.
+#[derive(Debug, Clone)]
+pub struct Merger {
+    /// Options of the merge
+    options: MergeOptions,
+
+    /// Reverse complement of the extended sequence of the last pair
+    rc_seq: Vec<u8>,
+
+    /// Reversed quality scores of the extended sequence of the last pair
+    rc_qual: Vec<u8>,
+
+    /// Consensus sequence of the last merged pair
+    sequence: Vec<u8>,
+
+    /// Consensus quality scores of the last merged pair (empty without quality scores)
+    quality: Vec<u8>,
+}
+impl Merger {
+    /// Creates a merger with the given options
+    pub fn new(options: MergeOptions) -> Self {
+        Self {
+            options,
+            rc_seq: Vec::new(),
+            rc_qual: Vec::new(),
+            sequence: Vec::new(),
+            quality: Vec::new(),
+        }
+    }
+
+    /// Returns the options of the merger
+    pub fn options(&self) -> MergeOptions {
+        self.options
+    }
+
+    /// Merges a pair of mates if they overlap
+    ///
+    /// # Parameters
+    ///
+    /// * `primary` - The primary sequence (read 1)
+    /// * `extended` - The extended sequence (read 2, as sequenced)
+    /// * `squal` - The quality scores of the primary sequence (empty if not present)
+    /// * `xqual` - The quality scores of the extended sequence (empty if not present)
+    ///
+    /// # Returns
+    ///
+    /// `true` if the mates overlap, in which case the consensus is available from
+    /// `sequence` and `quality`
+    pub fn merge(&mut self, primary: &[u8], extended: &[u8], squal: &[u8], xqual: &[u8]) -> bool {
+        let has_quality = !squal.is_empty() && !xqual.is_empty();
+        self.rc_seq.clear();
+        self.rc_seq
+            .extend(extended.iter().rev().map(|&base| complement(base)));
+        self.rc_qual.clear();
+        if has_quality {
+            self.rc_qual.extend(xqual.iter().rev());
+        }
+
+        let Some(offset) = self.find_overlap(primary) else {
+            return false;
+        };
+        let overlap = (primary.len() - offset).min(self.rc_seq.len());
+
+        // Primary nucleotides before the overlap
+        self.sequence.clear();
+        self.quality.clear();
+        self.sequence.extend_from_slice(&primary[..offset]);
+        if has_quality {
+            self.quality.extend_from_slice(&squal[..offset]);
+        }
+
+        // Consensus of the overlap
+        for i in 0..overlap {
+            let (s, x) = (primary[offset + i], self.rc_seq[i]);
+            let (sq, xq) = if has_quality {
+                (squal[offset + i], self.rc_qual[i])
+            } else {
+                (0, 0)
+            };
+            let keep_primary = if !is_acgt(x) {
+                true
+            } else if !is_acgt(s) {
+                false
+            } else {
+                s.eq_ignore_ascii_case(&x) || sq >= xq
+            };
+            self.sequence.push(if keep_primary { s } else { x });
+            if has_quality {
+                self.quality.push(sq.max(xq));
+            }
+        }
+
+        // Nucleotides of the mate extending past the overlap
+        if self.rc_seq.len() > overlap {
+            self.sequence.extend_from_slice(&self.rc_seq[overlap..]);
+            if has_quality {
+                self.quality.extend_from_slice(&self.rc_qual[overlap..]);
+            }
+        } else {
+            self.sequence
+                .extend_from_slice(&primary[offset + overlap..]);
+            if has_quality {
+                self.quality.extend_from_slice(&squal[offset + overlap..]);
+            }
+        }
+        true
+    }
+
+    /// Returns the consensus sequence of the last merged pair
+    pub fn sequence(&self) -> &[u8] {
+        &self.sequence
+    }
+
+    /// Returns the consensus quality scores of the last merged pair
+    ///
+    /// This is empty if the mates were merged without quality scores.
+    pub fn quality(&self) -> &[u8] {
+        &self.quality
+    }
+
+    /// Finds the offset of the reverse-complemented mate within the primary sequence
+    ///
+    /// Offsets are tested from the longest to the shortest overlap.
+    fn find_overlap(&self, primary: &[u8]) -> Option<usize> {
+        let min_overlap = self.options.min_overlap.max(1);
+        if primary.len() < min_overlap || self.rc_seq.len() < min_overlap {
+            return None;
+        }
+        (0..=primary.len() - min_overlap).find(|&offset| {
+            let overlap = (primary.len() - offset).min(self.rc_seq.len());
+            if overlap < min_overlap {
+                return false;
+            }
+            let max_mismatches = (overlap as f64 * self.options.max_mismatch_rate) as usize;
+            primary[offset..offset + overlap]
+                .iter()
+                .zip(&self.rc_seq)
+                .filter(|(s, x)| !is_acgt(**s) || !s.eq_ignore_ascii_case(x))
+                .take(max_mismatches + 1)
+                .count()
+                <= max_mismatches
+        })
+    }
+}
+
+/// Returns whether a byte is an unambiguous nucleotide
+fn is_acgt(base: u8) -> bool {
+    matches!(base, b'A' | b'C' | b'G' | b'T' | b'a' | b'c' | b'g' | b't')
+}
+
+/// Returns the complement of a nucleotide (keeping its case)
+pub(crate) fn complement(base: u8) -> u8 {
+    match base {
+        b'A' => b'T',
+        b'C' => b'G',
+        b'G' => b'C',
+        b'T' => b'A',
+        b'a' => b't',
+        b'c' => b'g',
+        b'g' => b'c',
+        b't' => b'a',
+        other => other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MemoryReader, Result, VBinseqHeader, VBinseqWriterBuilder};
+
+    /// Returns the reverse complement of a sequence
+    fn revcomp(sequence: &[u8]) -> Vec<u8> {
+        sequence
+            .iter()
+            .rev()
+            .map(|&base| complement(base))
+            .collect()
+    }
+
+    #[test]
+    fn test_merge_pairs() {
+        let fragment = b"ACGTACCGTTAGGCATCGATCCGA";
+        let mut merger = Merger::new(MergeOptions {
+            min_overlap: 6,
+            ..MergeOptions::default()
+        });
+
+        // Mates overlapping by 8 nucleotides
+        let (primary, extended) = (&fragment[..16], revcomp(&fragment[8..]));
+        assert!(merger.merge(primary, &extended, &[], &[]));
+        assert_eq!(merger.sequence(), fragment);
+        assert!(merger.quality().is_empty());
+
+        // Mismatches are resolved by quality, and Ns by the other mate
+        let mut merger = Merger::new(MergeOptions {
+            min_overlap: 6,
+            max_mismatch_rate: 0.25,
+            ..MergeOptions::default()
+        });
+        let mut primary = primary.to_vec();
+        primary[15] = b'N';
+        let mut extended = extended;
+        extended[9] = complement(b'T');
+        let (squal, mut xqual) = (vec![b'I'; 16], vec![b'5'; 16]);
+        xqual[9] = b'#';
+        assert!(merger.merge(&primary, &extended, &squal, &xqual));
+        assert_eq!(merger.sequence(), fragment);
+        assert_eq!(merger.quality().len(), fragment.len());
+        assert_eq!(&merger.quality()[8..16], b"IIIIIIII");
+
+        // A mate contained in the primary read keeps the primary tail
+        assert!(merger.merge(fragment, &revcomp(&fragment[4..12]), &[], &[]));
+        assert_eq!(merger.sequence(), fragment);
+
+        // Unrelated mates are not merged
+        assert!(!merger.merge(b"AAAAAAAAAAAA", b"AAAAAAAAAAAA", &[], &[]));
+        assert!(!merger.merge(b"ACGT", b"ACGT", &[], &[]));
+    }
+
+    #[test]
+    fn test_merging_writer() -> Result<()> {
+        let fragment = b"ACGTACCGTTAGGCATCGATCCGA";
+        let options = MergeOptions {
+            min_overlap: 6,
+            flag: 1 << 40,
+            ..MergeOptions::default()
+        };
+        let mut bytes = Vec::new();
+        let mut writer = VBinseqWriterBuilder::default()
+            .header(VBinseqHeader::with_capacity(1024, true, false, true))
+            .merge_overlaps(options)
+            .build(&mut bytes)?;
+        let quality = [b'I'; 16];
+        writer.write_nucleotides_quality_paired(
+            1,
+            &fragment[..16],
+            &revcomp(&fragment[8..]),
+            &quality,
+            &quality,
+        )?;
+        writer.write_nucleotides_quality_paired(
+            2,
+            b"AAAAAAAA",
+            b"AAAAAAAA",
+            &quality[..8],
+            &quality[..8],
+        )?;
+        assert_eq!(writer.merged_pairs(), 1);
+        writer.finish()?;
+        drop(writer);
+
+        let mut reader = MemoryReader::new(bytes)?;
+        let mut block = reader.new_block();
+        assert!(reader.read_block_into(&mut block)?);
+        let records: Vec<_> = block.iter().collect();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].flag(), 1 | options.flag);
+        assert_eq!(records[0].slen(), fragment.len() as u64);
+        assert_eq!(records[0].xlen(), 0);
+        assert_eq!(records[0].squal(), [b'I'; 24]);
+        assert_eq!(records[1].flag(), 2);
+        assert_eq!(records[1].xlen(), 8);
+        Ok(())
+    }
+}
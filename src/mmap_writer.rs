@@ -0,0 +1,171 @@
+//! # Memory-Mapped Output
+//!
+//! This module writes VBINSEQ files through a writable memory map of the output file.
+//!
+//! Writing a file through a `BufWriter` copies every block twice: once into the buffer of
+//! the `BufWriter` and once into the page cache by the `write` system call. For very large
+//! conversions `MmapWriter` instead preallocates the output file and maps it, so blocks are
+//! copied directly into the page cache without system calls. The mapping grows (and is
+//! remapped) whenever a write reaches its end, and the file is truncated to the bytes
+//! written whenever the writer is flushed, so the file is a regular VBINSEQ file once the
+//! `VBinseqWriter` finishes (or after `poll_flush`).
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use vbinseq::{MmapWriter, VBinseqWriterBuilder};
+//!
+//! // Reserve 1 GiB ahead of the write position
+//! let output = MmapWriter::create("reads.vbq", 1 << 30).unwrap();
+//! let mut writer = VBinseqWriterBuilder::default().build(output).unwrap();
+//! writer.write_nucleotides(0, b"ACGTACGT").unwrap();
+//! writer.finish().unwrap();
+//! ```
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+
+use memmap2::{MmapMut, MmapOptions};
+
+use crate::error::Result;
+
+/// Writer of a preallocated, memory-mapped output file
+///
+/// `MmapWriter` is used as the destination of a `VBinseqWriter` (see the module
+/// documentation). Until it is flushed the file is larger than the bytes written, and
+/// dropping the writer flushes it (ignoring errors).
+pub struct MmapWriter {
+    /// The output file
+    file: File,
+
+    /// Writable mapping of the file (if mapped)
+    /// The mapping is released when the file is truncated at a flush
+    mmap: Option<MmapMut>,
+
+    /// Number of bytes written
+    len: usize,
+
+    /// Number of bytes preallocated ahead of the write position
+    preallocate: usize,
+}
+impl MmapWriter {
+    /// Creates (or truncates) an output file and maps its preallocated region
+    ///
+    /// # Parameters
+    ///
+    /// * `path` - Path to the file to write
+    /// * `preallocate` - Number of bytes preallocated ahead of the write position, e.g. the
+    ///   expected size of the file (the mapping still grows past it if needed)
+    ///
+    /// # Errors
+    ///
+    /// * I/O errors if the file can't be created, resized, or mapped
+    pub fn create<P: AsRef<Path>>(path: P, preallocate: usize) -> Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+        let mut writer = Self {
+            file,
+            mmap: None,
+            len: 0,
+            preallocate: preallocate.max(1),
+        };
+        writer.reserve(0)?;
+        Ok(writer)
+    }
+
+    /// Returns the number of bytes written to the writer
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if no bytes were written to the writer
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Maps the file with room for at least `additional` more bytes
+    fn reserve(&mut self, additional: usize) -> io::Result<&mut MmapMut> {
+        let needed = self.len + additional;
+        let mapped = self.mmap.as_ref().map_or(0, |mmap| mmap.len());
+        if self.mmap.is_none() || needed > mapped {
+            // Doubling the mapping keeps the number of remaps logarithmic in the file size
+            let size = needed.max(mapped * 2).max(self.len + self.preallocate);
+            self.mmap = None;
+            self.file.set_len(size as u64)?;
+            // Safety: The file is owned by the writer and is only resized while unmapped
+            self.mmap = Some(unsafe { MmapOptions::new().len(size).map_mut(&self.file)? });
+        }
+        Ok(self.mmap.as_mut().expect("file is mapped"))
+    }
+}
+impl Write for MmapWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let start = self.len;
+        let mmap = self.reserve(buf.len())?;
+        mmap[start..start + buf.len()].copy_from_slice(buf);
+        self.len += buf.len();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        // The written bytes are in the page cache, so unmapping keeps them
+        if self.mmap.take().is_some() {
+            self.file.set_len(self.len as u64)?;
+        }
+        Ok(())
+    }
+}
+impl Drop for MmapWriter {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::SmallRng;
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::testing::{random_records, write_records};
+    use crate::{VBinseqHeader, VBinseqWriterBuilder};
+
+    #[test]
+    fn test_mmap_writer() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("vbq_mmap_writer_{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join("reads.vbq");
+
+        // Flushing truncates the preallocated file, later writes grow it again
+        let mut output = MmapWriter::create(&path, 64)?;
+        assert_eq!(std::fs::metadata(&path)?.len(), 64);
+        output.write_all(b"ACGT")?;
+        output.flush()?;
+        assert_eq!(std::fs::read(&path)?, b"ACGT");
+        output.write_all(&[b'N'; 200])?;
+        assert_eq!(output.len(), 204);
+        drop(output);
+        assert_eq!(std::fs::metadata(&path)?.len(), 204);
+
+        // Files written through the mapping match files written to memory
+        let mut header = VBinseqHeader::with_capacity(1024, true, true, true);
+        header.set_footer(true);
+        let records = random_records(&mut SmallRng::seed_from_u64(3), &header, 300);
+        let mut writer = VBinseqWriterBuilder::default()
+            .header(header)
+            .build(MmapWriter::create(&path, 4096)?)?;
+        for record in &records {
+            writer.write_record(record)?;
+        }
+        writer.close()?;
+        assert_eq!(std::fs::read(&path)?, write_records(header, &records)?);
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
@@ -12,6 +12,12 @@ pub trait ParallelProcessor: Send + Clone {
         Ok(())
     }
 
+    /// Called when a thread finishes processing all of its batches
+    /// Default implementation does nothing
+    fn on_thread_complete(&mut self) -> Result<()> {
+        Ok(())
+    }
+
     /// Set the thread ID for this processor
     ///
     /// Each thread should call this method with its own unique ID.
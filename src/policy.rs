@@ -1,33 +1,77 @@
+#[cfg(feature = "policy-rand")]
 use rand::Rng;
 
 use crate::{error::WriteError, Result};
 
 /// Policy for handling invalid nucleotide sequences
-#[derive(Debug, Clone, Copy, Default)]
+///
+/// Sequences are valid if they only consist of upper- or lowercase `ACGT`, or of any
+/// IUPAC nucleotide in IUPAC files (see `VBinseqHeader::set_iupac`). The policy decides
+/// what happens to records with any other nucleotide, such as `N`. Policies
+/// carrying flag bits mark the records they apply to by setting these bits in the
+/// record flag, so that downstream tools can tell modified records apart.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
 pub enum Policy {
+    /// Skips the record
     #[default]
     IgnoreSequence,
+
+    /// Fails with an error holding the sequence
     BreakOnInvalid,
+
+    /// Replaces invalid nucleotides with random nucleotides
+    ///
+    /// This requires the `policy-rand` feature.
+    #[cfg(feature = "policy-rand")]
     RandomDraw,
+
+    /// Replaces invalid nucleotides with A
     SetToA,
+
+    /// Replaces invalid nucleotides with C
     SetToC,
+
+    /// Replaces invalid nucleotides with G
     SetToG,
+
+    /// Replaces invalid nucleotides with T
     SetToT,
+
+    /// Replaces invalid nucleotides with A and sets the given bits of the record flag
+    SetToAWithFlag(u64),
+
+    /// Sets the given bits of the record flag if a sequence has lowercase (soft-masked)
+    /// nucleotides, and skips records with invalid nucleotides
+    ///
+    /// The 2-bit encoding does not preserve case, so this keeps track of which records
+    /// had masked regions.
+    FlagLowercase(u64),
+
+    /// Fails with an error holding the positions of the invalid nucleotides
+    ReportPositions,
 }
 impl Policy {
-    fn fill_with_known(sequence: &[u8], val: u8, ibuf: &mut Vec<u8>) {
+    fn fill_with_known(sequence: &[u8], val: u8, ibuf: &mut Vec<u8>, valid: fn(&u8) -> bool) {
         for &n in sequence {
-            ibuf.push(match n {
-                b'A' | b'C' | b'G' | b'T' => n,
-                _ => val,
+            // Lowercase nucleotides are valid, as in the encoding fast path
+            ibuf.push(if valid(&n) {
+                n.to_ascii_uppercase()
+            } else {
+                val
             });
         }
     }
 
-    fn fill_with_random<R: Rng>(sequence: &[u8], rng: &mut R, ibuf: &mut Vec<u8>) {
+    #[cfg(feature = "policy-rand")]
+    fn fill_with_random<R: Rng>(
+        sequence: &[u8],
+        rng: &mut R,
+        ibuf: &mut Vec<u8>,
+        valid: fn(&u8) -> bool,
+    ) {
         for &n in sequence {
             ibuf.push(match n {
-                b'A' | b'C' | b'G' | b'T' => n,
+                n if valid(&n) => n.to_ascii_uppercase(),
                 _ => match rng.gen_range(0..4) {
                     0 => b'A',
                     1 => b'C',
@@ -39,6 +83,27 @@ impl Policy {
         }
     }
 
+    /// Returns the bits to set in the flag of a record with the given sequence
+    ///
+    /// This is 0 unless the policy carries flag bits and applies to the sequence.
+    ///
+    /// # Arguments
+    /// * `sequence` - A sequence of the record (before conversion)
+    pub fn flag_bits(&self, sequence: &[u8]) -> u64 {
+        self.flag_bits_with(sequence, is_nucleotide)
+    }
+
+    /// Returns the bits to set in the flag of a record, with a custom set of valid bytes
+    ///
+    /// IUPAC files accept more nucleotides than `ACGT` (see the `iupac` module).
+    pub(crate) fn flag_bits_with(&self, sequence: &[u8], valid: fn(&u8) -> bool) -> u64 {
+        match self {
+            Self::SetToAWithFlag(bits) if !sequence.iter().all(valid) => *bits,
+            Self::FlagLowercase(bits) if sequence.iter().any(u8::is_ascii_lowercase) => *bits,
+            _ => 0,
+        }
+    }
+
     /// Convert the sequence according to the N-policy
     ///
     /// First clears the input buffer to ensure that it is empty.
@@ -49,38 +114,78 @@ impl Policy {
     /// # Arguments
     /// * `sequence` - The sequence to be converted
     /// * `ibuf` - The buffer to store the converted sequence
-    /// * `rng` - The random number generator
-    pub fn handle<R: Rng>(&self, sequence: &[u8], ibuf: &mut Vec<u8>, rng: &mut R) -> Result<bool> {
+    /// * `rng` - The random number generator (only with the `policy-rand` feature)
+    pub fn handle(
+        &self,
+        sequence: &[u8],
+        ibuf: &mut Vec<u8>,
+        #[cfg(feature = "policy-rand")] rng: &mut impl Rng,
+    ) -> Result<bool> {
+        self.handle_with(
+            sequence,
+            ibuf,
+            is_nucleotide,
+            #[cfg(feature = "policy-rand")]
+            rng,
+        )
+    }
+
+    /// Convert the sequence according to the N-policy, with a custom set of valid bytes
+    ///
+    /// Valid bytes are kept (in uppercase), and all others are handled like invalid
+    /// nucleotides by `handle`.
+    pub(crate) fn handle_with(
+        &self,
+        sequence: &[u8],
+        ibuf: &mut Vec<u8>,
+        valid: fn(&u8) -> bool,
+        #[cfg(feature = "policy-rand")] rng: &mut impl Rng,
+    ) -> Result<bool> {
         // First clears the input buffer to ensure that it is empty.
         ibuf.clear();
 
         // Returns a boolean indicating whether the sequence should be processed further.
         match self {
-            Self::IgnoreSequence => Ok(false),
+            Self::IgnoreSequence | Self::FlagLowercase(_) => Ok(false),
             Self::BreakOnInvalid => {
                 let seq_str = std::str::from_utf8(sequence)?.to_string();
                 Err(WriteError::InvalidNucleotideSequence(seq_str).into())
             }
+            #[cfg(feature = "policy-rand")]
             Self::RandomDraw => {
-                Self::fill_with_random(sequence, rng, ibuf);
+                Self::fill_with_random(sequence, rng, ibuf, valid);
                 Ok(true)
             }
-            Self::SetToA => {
-                Self::fill_with_known(sequence, b'A', ibuf);
+            Self::ReportPositions => {
+                let positions = sequence
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, n)| !valid(n))
+                    .map(|(i, _)| i)
+                    .collect();
+                Err(WriteError::InvalidNucleotidePositions(positions).into())
+            }
+            Self::SetToA | Self::SetToAWithFlag(_) => {
+                Self::fill_with_known(sequence, b'A', ibuf, valid);
                 Ok(true)
             }
             Self::SetToC => {
-                Self::fill_with_known(sequence, b'C', ibuf);
+                Self::fill_with_known(sequence, b'C', ibuf, valid);
                 Ok(true)
             }
             Self::SetToG => {
-                Self::fill_with_known(sequence, b'G', ibuf);
+                Self::fill_with_known(sequence, b'G', ibuf, valid);
                 Ok(true)
             }
             Self::SetToT => {
-                Self::fill_with_known(sequence, b'T', ibuf);
+                Self::fill_with_known(sequence, b'T', ibuf, valid);
                 Ok(true)
             }
         }
     }
 }
+
+/// Returns whether a byte is a valid (upper- or lowercase) nucleotide
+pub(crate) fn is_nucleotide(n: &u8) -> bool {
+    matches!(n, b'A' | b'C' | b'G' | b'T' | b'a' | b'c' | b'g' | b't')
+}
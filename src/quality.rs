@@ -0,0 +1,369 @@
+//! # Quality Score Transforms
+//!
+//! Quality scores are stored as one byte per nucleotide. Binned scores (e.g. the 4 or 8
+//! distinct values written by modern sequencers) and long reads have long runs of the same
+//! score, which the block compressor encodes more compactly once the runs are made
+//! explicit. A file can therefore apply a reversible transform to its quality scores
+//! before block compression, recorded in the file header (see
+//! `VBinseqHeader::set_quality_transform`).
+//!
+//! Both transforms are applied to the scores of each sequence separately and preserve
+//! their length, so the record layout is unchanged and readers restore the original
+//! scores while parsing blocks:
+//!
+//! * `QualityTransform::RunLength` stores each score that repeats its predecessor as 0,
+//!   turning runs into zero runs while keeping all other scores as-is.
+//! * `QualityTransform::Delta` stores the (wrapping) difference of each score to its
+//!   predecessor, which also turns runs into zero runs and gives small values for
+//!   slowly changing scores.
+//!
+//! The footer digest of a file covers the stored (transformed) scores.
+//!
+//! # Binning
+//!
+//! Writers can additionally bin the quality scores before storage (see
+//! `VBinseqWriterBuilder::quality_bins`), replacing every score by the representative
+//! score of its bin, e.g. with the 8 levels of Illumina's quality score binning
+//! (`QualityBins::illumina`). Unlike the transforms, binning is lossy: readers return the
+//! binned scores. The bins are stored in the header sections (see
+//! `HeaderSections::quality_bins`), so readers know the scores are quantized.
+//!
+//! # Example
+//!
+//! ```rust
+//! use vbinseq::QualityTransform;
+//!
+//! let mut scores = b"IIIIII####".to_vec();
+//! QualityTransform::RunLength.encode(&mut scores);
+//! assert_eq!(scores, b"I\0\0\0\0\0#\0\0\0");
+//!
+//! QualityTransform::RunLength.decode(&mut scores);
+//! assert_eq!(scores, b"IIIIII####");
+//! ```
+
+use std::fmt;
+
+use crate::error::{HeaderError, Result};
+
+/// Reversible transform applied to quality scores before block compression
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum QualityTransform {
+    /// Quality scores are stored as-is
+    #[default]
+    None,
+
+    /// Scores repeating their predecessor are stored as 0
+    ///
+    /// A literal 0 score following a nonzero score is stored as its predecessor, so the
+    /// transform is exact for any bytes.
+    RunLength,
+
+    /// Scores are stored as the wrapping difference to their predecessor
+    Delta,
+}
+impl QualityTransform {
+    /// Returns the byte representation of the transform
+    pub fn as_byte(self) -> u8 {
+        match self {
+            Self::None => 0,
+            Self::RunLength => 1,
+            Self::Delta => 2,
+        }
+    }
+
+    /// Parses a transform from its byte representation
+    ///
+    /// # Errors
+    ///
+    /// * `HeaderError::InvalidQualityTransform` - If the byte does not correspond to a
+    ///   known transform
+    pub fn from_byte(byte: u8) -> Result<Self> {
+        match byte {
+            0 => Ok(Self::None),
+            1 => Ok(Self::RunLength),
+            2 => Ok(Self::Delta),
+            _ => Err(HeaderError::InvalidQualityTransform(byte).into()),
+        }
+    }
+
+    /// Transforms the quality scores of a sequence in place
+    pub fn encode(self, scores: &mut [u8]) {
+        let mut prev = 0u8;
+        match self {
+            Self::None => {}
+            Self::RunLength => scores.iter_mut().for_each(|score| {
+                let current = *score;
+                *score = swap_zero(current, prev);
+                prev = current;
+            }),
+            Self::Delta => scores.iter_mut().for_each(|score| {
+                let current = *score;
+                *score = current.wrapping_sub(prev);
+                prev = current;
+            }),
+        }
+    }
+
+    /// Restores the quality scores of a sequence transformed with `encode` in place
+    pub fn decode(self, scores: &mut [u8]) {
+        let mut prev = 0u8;
+        match self {
+            Self::None => {}
+            Self::RunLength => scores.iter_mut().for_each(|score| {
+                *score = swap_zero(*score, prev);
+                prev = *score;
+            }),
+            Self::Delta => scores.iter_mut().for_each(|score| {
+                *score = score.wrapping_add(prev);
+                prev = *score;
+            }),
+        }
+    }
+}
+impl fmt::Display for QualityTransform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::None => write!(f, "none"),
+            Self::RunLength => write!(f, "rle"),
+            Self::Delta => write!(f, "delta"),
+        }
+    }
+}
+
+/// Offset of Phred scores stored as ASCII characters
+const PHRED_OFFSET: u8 = 33;
+
+/// Highest Phred score that can be stored as a printable ASCII character (`~`)
+const MAX_PHRED: u8 = b'~' - PHRED_OFFSET;
+
+/// Lossy binning of quality scores applied by writers before storage
+///
+/// Every bin covers the Phred scores from its lower bound up to the lower bound of the
+/// next bin, and scores are replaced by the representative score of their bin. Scores are
+/// expected as Phred+33 ASCII characters, and bytes outside of the printable range (`!` to
+/// `~`) are left unchanged.
+///
+/// # Example
+///
+/// ```rust
+/// use vbinseq::QualityBins;
+///
+/// let bins = QualityBins::illumina();
+/// let mut scores = b"#+5?FI".to_vec();
+/// bins.apply(&mut scores);
+/// assert_eq!(scores, b"#07BFI");
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct QualityBins {
+    /// Lower bound and representative Phred score of every bin, by increasing lower bound
+    bins: Vec<(u8, u8)>,
+}
+impl QualityBins {
+    /// Creates bins from their lower bounds and representative scores
+    ///
+    /// # Parameters
+    ///
+    /// * `bins` - The lower bound and representative Phred score (without the ASCII
+    ///   offset) of every bin, by increasing lower bound
+    ///
+    /// # Errors
+    ///
+    /// * `HeaderError::InvalidQualityBins` - If there are no bins, the first bin does not
+    ///   start at 0, the lower bounds are not increasing, or a representative score is
+    ///   above 93 (`~`)
+    pub fn new(bins: Vec<(u8, u8)>) -> Result<Self> {
+        let valid = bins.first().is_some_and(|&(lower, _)| lower == 0)
+            && bins.windows(2).all(|pair| pair[0].0 < pair[1].0)
+            && bins.iter().all(|&(_, score)| score <= MAX_PHRED);
+        if !valid {
+            return Err(HeaderError::InvalidQualityBins(bins).into());
+        }
+        Ok(Self { bins })
+    }
+
+    /// Returns the 8 levels of Illumina's quality score binning
+    ///
+    /// | Phred scores | Stored score |
+    /// | ------------ | ------------ |
+    /// | 0-2          | 2            |
+    /// | 3-9          | 6            |
+    /// | 10-19        | 15           |
+    /// | 20-24        | 22           |
+    /// | 25-29        | 27           |
+    /// | 30-34        | 33           |
+    /// | 35-39        | 37           |
+    /// | 40 and above | 40           |
+    ///
+    /// The scores of no-calls (2, `#`) are kept.
+    pub fn illumina() -> Self {
+        Self {
+            bins: vec![
+                (0, 2),
+                (3, 6),
+                (10, 15),
+                (20, 22),
+                (25, 27),
+                (30, 33),
+                (35, 37),
+                (40, 40),
+            ],
+        }
+    }
+
+    /// Returns the lower bound and representative Phred score of every bin
+    pub fn bins(&self) -> &[(u8, u8)] {
+        &self.bins
+    }
+
+    /// Returns the binned score of a Phred+33 quality score
+    pub fn bin(&self, score: u8) -> u8 {
+        if !(PHRED_OFFSET..=b'~').contains(&score) {
+            return score;
+        }
+        let phred = score - PHRED_OFFSET;
+        let index = self.bins.partition_point(|&(lower, _)| lower <= phred);
+        self.bins[index - 1].1 + PHRED_OFFSET
+    }
+
+    /// Bins the Phred+33 quality scores of a sequence in place
+    pub fn apply(&self, scores: &mut [u8]) {
+        let table = self.table();
+        scores
+            .iter_mut()
+            .for_each(|score| *score = table[*score as usize]);
+    }
+
+    /// Returns the binned score of every byte value
+    pub(crate) fn table(&self) -> [u8; 256] {
+        std::array::from_fn(|byte| self.bin(byte as u8))
+    }
+}
+
+/// Swaps the values 0 and `prev`, leaving all other values unchanged
+///
+/// This is its own inverse, which makes the run-length transform exact.
+fn swap_zero(value: u8, prev: u8) -> u8 {
+    if value == prev {
+        0
+    } else if value == 0 {
+        prev
+    } else {
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trips() {
+        let inputs: [&[u8]; 5] = [
+            b"",
+            b"IIIIFFFF####IIII",
+            b"I\0\0I\0#",
+            &[0, 0, 255, 255, 0, 1, 1],
+            &(0..=255).collect::<Vec<u8>>(),
+        ];
+        for transform in [
+            QualityTransform::None,
+            QualityTransform::RunLength,
+            QualityTransform::Delta,
+        ] {
+            assert_eq!(
+                QualityTransform::from_byte(transform.as_byte()).unwrap(),
+                transform
+            );
+            for input in inputs {
+                let mut scores = input.to_vec();
+                transform.encode(&mut scores);
+                transform.decode(&mut scores);
+                assert_eq!(scores, input, "{transform} round trip");
+            }
+        }
+
+        let mut scores = b"IIFF".to_vec();
+        QualityTransform::Delta.encode(&mut scores);
+        assert_eq!(scores, [b'I', 0, b'F'.wrapping_sub(b'I'), 0]);
+        assert!(QualityTransform::from_byte(3).is_err());
+    }
+
+    #[test]
+    fn test_quality_bins() {
+        let bins = QualityBins::illumina();
+        let scores: Vec<u8> = (0..=255).collect();
+        let mut binned = scores.clone();
+        bins.apply(&mut binned);
+        for (&score, &stored) in scores.iter().zip(&binned) {
+            if (b'!'..=b'~').contains(&score) {
+                let phred = score - b'!';
+                let &(lower, value) = bins.bins().iter().rev().find(|(l, _)| *l <= phred).unwrap();
+                assert!(lower <= phred);
+                assert_eq!(stored, value + b'!');
+            } else {
+                assert_eq!(stored, score);
+            }
+        }
+        // Binning is idempotent
+        let mut again = binned.clone();
+        bins.apply(&mut again);
+        assert_eq!(again, binned);
+
+        assert_eq!(QualityBins::new(bins.bins().to_vec()).unwrap(), bins);
+        assert!(QualityBins::new(Vec::new()).is_err());
+        assert!(QualityBins::new(vec![(1, 2)]).is_err());
+        assert!(QualityBins::new(vec![(0, 2), (10, 15), (10, 20)]).is_err());
+        assert!(QualityBins::new(vec![(0, 94)]).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "compression")]
+    fn test_binned_scores() -> Result<()> {
+        use rand::rngs::SmallRng;
+        use rand::{Rng, SeedableRng};
+
+        use crate::footer::compute_footer;
+        use crate::testing::{read_records, write_records};
+        use crate::{MemoryReader, OwnedRecord, VBinseqHeader};
+
+        // Long reads with 4 quality bins in runs of random length
+        let mut rng = SmallRng::seed_from_u64(42);
+        let records: Vec<OwnedRecord> = (0..50)
+            .map(|i| {
+                let mut quality = Vec::new();
+                while quality.len() < 2000 {
+                    let run = rng.gen_range(1..40);
+                    quality.extend(std::iter::repeat_n(b"#+5F"[rng.gen_range(0..4)], run));
+                }
+                quality.truncate(2000);
+                OwnedRecord::new(i, b"ACGT".repeat(500), quality)
+            })
+            .collect();
+
+        let mut sizes = Vec::new();
+        for transform in [
+            QualityTransform::None,
+            QualityTransform::RunLength,
+            QualityTransform::Delta,
+        ] {
+            let mut header = VBinseqHeader::with_capacity(1 << 16, true, true, false);
+            header.set_footer(true);
+            header.set_quality_transform(transform);
+            let bytes = write_records(header, &records)?;
+            sizes.push(bytes.len());
+
+            // The header and footer survive, and the original scores are restored
+            let reader = MemoryReader::new(bytes.clone())?;
+            assert_eq!(reader.header().quality_transform(), transform);
+            let footer = reader.footer().expect("file has a footer");
+            assert!(footer.matches(&compute_footer(&bytes, &header)?));
+            let read = read_records(bytes)?;
+            assert!(read
+                .iter()
+                .zip(&records)
+                .all(|(a, b)| a.squal() == b.squal()));
+        }
+        assert!(sizes[1] < sizes[0], "{sizes:?}");
+        Ok(())
+    }
+}
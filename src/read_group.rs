@@ -0,0 +1,218 @@
+//! # Read Groups
+//!
+//! Files combining several lanes, libraries, or samples keep their provenance in a
+//! BAM-style read-group table: every read group has an id and metadata tags (e.g. `SM`
+//! for the sample or `PL` for the platform), and every record references its read group.
+//!
+//! The table is stored in the header sections of the file (see the `sections` module),
+//! and records reference a read group by its position in the table, which is stored in
+//! bits 32 to 47 of the record flag (see `READ_GROUP_MASK`). A file holds at most 65536
+//! read groups. Writers of files with read groups reject records referencing read groups
+//! missing from the table, while files without read groups leave all flag bits to the
+//! user.
+//!
+//! # Example
+//!
+//! ```rust
+//! use vbinseq::read_group::{self, ReadGroup};
+//! use vbinseq::sections::HeaderSections;
+//! use vbinseq::{MemoryReader, VBinseqWriterBuilder};
+//!
+//! let sections = HeaderSections {
+//!     read_groups: vec![
+//!         ReadGroup::new("lane1").with_tag("SM", "sample1"),
+//!         ReadGroup::new("lane2").with_tag("SM", "sample1"),
+//!     ],
+//!     ..HeaderSections::default()
+//! };
+//! let mut writer = VBinseqWriterBuilder::default()
+//!     .sections(sections)
+//!     .build(Vec::new())
+//!     .unwrap();
+//! writer.write_nucleotides(read_group::with_read_group(0, 1), b"ACGT").unwrap();
+//! writer.finish().unwrap();
+//! ```
+
+use crate::sections::{push_string, SectionCursor};
+
+/// Position of the read-group id within the record flag
+pub const READ_GROUP_SHIFT: u32 = 32;
+
+/// Bits of the record flag holding the read-group id
+pub const READ_GROUP_MASK: u64 = 0xFFFF << READ_GROUP_SHIFT;
+
+/// A read group of the read-group table
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReadGroup {
+    /// Identifier of the read group (e.g. the `ID` of a BAM read group)
+    pub id: String,
+
+    /// Metadata of the read group as (key, value) pairs, e.g. `("SM", "sample1")`
+    pub tags: Vec<(String, String)>,
+}
+impl ReadGroup {
+    /// Creates a read group without tags
+    pub fn new(id: impl Into<String>) -> Self {
+        Self {
+            id: id.into(),
+            tags: Vec::new(),
+        }
+    }
+
+    /// Adds a metadata tag to the read group
+    pub fn with_tag(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.tags.push((key.into(), value.into()));
+        self
+    }
+
+    /// Returns the value of the first tag with the given key
+    pub fn tag(&self, key: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Returns the read-group id stored in a record flag
+pub fn read_group(flag: u64) -> u16 {
+    ((flag & READ_GROUP_MASK) >> READ_GROUP_SHIFT) as u16
+}
+
+/// Returns a record flag with the read-group id replaced
+///
+/// # Parameters
+///
+/// * `flag` - The flag of the record
+/// * `group` - The position of the read group in the read-group table
+pub fn with_read_group(flag: u64, group: u16) -> u64 {
+    (flag & !READ_GROUP_MASK) | (u64::from(group) << READ_GROUP_SHIFT)
+}
+
+/// Encodes a read-group table as the payload of its section
+///
+/// The table is stored as the u32 number of read groups followed by every read group: its
+/// id, the u32 number of tags, and every key and value (strings are prefixed with their
+/// u32 length).
+pub(crate) fn encode_table(groups: &[ReadGroup], payload: &mut Vec<u8>) {
+    payload.extend_from_slice(&(groups.len() as u32).to_le_bytes());
+    for group in groups {
+        push_string(payload, &group.id);
+        payload.extend_from_slice(&(group.tags.len() as u32).to_le_bytes());
+        for (key, value) in &group.tags {
+            push_string(payload, key);
+            push_string(payload, value);
+        }
+    }
+}
+
+/// Decodes a read-group table from the payload of its section
+///
+/// Returns `None` if the payload is invalid.
+pub(crate) fn decode_table(payload: &[u8]) -> Option<Vec<ReadGroup>> {
+    let mut cursor = SectionCursor::new(payload);
+    let n_groups = cursor.u32()?;
+    let mut groups = Vec::new();
+    for _ in 0..n_groups {
+        let mut group = ReadGroup::new(cursor.string()?);
+        for _ in 0..cursor.u32()? {
+            group.tags.push((cursor.string()?, cursor.string()?));
+        }
+        groups.push(group);
+    }
+    cursor.is_done().then_some(groups)
+}
+
+#[cfg(all(test, feature = "mmap"))]
+mod tests {
+    use std::fs::File;
+
+    use super::*;
+    use crate::error::WriteError;
+    use crate::sections::HeaderSections;
+    use crate::{
+        validate, Error, MemoryReader, MmapReader, Result, VBinseqHeader, VBinseqWriterBuilder,
+    };
+
+    #[test]
+    fn test_read_groups() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("vbq_read_group_{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join("reads.vbq");
+
+        let sections = HeaderSections {
+            read_groups: vec![
+                ReadGroup::new("lane1").with_tag("SM", "sample1"),
+                ReadGroup::new("lane2")
+                    .with_tag("SM", "sample2")
+                    .with_tag("PL", "ILLUMINA"),
+            ],
+            ..HeaderSections::default()
+        };
+        let mut header = VBinseqHeader::with_capacity(256, true, true, false);
+        header.set_footer(true);
+        let mut writer = VBinseqWriterBuilder::default()
+            .header(header)
+            .sections(sections.clone())
+            .build(File::create(&path)?)?;
+        for i in 0..100u64 {
+            let flag = with_read_group(i, (i % 2) as u16);
+            writer.write_nucleotides_quality(flag, b"ACGTACGTTG", b"IIIIIIIIII")?;
+        }
+        assert!(matches!(
+            writer.write_nucleotides_quality(with_read_group(0, 2), b"ACGT", b"IIII"),
+            Err(Error::WriteError(WriteError::UnknownReadGroup(2, 2)))
+        ));
+        writer.finish()?;
+        drop(writer);
+
+        // The table and the per-record ids are read back
+        let reader = MmapReader::new(&path)?;
+        assert_eq!(reader.sections()?, sections);
+        assert_eq!(
+            reader.sections()?.read_groups[1].tag("PL"),
+            Some("ILLUMINA")
+        );
+        assert_eq!(
+            reader.header().data_offset(),
+            32 + sections.to_bytes().len()
+        );
+        let mut memory = MemoryReader::new(std::fs::read(&path)?)?;
+        assert_eq!(memory.sections()?, sections);
+        let mut block = memory.new_block();
+        let mut n_records = 0;
+        while memory.read_block_into(&mut block)? {
+            for record in block.iter() {
+                assert_eq!(record.read_group(), (record.index() % 2) as u16);
+                assert_eq!(record.flag() & !READ_GROUP_MASK, record.index());
+                n_records += 1;
+            }
+        }
+        assert_eq!(n_records, 100);
+
+        // The sections don't affect indexing and validation
+        assert_eq!(
+            reader.load_index()?.n_blocks(),
+            memory.build_index()?.n_blocks()
+        );
+        assert!(validate::check(&path)?.is_valid());
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_sections() {
+        let sections = HeaderSections {
+            read_groups: vec![ReadGroup::new("lane1")],
+            ..HeaderSections::default()
+        };
+        let bytes = sections.to_bytes();
+        assert!(HeaderSections::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+
+        // Unknown sections are skipped
+        let mut extended = vec![9, 0, 2, 0, 0, 0, 0xAB, 0xCD];
+        extended.extend_from_slice(&bytes);
+        assert_eq!(HeaderSections::from_bytes(&extended).unwrap(), sections);
+    }
+}
@@ -1,31 +1,107 @@
-use std::path::{Path, PathBuf};
+use std::borrow::Cow;
+use std::fmt;
+#[cfg(feature = "mmap")]
+use std::ops::Range;
+use std::path::Path;
+#[cfg(feature = "mmap")]
+use std::path::PathBuf;
 use std::sync::Arc;
+#[cfg(feature = "mmap")]
+use std::time::{Duration, Instant};
 use std::{fs::File, io::Read};
 
 use byteorder::{ByteOrder, LittleEndian};
+#[cfg(feature = "mmap")]
 use memmap2::Mmap;
-use zstd::Decoder;
+use xxhash_rust::xxh3::xxh3_64;
+#[cfg(feature = "compression")]
+use zstd::bulk::Decompressor;
 
+use crate::error::IndexError;
+#[cfg(feature = "mmap")]
+use crate::IndexPolicy;
 use crate::{
-    error::ReadError,
-    header::{SIZE_BLOCK_HEADER, SIZE_HEADER},
-    BlockHeader, BlockIndex, BlockRange, ParallelProcessor, Result, VBinseqHeader,
+    codec,
+    columnar::{self, Layout, Regions},
+    dictionary,
+    error::{ErrorContext, ReadError},
+    footer::{data_end, record_size, Footer, FOOTER_MAGIC, SIZE_FOOTER},
+    header::{MAGIC, RECORD_NO_QUALITY, SIZE_BLOCK_HEADER, SIZE_HEADER},
+    homopolymer,
+    index::{INDEX_HEADER_SIZE, INDEX_MAGIC},
+    iupac, read_group,
+    scan::{Locations, Preambles},
+    sections::HeaderSections,
+    BlockHeader, BlockIndex, BlockRange, Codec, ParallelProcessor, QualityTransform, Result,
+    VBinseqHeader,
+};
+use crate::{
+    recovery::{ParseMode, Recovery, SkipCounts},
+    validate::CheckedRecords,
+    Error,
 };
 
 /// Calculates the number of 64-bit words needed to store a nucleotide sequence of the given length
 ///
-/// Nucleotides are packed into 64-bit words with 2 bits per nucleotide (32 nucleotides per word).
+/// Nucleotides are packed into 64-bit words with 2 bits per nucleotide (32 nucleotides per word),
+/// or with 4 bits per nucleotide (16 nucleotides per word) in IUPAC files.
 /// This function calculates how many 64-bit words are needed to encode a sequence of a given length.
 ///
 /// # Parameters
 ///
 /// * `len` - Length of the nucleotide sequence in basepairs
+/// * `iupac` - Whether the sequence is 4-bit encoded (see the `iupac` module)
 ///
 /// # Returns
 ///
 /// The number of 64-bit words required to encode the sequence
-fn encoded_sequence_len(len: u64) -> usize {
-    len.div_ceil(32) as usize
+pub(crate) fn encoded_sequence_len(len: u64, iupac: bool) -> usize {
+    if iupac {
+        iupac::encoded_len(len)
+    } else {
+        len.div_ceil(32) as usize
+    }
+}
+
+/// Decodes a 2-bit or 4-bit encoded sequence, appending the nucleotides to a buffer
+fn decode_sequence(ebuf: &[u64], len: u64, iupac: bool, dbuf: &mut Vec<u8>) -> Result<()> {
+    if iupac {
+        iupac::decode(ebuf, len as usize, dbuf)
+    } else {
+        bitnuc::decode(ebuf, len as usize, dbuf)?;
+        Ok(())
+    }
+}
+
+/// Calculates the number of bytes a record occupies after its flag and lengths
+///
+/// The `lens` are the lengths of the segments of the record, and the `trailer` is the
+/// number of bytes stored after the record data (e.g. a checksum). Returns `None` if the
+/// size overflows, which only happens for corrupted lengths.
+fn record_data_len(lens: &[u64], has_quality: bool, trailer: usize, iupac: bool) -> Option<u64> {
+    lens.iter().try_fold(trailer as u64, |size, &len| {
+        let size = size.checked_add(encoded_sequence_len(len, iupac) as u64 * 8)?;
+        if has_quality {
+            size.checked_add(len)
+        } else {
+            Some(size)
+        }
+    })
+}
+
+/// Appends the little-endian 64-bit words of a byte slice to a word buffer
+///
+/// The words are copied in bulk (a plain memory copy on little-endian targets) rather
+/// than decoded one at a time.
+///
+/// # Parameters
+///
+/// * `words` - The buffer to append the words to
+/// * `bytes` - The encoded words (the length must be a multiple of 8)
+fn extend_words(words: &mut Vec<u64>, bytes: &[u8]) {
+    let start = words.len();
+    words.resize(start + bytes.len() / 8, 0);
+    LittleEndian::read_u64_into(bytes, &mut words[start..]);
 }
 
 /// A container for a block of VBINSEQ records
@@ -48,24 +124,46 @@ fn encoded_sequence_len(len: u64) -> usize {
 pub struct RecordBlock {
     /// Index of the first record in the block
     /// This allows records to maintain their global position in the file
-    index: usize,
+    index: u64,
 
     /// Buffer containing all record flags in the block
     /// Each record has one flag value stored at the corresponding position
     flags: Vec<u64>,
 
     /// Buffer containing all sequence lengths in the block
-    /// For each record, two consecutive entries are stored: primary sequence length and extended sequence length
+    /// For each record, `length_fields` consecutive entries are stored: the primary sequence
+    /// length, the extended sequence length, and the lengths of any further segments
     lens: Vec<u64>,
 
+    /// Number of lengths stored per record (2, or the number of segments if larger)
+    /// Set from the header of the file on every read
+    length_fields: usize,
+
+    /// Whether the sequences are 4-bit encoded (see the `iupac` module)
+    /// Set from the header of the file on every read
+    iupac: bool,
+
+    /// Whether the sequences are homopolymer-compressed (see the `homopolymer` module)
+    /// Set from the header of the file on every read
+    homopolymer: bool,
+
     /// Buffer containing all packed nucleotide sequences in the block
-    /// Nucleotides are encoded as 2-bit values (4 nucleotides per byte)
+    /// Nucleotides are encoded as 2-bit values (4 nucleotides per byte), or as 4-bit
+    /// values in IUPAC files
     sequences: Vec<u64>,
 
     /// Buffer containing all quality scores in the block
     /// Quality scores are stored as raw bytes, one byte per nucleotide
     qualities: Vec<u8>,
 
+    /// Buffer containing the auxiliary values of all records in the block
+    /// This is empty if the file has no auxiliary values
+    aux: Vec<u64>,
+
+    /// Buffer marking the records of the block without quality scores
+    /// This is empty unless the file has optional quality scores
+    no_quality: Vec<bool>,
+
     /// Maximum size of the block in bytes
     /// This is derived from the file header's block size field
     block_size: usize,
@@ -73,6 +171,27 @@ pub struct RecordBlock {
     /// Reusable buffer for temporary storage during decompression
     /// Using a reusable buffer reduces memory allocations
     rbuf: Vec<u8>,
+
+    /// Reusable buffer for restoring the row layout of columnar blocks
+    /// Only used to ingest single records (see `ingest_record`)
+    rows: Vec<u8>,
+
+    /// Reusable decompression context
+    /// Created on the first compressed block and reused for all following blocks
+    #[cfg(feature = "compression")]
+    decompressor: Option<Decompressor<'static>>,
+
+    /// Compression dictionaries of the file the block is read from
+    /// Set by the reader on every read (see the `dictionary` module)
+    dictionaries: Arc<[Vec<u8>]>,
+
+    /// Dictionary loaded into the decompression context (if any)
+    #[cfg(feature = "compression")]
+    loaded_dictionary: Option<u8>,
+
+    /// Whether the checksums of blocks are verified (in files with block checksums)
+    /// Set by the reader on every read
+    verify_checksums: bool,
 }
 impl RecordBlock {
     /// Creates a new empty `RecordBlock` with the specified block size
@@ -93,13 +212,85 @@ impl RecordBlock {
             index: 0,
             flags: Vec::new(),
             lens: Vec::new(),
+            length_fields: 2,
+            iupac: false,
+            homopolymer: false,
             sequences: Vec::new(),
             qualities: Vec::new(),
+            aux: Vec::new(),
+            no_quality: Vec::new(),
             block_size,
             rbuf: Vec::new(),
+            rows: Vec::new(),
+            #[cfg(feature = "compression")]
+            decompressor: None,
+            dictionaries: Arc::from([]),
+            #[cfg(feature = "compression")]
+            loaded_dictionary: None,
+            verify_checksums: false,
+        }
+    }
+
+    /// Sets the compression dictionaries of the file the block is read from
+    ///
+    /// The decompression context is kept if the dictionaries are unchanged.
+    pub(crate) fn set_dictionaries(&mut self, dictionaries: &Arc<[Vec<u8>]>) {
+        if !Arc::ptr_eq(&self.dictionaries, dictionaries) {
+            self.dictionaries = Arc::clone(dictionaries);
+            #[cfg(feature = "compression")]
+            {
+                self.loaded_dictionary = None;
+            }
         }
     }
 
+    /// Sets whether the checksums of blocks are verified when they are ingested
+    pub(crate) fn set_verify_checksums(&mut self, verify: bool) {
+        self.verify_checksums = verify;
+    }
+
+    /// Reserves capacity for blocks of up to `n_records` records
+    ///
+    /// Block buffers are otherwise grown on demand while reading, so the first blocks
+    /// read into a `RecordBlock` allocate. After reserving, reading blocks of at most
+    /// `n_records` records and iterating over their records does not allocate (the
+    /// decompression context is created once on the first compressed block). Use
+    /// `BlockIndex::max_block_records` to find the capacity needed for a file.
+    ///
+    /// # Parameters
+    ///
+    /// * `n_records` - Maximum number of records of the blocks to be read
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use vbinseq::MmapReader;
+    ///
+    /// let mut reader = MmapReader::new("example.vbq").unwrap();
+    /// let index = reader.load_index().unwrap();
+    ///
+    /// let mut block = reader.new_block();
+    /// block.reserve(index.max_block_records());
+    ///
+    /// // Decode into a reusable buffer to keep the loop allocation-free
+    /// let mut sequence = Vec::new();
+    /// while reader.read_block_into(&mut block).unwrap() {
+    ///     for record in block.iter() {
+    ///         sequence.clear();
+    ///         record.decode_s(&mut sequence).unwrap();
+    ///     }
+    /// }
+    /// ```
+    pub fn reserve(&mut self, n_records: usize) {
+        // Sequences and qualities are bounded by the size of the decompressed block
+        self.flags.reserve(n_records);
+        self.lens.reserve(self.length_fields * n_records);
+        self.aux.reserve(n_records);
+        self.sequences.reserve(self.block_size / 8);
+        self.qualities.reserve(self.block_size);
+        self.rbuf.reserve(self.block_size);
+    }
+
     /// Returns the number of records in this block
     ///
     /// # Returns
@@ -132,10 +323,128 @@ impl RecordBlock {
     ///     println!("Record {}", record.index());
     /// }
     /// ```
-    pub fn iter(&self) -> RecordBlockIter {
+    pub fn iter(&self) -> RecordBlockIter<'_> {
         RecordBlockIter::new(self)
     }
 
+    /// Returns an iterator over the records in this block that validates every record
+    ///
+    /// This is a cheap lint pass over the records of a file: see `CheckedRecords` for the
+    /// checks performed.
+    ///
+    /// # Parameters
+    ///
+    /// * `header` - The header of the file the block was read from
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use vbinseq::MmapReader;
+    ///
+    /// let mut reader = MmapReader::new("example.vbq").unwrap();
+    /// let header = reader.header();
+    /// let mut block = reader.new_block();
+    /// while reader.read_block_into(&mut block).unwrap() {
+    ///     for result in block.iter_checked(&header) {
+    ///         if let Err(e) = result {
+    ///             eprintln!("{e}");
+    ///         }
+    ///     }
+    /// }
+    /// ```
+    pub fn iter_checked(&self, header: &VBinseqHeader) -> CheckedRecords<'_> {
+        CheckedRecords::new(self.iter(), header)
+    }
+
+    /// Decodes the primary sequences of all records into a single buffer
+    ///
+    /// The sequences are decoded as ASCII nucleotides and concatenated in record order,
+    /// which gives vectorized or GPU consumers a contiguous batch instead of one buffer per
+    /// record. The sequence of the `i`-th record of the block is
+    /// `sequences[offsets[i] as usize..offsets[i + 1] as usize]`.
+    ///
+    /// Both buffers are cleared before decoding, so they can be reused across blocks.
+    ///
+    /// # Parameters
+    ///
+    /// * `sequences` - Buffer receiving the concatenated sequences
+    /// * `offsets` - Buffer receiving the `n_records() + 1` sequence boundaries
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the decoding was successful
+    /// * `Err(_)` - If an error occurred during decoding
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use vbinseq::MmapReader;
+    ///
+    /// let mut reader = MmapReader::new("example.vbq").unwrap();
+    /// let mut block = reader.new_block();
+    /// let mut sequences = Vec::new();
+    /// let mut offsets = Vec::new();
+    /// while reader.read_block_into(&mut block).unwrap() {
+    ///     block.decode_all(&mut sequences, &mut offsets).unwrap();
+    ///     for bounds in offsets.windows(2) {
+    ///         let sequence = &sequences[bounds[0] as usize..bounds[1] as usize];
+    ///         println!("{}", std::str::from_utf8(sequence).unwrap());
+    ///     }
+    /// }
+    /// ```
+    pub fn decode_all(&self, sequences: &mut Vec<u8>, offsets: &mut Vec<u32>) -> Result<()> {
+        let total = self.lens.iter().step_by(self.length_fields).sum::<u64>();
+        self.decode_all_with(sequences, offsets, total, |record, dbuf| {
+            record.decode_s(dbuf)
+        })
+    }
+
+    /// Decodes the extended sequences of all records into a single buffer
+    ///
+    /// This is the counterpart of `decode_all` for the extended (paired) sequences. Records
+    /// without an extended sequence span an empty range of the buffer.
+    ///
+    /// # Parameters
+    ///
+    /// * `sequences` - Buffer receiving the concatenated extended sequences
+    /// * `offsets` - Buffer receiving the `n_records() + 1` sequence boundaries
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the decoding was successful
+    /// * `Err(_)` - If an error occurred during decoding
+    pub fn decode_all_x(&self, sequences: &mut Vec<u8>, offsets: &mut Vec<u32>) -> Result<()> {
+        let total = self
+            .lens
+            .iter()
+            .skip(1)
+            .step_by(self.length_fields)
+            .sum::<u64>();
+        self.decode_all_with(sequences, offsets, total, |record, dbuf| {
+            record.decode_x(dbuf)
+        })
+    }
+
+    /// Decodes one sequence per record into a single buffer
+    fn decode_all_with(
+        &self,
+        sequences: &mut Vec<u8>,
+        offsets: &mut Vec<u32>,
+        total: u64,
+        decode: impl Fn(&RefRecord<'_>, &mut Vec<u8>) -> Result<()>,
+    ) -> Result<()> {
+        sequences.clear();
+        sequences.reserve(total as usize);
+        offsets.clear();
+        offsets.reserve(self.n_records() + 1);
+        offsets.push(0);
+        for record in self.iter() {
+            decode(&record, sequences)?;
+            offsets.push(sequences.len() as u32);
+        }
+        Ok(())
+    }
+
     /// Updates the starting index of the block
     ///
     /// This is used internally to keep track of the global position of records
@@ -144,7 +453,7 @@ impl RecordBlock {
     /// # Parameters
     ///
     /// * `index` - The index of the first record in the block
-    fn update_index(&mut self, index: usize) {
+    pub(crate) fn update_index(&mut self, index: u64) {
         self.index = index;
     }
 
@@ -159,6 +468,8 @@ impl RecordBlock {
         self.lens.clear();
         self.sequences.clear();
         self.qualities.clear();
+        self.aux.clear();
+        self.no_quality.clear();
     }
 
     /// Ingest the bytes from a block into the record block
@@ -167,23 +478,63 @@ impl RecordBlock {
     /// the records from the block. It is used when reading a block from
     /// a file into a record block.
     ///
+    /// Exactly the number of records declared by the block header are parsed. The
+    /// zero-length sentinel that precedes the padding of the block is used as a cross-check:
+    /// unless the block is marked as holding zero-length records, it must follow the last
+    /// record (see `BlockHeader::has_empty_records`). If requested, the checksum of the
+    /// block is verified before any record is parsed.
+    ///
     /// This is a private method used primarily for parallel processing.
     ///
     /// # Parameters
     ///
     /// * `bytes` - A slice of bytes containing the block data
-    /// * `has_quality` - A boolean indicating whether the block contains quality scores
+    /// * `header` - The header of the file the block belongs to
+    /// * `block_header` - The header of the block
     ///
     /// # Returns
     ///
     /// A `Result` indicating success or an error
-    fn ingest_bytes(&mut self, bytes: &[u8], has_quality: bool) -> Result<()> {
+    fn ingest_bytes(
+        &mut self,
+        bytes: &[u8],
+        header: &VBinseqHeader,
+        block_header: &BlockHeader,
+    ) -> Result<()> {
+        if self.verify_checksums && header.has_block_checksum() {
+            let checksum = xxh3_64(bytes);
+            if checksum != block_header.checksum() {
+                return Err(
+                    ReadError::BlockChecksumMismatch(block_header.checksum(), checksum).into(),
+                );
+            }
+        }
+        self.iupac = header.is_iupac();
+        self.homopolymer = header.is_homopolymer();
+        if header.is_columnar() {
+            return self.ingest_columnar(bytes, header, block_header);
+        }
+        if header.is_fixed_length() {
+            return self.ingest_fixed(bytes, header, block_header);
+        }
+        let marks_quality = header.marks_quality();
+        let aux = header.record_aux();
+        let trailer = header.record_trailer();
+        // Run lengths of homopolymer-compressed files are stored as-is
+        let transform = if header.qual() {
+            header.quality_transform()
+        } else {
+            QualityTransform::None
+        };
+        let declared = block_header.records;
+        let length_fields = header.length_fields();
+        let preamble = 8 + header.record_lengths();
+        self.length_fields = length_fields;
         let mut pos = 0;
-        loop {
-            // Check that we have enough bytes to at least read the flag
-            // and lengths. If not, break out of the loop.
-            if pos + 24 > bytes.len() {
-                break;
+        for found in 0..declared as usize {
+            // The block must hold the flag and lengths of every declared record
+            if pos + preamble > bytes.len() {
+                return Err(ReadError::RecordCountMismatch(declared, found).into());
             }
 
             // Read the flag and advance the position
@@ -191,136 +542,438 @@ impl RecordBlock {
             pos += 8;
 
             // Read the primary length and advance the position
-            let slen = LittleEndian::read_u64(&bytes[pos..pos + 8]);
-            pos += 8;
-
-            // Read the extended length and advance the position
-            let xlen = LittleEndian::read_u64(&bytes[pos..pos + 8]);
+            let mut slen = LittleEndian::read_u64(&bytes[pos..pos + 8]);
             pos += 8;
 
-            // No more records in the block
-            if slen == 0 {
-                // It is possible to end up here if the block is not full
-                // In this case the flag and the length are both zero
-                // and effectively blank but initialized memory.
-                break;
+            // Records without quality scores are marked in files with optional ones
+            let mut has_quality = header.has_base_bytes();
+            if marks_quality {
+                has_quality = slen & RECORD_NO_QUALITY == 0;
+                slen &= !RECORD_NO_QUALITY;
+                self.no_quality.push(!has_quality);
             }
 
-            // Add the record to the block
-            self.flags.push(flag);
+            // Read the extended length and the lengths of any further segments
+            let lens_start = self.lens.len();
             self.lens.push(slen);
-            self.lens.push(xlen);
-
-            // Add the primary sequence to the block
-            let mut seq = [0u8; 8];
-            for _ in 0..encoded_sequence_len(slen) {
-                seq.copy_from_slice(&bytes[pos..pos + 8]);
-                self.sequences.push(LittleEndian::read_u64(&seq));
+            for _ in 1..length_fields {
+                self.lens.push(LittleEndian::read_u64(&bytes[pos..pos + 8]));
                 pos += 8;
             }
+            let lens = &self.lens[lens_start..];
+            let xlen = lens[1];
 
-            // Add the primary quality score to the block
-            if has_quality {
-                let qual_buffer = &bytes[pos..pos + slen as usize];
-                self.qualities.extend_from_slice(qual_buffer);
-                pos += slen as usize;
+            // The sentinel before the padding of the block (unless empty records are allowed)
+            if slen == 0 && !block_header.has_empty_records() {
+                self.lens.truncate(lens_start);
+                return Err(ReadError::RecordCountMismatch(declared, found).into());
             }
 
-            // Add the extended sequence to the block
-            for _ in 0..encoded_sequence_len(xlen) {
-                seq.copy_from_slice(&bytes[pos..pos + 8]);
-                self.sequences.push(LittleEndian::read_u64(&seq));
-                pos += 8;
+            // Lengths are untrusted, so check the record fits into the block before using them
+            let record_start = pos - preamble;
+            let record_len = record_data_len(lens, has_quality, trailer, self.iupac);
+            if record_len.is_none_or(|len| len > (bytes.len() - pos) as u64) {
+                self.lens.truncate(lens_start);
+                return Err(ReadError::InvalidRecordLength(record_start, slen, xlen).into());
             }
 
-            // Add the extended quality score to the block
-            if has_quality {
-                let qual_buffer = &bytes[pos..pos + xlen as usize];
-                self.qualities.extend_from_slice(qual_buffer);
-                pos += xlen as usize;
+            // Add the record to the block
+            self.flags.push(flag);
+
+            // Add the sequence and quality scores of every segment to the block
+            for i in lens_start..lens_start + length_fields {
+                let len = self.lens[i];
+                let chunk_bytes = encoded_sequence_len(len, self.iupac) * 8;
+                extend_words(&mut self.sequences, &bytes[pos..pos + chunk_bytes]);
+                pos += chunk_bytes;
+                if has_quality {
+                    let qual_buffer = &bytes[pos..pos + len as usize];
+                    self.extend_qualities(qual_buffer, transform);
+                    pos += len as usize;
+                }
+            }
+
+            // Add the auxiliary value to the block
+            if aux > 0 {
+                self.aux
+                    .push(LittleEndian::read_u64(&bytes[pos..pos + aux]));
             }
+
+            // Skip the record checksum (verified by `validate::check`)
+            pos += trailer;
+        }
+
+        // The writer pads blocks with zeros, so any other data after the last record means
+        // the block holds more records than declared or they were parsed with the wrong layout
+        if let Some(offset) = bytes[pos..].iter().position(|&byte| byte != 0) {
+            return Err(ReadError::UnexpectedBlockData(pos + offset).into());
         }
         Ok(())
     }
 
-    fn ingest_compressed_bytes(&mut self, bytes: &[u8], has_quality: bool) -> Result<()> {
-        let mut decoder = Decoder::with_buffer(bytes)?;
+    /// Ingests the bytes of a block of a fixed-length file
+    ///
+    /// Records of fixed-length files omit their lengths and all have the length recorded
+    /// in the block header, so every record has the same size. The block is checked to
+    /// hold all declared records once, after which records are parsed with a constant
+    /// stride.
+    fn ingest_fixed(
+        &mut self,
+        bytes: &[u8],
+        header: &VBinseqHeader,
+        block_header: &BlockHeader,
+    ) -> Result<()> {
+        let has_quality = header.qual();
+        let aux = header.record_aux();
+        let length = block_header.fixed_length();
+        let declared = block_header.records as usize;
+        let transform = header.quality_transform();
 
-        let mut pos = 0;
-        loop {
-            // Check that we have enough bytes to at least read the flag
-            // and lengths. If not, break out of the loop.
-            if pos + 24 > self.block_size {
-                break;
+        // Lengths are untrusted, so check the block holds every declared record
+        let stride = record_data_len(&[length], has_quality, header.record_trailer(), self.iupac)
+            .and_then(|len| len.checked_add(8))
+            .and_then(|len| usize::try_from(len).ok());
+        let end = stride.and_then(|stride| stride.checked_mul(declared));
+        let (stride, end) = match stride.zip(end) {
+            Some((stride, end)) if end <= bytes.len() => (stride, end),
+            _ => return Err(ReadError::InvalidRecordLength(0, length, 0).into()),
+        };
+
+        let schunk_bytes = encoded_sequence_len(length, self.iupac) * 8;
+        let squal = 8 + schunk_bytes;
+        let saux = squal + if has_quality { length as usize } else { 0 };
+        self.length_fields = 2;
+        self.flags.reserve(declared);
+        self.lens.reserve(2 * declared);
+        for record in bytes[..end].chunks_exact(stride) {
+            self.flags.push(LittleEndian::read_u64(&record[..8]));
+            self.lens.push(length);
+            self.lens.push(0);
+            extend_words(&mut self.sequences, &record[8..squal]);
+            if has_quality {
+                self.extend_qualities(&record[squal..saux], transform);
+            }
+            if aux > 0 {
+                self.aux
+                    .push(LittleEndian::read_u64(&record[saux..saux + aux]));
             }
+        }
 
-            // Pull the preambles out of the compressed block and advance the position
-            let mut preamble = [0u8; 24];
-            decoder.read_exact(&mut preamble)?;
-            pos += 24;
+        // The writer pads blocks with zeros (see `ingest_bytes`)
+        if let Some(offset) = bytes[end..].iter().position(|&byte| byte != 0) {
+            return Err(ReadError::UnexpectedBlockData(end + offset).into());
+        }
+        Ok(())
+    }
 
-            // Read the flag + lengths
-            let flag = LittleEndian::read_u64(&preamble[0..8]);
-            let slen = LittleEndian::read_u64(&preamble[8..16]);
-            let xlen = LittleEndian::read_u64(&preamble[16..24]);
+    /// Ingests the bytes of a block of a columnar file
+    ///
+    /// The preambles, sequences, quality scores, and trailers of the records are stored as
+    /// separate regions (see the `columnar` module), so the sequences of all records are
+    /// copied at once and the other parts are read with one cursor per region.
+    fn ingest_columnar(
+        &mut self,
+        bytes: &[u8],
+        header: &VBinseqHeader,
+        block_header: &BlockHeader,
+    ) -> Result<()> {
+        let layout = Layout::of_block(header, block_header);
+        let declared = block_header.records as usize;
+        let regions = Regions::find(bytes, declared, &layout, true)?;
+        let aux = header.record_aux();
+        // Run lengths of homopolymer-compressed files are stored as-is
+        let transform = if header.qual() {
+            header.quality_transform()
+        } else {
+            QualityTransform::None
+        };
+        let fixed = header.is_fixed_length();
+        self.length_fields = if fixed { 2 } else { header.length_fields() };
 
-            // No more records in the block
-            if slen == 0 {
-                // It is possible to end up here if the block is not full
-                // In this case the flag and the length are both zero
-                // and effectively blank but initialized memory.
-                break;
+        // Read the flags and lengths, and add the quality scores of every segment
+        let mut qpos = regions.qualities;
+        let preambles = bytes[..regions.sequences].chunks_exact(layout.preamble());
+        for (found, preamble) in preambles.enumerate() {
+            let lens_start = self.lens.len();
+            let has_quality = layout.read_lengths(preamble, &mut self.lens);
+            if header.marks_quality() {
+                self.no_quality.push(!has_quality);
             }
 
-            // Add the record to the block
-            self.flags.push(flag);
-            self.lens.push(slen);
-            self.lens.push(xlen);
-
-            // Read the sequence and advance the position
-            let schunk = encoded_sequence_len(slen);
-            let schunk_bytes = schunk * 8;
-            self.rbuf.resize(schunk_bytes, 0);
-            decoder.read_exact(&mut self.rbuf[0..schunk_bytes])?;
-            for chunk in self.rbuf.chunks_exact(8) {
-                let seq_part = LittleEndian::read_u64(chunk);
-                self.sequences.push(seq_part);
+            // Records of fixed-length files are read with an empty extended sequence
+            if fixed {
+                self.lens.push(0);
+            } else if self.lens[lens_start] == 0 && !block_header.has_empty_records() {
+                self.lens.truncate(lens_start);
+                return Err(ReadError::RecordCountMismatch(block_header.records, found).into());
             }
-            self.rbuf.clear();
-            pos += schunk_bytes;
-
-            // Add the quality score to the block
-            if has_quality {
-                self.rbuf.resize(slen as usize, 0);
-                decoder.read_exact(&mut self.rbuf[0..slen as usize])?;
-                self.qualities.extend_from_slice(&self.rbuf);
-                self.rbuf.clear();
-                pos += slen as usize;
-            }
-
-            // Read the sequence and advance the position
-            let xchunk = encoded_sequence_len(xlen);
-            let xchunk_bytes = xchunk * 8;
-            self.rbuf.resize(xchunk_bytes, 0);
-            decoder.read_exact(&mut self.rbuf[0..xchunk_bytes])?;
-            for chunk in self.rbuf.chunks_exact(8) {
-                let seq_part = LittleEndian::read_u64(chunk);
-                self.sequences.push(seq_part);
-            }
-            self.rbuf.clear();
-            pos += xchunk_bytes;
-
-            // Add the quality score to the block
+            self.flags.push(LittleEndian::read_u64(&preamble[..8]));
             if has_quality {
-                self.rbuf.resize(xlen as usize, 0);
-                decoder.read_exact(&mut self.rbuf[0..xlen as usize])?;
-                self.qualities.extend_from_slice(&self.rbuf);
-                self.rbuf.clear();
-                pos += xlen as usize;
+                for i in lens_start..self.lens.len() {
+                    let len = self.lens[i] as usize;
+                    let stored = &bytes[qpos..qpos + len];
+                    self.extend_qualities(stored, transform);
+                    qpos += len;
+                }
             }
         }
+        extend_words(
+            &mut self.sequences,
+            &bytes[regions.sequences..regions.qualities],
+        );
+
+        // Add the auxiliary values (the record checksums are verified by `validate::check`)
+        if aux > 0 {
+            let trailers = bytes[regions.trailers..regions.end].chunks_exact(layout.trailer());
+            self.aux
+                .extend(trailers.map(|trailer| LittleEndian::read_u64(&trailer[..aux])));
+        }
+
+        // The writer pads blocks with zeros (see `ingest_bytes`)
+        if let Some(offset) = bytes[regions.end..].iter().position(|&byte| byte != 0) {
+            return Err(ReadError::UnexpectedBlockData(regions.end + offset).into());
+        }
         Ok(())
     }
+
+    /// Adds the stored quality scores of a sequence, restoring the original scores
+    fn extend_qualities(&mut self, stored: &[u8], transform: QualityTransform) {
+        let start = self.qualities.len();
+        self.qualities.extend_from_slice(stored);
+        transform.decode(&mut self.qualities[start..]);
+    }
+
+    /// Ingest the bytes of a block according to its codec
+    ///
+    /// The codec recorded in the block header takes precedence over the codec of the
+    /// file header, which only applies to blocks written without a recorded codec.
+    ///
+    /// # Parameters
+    ///
+    /// * `block_header` - The header of the block being ingested
+    /// * `bytes` - A slice of bytes containing the block data
+    /// * `header` - The header of the file the block belongs to
+    ///
+    /// # Returns
+    ///
+    /// A `Result` indicating success or an error
+    ///
+    /// # Errors
+    ///
+    /// * `ReadError::RecordCountMismatch` if the block holds fewer records than its header
+    ///   declares
+    /// * `ReadError::UnexpectedBlockData` if non-padding data follows the last record
+    /// * `ReadError::InvalidRecordLength` if the lengths of a record exceed the block
+    pub(crate) fn ingest_block(
+        &mut self,
+        block_header: &BlockHeader,
+        bytes: &[u8],
+        header: &VBinseqHeader,
+    ) -> Result<()> {
+        match block_header.codec()?.unwrap_or(header.codec()) {
+            Codec::Uncompressed => self.ingest_bytes(bytes, header, block_header),
+            codec => self.ingest_compressed_bytes(codec, bytes, header, block_header),
+        }
+    }
+
+    /// Ingests a single record of a block
+    ///
+    /// The record is located by its offset in the decompressed block if it is known (see
+    /// `BlockIndex::add_record_offsets`), and by skipping the records preceding it
+    /// otherwise. Block checksums are not verified, as only the record is parsed.
+    ///
+    /// # Parameters
+    ///
+    /// * `block_header` - The header of the block holding the record
+    /// * `bytes` - The stored bytes of the block
+    /// * `header` - The header of the file the block belongs to
+    /// * `position` - The position of the record in the block
+    /// * `offset` - The offset of the record in the decompressed block (if known)
+    pub(crate) fn ingest_record(
+        &mut self,
+        block_header: &BlockHeader,
+        bytes: &[u8],
+        header: &VBinseqHeader,
+        position: usize,
+        offset: Option<u32>,
+    ) -> Result<()> {
+        self.clear();
+        let codec = block_header.codec()?.unwrap_or(header.codec());
+        if codec == Codec::Uncompressed {
+            return self.ingest_record_bytes(bytes, header, block_header, position, offset);
+        }
+        let mut rbuf = std::mem::take(&mut self.rbuf);
+        rbuf.resize(self.block_size.max(header.block() as usize), 0);
+        let size = match codec {
+            #[cfg(feature = "compression")]
+            Codec::Zstd => self.decompress_zstd(bytes, &mut rbuf, block_header),
+            codec => codec::decompress(codec, bytes, &mut rbuf),
+        };
+        let status = size.and_then(|size| {
+            self.ingest_record_bytes(&rbuf[..size], header, block_header, position, offset)
+        });
+        self.rbuf = rbuf;
+        status
+    }
+
+    /// Ingests a single record of the decompressed bytes of a block (see `ingest_record`)
+    fn ingest_record_bytes(
+        &mut self,
+        bytes: &[u8],
+        header: &VBinseqHeader,
+        block_header: &BlockHeader,
+        position: usize,
+        offset: Option<u32>,
+    ) -> Result<()> {
+        // Records are located in the row layout of columnar blocks
+        if header.is_columnar() {
+            let mut rows = std::mem::take(&mut self.rows);
+            let mut row_header = *header;
+            row_header.set_columnar(false);
+            let status = columnar::to_rows(bytes, header, block_header, &mut rows).and_then(|()| {
+                self.ingest_record_bytes(&rows, &row_header, block_header, position, offset)
+            });
+            self.rows = rows;
+            return status;
+        }
+        let start = match offset {
+            Some(offset) => offset as usize,
+            None => {
+                let mut pos = 0;
+                for _ in 0..position {
+                    pos += record_size(bytes, pos, header, block_header)?;
+                }
+                pos
+            }
+        };
+        let len = record_size(bytes, start, header, block_header)?;
+        let record_header = BlockHeader {
+            records: 1,
+            ..*block_header
+        };
+        let verify = std::mem::replace(&mut self.verify_checksums, false);
+        let status = self.ingest_bytes(&bytes[start..start + len], header, &record_header);
+        self.verify_checksums = verify;
+        status
+    }
+
+    /// Decompress a block and ingest its records
+    ///
+    /// The block is decompressed in a single pass into the reusable buffer (sized to the
+    /// virtual block size) and then parsed like an uncompressed block.
+    ///
+    /// # Parameters
+    ///
+    /// * `codec` - The codec the block was compressed with
+    /// * `bytes` - A slice of bytes containing the compressed block data
+    /// * `header` - The header of the file the block belongs to
+    /// * `block_header` - The header of the block
+    ///
+    /// # Returns
+    ///
+    /// A `Result` indicating success or an error
+    fn ingest_compressed_bytes(
+        &mut self,
+        codec: Codec,
+        bytes: &[u8],
+        header: &VBinseqHeader,
+        block_header: &BlockHeader,
+    ) -> Result<()> {
+        // Members of concatenated files may have larger blocks than the first member
+        let mut rbuf = std::mem::take(&mut self.rbuf);
+        rbuf.resize(self.block_size.max(header.block() as usize), 0);
+        let size = match codec {
+            #[cfg(feature = "compression")]
+            Codec::Zstd => self.decompress_zstd(bytes, &mut rbuf, block_header),
+            codec => codec::decompress(codec, bytes, &mut rbuf),
+        };
+        let status = size.and_then(|size| self.ingest_bytes(&rbuf[..size], header, block_header));
+        self.rbuf = rbuf;
+        status
+    }
+
+    /// Decompress a zstd block with the reusable context and the dictionary of the block
+    ///
+    /// # Returns
+    ///
+    /// The decompressed size of the block
+    #[cfg(feature = "compression")]
+    fn decompress_zstd(
+        &mut self,
+        bytes: &[u8],
+        rbuf: &mut [u8],
+        block_header: &BlockHeader,
+    ) -> Result<usize> {
+        let decompressor = match &mut self.decompressor {
+            Some(decompressor) => decompressor,
+            None => self.decompressor.insert(Decompressor::new()?),
+        };
+        // Switching dictionaries replaces the one loaded into the context
+        let dictionary = block_header.dictionary();
+        if dictionary != self.loaded_dictionary {
+            let bytes = dictionary::block_dictionary(block_header, &self.dictionaries)?;
+            decompressor.set_dictionary(bytes.unwrap_or_default())?;
+            self.loaded_dictionary = dictionary;
+        }
+        Ok(decompressor.decompress_to_buffer(bytes, rbuf)?)
+    }
+}
+
+/// Formats the position and contents of the block
+///
+/// The reusable decompression buffers and the dictionaries are omitted.
+impl fmt::Debug for RecordBlock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RecordBlock")
+            .field("index", &self.index)
+            .field("n_records", &self.n_records())
+            .field("block_size", &self.block_size)
+            .field("flags", &self.flags)
+            .field("lens", &self.lens)
+            .field("sequences", &self.sequences)
+            .field("qualities", &self.qualities)
+            .field("aux", &self.aux)
+            .finish()
+    }
+}
+
+/// Blocks are equal if they hold the same records at the same position of a file
+///
+/// The block size, the reusable decompression buffers, and the dictionaries are not compared.
+impl PartialEq for RecordBlock {
+    fn eq(&self, other: &Self) -> bool {
+        self.index == other.index
+            && self.flags == other.flags
+            && self.lens == other.lens
+            && self.sequences == other.sequences
+            && self.qualities == other.qualities
+            && self.aux == other.aux
+    }
+}
+impl Eq for RecordBlock {}
+
+/// Iterates over the records of a block, like `RecordBlock::iter`
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use vbinseq::MmapReader;
+///
+/// let mut reader = MmapReader::new("example.vbq").unwrap();
+/// let mut block = reader.new_block();
+/// reader.read_block_into(&mut block).unwrap();
+///
+/// for record in &block {
+///     println!("Record {}", record.index());
+/// }
+/// ```
+impl<'a> IntoIterator for &'a RecordBlock {
+    type Item = RefRecord<'a>;
+    type IntoIter = RecordBlockIter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
 }
 
 pub struct RecordBlockIter<'a> {
@@ -329,6 +982,8 @@ pub struct RecordBlockIter<'a> {
     rpos: usize,
     /// Encoded sequence position in the block
     epos: usize,
+    /// Quality score position in the block
+    qpos: usize,
 }
 impl<'a> RecordBlockIter<'a> {
     pub fn new(block: &'a RecordBlock) -> Self {
@@ -336,6 +991,7 @@ impl<'a> RecordBlockIter<'a> {
             block,
             rpos: 0,
             epos: 0,
+            qpos: 0,
         }
     }
 }
@@ -346,35 +1002,68 @@ impl<'a> Iterator for RecordBlockIter<'a> {
         if self.rpos == self.block.n_records() {
             return None;
         }
-        let index = (self.block.index + self.rpos) as u64;
+        let index = self.block.index + self.rpos as u64;
         let flag = self.block.flags[self.rpos];
-        let slen = self.block.lens[2 * self.rpos];
-        let xlen = self.block.lens[(2 * self.rpos) + 1];
-        let schunk = encoded_sequence_len(slen);
-        let xchunk = encoded_sequence_len(xlen);
+        let length_fields = self.block.length_fields;
+        let lens = &self.block.lens[length_fields * self.rpos..length_fields * (self.rpos + 1)];
+        let (slen, xlen) = (lens[0], lens[1]);
+        let iupac = self.block.iupac;
+        let schunk = encoded_sequence_len(slen, iupac);
+        let xchunk = encoded_sequence_len(xlen, iupac);
 
         let s_seq = &self.block.sequences[self.epos..self.epos + schunk];
-        let s_qual = if self.block.qualities.is_empty() {
+        let has_quality = !self.block.qualities.is_empty()
+            && !self
+                .block
+                .no_quality
+                .get(self.rpos)
+                .copied()
+                .unwrap_or(false);
+        let s_qual = if !has_quality {
             &[]
         } else {
-            &self.block.qualities[self.epos..self.epos + slen as usize]
+            let qual = &self.block.qualities[self.qpos..self.qpos + slen as usize];
+            self.qpos += slen as usize;
+            qual
         };
         self.epos += schunk;
 
         let x_seq = &self.block.sequences[self.epos..self.epos + xchunk];
-        let x_qual = if self.block.qualities.is_empty() {
+        let x_qual = if !has_quality {
             &[]
         } else {
-            &self.block.qualities[self.epos..self.epos + xlen as usize]
+            let qual = &self.block.qualities[self.qpos..self.qpos + xlen as usize];
+            self.qpos += xlen as usize;
+            qual
         };
         self.epos += xchunk;
 
-        // update record position
+        let mut record = RefRecord::new(index, flag, slen, xlen, s_seq, x_seq, s_qual, x_qual);
+        record.aux = self.block.aux.get(self.rpos).copied().unwrap_or(0);
+        record.iupac = iupac;
+        record.homopolymer = self.block.homopolymer;
+
+        // Further segments are kept together, and located by their lengths on access
+        if length_fields > 2 {
+            let extra_lens = &lens[2..];
+            let chunks = extra_lens
+                .iter()
+                .map(|&len| encoded_sequence_len(len, iupac));
+            let ebuf_len = chunks.sum::<usize>();
+            record.extra_lens = extra_lens;
+            record.extra_buf = &self.block.sequences[self.epos..self.epos + ebuf_len];
+            self.epos += ebuf_len;
+            if has_quality {
+                let equal_len = extra_lens.iter().sum::<u64>() as usize;
+                record.extra_qual = &self.block.qualities[self.qpos..self.qpos + equal_len];
+                self.qpos += equal_len;
+            }
+        }
+
+        // update record position
         self.rpos += 1;
 
-        Some(RefRecord::new(
-            index, flag, slen, xlen, s_seq, x_seq, s_qual, x_qual,
-        ))
+        Some(record)
     }
 }
 
@@ -421,6 +1110,7 @@ impl<'a> Iterator for RecordBlockIter<'a> {
 ///     }
 /// }
 /// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct RefRecord<'a> {
     /// Global index of this record within the file
     index: u64,
@@ -445,6 +1135,25 @@ pub struct RefRecord<'a> {
 
     /// Quality scores for the extended/paired sequence (empty if not paired or no quality)
     xqual: &'a [u8],
+
+    /// Auxiliary value of this record (0 if the file has no auxiliary values)
+    aux: u64,
+
+    /// Lengths of the segments after the extended sequence (empty unless the file has more
+    /// than two segments)
+    extra_lens: &'a [u64],
+
+    /// Buffer containing the encoded segments after the extended sequence
+    extra_buf: &'a [u64],
+
+    /// Quality scores of the segments after the extended sequence
+    extra_qual: &'a [u8],
+
+    /// Whether the sequences are 4-bit encoded (see the `iupac` module)
+    iupac: bool,
+
+    /// Whether the sequences are homopolymer-compressed (see the `homopolymer` module)
+    homopolymer: bool,
 }
 impl<'a> RefRecord<'a> {
     #[allow(clippy::too_many_arguments)]
@@ -467,6 +1176,12 @@ impl<'a> RefRecord<'a> {
             xbuf,
             squal,
             xqual,
+            aux: 0,
+            extra_lens: &[],
+            extra_buf: &[],
+            extra_qual: &[],
+            iupac: false,
+            homopolymer: false,
         }
     }
     /// Returns the global index of this record within the file
@@ -491,6 +1206,19 @@ impl<'a> RefRecord<'a> {
     pub fn flag(&self) -> u64 {
         self.flag
     }
+    /// Returns the auxiliary value of this record
+    ///
+    /// This is 0 if the file has no auxiliary values (see `VBinseqHeader::set_aux`).
+    pub fn aux(&self) -> u64 {
+        self.aux
+    }
+    /// Returns the read-group id stored in the flag of this record
+    ///
+    /// The id is the position of the record's read group in the read-group table of the
+    /// file (see `MmapReader::sections` and the `read_group` module).
+    pub fn read_group(&self) -> u16 {
+        read_group::read_group(self.flag)
+    }
     /// Returns the length of the primary nucleotide sequence
     ///
     /// # Returns
@@ -511,8 +1239,9 @@ impl<'a> RefRecord<'a> {
     }
     /// Returns a reference to the encoded primary nucleotide sequence buffer
     ///
-    /// This provides access to the raw 2-bit encoded sequence data. In most cases,
-    /// you should use `decode_s()` instead to get the decoded sequence.
+    /// This provides access to the raw 2-bit encoded sequence data (4-bit in IUPAC files,
+    /// see `is_iupac`). In most cases, you should use `decode_s()` instead to get the
+    /// decoded sequence.
     ///
     /// # Returns
     ///
@@ -522,8 +1251,9 @@ impl<'a> RefRecord<'a> {
     }
     /// Returns a reference to the encoded extended/paired nucleotide sequence buffer
     ///
-    /// This provides access to the raw 2-bit encoded sequence data. In most cases,
-    /// you should use `decode_x()` instead to get the decoded sequence.
+    /// This provides access to the raw 2-bit encoded sequence data (4-bit in IUPAC files,
+    /// see `is_iupac`). In most cases, you should use `decode_x()` instead to get the
+    /// decoded sequence.
     ///
     /// # Returns
     ///
@@ -559,7 +1289,8 @@ impl<'a> RefRecord<'a> {
     /// This method converts the 2-bit encoded nucleotide sequence (where each nucleotide is
     /// represented by 2 bits) into a sequence of ASCII characters (A, C, G, T). The encoded
     /// format allows for efficient storage (4 nucleotides per byte), while the decoded format
-    /// is easier to work with and display.
+    /// is easier to work with and display. In IUPAC files, sequences are 4-bit encoded and
+    /// decode to any IUPAC nucleotide (see the `iupac` module).
     ///
     /// # Parameters
     ///
@@ -593,8 +1324,7 @@ impl<'a> RefRecord<'a> {
     /// }
     /// ```
     pub fn decode_s(&self, dbuf: &mut Vec<u8>) -> Result<()> {
-        bitnuc::decode(self.sbuf, self.slen as usize, dbuf)?;
-        Ok(())
+        decode_sequence(self.sbuf, self.slen, self.iupac, dbuf)
     }
     /// Decodes the extended/paired nucleotide sequence into ASCII characters
     ///
@@ -639,9 +1369,89 @@ impl<'a> RefRecord<'a> {
     /// }
     /// ```
     pub fn decode_x(&self, dbuf: &mut Vec<u8>) -> Result<()> {
-        bitnuc::decode(self.xbuf, self.xlen as usize, dbuf)?;
-        Ok(())
+        decode_sequence(self.xbuf, self.xlen, self.iupac, dbuf)
+    }
+
+    /// Returns the decoded primary nucleotide sequence
+    ///
+    /// This is a convenience for scripts and tests that allocates a new vector for every
+    /// call. Use `decode_s` with a reused buffer in hot loops.
+    ///
+    /// # Panics
+    ///
+    /// If the sequence can't be decoded, which doesn't happen for records read from a
+    /// block (see `seq_string` for a fallible variant)
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use vbinseq::MmapReader;
+    /// # let mut reader = MmapReader::new("example.vbq").unwrap();
+    /// # let mut block = reader.new_block();
+    /// # reader.read_block_into(&mut block).unwrap();
+    /// for record in block.iter() {
+    ///     assert_eq!(record.seq().len() as u64, record.slen());
+    /// }
+    /// ```
+    pub fn seq(&self) -> Vec<u8> {
+        let mut sequence = Vec::with_capacity(self.slen as usize);
+        self.decode_s(&mut sequence)
+            .expect("Failed to decode primary sequence");
+        sequence
+    }
+
+    /// Returns the decoded extended/paired nucleotide sequence
+    ///
+    /// The sequence is empty if the record is not paired. Like `seq`, this allocates a new
+    /// vector for every call.
+    ///
+    /// # Panics
+    ///
+    /// If the sequence can't be decoded, which doesn't happen for records read from a
+    /// block (see `xseq_string` for a fallible variant)
+    pub fn xseq(&self) -> Vec<u8> {
+        let mut sequence = Vec::with_capacity(self.xlen as usize);
+        self.decode_x(&mut sequence)
+            .expect("Failed to decode extended sequence");
+        sequence
+    }
+
+    /// Returns the decoded primary nucleotide sequence as a string
+    ///
+    /// # Errors
+    ///
+    /// * Bitnuc errors if the sequence can't be decoded
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use vbinseq::MmapReader;
+    /// # let mut reader = MmapReader::new("example.vbq").unwrap();
+    /// # let mut block = reader.new_block();
+    /// # reader.read_block_into(&mut block).unwrap();
+    /// for record in block.iter() {
+    ///     println!("{}\t{}", record.index(), record.seq_string().unwrap());
+    /// }
+    /// ```
+    pub fn seq_string(&self) -> Result<String> {
+        let mut sequence = Vec::with_capacity(self.slen as usize);
+        self.decode_s(&mut sequence)?;
+        Ok(String::from_utf8(sequence).map_err(|e| e.utf8_error())?)
     }
+
+    /// Returns the decoded extended/paired nucleotide sequence as a string
+    ///
+    /// The string is empty if the record is not paired.
+    ///
+    /// # Errors
+    ///
+    /// * Bitnuc errors if the sequence can't be decoded
+    pub fn xseq_string(&self) -> Result<String> {
+        let mut sequence = Vec::with_capacity(self.xlen as usize);
+        self.decode_x(&mut sequence)?;
+        Ok(String::from_utf8(sequence).map_err(|e| e.utf8_error())?)
+    }
+
     /// Checks if this record has a paired/extended sequence
     ///
     /// # Returns
@@ -666,6 +1476,132 @@ impl<'a> RefRecord<'a> {
     pub fn is_paired(&self) -> bool {
         self.xlen > 0
     }
+
+    /// Checks if the sequences of this record are 4-bit encoded IUPAC codes
+    ///
+    /// This is the case for records of files with the IUPAC extension (see
+    /// `VBinseqHeader::set_iupac`), whose encoded buffers (see `sbuf`) hold 16 nucleotides
+    /// per word instead of 32.
+    pub fn is_iupac(&self) -> bool {
+        self.iupac
+    }
+
+    /// Checks if the sequences of this record are homopolymer-compressed
+    ///
+    /// This is the case for records of homopolymer-compressed files (see
+    /// `VBinseqHeader::set_homopolymer`), whose sequences decode to one nucleotide per run
+    /// and whose quality scores are the run lengths (see the `homopolymer` module).
+    pub fn is_homopolymer(&self) -> bool {
+        self.homopolymer
+    }
+
+    /// Returns the number of segments of this record
+    ///
+    /// Records of files with more than two segments (see `VBinseqHeader::set_segments`)
+    /// have all segments of the file. Other records have one segment, or two if they are
+    /// paired.
+    pub fn n_segments(&self) -> usize {
+        if self.extra_lens.is_empty() {
+            1 + usize::from(self.is_paired())
+        } else {
+            2 + self.extra_lens.len()
+        }
+    }
+
+    /// Returns the length of a segment in nucleotides
+    ///
+    /// Segments 0 and 1 are the primary and extended sequences. Segments past the last
+    /// segment of the record have a length of 0.
+    ///
+    /// # Parameters
+    ///
+    /// * `segment` - The position of the segment in the record
+    pub fn segment_len(&self, segment: usize) -> u64 {
+        match segment {
+            0 => self.slen,
+            1 => self.xlen,
+            _ => self.extra_lens.get(segment - 2).copied().unwrap_or(0),
+        }
+    }
+
+    /// Returns a reference to the encoded nucleotide sequence of a segment
+    ///
+    /// Segments past the last segment of the record are empty.
+    ///
+    /// # Parameters
+    ///
+    /// * `segment` - The position of the segment in the record
+    pub fn segment_buf(&self, segment: usize) -> &'a [u64] {
+        match segment {
+            0 => self.sbuf,
+            1 => self.xbuf,
+            _ => {
+                let Some(preceding) = self.extra_lens.get(..segment - 2) else {
+                    return &[];
+                };
+                let start = preceding
+                    .iter()
+                    .map(|&len| encoded_sequence_len(len, self.iupac))
+                    .sum();
+                let len = encoded_sequence_len(self.segment_len(segment), self.iupac);
+                &self.extra_buf[start..start + len]
+            }
+        }
+    }
+
+    /// Returns a reference to the quality scores of a segment
+    ///
+    /// This is empty if the record has no quality scores, and for segments past the last
+    /// segment of the record.
+    ///
+    /// # Parameters
+    ///
+    /// * `segment` - The position of the segment in the record
+    pub fn segment_qual(&self, segment: usize) -> &'a [u8] {
+        match segment {
+            0 => self.squal,
+            1 => self.xqual,
+            _ if self.extra_qual.is_empty() => &[],
+            _ => {
+                let Some(preceding) = self.extra_lens.get(..segment - 2) else {
+                    return &[];
+                };
+                let start = preceding.iter().sum::<u64>() as usize;
+                &self.extra_qual[start..start + self.segment_len(segment) as usize]
+            }
+        }
+    }
+
+    /// Decodes the nucleotide sequence of a segment into ASCII characters
+    ///
+    /// This is `decode_s` for segment 0 and `decode_x` for segment 1. Segments past the
+    /// last segment of the record decode to nothing.
+    ///
+    /// # Parameters
+    ///
+    /// * `segment` - The position of the segment in the record
+    /// * `dbuf` - A mutable vector the decoded nucleotides are appended to
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use vbinseq::MmapReader;
+    /// # let mut reader = MmapReader::new("example.vbq").unwrap();
+    /// # let mut block = reader.new_block();
+    /// # reader.read_block_into(&mut block).unwrap();
+    /// let mut sequence = Vec::new();
+    /// for record in block.iter() {
+    ///     for segment in 0..record.n_segments() {
+    ///         sequence.clear();
+    ///         record.decode_segment(segment, &mut sequence).unwrap();
+    ///         println!("{}", std::str::from_utf8(&sequence).unwrap());
+    ///     }
+    /// }
+    /// ```
+    pub fn decode_segment(&self, segment: usize, dbuf: &mut Vec<u8>) -> Result<()> {
+        let len = self.segment_len(segment);
+        decode_sequence(self.segment_buf(segment), len, self.iupac, dbuf)
+    }
     /// Checks if this record has quality scores
     ///
     /// # Returns
@@ -692,470 +1628,4009 @@ impl<'a> RefRecord<'a> {
     }
 }
 
-/// Memory-mapped reader for VBINSEQ files
+/// A decoded record that owns its sequences and quality scores
 ///
-/// `MmapReader` provides efficient, memory-mapped access to VBINSEQ files. It allows
-/// sequential reading of record blocks and supports parallel processing of records.
+/// `OwnedRecord` holds the decoded (ASCII) nucleotide sequences of a `RefRecord`, so the
+/// record can outlive the block it was read from. This is useful for interoperating with
+/// APIs that expect decoded sequences, such as the record traits of other sequence parsers.
 ///
-/// Memory mapping allows the operating system to lazily load file contents as needed,
-/// which can be more efficient than standard file I/O, especially for large files.
+/// An `OwnedRecord` can be refilled from another record with `fill`, which reuses the
+/// allocated buffers.
 ///
 /// # Examples
 ///
 /// ```rust,no_run
-/// use vbinseq::MmapReader;
+/// use vbinseq::{MmapReader, OwnedRecord};
 ///
-/// // Open a VBINSEQ file
 /// let mut reader = MmapReader::new("example.vbq").unwrap();
-///
-/// // Create a block to hold records
 /// let mut block = reader.new_block();
+/// reader.read_block_into(&mut block).unwrap();
 ///
-/// // Read blocks sequentially
-/// while reader.read_block_into(&mut block).unwrap() {
-///     println!("Read a block with {} records", block.n_records());
-///     // Process records...
+/// let mut owned = OwnedRecord::default();
+/// for record in block.iter() {
+///     owned.fill(&record).unwrap();
+///     println!("{}", std::str::from_utf8(owned.seq()).unwrap());
 /// }
 /// ```
-pub struct MmapReader {
-    /// Path to the VBINSEQ file
-    path: PathBuf,
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OwnedRecord {
+    /// Global index of this record within the file
+    index: u64,
 
-    /// Memory-mapped file contents for efficient access
-    mmap: Arc<Mmap>,
+    /// Flag value for this record
+    flag: u64,
 
-    /// Parsed header information from the file
-    header: VBinseqHeader,
+    /// Decoded primary nucleotide sequence
+    sequence: Vec<u8>,
 
-    /// Current cursor position in the file (in bytes)
-    pos: usize,
+    /// Decoded extended/paired nucleotide sequence (empty if not paired)
+    extended: Vec<u8>,
 
-    /// Total number of records read from the file so far
-    total: usize,
+    /// Quality scores for the primary sequence (empty if quality scores not present)
+    squal: Vec<u8>,
+
+    /// Quality scores for the extended/paired sequence (empty if not paired or no quality)
+    xqual: Vec<u8>,
+
+    /// Auxiliary value of this record (0 if the file has no auxiliary values)
+    aux: u64,
+
+    /// Decoded segments after the extended sequence (empty unless the record has more than
+    /// two segments)
+    extra: Vec<Vec<u8>>,
+
+    /// Quality scores of the segments after the extended sequence (empty if not present)
+    extra_qual: Vec<Vec<u8>>,
+
+    /// Whether the sequences are homopolymer-compressed, with the run lengths in place of
+    /// the quality scores (see `expand_homopolymers`)
+    homopolymer: bool,
 }
-impl MmapReader {
-    /// Creates a new `MmapReader` for a VBINSEQ file
+impl OwnedRecord {
+    /// Creates a new single-end record
     ///
-    /// This method opens the specified file, memory-maps its contents, and reads the
-    /// VBINSEQ header information. The reader is positioned at the beginning of the first
-    /// record block after the header.
+    /// Records created this way have an index of 0, since they were not read from a file.
     ///
     /// # Parameters
     ///
-    /// * `path` - Path to the VBINSEQ file to open
-    ///
-    /// # Returns
-    ///
-    /// A new `MmapReader` instance if successful
-    ///
-    /// # Errors
-    ///
-    /// * `ReadError::InvalidFileType` if the path doesn't point to a regular file
-    /// * I/O errors if the file can't be opened or memory-mapped
-    /// * Header validation errors if the file doesn't contain a valid VBINSEQ header
+    /// * `flag` - Flag value of the record
+    /// * `sequence` - Nucleotide sequence (ASCII)
+    /// * `quality` - Quality scores of the sequence (empty if not present)
+    pub fn new(flag: u64, sequence: Vec<u8>, quality: Vec<u8>) -> Self {
+        Self {
+            flag,
+            sequence,
+            squal: quality,
+            ..Default::default()
+        }
+    }
+
+    /// Creates a new paired record
     ///
-    /// # Examples
+    /// Records created this way have an index of 0, since they were not read from a file.
     ///
-    /// ```rust,no_run
-    /// use vbinseq::MmapReader;
+    /// # Parameters
     ///
-    /// let reader = MmapReader::new("path/to/file.vbq").unwrap();
-    /// ```
-    pub fn new<P: AsRef<Path>>(path: P) -> Result<Self> {
-        // Verify it's a regular file before attempting to map
-        let file = File::open(&path)?;
-        if !file.metadata()?.is_file() {
-            return Err(ReadError::InvalidFileType.into());
+    /// * `flag` - Flag value of the record
+    /// * `sequence` - Primary nucleotide sequence (ASCII)
+    /// * `extended` - Extended/paired nucleotide sequence (ASCII)
+    /// * `squal` - Quality scores of the primary sequence (empty if not present)
+    /// * `xqual` - Quality scores of the extended sequence (empty if not present)
+    pub fn new_paired(
+        flag: u64,
+        sequence: Vec<u8>,
+        extended: Vec<u8>,
+        squal: Vec<u8>,
+        xqual: Vec<u8>,
+    ) -> Self {
+        Self {
+            index: 0,
+            flag,
+            sequence,
+            extended,
+            squal,
+            xqual,
+            ..Default::default()
         }
-
-        // Safety: The file is open and won't be modified while mapped
-        let mmap = unsafe { Mmap::map(&file)? };
-
-        // Read header from mapped memory
-        let header = {
-            let mut header_bytes = [0u8; SIZE_HEADER];
-            header_bytes.copy_from_slice(&mmap[..SIZE_HEADER]);
-            VBinseqHeader::from_bytes(&header_bytes)?
-        };
-
-        Ok(Self {
-            path: PathBuf::from(path.as_ref()),
-            mmap: Arc::new(mmap),
-            header,
-            pos: SIZE_HEADER,
-            total: 0,
-        })
     }
 
-    /// Creates a new empty record block with the appropriate size for this file
+    /// Creates a new record with any number of segments
     ///
-    /// This creates a `RecordBlock` with a block size matching the one specified in the
-    /// file's header, ensuring it will be able to hold a full block of records.
+    /// The first two segments are the primary and extended sequences of the record (see
+    /// `VBinseqHeader::set_segments`). Records created this way have an index of 0, since
+    /// they were not read from a file.
     ///
-    /// # Returns
+    /// # Parameters
     ///
-    /// A new empty `RecordBlock` instance sized appropriately for this file
+    /// * `flag` - Flag value of the record
+    /// * `sequences` - Nucleotide sequences (ASCII) of the segments
+    /// * `qualities` - Quality scores of the segments (empty if not present)
     ///
     /// # Examples
     ///
-    /// ```rust,no_run
-    /// use vbinseq::MmapReader;
+    /// ```rust
+    /// use vbinseq::OwnedRecord;
     ///
-    /// let reader = MmapReader::new("example.vbq").unwrap();
-    /// let mut block = reader.new_block();
+    /// let sequences = vec![b"ACGT".to_vec(), b"TTGCA".to_vec(), b"GGCC".to_vec()];
+    /// let record = OwnedRecord::new_segments(0, sequences, Vec::new());
+    /// assert_eq!(record.n_segments(), 3);
+    /// assert_eq!(record.xseq(), b"TTGCA");
+    /// assert_eq!(record.segment(2), b"GGCC");
     /// ```
-    pub fn new_block(&self) -> RecordBlock {
-        RecordBlock::new(self.header.block as usize)
-    }
-
-    /// Returns the path where the index file would be located
-    ///
-    /// The index file is used for random access to blocks and has the same path as
-    /// the VBINSEQ file with the ".vqi" extension appended.
-    ///
-    /// # Returns
-    ///
-    /// The path where the index file would be located
-    pub fn index_path(&self) -> PathBuf {
-        let mut p = self.path.as_os_str().to_owned();
-        p.push(".vqi");
-        p.into()
+    pub fn new_segments(flag: u64, sequences: Vec<Vec<u8>>, qualities: Vec<Vec<u8>>) -> Self {
+        let mut sequences = sequences.into_iter();
+        let mut qualities = qualities.into_iter();
+        Self {
+            flag,
+            sequence: sequences.next().unwrap_or_default(),
+            extended: sequences.next().unwrap_or_default(),
+            squal: qualities.next().unwrap_or_default(),
+            xqual: qualities.next().unwrap_or_default(),
+            extra: sequences.collect(),
+            extra_qual: qualities.collect(),
+            ..Default::default()
+        }
     }
 
-    /// Returns a copy of the file's header information
-    ///
-    /// The header contains information about the file format, including whether
-    /// quality scores are included, whether blocks are compressed, and whether
-    /// records are paired.
-    ///
-    /// # Returns
+    /// Sets the auxiliary value of the record
     ///
-    /// A copy of the file's `VBinseqHeader`
-    pub fn header(&self) -> VBinseqHeader {
-        self.header
+    /// The value is written by `VBinseqWriter::write_record` to files with auxiliary
+    /// values (see `VBinseqHeader::set_aux`).
+    pub fn with_aux(mut self, aux: u64) -> Self {
+        self.aux = aux;
+        self
     }
 
-    /// Fills an existing RecordBlock with the next block of records from the file
-    ///
-    /// This method reads the next block of records from the current position in the file
-    /// and populates the provided `RecordBlock` with the data. The block is cleared and reused
-    /// to avoid unnecessary memory allocations. This is the primary method for sequential
-    /// reading of VBINSEQ files.
+    /// Replaces the contents of this record with the decoded contents of `record`
     ///
-    /// The method automatically handles decompression if the file was written with
-    /// compression enabled and updates the total record count as it progresses through the file.
+    /// The existing buffers are reused, so refilling a record avoids reallocations.
     ///
     /// # Parameters
     ///
-    /// * `block` - A mutable reference to a `RecordBlock` to be filled with data
+    /// * `record` - The record to decode
     ///
     /// # Returns
     ///
-    /// * `Ok(true)` - If a block was successfully read
-    /// * `Ok(false)` - If the end of the file was reached (no more blocks)
-    /// * `Err(_)` - If an error occurred during reading
-    ///
-    /// # Examples
-    ///
-    /// ```rust,no_run
-    /// use vbinseq::MmapReader;
-    /// use std::io::Write;
-    ///
-    /// let mut reader = MmapReader::new("example.vbq").unwrap();
-    /// let mut block = reader.new_block();
-    /// let mut sequence_buffer = Vec::new();
-    ///
-    /// // Read blocks until the end of file
-    /// while reader.read_block_into(&mut block).unwrap() {
-    ///     println!("Read block with {} records", block.n_records());
-    ///
-    ///     // Process each record
-    ///     for record in block.iter() {
-    ///         // Decode the nucleotide sequence
-    ///         record.decode_s(&mut sequence_buffer).unwrap();
-    ///
-    ///         // Do something with the sequence
-    ///         println!("Record {}: length {}", record.index(), sequence_buffer.len());
-    ///         sequence_buffer.clear();
-    ///     }
-    /// }
-    /// ```
-    pub fn read_block_into(&mut self, block: &mut RecordBlock) -> Result<bool> {
-        // Clear the block
-        block.clear();
+    /// * `Ok(())` - If the sequences were decoded successfully
+    /// * `Err(_)` - If an error occurred during decoding
+    pub fn fill(&mut self, record: &RefRecord) -> Result<()> {
+        self.index = record.index();
+        self.flag = record.flag();
+        self.aux = record.aux();
+        self.homopolymer = record.is_homopolymer();
+        self.sequence.clear();
+        self.extended.clear();
+        self.squal.clear();
+        self.xqual.clear();
+        record.decode_s(&mut self.sequence)?;
+        record.decode_x(&mut self.extended)?;
+        self.squal.extend_from_slice(record.squal());
+        self.xqual.extend_from_slice(record.xqual());
 
-        // Validate the next block header is within bounds and present
-        if self.pos + SIZE_BLOCK_HEADER > self.mmap.len() {
-            return Ok(false);
+        let n_extra = record.extra_lens.len();
+        self.extra.resize_with(n_extra, Vec::new);
+        for (segment, sequence) in (2..).zip(&mut self.extra) {
+            sequence.clear();
+            record.decode_segment(segment, sequence)?;
         }
-        let mut header_bytes = [0u8; SIZE_BLOCK_HEADER];
-        header_bytes.copy_from_slice(&self.mmap[self.pos..self.pos + SIZE_BLOCK_HEADER]);
-        let header = BlockHeader::from_bytes(&header_bytes)?;
-        self.pos += SIZE_BLOCK_HEADER; // advance past the block header
-
-        // Read the block contents
-        let rbound = if self.header.compressed {
-            header.size as usize
+        let n_extra_qual = if record.extra_qual.is_empty() {
+            0
         } else {
-            self.header.block as usize
+            n_extra
         };
-        if self.pos + rbound > self.mmap.len() {
-            return Err(ReadError::UnexpectedEndOfFile(self.pos).into());
+        self.extra_qual.resize_with(n_extra_qual, Vec::new);
+        for (segment, quality) in (2..).zip(&mut self.extra_qual) {
+            quality.clear();
+            quality.extend_from_slice(record.segment_qual(segment));
         }
-        let block_buffer = &self.mmap[self.pos..self.pos + rbound];
-        if self.header.compressed {
-            block.ingest_compressed_bytes(block_buffer, self.header.qual)?;
+        Ok(())
+    }
+
+    /// Returns the global index of this record within the file
+    pub fn index(&self) -> u64 {
+        self.index
+    }
+
+    /// Returns the flag value of this record
+    pub fn flag(&self) -> u64 {
+        self.flag
+    }
+
+    /// Returns the read-group id stored in the flag of this record
+    pub fn read_group(&self) -> u16 {
+        read_group::read_group(self.flag)
+    }
+
+    /// Returns the auxiliary value of this record (0 if the file has none)
+    pub fn aux(&self) -> u64 {
+        self.aux
+    }
+
+    /// Returns the decoded primary nucleotide sequence
+    pub fn seq(&self) -> &[u8] {
+        &self.sequence
+    }
+
+    /// Returns the decoded extended/paired nucleotide sequence (empty if not paired)
+    pub fn xseq(&self) -> &[u8] {
+        &self.extended
+    }
+
+    /// Returns the quality scores of the primary sequence (empty if not present)
+    pub fn squal(&self) -> &[u8] {
+        &self.squal
+    }
+
+    /// Returns the quality scores of the extended/paired sequence (empty if not present)
+    pub fn xqual(&self) -> &[u8] {
+        &self.xqual
+    }
+
+    /// Checks if this record has a paired/extended sequence
+    pub fn is_paired(&self) -> bool {
+        !self.extended.is_empty()
+    }
+
+    /// Returns the number of segments of this record (see `RefRecord::n_segments`)
+    pub fn n_segments(&self) -> usize {
+        if self.extra.is_empty() {
+            1 + usize::from(self.is_paired())
         } else {
-            block.ingest_bytes(block_buffer, self.header.qual)?;
+            2 + self.extra.len()
         }
+    }
 
-        // Update the block index
-        block.update_index(self.total);
+    /// Returns the decoded nucleotide sequence of a segment
+    ///
+    /// Segments 0 and 1 are the primary and extended sequences. Segments past the last
+    /// segment of the record are empty.
+    pub fn segment(&self, segment: usize) -> &[u8] {
+        match segment {
+            0 => &self.sequence,
+            1 => &self.extended,
+            _ => self.extra.get(segment - 2).map_or(&[], Vec::as_slice),
+        }
+    }
 
-        self.pos += rbound;
-        self.total += header.records as usize;
+    /// Returns the quality scores of a segment (empty if not present)
+    pub fn segment_qual(&self, segment: usize) -> &[u8] {
+        match segment {
+            0 => &self.squal,
+            1 => &self.xqual,
+            _ => self.extra_qual.get(segment - 2).map_or(&[], Vec::as_slice),
+        }
+    }
 
-        Ok(true)
+    /// Checks if this record has quality scores
+    pub fn has_quality(&self) -> bool {
+        !self.squal.is_empty()
     }
 
-    /// Loads or creates the block index for this VBINSEQ file
-    ///
-    /// The block index provides metadata about each block in the file, enabling
-    /// random access to blocks and parallel processing. This method first attempts to
-    /// load an existing index file. If the index doesn't exist or doesn't match the
-    /// current file, it automatically generates a new index from the VBINSEQ file
-    /// and saves it for future use.
+    /// Checks if the sequences of this record are homopolymer-compressed
     ///
-    /// # Returns
+    /// Records filled from homopolymer-compressed files (see `RefRecord::is_homopolymer`)
+    /// hold one nucleotide per run, with the run lengths in place of the quality scores,
+    /// until they are expanded with `expand_homopolymers`.
+    pub fn is_homopolymer(&self) -> bool {
+        self.homopolymer
+    }
+
+    /// Expands homopolymer-compressed sequences into the original sequences
     ///
-    /// The loaded or newly created `BlockIndex` if successful
+    /// The run lengths are dropped, so expanded records have no quality scores. Records
+    /// that are not homopolymer-compressed are left unchanged.
     ///
     /// # Errors
     ///
-    /// * File I/O errors when reading or creating the index
-    /// * Parsing errors if the VBINSEQ file has invalid format
-    /// * Other index-related errors that cannot be resolved by creating a new index
+    /// * `ReadError::InvalidHomopolymerRuns` - If the run lengths do not match a sequence
     ///
     /// # Examples
     ///
     /// ```rust,no_run
-    /// use vbinseq::MmapReader;
-    ///
-    /// let reader = MmapReader::new("example.vbq").unwrap();
-    ///
-    /// // Load the index file (or create if it doesn't exist)
-    /// let index = reader.load_index().unwrap();
-    ///
-    /// // Use the index to get information about the file
-    /// println!("Number of blocks: {}", index.n_blocks());
+    /// # use vbinseq::{MmapReader, OwnedRecord};
+    /// # let mut reader = MmapReader::new("example.vbq").unwrap();
+    /// # let mut block = reader.new_block();
+    /// # reader.read_block_into(&mut block).unwrap();
+    /// let mut owned = OwnedRecord::default();
+    /// for record in block.iter() {
+    ///     owned.fill(&record).unwrap();
+    ///     owned.expand_homopolymers().unwrap();
+    ///     assert!(!owned.is_homopolymer());
+    /// }
     /// ```
-    ///
-    /// # Notes
-    ///
-    /// The index file is stored with the same path as the VBINSEQ file but with a ".vqi"
-    /// extension appended. This allows for reusing the index across multiple runs,
-    /// which can significantly improve startup performance for large files.
-    pub fn load_index(&self) -> Result<BlockIndex> {
-        if self.index_path().exists() {
-            match BlockIndex::from_path(self.index_path()) {
-                Ok(index) => Ok(index),
-                Err(e) => {
-                    if e.is_index_mismatch() {
-                        let index = BlockIndex::from_vbq(&self.path)?;
-                        index.save_to_path(self.index_path())?;
-                        Ok(index)
-                    } else {
-                        Err(e)
-                    }
-                }
-            }
-        } else {
-            let index = BlockIndex::from_vbq(&self.path)?;
-            index.save_to_path(self.index_path())?;
-            Ok(index)
+    pub fn expand_homopolymers(&mut self) -> Result<()> {
+        if !self.homopolymer {
+            return Ok(());
         }
+        // Homopolymer-compressed files hold at most two segments
+        for (sequence, runs) in [
+            (&mut self.sequence, &mut self.squal),
+            (&mut self.extended, &mut self.xqual),
+        ] {
+            let mut expanded = Vec::new();
+            homopolymer::expand(sequence, runs, &mut expanded)?;
+            *sequence = expanded;
+            runs.clear();
+        }
+        self.homopolymer = false;
+        Ok(())
     }
-}
 
-impl MmapReader {
-    /// Processes all records in the file in parallel using multiple threads
-    ///
-    /// This method provides efficient parallel processing of VBINSEQ files by distributing
-    /// blocks across multiple worker threads. The file's block structure is leveraged to divide
-    /// the work evenly without requiring thread synchronization during processing, which leads
-    /// to near-linear scaling with the number of threads.
-    ///
-    /// The method automatically loads or creates an index file to identify block boundaries,
-    /// then distributes the blocks among the requested number of threads. Each thread processes
-    /// its assigned blocks sequentially, but multiple blocks are processed in parallel across
-    /// threads.
-    ///
-    /// # Type Parameters
-    ///
-    /// * `P` - A type that implements the `ParallelProcessor` trait, which defines how records are processed
-    ///
-    /// # Parameters
-    ///
-    /// * `self` - Consumes the reader, as it will be used across multiple threads
-    /// * `processor` - An instance of a type implementing `ParallelProcessor` that will be cloned for each thread
-    /// * `num_threads` - Number of worker threads to use for processing
-    ///
-    /// # Returns
-    ///
-    /// * `Ok(())` - If all records were successfully processed
-    /// * `Err(_)` - If an error occurs during processing
-    ///
-    /// # Examples
-    ///
-    /// ```rust,no_run
-    /// use vbinseq::{MmapReader, ParallelProcessor, RefRecord, Result};
-    /// use std::sync::atomic::{AtomicUsize, Ordering};
-    /// use std::sync::Arc;
-    ///
-    /// // Create a simple processor that counts records
-    /// struct RecordCounter {
-    ///     count: Arc<AtomicUsize>,
-    ///     thread_id: usize,
-    /// }
-    ///
-    /// impl RecordCounter {
-    ///     fn new() -> Self {
-    ///         Self {
-    ///             count: Arc::new(AtomicUsize::new(0)),
-    ///             thread_id: 0,
-    ///         }
-    ///     }
-    ///
-    ///     fn total_count(&self) -> usize {
-    ///         self.count.load(Ordering::Relaxed)
-    ///     }
-    /// }
-    ///
-    /// impl Clone for RecordCounter {
-    ///     fn clone(&self) -> Self {
-    ///         Self {
-    ///             count: Arc::clone(&self.count),
-    ///             thread_id: 0,
-    ///         }
-    ///     }
-    /// }
+    /// Returns this record with homopolymer-compressed sequences expanded
     ///
-    /// impl ParallelProcessor for RecordCounter {
-    ///     fn process_record(&mut self, _record: RefRecord) -> Result<()> {
-    ///         self.count.fetch_add(1, Ordering::Relaxed);
-    ///         Ok(())
-    ///     }
-    ///
-    ///     fn on_batch_complete(&mut self) -> Result<()> {
-    ///         // Optional: perform actions after each block is processed
-    ///         Ok(())
-    ///     }
-    ///
-    ///     fn set_tid(&mut self, tid: usize) {
-    ///         self.thread_id = tid;
-    ///     }
-    /// }
-    ///
-    /// // Use the processor with a VBINSEQ file
-    /// let reader = MmapReader::new("example.vbq").unwrap();
-    /// let counter = RecordCounter::new();
-    ///
-    /// // Process the file with 4 threads
-    /// reader.process_parallel(counter.clone(), 4).unwrap();
-    ///
-    /// // Get the total number of records processed
-    /// println!("Total records: {}", counter.total_count());
-    /// ```
+    /// The record is only copied if it is homopolymer-compressed (see
+    /// `expand_homopolymers`).
     ///
-    /// # Notes
+    /// # Errors
     ///
-    /// * The `ParallelProcessor` instance is cloned for each worker thread, so any shared state
-    ///   should be wrapped in thread-safe containers like `Arc`.
-    /// * The `set_tid` method is called with a unique thread ID before processing begins, which
-    ///   can be used to distinguish between worker threads.
-    /// * This method consumes the reader (takes ownership), as it's distributed across threads.
-    pub fn process_parallel<P: ParallelProcessor + Clone + 'static>(
-        self,
-        processor: P,
-        num_threads: usize,
-    ) -> Result<()> {
-        // Generate or load the index first
-        let index = self.load_index()?;
-
-        // Get the number of blocks
-        let n_blocks = index.n_blocks();
-        if n_blocks == 0 {
-            return Ok(()); // Nothing to process
+    /// * `ReadError::InvalidHomopolymerRuns` - If the run lengths do not match a sequence
+    pub fn expanded(&self) -> Result<Cow<'_, Self>> {
+        if !self.homopolymer {
+            return Ok(Cow::Borrowed(self));
         }
+        let mut expanded = self.clone();
+        expanded.expand_homopolymers()?;
+        Ok(Cow::Owned(expanded))
+    }
+}
+impl TryFrom<&RefRecord<'_>> for OwnedRecord {
+    type Error = crate::Error;
 
-        // Calculate block assignments
-        let blocks_per_thread = n_blocks.div_ceil(num_threads);
-
-        // Create shared resources
-        let mmap = Arc::clone(&self.mmap);
-        let header = self.header;
+    fn try_from(record: &RefRecord<'_>) -> Result<Self> {
+        let mut owned = Self::default();
+        owned.fill(record)?;
+        Ok(owned)
+    }
+}
 
-        // Spawn worker threads
-        let mut handles = Vec::new();
+/// Contents of a file loaded for reading
+///
+/// Files are memory-mapped if the `mmap` feature is enabled, and read into memory otherwise.
+#[cfg(feature = "mmap")]
+pub(crate) type FileBytes = Mmap;
 
-        for thread_id in 0..num_threads {
-            // Calculate this thread's block range
-            let start_block = thread_id * blocks_per_thread;
-            let end_block = std::cmp::min((thread_id + 1) * blocks_per_thread, n_blocks);
-            if start_block > n_blocks {
-                continue;
-            }
+/// Contents of a file loaded for reading
+///
+/// Files are memory-mapped if the `mmap` feature is enabled, and read into memory otherwise.
+#[cfg(not(feature = "mmap"))]
+pub(crate) type FileBytes = Vec<u8>;
 
-            let mmap = Arc::clone(&mmap);
-            let mut proc = processor.clone();
-            proc.set_tid(thread_id);
+/// Loads the contents of a file for reading
+///
+/// # Errors
+///
+/// * `ReadError::InvalidFileType` if the path doesn't point to a regular file
+/// * I/O errors if the file can't be opened, memory-mapped, or read
+pub(crate) fn load_file<P: AsRef<Path>>(path: P) -> Result<FileBytes> {
+    let file = open_regular_file(path)?;
 
-            // Get block ranges for this thread
-            let blocks: Vec<BlockRange> = index.ranges()[start_block..end_block].to_vec();
+    #[cfg(feature = "mmap")]
+    let bytes = map_file(&file, &MapOptions::default())?;
 
-            let handle = std::thread::spawn(move || -> Result<()> {
-                // Create block to reuse for processing (within thread)
-                let mut record_block = RecordBlock::new(header.block as usize);
+    #[cfg(not(feature = "mmap"))]
+    let bytes = {
+        let mut bytes = Vec::new();
+        let mut file = file;
+        file.read_to_end(&mut bytes)?;
+        bytes
+    };
 
-                // Process each assigned block
-                for block_range in blocks {
-                    // Clear the block for reuse
-                    record_block.clear();
-
-                    // Skip the block header to get to data
-                    let block_start = block_range.start_offset as usize + SIZE_BLOCK_HEADER;
-                    let block_data = &mmap[block_start..block_start + block_range.len as usize];
-
-                    // Ingest data according to the compression setting
-                    if header.compressed {
-                        record_block.ingest_compressed_bytes(block_data, header.qual)?;
-                    } else {
-                        record_block.ingest_bytes(block_data, header.qual)?;
-                    }
+    Ok(bytes)
+}
 
-                    // Update the record block index
-                    record_block.update_index(block_range.cumulative_records as usize);
+/// Opens a file, verifying that it is a regular file
+fn open_regular_file<P: AsRef<Path>>(path: P) -> Result<File> {
+    let file = File::open(path)?;
+    if !file.metadata()?.is_file() {
+        return Err(ReadError::InvalidFileType.into());
+    }
+    Ok(file)
+}
 
-                    // Process each record in the block
-                    for record in record_block.iter() {
-                        proc.process_record(record)?;
-                    }
+/// Options for memory-mapping files
+///
+/// Both options only take effect on Linux and are ignored on other platforms.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use vbinseq::{MapOptions, MmapReader};
+///
+/// let options = MapOptions {
+///     populate: true,
+///     huge_pages: true,
+/// };
+/// let reader = MmapReader::with_options("example.vbq", &options).unwrap();
+/// ```
+#[cfg(feature = "mmap")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MapOptions {
+    /// Whether to read the whole file into the page cache when mapping it (`MAP_POPULATE`)
+    ///
+    /// This moves the cost of page faults to the creation of the reader and improves the
+    /// throughput of the first pass over a file on fast local storage.
+    pub populate: bool,
 
-                    // Signal batch completion
-                    proc.on_batch_complete()?;
-                }
+    /// Whether to request transparent hugepages for the mapping (`MADV_HUGEPAGE`)
+    ///
+    /// This reduces TLB pressure for very large files. The request is advisory and is
+    /// silently dropped if the kernel does not support transparent hugepages.
+    pub huge_pages: bool,
+}
 
-                Ok(())
-            });
+/// Options for following a file that is still being written
+///
+/// A reader opened with `MmapReader::follow` does not stop at the end of the file.
+/// Instead it waits for more blocks, checking the file for growth every `poll_interval`.
+/// It stops once the file has a footer (written when the writer finishes) or once the
+/// file has not grown for `timeout`.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use std::time::Duration;
+/// use vbinseq::FollowOptions;
+///
+/// // Stop if no new records arrive for a minute
+/// let options = FollowOptions {
+///     timeout: Some(Duration::from_secs(60)),
+///     ..Default::default()
+/// };
+/// ```
+#[cfg(feature = "mmap")]
+#[derive(Debug, Clone, Copy)]
+pub struct FollowOptions {
+    /// Time to wait before checking the file for new blocks again
+    pub poll_interval: Duration,
+
+    /// Time without growth of the file after which reading stops
+    ///
+    /// If `None`, files without a footer are followed indefinitely.
+    pub timeout: Option<Duration>,
+}
+#[cfg(feature = "mmap")]
+impl Default for FollowOptions {
+    fn default() -> Self {
+        Self {
+            poll_interval: Duration::from_millis(500),
+            timeout: None,
+        }
+    }
+}
+
+/// Memory-maps a file with the given options
+#[cfg(feature = "mmap")]
+fn map_file(file: &File, options: &MapOptions) -> Result<Mmap> {
+    let mut mmap_options = memmap2::MmapOptions::new();
+    if options.populate {
+        mmap_options.populate();
+    }
+
+    // Safety: The file is open and won't be modified while mapped
+    let mmap = unsafe { mmap_options.map(file)? };
+
+    #[cfg(target_os = "linux")]
+    if options.huge_pages {
+        // The advice is only a hint, so kernels without hugepage support are not an error
+        let _ = mmap.advise(memmap2::Advice::HugePage);
+    }
+    Ok(mmap)
+}
+
+/// Parses the header and footer of a VBINSEQ file held in memory
+///
+/// Returns the header, the footer (if any), and the position where the record blocks end.
+pub(crate) fn parse_file_layout(bytes: &[u8]) -> Result<(VBinseqHeader, Option<Footer>, usize)> {
+    if bytes.is_empty() {
+        return Err(ReadError::EmptyFile.into());
+    }
+    if bytes.len() < SIZE_HEADER {
+        return Err(ReadError::UnexpectedEndOfFile(bytes.len()).into());
+    }
+    let header = {
+        let mut header_bytes = [0u8; SIZE_HEADER];
+        header_bytes.copy_from_slice(&bytes[..SIZE_HEADER]);
+        VBinseqHeader::from_bytes(&header_bytes)?
+    };
+    if bytes.len() < header.data_offset() {
+        return Err(ReadError::UnexpectedEndOfFile(bytes.len()).into());
+    }
+    let footer = Footer::from_file_bytes(bytes, &header)?;
+    let end = data_end(bytes, &header)?;
+    Ok((header, footer, end))
+}
+
+/// Parses the header and footer of a VBINSEQ file that may still be written to
+///
+/// Unlike `parse_file_layout`, a missing or incomplete footer is not an error: the file
+/// is assumed to still grow and its record blocks to extend to its end.
+#[cfg(feature = "mmap")]
+fn parse_growing_layout(bytes: &[u8]) -> Result<(VBinseqHeader, Option<Footer>, usize)> {
+    if bytes.is_empty() {
+        return Err(ReadError::EmptyFile.into());
+    }
+    if bytes.len() < SIZE_HEADER {
+        return Err(ReadError::UnexpectedEndOfFile(bytes.len()).into());
+    }
+    let header = {
+        let mut header_bytes = [0u8; SIZE_HEADER];
+        header_bytes.copy_from_slice(&bytes[..SIZE_HEADER]);
+        VBinseqHeader::from_bytes(&header_bytes)?
+    };
+    if bytes.len() < header.data_offset() {
+        return Err(ReadError::UnexpectedEndOfFile(bytes.len()).into());
+    }
+    match Footer::from_file_bytes(bytes, &header) {
+        Ok(Some(footer)) => match data_end(bytes, &header) {
+            Ok(end) => Ok((header, Some(footer), end)),
+            Err(_) => Ok((header, None, bytes.len())),
+        },
+        _ => Ok((header, None, bytes.len())),
+    }
+}
+
+/// A member of a concatenated stream (see `next_member`)
+pub(crate) struct Member {
+    /// Position of the header of the member in the stream
+    pub(crate) start: usize,
+    /// Header of the member
+    pub(crate) header: VBinseqHeader,
+    /// Compression dictionaries of the member
+    pub(crate) dictionaries: Arc<[Vec<u8>]>,
+    /// Position in the stream where the record blocks end (at the latest)
+    pub(crate) end: usize,
+}
+
+/// Parses the member of a concatenated stream starting at `pos`, if any
+///
+/// Concatenated files (e.g. `cat a.vbq b.vbq > c.vbq`) form a stream of members, like the
+/// members of a gzip file. Each member starts with its file header directly after the
+/// record blocks of the previous member, or after its footer (and embedded index). Returns
+/// `None` if no member starts at `pos`, e.g. at the end of the stream.
+///
+/// The record blocks of a member end at the next member, so the returned end only bounds
+/// the blocks of the last member (before the footer of the stream, if any).
+pub(crate) fn next_member(bytes: &[u8], pos: usize) -> Result<Option<Member>> {
+    let read_u64 = |pos: usize| bytes.get(pos..pos + 8).map(LittleEndian::read_u64);
+    // Embedded indexes record their own size (see `BlockIndex::write_embedded`)
+    let pos = if read_u64(pos) == Some(INDEX_MAGIC) {
+        read_u64(pos + 8)
+            .and_then(|size| pos.checked_add(usize::try_from(size).ok()?))
+            .ok_or(ReadError::UnexpectedEndOfFile(bytes.len()))?
+    } else {
+        pos
+    };
+    let start = if read_u64(pos) == Some(FOOTER_MAGIC) {
+        pos + SIZE_FOOTER
+    } else {
+        pos
+    };
+    if bytes.get(start..start + 4).map(LittleEndian::read_u32) != Some(MAGIC) {
+        return Ok(None);
+    }
+    let member = &bytes[start..];
+    let (header, end) = match parse_file_layout(member) {
+        Ok((header, _, end)) => (header, end),
+        // Only the last member ends the stream, so its footer may be absent
+        Err(_) if member.len() >= SIZE_HEADER => {
+            let mut header_bytes = [0u8; SIZE_HEADER];
+            header_bytes.copy_from_slice(&member[..SIZE_HEADER]);
+            (VBinseqHeader::from_bytes(&header_bytes)?, member.len())
+        }
+        Err(e) => return Err(e),
+    };
+    if member.len() < header.data_offset() {
+        return Err(ReadError::UnexpectedEndOfFile(bytes.len()).into());
+    }
+    Ok(Some(Member {
+        start,
+        header,
+        dictionaries: dictionary::load(member, &header)?,
+        end: start + end,
+    }))
+}
+
+/// Returns the first member of a file held in memory
+///
+/// Readers positioned in the first member reuse its header and dictionaries, other readers
+/// parse them again from the start of the file.
+pub(crate) fn first_member(
+    bytes: &[u8],
+    member: usize,
+    header: &VBinseqHeader,
+    dictionaries: &Arc<[Vec<u8>]>,
+) -> Result<Member> {
+    if member == 0 {
+        return Ok(Member {
+            start: 0,
+            header: *header,
+            dictionaries: dictionaries.clone(),
+            end: data_end(bytes, header)?,
+        });
+    }
+    next_member(bytes, 0)?.ok_or_else(|| ReadError::EmptyFile.into())
+}
+
+/// Fills a RecordBlock with a single record of a file held in memory
+///
+/// The record is located with the block index of the file (see `MmapReader::seek_record`).
+/// Returns `false` if the record index is past the last record of the index.
+///
+/// # Parameters
+///
+/// * `bytes` - The contents of the file
+/// * `member` - The first member of the file (which the index describes)
+/// * `index` - The block index of the file
+/// * `record` - The index of the record in the file
+/// * `block` - The block to fill with the record
+pub(crate) fn read_record_at(
+    bytes: &[u8],
+    member: &Member,
+    index: &BlockIndex,
+    record: u64,
+    block: &mut RecordBlock,
+) -> Result<bool> {
+    let Some(position) = index.block_of_record(record) else {
+        block.clear();
+        return Ok(false);
+    };
+    let range = &index.ranges()[position];
+    let (mut pos, mut total) = (range.start_offset as usize, range.cumulative_records);
+    let Some(raw) = read_next_raw_block(bytes, member.end, &member.header, &mut pos, &mut total)?
+    else {
+        return Err(IndexError::InvalidBlockOffset(range.start_offset).into());
+    };
+    if raw.header.records != range.block_records {
+        return Err(IndexError::RecordCountMismatch(
+            format!("records in block {position}"),
+            u64::from(range.block_records),
+            u64::from(raw.header.records),
+        )
+        .into());
+    }
+    let in_block = (record - range.cumulative_records) as usize;
+    let offset = index
+        .record_offsets(position)
+        .map(|offsets| offsets[in_block]);
+    block.set_dictionaries(&member.dictionaries);
+    block
+        .ingest_record(&raw.header, raw.data, &member.header, in_block, offset)
+        .map_err(|e| e.with_context(block_context(range.start_offset as usize, record)))?;
+    block.update_index(record);
+    Ok(true)
+}
+
+/// Fills a RecordBlock with the block starting at `*pos` of a file held in memory
+///
+/// Advances `*pos` past the block and `*total` by its number of records.
+/// Returns `false` if there are no more blocks before `end`. Errors are annotated with the
+/// offset of the block and the index of the record being read.
+pub(crate) fn read_next_block(
+    bytes: &[u8],
+    end: usize,
+    header: &VBinseqHeader,
+    pos: &mut usize,
+    total: &mut u64,
+    block: &mut RecordBlock,
+) -> Result<bool> {
+    // Clear the block
+    block.clear();
+
+    let (offset, first_index) = (*pos, *total);
+    let Some(raw) = read_next_raw_block(bytes, end, header, pos, total)? else {
+        return Ok(false);
+    };
+    block
+        .ingest_block(&raw.header, raw.data, header)
+        .map_err(|e| {
+            e.with_context(block_context(
+                offset,
+                first_index + block.n_records() as u64,
+            ))
+        })?;
+
+    // Update the block index
+    block.update_index(first_index);
+
+    Ok(true)
+}
+
+/// Returns the stored block starting at `*pos` of a file held in memory
+///
+/// Advances `*pos` past the block and `*total` by its number of records.
+/// Returns `None` if there are no more blocks before `end`. Errors are annotated with the
+/// offset of the block and the index of its first record.
+pub(crate) fn read_next_raw_block<'a>(
+    bytes: &'a [u8],
+    end: usize,
+    header: &VBinseqHeader,
+    pos: &mut usize,
+    total: &mut u64,
+) -> Result<Option<RawBlock<'a>>> {
+    // Validate the next block header is within bounds and present
+    if *pos + SIZE_BLOCK_HEADER > end {
+        return Ok(None);
+    }
+    let locate = |e: crate::Error| e.with_context(block_context(*pos, *total));
+    let mut header_bytes = [0u8; SIZE_BLOCK_HEADER];
+    header_bytes.copy_from_slice(&bytes[*pos..*pos + SIZE_BLOCK_HEADER]);
+    let mut block_header = BlockHeader::from_bytes(&header_bytes).map_err(locate)?;
+
+    // Read the block contents
+    let rbound = if header.compressed() {
+        block_header.size as usize
+    } else {
+        header.block() as usize
+    };
+    let data_start = *pos + SIZE_BLOCK_HEADER;
+    if rbound > end - data_start {
+        return Err(locate(ReadError::UnexpectedEndOfFile(data_start).into()));
+    }
+    let data = &bytes[data_start..data_start + rbound];
+
+    // Record the codec explicitly so the block is self-describing in any file
+    block_header.set_codec(
+        block_header
+            .codec()
+            .map_err(locate)?
+            .unwrap_or(header.codec()),
+    );
+
+    *pos = data_start + rbound;
+    *total += u64::from(block_header.records);
+
+    // The readers returning raw blocks fill in the dictionaries of their file
+    Ok(Some(RawBlock {
+        header: block_header,
+        file_header: *header,
+        dictionaries: &[],
+        data,
+    }))
+}
+
+/// Describes the location of the block at `offset` whose first record has the (0-based)
+/// index `record`
+fn block_context(offset: usize, record: u64) -> ErrorContext {
+    ErrorContext {
+        offset: Some(offset as u64),
+        record: Some(record),
+        ..Default::default()
+    }
+}
+
+/// A record block in its stored form
+///
+/// Raw blocks give access to the stored (possibly compressed) bytes of a block, so blocks
+/// can be copied between files without decoding or recompressing their records (see
+/// `VBinseqWriter::write_raw_block`).
+#[derive(Debug, Clone, Copy)]
+pub struct RawBlock<'a> {
+    /// Header of the block
+    ///
+    /// The codec of the block is always recorded, even if the block was written without
+    /// one and relied on the codec of the file header.
+    pub header: BlockHeader,
+
+    /// Header of the file the block was read from
+    pub file_header: VBinseqHeader,
+
+    /// Compression dictionaries of the file the block was read from
+    pub dictionaries: &'a [Vec<u8>],
+
+    /// Stored bytes of the block (without the block header)
+    pub data: &'a [u8],
+}
+
+/// Processes the records of a single block of a file held in memory
+///
+/// The block described by `range` is ingested into `block`, its records are passed to the
+/// processor with global indices starting at `first_index`, and the batch is completed.
+/// Errors reading the block are annotated with its offset and the index of the record
+/// being read, errors of the processor are returned as they are.
+pub(crate) fn process_block<P: ParallelProcessor>(
+    bytes: &[u8],
+    header: &VBinseqHeader,
+    range: &BlockRange,
+    first_index: u64,
+    block: &mut RecordBlock,
+    processor: &mut P,
+) -> Result<()> {
+    // Clear the block for reuse
+    block.clear();
+    ingest_range(bytes, header, range, block).map_err(|e| {
+        e.with_context(block_context(
+            range.start_offset as usize,
+            first_index + block.n_records() as u64,
+        ))
+    })?;
+
+    // Update the record block index
+    block.update_index(first_index);
+
+    // Process each record in the block
+    for record in block.iter() {
+        processor.process_record(record)?;
+    }
+
+    // Signal batch completion
+    processor.on_batch_complete()
+}
+
+/// Ingests the block described by `range` into `block`
+fn ingest_range(
+    bytes: &[u8],
+    header: &VBinseqHeader,
+    range: &BlockRange,
+    block: &mut RecordBlock,
+) -> Result<()> {
+    // Parse the block header and skip it to get to data
+    // The range may come from a stale or corrupted index, so check it lies within the file
+    let header_start = range.start_offset as usize;
+    let block_start = header_start.saturating_add(SIZE_BLOCK_HEADER);
+    let Some(block_data) = block_start
+        .checked_add(range.len as usize)
+        .and_then(|block_end| bytes.get(block_start..block_end))
+    else {
+        return Err(ReadError::UnexpectedEndOfFile(header_start).into());
+    };
+    let block_header = {
+        let mut header_bytes = [0u8; SIZE_BLOCK_HEADER];
+        header_bytes.copy_from_slice(&bytes[header_start..block_start]);
+        BlockHeader::from_bytes(&header_bytes)?
+    };
+
+    // Ingest data according to the block codec
+    block.ingest_block(&block_header, block_data, header)
+}
+
+/// Memory-mapped reader for VBINSEQ files
+///
+/// `MmapReader` provides efficient, memory-mapped access to VBINSEQ files. It allows
+/// sequential reading of record blocks and supports parallel processing of records.
+///
+/// Memory mapping allows the operating system to lazily load file contents as needed,
+/// which can be more efficient than standard file I/O, especially for large files.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use vbinseq::MmapReader;
+///
+/// // Open a VBINSEQ file
+/// let mut reader = MmapReader::new("example.vbq").unwrap();
+///
+/// // Create a block to hold records
+/// let mut block = reader.new_block();
+///
+/// // Read blocks sequentially
+/// while reader.read_block_into(&mut block).unwrap() {
+///     println!("Read a block with {} records", block.n_records());
+///     // Process records...
+/// }
+/// ```
+#[cfg(feature = "mmap")]
+pub struct MmapReader {
+    /// Path to the VBINSEQ file
+    path: PathBuf,
+
+    /// Memory-mapped file contents for efficient access
+    mmap: Arc<Mmap>,
+
+    /// Parsed header information from the file
+    header: VBinseqHeader,
+
+    /// Footer of the file (if written with one)
+    footer: Option<Footer>,
+
+    /// Compression dictionaries of the file (see the `dictionary` module)
+    dictionaries: Arc<[Vec<u8>]>,
+
+    /// Position of the header of the current member of a concatenated file (in bytes)
+    member: usize,
+
+    /// Position in the file where the record blocks end (in bytes)
+    end: usize,
+
+    /// Current cursor position in the file (in bytes)
+    pos: usize,
+
+    /// Total number of records read from the file so far
+    total: u64,
+
+    /// Index of the next block
+    /// None after jumping to a block by its range, where the index is not known
+    block_index: Option<usize>,
+
+    /// When the index of the file is rebuilt instead of loaded
+    index_policy: IndexPolicy,
+
+    /// How structural anomalies are handled
+    recovery: Recovery,
+
+    /// Whether the checksums of blocks are verified (in files with block checksums)
+    verify_checksums: bool,
+
+    /// Whether `load_index` requires the index to hold record offsets
+    record_index: bool,
+
+    /// Options the file is mapped with
+    options: MapOptions,
+
+    /// Options for following the file as it grows
+    /// If None, reading stops at the end of the file
+    follow: Option<FollowOptions>,
+}
+#[cfg(feature = "mmap")]
+impl MmapReader {
+    /// Creates a new `MmapReader` for a VBINSEQ file
+    ///
+    /// This method opens the specified file, memory-maps its contents, and reads the
+    /// VBINSEQ header information. The reader is positioned at the beginning of the first
+    /// record block after the header.
+    ///
+    /// # Parameters
+    ///
+    /// * `path` - Path to the VBINSEQ file to open
+    ///
+    /// # Returns
+    ///
+    /// A new `MmapReader` instance if successful
+    ///
+    /// # Errors
+    ///
+    /// * `ReadError::InvalidFileType` if the path doesn't point to a regular file
+    /// * `ReadError::EmptyFile` if the file is empty
+    /// * I/O errors if the file can't be opened or memory-mapped
+    /// * Header validation errors if the file doesn't contain a valid VBINSEQ header
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use vbinseq::MmapReader;
+    ///
+    /// let reader = MmapReader::new("path/to/file.vbq").unwrap();
+    /// ```
+    pub fn new<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::with_options(path, &MapOptions::default())
+    }
+
+    /// Creates a new `MmapReader` for a VBINSEQ file with custom mapping options
+    ///
+    /// This behaves like `MmapReader::new` but maps the file with the given options, e.g.
+    /// to pre-populate the mapping of a file that is about to be read in full.
+    ///
+    /// # Parameters
+    ///
+    /// * `path` - Path to the VBINSEQ file to open
+    /// * `options` - Options for memory-mapping the file
+    ///
+    /// # Returns
+    ///
+    /// A new `MmapReader` instance if successful
+    ///
+    /// # Errors
+    ///
+    /// * `ReadError::InvalidFileType` if the path doesn't point to a regular file
+    /// * `ReadError::EmptyFile` if the file is empty
+    /// * I/O errors if the file can't be opened or memory-mapped
+    /// * Header validation errors if the file doesn't contain a valid VBINSEQ header
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use vbinseq::{MapOptions, MmapReader};
+    ///
+    /// let options = MapOptions {
+    ///     populate: true,
+    ///     ..Default::default()
+    /// };
+    /// let reader = MmapReader::with_options("path/to/file.vbq", &options).unwrap();
+    /// ```
+    pub fn with_options<P: AsRef<Path>>(path: P, options: &MapOptions) -> Result<Self> {
+        let locate = |e: crate::Error| e.with_context(ErrorContext::for_path(path.as_ref()));
+
+        // Verify it's a regular file and map it
+        let mmap = map_file(&open_regular_file(&path).map_err(locate)?, options).map_err(locate)?;
+
+        // Read header from mapped memory and locate the end of the record blocks
+        let (header, footer, end) = parse_file_layout(&mmap).map_err(locate)?;
+        let dictionaries = dictionary::load(&mmap, &header).map_err(locate)?;
+
+        Ok(Self {
+            path: PathBuf::from(path.as_ref()),
+            mmap: Arc::new(mmap),
+            header,
+            footer,
+            dictionaries,
+            member: 0,
+            end,
+            pos: header.data_offset(),
+            total: 0,
+            block_index: Some(0),
+            index_policy: IndexPolicy::default(),
+            recovery: Recovery::default(),
+            verify_checksums: false,
+            record_index: false,
+            options: *options,
+            follow: None,
+        })
+    }
+
+    /// Creates a new `MmapReader` restricted to a byte range of a VBINSEQ file
+    ///
+    /// The range must start at a block header and end after the last byte of a block, as
+    /// the ranges of `BlockIndex::split_points` do. The reader reads the blocks of the range
+    /// like a complete file, with record indices counted from the start of the file, so
+    /// distributed workers can each process their own slice of a file. The index of the
+    /// file is loaded (or built) according to the default `IndexPolicy` to locate the range.
+    /// Methods that work on the whole file through its index (e.g. `process_parallel`) are
+    /// not restricted to the range (see `process_parallel_range` instead).
+    ///
+    /// # Parameters
+    ///
+    /// * `path` - Path to the VBINSEQ file to open
+    /// * `offset` - Offset of the first block of the range (in bytes)
+    /// * `len` - Length of the range (in bytes)
+    ///
+    /// # Returns
+    ///
+    /// A new `MmapReader` instance if successful
+    ///
+    /// # Errors
+    ///
+    /// * `IndexError::UnalignedRange` if the range is not aligned to the blocks of the file
+    /// * Any error of `MmapReader::new` or `MmapReader::load_index`
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use vbinseq::MmapReader;
+    ///
+    /// // Divide the file among 4 workers and read the slice of the first one
+    /// let index = MmapReader::new("example.vbq").unwrap().load_index().unwrap();
+    /// let (offset, len) = index.split_points(4)[0];
+    /// let mut reader = MmapReader::with_range("example.vbq", offset, len).unwrap();
+    /// let mut block = reader.new_block();
+    /// while reader.read_block_into(&mut block).unwrap() {
+    ///     println!("Read a block with {} records", block.n_records());
+    /// }
+    /// ```
+    pub fn with_range<P: AsRef<Path>>(path: P, offset: u64, len: u64) -> Result<Self> {
+        let mut reader = Self::new(&path)?;
+        let locate = |e: crate::Error| e.with_context(ErrorContext::for_path(path.as_ref()));
+        let index = reader.load_index().map_err(locate)?;
+        let ranges = index.ranges();
+        let first = ranges.partition_point(|range| range.start_offset < offset);
+        let last = ranges.partition_point(|range| range.start_offset < offset + len);
+        let aligned = len > 0
+            && ranges
+                .get(first)
+                .is_some_and(|range| range.start_offset == offset)
+            && ranges[last - 1].start_offset + SIZE_BLOCK_HEADER as u64 + ranges[last - 1].len
+                == offset + len;
+        if !aligned {
+            return Err(locate(IndexError::UnalignedRange(offset, len).into()));
+        }
+        reader.pos = offset as usize;
+        reader.end = (offset + len) as usize;
+        reader.total = ranges[first].cumulative_records;
+        reader.block_index = Some(first);
+        Ok(reader)
+    }
+
+    /// Creates a new `MmapReader` following a VBINSEQ file that is still being written
+    ///
+    /// When `read_block_into` reaches the end of the file, the reader waits for the writer
+    /// to append more blocks instead of returning `false` (see `FollowOptions`). The file
+    /// is remapped whenever it grows, so consumers can process records while they are
+    /// being written, e.g. by a sequencing pipeline with an auto-flushing writer (see
+    /// `VBinseqWriterBuilder::flush_interval`).
+    ///
+    /// Reading ends once the writer has written the footer of the file (if the file has
+    /// one) or once the file did not grow for the timeout of the options. Incomplete
+    /// blocks at the end of the file are retried until the timeout, after which the error
+    /// is returned. Only `read_block_into` follows the file, other methods see the file as
+    /// of the last refresh (see `refresh`).
+    ///
+    /// # Parameters
+    ///
+    /// * `path` - Path to the VBINSEQ file to follow
+    /// * `options` - When to check for and when to stop waiting for new blocks
+    ///
+    /// # Returns
+    ///
+    /// A new `MmapReader` instance if successful
+    ///
+    /// # Errors
+    ///
+    /// * `ReadError::InvalidFileType` if the path doesn't point to a regular file
+    /// * `ReadError::EmptyFile` if the file is empty
+    /// * I/O errors if the file can't be opened or memory-mapped
+    /// * Header validation errors if the file doesn't (yet) contain a valid VBINSEQ header
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use vbinseq::{FollowOptions, MmapReader};
+    ///
+    /// let mut reader = MmapReader::follow("live.vbq", FollowOptions::default()).unwrap();
+    /// let mut block = reader.new_block();
+    ///
+    /// // Returns false once the writer has finished the file
+    /// while reader.read_block_into(&mut block).unwrap() {
+    ///     println!("Read a block with {} records", block.n_records());
+    /// }
+    /// ```
+    pub fn follow<P: AsRef<Path>>(path: P, options: FollowOptions) -> Result<Self> {
+        let locate = |e: crate::Error| e.with_context(ErrorContext::for_path(path.as_ref()));
+        let map_options = MapOptions::default();
+        let mmap =
+            map_file(&open_regular_file(&path).map_err(locate)?, &map_options).map_err(locate)?;
+        let (header, footer, end) = parse_growing_layout(&mmap).map_err(locate)?;
+        let dictionaries = dictionary::load(&mmap, &header).map_err(locate)?;
+        Ok(Self {
+            path: PathBuf::from(path.as_ref()),
+            mmap: Arc::new(mmap),
+            header,
+            footer,
+            dictionaries,
+            member: 0,
+            end,
+            pos: header.data_offset(),
+            total: 0,
+            block_index: Some(0),
+            index_policy: IndexPolicy::default(),
+            recovery: Recovery::default(),
+            verify_checksums: false,
+            record_index: false,
+            options: map_options,
+            follow: Some(options),
+        })
+    }
+
+    /// Remaps the file if it has grown since it was mapped
+    ///
+    /// This picks up blocks appended after the reader was created. The reader keeps its
+    /// position, so subsequent reads continue with the new blocks. Readers created with
+    /// `MmapReader::follow` refresh automatically when they reach the end of the file.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(true)` - If the file has grown and was remapped
+    /// * `Ok(false)` - If the file has not changed in size
+    ///
+    /// # Errors
+    ///
+    /// * I/O errors if the file can't be opened or memory-mapped
+    /// * Footer validation errors if the file is not followed and its footer is invalid
+    pub fn refresh(&mut self) -> Result<bool> {
+        let file = open_regular_file(&self.path)?;
+        if file.metadata()?.len() == self.mmap.len() as u64 {
+            return Ok(false);
+        }
+        let mmap = map_file(&file, &self.options)?;
+        let (_, footer, end) = if self.follow.is_some() {
+            parse_growing_layout(&mmap)?
+        } else {
+            parse_file_layout(&mmap)?
+        };
+        self.mmap = Arc::new(mmap);
+        self.footer = footer;
+        self.end = end;
+        Ok(true)
+    }
+
+    /// Creates a new empty record block with the appropriate size for this file
+    ///
+    /// This creates a `RecordBlock` with a block size matching the one specified in the
+    /// file's header, ensuring it will be able to hold a full block of records.
+    ///
+    /// # Returns
+    ///
+    /// A new empty `RecordBlock` instance sized appropriately for this file
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use vbinseq::MmapReader;
+    ///
+    /// let reader = MmapReader::new("example.vbq").unwrap();
+    /// let mut block = reader.new_block();
+    /// ```
+    pub fn new_block(&self) -> RecordBlock {
+        RecordBlock::new(self.header.block() as usize)
+    }
+
+    /// Converts the reader into an iterator over the decoded records of the file
+    ///
+    /// The iterator reads the remaining blocks of the file sequentially, reusing a single
+    /// block internally, and yields every record as an `OwnedRecord`. This is the simplest
+    /// way to scan a whole file; use `read_block_into` to avoid decoding records into owned
+    /// buffers, or `process_parallel` to process blocks on multiple threads.
+    ///
+    /// The iterator ends after yielding the first error.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use vbinseq::MmapReader;
+    ///
+    /// let reader = MmapReader::new("example.vbq").unwrap();
+    /// for record in reader.into_records() {
+    ///     let record = record.unwrap();
+    ///     println!("{}", std::str::from_utf8(record.seq()).unwrap());
+    /// }
+    /// ```
+    pub fn into_records(self) -> Records {
+        Records {
+            block: self.new_block(),
+            reader: self,
+            records: Vec::new().into_iter(),
+            finished: false,
+        }
+    }
+
+    /// Returns the path where the index file would be located
+    ///
+    /// The index file is used for random access to blocks and has the same path as
+    /// the VBINSEQ file with the ".vqi" extension appended.
+    ///
+    /// # Returns
+    ///
+    /// The path where the index file would be located
+    pub fn index_path(&self) -> PathBuf {
+        let mut p = self.path.as_os_str().to_owned();
+        p.push(".vqi");
+        p.into()
+    }
+
+    /// Returns a copy of the file's header information
+    ///
+    /// The header contains information about the file format, including whether
+    /// quality scores are included, whether blocks are compressed, and whether
+    /// records are paired. In concatenated files, this is the header of the member
+    /// currently being read.
+    ///
+    /// # Returns
+    ///
+    /// A copy of the file's `VBinseqHeader`
+    pub fn header(&self) -> VBinseqHeader {
+        self.header
+    }
+
+    /// Parses the header sections of the file (e.g. the read-group table)
+    ///
+    /// # Errors
+    ///
+    /// * `HeaderError::InvalidSection` if the sections cannot be parsed
+    pub fn sections(&self) -> Result<HeaderSections> {
+        HeaderSections::from_file_bytes(&self.mmap[self.member..], &self.header)
+    }
+
+    /// Returns the user-defined key/value metadata of the file (e.g. its provenance)
+    ///
+    /// This is the metadata section of the header sections (see `HeaderSections::metadata`),
+    /// which is empty for files written without metadata.
+    ///
+    /// # Errors
+    ///
+    /// * `HeaderError::InvalidSection` if the sections cannot be parsed
+    pub fn metadata(&self) -> Result<Vec<(String, String)>> {
+        Ok(self.sections()?.metadata)
+    }
+
+    /// Scans the preambles (index, flag, and lengths) of all records of the file
+    ///
+    /// The scan is independent of the position of the reader and skips the payloads of the
+    /// records (see the `scan` module).
+    ///
+    /// # Errors
+    ///
+    /// * Header and footer validation errors if the file cannot be parsed
+    pub fn preambles(&self) -> Result<Preambles<'_>> {
+        Preambles::new(&self.mmap)
+    }
+
+    /// Returns the locations (block, offset, and size) of all records of the file
+    ///
+    /// See `scan::RecordLocation` for how records are addressed.
+    ///
+    /// # Errors
+    ///
+    /// * Header and footer validation errors if the file cannot be parsed
+    pub fn locations(&self) -> Result<Locations<'_>> {
+        Locations::new(&self.mmap)
+    }
+
+    /// Returns the footer of the file
+    ///
+    /// Returns `None` if the file was written without a footer
+    /// (see `VBinseqHeader::set_footer`).
+    pub fn footer(&self) -> Option<Footer> {
+        self.footer
+    }
+
+    /// Fills an existing RecordBlock with the next block of records from the file
+    ///
+    /// This method reads the next block of records from the current position in the file
+    /// and populates the provided `RecordBlock` with the data. The block is cleared and reused
+    /// to avoid unnecessary memory allocations. This is the primary method for sequential
+    /// reading of VBINSEQ files.
+    ///
+    /// The method automatically handles decompression if the file was written with
+    /// compression enabled and updates the total record count as it progresses through the file.
+    ///
+    /// # Parameters
+    ///
+    /// * `block` - A mutable reference to a `RecordBlock` to be filled with data
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(true)` - If a block was successfully read
+    /// * `Ok(false)` - If the end of the file was reached (no more blocks), including
+    ///   immediately for files holding only a header
+    /// * `Err(_)` - If an error occurred during reading
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use vbinseq::MmapReader;
+    /// use std::io::Write;
+    ///
+    /// let mut reader = MmapReader::new("example.vbq").unwrap();
+    /// let mut block = reader.new_block();
+    /// let mut sequence_buffer = Vec::new();
+    ///
+    /// // Read blocks until the end of file
+    /// while reader.read_block_into(&mut block).unwrap() {
+    ///     println!("Read block with {} records", block.n_records());
+    ///
+    ///     // Process each record
+    ///     for record in block.iter() {
+    ///         // Decode the nucleotide sequence
+    ///         record.decode_s(&mut sequence_buffer).unwrap();
+    ///
+    ///         // Do something with the sequence
+    ///         println!("Record {}: length {}", record.index(), sequence_buffer.len());
+    ///         sequence_buffer.clear();
+    ///     }
+    /// }
+    /// ```
+    pub fn read_block_into(&mut self, block: &mut RecordBlock) -> Result<bool> {
+        match self.read_next(block) {
+            Ok(found) => {
+                if found {
+                    self.block_index = self.block_index.map(|index| index + 1);
+                }
+                Ok(found)
+            }
+            Err(e) => Err(e.with_context(self.context())),
+        }
+    }
+
+    /// Describes the location of the next block for errors
+    fn context(&self) -> ErrorContext {
+        ErrorContext {
+            path: Some(self.path.clone()),
+            block: self.block_index,
+            ..Default::default()
+        }
+    }
+
+    /// Fills a block with the next block of the file, following the file if requested
+    fn read_next(&mut self, block: &mut RecordBlock) -> Result<bool> {
+        let Some(follow) = self.follow else {
+            self.advance_member()?;
+            block.set_dictionaries(&self.dictionaries);
+            block.set_verify_checksums(self.verify_checksums);
+            let skipped = self.recovery.counts.blocks;
+            let status = self.recovery.read_next_block(
+                &self.mmap,
+                self.end,
+                &self.header,
+                &mut self.pos,
+                &mut self.total,
+                block,
+            );
+            if self.recovery.counts.blocks != skipped {
+                self.block_index = None;
+            }
+            return status;
+        };
+
+        block.set_dictionaries(&self.dictionaries);
+        block.set_verify_checksums(self.verify_checksums);
+        let mut last_growth = Instant::now();
+        loop {
+            let (pos, total) = (self.pos, self.total);
+            let status = read_next_block(
+                &self.mmap,
+                self.end,
+                &self.header,
+                &mut self.pos,
+                &mut self.total,
+                block,
+            );
+            match status {
+                Ok(true) => return Ok(true),
+                // The footer is only written once the file is complete
+                _ if self.footer.is_some() => return status,
+                _ => {
+                    // Retry incomplete blocks at the end of the file once they are written
+                    self.pos = pos;
+                    self.total = total;
+                }
+            }
+            if follow
+                .timeout
+                .is_some_and(|timeout| last_growth.elapsed() >= timeout)
+            {
+                block.clear();
+                return status;
+            }
+            std::thread::sleep(follow.poll_interval);
+            if self.refresh()? {
+                last_growth = Instant::now();
+            }
+        }
+    }
+
+    /// Continues with the next member of a concatenated file if one starts at the cursor
+    ///
+    /// See `MemoryReader::advance_member`.
+    fn advance_member(&mut self) -> Result<()> {
+        // Readers of a byte range end at the range, even if a member follows it
+        while self.pos < self.end {
+            let Some(member) = next_member(&self.mmap, self.pos)? else {
+                break;
+            };
+            self.member = member.start;
+            self.header = member.header;
+            self.dictionaries = member.dictionaries;
+            self.end = member.end;
+            self.pos = member.start + member.header.data_offset();
+        }
+        Ok(())
+    }
+
+    /// Reads the block of a range directly, without reading the blocks before it
+    ///
+    /// Record indices of the block are set from the cumulative record count of the range.
+    /// Subsequent calls to `read_block_into` continue with the following block.
+    ///
+    /// # Parameters
+    ///
+    /// * `range` - The range of the block (from the index of this file)
+    /// * `block` - The block to fill
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(true)` - If the block was successfully read
+    /// * `Ok(false)` - If the range starts past the last block of the file
+    /// * `Err(_)` - If an error occurred during reading
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use vbinseq::MmapReader;
+    ///
+    /// let mut reader = MmapReader::new("example.vbq").unwrap();
+    /// let index = reader.load_index().unwrap();
+    /// let mut block = reader.new_block();
+    ///
+    /// // Read only the last block of the file
+    /// if let Some(range) = index.ranges().last() {
+    ///     reader.read_block_at(range, &mut block).unwrap();
+    /// }
+    /// ```
+    pub fn read_block_at(&mut self, range: &BlockRange, block: &mut RecordBlock) -> Result<bool> {
+        self.pos = range.start_offset as usize;
+        self.total = range.cumulative_records;
+        self.block_index = None;
+        self.read_block_into(block)
+    }
+
+    /// Reads a single record by its index in the file
+    ///
+    /// The block holding the record is located with the index, and only the record is
+    /// decoded into the block. With record offsets in the index (see `set_record_index`),
+    /// the record is sliced from its decompressed block directly; otherwise the records
+    /// before it in its block are skipped by their sizes. Either way, only the block of
+    /// the record is decompressed.
+    ///
+    /// Unlike `read_block_at`, this doesn't move the reader, and block checksums are not
+    /// verified, as only part of the block is decoded.
+    ///
+    /// # Parameters
+    ///
+    /// * `index` - The index of this file (see `load_index`)
+    /// * `record` - The index of the record in the file
+    /// * `block` - The block to fill with the record
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(true)` - If the record was read
+    /// * `Ok(false)` - If the file has no record with the index
+    /// * `Err(_)` - If the index doesn't match the file or the block is corrupt
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use vbinseq::MmapReader;
+    ///
+    /// let mut reader = MmapReader::new("example.vbq").unwrap();
+    /// reader.set_record_index(true);
+    /// let index = reader.load_index().unwrap();
+    /// let mut block = reader.new_block();
+    ///
+    /// if reader.seek_record(&index, 1_000_000, &mut block).unwrap() {
+    ///     let record = block.iter().next().unwrap();
+    ///     println!("Record {} has flag {}", record.index(), record.flag());
+    /// }
+    /// ```
+    pub fn seek_record(
+        &self,
+        index: &BlockIndex,
+        record: u64,
+        block: &mut RecordBlock,
+    ) -> Result<bool> {
+        let member = first_member(&self.mmap, self.member, &self.header, &self.dictionaries)?;
+        read_record_at(&self.mmap, &member, index, record, block)
+            .map_err(|e| e.with_context(self.context()))
+    }
+
+    /// Returns the next block in its stored form
+    ///
+    /// This advances the reader like `read_block_into` without decompressing or parsing
+    /// the block, which is useful to copy blocks between files verbatim.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Some(RawBlock))` - The next block
+    /// * `Ok(None)` - If there are no more blocks
+    ///
+    /// # Errors
+    ///
+    /// * `ReadError::InvalidBlockMagicNumber` if the block header is invalid
+    /// * `ReadError::UnexpectedEndOfFile` if the block extends beyond the end of the file
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use vbinseq::{MmapReader, VBinseqWriterBuilder};
+    /// use std::fs::File;
+    ///
+    /// // Concatenate two files without recompressing their blocks
+    /// let first = MmapReader::new("first.vbq").unwrap();
+    /// let mut writer = VBinseqWriterBuilder::default()
+    ///     .header(first.header())
+    ///     .build(File::create("both.vbq").unwrap())
+    ///     .unwrap();
+    /// for mut reader in [first, MmapReader::new("second.vbq").unwrap()] {
+    ///     while let Some(block) = reader.next_raw_block().unwrap() {
+    ///         writer.write_raw_block(&block).unwrap();
+    ///     }
+    /// }
+    /// writer.finish().unwrap();
+    /// ```
+    pub fn next_raw_block(&mut self) -> Result<Option<RawBlock<'_>>> {
+        let context = self.context();
+        if self.follow.is_none() {
+            self.advance_member()
+                .map_err(|e| e.with_context(context.clone()))?;
+        }
+        let raw = read_next_raw_block(
+            &self.mmap,
+            self.end,
+            &self.header,
+            &mut self.pos,
+            &mut self.total,
+        )
+        .map_err(|e| e.with_context(context))?;
+        if raw.is_some() {
+            self.block_index = self.block_index.map(|index| index + 1);
+        }
+        Ok(raw.map(|raw| RawBlock {
+            dictionaries: &self.dictionaries,
+            ..raw
+        }))
+    }
+
+    /// Returns the block of a range in its stored form, without reading the blocks before it
+    ///
+    /// Like `read_block_at`, this moves the reader to the block, so subsequent calls to
+    /// `next_raw_block` continue with the blocks following it.
+    ///
+    /// # Parameters
+    ///
+    /// * `range` - The range of the block (from the index of this file)
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Some(RawBlock))` - The block of the range
+    /// * `Ok(None)` - If the range starts past the last block of the file
+    ///
+    /// # Errors
+    ///
+    /// * `ReadError::InvalidBlockMagicNumber` if the range doesn't point to a block header
+    /// * `ReadError::UnexpectedEndOfFile` if the block extends beyond the end of the file
+    pub fn raw_block_at(&mut self, range: &BlockRange) -> Result<Option<RawBlock<'_>>> {
+        self.pos = range.start_offset as usize;
+        self.total = range.cumulative_records;
+        self.block_index = None;
+        self.next_raw_block()
+    }
+
+    /// Loads or creates the block index for this VBINSEQ file
+    ///
+    /// The block index provides metadata about each block in the file, enabling
+    /// random access to blocks and parallel processing. By default, this method first
+    /// attempts to load an existing index file. If the index doesn't exist or doesn't match
+    /// the current file, it automatically generates a new index from the VBINSEQ file
+    /// and saves it for future use. The index policy of the reader controls when the
+    /// index is rebuilt (see `set_index_policy`).
+    ///
+    /// # Returns
+    ///
+    /// The loaded or newly created `BlockIndex` if successful
+    ///
+    /// # Errors
+    ///
+    /// * File I/O errors when reading or creating the index
+    /// * Parsing errors if the VBINSEQ file has invalid format
+    /// * `IndexError::MissingIndexFile` if the policy is `IndexPolicy::RequireValid` and
+    ///   the index file is missing
+    /// * `IndexError::ByteSizeMismatch`, `IndexError::RecordCountMismatch`, or
+    ///   `IndexError::InvalidBlockOffset` if the policy is `IndexPolicy::RequireValid` and
+    ///   the index file is stale (see `check_index`)
+    /// * Other index-related errors (e.g. a corrupt index file) unless the policy is
+    ///   `IndexPolicy::AlwaysRebuild`
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use vbinseq::MmapReader;
+    ///
+    /// let reader = MmapReader::new("example.vbq").unwrap();
+    ///
+    /// // Load the index file (or create if it doesn't exist)
+    /// let index = reader.load_index().unwrap();
+    ///
+    /// // Use the index to get information about the file
+    /// println!("Number of blocks: {}", index.n_blocks());
+    /// ```
+    ///
+    /// # Notes
+    ///
+    /// The index file is stored with the same path as the VBINSEQ file but with a ".vqi"
+    /// extension appended. This allows for reusing the index across multiple runs,
+    /// which can significantly improve startup performance for large files.
+    ///
+    /// Files written with an embedded index (see `VBinseqHeader::set_embedded_index`) need
+    /// no index file: their index is read from the file itself, unless the policy is
+    /// `IndexPolicy::AlwaysRebuild` or the index lacks record offsets required by
+    /// `set_record_index`. An embedded index that doesn't match the file is handled like
+    /// a stale index file.
+    pub fn load_index(&self) -> Result<BlockIndex> {
+        if self.index_policy != IndexPolicy::AlwaysRebuild {
+            match self.load_embedded_index() {
+                Ok(Some(index)) => return Ok(index),
+                Ok(None) => {}
+                Err(e) if self.index_policy == IndexPolicy::RequireValid => return Err(e),
+                Err(_) => {}
+            }
+        }
+        let index_path = self.index_path();
+        match self.index_policy {
+            IndexPolicy::AlwaysRebuild => self.rebuild_index(),
+            _ if !index_path.exists() => {
+                if self.index_policy == IndexPolicy::RequireValid {
+                    let path = index_path.to_string_lossy().to_string();
+                    return Err(IndexError::MissingIndexFile(path).into());
+                }
+                self.rebuild_index()
+            }
+            IndexPolicy::RequireValid => self.load_checked_index(),
+            IndexPolicy::RebuildOnMismatch => match self.load_checked_index() {
+                Err(e) if e.is_index_mismatch() => self.rebuild_index(),
+                status => status,
+            },
+        }
+    }
+
+    /// Loads the embedded index of the file and checks it against the file
+    ///
+    /// Returns `None` if the file has no embedded index, or if record offsets are required
+    /// but the embedded index has none.
+    fn load_embedded_index(&self) -> Result<Option<BlockIndex>> {
+        if !crate::footer::read_header(&self.mmap)?.has_embedded_index() {
+            return Ok(None);
+        }
+        let index = BlockIndex::from_embedded(&self.mmap)?;
+        self.check_index(&index)?;
+        if self.record_index && !index.has_record_offsets() {
+            return Ok(None);
+        }
+        Ok(Some(index))
+    }
+
+    /// Loads the index file and checks it against the file
+    fn load_checked_index(&self) -> Result<BlockIndex> {
+        let index = BlockIndex::from_path(self.index_path())?;
+        self.check_index(&index)?;
+        if self.record_index && !index.has_record_offsets() {
+            return Err(IndexError::MissingRecordOffsets.into());
+        }
+        Ok(index)
+    }
+
+    /// Checks that the record counts of an index agree with the file
+    ///
+    /// Index files of other versions of the file (e.g. written by older tools or for a
+    /// rewritten file of the same size) would misalign the global record indices. This
+    /// catches them without scanning the whole file: the total record count is compared
+    /// to the footer (if the file has one), and the first and last block ranges are
+    /// compared to their block headers.
+    ///
+    /// # Parameters
+    ///
+    /// * `index` - The index to check
+    ///
+    /// # Errors
+    ///
+    /// * `IndexError::RecordCountMismatch` if a record count of the index disagrees with
+    ///   the footer or a block header
+    /// * `IndexError::InvalidBlockOffset` if a block range doesn't point to a block header
+    pub fn check_index(&self, index: &BlockIndex) -> Result<()> {
+        if let Some(footer) = self.footer {
+            if index.n_records() != footer.n_records {
+                return Err(IndexError::RecordCountMismatch(
+                    "records".to_string(),
+                    index.n_records(),
+                    footer.n_records,
+                )
+                .into());
+            }
+        }
+        let ranges = index.ranges();
+        let last = ranges.len().checked_sub(1).filter(|&i| i > 0);
+        for i in ranges.first().map(|_| 0).into_iter().chain(last) {
+            let range = &ranges[i];
+            let start = range.start_offset as usize;
+            let block_header = start
+                .checked_add(SIZE_BLOCK_HEADER)
+                .filter(|&end| end <= self.end)
+                .and_then(|end| {
+                    BlockHeader::from_bytes(self.mmap[start..end].try_into().ok()?).ok()
+                })
+                .ok_or(IndexError::InvalidBlockOffset(range.start_offset))?;
+            if block_header.records != range.block_records {
+                return Err(IndexError::RecordCountMismatch(
+                    format!("records in block {i}"),
+                    u64::from(range.block_records),
+                    u64::from(block_header.records),
+                )
+                .into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the policy deciding when `load_index` rebuilds the index
+    pub fn index_policy(&self) -> IndexPolicy {
+        self.index_policy
+    }
+
+    /// Returns how the reader handles structural anomalies of the file
+    pub fn parse_mode(&self) -> ParseMode {
+        self.recovery.mode
+    }
+
+    /// Sets how `read_block_into` and `read_block_at` handle structural anomalies
+    ///
+    /// The default mode is `ParseMode::Strict`. See the `recovery` module for what is
+    /// skipped in `ParseMode::Permissive`.
+    /// Readers following a file (see `MmapReader::follow`) always parse strictly, as
+    /// incomplete blocks at the end of the file are expected there.
+    ///
+    /// # Parameters
+    ///
+    /// * `mode` - The parse mode to use
+    pub fn set_parse_mode(&mut self, mode: ParseMode) {
+        self.recovery.mode = mode;
+    }
+
+    /// Sets a callback receiving every anomaly skipped in permissive mode
+    ///
+    /// # Parameters
+    ///
+    /// * `callback` - Called with the error describing the anomaly
+    pub fn set_warning_callback<F: FnMut(&Error) + Send + 'static>(&mut self, callback: F) {
+        self.recovery.callback = Some(Box::new(callback));
+    }
+
+    /// Returns the counts of the anomalies skipped in permissive mode so far
+    pub fn skip_counts(&self) -> SkipCounts {
+        self.recovery.counts
+    }
+
+    /// Sets when `load_index` (and thus `process_parallel`) rebuilds the index
+    ///
+    /// The default policy is `IndexPolicy::RebuildOnMismatch`.
+    ///
+    /// # Parameters
+    ///
+    /// * `policy` - The policy to use
+    pub fn set_index_policy(&mut self, policy: IndexPolicy) {
+        self.index_policy = policy;
+    }
+
+    /// Returns whether the checksums of blocks are verified
+    pub fn verify_checksums(&self) -> bool {
+        self.verify_checksums
+    }
+
+    /// Sets whether `read_block_into` and `process_parallel` verify the checksums of blocks
+    ///
+    /// Verification is disabled by default and only applies to files with block checksums
+    /// (see `VBinseqHeader::set_block_checksum`). Blocks not matching their checksum fail
+    /// to read with `ReadError::BlockChecksumMismatch`.
+    ///
+    /// # Parameters
+    ///
+    /// * `verify` - Whether to verify the checksums
+    pub fn set_verify_checksums(&mut self, verify: bool) {
+        self.verify_checksums = verify;
+    }
+
+    /// Returns whether `load_index` requires the index to hold record offsets
+    pub fn record_index(&self) -> bool {
+        self.record_index
+    }
+
+    /// Sets whether `load_index` builds indexes with record offsets
+    ///
+    /// Record offsets locate every record in its decompressed block, so `seek_record`
+    /// slices records from their blocks without skipping the records before them. They
+    /// are stored in the index file (4 bytes per record), so this is disabled by default.
+    /// Index files without record offsets are rebuilt with them, unless the index policy
+    /// is `IndexPolicy::RequireValid`, in which case `load_index` fails with
+    /// `IndexError::MissingRecordOffsets`.
+    ///
+    /// # Parameters
+    ///
+    /// * `record_index` - Whether the index holds record offsets
+    pub fn set_record_index(&mut self, record_index: bool) {
+        self.record_index = record_index;
+    }
+
+    /// Builds the index of the file by scanning its blocks and saves it
+    fn rebuild_index(&self) -> Result<BlockIndex> {
+        let mut index = BlockIndex::from_vbq(&self.path)?;
+        if self.record_index {
+            index.add_record_offsets(&self.mmap)?;
+        }
+        index.save_to_path(self.index_path())?;
+        Ok(index)
+    }
+}
+
+/// Iterator over the decoded records of a file
+///
+/// Created by `MmapReader::into_records`.
+#[cfg(feature = "mmap")]
+pub struct Records {
+    /// The reader of the file
+    reader: MmapReader,
+
+    /// Block reused for reading
+    block: RecordBlock,
+
+    /// Decoded records of the current block not yet yielded
+    records: std::vec::IntoIter<OwnedRecord>,
+
+    /// Whether the end of the file or an error was reached
+    finished: bool,
+}
+#[cfg(feature = "mmap")]
+impl Records {
+    /// Decodes the records of the next non-empty block
+    ///
+    /// Returns `Ok(false)` at the end of the file.
+    fn next_block(&mut self) -> Result<bool> {
+        while self.reader.read_block_into(&mut self.block)? {
+            if self.block.n_records() > 0 {
+                let records = self
+                    .block
+                    .iter()
+                    .map(|record| OwnedRecord::try_from(&record))
+                    .collect::<Result<Vec<_>>>()?;
+                self.records = records.into_iter();
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+}
+#[cfg(feature = "mmap")]
+impl Iterator for Records {
+    type Item = Result<OwnedRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(record) = self.records.next() {
+                return Some(Ok(record));
+            }
+            if self.finished {
+                return None;
+            }
+            match self.next_block() {
+                Ok(true) => {}
+                Ok(false) => self.finished = true,
+                Err(e) => {
+                    self.finished = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(feature = "mmap")]
+impl MmapReader {
+    /// Processes all records in the file in parallel using multiple threads
+    ///
+    /// This method provides efficient parallel processing of VBINSEQ files by distributing
+    /// blocks across multiple worker threads. The file's block structure is leveraged to divide
+    /// the work evenly without requiring thread synchronization during processing, which leads
+    /// to near-linear scaling with the number of threads.
+    ///
+    /// The method automatically loads or creates an index file to identify block boundaries,
+    /// then distributes the blocks among the requested number of threads. Each thread processes
+    /// its assigned blocks sequentially, but multiple blocks are processed in parallel across
+    /// threads.
+    ///
+    /// # Type Parameters
+    ///
+    /// * `P` - A type that implements the `ParallelProcessor` trait, which defines how records are processed
+    ///
+    /// # Parameters
+    ///
+    /// * `self` - Consumes the reader, as it will be used across multiple threads
+    /// * `processor` - An instance of a type implementing `ParallelProcessor` that will be cloned for each thread
+    /// * `num_threads` - Number of worker threads to use for processing (at least one)
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If all records were successfully processed
+    /// * `Err(_)` - If an error occurs during processing
+    ///
+    /// Files without blocks (e.g. from a writer finished without records) are processed
+    /// without spawning threads, so none of the processor's methods are called.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use vbinseq::{MmapReader, ParallelProcessor, RefRecord, Result};
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use std::sync::Arc;
+    ///
+    /// // Create a simple processor that counts records
+    /// struct RecordCounter {
+    ///     count: Arc<AtomicUsize>,
+    ///     thread_id: usize,
+    /// }
+    ///
+    /// impl RecordCounter {
+    ///     fn new() -> Self {
+    ///         Self {
+    ///             count: Arc::new(AtomicUsize::new(0)),
+    ///             thread_id: 0,
+    ///         }
+    ///     }
+    ///
+    ///     fn total_count(&self) -> usize {
+    ///         self.count.load(Ordering::Relaxed)
+    ///     }
+    /// }
+    ///
+    /// impl Clone for RecordCounter {
+    ///     fn clone(&self) -> Self {
+    ///         Self {
+    ///             count: Arc::clone(&self.count),
+    ///             thread_id: 0,
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// impl ParallelProcessor for RecordCounter {
+    ///     fn process_record(&mut self, _record: RefRecord) -> Result<()> {
+    ///         self.count.fetch_add(1, Ordering::Relaxed);
+    ///         Ok(())
+    ///     }
+    ///
+    ///     fn on_batch_complete(&mut self) -> Result<()> {
+    ///         // Optional: perform actions after each block is processed
+    ///         Ok(())
+    ///     }
+    ///
+    ///     fn set_tid(&mut self, tid: usize) {
+    ///         self.thread_id = tid;
+    ///     }
+    /// }
+    ///
+    /// // Use the processor with a VBINSEQ file
+    /// let reader = MmapReader::new("example.vbq").unwrap();
+    /// let counter = RecordCounter::new();
+    ///
+    /// // Process the file with 4 threads
+    /// reader.process_parallel(counter.clone(), 4).unwrap();
+    ///
+    /// // Get the total number of records processed
+    /// println!("Total records: {}", counter.total_count());
+    /// ```
+    ///
+    /// # Notes
+    ///
+    /// * The `ParallelProcessor` instance is cloned for each worker thread, so any shared state
+    ///   should be wrapped in thread-safe containers like `Arc`.
+    /// * The `set_tid` method is called with a unique thread ID before processing begins, which
+    ///   can be used to distinguish between worker threads.
+    /// * This method consumes the reader (takes ownership), as it's distributed across threads.
+    pub fn process_parallel<P: ParallelProcessor + Clone + 'static>(
+        self,
+        processor: P,
+        num_threads: usize,
+    ) -> Result<()> {
+        // Generate or load the index first
+        let index = self.load_index()?;
+        let blocks = 0..index.n_blocks();
+        self.process_blocks(processor, num_threads, &index, blocks)
+    }
+
+    /// Processes the records of a range of blocks in parallel using multiple threads
+    ///
+    /// This works like `process_parallel`, but only the blocks in `blocks` (positions in the
+    /// index of the file) are distributed among the threads. A scheduler can thereby split a
+    /// single large file into independent jobs, e.g. with the ranges of
+    /// `BlockIndex::split_blocks`. Records keep their indices in the whole file, and errors
+    /// report the position of the block in the whole file.
+    ///
+    /// # Parameters
+    ///
+    /// * `self` - Consumes the reader, as it will be used across multiple threads
+    /// * `processor` - An instance of a type implementing `ParallelProcessor` that will be cloned for each thread
+    /// * `num_threads` - Number of worker threads to use for processing (at least one)
+    /// * `blocks` - Positions of the blocks to process
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If all records of the blocks were successfully processed
+    /// * `Err(_)` - If an error occurs during processing
+    ///
+    /// # Errors
+    ///
+    /// * `IndexError::BlockRangeOutOfBounds` if the range extends beyond the blocks of the file
+    /// * Any error of `MmapReader::load_index` or of the processor
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use vbinseq::{MmapReader, ParallelProcessor, RefRecord, Result};
+    ///
+    /// #[derive(Clone)]
+    /// struct Printer;
+    ///
+    /// impl ParallelProcessor for Printer {
+    ///     fn process_record(&mut self, record: RefRecord) -> Result<()> {
+    ///         println!("{}", record.index());
+    ///         Ok(())
+    ///     }
+    /// }
+    ///
+    /// // Process the second of 4 parts of the file (e.g. in the second of 4 jobs)
+    /// let index = MmapReader::new("example.vbq").unwrap().load_index().unwrap();
+    /// let blocks = index.split_blocks(4)[1].clone();
+    /// let reader = MmapReader::new("example.vbq").unwrap();
+    /// reader.process_parallel_range(Printer, 4, blocks).unwrap();
+    /// ```
+    pub fn process_parallel_range<P: ParallelProcessor + Clone + 'static>(
+        self,
+        processor: P,
+        num_threads: usize,
+        blocks: Range<usize>,
+    ) -> Result<()> {
+        let index = self.load_index()?;
+        if blocks.start > blocks.end || blocks.end > index.n_blocks() {
+            return Err(IndexError::BlockRangeOutOfBounds(
+                blocks.start,
+                blocks.end,
+                index.n_blocks(),
+            )
+            .into());
+        }
+        self.process_blocks(processor, num_threads, &index, blocks)
+    }
+
+    /// Distributes a range of blocks of the index among worker threads
+    fn process_blocks<P: ParallelProcessor + Clone + 'static>(
+        self,
+        processor: P,
+        num_threads: usize,
+        index: &BlockIndex,
+        blocks: Range<usize>,
+    ) -> Result<()> {
+        // Get the number of blocks
+        let n_blocks = blocks.len();
+        if n_blocks == 0 {
+            return Ok(()); // Nothing to process
+        }
+
+        // Calculate block assignments
+        let num_threads = num_threads.max(1);
+        let blocks_per_thread = n_blocks.div_ceil(num_threads);
+
+        // Create shared resources
+        let mmap = Arc::clone(&self.mmap);
+        let header = self.header;
+        let path = self.path.clone();
+        let dictionaries = Arc::clone(&self.dictionaries);
+        let verify_checksums = self.verify_checksums;
+
+        // Spawn worker threads
+        let mut handles = Vec::new();
+
+        for thread_id in 0..num_threads {
+            // Calculate this thread's block range
+            let start_block = blocks.start + thread_id * blocks_per_thread;
+            let end_block = std::cmp::min(start_block + blocks_per_thread, blocks.end);
+            if start_block > blocks.end {
+                continue;
+            }
+
+            let mmap = Arc::clone(&mmap);
+            let path = path.clone();
+            let dictionaries = Arc::clone(&dictionaries);
+            let mut proc = processor.clone();
+            proc.set_tid(thread_id);
+
+            // Get block ranges for this thread
+            let blocks: Vec<BlockRange> = index.ranges()[start_block..end_block].to_vec();
+
+            let handle = std::thread::spawn(move || -> Result<()> {
+                // Create block to reuse for processing (within thread)
+                let mut record_block = RecordBlock::new(header.block() as usize);
+                record_block.set_dictionaries(&dictionaries);
+                record_block.set_verify_checksums(verify_checksums);
+
+                // Process each assigned block
+                for (block_index, block_range) in (start_block..).zip(blocks) {
+                    process_block(
+                        &mmap,
+                        &header,
+                        &block_range,
+                        block_range.cumulative_records,
+                        &mut record_block,
+                        &mut proc,
+                    )
+                    .map_err(|e| match e.context() {
+                        // Only read errors have a context, processor errors are kept as is
+                        Some(_) => e.with_context(ErrorContext {
+                            path: Some(path.clone()),
+                            block: Some(block_index),
+                            ..Default::default()
+                        }),
+                        None => e,
+                    })?;
+                }
+
+                // Signal thread completion
+                proc.on_thread_complete()?;
+
+                Ok(())
+            });
 
             handles.push(handle);
         }
 
-        // Wait for all threads to complete
-        for handle in handles {
-            handle.join().unwrap()?;
+        // Wait for all threads to complete
+        for handle in handles {
+            handle.join().unwrap()?;
+        }
+
+        Ok(())
+    }
+}
+
+/// In-memory reader for VBINSEQ files
+///
+/// `MemoryReader` reads VBINSEQ data from a byte buffer instead of a memory-mapped file.
+/// It is available on every platform (including `wasm32`, where memory mapping is not
+/// supported) and is useful for data that is already in memory, such as downloaded files.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use vbinseq::MemoryReader;
+///
+/// let bytes = std::fs::read("example.vbq").unwrap();
+/// let mut reader = MemoryReader::new(bytes).unwrap();
+/// let mut block = reader.new_block();
+///
+/// while reader.read_block_into(&mut block).unwrap() {
+///     println!("Read a block with {} records", block.n_records());
+/// }
+/// ```
+pub struct MemoryReader {
+    /// Contents of the VBINSEQ file
+    bytes: Vec<u8>,
+
+    /// Parsed header information from the file
+    header: VBinseqHeader,
+
+    /// Footer of the file (if written with one)
+    footer: Option<Footer>,
+
+    /// Compression dictionaries of the file (see the `dictionary` module)
+    dictionaries: Arc<[Vec<u8>]>,
+
+    /// Position of the header of the current member of a concatenated file (in bytes)
+    member: usize,
+
+    /// Position in the buffer where the record blocks end (in bytes)
+    end: usize,
+
+    /// Current cursor position in the buffer (in bytes)
+    pos: usize,
+
+    /// Total number of records read so far
+    total: u64,
+
+    /// Index of the next block
+    /// None after jumping to a block by its range, where the index is not known
+    block_index: Option<usize>,
+
+    /// How structural anomalies are handled
+    recovery: Recovery,
+
+    /// Whether the checksums of blocks are verified (in files with block checksums)
+    verify_checksums: bool,
+}
+impl MemoryReader {
+    /// Creates a new `MemoryReader` over the contents of a VBINSEQ file
+    ///
+    /// # Parameters
+    ///
+    /// * `bytes` - The full contents of a VBINSEQ file
+    ///
+    /// # Errors
+    ///
+    /// * `ReadError::EmptyFile` if the buffer is empty
+    /// * `ReadError::UnexpectedEndOfFile` if the buffer is too small to hold a header
+    /// * Header validation errors if the buffer doesn't start with a valid VBINSEQ header
+    /// * Footer validation errors if the header announces a footer that is missing
+    pub fn new(bytes: Vec<u8>) -> Result<Self> {
+        let (header, footer, end) = parse_file_layout(&bytes)?;
+        let dictionaries = dictionary::load(&bytes, &header)?;
+        Ok(Self {
+            bytes,
+            header,
+            footer,
+            dictionaries,
+            member: 0,
+            end,
+            pos: header.data_offset(),
+            total: 0,
+            block_index: Some(0),
+            recovery: Recovery::default(),
+            verify_checksums: false,
+        })
+    }
+
+    /// Creates a new `MemoryReader` by reading a VBINSEQ file from a reader until EOF
+    ///
+    /// # Parameters
+    ///
+    /// * `reader` - Any type that implements the `Read` trait
+    ///
+    /// # Errors
+    ///
+    /// * I/O errors if reading from the reader fails
+    /// * The errors of `MemoryReader::new`
+    pub fn from_reader<R: Read>(mut reader: R) -> Result<Self> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        Self::new(bytes)
+    }
+
+    /// Creates a new empty record block with the appropriate size for this file
+    pub fn new_block(&self) -> RecordBlock {
+        RecordBlock::new(self.header.block() as usize)
+    }
+
+    /// Returns a copy of the file's header information
+    ///
+    /// In concatenated files, this is the header of the member currently being read.
+    pub fn header(&self) -> VBinseqHeader {
+        self.header
+    }
+
+    /// Parses the header sections of the file (e.g. the read-group table)
+    ///
+    /// # Errors
+    ///
+    /// * `HeaderError::InvalidSection` if the sections cannot be parsed
+    pub fn sections(&self) -> Result<HeaderSections> {
+        HeaderSections::from_file_bytes(&self.bytes[self.member..], &self.header)
+    }
+
+    /// Returns the user-defined key/value metadata of the file (e.g. its provenance)
+    ///
+    /// This is the metadata section of the header sections (see `HeaderSections::metadata`),
+    /// which is empty for files written without metadata.
+    ///
+    /// # Errors
+    ///
+    /// * `HeaderError::InvalidSection` if the sections cannot be parsed
+    pub fn metadata(&self) -> Result<Vec<(String, String)>> {
+        Ok(self.sections()?.metadata)
+    }
+
+    /// Scans the preambles (index, flag, and lengths) of all records of the file
+    ///
+    /// The scan is independent of the position of the reader and skips the payloads of the
+    /// records (see the `scan` module).
+    ///
+    /// # Errors
+    ///
+    /// * Header and footer validation errors if the file cannot be parsed
+    pub fn preambles(&self) -> Result<Preambles<'_>> {
+        Preambles::new(&self.bytes)
+    }
+
+    /// Returns the locations (block, offset, and size) of all records of the file
+    ///
+    /// See `scan::RecordLocation` for how records are addressed.
+    ///
+    /// # Errors
+    ///
+    /// * Header and footer validation errors if the file cannot be parsed
+    pub fn locations(&self) -> Result<Locations<'_>> {
+        Locations::new(&self.bytes)
+    }
+
+    /// Returns the footer of the file
+    ///
+    /// Returns `None` if the file was written without a footer
+    /// (see `VBinseqHeader::set_footer`).
+    pub fn footer(&self) -> Option<Footer> {
+        self.footer
+    }
+
+    /// Builds the block index of the file
+    ///
+    /// The index is built by scanning the block headers of the buffer
+    /// (see `BlockIndex::from_bytes`).
+    pub fn build_index(&self) -> Result<BlockIndex> {
+        BlockIndex::from_bytes(&self.bytes)
+    }
+
+    /// Returns how the reader handles structural anomalies of the file
+    pub fn parse_mode(&self) -> ParseMode {
+        self.recovery.mode
+    }
+
+    /// Sets how `read_block_into` and `read_block_at` handle structural anomalies
+    ///
+    /// The default mode is `ParseMode::Strict`. See the `recovery` module for what is
+    /// skipped in `ParseMode::Permissive`.
+    ///
+    /// # Parameters
+    ///
+    /// * `mode` - The parse mode to use
+    pub fn set_parse_mode(&mut self, mode: ParseMode) {
+        self.recovery.mode = mode;
+    }
+
+    /// Sets a callback receiving every anomaly skipped in permissive mode
+    ///
+    /// # Parameters
+    ///
+    /// * `callback` - Called with the error describing the anomaly
+    pub fn set_warning_callback<F: FnMut(&Error) + Send + 'static>(&mut self, callback: F) {
+        self.recovery.callback = Some(Box::new(callback));
+    }
+
+    /// Returns the counts of the anomalies skipped in permissive mode so far
+    pub fn skip_counts(&self) -> SkipCounts {
+        self.recovery.counts
+    }
+
+    /// Returns whether the checksums of blocks are verified
+    pub fn verify_checksums(&self) -> bool {
+        self.verify_checksums
+    }
+
+    /// Sets whether `read_block_into` verifies the checksums of blocks
+    ///
+    /// Verification is disabled by default and only applies to files with block checksums
+    /// (see `VBinseqHeader::set_block_checksum`). Blocks not matching their checksum fail
+    /// to read with `ReadError::BlockChecksumMismatch`.
+    ///
+    /// # Parameters
+    ///
+    /// * `verify` - Whether to verify the checksums
+    pub fn set_verify_checksums(&mut self, verify: bool) {
+        self.verify_checksums = verify;
+    }
+
+    /// Fills an existing RecordBlock with the next block of records
+    ///
+    /// This behaves like `MmapReader::read_block_into`.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(true)` - If a block was successfully read
+    /// * `Ok(false)` - If the end of the file was reached (no more blocks)
+    /// * `Err(_)` - If an error occurred during reading
+    pub fn read_block_into(&mut self, block: &mut RecordBlock) -> Result<bool> {
+        let context = self.context();
+        self.advance_member()
+            .map_err(|e| e.with_context(context.clone()))?;
+        block.set_dictionaries(&self.dictionaries);
+        block.set_verify_checksums(self.verify_checksums);
+        let skipped = self.recovery.counts.blocks;
+        let found = self
+            .recovery
+            .read_next_block(
+                &self.bytes,
+                self.end,
+                &self.header,
+                &mut self.pos,
+                &mut self.total,
+                block,
+            )
+            .map_err(|e| e.with_context(context))?;
+        if self.recovery.counts.blocks != skipped {
+            self.block_index = None;
+        } else if found {
+            self.block_index = self.block_index.map(|index| index + 1);
+        }
+        Ok(found)
+    }
+
+    /// Describes the location of the next block for errors
+    fn context(&self) -> ErrorContext {
+        ErrorContext {
+            block: self.block_index,
+            ..Default::default()
+        }
+    }
+
+    /// Continues with the next member of a concatenated file if one starts at the cursor
+    ///
+    /// Members start directly after the record blocks (or footer) of the previous member
+    /// (see `next_member`). Members without record blocks are skipped, and the header and
+    /// dictionaries of the reader are replaced by those of the new member.
+    fn advance_member(&mut self) -> Result<()> {
+        while self.pos < self.end {
+            let Some(member) = next_member(&self.bytes, self.pos)? else {
+                break;
+            };
+            self.member = member.start;
+            self.header = member.header;
+            self.dictionaries = member.dictionaries;
+            self.end = member.end;
+            self.pos = member.start + member.header.data_offset();
+        }
+        Ok(())
+    }
+
+    /// Reads the block of a range directly, without reading the blocks before it
+    ///
+    /// This behaves like `MmapReader::read_block_at`.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(true)` - If the block was successfully read
+    /// * `Ok(false)` - If the range starts past the last block of the buffer
+    /// * `Err(_)` - If an error occurred during reading
+    pub fn read_block_at(&mut self, range: &BlockRange, block: &mut RecordBlock) -> Result<bool> {
+        self.pos = range.start_offset as usize;
+        self.total = range.cumulative_records;
+        self.block_index = None;
+        self.read_block_into(block)
+    }
+
+    /// Reads a single record by its index in the buffer
+    ///
+    /// This behaves like `MmapReader::seek_record`, so it doesn't move the reader.
+    ///
+    /// # Parameters
+    ///
+    /// * `index` - The index of the buffer (e.g. built with `BlockIndex::from_bytes`)
+    /// * `record` - The index of the record in the buffer
+    /// * `block` - The block to fill with the record
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(true)` - If the record was read
+    /// * `Ok(false)` - If the buffer has no record with the index
+    /// * `Err(_)` - If the index doesn't match the buffer or the block is corrupt
+    pub fn seek_record(
+        &self,
+        index: &BlockIndex,
+        record: u64,
+        block: &mut RecordBlock,
+    ) -> Result<bool> {
+        let member = first_member(&self.bytes, self.member, &self.header, &self.dictionaries)?;
+        read_record_at(&self.bytes, &member, index, record, block)
+            .map_err(|e| e.with_context(self.context()))
+    }
+
+    /// Returns the next block in its stored form
+    ///
+    /// This advances the reader like `read_block_into` without decompressing or parsing
+    /// the block, which is useful to copy blocks between files verbatim.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Some(RawBlock))` - The next block
+    /// * `Ok(None)` - If there are no more blocks
+    ///
+    /// # Errors
+    ///
+    /// * `ReadError::InvalidBlockMagicNumber` if the block header is invalid
+    /// * `ReadError::UnexpectedEndOfFile` if the block extends beyond the end of the file
+    pub fn next_raw_block(&mut self) -> Result<Option<RawBlock<'_>>> {
+        let context = self.context();
+        self.advance_member()
+            .map_err(|e| e.with_context(context.clone()))?;
+        let raw = read_next_raw_block(
+            &self.bytes,
+            self.end,
+            &self.header,
+            &mut self.pos,
+            &mut self.total,
+        )
+        .map_err(|e| e.with_context(context))?;
+        if raw.is_some() {
+            self.block_index = self.block_index.map(|index| index + 1);
+        }
+        Ok(raw.map(|raw| RawBlock {
+            dictionaries: &self.dictionaries,
+            ..raw
+        }))
+    }
+
+    /// Returns the block of a range in its stored form, without reading the blocks before it
+    ///
+    /// This behaves like `MmapReader::raw_block_at`.
+    pub fn raw_block_at(&mut self, range: &BlockRange) -> Result<Option<RawBlock<'_>>> {
+        self.pos = range.start_offset as usize;
+        self.total = range.cumulative_records;
+        self.block_index = None;
+        self.next_raw_block()
+    }
+}
+
+/// A reader for VBINSEQ files from any byte stream
+///
+/// `MmapReader` requires a seekable regular file and `MemoryReader` holds the whole file
+/// in memory. `StreamReader` reads blocks one at a time from any `Read` source (e.g. stdin
+/// or a pipe), so only a single block is held in memory at a time. Blocks are read in
+/// order and decoded into a `RecordBlock` like with the other readers, and concatenated
+/// files are read member by member.
+///
+/// Streams can't be rewound, so there is no random access, no block index, and no
+/// permissive parse mode. A footer is only known once the stream reached it (see
+/// `footer`), and streams ending without the footer announced by their header are
+/// reported as truncated.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use std::io::stdin;
+/// use vbinseq::StreamReader;
+///
+/// let mut reader = StreamReader::new(stdin().lock()).unwrap();
+/// let mut block = reader.new_block();
+///
+/// while reader.read_block_into(&mut block).unwrap() {
+///     println!("Read a block with {} records", block.n_records());
+/// }
+/// ```
+pub struct StreamReader<R: Read> {
+    /// Source of the stream
+    inner: R,
+
+    /// Header of the current member
+    header: VBinseqHeader,
+
+    /// Header and header sections of the current member, as stored
+    prefix: Vec<u8>,
+
+    /// Compression dictionaries of the current member (see the `dictionary` module)
+    dictionaries: Arc<[Vec<u8>]>,
+
+    /// Footer of the current member (once it was read)
+    footer: Option<Footer>,
+
+    /// Number of bytes read from the stream
+    pos: u64,
+
+    /// Total number of records read so far
+    total: u64,
+
+    /// Index of the next block
+    block_index: usize,
+
+    /// Stored bytes of the last block read
+    rbuf: Vec<u8>,
+
+    /// Whether the checksums of blocks are verified (in files with block checksums)
+    verify_checksums: bool,
+}
+impl<R: Read> StreamReader<R> {
+    /// Creates a new `StreamReader` and reads the header of the stream
+    ///
+    /// # Parameters
+    ///
+    /// * `inner` - Source of the stream (wrap unbuffered sources in a `BufReader`)
+    ///
+    /// # Errors
+    ///
+    /// * `ReadError::EmptyFile` if the stream is empty
+    /// * `ReadError::UnexpectedEndOfFile` if the stream ends within the header
+    /// * Header validation errors if the stream doesn't start with a valid VBINSEQ header
+    /// * I/O errors if reading from the stream fails
+    pub fn new(inner: R) -> Result<Self> {
+        let mut reader = Self {
+            inner,
+            header: VBinseqHeader::default(),
+            prefix: Vec::new(),
+            dictionaries: Arc::from([]),
+            footer: None,
+            pos: 0,
+            total: 0,
+            block_index: 0,
+            rbuf: Vec::new(),
+            verify_checksums: false,
+        };
+        let mut header_bytes = [0u8; SIZE_HEADER];
+        match reader.read_full(&mut header_bytes)? {
+            0 => return Err(ReadError::EmptyFile.into()),
+            SIZE_HEADER => reader.start_member(header_bytes)?,
+            n => return Err(ReadError::UnexpectedEndOfFile(n).into()),
+        }
+        Ok(reader)
+    }
+
+    /// Creates a new empty record block with the appropriate size for this stream
+    pub fn new_block(&self) -> RecordBlock {
+        RecordBlock::new(self.header.block() as usize)
+    }
+
+    /// Returns a copy of the stream's header information
+    ///
+    /// In concatenated files, this is the header of the member currently being read.
+    pub fn header(&self) -> VBinseqHeader {
+        self.header
+    }
+
+    /// Parses the header sections of the current member (e.g. the read-group table)
+    ///
+    /// # Errors
+    ///
+    /// * `HeaderError::InvalidSection` if the sections cannot be parsed
+    pub fn sections(&self) -> Result<HeaderSections> {
+        HeaderSections::from_file_bytes(&self.prefix, &self.header)
+    }
+
+    /// Returns the user-defined key/value metadata of the current member (e.g. its provenance)
+    ///
+    /// This is the metadata section of the header sections (see `HeaderSections::metadata`),
+    /// which is empty for files written without metadata.
+    ///
+    /// # Errors
+    ///
+    /// * `HeaderError::InvalidSection` if the sections cannot be parsed
+    pub fn metadata(&self) -> Result<Vec<(String, String)>> {
+        Ok(self.sections()?.metadata)
+    }
+
+    /// Returns the footer of the current member
+    ///
+    /// Returns `None` if the member was written without a footer or the stream has not
+    /// reached the footer yet (i.e. before `read_block_into` returned `false`).
+    pub fn footer(&self) -> Option<Footer> {
+        self.footer
+    }
+
+    /// Returns the number of bytes read from the stream so far
+    pub fn position(&self) -> u64 {
+        self.pos
+    }
+
+    /// Unwraps the reader, returning the source of the stream
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// Returns whether the checksums of blocks are verified
+    pub fn verify_checksums(&self) -> bool {
+        self.verify_checksums
+    }
+
+    /// Sets whether `read_block_into` verifies the checksums of blocks
+    ///
+    /// Verification is disabled by default and only applies to files with block checksums
+    /// (see `VBinseqHeader::set_block_checksum`). Blocks not matching their checksum fail
+    /// to read with `ReadError::BlockChecksumMismatch`.
+    ///
+    /// # Parameters
+    ///
+    /// * `verify` - Whether to verify the checksums
+    pub fn set_verify_checksums(&mut self, verify: bool) {
+        self.verify_checksums = verify;
+    }
+
+    /// Fills an existing RecordBlock with the next block of records
+    ///
+    /// This behaves like `MmapReader::read_block_into`.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(true)` - If a block was successfully read
+    /// * `Ok(false)` - If the end of the stream was reached (no more blocks)
+    /// * `Err(_)` - If an error occurred during reading
+    ///
+    /// # Errors
+    ///
+    /// * `ReadError::UnexpectedEndOfFile` if the stream ends within a block or footer
+    /// * `ReadError::MissingFooter` if the stream ends without the footer announced by
+    ///   its header
+    /// * I/O errors if reading from the stream fails
+    /// * Parsing errors if a block is invalid
+    pub fn read_block_into(&mut self, block: &mut RecordBlock) -> Result<bool> {
+        block.clear();
+        let (offset, first_index) = (self.pos, self.total);
+        let Some(block_header) = self.next_stored_block()? else {
+            return Ok(false);
+        };
+        block.set_dictionaries(&self.dictionaries);
+        block.set_verify_checksums(self.verify_checksums);
+        block
+            .ingest_block(&block_header, &self.rbuf, &self.header)
+            .map_err(|e| {
+                e.with_context(ErrorContext {
+                    block: Some(self.block_index - 1),
+                    ..block_context(offset as usize, first_index + block.n_records() as u64)
+                })
+            })?;
+        block.update_index(first_index);
+        Ok(true)
+    }
+
+    /// Returns the next block in its stored form
+    ///
+    /// This advances the reader like `read_block_into` without decompressing or parsing
+    /// the block, which is useful to copy blocks between files verbatim.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Some(RawBlock))` - The next block
+    /// * `Ok(None)` - If there are no more blocks
+    ///
+    /// # Errors
+    ///
+    /// * The errors of `read_block_into` that are not caused by parsing records
+    pub fn next_raw_block(&mut self) -> Result<Option<RawBlock<'_>>> {
+        Ok(self.next_stored_block()?.map(|header| RawBlock {
+            header,
+            file_header: self.header,
+            dictionaries: &self.dictionaries,
+            data: &self.rbuf,
+        }))
+    }
+
+    /// Reads the next block into the block buffer and returns its header
+    ///
+    /// Footers and the headers of concatenated members between blocks are consumed.
+    /// Errors are annotated with the offset of the block and its index.
+    fn next_stored_block(&mut self) -> Result<Option<BlockHeader>> {
+        loop {
+            let (offset, context) = (self.pos, self.context());
+            let locate = |e: Error| e.with_context(context.clone());
+            let mut head = [0u8; SIZE_BLOCK_HEADER];
+            match self.read_full(&mut head).map_err(locate)? {
+                0 if self.header.has_footer() && self.footer.is_none() => {
+                    return Err(locate(ReadError::MissingFooter.into()));
+                }
+                0 => return Ok(None),
+                SIZE_BLOCK_HEADER => {}
+                _ => {
+                    return Err(locate(
+                        ReadError::UnexpectedEndOfFile(self.pos as usize).into(),
+                    ))
+                }
+            }
+
+            // Embedded indexes and footers end a member, headers start the next member of a
+            // concatenated file
+            if LittleEndian::read_u64(&head) == INDEX_MAGIC {
+                let size = LittleEndian::read_u64(&head[8..16]);
+                let remaining = usize::try_from(size)
+                    .ok()
+                    .and_then(|size| size.checked_sub(INDEX_HEADER_SIZE))
+                    .ok_or_else(|| locate(IndexError::InvalidEmbeddedIndex(offset).into()))?;
+                let mut rbuf = std::mem::take(&mut self.rbuf);
+                rbuf.resize(remaining, 0);
+                let status = self.read_exact(&mut rbuf);
+                self.rbuf = rbuf;
+                status.map_err(locate)?;
+                continue;
+            }
+            if LittleEndian::read_u64(&head) == FOOTER_MAGIC {
+                let mut footer_bytes = [0u8; SIZE_FOOTER];
+                footer_bytes[..SIZE_BLOCK_HEADER].copy_from_slice(&head);
+                self.read_exact(&mut footer_bytes[SIZE_BLOCK_HEADER..])
+                    .map_err(locate)?;
+                self.footer = Some(Footer::from_bytes(&footer_bytes, offset as usize)?);
+                continue;
+            }
+            if LittleEndian::read_u32(&head) == MAGIC {
+                self.start_member(head).map_err(locate)?;
+                continue;
+            }
+
+            let mut block_header = BlockHeader::from_bytes(&head).map_err(locate)?;
+            let rbound = if self.header.compressed() {
+                block_header.size as usize
+            } else {
+                self.header.block() as usize
+            };
+            // Record the codec explicitly so the block is self-describing in any file
+            block_header.set_codec(
+                block_header
+                    .codec()
+                    .map_err(locate)?
+                    .unwrap_or(self.header.codec()),
+            );
+            let mut rbuf = std::mem::take(&mut self.rbuf);
+            rbuf.resize(rbound, 0);
+            let status = self.read_exact(&mut rbuf);
+            self.rbuf = rbuf;
+            status.map_err(locate)?;
+
+            self.total += u64::from(block_header.records);
+            self.block_index += 1;
+            return Ok(Some(block_header));
+        }
+    }
+
+    /// Parses the header of a member and reads its header sections
+    fn start_member(&mut self, header_bytes: [u8; SIZE_HEADER]) -> Result<()> {
+        let header = VBinseqHeader::from_bytes(&header_bytes)?;
+        let mut prefix = header_bytes.to_vec();
+        prefix.resize(header.data_offset(), 0);
+        self.read_exact(&mut prefix[SIZE_HEADER..])?;
+        self.dictionaries = dictionary::load(&prefix, &header)?;
+        self.header = header;
+        self.prefix = prefix;
+        self.footer = None;
+        Ok(())
+    }
+
+    /// Describes the location of the next block for errors
+    fn context(&self) -> ErrorContext {
+        ErrorContext {
+            block: Some(self.block_index),
+            ..block_context(self.pos as usize, self.total)
+        }
+    }
+
+    /// Fills a buffer from the stream, returning fewer bytes only at the end of the stream
+    fn read_full(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let mut filled = 0;
+        while filled < buf.len() {
+            match self.inner.read(&mut buf[filled..]) {
+                Ok(0) => break,
+                Ok(n) => filled += n,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        self.pos += filled as u64;
+        Ok(filled)
+    }
+
+    /// Fills a buffer from the stream, failing if the stream ends before
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<()> {
+        if self.read_full(buf)? < buf.len() {
+            return Err(ReadError::UnexpectedEndOfFile(self.pos as usize).into());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::alloc::{GlobalAlloc, Layout, System};
+    use std::cell::Cell;
+
+    use super::*;
+    use crate::VBinseqWriterBuilder;
+
+    /// Allocator counting the allocations of the current thread
+    struct CountingAllocator;
+
+    thread_local! {
+        static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+    }
+
+    fn count() {
+        let _ = ALLOCATIONS.try_with(|n| n.set(n.get() + 1));
+    }
+
+    unsafe impl GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            count();
+            System.alloc(layout)
+        }
+
+        unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+            count();
+            System.alloc_zeroed(layout)
+        }
+
+        unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+            count();
+            System.realloc(ptr, layout, new_size)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            System.dealloc(ptr, layout)
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+    fn allocations() -> usize {
+        ALLOCATIONS.with(Cell::get)
+    }
+
+    #[test]
+    fn test_zero_allocation_iteration() -> Result<()> {
+        for compressed in [false, true] {
+            // Records of increasing length, so later blocks hold more data than the first
+            let header = VBinseqHeader::with_capacity(4096, true, compressed, true);
+            let mut bytes = Vec::new();
+            let mut writer = VBinseqWriterBuilder::default()
+                .header(header)
+                .build(&mut bytes)?;
+            for i in 0..500usize {
+                let sequence = b"ACGT".repeat(1 + i / 10);
+                let quality = vec![b'I'; sequence.len()];
+                writer.write_nucleotides_quality_paired(
+                    i as u64, &sequence, &sequence, &quality, &quality,
+                )?;
+            }
+            writer.finish()?;
+            drop(writer);
+
+            let mut reader = MemoryReader::new(bytes)?;
+            let index = reader.build_index()?;
+            let mut block = reader.new_block();
+            block.reserve(index.max_block_records());
+            let mut sequence = Vec::with_capacity(4096);
+            let mut extended = Vec::with_capacity(4096);
+
+            // Warm up on the first block
+            assert!(reader.read_block_into(&mut block)?);
+
+            let before = allocations();
+            let mut n_records = block.n_records();
+            while reader.read_block_into(&mut block)? {
+                for record in block.iter() {
+                    sequence.clear();
+                    extended.clear();
+                    record.decode_s(&mut sequence)?;
+                    record.decode_x(&mut extended)?;
+                    assert_eq!(sequence, extended);
+                    n_records += 1;
+                }
+            }
+            assert_eq!(allocations(), before, "compressed: {compressed}");
+            assert_eq!(n_records, 500);
+        }
+        Ok(())
+    }
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn test_map_options() -> Result<()> {
+        let path = std::env::temp_dir().join(format!("vbq_map_{}.vbq", std::process::id()));
+        let mut writer = VBinseqWriterBuilder::default()
+            .header(VBinseqHeader::with_capacity(1024, false, true, false))
+            .build(File::create(&path)?)?;
+        for flag in 0..200 {
+            writer.write_nucleotides(flag, b"ACGTACGTACGTACGTACGTACGTACGTACGTACGT")?;
+        }
+        writer.finish()?;
+        drop(writer);
+
+        let options = MapOptions {
+            populate: true,
+            huge_pages: true,
+        };
+        let mut reader = MmapReader::with_options(&path, &options)?;
+        let mut block = reader.new_block();
+        let mut n_records = 0;
+        while reader.read_block_into(&mut block)? {
+            n_records += block.n_records();
+        }
+        assert_eq!(n_records, 200);
+
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+    #[test]
+    fn test_inconsistent_block() -> Result<()> {
+        let header = VBinseqHeader::with_capacity(1024, false, false, false);
+        let mut bytes = Vec::new();
+        let mut writer = VBinseqWriterBuilder::default()
+            .header(header)
+            .build(&mut bytes)?;
+        for flag in 0..10 {
+            writer.write_nucleotides(flag, b"ACGTACGT")?;
+        }
+        writer.finish()?;
+        drop(writer);
+
+        // A record count disagreeing with the records of the block
+        let mut tampered = bytes.clone();
+        LittleEndian::write_u32(&mut tampered[SIZE_HEADER + 16..SIZE_HEADER + 20], 11);
+        let mut reader = MemoryReader::new(tampered)?;
+        let mut block = reader.new_block();
+        assert!(matches!(
+            reader.read_block_into(&mut block).unwrap_err().root(),
+            crate::Error::ReadError(ReadError::RecordCountMismatch(11, 10))
+        ));
+
+        // Data in the padding after the last record
+        let mut tampered = bytes.clone();
+        let padding = SIZE_HEADER + SIZE_BLOCK_HEADER + 10 * 32 + 24;
+        tampered[padding + 3] = 1;
+        let mut reader = MemoryReader::new(tampered)?;
+        assert!(matches!(
+            reader.read_block_into(&mut block).unwrap_err().root(),
+            crate::Error::ReadError(ReadError::UnexpectedBlockData(347))
+        ));
+
+        let mut reader = MemoryReader::new(bytes)?;
+        assert!(reader.read_block_into(&mut block)?);
+        assert_eq!(block.n_records(), 10);
+        Ok(())
+    }
+    #[test]
+    fn test_empty_records() -> Result<()> {
+        for (qual, compressed) in [(false, false), (true, false), (true, true)] {
+            let header = VBinseqHeader::with_capacity(256, qual, compressed, true);
+            let mut bytes = Vec::new();
+            let mut writer = VBinseqWriterBuilder::default()
+                .header(header)
+                .build(&mut bytes)?;
+            let mut expected = Vec::new();
+            for flag in 0..50 {
+                // Every third record is empty, ending some blocks with empty records
+                let sequence = if flag % 3 == 0 {
+                    Vec::new()
+                } else {
+                    b"ACGTTGCA".repeat(flag as usize % 4)
+                };
+                let mate = b"TTGCA".repeat(flag as usize % 2);
+                if qual {
+                    writer.write_nucleotides_quality_paired(
+                        0,
+                        &sequence,
+                        &mate,
+                        &vec![b'I'; sequence.len()],
+                        &vec![b'I'; mate.len()],
+                    )?;
+                } else {
+                    writer.write_nucleotides_paired(0, &sequence, &mate)?;
+                }
+                expected.push((sequence, mate));
+            }
+            writer.finish()?;
+            drop(writer);
+
+            let mut reader = MemoryReader::new(bytes)?;
+            let mut block = reader.new_block();
+            let mut records = Vec::new();
+            while reader.read_block_into(&mut block)? {
+                for record in block.iter() {
+                    let (mut sequence, mut mate) = (Vec::new(), Vec::new());
+                    record.decode_s(&mut sequence)?;
+                    record.decode_x(&mut mate)?;
+                    assert_eq!(record.squal().len(), if qual { sequence.len() } else { 0 });
+                    records.push((sequence, mate));
+                }
+            }
+            assert_eq!(records, expected);
+        }
+        Ok(())
+    }
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn test_index_policy() -> Result<()> {
+        use crate::error::IndexError;
+        use crate::index::IndexHeader;
+
+        let path = std::env::temp_dir().join(format!("vbq_policy_{}.vbq", std::process::id()));
+        let header = VBinseqHeader::with_capacity(256, false, false, false);
+        let mut writer = VBinseqWriterBuilder::default()
+            .header(header)
+            .build(File::create(&path)?)?;
+        for flag in 0..100 {
+            writer.write_nucleotides(flag, b"ACGTACGT")?;
+        }
+        writer.finish()?;
+        drop(writer);
+        let n_blocks = BlockIndex::from_vbq(&path)?.n_blocks();
+        let mut reader = MmapReader::new(&path)?;
+        let index_path = reader.index_path();
+        std::fs::remove_file(&index_path).ok();
+
+        // Missing index files are only built if allowed
+        reader.set_index_policy(IndexPolicy::RequireValid);
+        assert!(matches!(
+            reader.load_index(),
+            Err(crate::Error::IndexError(IndexError::MissingIndexFile(_)))
+        ));
+        reader.set_index_policy(IndexPolicy::RebuildOnMismatch);
+        assert_eq!(reader.load_index()?.n_blocks(), n_blocks);
+        assert!(index_path.exists());
+
+        // Stale index files are rebuilt on mismatch
+        BlockIndex::new(IndexHeader::new(1)).save_to_path(&index_path)?;
+        reader.set_index_policy(IndexPolicy::RequireValid);
+        let error = reader.load_index().unwrap_err();
+        assert!(error.is_index_mismatch());
+        reader.set_index_policy(IndexPolicy::RebuildOnMismatch);
+        assert_eq!(reader.load_index()?.n_blocks(), n_blocks);
+        reader.set_index_policy(IndexPolicy::RequireValid);
+        assert_eq!(reader.load_index()?.n_blocks(), n_blocks);
+
+        // Corrupt index files are not silently rebuilt
+        std::fs::write(&index_path, [0; 64])?;
+        reader.set_index_policy(IndexPolicy::RebuildOnMismatch);
+        let error = reader.load_index().unwrap_err();
+        assert!(!error.is_index_mismatch());
+        assert!(matches!(
+            error,
+            crate::Error::IndexError(IndexError::InvalidMagicNumber(0))
+        ));
+        reader.set_index_policy(IndexPolicy::AlwaysRebuild);
+        assert_eq!(reader.load_index()?.n_blocks(), n_blocks);
+        reader.set_index_policy(IndexPolicy::RequireValid);
+        assert_eq!(reader.load_index()?.n_blocks(), n_blocks);
+
+        std::fs::remove_file(&index_path)?;
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn test_into_records() -> Result<()> {
+        use rand::rngs::SmallRng;
+        use rand::SeedableRng;
+
+        let mut rng = SmallRng::seed_from_u64(7);
+        let path = std::env::temp_dir().join(format!("vbq_records_{}.vbq", std::process::id()));
+        for header in crate::testing::headers() {
+            let records = crate::testing::random_records(&mut rng, &header, 100);
+            std::fs::write(&path, crate::testing::write_records(header, &records)?)?;
+            let read = MmapReader::new(&path)?
+                .into_records()
+                .collect::<Result<Vec<_>>>()?;
+            assert_eq!(read.len(), records.len());
+            let stored = crate::testing::stored_records(&header, &records);
+            for (position, (read, written)) in read.iter().zip(&stored).enumerate() {
+                assert_eq!(read.index(), position as u64);
+                assert_eq!((read.flag(), read.seq()), (written.flag(), written.seq()));
+                assert_eq!(read.squal(), written.squal());
+            }
+        }
+
+        // Iteration ends after the first error
+        let header = VBinseqHeader::with_capacity(1024, false, false, false);
+        let records = crate::testing::random_records(&mut rng, &header, 100);
+        let mut bytes = crate::testing::write_records(header, &records)?;
+        bytes.truncate(bytes.len() - 10);
+        std::fs::write(&path, bytes)?;
+        let results: Vec<_> = MmapReader::new(&path)?.into_records().collect();
+        assert!(results.last().is_some_and(|result| result.is_err()));
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn test_byte_ranges() -> Result<()> {
+        use rand::rngs::SmallRng;
+        use rand::SeedableRng;
+
+        use crate::error::IndexError;
+
+        #[derive(Clone)]
+        struct Collector(Arc<std::sync::Mutex<Vec<u64>>>);
+        impl ParallelProcessor for Collector {
+            fn process_record(&mut self, record: RefRecord) -> Result<()> {
+                self.0.lock().unwrap().push(record.index());
+                Ok(())
+            }
+        }
+
+        let mut rng = SmallRng::seed_from_u64(8);
+        let path = std::env::temp_dir().join(format!("vbq_ranges_{}.vbq", std::process::id()));
+        for header in crate::testing::headers() {
+            let records = crate::testing::random_records(&mut rng, &header, 300);
+            std::fs::write(&path, crate::testing::write_records(header, &records)?)?;
+            let index = MmapReader::new(&path)?.load_index()?;
+            for n_parts in [1, 3, 7, 1000] {
+                let ranges = index.split_points(n_parts);
+                assert!(!ranges.is_empty() && ranges.len() <= n_parts);
+
+                // The ranges cover the blocks of the file without gaps
+                for window in ranges.windows(2) {
+                    assert_eq!(window[0].0 + window[0].1, window[1].0);
+                }
+
+                // Reading the ranges one after another reads the whole file
+                let mut read = Vec::new();
+                for &(offset, len) in &ranges {
+                    let reader = MmapReader::with_range(&path, offset, len)?;
+                    read.extend(reader.into_records().collect::<Result<Vec<_>>>()?);
+                }
+                assert_eq!(read.len(), records.len());
+                let stored = crate::testing::stored_records(&header, &records);
+                for (position, (read, written)) in read.iter().zip(&stored).enumerate() {
+                    assert_eq!(read.index(), position as u64);
+                    assert_eq!((read.flag(), read.seq()), (written.flag(), written.seq()));
+                }
+
+                // Processing the block ranges in parallel processes every record once
+                let blocks = index.split_blocks(n_parts);
+                assert_eq!(blocks.len(), ranges.len());
+                let collector = Collector(Arc::default());
+                for blocks in blocks {
+                    let expected = index.ranges()[blocks.clone()]
+                        .iter()
+                        .map(|range| u64::from(range.block_records))
+                        .sum::<u64>();
+                    let before = collector.0.lock().unwrap().len() as u64;
+                    MmapReader::new(&path)?.process_parallel_range(collector.clone(), 3, blocks)?;
+                    assert_eq!(collector.0.lock().unwrap().len() as u64 - before, expected);
+                }
+                let mut indices = collector.0.lock().unwrap().clone();
+                indices.sort_unstable();
+                assert!(indices.into_iter().eq(0..records.len() as u64));
+            }
+
+            // Block ranges must be within the file
+            let n_blocks = index.n_blocks();
+            let collector = Collector(Arc::default());
+            MmapReader::new(&path)?.process_parallel_range(collector.clone(), 2, 1..1)?;
+            assert!(collector.0.lock().unwrap().is_empty());
+            let e = MmapReader::new(&path)?
+                .process_parallel_range(collector.clone(), 2, 0..n_blocks + 1)
+                .unwrap_err();
+            assert!(matches!(
+                e.root(),
+                crate::Error::IndexError(IndexError::BlockRangeOutOfBounds(..))
+            ));
+
+            // Ranges must be aligned to the blocks of the file
+            let (offset, len) = index.split_points(1)[0];
+            for (offset, len) in [(offset + 1, len - 1), (offset, len - 1), (offset, 0)] {
+                let e = MmapReader::with_range(&path, offset, len).err().unwrap();
+                assert!(matches!(
+                    e.root(),
+                    crate::Error::IndexError(IndexError::UnalignedRange(..))
+                ));
+            }
+            // Embedded indexes are not saved to an index file
+            if !header.has_embedded_index() {
+                std::fs::remove_file(path.with_extension("vbq.vqi"))?;
+            }
+        }
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn test_stale_index() -> Result<()> {
+        use crate::error::IndexError;
+        use crate::index::IndexHeader;
+
+        for footer in [false, true] {
+            let path =
+                std::env::temp_dir().join(format!("vbq_stale_{footer}_{}.vbq", std::process::id()));
+            let mut header = VBinseqHeader::with_capacity(256, false, true, false);
+            header.set_footer(footer);
+            let mut writer = VBinseqWriterBuilder::default()
+                .header(header)
+                .build(File::create(&path)?)?;
+            for flag in 0..100 {
+                writer.write_nucleotides(flag, b"ACGTACGT")?;
+            }
+            writer.finish()?;
+            drop(writer);
+            let mut reader = MmapReader::new(&path)?;
+            let index = BlockIndex::from_vbq(&path)?;
+            reader.check_index(&index)?;
+
+            // An index of the same size as the file but with other record counts
+            let file_size = std::fs::metadata(&path)?.len();
+            let mut stale = BlockIndex::new(IndexHeader::new(file_size));
+            let mut cumulative_records = 0;
+            for (i, range) in index.ranges().iter().enumerate() {
+                let block_records = range.block_records + u32::from(i == 0);
+                stale.add_range(BlockRange::new(
+                    range.start_offset,
+                    range.len,
+                    block_records,
+                    cumulative_records,
+                ));
+                cumulative_records += u64::from(block_records);
+            }
+            let error = reader.check_index(&stale).unwrap_err();
+            let expected = if footer {
+                ("records".to_string(), 101, 100)
+            } else {
+                let records = u64::from(index.ranges()[0].block_records);
+                ("records in block 0".to_string(), records + 1, records)
+            };
+            match error {
+                crate::Error::IndexError(IndexError::RecordCountMismatch(what, indexed, found)) => {
+                    assert_eq!((what, indexed, found), expected)
+                }
+                e => panic!("Unexpected error: {e}"),
+            }
+
+            // Stale index files are caught on load and rebuilt unless a valid index is required
+            stale.save_to_path(reader.index_path())?;
+            reader.set_index_policy(IndexPolicy::RequireValid);
+            assert!(reader.load_index().unwrap_err().is_index_mismatch());
+            reader.set_index_policy(IndexPolicy::RebuildOnMismatch);
+            assert_eq!(reader.load_index()?.n_records(), 100);
+
+            // Block ranges pointing elsewhere are caught as well
+            let mut shifted = BlockIndex::new(IndexHeader::new(file_size));
+            for range in index.ranges() {
+                shifted.add_range(BlockRange::new(
+                    range.start_offset + 8,
+                    range.len,
+                    range.block_records,
+                    range.cumulative_records,
+                ));
+            }
+            assert!(matches!(
+                reader.check_index(&shifted),
+                Err(crate::Error::IndexError(IndexError::InvalidBlockOffset(_)))
+            ));
+
+            std::fs::remove_file(reader.index_path())?;
+            std::fs::remove_file(&path)?;
+        }
+        Ok(())
+    }
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn test_seek_record() -> Result<()> {
+        use crate::error::IndexError;
+        use rand::rngs::SmallRng;
+        use rand::SeedableRng;
+
+        let mut rng = SmallRng::seed_from_u64(12);
+        let path = std::env::temp_dir().join(format!("vbq_seek_{}.vbq", std::process::id()));
+        let mut headers = crate::testing::headers();
+        headers.push(VBinseqHeader::with_capacity(256, false, true, false));
+        for header in headers {
+            let records = crate::testing::random_records(&mut rng, &header, 200);
+            let bytes = crate::testing::write_records(header, &records)?;
+            std::fs::write(&path, &bytes)?;
+            let mut reader = MmapReader::new(&path)?;
+            reader.set_index_policy(IndexPolicy::AlwaysRebuild);
+            let plain = reader.load_index()?;
+            reader.set_record_index(true);
+            let indexed = reader.load_index()?;
+            assert!(!plain.has_record_offsets());
+            assert!(indexed.has_record_offsets());
+
+            let memory = MemoryReader::new(bytes)?;
+            let mut block = reader.new_block();
+            let stored = crate::testing::stored_records(&header, &records);
+            for index in [&plain, &indexed] {
+                for (position, written) in stored.iter().enumerate() {
+                    let position = position as u64;
+                    let seeks = [
+                        reader.seek_record(index, position, &mut block)?,
+                        memory.seek_record(index, position, &mut block)?,
+                    ];
+                    assert_eq!(seeks, [true, true]);
+                    assert_eq!(block.n_records(), 1);
+                    let read = OwnedRecord::try_from(&block.iter().next().unwrap())?;
+                    assert_eq!(read.index(), position);
+                    assert_eq!((read.flag(), read.seq()), (written.flag(), written.seq()));
+                    assert_eq!((read.xseq(), read.aux()), (written.xseq(), written.aux()));
+                    assert_eq!(
+                        (read.squal(), read.xqual()),
+                        (written.squal(), written.xqual())
+                    );
+                }
+                let past = records.len() as u64;
+                assert!(!reader.seek_record(index, past, &mut block)?);
+                assert_eq!(block.n_records(), 0);
+            }
+            // Seeking doesn't move the reader
+            assert!(reader.read_block_into(&mut block)?);
+            assert_eq!(block.iter().next().unwrap().index(), 0);
+        }
+
+        // Index files without record offsets are rebuilt unless a valid index is required
+        let mut reader = MmapReader::new(&path)?;
+        reader.set_index_policy(IndexPolicy::AlwaysRebuild);
+        reader.load_index()?;
+        reader.set_record_index(true);
+        reader.set_index_policy(IndexPolicy::RequireValid);
+        assert!(matches!(
+            reader.load_index(),
+            Err(crate::Error::IndexError(IndexError::MissingRecordOffsets))
+        ));
+        reader.set_index_policy(IndexPolicy::RebuildOnMismatch);
+        assert!(reader.load_index()?.has_record_offsets());
+        reader.set_index_policy(IndexPolicy::RequireValid);
+        assert!(reader.load_index()?.has_record_offsets());
+
+        // Readers without record indexes load indexes with record offsets
+        reader.set_record_index(false);
+        assert!(reader.load_index()?.has_record_offsets());
+
+        std::fs::remove_file(reader.index_path())?;
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn test_error_context() -> Result<()> {
+        let path = std::env::temp_dir().join(format!("vbq_context_{}.vbq", std::process::id()));
+        let header = VBinseqHeader::with_capacity(512, false, false, false);
+        let mut writer = VBinseqWriterBuilder::default()
+            .header(header)
+            .build(File::create(&path)?)?;
+        for flag in 0..100 {
+            writer.write_nucleotides(flag, b"ACGTACGT")?;
+        }
+        writer.finish()?;
+        drop(writer);
+
+        // Corrupt the length of the third record of the third block
+        let index = BlockIndex::from_vbq(&path)?;
+        let range = index.ranges()[2];
+        let mut bytes = std::fs::read(&path)?;
+        let record = range.start_offset as usize + SIZE_BLOCK_HEADER + 2 * 32;
+        LittleEndian::write_u64(&mut bytes[record + 8..record + 16], 1 << 40);
+        std::fs::write(&path, &bytes)?;
+
+        let expected = ErrorContext {
+            path: Some(path.clone()),
+            block: Some(2),
+            offset: Some(range.start_offset),
+            record: Some(range.cumulative_records + 2),
+        };
+        let mut reader = MmapReader::new(&path)?;
+        let mut block = reader.new_block();
+        let error = loop {
+            if let Err(error) = reader.read_block_into(&mut block) {
+                break error;
+            }
+        };
+        assert_eq!(error.context(), Some(&expected));
+        assert!(matches!(
+            error.root(),
+            crate::Error::ReadError(ReadError::InvalidRecordLength(64, _, 0))
+        ));
+        assert!(error.to_string().contains(&format!(
+            "file {}, block 2, offset {}, record {}",
+            path.display(),
+            range.start_offset,
+            range.cumulative_records + 2
+        )));
+
+        // Parallel processing reports the same location
+        #[derive(Clone)]
+        struct Nothing;
+        impl ParallelProcessor for Nothing {
+            fn process_record(&mut self, _record: RefRecord) -> Result<()> {
+                Ok(())
+            }
+        }
+        std::fs::remove_file(reader.index_path()).ok();
+        let error = MmapReader::new(&path)?
+            .process_parallel(Nothing, 2)
+            .unwrap_err();
+        assert_eq!(error.context(), Some(&expected));
+
+        std::fs::remove_file(reader.index_path()).ok();
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn test_block_checksums() -> Result<()> {
+        #[derive(Clone)]
+        struct Nothing;
+        impl ParallelProcessor for Nothing {
+            fn process_record(&mut self, _record: RefRecord) -> Result<()> {
+                Ok(())
+            }
+        }
+        let is_mismatch = |error: &crate::Error| {
+            matches!(
+                error.root(),
+                crate::Error::ReadError(ReadError::BlockChecksumMismatch(..))
+            )
+        };
+
+        let path = std::env::temp_dir().join(format!("vbq_checksum_{}.vbq", std::process::id()));
+        for compressed in [false, true] {
+            let mut header = VBinseqHeader::with_capacity(512, true, compressed, false);
+            header.set_block_checksum(true);
+            let mut writer = VBinseqWriterBuilder::default()
+                .header(header)
+                .build(File::create(&path)?)?;
+            for flag in 0..100 {
+                writer.write_nucleotides_quality(flag, b"ACGTACGT", b"IIIIIIII")?;
+            }
+            writer.finish()?;
+            drop(writer);
+
+            // Intact blocks pass verification
+            let mut reader = MmapReader::new(&path)?;
+            reader.set_verify_checksums(true);
+            assert_eq!(reader.into_records().count(), 100);
+            let mut reader = MmapReader::new(&path)?;
+            reader.set_verify_checksums(true);
+            reader.process_parallel(Nothing, 2)?;
+            std::fs::remove_file(path.with_extension("vbq.vqi")).ok();
+        }
+
+        // Flip a quality score of the third block of an uncompressed file
+        let header = VBinseqHeader::with_capacity(512, true, false, false);
+        let mut checked = header;
+        checked.set_block_checksum(true);
+        let mut bytes = Vec::new();
+        let mut writer = VBinseqWriterBuilder::default()
+            .header(checked)
+            .build(&mut bytes)?;
+        for flag in 0..100 {
+            writer.write_nucleotides_quality(flag, b"ACGTACGT", b"IIIIIIII")?;
+        }
+        writer.finish()?;
+        drop(writer);
+        let range = BlockIndex::from_bytes(&bytes)?.ranges()[2];
+        bytes[range.start_offset as usize + SIZE_BLOCK_HEADER + 32] = b'#';
+        std::fs::write(&path, &bytes)?;
+
+        // Corruption goes unnoticed without verification
+        let records = MmapReader::new(&path)?
+            .into_records()
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(records.len(), 100);
+
+        // Every reader reports the mismatching block when verifying
+        let mut reader = MmapReader::new(&path)?;
+        reader.set_verify_checksums(true);
+        let error = reader
+            .into_records()
+            .find_map(|record| record.err())
+            .unwrap();
+        assert!(is_mismatch(&error), "{error}");
+        assert_eq!(error.context().and_then(|context| context.block), Some(2));
+        let mut reader = MmapReader::new(&path)?;
+        reader.set_verify_checksums(true);
+        let error = reader.process_parallel(Nothing, 2).unwrap_err();
+        assert!(is_mismatch(&error), "{error}");
+        std::fs::remove_file(path.with_extension("vbq.vqi")).ok();
+
+        let mut reader = MemoryReader::new(bytes.clone())?;
+        reader.set_verify_checksums(true);
+        let mut block = reader.new_block();
+        let error = loop {
+            if let Err(error) = reader.read_block_into(&mut block) {
+                break error;
+            }
+        };
+        assert!(is_mismatch(&error), "{error}");
+        let mut reader = StreamReader::new(bytes.as_slice())?;
+        reader.set_verify_checksums(true);
+        let error = loop {
+            if let Err(error) = reader.read_block_into(&mut block) {
+                break error;
+            }
+        };
+        assert!(is_mismatch(&error), "{error}");
+
+        // Fixed-length files cannot have block checksums
+        checked.set_fixed_length(true);
+        assert!(VBinseqWriterBuilder::default()
+            .header(checked)
+            .build(Vec::new())
+            .is_err());
+
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+    #[test]
+    fn test_corrupt_blocks() -> Result<()> {
+        use rand::{Rng, SeedableRng};
+
+        for (qual, compressed) in [(false, false), (true, false), (true, true)] {
+            let header = VBinseqHeader::with_capacity(512, qual, compressed, true);
+            let mut bytes = Vec::new();
+            let mut writer = VBinseqWriterBuilder::default()
+                .header(header)
+                .build(&mut bytes)?;
+            for flag in 0..100 {
+                let sequence = b"ACGTTGCAA".repeat(1 + flag as usize % 5);
+                if qual {
+                    let quality = vec![b'I'; sequence.len()];
+                    writer.write_nucleotides_quality_paired(
+                        flag, &sequence, b"ACGT", &quality, b"IIII",
+                    )?;
+                } else {
+                    writer.write_nucleotides_paired(flag, &sequence, b"ACGT")?;
+                }
+            }
+            writer.finish()?;
+            drop(writer);
+
+            // Reading with the wrong quality flag fails instead of misreading records
+            let mut tampered = bytes.clone();
+            let mut wrong = header;
+            wrong.set_qual(!qual);
+            wrong.write_bytes(&mut &mut tampered[..SIZE_HEADER])?;
+            let mut reader = MemoryReader::new(tampered)?;
+            let mut block = reader.new_block();
+            assert!(reader.read_block_into(&mut block).is_err());
+
+            // Random corruption never panics
+            let mut rng = rand::rngs::SmallRng::seed_from_u64(42);
+            for _ in 0..500 {
+                let mut tampered = bytes.clone();
+                for _ in 0..rng.gen_range(1..8) {
+                    let pos = rng.gen_range(SIZE_HEADER..tampered.len());
+                    tampered[pos] = rng.gen();
+                }
+                let _ = BlockIndex::from_bytes(&tampered);
+                let mut reader = MemoryReader::new(tampered)?;
+                while let Ok(true) = reader.read_block_into(&mut block) {
+                    for record in block.iter() {
+                        let mut sequence = Vec::new();
+                        record.decode_s(&mut sequence)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn test_empty_files() -> Result<()> {
+        #[derive(Clone)]
+        struct Counter(Arc<std::sync::atomic::AtomicUsize>);
+        impl ParallelProcessor for Counter {
+            fn process_record(&mut self, _record: RefRecord) -> Result<()> {
+                self.0.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                Ok(())
+            }
+        }
+
+        let dir = std::env::temp_dir().join(format!("vbq_empty_{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+
+        // Zero-byte files are rejected with a clear error
+        let path = dir.join("zero.vbq");
+        File::create(&path)?;
+        assert!(matches!(
+            MmapReader::new(&path)
+                .err()
+                .as_ref()
+                .map(crate::Error::root),
+            Some(crate::Error::ReadError(ReadError::EmptyFile))
+        ));
+        assert!(matches!(
+            BlockIndex::from_vbq(&path).unwrap_err().root(),
+            crate::Error::ReadError(ReadError::EmptyFile)
+        ));
+        assert!(matches!(
+            MemoryReader::new(Vec::new()),
+            Err(crate::Error::ReadError(ReadError::EmptyFile))
+        ));
+
+        // Header-only files (with and without a footer) hold no blocks
+        for footer in [false, true] {
+            let path = dir.join(format!("empty_{footer}.vbq"));
+            let mut header = VBinseqHeader::with_capacity(1024, false, true, false);
+            header.set_footer(footer);
+            let mut writer = VBinseqWriterBuilder::default()
+                .header(header)
+                .build(File::create(&path)?)?;
+            writer.finish()?;
+            drop(writer);
+
+            let mut reader = MmapReader::new(&path)?;
+            let mut block = reader.new_block();
+            assert!(!reader.read_block_into(&mut block)?);
+            assert!(!reader.read_block_into(&mut block)?);
+            assert!(reader.next_raw_block()?.is_none());
+            assert_eq!(BlockIndex::from_vbq(&path)?.n_blocks(), 0);
+            assert_eq!(reader.load_index()?.n_blocks(), 0);
+
+            let mut reader = MemoryReader::new(std::fs::read(&path)?)?;
+            assert!(!reader.read_block_into(&mut block)?);
+
+            let counter = Counter(Arc::default());
+            MmapReader::new(&path)?.process_parallel(counter.clone(), 4)?;
+            assert_eq!(counter.0.load(std::sync::atomic::Ordering::Relaxed), 0);
+        }
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn test_concatenated_files() -> Result<()> {
+        use rand::rngs::SmallRng;
+        use rand::SeedableRng;
+
+        use crate::testing::{random_records, read_records, write_records};
+
+        let rng = &mut SmallRng::seed_from_u64(3);
+        let mut with_footer = VBinseqHeader::with_capacity(512, true, true, false);
+        with_footer.set_footer(true);
+        let mut headers = [
+            with_footer,
+            VBinseqHeader::with_capacity(1024, false, false, true),
+            with_footer,
+            VBinseqHeader::with_capacity(256, false, true, false),
+            with_footer,
+        ];
+        headers[4].set_block(2048)?;
+        let (mut bytes, mut expected, mut n_blocks) = (Vec::new(), Vec::new(), 0);
+        for (i, &header) in headers.iter().enumerate() {
+            // The fourth member has no records
+            let records = random_records(rng, &header, if i == 3 { 0 } else { 100 });
+            let member = write_records(header, &records)?;
+            n_blocks += MemoryReader::new(member.clone())?.build_index()?.n_blocks();
+            expected.extend(read_records(member.clone())?);
+            bytes.extend(member);
+        }
+        let sequences = |records: Vec<OwnedRecord>| -> Vec<_> {
+            records
+                .into_iter()
+                .map(|record| (record.flag(), record.seq().to_vec(), record.xseq().to_vec()))
+                .collect()
+        };
+
+        // Members are read in order, each with its own header
+        let records = read_records(bytes.clone())?;
+        assert_eq!(sequences(records), sequences(expected));
+        let mut reader = MemoryReader::new(bytes.clone())?;
+        let mut headers_read = Vec::new();
+        let mut raw_blocks = 0;
+        while reader.next_raw_block()?.is_some() {
+            raw_blocks += 1;
+            headers_read.push(reader.header());
+        }
+        assert_eq!(raw_blocks, n_blocks);
+        headers_read.dedup();
+        assert_eq!(
+            headers_read,
+            [headers[0], headers[1], headers[2], headers[4]]
+        );
+
+        let path = std::env::temp_dir().join(format!("vbq_concat_{}.vbq", std::process::id()));
+        std::fs::write(&path, &bytes)?;
+        let mut reader = MmapReader::new(&path)?;
+        let mut block = reader.new_block();
+        let mut n_records = 0;
+        while reader.read_block_into(&mut block)? {
+            n_records += block.n_records();
+        }
+        assert_eq!(n_records, 400);
+        assert_eq!(reader.header(), headers[4]);
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn test_follow() -> Result<()> {
+        let path = std::env::temp_dir().join(format!("vbq_follow_{}.vbq", std::process::id()));
+        let mut header = VBinseqHeader::with_capacity(1024, false, true, false);
+        header.set_footer(true);
+        let mut writer = VBinseqWriterBuilder::default()
+            .header(header)
+            .flush_threshold(1)
+            .build(File::create(&path)?)?;
+        writer.write_nucleotides(0, b"ACGTACGT")?;
+
+        let options = FollowOptions {
+            poll_interval: std::time::Duration::from_millis(5),
+            timeout: Some(std::time::Duration::from_secs(30)),
+        };
+        let mut reader = MmapReader::follow(&path, options)?;
+        let mut block = reader.new_block();
+        let flags = std::thread::scope(|scope| -> Result<Vec<u64>> {
+            // Append the remaining records while the reader is waiting for them
+            let producer = scope.spawn(move || -> Result<()> {
+                for flag in 1..20 {
+                    std::thread::sleep(std::time::Duration::from_millis(2));
+                    writer.write_nucleotides(flag, b"ACGTACGT")?;
+                }
+                writer.finish()
+            });
+            let mut flags = Vec::new();
+            while reader.read_block_into(&mut block)? {
+                flags.extend(block.iter().map(|record| record.flag()));
+            }
+            producer.join().expect("producer panicked")?;
+            Ok(flags)
+        })?;
+        assert_eq!(flags, (0..20).collect::<Vec<u64>>());
+        assert!(reader.footer().is_some());
+
+        // Without a footer, reading stops once the file stops growing
+        let header = VBinseqHeader::with_capacity(1024, false, true, false);
+        let mut writer = VBinseqWriterBuilder::default()
+            .header(header)
+            .build(File::create(&path)?)?;
+        writer.write_nucleotides(0, b"ACGTACGT")?;
+        writer.finish()?;
+        let options = FollowOptions {
+            poll_interval: std::time::Duration::from_millis(5),
+            timeout: Some(std::time::Duration::from_millis(20)),
+        };
+        let mut reader = MmapReader::follow(&path, options)?;
+        assert!(reader.read_block_into(&mut block)?);
+        assert!(!reader.read_block_into(&mut block)?);
+        drop(writer);
+
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+    #[test]
+    fn test_decode_all() -> Result<()> {
+        let header = VBinseqHeader::with_capacity(1024, false, false, true);
+        let mut bytes = Vec::new();
+        let mut writer = VBinseqWriterBuilder::default()
+            .header(header)
+            .build(&mut bytes)?;
+        for i in 0..100usize {
+            let sequence = b"ACGTTGCA".repeat(1 + i % 9);
+            writer.write_nucleotides_paired(i as u64, &sequence[1..], &sequence[..5])?;
+        }
+        writer.finish()?;
+        drop(writer);
+
+        let mut reader = MemoryReader::new(bytes.clone())?;
+        let mut block = reader.new_block();
+        let mut other_reader = MemoryReader::new(bytes)?;
+        let mut other = other_reader.new_block();
+        let (mut sequences, mut offsets) = (Vec::new(), Vec::new());
+        let (mut xsequences, mut xoffsets) = (Vec::new(), Vec::new());
+        let mut dbuf = Vec::new();
+        while reader.read_block_into(&mut block)? {
+            assert!(other_reader.read_block_into(&mut other)?);
+            assert_eq!(block, other);
+            assert_eq!(block.iter().next(), other.iter().next());
+            block.decode_all(&mut sequences, &mut offsets)?;
+            block.decode_all_x(&mut xsequences, &mut xoffsets)?;
+            assert_eq!(offsets.len(), block.n_records() + 1);
+            assert_eq!(xoffsets.len(), block.n_records() + 1);
+            for (i, record) in (&block).into_iter().enumerate() {
+                dbuf.clear();
+                record.decode_s(&mut dbuf)?;
+                assert_eq!(
+                    dbuf,
+                    &sequences[offsets[i] as usize..offsets[i + 1] as usize]
+                );
+                dbuf.clear();
+                record.decode_x(&mut dbuf)?;
+                assert_eq!(
+                    dbuf,
+                    &xsequences[xoffsets[i] as usize..xoffsets[i + 1] as usize]
+                );
+                assert_eq!(record.xseq(), dbuf);
+                assert_eq!(record.xseq_string()?.as_bytes(), dbuf);
+                let sequence = b"ACGTTGCA".repeat(1 + record.flag() as usize % 9);
+                assert_eq!(record.seq(), &sequence[1..]);
+                assert_eq!(record.seq_string()?.as_bytes(), &sequence[1..]);
+            }
         }
+        Ok(())
+    }
+
+    /// Source returning at most 7 bytes per read, like a slow pipe
+    struct Trickle<'a>(&'a [u8]);
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let n = buf.len().min(self.0.len()).min(7);
+            buf[..n].copy_from_slice(&self.0[..n]);
+            self.0 = &self.0[n..];
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn test_stream_reader() -> Result<()> {
+        use rand::rngs::SmallRng;
+        use rand::SeedableRng;
+
+        use crate::testing::{headers, random_records, read_records, write_records};
+
+        /// Reads all records of a stream
+        fn stream_records(bytes: &[u8]) -> Result<Vec<OwnedRecord>> {
+            let mut reader = StreamReader::new(Trickle(bytes))?;
+            let mut block = reader.new_block();
+            let mut records = Vec::new();
+            while reader.read_block_into(&mut block)? {
+                records.extend(block.iter().map(|record| OwnedRecord::try_from(&record)));
+            }
+            records.into_iter().collect()
+        }
+
+        let rng = &mut SmallRng::seed_from_u64(23);
+        for header in headers() {
+            let records = random_records(rng, &header, 200);
+            let bytes = write_records(header, &records)?;
+
+            // Streams yield the records, sections, and footer of the file
+            assert_eq!(stream_records(&bytes)?, read_records(bytes.clone())?);
+            let memory = MemoryReader::new(bytes.clone())?;
+            let mut reader = StreamReader::new(Trickle(&bytes))?;
+            assert_eq!(reader.header(), header);
+            assert_eq!(reader.sections()?, memory.sections()?);
+            let mut n_raw = 0;
+            while reader.next_raw_block()?.is_some() {
+                n_raw += 1;
+            }
+            assert_eq!(n_raw, memory.build_index()?.n_blocks());
+            assert_eq!(reader.footer(), memory.footer());
+            assert_eq!(reader.position(), bytes.len() as u64);
+
+            // Concatenated files are read member by member
+            let concatenated = [bytes.clone(), bytes.clone()].concat();
+            assert_eq!(stream_records(&concatenated)?.len(), 400);
 
+            // Truncated streams are reported
+            let truncated = &bytes[..bytes.len() - SIZE_FOOTER - 10];
+            assert!(stream_records(truncated).is_err());
+            if header.has_footer() {
+                let e = stream_records(&bytes[..bytes.len() - SIZE_FOOTER]).unwrap_err();
+                assert!(matches!(
+                    e.root(),
+                    Error::ReadError(ReadError::MissingFooter)
+                ));
+            }
+        }
+        assert!(matches!(
+            StreamReader::new(Trickle(&[])),
+            Err(Error::ReadError(ReadError::EmptyFile))
+        ));
         Ok(())
     }
 }
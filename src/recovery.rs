@@ -0,0 +1,242 @@
+//! # Parsing Modes
+//!
+//! Readers parse files strictly by default: any structural anomaly, such as a corrupt
+//! block header or a record overrunning its block, is returned as an error. This is what
+//! archival verification needs. Best-effort recovery of damaged files needs the opposite,
+//! so readers can be switched to a permissive mode (see `ParseMode`).
+//!
+//! In permissive mode, readers skip what they cannot parse and continue with the next
+//! block:
+//!
+//! * Records of a block that could be parsed before an anomaly are kept, the remaining
+//!   records of the block are skipped.
+//! * Blocks with a corrupt header are skipped by searching for the next block header.
+//!   The number of records lost this way is unknown, so the global indices of subsequent
+//!   records are shifted.
+//!
+//! Every skipped anomaly is passed to a warning callback and counted (see `SkipCounts`).
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use vbinseq::{MmapReader, ParseMode};
+//!
+//! let mut reader = MmapReader::new("damaged.vbq").unwrap();
+//! reader.set_parse_mode(ParseMode::Permissive);
+//! reader.set_warning_callback(|e| eprintln!("Skipping: {e}"));
+//!
+//! let mut block = reader.new_block();
+//! while reader.read_block_into(&mut block).unwrap() {
+//!     println!("Recovered {} records", block.n_records());
+//! }
+//! println!("Skipped: {:?}", reader.skip_counts());
+//! ```
+
+use byteorder::{ByteOrder, LittleEndian};
+
+use crate::header::{BLOCK_MAGIC, SIZE_BLOCK_HEADER};
+use crate::reader::{read_next_block, RecordBlock};
+use crate::{BlockHeader, Error, Result, VBinseqHeader};
+
+/// How readers handle structural anomalies of a file
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ParseMode {
+    /// Any structural anomaly is an error
+    #[default]
+    Strict,
+
+    /// Malformed records and blocks are skipped, reporting them to the warning callback
+    Permissive,
+}
+
+/// Counters of the anomalies skipped in permissive mode
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SkipCounts {
+    /// Number of blocks with an anomaly (including blocks that were partially read)
+    pub blocks: u64,
+
+    /// Number of records declared by readable block headers that could not be read
+    pub records: u64,
+
+    /// Number of bytes skipped while searching for the next block after a corrupt block
+    /// header
+    pub bytes: u64,
+}
+
+/// Callback receiving the anomalies skipped in permissive mode
+pub(crate) type WarningCallback = Box<dyn FnMut(&Error) + Send>;
+
+/// Parsing mode of a reader along with its warning callback and counters
+#[derive(Default)]
+pub(crate) struct Recovery {
+    /// How anomalies are handled
+    pub(crate) mode: ParseMode,
+
+    /// Counters of the skipped anomalies
+    pub(crate) counts: SkipCounts,
+
+    /// Called with every skipped anomaly
+    pub(crate) callback: Option<WarningCallback>,
+}
+impl Recovery {
+    /// Fills a block with the next block of a file held in memory
+    ///
+    /// This behaves like `read_next_block` in strict mode. In permissive mode, anomalies
+    /// are reported and skipped, and `Ok(true)` is only returned for blocks holding at
+    /// least one readable record.
+    pub(crate) fn read_next_block(
+        &mut self,
+        bytes: &[u8],
+        end: usize,
+        header: &VBinseqHeader,
+        pos: &mut usize,
+        total: &mut u64,
+        block: &mut RecordBlock,
+    ) -> Result<bool> {
+        loop {
+            let (start, first_index) = (*pos, *total);
+            let error = match read_next_block(bytes, end, header, pos, total, block) {
+                Err(e) if self.mode == ParseMode::Permissive => e,
+                status => return status,
+            };
+            if let Some(callback) = &mut self.callback {
+                callback(&error);
+            }
+            self.counts.blocks += 1;
+
+            // Keep the records parsed before the anomaly if the block itself is intact
+            if let Some((declared, block_end)) = block_extent(bytes, end, header, start) {
+                self.counts.records += u64::from(declared) - block.n_records() as u64;
+                *pos = block_end;
+                *total = first_index + u64::from(declared);
+                if block.n_records() > 0 {
+                    block.update_index(first_index);
+                    return Ok(true);
+                }
+                continue;
+            }
+
+            // Otherwise continue with the next block header
+            block.clear();
+            *total = first_index;
+            *pos = find_block(bytes, end, start + 1);
+            self.counts.bytes += (*pos - start) as u64;
+        }
+    }
+}
+
+/// Returns the declared record count and end of the block at `start` if its header is
+/// valid and the block lies within the file
+fn block_extent(
+    bytes: &[u8],
+    end: usize,
+    header: &VBinseqHeader,
+    start: usize,
+) -> Option<(u32, usize)> {
+    let header_bytes = bytes.get(start..start.checked_add(SIZE_BLOCK_HEADER)?)?;
+    let block_header = BlockHeader::from_bytes(header_bytes.try_into().ok()?).ok()?;
+    let size = if header.compressed() {
+        usize::try_from(block_header.size).ok()?
+    } else {
+        header.block() as usize
+    };
+    let block_end = (start + SIZE_BLOCK_HEADER).checked_add(size)?;
+    (block_end <= end).then_some((block_header.records, block_end))
+}
+
+/// Returns the position of the next block magic number at or after `from`, or `end` if
+/// there is none
+fn find_block(bytes: &[u8], end: usize, from: usize) -> usize {
+    (from..end.saturating_sub(7))
+        .find(|&pos| LittleEndian::read_u64(&bytes[pos..pos + 8]) == BLOCK_MAGIC)
+        .unwrap_or(end)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::header::SIZE_HEADER;
+    use crate::{MemoryReader, VBinseqWriterBuilder};
+
+    /// Reads all record flags of a file, collecting the skipped anomalies
+    fn read_flags(bytes: Vec<u8>) -> Result<(Vec<u64>, SkipCounts, usize)> {
+        let warnings = Arc::new(Mutex::new(0));
+        let mut reader = MemoryReader::new(bytes)?;
+        reader.set_parse_mode(ParseMode::Permissive);
+        let counter = Arc::clone(&warnings);
+        reader.set_warning_callback(move |_| *counter.lock().unwrap() += 1);
+        let mut block = reader.new_block();
+        let mut flags = Vec::new();
+        while reader.read_block_into(&mut block)? {
+            flags.extend(block.iter().map(|record| record.flag()));
+        }
+        let n_warnings = *warnings.lock().unwrap();
+        Ok((flags, reader.skip_counts(), n_warnings))
+    }
+
+    #[test]
+    fn test_permissive() -> Result<()> {
+        for compressed in [false, true] {
+            let header = VBinseqHeader::with_capacity(512, false, compressed, false);
+            let mut bytes = Vec::new();
+            let mut writer = VBinseqWriterBuilder::default()
+                .header(header)
+                .build(&mut bytes)?;
+            for flag in 0..100 {
+                writer.write_nucleotides(flag, b"ACGTACGTACGTACGTACGT")?;
+            }
+            writer.finish()?;
+            drop(writer);
+            let index = MemoryReader::new(bytes.clone())?.build_index()?;
+            let ranges = index.ranges();
+            let records_per_block = ranges[0].block_records as u64;
+
+            // Intact files are read without warnings
+            let (flags, counts, n_warnings) = read_flags(bytes.clone())?;
+            assert_eq!(flags, (0..100).collect::<Vec<_>>());
+            assert_eq!((counts, n_warnings), (SkipCounts::default(), 0));
+
+            // A corrupt block header skips its block
+            let mut tampered = bytes.clone();
+            tampered[ranges[1].start_offset as usize] ^= 0xFF;
+            let (flags, counts, n_warnings) = read_flags(tampered.clone())?;
+            let expected: Vec<u64> = (0..records_per_block)
+                .chain(2 * records_per_block..100)
+                .collect();
+            assert_eq!(flags, expected);
+            assert_eq!(counts.blocks, 1);
+            assert_eq!(
+                counts.bytes,
+                ranges[2].start_offset - ranges[1].start_offset
+            );
+            assert_eq!(n_warnings, 1);
+
+            // Strict mode fails on the same file
+            let mut reader = MemoryReader::new(tampered)?;
+            let mut block = reader.new_block();
+            assert!(reader.read_block_into(&mut block).is_ok());
+            assert!(reader.read_block_into(&mut block).is_err());
+
+            // An overrunning record keeps the records before it (uncompressed only)
+            if !compressed {
+                let mut tampered = bytes.clone();
+                let record = ranges[0].start_offset as usize + SIZE_BLOCK_HEADER + 3 * 32;
+                LittleEndian::write_u64(&mut tampered[record + 8..record + 16], 1 << 20);
+                let (flags, counts, _) = read_flags(tampered)?;
+                let expected: Vec<u64> = (0..3).chain(records_per_block..100).collect();
+                assert_eq!(flags, expected);
+                assert_eq!(counts.blocks, 1);
+                assert_eq!(counts.records, records_per_block - 3);
+            }
+
+            // Truncated files end at the last complete block
+            let truncated = bytes[..SIZE_HEADER + 100].to_vec();
+            let (flags, counts, _) = read_flags(truncated)?;
+            assert!(flags.is_empty());
+            assert_eq!(counts.blocks, 1);
+        }
+        Ok(())
+    }
+}
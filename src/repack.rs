@@ -0,0 +1,276 @@
+//! # Block Repacking
+//!
+//! Blocks are only as full as the writer made them: flushing a writer often (see
+//! `VBinseqWriterBuilder::flush_interval`), dropping records from blocks in place, or
+//! concatenating many small files leaves blocks that hold few records. Uncompressed files pad every block to the full block size, so
+//! under-filled blocks waste disk space, and all files pay the per-block overhead of block
+//! headers and index entries. This module requires the `mmap` feature.
+//!
+//! `repack` rewrites a file with its records packed into as few blocks as possible,
+//! optionally changing the block size, and reports how full the blocks were before and
+//! after.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use vbinseq::repack::{repack, RepackOptions};
+//!
+//! let options = RepackOptions {
+//!     block_size: Some(1 << 20),
+//! };
+//! let report = repack("filtered.vbq", "packed.vbq", &options).unwrap();
+//! println!("Before:\n{}\nAfter:\n{}", report.before, report.after);
+//! ```
+
+use std::fmt;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+use crate::summary::FileSummary;
+use crate::{MmapReader, RefRecord, Result, VBinseqWriterBuilder};
+
+/// Options for repacking a file
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RepackOptions {
+    /// Block size of the output (in bytes)
+    ///
+    /// If None, the output keeps the block size of the input.
+    pub block_size: Option<u64>,
+}
+
+/// Statistics on how full the blocks of a file are
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FillStats {
+    /// Total size of the file in bytes
+    pub file_size: u64,
+
+    /// Number of record blocks in the file
+    pub n_blocks: usize,
+
+    /// Total number of records in the file
+    pub n_records: u64,
+
+    /// Total size of the records in bytes (before compression)
+    pub record_bytes: u64,
+
+    /// Total capacity of all blocks in bytes (the block size times the number of blocks)
+    pub capacity: u64,
+}
+impl FillStats {
+    /// Collects the fill statistics of a VBINSEQ file
+    ///
+    /// This decompresses every block to measure its records, but decodes no record.
+    ///
+    /// # Parameters
+    ///
+    /// * `path` - Path to the VBINSEQ file
+    ///
+    /// # Errors
+    ///
+    /// * I/O errors if the file cannot be read
+    /// * Parsing errors if the file has an invalid format
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let summary = FileSummary::from_path(&path)?;
+        let mut record_bytes = 0;
+        for location in MmapReader::new(&path)?.locations()? {
+            record_bytes += location?.len;
+        }
+        Ok(Self {
+            file_size: summary.file_size,
+            n_blocks: summary.n_blocks,
+            n_records: summary.n_records,
+            record_bytes,
+            capacity: summary.virtual_size,
+        })
+    }
+
+    /// Returns the fraction of the block capacity holding records
+    ///
+    /// Files without blocks report a fill of 1.0.
+    pub fn fill(&self) -> f64 {
+        if self.capacity == 0 {
+            1.0
+        } else {
+            self.record_bytes as f64 / self.capacity as f64
+        }
+    }
+}
+impl fmt::Display for FillStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "File size:       {} bytes", self.file_size)?;
+        writeln!(f, "Blocks:          {}", self.n_blocks)?;
+        writeln!(f, "Records:         {}", self.n_records)?;
+        writeln!(f, "Record bytes:    {}", self.record_bytes)?;
+        writeln!(f, "Capacity:        {} bytes", self.capacity)?;
+        write!(f, "Fill:            {:.1}%", 100.0 * self.fill())
+    }
+}
+
+/// Fill statistics of a file before and after repacking
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RepackReport {
+    /// Statistics of the input
+    pub before: FillStats,
+
+    /// Statistics of the output
+    pub after: FillStats,
+}
+
+/// Rewrites a file with its records packed into full blocks
+///
+/// The output has the header of the input (apart from the block size, if changed by the
+/// options), its header sections, and its records in their original order, including their
+/// flags and auxiliary values. The records of every block are re-encoded, so the output
+/// is compressed with the default compression level of the writer.
+///
+/// # Parameters
+///
+/// * `path` - Path to the VBINSEQ file to repack
+/// * `output` - Path of the file to write
+/// * `options` - Options for the output
+///
+/// # Returns
+///
+/// The fill statistics of the input and the output
+///
+/// # Errors
+///
+/// * `HeaderError::InvalidBlockSize` if the block size of the options is zero
+/// * I/O errors from reading the input or writing the output
+/// * Parsing errors if the input has an invalid format
+/// * Write errors if a record does not fit into a block of the new block size
+pub fn repack<P: AsRef<Path>, Q: AsRef<Path>>(
+    path: P,
+    output: Q,
+    options: &RepackOptions,
+) -> Result<RepackReport> {
+    let before = FillStats::from_path(&path)?;
+    copy_records(&path, &output, options, |_| true)?;
+    Ok(RepackReport {
+        before,
+        after: FillStats::from_path(&output)?,
+    })
+}
+
+/// Copies the records selected by a predicate into packed blocks of a new file
+///
+/// This is `repack` without the statistics, for rewrites that also drop records (see
+/// `tombstone::compact`). Returns the number of copied records.
+pub(crate) fn copy_records<P, Q, F>(
+    path: P,
+    output: Q,
+    options: &RepackOptions,
+    mut predicate: F,
+) -> Result<u64>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+    F: FnMut(&RefRecord) -> bool,
+{
+    let mut reader = MmapReader::new(&path)?;
+    let mut header = reader.header();
+    if let Some(block_size) = options.block_size {
+        header.set_block(block_size)?;
+    }
+    let mut writer = VBinseqWriterBuilder::default()
+        .header(header)
+        .sections(reader.sections()?)
+        .build(File::create(&output).map(BufWriter::new)?)?;
+
+    let mut block = reader.new_block();
+    let mut n_copied = 0;
+    while reader.read_block_into(&mut block)? {
+        for record in block.iter().filter(|record| predicate(record)) {
+            writer.write_stored(&record)?;
+            n_copied += 1;
+        }
+    }
+    writer.finish()?;
+    Ok(n_copied)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::SmallRng;
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::testing::{headers, random_records, read_records, stored_records, write_records};
+    use crate::{OwnedRecord, VBinseqHeader};
+
+    /// Returns the sequences and qualities of records to compare them regardless of index
+    fn contents(records: &[OwnedRecord]) -> Vec<(u64, [&[u8]; 4])> {
+        records
+            .iter()
+            .map(|r| (r.flag(), [r.seq(), r.xseq(), r.squal(), r.xqual()]))
+            .collect()
+    }
+
+    #[test]
+    fn test_repack() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("vbq_repack_{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let (path, underfilled, output) = (
+            dir.join("reads.vbq"),
+            dir.join("underfilled.vbq"),
+            dir.join("packed.vbq"),
+        );
+        let rng = &mut SmallRng::seed_from_u64(19);
+        for header in headers() {
+            let records = random_records(rng, &header, 300);
+            std::fs::write(&path, write_records(header, &records)?)?;
+            let stored = stored_records(&header, &records);
+
+            // Repacking a packed file keeps its records and its number of blocks
+            let report = repack(&path, &output, &RepackOptions::default())?;
+            assert_eq!(report.before.n_records, 300);
+            assert_eq!(report.after, FillStats::from_path(&output)?);
+            assert_eq!(report.after.n_blocks, report.before.n_blocks);
+            let repacked = read_records(std::fs::read(&output)?)?;
+            assert_eq!(contents(&repacked), contents(&stored));
+            assert_eq!(MmapReader::new(&output)?.header().block(), header.block());
+
+            // Blocks flushed early are packed again
+            let mut writer = VBinseqWriterBuilder::default()
+                .header(header)
+                .flush_threshold(header.block() as usize / 8)
+                .build(File::create(&underfilled)?)?;
+            for record in &records {
+                writer.write_record(record)?;
+            }
+            writer.finish()?;
+            let report = repack(&underfilled, &output, &RepackOptions::default())?;
+            assert_eq!(report.before.record_bytes, report.after.record_bytes);
+            assert!(report.after.n_blocks < report.before.n_blocks);
+            assert!(report.after.fill() > report.before.fill());
+            let repacked = read_records(std::fs::read(&output)?)?;
+            assert_eq!(contents(&repacked), contents(&stored));
+
+            // The block size can be changed
+            let options = RepackOptions {
+                block_size: Some(4 * header.block()),
+            };
+            let report = repack(&path, &output, &options)?;
+            assert!(report.after.n_blocks < report.before.n_blocks);
+            let repacked = read_records(std::fs::read(&output)?)?;
+            assert_eq!(contents(&repacked), contents(&stored));
+            assert_eq!(
+                MmapReader::new(&output)?.header().block(),
+                4 * header.block()
+            );
+        }
+
+        // Records that don't fit into the new block size are reported
+        let header = VBinseqHeader::with_capacity(1024, false, false, false);
+        let records = random_records(rng, &header, 10);
+        std::fs::write(&path, write_records(header, &records)?)?;
+        let options = RepackOptions {
+            block_size: Some(8),
+        };
+        assert!(repack(&path, &output, &options).is_err());
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
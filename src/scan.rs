@@ -0,0 +1,370 @@
+//! # Metadata-Only Scans
+//!
+//! Audits of record flags or lengths (e.g. counting the records of every read group, or a
+//! read length histogram) don't need the sequences or quality scores of the records. A
+//! scan iterates only the preambles of the records (their flag and lengths): blocks are
+//! still decompressed, but no payload is copied or decoded, which makes scans much faster
+//! than reading the records.
+//!
+//! Record locations (see `Locations`) are found the same way: each record is addressed by
+//! the index of its block, its byte offset within the decompressed block, and its stored
+//! size, which lets external tools build their own lookup structures over a file.
+//!
+//! Scans start at the first block of the file, independent of the position of the
+//! reader they are created from, and continue with the following members of concatenated
+//! files.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use vbinseq::MmapReader;
+//!
+//! let reader = MmapReader::new("example.vbq").unwrap();
+//! let mut longest = 0;
+//! for preamble in reader.preambles().unwrap() {
+//!     let preamble = preamble.unwrap();
+//!     longest = longest.max(preamble.slen + preamble.xlen);
+//! }
+//! println!("Longest record: {longest} bp");
+//! ```
+
+use std::sync::Arc;
+
+use byteorder::{ByteOrder, LittleEndian};
+
+use crate::footer::block_records;
+use crate::header::RECORD_NO_QUALITY;
+use crate::reader::{next_member, parse_file_layout, read_next_raw_block};
+use crate::{dictionary, BlockHeader, Result, VBinseqHeader};
+
+/// Metadata of a record, as stored before its payload
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Preamble {
+    /// Global index of the record within the file
+    pub index: u64,
+
+    /// Flag of the record
+    pub flag: u64,
+
+    /// Length of the primary sequence
+    pub slen: u64,
+
+    /// Length of the extended sequence (0 if not paired)
+    pub xlen: u64,
+}
+impl Preamble {
+    /// Parses the preamble of the stored bytes of a record
+    fn parse(
+        record: &[u8],
+        index: u64,
+        header: &VBinseqHeader,
+        block_header: &BlockHeader,
+    ) -> Self {
+        let flag = LittleEndian::read_u64(&record[..8]);
+        if header.is_fixed_length() {
+            return Self {
+                index,
+                flag,
+                slen: block_header.fixed_length(),
+                xlen: 0,
+            };
+        }
+        let mut slen = LittleEndian::read_u64(&record[8..16]);
+        if header.marks_quality() {
+            slen &= !RECORD_NO_QUALITY;
+        }
+        Self {
+            index,
+            flag,
+            slen,
+            xlen: LittleEndian::read_u64(&record[16..24]),
+        }
+    }
+}
+
+/// Walks the stored blocks of a VBINSEQ file held in memory, across concatenated members
+struct BlockScan<'a> {
+    /// Contents of the file
+    bytes: &'a [u8],
+    /// Header of the current member
+    header: VBinseqHeader,
+    /// Compression dictionaries of the current member
+    dictionaries: Arc<[Vec<u8>]>,
+    /// Position where the record blocks of the current member end
+    end: usize,
+    /// Position of the next block
+    pos: usize,
+    /// Number of records of all blocks before the next block
+    total: u64,
+    /// Number of blocks before the next block
+    blocks: u64,
+    /// Reusable buffer for decompressing blocks
+    dbuf: Vec<u8>,
+}
+impl<'a> BlockScan<'a> {
+    fn new(bytes: &'a [u8]) -> Result<Self> {
+        let (header, _, end) = parse_file_layout(bytes)?;
+        Ok(Self {
+            bytes,
+            header,
+            dictionaries: dictionary::load(bytes, &header)?,
+            end,
+            pos: header.data_offset(),
+            total: 0,
+            blocks: 0,
+            dbuf: Vec::new(),
+        })
+    }
+
+    /// Passes the stored records of the next block to `visit`
+    ///
+    /// `visit` receives the file and block headers, the index of the block and of its
+    /// first record, and the stored bytes of the records. Returns `false` if there are no
+    /// more blocks.
+    fn next_block<F>(&mut self, visit: F) -> Result<bool>
+    where
+        F: FnOnce(&VBinseqHeader, &BlockHeader, u64, u64, &[&[u8]]),
+    {
+        while let Some(member) = next_member(self.bytes, self.pos)? {
+            self.header = member.header;
+            self.dictionaries = member.dictionaries;
+            self.end = member.end;
+            self.pos = member.start + member.header.data_offset();
+        }
+        let first = self.total;
+        let Some(raw) = read_next_raw_block(
+            self.bytes,
+            self.end,
+            &self.header,
+            &mut self.pos,
+            &mut self.total,
+        )?
+        else {
+            return Ok(false);
+        };
+        let records = block_records(
+            &raw.header,
+            raw.data,
+            &self.header,
+            &self.dictionaries,
+            &mut self.dbuf,
+        )?;
+        visit(&self.header, &raw.header, self.blocks, first, &records);
+        self.blocks += 1;
+        Ok(true)
+    }
+}
+
+/// Iterator over the record preambles of a VBINSEQ file held in memory
+///
+/// Created with `Preambles::new`, `MmapReader::preambles`, or `MemoryReader::preambles`.
+/// The iterator stops after the first error.
+pub struct Preambles<'a> {
+    /// Walk over the blocks of the file
+    scan: BlockScan<'a>,
+    /// Preambles of the current block (in reverse order)
+    block: Vec<Preamble>,
+    /// Whether the scan has ended (after the last block or an error)
+    done: bool,
+}
+impl<'a> Preambles<'a> {
+    /// Creates a scan over the records of a VBINSEQ file
+    ///
+    /// # Parameters
+    ///
+    /// * `bytes` - The full contents of the file (e.g. a memory map)
+    ///
+    /// # Errors
+    ///
+    /// * Header and footer validation errors if the file cannot be parsed
+    pub fn new(bytes: &'a [u8]) -> Result<Self> {
+        Ok(Self {
+            scan: BlockScan::new(bytes)?,
+            block: Vec::new(),
+            done: false,
+        })
+    }
+}
+impl Iterator for Preambles<'_> {
+    type Item = Result<Preamble>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            if let Some(preamble) = self.block.pop() {
+                return Some(Ok(preamble));
+            }
+            let block = &mut self.block;
+            let visit = |header: &VBinseqHeader,
+                         block_header: &BlockHeader,
+                         _,
+                         first,
+                         records: &[&[u8]]| {
+                block.extend(records.iter().enumerate().rev().map(|(i, record)| {
+                    Preamble::parse(record, first + i as u64, header, block_header)
+                }));
+            };
+            match self.scan.next_block(visit) {
+                Ok(true) => {}
+                Ok(false) => self.done = true,
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Location of the stored bytes of a record within its block
+///
+/// Offsets refer to the decompressed contents of the block, which start directly after
+/// the block header in uncompressed files. Together with a block index (see `BlockIndex`),
+/// locations allow external lookup structures to address single records.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecordLocation {
+    /// Index of the block within the file (0-based, across concatenated members)
+    pub block: u64,
+
+    /// Offset of the record within the decompressed block (in bytes)
+    pub offset: u64,
+
+    /// Size of the stored record, including its flag and lengths (in bytes)
+    pub len: u64,
+}
+
+/// Iterator over the locations of the records of a VBINSEQ file held in memory
+///
+/// Created with `Locations::new`, `MmapReader::locations`, or `MemoryReader::locations`.
+/// Locations are yielded in the order of the records, so the n-th location belongs to the
+/// record with index n. The iterator stops after the first error.
+pub struct Locations<'a> {
+    /// Walk over the blocks of the file
+    scan: BlockScan<'a>,
+    /// Locations of the current block (in reverse order)
+    block: Vec<RecordLocation>,
+    /// Whether the scan has ended (after the last block or an error)
+    done: bool,
+}
+impl<'a> Locations<'a> {
+    /// Creates an iterator over the record locations of a VBINSEQ file
+    ///
+    /// # Parameters
+    ///
+    /// * `bytes` - The full contents of the file (e.g. a memory map)
+    ///
+    /// # Errors
+    ///
+    /// * Header and footer validation errors if the file cannot be parsed
+    pub fn new(bytes: &'a [u8]) -> Result<Self> {
+        Ok(Self {
+            scan: BlockScan::new(bytes)?,
+            block: Vec::new(),
+            done: false,
+        })
+    }
+}
+impl Iterator for Locations<'_> {
+    type Item = Result<RecordLocation>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            if let Some(location) = self.block.pop() {
+                return Some(Ok(location));
+            }
+            let block = &mut self.block;
+            let visit = |_: &VBinseqHeader, _: &BlockHeader, id, _, records: &[&[u8]]| {
+                let mut offset = 0;
+                let start = block.len();
+                for record in records {
+                    let len = record.len() as u64;
+                    block.push(RecordLocation {
+                        block: id,
+                        offset,
+                        len,
+                    });
+                    offset += len;
+                }
+                block[start..].reverse();
+            };
+            match self.scan.next_block(visit) {
+                Ok(true) => {}
+                Ok(false) => self.done = true,
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::SmallRng;
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::testing::{headers, random_records, write_records};
+    use crate::MemoryReader;
+
+    #[test]
+    fn test_preambles() -> Result<()> {
+        let rng = &mut SmallRng::seed_from_u64(17);
+        for header in headers() {
+            let records = random_records(rng, &header, 300);
+            let bytes = write_records(header, &records)?;
+
+            // Preambles match the records read in full
+            let mut reader = MemoryReader::new(bytes.clone())?;
+            let mut block = reader.new_block();
+            let mut expected = Vec::new();
+            while reader.read_block_into(&mut block)? {
+                expected.extend(block.iter().map(|record| Preamble {
+                    index: record.index(),
+                    flag: record.flag(),
+                    slen: record.slen(),
+                    xlen: record.xlen(),
+                }));
+            }
+            let preambles = reader.preambles()?.collect::<Result<Vec<_>>>()?;
+            assert_eq!(preambles, expected);
+
+            // Scans continue with the following members of concatenated files
+            let concatenated = [bytes.clone(), bytes].concat();
+            assert_eq!(Preambles::new(&concatenated)?.count(), 2 * expected.len());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_locations() -> Result<()> {
+        let rng = &mut SmallRng::seed_from_u64(18);
+        for header in headers() {
+            let records = random_records(rng, &header, 300);
+            let bytes = write_records(header, &records)?;
+
+            // Locations address the stored bytes of every record within its block
+            let dictionaries = dictionary::load(&bytes, &header)?;
+            let mut reader = MemoryReader::new(bytes.clone())?;
+            let mut locations = Locations::new(&bytes)?;
+            let (mut id, mut dbuf) = (0, Vec::new());
+            while let Some(raw) = reader.next_raw_block()? {
+                let stored =
+                    block_records(&raw.header, raw.data, &header, &dictionaries, &mut dbuf)?;
+                let block = stored.concat();
+                for record in stored {
+                    let location = locations.next().unwrap()?;
+                    assert_eq!(location.block, id);
+                    let start = location.offset as usize;
+                    assert_eq!(&block[start..start + location.len as usize], record);
+                }
+                id += 1;
+            }
+            assert!(locations.next().is_none());
+            assert_eq!(reader.locations()?.count(), records.len());
+        }
+        Ok(())
+    }
+}
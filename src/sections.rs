@@ -0,0 +1,311 @@
+//! # Header Sections
+//!
+//! Files can store variable-length sections between the file header and the first record
+//! block, e.g. a read-group table. The total size of the sections is recorded in the
+//! header (see `VBinseqHeader::sections_size`), so readers find the first record block at
+//! `VBinseqHeader::data_offset` without parsing the sections.
+//!
+//! Each section is stored as a u16 tag, the u32 size of its payload, and the payload (all
+//! little-endian). Sections with unknown tags are skipped, so new kinds of sections can be
+//! added without breaking readers.
+//!
+//! | Tag | Section                                                |
+//! | --- | ------------------------------------------------------ |
+//! | 1   | Read-group table (see the `read_group` module)         |
+//! | 2   | Compression dictionaries (see the `dictionary` module) |
+//! | 3   | User-defined key/value metadata                        |
+//! | 4   | Quality score bins (see `QualityBins`)                 |
+//!
+//! Writers write the sections passed to `VBinseqWriterBuilder::sections`, and readers
+//! parse them with `MmapReader::sections` or `MemoryReader::sections`. The metadata
+//! section stores provenance of the data (e.g. the sample, instrument, or command line)
+//! and can also be set with `VBinseqWriterBuilder::metadata` and read with
+//! `MmapReader::metadata` or `MemoryReader::metadata`. The quality bins section records
+//! that the quality scores were binned at write time (see
+//! `VBinseqWriterBuilder::quality_bins`). Files without sections keep
+//! format 1 headers, so readers unaware of sections can read them.
+//!
+//! # Example
+//!
+//! ```rust
+//! use vbinseq::read_group::ReadGroup;
+//! use vbinseq::sections::HeaderSections;
+//!
+//! let sections = HeaderSections {
+//!     read_groups: vec![ReadGroup::new("lane1").with_tag("SM", "sample1")],
+//!     ..HeaderSections::default()
+//! };
+//! let bytes = sections.to_bytes();
+//! assert_eq!(HeaderSections::from_bytes(&bytes).unwrap(), sections);
+//! ```
+
+use std::io::Read;
+
+use byteorder::{ByteOrder, LittleEndian};
+
+use crate::dictionary;
+use crate::error::{HeaderError, ReadError, Result};
+use crate::header::SIZE_HEADER;
+use crate::quality::QualityBins;
+use crate::read_group::{self, ReadGroup};
+use crate::VBinseqHeader;
+
+/// Tag of the read-group table section
+const TAG_READ_GROUPS: u16 = 1;
+
+/// Tag of the compression dictionaries section
+const TAG_DICTIONARIES: u16 = 2;
+
+/// Tag of the key/value metadata section
+const TAG_METADATA: u16 = 3;
+
+/// Tag of the quality score bins section
+const TAG_QUALITY_BINS: u16 = 4;
+
+/// Size of the tag and payload size preceding every section
+const SIZE_SECTION_HEADER: usize = 6;
+
+/// Variable-length sections stored between the file header and the first record block
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HeaderSections {
+    /// Read groups referenced by the records (see the `read_group` module)
+    pub read_groups: Vec<ReadGroup>,
+
+    /// zstd dictionaries the record blocks can be compressed with (see the `dictionary`
+    /// module)
+    pub dictionaries: Vec<Vec<u8>>,
+
+    /// User-defined metadata as (key, value) pairs, e.g. `("sample", "NA12878")`
+    pub metadata: Vec<(String, String)>,
+
+    /// Bins the quality scores were quantized to before storage, if any
+    pub quality_bins: Option<QualityBins>,
+}
+impl HeaderSections {
+    /// Returns `true` if there are no sections to store
+    pub fn is_empty(&self) -> bool {
+        self.read_groups.is_empty()
+            && self.dictionaries.is_empty()
+            && self.metadata.is_empty()
+            && self.quality_bins.is_none()
+    }
+
+    /// Returns the value of the first metadata entry with the given key
+    pub fn metadata_value(&self, key: &str) -> Option<&str> {
+        self.metadata
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Encodes the sections as they are stored after the file header
+    ///
+    /// Returns an empty buffer if there are no sections.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        if !self.read_groups.is_empty() {
+            let mut payload = Vec::new();
+            read_group::encode_table(&self.read_groups, &mut payload);
+            push_section(&mut bytes, TAG_READ_GROUPS, &payload);
+        }
+        if !self.dictionaries.is_empty() {
+            let mut payload = Vec::new();
+            dictionary::encode_table(&self.dictionaries, &mut payload);
+            push_section(&mut bytes, TAG_DICTIONARIES, &payload);
+        }
+        if !self.metadata.is_empty() {
+            let mut payload = Vec::new();
+            encode_metadata(&self.metadata, &mut payload);
+            push_section(&mut bytes, TAG_METADATA, &payload);
+        }
+        if let Some(bins) = &self.quality_bins {
+            let mut payload = Vec::new();
+            encode_quality_bins(bins, &mut payload);
+            push_section(&mut bytes, TAG_QUALITY_BINS, &payload);
+        }
+        bytes
+    }
+
+    /// Parses sections as they are stored after the file header
+    ///
+    /// # Errors
+    ///
+    /// * `HeaderError::InvalidSection` - If a section is truncated or cannot be parsed
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let mut sections = Self::default();
+        let mut pos = 0;
+        while pos < bytes.len() {
+            if pos + SIZE_SECTION_HEADER > bytes.len() {
+                return Err(HeaderError::InvalidSection(pos).into());
+            }
+            let tag = LittleEndian::read_u16(&bytes[pos..pos + 2]);
+            let size = LittleEndian::read_u32(&bytes[pos + 2..pos + 6]) as usize;
+            let start = pos + SIZE_SECTION_HEADER;
+            let Some(payload) = bytes.get(start..start.saturating_add(size)) else {
+                return Err(HeaderError::InvalidSection(pos).into());
+            };
+            match tag {
+                TAG_READ_GROUPS => {
+                    sections.read_groups = read_group::decode_table(payload)
+                        .ok_or(HeaderError::InvalidSection(pos))?;
+                }
+                TAG_DICTIONARIES => {
+                    sections.dictionaries = dictionary::decode_table(payload)
+                        .ok_or(HeaderError::InvalidSection(pos))?;
+                }
+                TAG_METADATA => {
+                    sections.metadata =
+                        decode_metadata(payload).ok_or(HeaderError::InvalidSection(pos))?;
+                }
+                TAG_QUALITY_BINS => {
+                    sections.quality_bins =
+                        Some(decode_quality_bins(payload).ok_or(HeaderError::InvalidSection(pos))?);
+                }
+                _ => {}
+            }
+            pos = start + size;
+        }
+        Ok(sections)
+    }
+
+    /// Parses the sections of a file held in memory
+    ///
+    /// # Parameters
+    ///
+    /// * `bytes` - The full contents of the file (e.g. a memory map)
+    /// * `header` - The header of the file
+    pub(crate) fn from_file_bytes(bytes: &[u8], header: &VBinseqHeader) -> Result<Self> {
+        match bytes.get(SIZE_HEADER..header.data_offset()) {
+            Some(sections) => Self::from_bytes(sections),
+            None => Err(ReadError::UnexpectedEndOfFile(bytes.len()).into()),
+        }
+    }
+
+    /// Reads the sections following a header from a reader
+    ///
+    /// The reader must be positioned right after the header, and is left at the first
+    /// record block.
+    ///
+    /// # Parameters
+    ///
+    /// * `reader` - The reader to read the sections from
+    /// * `header` - The header read from the reader
+    pub fn from_reader<R: Read>(reader: &mut R, header: &VBinseqHeader) -> Result<Self> {
+        let mut bytes = vec![0; header.sections_size()];
+        reader.read_exact(&mut bytes)?;
+        Self::from_bytes(&bytes)
+    }
+}
+
+/// Appends a tagged section to a buffer
+fn push_section(bytes: &mut Vec<u8>, tag: u16, payload: &[u8]) {
+    let mut section_header = [0u8; SIZE_SECTION_HEADER];
+    LittleEndian::write_u16(&mut section_header[..2], tag);
+    LittleEndian::write_u32(&mut section_header[2..], payload.len() as u32);
+    bytes.extend_from_slice(&section_header);
+    bytes.extend_from_slice(payload);
+}
+
+/// Encodes metadata as the payload of its section
+///
+/// The metadata is stored as the u32 number of entries followed by the key and value of
+/// every entry (strings are prefixed with their u32 length).
+fn encode_metadata(metadata: &[(String, String)], payload: &mut Vec<u8>) {
+    payload.extend_from_slice(&(metadata.len() as u32).to_le_bytes());
+    for (key, value) in metadata {
+        push_string(payload, key);
+        push_string(payload, value);
+    }
+}
+
+/// Decodes metadata from the payload of its section
+///
+/// Returns `None` if the payload is invalid.
+fn decode_metadata(payload: &[u8]) -> Option<Vec<(String, String)>> {
+    let mut cursor = SectionCursor::new(payload);
+    let n_entries = cursor.u32()?;
+    let mut metadata = Vec::new();
+    for _ in 0..n_entries {
+        metadata.push((cursor.string()?, cursor.string()?));
+    }
+    cursor.is_done().then_some(metadata)
+}
+
+/// Encodes quality score bins as the payload of their section
+///
+/// The bins are stored as the u32 number of bins followed by the lower bound and
+/// representative score of every bin (one byte each).
+fn encode_quality_bins(bins: &QualityBins, payload: &mut Vec<u8>) {
+    payload.extend_from_slice(&(bins.bins().len() as u32).to_le_bytes());
+    for &(lower, score) in bins.bins() {
+        payload.extend_from_slice(&[lower, score]);
+    }
+}
+
+/// Decodes quality score bins from the payload of their section
+///
+/// Returns `None` if the payload is invalid.
+fn decode_quality_bins(payload: &[u8]) -> Option<QualityBins> {
+    let mut cursor = SectionCursor::new(payload);
+    let n_bins = cursor.u32()?;
+    let mut bins = Vec::new();
+    for _ in 0..n_bins {
+        let bin = cursor.take(2)?;
+        bins.push((bin[0], bin[1]));
+    }
+    if !cursor.is_done() {
+        return None;
+    }
+    QualityBins::new(bins).ok()
+}
+
+/// Cursor over the payload of a section
+///
+/// All reads return `None` once the payload is exhausted.
+pub(crate) struct SectionCursor<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+impl<'a> SectionCursor<'a> {
+    pub(crate) fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, pos: 0 }
+    }
+
+    /// Returns whether the whole payload has been read
+    pub(crate) fn is_done(&self) -> bool {
+        self.pos == self.bytes.len()
+    }
+
+    /// Reads a little-endian u32
+    pub(crate) fn u32(&mut self) -> Option<u32> {
+        self.take(4).map(LittleEndian::read_u32)
+    }
+
+    /// Reads a UTF-8 string prefixed with its u32 length
+    pub(crate) fn string(&mut self) -> Option<String> {
+        String::from_utf8(self.bytes()?.to_vec()).ok()
+    }
+
+    /// Reads bytes prefixed with their u32 length
+    pub(crate) fn bytes(&mut self) -> Option<&'a [u8]> {
+        let len = self.u32()? as usize;
+        self.take(len)
+    }
+
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        let bytes = self.bytes.get(self.pos..self.pos.checked_add(n)?)?;
+        self.pos += n;
+        Some(bytes)
+    }
+}
+
+/// Appends a UTF-8 string prefixed with its u32 length to a payload
+pub(crate) fn push_string(payload: &mut Vec<u8>, string: &str) {
+    push_bytes(payload, string.as_bytes());
+}
+
+/// Appends bytes prefixed with their u32 length to a payload
+pub(crate) fn push_bytes(payload: &mut Vec<u8>, bytes: &[u8]) {
+    payload.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+    payload.extend_from_slice(bytes);
+}
@@ -0,0 +1,555 @@
+//! # Synthetic Data
+//!
+//! Benchmarks of readers and writers and test fixtures need files with realistic record
+//! lengths, pairing, and quality scores, but shipping real sequencing data is often not
+//! possible. This module generates such files deterministically from a seed. It requires
+//! the `policy-rand` feature.
+//!
+//! Reads are sampled from a random reference sequence, so files compress like sequencing
+//! data of a small genome rather than like random noise. Paired reads are the two ends of
+//! a fragment, with the extended sequence on the reverse strand. Sequencing errors
+//! substitute nucleotides, and `N` calls are stored as decided by the encoding policy of
+//! the options. The flag of every record holds the position of its fragment on the
+//! reference (see `Simulator::reference`), so simulated files carry their own truth.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use vbinseq::simulate::{simulate, LengthDistribution, SimulationOptions};
+//!
+//! let options = SimulationOptions {
+//!     n_records: 1_000_000,
+//!     length: LengthDistribution::Uniform { min: 100, max: 150 },
+//!     paired: true,
+//!     seed: 7,
+//!     ..SimulationOptions::default()
+//! };
+//! simulate("simulated.vbq", &options).unwrap();
+//! ```
+
+use std::path::Path;
+
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
+
+use crate::error::WriteError;
+use crate::header::BLOCK_SIZE;
+use crate::merge::complement;
+use crate::{OwnedRecord, Policy, Result, VBinseqHeader, VBinseqWriterBuilder};
+
+/// Phred score of `N` calls
+pub const N_QUALITY: u8 = 2;
+
+/// Highest Phred score that can be stored as a printable Phred+33 character
+pub const MAX_QUALITY: u8 = 93;
+
+/// Distribution of the lengths of simulated reads
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LengthDistribution {
+    /// All reads have the same length (e.g. short reads)
+    Fixed(usize),
+
+    /// Lengths are drawn uniformly from `min..=max` (e.g. trimmed short reads)
+    Uniform {
+        /// Shortest length
+        min: usize,
+        /// Longest length
+        max: usize,
+    },
+
+    /// Lengths are drawn from a normal distribution and clamped to `min..=max` (e.g. long
+    /// reads)
+    Normal {
+        /// Mean length
+        mean: f64,
+        /// Standard deviation of the length
+        sd: f64,
+        /// Shortest length
+        min: usize,
+        /// Longest length
+        max: usize,
+    },
+}
+impl LengthDistribution {
+    /// Draws a read length
+    pub fn sample<R: Rng>(&self, rng: &mut R) -> usize {
+        match *self {
+            Self::Fixed(len) => len,
+            Self::Uniform { min, max } => rng.gen_range(min..=max),
+            Self::Normal { mean, sd, min, max } => {
+                (normal(rng, mean, sd).round().max(0.0) as usize).clamp(min, max)
+            }
+        }
+    }
+
+    /// Returns the longest length of the distribution
+    pub fn max(&self) -> usize {
+        match *self {
+            Self::Fixed(len) => len,
+            Self::Uniform { max, .. } | Self::Normal { max, .. } => max,
+        }
+    }
+
+    /// Checks that lengths can be drawn from the distribution
+    fn validate(&self) -> Result<()> {
+        let valid = match *self {
+            Self::Fixed(_) => true,
+            Self::Uniform { min, max } => min <= max,
+            Self::Normal { mean, sd, min, max } => {
+                min <= max && mean.is_finite() && sd.is_finite() && sd >= 0.0
+            }
+        };
+        if valid {
+            Ok(())
+        } else {
+            Err(
+                WriteError::InvalidSimulationOptions(format!("length distribution {self:?}"))
+                    .into(),
+            )
+        }
+    }
+}
+
+/// Quality scores of simulated reads (as Phred scores)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum QualityProfile {
+    /// All nucleotides have the same score
+    Constant(u8),
+
+    /// Scores are drawn uniformly from `min..=max`
+    Uniform {
+        /// Lowest score
+        min: u8,
+        /// Highest score
+        max: u8,
+    },
+
+    /// Scores decline linearly along the read, with normally distributed noise
+    ///
+    /// This resembles the quality profile of short-read sequencing.
+    Decay {
+        /// Mean score of the first nucleotide
+        start: u8,
+        /// Mean score of the last nucleotide
+        end: u8,
+        /// Standard deviation of the noise
+        sd: f64,
+    },
+}
+impl QualityProfile {
+    /// Draws the Phred score of the nucleotide at a position of a read of length `len`
+    pub fn sample<R: Rng>(&self, rng: &mut R, position: usize, len: usize) -> u8 {
+        match *self {
+            Self::Constant(score) => score,
+            Self::Uniform { min, max } => rng.gen_range(min..=max),
+            Self::Decay { start, end, sd } => {
+                let progress = position as f64 / len.saturating_sub(1).max(1) as f64;
+                let mean = f64::from(start) + (f64::from(end) - f64::from(start)) * progress;
+                normal(rng, mean, sd)
+                    .round()
+                    .clamp(0.0, f64::from(MAX_QUALITY)) as u8
+            }
+        }
+    }
+
+    /// Checks that scores can be drawn from the profile
+    fn validate(&self) -> Result<()> {
+        let valid = match *self {
+            Self::Constant(score) => score <= MAX_QUALITY,
+            Self::Uniform { min, max } => min <= max && max <= MAX_QUALITY,
+            Self::Decay { start, end, sd } => {
+                start.max(end) <= MAX_QUALITY && sd.is_finite() && sd >= 0.0
+            }
+        };
+        if valid {
+            Ok(())
+        } else {
+            Err(WriteError::InvalidSimulationOptions(format!("quality profile {self:?}")).into())
+        }
+    }
+}
+
+/// Options for simulating records
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SimulationOptions {
+    /// Number of records to simulate
+    pub n_records: u64,
+
+    /// Distribution of the read lengths (of both mates if paired)
+    pub length: LengthDistribution,
+
+    /// Whether records are paired
+    pub paired: bool,
+
+    /// Probability of a nucleotide being substituted by another nucleotide
+    pub error_rate: f64,
+
+    /// Probability of a nucleotide being called as `N`
+    pub n_rate: f64,
+
+    /// Quality scores of the records (None for records without quality scores)
+    pub quality: Option<QualityProfile>,
+
+    /// Length of the random reference reads are sampled from
+    ///
+    /// The reference is extended to the longest read length if it is shorter.
+    pub reference_len: usize,
+
+    /// Seed of the random number generator
+    pub seed: u64,
+
+    /// Virtual block size of the output file in bytes
+    pub block_size: u64,
+
+    /// Whether the blocks of the output file are ZSTD compressed
+    pub compressed: bool,
+
+    /// Policy for encoding `N` calls
+    pub policy: Policy,
+}
+impl Default for SimulationOptions {
+    /// Creates options for 10,000 single-end 150 bp reads with an Illumina-like quality
+    /// profile, an error rate of 0.1%, and an `N` rate of 0.01%, sampled from a 1 Mbp
+    /// reference
+    fn default() -> Self {
+        Self {
+            n_records: 10_000,
+            length: LengthDistribution::Fixed(150),
+            paired: false,
+            error_rate: 0.001,
+            n_rate: 0.0001,
+            quality: Some(QualityProfile::Decay {
+                start: 38,
+                end: 25,
+                sd: 3.0,
+            }),
+            reference_len: 1_000_000,
+            seed: 0,
+            block_size: BLOCK_SIZE,
+            compressed: cfg!(feature = "compression"),
+            policy: Policy::RandomDraw,
+        }
+    }
+}
+impl SimulationOptions {
+    /// Returns the header of files holding the simulated records
+    pub fn header(&self) -> VBinseqHeader {
+        VBinseqHeader::with_capacity(
+            self.block_size,
+            self.quality.is_some(),
+            self.compressed,
+            self.paired,
+        )
+    }
+
+    /// Checks that records can be simulated with the options
+    fn validate(&self) -> Result<()> {
+        self.length.validate()?;
+        if let Some(quality) = self.quality {
+            quality.validate()?;
+        }
+        for (name, rate) in [("error rate", self.error_rate), ("N rate", self.n_rate)] {
+            if !(0.0..=1.0).contains(&rate) {
+                return Err(WriteError::InvalidSimulationOptions(format!(
+                    "{name} {rate} is not a probability"
+                ))
+                .into());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Iterator over simulated records
+///
+/// The records only depend on the options, so simulators with the same options yield the
+/// same records.
+pub struct Simulator {
+    /// Options of the simulation
+    options: SimulationOptions,
+
+    /// Random number generator seeded by the options
+    rng: SmallRng,
+
+    /// Reference the reads are sampled from
+    reference: Vec<u8>,
+
+    /// Number of records simulated so far
+    n_simulated: u64,
+}
+impl Simulator {
+    /// Creates a simulator and draws its reference
+    ///
+    /// # Parameters
+    ///
+    /// * `options` - Options of the simulation
+    ///
+    /// # Errors
+    ///
+    /// * `WriteError::InvalidSimulationOptions` if a distribution or rate of the options
+    ///   is invalid
+    pub fn new(options: SimulationOptions) -> Result<Self> {
+        options.validate()?;
+        let mut rng = SmallRng::seed_from_u64(options.seed);
+        let reference_len = options.reference_len.max(options.length.max());
+        let reference = (0..reference_len)
+            .map(|_| b"ACGT"[rng.gen_range(0..4)])
+            .collect();
+        Ok(Self {
+            options,
+            rng,
+            reference,
+            n_simulated: 0,
+        })
+    }
+
+    /// Returns the reference the reads are sampled from
+    pub fn reference(&self) -> &[u8] {
+        &self.reference
+    }
+
+    /// Copies a read from the reference and adds sequencing errors and `N` calls
+    fn read(&mut self, start: usize, len: usize, reverse: bool) -> Vec<u8> {
+        let segment = &self.reference[start..start + len];
+        let mut read: Vec<u8> = if reverse {
+            segment.iter().rev().map(|&base| complement(base)).collect()
+        } else {
+            segment.to_vec()
+        };
+        for base in read.iter_mut() {
+            if self.rng.gen_bool(self.options.error_rate) {
+                // Substitute with one of the three other nucleotides
+                let offset = self.rng.gen_range(1..4);
+                let index = b"ACGT".iter().position(|b| b == base).unwrap_or(0);
+                *base = b"ACGT"[(index + offset) % 4];
+            }
+            if self.rng.gen_bool(self.options.n_rate) {
+                *base = b'N';
+            }
+        }
+        read
+    }
+
+    /// Draws the quality scores of a read (as Phred+33 characters)
+    fn quality(&mut self, read: &[u8]) -> Vec<u8> {
+        let Some(profile) = self.options.quality else {
+            return Vec::new();
+        };
+        let len = read.len();
+        read.iter()
+            .enumerate()
+            .map(|(position, &base)| {
+                let score = if base == b'N' {
+                    N_QUALITY
+                } else {
+                    profile.sample(&mut self.rng, position, len)
+                };
+                score + 33
+            })
+            .collect()
+    }
+}
+impl Iterator for Simulator {
+    type Item = OwnedRecord;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.n_simulated == self.options.n_records {
+            return None;
+        }
+        self.n_simulated += 1;
+
+        // Mates are the two ends of a fragment spanning both of them
+        let slen = self.options.length.sample(&mut self.rng);
+        let xlen = if self.options.paired {
+            self.options.length.sample(&mut self.rng)
+        } else {
+            0
+        };
+        let fragment_len = slen.max(xlen);
+        let start = self.rng.gen_range(0..=self.reference.len() - fragment_len);
+        let sequence = self.read(start, slen, false);
+        let extended = self.read(start + fragment_len - xlen, xlen, true);
+        let squal = self.quality(&sequence);
+        let xqual = self.quality(&extended);
+        Some(OwnedRecord::new_paired(
+            start as u64,
+            sequence,
+            extended,
+            squal,
+            xqual,
+        ))
+    }
+}
+
+/// Writes a file of simulated records
+///
+/// # Parameters
+///
+/// * `path` - Path of the file to write
+/// * `options` - Options of the simulation
+///
+/// # Returns
+///
+/// The number of written records (records skipped by the encoding policy are not counted)
+///
+/// # Errors
+///
+/// * `WriteError::InvalidSimulationOptions` if a distribution or rate of the options is
+///   invalid
+/// * `HeaderError::InvalidBlockSize` if the block size of the options is invalid
+/// * Write errors if a read does not fit into a block
+/// * I/O errors if the file cannot be written
+pub fn simulate<P: AsRef<Path>>(path: P, options: &SimulationOptions) -> Result<u64> {
+    let simulator = Simulator::new(*options)?;
+    let mut writer = VBinseqWriterBuilder::default()
+        .header(options.header())
+        .policy(options.policy)
+        .create(path)?;
+    let mut n_written = 0;
+    for record in simulator {
+        n_written += u64::from(writer.write_record(&record)?);
+    }
+    writer.finish()?;
+    Ok(n_written)
+}
+
+/// Draws a value from a normal distribution (Box-Muller transform)
+fn normal<R: Rng>(rng: &mut R, mean: f64, sd: f64) -> f64 {
+    // Drawn from (0, 1] to keep the logarithm finite
+    let u = 1.0 - rng.gen::<f64>();
+    let v = rng.gen::<f64>();
+    mean + sd * (-2.0 * u.ln()).sqrt() * (2.0 * std::f64::consts::PI * v).cos()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::read_records;
+    use crate::Error;
+
+    #[test]
+    fn test_simulator() -> Result<()> {
+        let options = SimulationOptions {
+            n_records: 200,
+            length: LengthDistribution::Uniform { min: 50, max: 120 },
+            paired: true,
+            error_rate: 0.0,
+            n_rate: 0.0,
+            reference_len: 5_000,
+            seed: 3,
+            ..SimulationOptions::default()
+        };
+        let simulator = Simulator::new(options)?;
+        let reference = simulator.reference().to_vec();
+        let records: Vec<_> = simulator.collect();
+        assert_eq!(records.len(), 200);
+
+        // Without errors, mates are the two ends of a fragment starting at the flag
+        for record in &records {
+            let (slen, xlen) = (record.seq().len(), record.xseq().len());
+            assert!((50..=120).contains(&slen) && (50..=120).contains(&xlen));
+            let start = record.flag() as usize;
+            let end = start + slen.max(xlen);
+            assert_eq!(record.seq(), &reference[start..start + slen]);
+            let mate: Vec<_> = reference[end - xlen..end]
+                .iter()
+                .rev()
+                .map(|&base| complement(base))
+                .collect();
+            assert_eq!(record.xseq(), mate);
+            assert_eq!(record.squal().len(), slen);
+            assert!(record
+                .squal()
+                .iter()
+                .all(|q| (33..=33 + MAX_QUALITY).contains(q)));
+        }
+
+        // Simulations are reproducible
+        assert_eq!(Simulator::new(options)?.collect::<Vec<_>>(), records);
+
+        // Every nucleotide is substituted or called as N at a rate of one
+        for (error_rate, n_rate) in [(1.0, 0.0), (0.0, 1.0)] {
+            let options = SimulationOptions {
+                error_rate,
+                n_rate,
+                ..options
+            };
+            let simulator = Simulator::new(options)?;
+            let reference = simulator.reference().to_vec();
+            for record in simulator {
+                let start = record.flag() as usize;
+                let truth = &reference[start..start + record.seq().len()];
+                for (&base, &expected) in record.seq().iter().zip(truth) {
+                    if n_rate == 1.0 {
+                        assert_eq!(base, b'N');
+                    } else {
+                        assert!(base != expected && base != b'N');
+                    }
+                }
+                if n_rate == 1.0 {
+                    assert!(record.squal().iter().all(|&q| q == N_QUALITY + 33));
+                }
+            }
+        }
+
+        // Invalid options are rejected
+        for options in [
+            SimulationOptions {
+                error_rate: 1.5,
+                ..options
+            },
+            SimulationOptions {
+                length: LengthDistribution::Uniform { min: 10, max: 5 },
+                ..options
+            },
+            SimulationOptions {
+                quality: Some(QualityProfile::Constant(100)),
+                ..options
+            },
+        ] {
+            assert!(matches!(
+                Simulator::new(options),
+                Err(Error::WriteError(WriteError::InvalidSimulationOptions(_)))
+            ));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_simulate() -> Result<()> {
+        let path = std::env::temp_dir().join(format!("vbq_simulate_{}.vbq", std::process::id()));
+        for quality in [None, Some(QualityProfile::Uniform { min: 10, max: 40 })] {
+            let options = SimulationOptions {
+                n_records: 500,
+                length: LengthDistribution::Normal {
+                    mean: 300.0,
+                    sd: 50.0,
+                    min: 100,
+                    max: 500,
+                },
+                n_rate: 0.01,
+                quality,
+                block_size: 4096,
+                seed: 5,
+                ..SimulationOptions::default()
+            };
+            assert_eq!(simulate(&path, &options)?, 500);
+
+            // The file holds the simulated records, with N calls drawn by the policy
+            let records = read_records(std::fs::read(&path)?)?;
+            let simulated: Vec<_> = Simulator::new(options)?.collect();
+            assert_eq!(records.len(), simulated.len());
+            for (read, simulated) in records.iter().zip(&simulated) {
+                assert_eq!(read.flag(), simulated.flag());
+                assert_eq!(read.squal(), simulated.squal());
+                assert_eq!(read.seq().len(), simulated.seq().len());
+                assert!((100..=500).contains(&read.seq().len()));
+                for (&read, &simulated) in read.seq().iter().zip(simulated.seq()) {
+                    assert!(read == simulated || simulated == b'N');
+                }
+            }
+        }
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+}
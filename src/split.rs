@@ -0,0 +1,271 @@
+//! # File Splitting
+//!
+//! This module splits VBINSEQ files into shards at block boundaries and extracts ranges of
+//! blocks into files of their own. It requires the `mmap` feature.
+//!
+//! Blocks are copied verbatim (see `MmapReader::next_raw_block`), so no record is decoded
+//! or recompressed and splitting runs close to I/O speed even for very large files. Every
+//! shard is a complete VBINSEQ file with the header of the input and its own index. Shards
+//! of files with a footer get their own footer, which only requires decompressing (not
+//! recompressing) their blocks.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use vbinseq::split;
+//!
+//! // Write shards of at most 10M records as reads.0.vbq, reads.1.vbq, ...
+//! let shards = split::by_records("reads.vbq", 10_000_000).unwrap();
+//! println!("Wrote {} shards", shards.len());
+//!
+//! // Write blocks 10 to 19 of the file to a small file of their own
+//! split::extract_blocks("reads.vbq", "slice.vbq", 10..20).unwrap();
+//! ```
+
+use std::fs::File;
+use std::io::BufWriter;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use crate::error::IndexError;
+use crate::{BlockIndex, MmapReader, Result, VBinseqWriter, VBinseqWriterBuilder};
+
+/// Splits a file into shards of at most `n_per_shard` records
+///
+/// Shards are cut at block boundaries, so a shard holds as many consecutive blocks as fit
+/// into `n_per_shard` records. A block holding more than `n_per_shard` records on its own
+/// forms a shard of its own. A file without records yields a single empty shard.
+///
+/// The shards are written next to the input as `<stem>.<N>.vbq`, where `<stem>` is the
+/// input path without its extension and `<N>` is the zero-padded shard number. Each shard
+/// is written along with its index (`<stem>.<N>.vbq.vqi`).
+///
+/// # Parameters
+///
+/// * `path` - Path to the VBINSEQ file to split
+/// * `n_per_shard` - Maximum number of records per shard
+///
+/// # Returns
+///
+/// The paths of the shards in file order
+///
+/// # Errors
+///
+/// * If `n_per_shard` is zero
+/// * I/O errors from reading the input or writing the shards
+/// * Parsing errors if the input has an invalid format
+pub fn by_records<P: AsRef<Path>>(path: P, n_per_shard: u64) -> Result<Vec<PathBuf>> {
+    if n_per_shard == 0 {
+        return Err(anyhow::anyhow!("The number of records per shard must be positive").into());
+    }
+    let path = path.as_ref();
+    let mut reader = MmapReader::new(path)?;
+    let index = reader.load_index()?;
+
+    // Assign the blocks to shards up front to know the width of the shard numbers
+    let mut shard_starts = vec![0];
+    let mut shard_records = 0;
+    for (i, range) in index.ranges().iter().enumerate() {
+        let records = range.block_records as u64;
+        if shard_records > 0 && shard_records + records > n_per_shard {
+            shard_starts.push(i);
+            shard_records = 0;
+        }
+        shard_records += records;
+    }
+    let width = (shard_starts.len() - 1).to_string().len();
+    let stem = path.with_extension("");
+    let shard_path = |shard: usize| {
+        let mut shard_path = stem.as_os_str().to_owned();
+        shard_path.push(format!(".{shard:0width$}.vbq"));
+        PathBuf::from(shard_path)
+    };
+
+    let mut paths: Vec<PathBuf> = Vec::with_capacity(shard_starts.len());
+    let mut writer: Option<VBinseqWriter<BufWriter<File>>> = None;
+    let mut block_index = 0;
+    loop {
+        if shard_starts.get(paths.len()) == Some(&block_index) {
+            if let Some(writer) = writer.take() {
+                finish_shard(writer, paths.last().expect("shard path exists"))?;
+            }
+            let shard = shard_path(paths.len());
+            writer = Some(
+                VBinseqWriterBuilder::default()
+                    .header(reader.header())
+                    .sections(reader.sections()?)
+                    .build(File::create(&shard).map(BufWriter::new)?)?,
+            );
+            paths.push(shard);
+        }
+        let Some(block) = reader.next_raw_block()? else {
+            break;
+        };
+        writer
+            .as_mut()
+            .expect("writer is opened before the first block")
+            .write_raw_block(&block)?;
+        block_index += 1;
+    }
+    if let Some(writer) = writer {
+        finish_shard(writer, paths.last().expect("shard path exists"))?;
+    }
+    Ok(paths)
+}
+
+/// Copies a range of blocks into a new file
+///
+/// The blocks are copied verbatim into a file with the header of the input, so the output
+/// is a valid VBINSEQ file holding exactly the records of the blocks. The blocks before the
+/// range are skipped using the index of the input. The output is written along with its
+/// index (`<output>.vqi`).
+///
+/// # Parameters
+///
+/// * `path` - Path to the VBINSEQ file to copy the blocks from
+/// * `output` - Path of the file to write
+/// * `blocks` - The (0-based) indices of the blocks to copy
+///
+/// # Errors
+///
+/// * `IndexError::BlockRangeOutOfBounds` if the range extends beyond the blocks of the file
+/// * I/O errors from reading the input or writing the output
+/// * Parsing errors if the input has an invalid format
+pub fn extract_blocks<P: AsRef<Path>, Q: AsRef<Path>>(
+    path: P,
+    output: Q,
+    blocks: Range<usize>,
+) -> Result<()> {
+    let mut reader = MmapReader::new(path)?;
+    let index = reader.load_index()?;
+    let Some(ranges) = index.ranges().get(blocks.clone()) else {
+        return Err(
+            IndexError::BlockRangeOutOfBounds(blocks.start, blocks.end, index.n_blocks()).into(),
+        );
+    };
+
+    let output = output.as_ref();
+    let mut writer = VBinseqWriterBuilder::default()
+        .header(reader.header())
+        .sections(reader.sections()?)
+        .build(File::create(output).map(BufWriter::new)?)?;
+    if let Some(first) = ranges.first() {
+        if let Some(block) = reader.raw_block_at(first)? {
+            writer.write_raw_block(&block)?;
+        }
+        for _ in 1..ranges.len() {
+            let Some(block) = reader.next_raw_block()? else {
+                break;
+            };
+            writer.write_raw_block(&block)?;
+        }
+    }
+    finish_shard(writer, output)
+}
+
+/// Completes a shard and writes its index
+fn finish_shard(mut writer: VBinseqWriter<BufWriter<File>>, path: &Path) -> Result<()> {
+    writer.finish()?;
+    drop(writer);
+
+    let mut index_path = path.as_os_str().to_owned();
+    index_path.push(".vqi");
+    BlockIndex::from_vbq(path)?.save_to_path(PathBuf::from(index_path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::footer::verify;
+    use crate::VBinseqHeader;
+
+    #[test]
+    fn test_by_records() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("vbq_split_{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join("reads.vbq");
+
+        let mut header = VBinseqHeader::with_capacity(512, true, true, false);
+        header.set_footer(true);
+        let mut writer = VBinseqWriterBuilder::default()
+            .header(header)
+            .build(File::create(&path)?)?;
+        for flag in 0..1000 {
+            let sequence = b"ACGTTGCA".repeat(1 + flag as usize % 4);
+            writer.write_nucleotides_quality(flag, &sequence, &vec![b'I'; sequence.len()])?;
+        }
+        writer.finish()?;
+        drop(writer);
+
+        let n_per_shard = 150;
+        let shards = by_records(&path, n_per_shard)?;
+        assert!(shards.len() > 1 && shards.len() <= 10);
+
+        // The shards hold all records in order and respect the maximum
+        let mut flags = Vec::new();
+        for shard in &shards {
+            assert!(verify(shard)?);
+            let mut reader = MmapReader::new(shard)?;
+            assert_eq!(reader.header(), header);
+            assert!(reader.index_path().exists());
+            let mut block = reader.new_block();
+            let mut n_records = 0;
+            while reader.read_block_into(&mut block)? {
+                flags.extend(block.iter().map(|record| record.flag()));
+                n_records += block.n_records() as u64;
+            }
+            assert!(n_records <= n_per_shard);
+        }
+        assert_eq!(flags, (0..1000).collect::<Vec<u64>>());
+        assert!(shards[0].ends_with("reads.0.vbq"));
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_extract_blocks() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("vbq_extract_{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join("reads.vbq");
+        let output = dir.join("slice.vbq");
+
+        let mut header = VBinseqHeader::with_capacity(512, false, true, false);
+        header.set_footer(true);
+        let mut writer = VBinseqWriterBuilder::default()
+            .header(header)
+            .build(File::create(&path)?)?;
+        for flag in 0..1000 {
+            writer.write_nucleotides(flag, &b"ACGTTGCA".repeat(1 + flag as usize % 4))?;
+        }
+        writer.finish()?;
+        drop(writer);
+
+        let index = BlockIndex::from_vbq(&path)?;
+        assert!(index.n_blocks() > 4);
+        let ranges = &index.ranges()[2..4];
+        let start = ranges[0].cumulative_records as u64;
+        let end = start + ranges.iter().map(|r| r.block_records as u64).sum::<u64>();
+
+        extract_blocks(&path, &output, 2..4)?;
+        assert!(verify(&output)?);
+        let mut reader = MmapReader::new(&output)?;
+        assert_eq!(reader.load_index()?.n_blocks(), 2);
+        let mut block = reader.new_block();
+        let mut records = Vec::new();
+        while reader.read_block_into(&mut block)? {
+            records.extend(block.iter().map(|record| (record.index(), record.flag())));
+        }
+        let expected: Vec<_> = (start..end).map(|flag| (flag - start, flag)).collect();
+        assert_eq!(records, expected);
+
+        // Empty and out-of-bounds ranges
+        extract_blocks(&path, &output, 3..3)?;
+        assert_eq!(MmapReader::new(&output)?.load_index()?.n_blocks(), 0);
+        let n_blocks = index.n_blocks();
+        assert!(extract_blocks(&path, &output, 1..n_blocks + 1).is_err());
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
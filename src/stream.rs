@@ -0,0 +1,178 @@
+//! # Async Record Streams
+//!
+//! This module exposes the records of a file as a `futures::Stream`, so async services can
+//! consume VBINSEQ files with the standard stream combinators. It requires the `async`
+//! feature.
+//!
+//! Blocks are read and decoded on a dedicated thread, which never blocks the executor.
+//! Decoded blocks are passed to the stream through a bounded channel: once the channel
+//! holds `capacity` blocks, the reading thread waits until the stream consumes them, so a
+//! slow consumer exerts backpressure on reading instead of buffering the whole file. The
+//! stream works with any executor, and dropping it stops the reading thread.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use futures::{executor::block_on, StreamExt};
+//! use vbinseq::MmapReader;
+//!
+//! let reader = MmapReader::new("example.vbq").unwrap();
+//! let n_long = block_on(
+//!     reader
+//!         .record_stream()
+//!         .filter(|record| {
+//!             let long = record.as_ref().is_ok_and(|record| record.seq().len() >= 100);
+//!             async move { long }
+//!         })
+//!         .count(),
+//! );
+//! println!("{n_long} records of at least 100 bp");
+//! ```
+
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures::channel::mpsc;
+use futures::executor::block_on;
+use futures::{SinkExt, Stream, StreamExt};
+
+use crate::{MmapReader, OwnedRecord, Result};
+
+/// Default number of decoded blocks buffered ahead of a stream
+pub const DEFAULT_STREAM_CAPACITY: usize = 4;
+
+/// Stream over the decoded records of a file
+///
+/// Created by `MmapReader::record_stream`. The stream ends after yielding the first error.
+pub struct RecordStream {
+    /// Decoded blocks sent by the reading thread
+    blocks: mpsc::Receiver<Result<Vec<OwnedRecord>>>,
+
+    /// Records of the current block not yet yielded
+    records: std::vec::IntoIter<OwnedRecord>,
+}
+impl RecordStream {
+    /// Starts reading the remaining blocks of a reader on a dedicated thread
+    fn new(mut reader: MmapReader, capacity: usize) -> Self {
+        // The channel holds one block per sender on top of its buffer
+        let (mut sender, blocks) = mpsc::channel(capacity.max(1) - 1);
+        std::thread::spawn(move || {
+            let mut block = reader.new_block();
+            loop {
+                let records = match reader.read_block_into(&mut block) {
+                    Ok(true) => block
+                        .iter()
+                        .map(|record| OwnedRecord::try_from(&record))
+                        .collect::<Result<Vec<_>>>(),
+                    Ok(false) => break,
+                    Err(e) => Err(e),
+                };
+                let failed = records.is_err();
+                // Sending fails once the stream was dropped
+                if block_on(sender.send(records)).is_err() || failed {
+                    break;
+                }
+            }
+        });
+        Self {
+            blocks,
+            records: Vec::new().into_iter(),
+        }
+    }
+}
+impl Stream for RecordStream {
+    type Item = Result<OwnedRecord>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            if let Some(record) = self.records.next() {
+                return Poll::Ready(Some(Ok(record)));
+            }
+            match self.blocks.poll_next_unpin(cx) {
+                Poll::Ready(Some(Ok(records))) => self.records = records.into_iter(),
+                Poll::Ready(Some(Err(e))) => return Poll::Ready(Some(Err(e))),
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+impl MmapReader {
+    /// Converts the reader into an async stream over the decoded records of the file
+    ///
+    /// The stream yields the records of the remaining blocks like `into_records`, with at
+    /// most `DEFAULT_STREAM_CAPACITY` decoded blocks buffered ahead of the consumer (see
+    /// the `stream` module).
+    pub fn record_stream(self) -> impl Stream<Item = Result<OwnedRecord>> {
+        self.record_stream_with_capacity(DEFAULT_STREAM_CAPACITY)
+    }
+
+    /// Converts the reader into an async stream buffering up to `capacity` decoded blocks
+    ///
+    /// See `record_stream`. Larger buffers smooth out bursts of a consumer at the cost of
+    /// memory (about one block size per buffered block). A capacity of zero is treated as
+    /// one.
+    pub fn record_stream_with_capacity(
+        self,
+        capacity: usize,
+    ) -> impl Stream<Item = Result<OwnedRecord>> {
+        RecordStream::new(self, capacity)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+    use futures::StreamExt;
+    use rand::rngs::SmallRng;
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::testing::{headers, random_records, stored_records, write_records};
+
+    #[test]
+    fn test_record_stream() -> Result<()> {
+        let path = std::env::temp_dir().join(format!("vbq_stream_{}.vbq", std::process::id()));
+        let rng = &mut SmallRng::seed_from_u64(21);
+        for header in headers() {
+            let records = random_records(rng, &header, 300);
+            std::fs::write(&path, write_records(header, &records)?)?;
+            let stored = stored_records(&header, &records);
+
+            // The stream yields the records of the file in order
+            for capacity in [0, 1, 8] {
+                let reader = MmapReader::new(&path)?;
+                let streamed: Vec<_> = block_on(
+                    reader
+                        .record_stream_with_capacity(capacity)
+                        .collect::<Vec<_>>(),
+                );
+                let streamed = streamed.into_iter().collect::<Result<Vec<_>>>()?;
+                assert_eq!(streamed.len(), records.len());
+                for (position, (read, written)) in streamed.iter().zip(&stored).enumerate() {
+                    assert_eq!(read.index(), position as u64);
+                    assert_eq!((read.flag(), read.seq()), (written.flag(), written.seq()));
+                    assert_eq!(read.squal(), written.squal());
+                }
+            }
+
+            // Streams can be dropped before their end
+            let reader = MmapReader::new(&path)?;
+            let first = block_on(reader.record_stream().take(3).collect::<Vec<_>>());
+            assert_eq!(first.len(), 3);
+        }
+
+        // The stream ends after the first error
+        let header = headers()[0];
+        let mut bytes = write_records(header, &random_records(rng, &header, 300))?;
+        bytes.truncate(bytes.len() - 10);
+        std::fs::write(&path, bytes)?;
+        let results = block_on(MmapReader::new(&path)?.record_stream().collect::<Vec<_>>());
+        assert!(results.last().is_some_and(|result| result.is_err()));
+        assert_eq!(results.iter().filter(|result| result.is_err()).count(), 1);
+
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+}
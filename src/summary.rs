@@ -0,0 +1,126 @@
+//! # File Summaries
+//!
+//! This module provides a human-readable overview of a VBINSEQ file, similar in spirit
+//! to `samtools view -H` for SAM/BAM files.
+//!
+//! A `FileSummary` is built by scanning only the block headers of a file (no record data
+//! is decoded), so it is cheap to compute even for very large files.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use vbinseq::summary::{describe, FileSummary};
+//!
+//! // Print the summary of a file to stdout
+//! describe("example.vbq").unwrap();
+//!
+//! // Or inspect the summary programmatically
+//! let summary = FileSummary::from_path("example.vbq").unwrap();
+//! println!("{} records in {} blocks", summary.n_records, summary.n_blocks);
+//! ```
+
+use std::fmt;
+use std::fs::File;
+use std::path::Path;
+
+use crate::{BlockIndex, Result, VBinseqHeader};
+
+/// Overview of the contents of a VBINSEQ file
+///
+/// The summary combines the file header with aggregate statistics derived from
+/// the block headers: the number of blocks and records, and the relationship between
+/// the virtual (uncompressed) size of the blocks and the bytes actually stored on disk.
+#[derive(Debug, Clone, Copy)]
+pub struct FileSummary {
+    /// Header of the file
+    pub header: VBinseqHeader,
+
+    /// Total size of the file in bytes
+    pub file_size: u64,
+
+    /// Number of record blocks in the file
+    pub n_blocks: usize,
+
+    /// Total number of records in the file
+    pub n_records: u64,
+
+    /// Total virtual (uncompressed) size of all blocks in bytes
+    pub virtual_size: u64,
+
+    /// Total size of all block data as stored on disk in bytes (excluding block headers)
+    pub stored_size: u64,
+}
+impl FileSummary {
+    /// Builds a summary by scanning the block headers of a VBINSEQ file
+    ///
+    /// # Parameters
+    ///
+    /// * `path` - Path to the VBINSEQ file to summarize
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(FileSummary)` - The summary of the file
+    /// * `Err(_)` - If the file could not be opened or has an invalid format
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let header = VBinseqHeader::from_reader(&mut File::open(&path)?)?;
+        let file_size = std::fs::metadata(&path)?.len();
+        let index = BlockIndex::from_vbq(&path)?;
+
+        let n_blocks = index.n_blocks();
+        let n_records = index
+            .ranges()
+            .iter()
+            .map(|range| range.block_records as u64)
+            .sum();
+        let stored_size = index.ranges().iter().map(|range| range.len).sum();
+        let virtual_size = n_blocks as u64 * header.block();
+
+        Ok(Self {
+            header,
+            file_size,
+            n_blocks,
+            n_records,
+            virtual_size,
+            stored_size,
+        })
+    }
+
+    /// Returns the compression ratio of the block data
+    ///
+    /// This is the ratio of the virtual (uncompressed) block size to the stored block size.
+    /// Uncompressed files have a ratio of 1.0, and empty files report a ratio of 1.0 as well.
+    pub fn compression_ratio(&self) -> f64 {
+        if self.stored_size == 0 {
+            1.0
+        } else {
+            self.virtual_size as f64 / self.stored_size as f64
+        }
+    }
+}
+impl fmt::Display for FileSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", self.header)?;
+        writeln!(f, "File size:       {} bytes", self.file_size)?;
+        writeln!(f, "Blocks:          {}", self.n_blocks)?;
+        writeln!(f, "Records:         {}", self.n_records)?;
+        writeln!(f, "Virtual size:    {} bytes", self.virtual_size)?;
+        writeln!(f, "Stored size:     {} bytes", self.stored_size)?;
+        write!(f, "Compression:     {:.2}x", self.compression_ratio())
+    }
+}
+
+/// Prints a human-readable summary of a VBINSEQ file to stdout
+///
+/// # Parameters
+///
+/// * `path` - Path to the VBINSEQ file to describe
+///
+/// # Returns
+///
+/// * `Ok(())` - If the summary was printed
+/// * `Err(_)` - If the file could not be opened or has an invalid format
+pub fn describe<P: AsRef<Path>>(path: P) -> Result<()> {
+    let summary = FileSummary::from_path(path)?;
+    println!("{summary}");
+    Ok(())
+}
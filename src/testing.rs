@@ -0,0 +1,478 @@
+//! # Property Testing Harness
+//!
+//! This module generates random headers and records and checks that they survive a
+//! write→read round trip unchanged. It requires the `testing` feature and is meant for
+//! property and fuzz tests of format changes and of downstream crates.
+//!
+//! All generators take the random number generator as a parameter, so tests can be made
+//! reproducible by seeding it. Generated records fit into the blocks of every generated
+//! header (see `MAX_RECORD_LEN`). Records are compared with the records as readers return
+//! them (see `stored_records`).
+//!
+//! # Example
+//!
+//! ```rust
+//! use rand::rngs::SmallRng;
+//! use rand::SeedableRng;
+//! use vbinseq::testing;
+//!
+//! let mut rng = SmallRng::seed_from_u64(42);
+//! for header in testing::headers() {
+//!     let records = testing::random_records(&mut rng, &header, 100);
+//!     testing::assert_round_trip(header, &records);
+//! }
+//! ```
+
+use std::io::Write;
+
+use rand::Rng;
+
+use crate::header::{
+    FLAG_AUX, FLAG_BLOCK_CHECKSUM, FLAG_CODEC_FALLBACK, FLAG_COLUMNAR, FLAG_EMBEDDED_INDEX,
+    FLAG_FIXED_LENGTH, FLAG_HOMOPOLYMER, FLAG_IUPAC, FLAG_OPTIONAL_QUALITY, FLAG_RECORD_CRC,
+    FLAG_SEGMENTS,
+};
+use crate::homopolymer::collapse;
+use crate::{
+    iupac, MemoryReader, OwnedRecord, QualityTransform, Result, VBinseqHeader, VBinseqWriter,
+    VBinseqWriterBuilder,
+};
+
+/// Block sizes of generated headers
+///
+/// These are small, so a few hundred records span many blocks.
+pub const BLOCK_SIZES: [u64; 3] = [1024, 2048, 4096];
+
+/// Longest sequence of generated records
+///
+/// A paired record with quality scores of this length fits into the smallest block size,
+/// and a record of up to `MAX_RECORD_SEGMENTS` segments fits into `SEGMENTS_BLOCK_SIZE`.
+pub const MAX_RECORD_LEN: usize = 300;
+
+/// Most segments of generated headers with more than two segments
+pub const MAX_RECORD_SEGMENTS: usize = 4;
+
+/// Smallest block size of generated headers with more than two segments
+pub const SEGMENTS_BLOCK_SIZE: u64 = 2048;
+
+/// Length of the records of generated fixed-length headers
+pub const FIXED_RECORD_LEN: usize = 150;
+
+/// Format extensions enabled on their own by `headers`
+///
+/// The footer is part of the combinations of file features instead.
+const EXTENSIONS: [u32; 11] = [
+    FLAG_HOMOPOLYMER,
+    FLAG_RECORD_CRC,
+    FLAG_AUX,
+    FLAG_FIXED_LENGTH,
+    FLAG_OPTIONAL_QUALITY,
+    FLAG_BLOCK_CHECKSUM,
+    FLAG_EMBEDDED_INDEX,
+    FLAG_SEGMENTS,
+    FLAG_IUPAC,
+    FLAG_COLUMNAR,
+    FLAG_CODEC_FALLBACK,
+];
+
+/// Quality transforms of generated headers with quality scores
+pub const QUALITY_TRANSFORMS: [QualityTransform; 3] = [
+    QualityTransform::None,
+    QualityTransform::RunLength,
+    QualityTransform::Delta,
+];
+
+/// Returns headers covering every combination of file features
+///
+/// This covers quality scores (with every quality transform), compression, pairing, and
+/// the footer, all with the smallest block size of `BLOCK_SIZES`. Every other format
+/// extension is then enabled on its own, combined with every quality, compression, and
+/// pairing setting it supports: homopolymer compression only without quality scores,
+/// optional quality scores only with them, fixed-length records only for single-end
+/// files, embedded indexes only with the footer, and the codec fallback only with
+/// compression. Headers with more segments have
+/// `MAX_RECORD_SEGMENTS` segments and blocks of `SEGMENTS_BLOCK_SIZE`.
+pub fn headers() -> Vec<VBinseqHeader> {
+    let mut headers = Vec::new();
+    for qual in [false, true] {
+        let transforms = if qual {
+            &QUALITY_TRANSFORMS[..]
+        } else {
+            &QUALITY_TRANSFORMS[..1]
+        };
+        for &transform in transforms {
+            for compressed in [false, true] {
+                for paired in [false, true] {
+                    for footer in [false, true] {
+                        let mut header =
+                            VBinseqHeader::with_capacity(BLOCK_SIZES[0], qual, compressed, paired);
+                        header.set_footer(footer);
+                        header.set_quality_transform(transform);
+                        headers.push(header);
+                    }
+                }
+            }
+        }
+    }
+    for extension in EXTENSIONS {
+        for qual in [false, true] {
+            for compressed in [false, true] {
+                for paired in [false, true] {
+                    let header =
+                        VBinseqHeader::with_capacity(BLOCK_SIZES[0], qual, compressed, paired);
+                    headers.extend(with_extension(header, extension));
+                }
+            }
+        }
+    }
+    headers
+}
+
+/// Enables a format extension on a header, if the header supports it
+fn with_extension(mut header: VBinseqHeader, extension: u32) -> Option<VBinseqHeader> {
+    match extension {
+        FLAG_HOMOPOLYMER if !header.qual() => header.set_homopolymer(true),
+        FLAG_RECORD_CRC => header.set_record_crc(true),
+        FLAG_AUX => header.set_aux(true),
+        FLAG_FIXED_LENGTH if header.supports_fixed_length() => header.set_fixed_length(true),
+        FLAG_OPTIONAL_QUALITY if header.qual() => header.set_optional_quality(true),
+        FLAG_BLOCK_CHECKSUM => header.set_block_checksum(true),
+        FLAG_EMBEDDED_INDEX => {
+            header.set_footer(true);
+            header.set_embedded_index(true);
+        }
+        FLAG_SEGMENTS if header.paired() => {
+            header.set_segments(MAX_RECORD_SEGMENTS).ok()?;
+            header.set_block(SEGMENTS_BLOCK_SIZE).ok()?;
+        }
+        FLAG_IUPAC => header.set_iupac(true),
+        FLAG_COLUMNAR => header.set_columnar(true),
+        FLAG_CODEC_FALLBACK if header.compressed() => header.set_codec_fallback(true),
+        _ => return None,
+    }
+    Some(header)
+}
+
+/// Generates a header with random file features and block size
+///
+/// Every format extension is enabled at random, as long as the combination is valid.
+pub fn random_header<R: Rng>(rng: &mut R) -> VBinseqHeader {
+    let block = BLOCK_SIZES[rng.gen_range(0..BLOCK_SIZES.len())];
+    let mut header = VBinseqHeader::with_capacity(block, rng.gen(), rng.gen(), rng.gen());
+    header.set_footer(rng.gen());
+    header.set_record_crc(rng.gen());
+    header.set_aux(rng.gen());
+    if header.qual() {
+        header
+            .set_quality_transform(QUALITY_TRANSFORMS[rng.gen_range(0..QUALITY_TRANSFORMS.len())]);
+        header.set_optional_quality(rng.gen());
+    } else {
+        header.set_homopolymer(rng.gen());
+    }
+    if header.paired() && !header.is_homopolymer() && rng.gen() {
+        header
+            .set_segments(rng.gen_range(3..=MAX_RECORD_SEGMENTS))
+            .expect("segment count is valid");
+        header
+            .set_block(block.max(SEGMENTS_BLOCK_SIZE))
+            .expect("block size is valid");
+    }
+    header.set_block_checksum(rng.gen());
+    header.set_embedded_index(header.has_footer() && rng.gen());
+    header.set_iupac(rng.gen());
+    header.set_columnar(rng.gen());
+    header.set_codec_fallback(header.compressed() && rng.gen());
+    if header.supports_fixed_length() {
+        header.set_fixed_length(rng.gen());
+    }
+    header
+}
+
+/// Generates a random record matching the features of a header
+///
+/// Sequences have random lengths of up to `MAX_RECORD_LEN` nucleotides (including empty
+/// sequences), or `FIXED_RECORD_LEN` nucleotides in fixed-length files. Records only have
+/// an extended sequence if the header is paired and only have quality scores if the header
+/// has quality scores, and a quarter of the records of files with optional quality scores
+/// have none. Sequences of IUPAC files hold all IUPAC nucleotides, and those of other
+/// files only `ACGT`.
+///
+/// # Parameters
+///
+/// * `rng` - The random number generator
+/// * `header` - The header of the file the record is written to
+pub fn random_record<R: Rng>(rng: &mut R, header: &VBinseqHeader) -> OwnedRecord {
+    let slen = if header.is_fixed_length() {
+        FIXED_RECORD_LEN
+    } else {
+        rng.gen_range(0..=MAX_RECORD_LEN)
+    };
+    let xlen = if header.paired() {
+        rng.gen_range(0..=MAX_RECORD_LEN)
+    } else {
+        0
+    };
+    let alphabet: &[u8] = if header.is_iupac() {
+        &iupac::ALPHABET[1..]
+    } else {
+        b"ACGT"
+    };
+    let sequence = random_sequence(rng, slen, alphabet);
+    let extended = random_sequence(rng, xlen, alphabet);
+    let qual = header.qual() && !(header.has_optional_quality() && rng.gen_ratio(1, 4));
+    let (squal, xqual) = if qual {
+        (random_quality(rng, slen), random_quality(rng, xlen))
+    } else {
+        (Vec::new(), Vec::new())
+    };
+    let aux = if header.has_aux() { rng.gen() } else { 0 };
+    if header.segments() > 2 {
+        let mut sequences = vec![sequence, extended];
+        let mut qualities = vec![squal, xqual];
+        for _ in 2..header.segments() {
+            let len = rng.gen_range(0..=MAX_RECORD_LEN);
+            sequences.push(random_sequence(rng, len, alphabet));
+            if qual {
+                qualities.push(random_quality(rng, len));
+            }
+        }
+        if !qual {
+            qualities.clear();
+        }
+        return OwnedRecord::new_segments(rng.gen(), sequences, qualities).with_aux(aux);
+    }
+    OwnedRecord::new_paired(rng.gen(), sequence, extended, squal, xqual).with_aux(aux)
+}
+
+/// Generates random records matching the features of a header
+///
+/// # Parameters
+///
+/// * `rng` - The random number generator
+/// * `header` - The header of the file the records are written to
+/// * `n_records` - The number of records to generate
+pub fn random_records<R: Rng>(
+    rng: &mut R,
+    header: &VBinseqHeader,
+    n_records: usize,
+) -> Vec<OwnedRecord> {
+    (0..n_records).map(|_| random_record(rng, header)).collect()
+}
+
+/// Writes records into an in-memory VBINSEQ file
+///
+/// # Parameters
+///
+/// * `header` - The header of the file
+/// * `records` - The records to write (matching the features of the header)
+///
+/// # Errors
+///
+/// * Any error of the writer, e.g. if a record does not match the header
+pub fn write_records(header: VBinseqHeader, records: &[OwnedRecord]) -> Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    let mut writer = VBinseqWriterBuilder::default()
+        .header(header)
+        .build(&mut bytes)?;
+    write_all(&mut writer, records)?;
+    writer.close()?;
+    Ok(bytes)
+}
+
+/// Writes records with a writer
+///
+/// Records are written with the write method matching the features of the header of the
+/// writer, e.g. to add records to a file opened with `VBinseqWriterBuilder::append`.
+///
+/// # Parameters
+///
+/// * `writer` - The writer to write the records with
+/// * `records` - The records to write (matching the features of the header)
+///
+/// # Errors
+///
+/// * Any error of the writer, e.g. if a record does not match the header
+pub fn write_all<W: Write>(writer: &mut VBinseqWriter<W>, records: &[OwnedRecord]) -> Result<()> {
+    let header = writer.header();
+    for record in records {
+        if header.has_aux() {
+            writer.set_record_aux(record.aux())?;
+        }
+        if header.segments() > 2 || header.has_optional_quality() {
+            writer.write_record(record)?;
+            continue;
+        }
+        match (header.qual(), header.paired()) {
+            (false, false) => writer.write_nucleotides(record.flag(), record.seq())?,
+            (false, true) => {
+                writer.write_nucleotides_paired(record.flag(), record.seq(), record.xseq())?
+            }
+            (true, false) => {
+                writer.write_nucleotides_quality(record.flag(), record.seq(), record.squal())?
+            }
+            (true, true) => writer.write_nucleotides_quality_paired(
+                record.flag(),
+                record.seq(),
+                record.xseq(),
+                record.squal(),
+                record.xqual(),
+            )?,
+        };
+    }
+    Ok(())
+}
+
+/// Reads all records of an in-memory VBINSEQ file
+///
+/// # Parameters
+///
+/// * `bytes` - The contents of the file
+///
+/// # Errors
+///
+/// * Any error of the reader, e.g. if the file has an invalid format
+pub fn read_records(bytes: Vec<u8>) -> Result<Vec<OwnedRecord>> {
+    let mut reader = MemoryReader::new(bytes)?;
+    let mut block = reader.new_block();
+    let mut records = Vec::new();
+    while reader.read_block_into(&mut block)? {
+        for ref_record in block.iter() {
+            let mut record = OwnedRecord::default();
+            record.fill(&ref_record)?;
+            records.push(record);
+        }
+    }
+    Ok(records)
+}
+
+/// Returns records as readers return them after writing them with a header
+///
+/// These are the records themselves, except in homopolymer-compressed files, whose
+/// readers return the compressed sequences with the run lengths in place of the quality
+/// scores (see the `homopolymer` module).
+///
+/// # Parameters
+///
+/// * `header` - The header of the file the records are written to
+/// * `records` - The written records
+pub fn stored_records(header: &VBinseqHeader, records: &[OwnedRecord]) -> Vec<OwnedRecord> {
+    if !header.is_homopolymer() {
+        return records.to_vec();
+    }
+    let (mut sruns, mut xruns) = (Vec::new(), Vec::new());
+    let (mut sequence, mut extended) = (Vec::new(), Vec::new());
+    records
+        .iter()
+        .map(|record| {
+            collapse(record.seq(), &mut sequence, &mut sruns);
+            collapse(record.xseq(), &mut extended, &mut xruns);
+            OwnedRecord::new_paired(
+                record.flag(),
+                sequence.clone(),
+                extended.clone(),
+                sruns.clone(),
+                xruns.clone(),
+            )
+            .with_aux(record.aux())
+        })
+        .collect()
+}
+
+/// Asserts that records are read back unchanged after writing them
+///
+/// The records are written with the header and read back, and every read record must
+/// match the written record of the same position. Record indices are ignored for the
+/// comparison but must match the position of the records. Records of
+/// homopolymer-compressed files are compared in their compressed form (see
+/// `stored_records`).
+///
+/// # Parameters
+///
+/// * `header` - The header of the file
+/// * `records` - The records to write (matching the features of the header)
+///
+/// # Panics
+///
+/// If writing or reading fails or a record differs after the round trip
+pub fn assert_round_trip(header: VBinseqHeader, records: &[OwnedRecord]) {
+    let bytes = write_records(header, records)
+        .unwrap_or_else(|e| panic!("Failed to write records with {}: {e}", header.summary()));
+    let read = read_records(bytes)
+        .unwrap_or_else(|e| panic!("Failed to read records with {}: {e}", header.summary()));
+    let records = &stored_records(&header, records);
+    assert_eq!(
+        read.len(),
+        records.len(),
+        "Number of records differs with {}",
+        header.summary()
+    );
+    for (position, (read, written)) in read.iter().zip(records).enumerate() {
+        assert_eq!(read.index(), position as u64, "Record index differs");
+        assert_eq!(
+            (
+                read.flag(),
+                read.seq(),
+                read.xseq(),
+                read.squal(),
+                read.xqual(),
+                read.aux()
+            ),
+            (
+                written.flag(),
+                written.seq(),
+                written.xseq(),
+                written.squal(),
+                written.xqual(),
+                written.aux()
+            ),
+            "Record {position} differs with {}",
+            header.summary()
+        );
+        let segments = |record: &OwnedRecord| {
+            (2..record.n_segments())
+                .map(|i| (record.segment(i).to_vec(), record.segment_qual(i).to_vec()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            segments(read),
+            segments(written),
+            "Segments of record {position} differ with {}",
+            header.summary()
+        );
+    }
+}
+
+/// Generates a random sequence of the given length from the nucleotides of an alphabet
+fn random_sequence<R: Rng>(rng: &mut R, len: usize, alphabet: &[u8]) -> Vec<u8> {
+    (0..len)
+        .map(|_| alphabet[rng.gen_range(0..alphabet.len())])
+        .collect()
+}
+
+/// Generates random quality scores of the given length (Phred+33, Q0-Q41)
+fn random_quality<R: Rng>(rng: &mut R, len: usize) -> Vec<u8> {
+    (0..len).map(|_| rng.gen_range(b'!'..=b'J')).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::SmallRng;
+    use rand::SeedableRng;
+
+    use super::*;
+
+    #[test]
+    fn test_random_round_trips() {
+        let mut rng = SmallRng::seed_from_u64(42);
+        for header in headers() {
+            let records = random_records(&mut rng, &header, 200);
+            assert_round_trip(header, &records);
+        }
+        for _ in 0..20 {
+            let header = random_header(&mut rng);
+            let n_records = rng.gen_range(0..500);
+            let records = random_records(&mut rng, &header, n_records);
+            assert_round_trip(header, &records);
+        }
+    }
+}
@@ -0,0 +1,335 @@
+//! # Record Deletion
+//!
+//! Curation workflows often remove records a few at a time (e.g. contaminants or
+//! duplicates found by later analyses), and rewriting a large file for every removal is
+//! wasteful. This module deletes records logically: the indices of deleted records are
+//! kept in a tombstone file next to the VBINSEQ file (`<path>.vqd`), and `compact`
+//! physically drops them in a single rewrite once enough records were deleted. It
+//! requires the `mmap` feature.
+//!
+//! Tombstones are kept in a sidecar rather than in the flags of the records, since records
+//! can't be rewritten in place in compressed blocks and rewriting them would invalidate
+//! the footer digest and record checksums of a file. Readers don't skip deleted records
+//! on their own; consumers check the records they read against the `Tombstones` of the
+//! file.
+//!
+//! # Tombstone File Format
+//!
+//! | Bytes  | Contents                                                      |
+//! |--------|---------------------------------------------------------------|
+//! | 0-7    | Magic number (`VBQTOMBS` in ASCII)                            |
+//! | 8-15   | Size of the VBINSEQ file in bytes (to detect stale tombstones) |
+//! | 16-    | Indices of the deleted records (u64 each, in ascending order) |
+//!
+//! All values are little endian.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use vbinseq::tombstone::{self, Tombstones};
+//! use vbinseq::MmapReader;
+//!
+//! // Delete two records
+//! tombstone::delete("reads.vbq", [17, 42]).unwrap();
+//!
+//! // Skip deleted records while reading
+//! let tombstones = Tombstones::load("reads.vbq").unwrap();
+//! let mut reader = MmapReader::new("reads.vbq").unwrap();
+//! let mut block = reader.new_block();
+//! while reader.read_block_into(&mut block).unwrap() {
+//!     for record in block.iter().filter(|record| !tombstones.contains(record.index())) {
+//!         // process the record
+//!     }
+//! }
+//!
+//! // Drop the deleted records for good
+//! tombstone::compact("reads.vbq", "curated.vbq").unwrap();
+//! ```
+
+use std::collections::BTreeSet;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use byteorder::{ByteOrder, LittleEndian};
+
+use crate::error::ReadError;
+use crate::repack::{copy_records, RepackOptions};
+use crate::{BlockIndex, Result};
+
+/// Magic number of tombstone files ("VBQTOMBS" in ASCII)
+pub const TOMBSTONE_MAGIC: u64 = 0x53424D4F54514256;
+
+/// Size of the header of a tombstone file in bytes
+const SIZE_TOMBSTONE_HEADER: usize = 16;
+
+/// Returns the path of the tombstone file of a VBINSEQ file (`<path>.vqd`)
+pub fn tombstone_path<P: AsRef<Path>>(path: P) -> PathBuf {
+    let mut p = path.as_ref().as_os_str().to_owned();
+    p.push(".vqd");
+    p.into()
+}
+
+/// Indices of the deleted records of a VBINSEQ file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tombstones {
+    /// Size of the VBINSEQ file the tombstones belong to (in bytes)
+    file_size: u64,
+
+    /// Global indices of the deleted records
+    deleted: BTreeSet<u64>,
+}
+impl Tombstones {
+    /// Loads the tombstones of a VBINSEQ file
+    ///
+    /// Files without a tombstone file have no deleted records.
+    ///
+    /// # Parameters
+    ///
+    /// * `path` - Path to the VBINSEQ file (not its tombstone file)
+    ///
+    /// # Errors
+    ///
+    /// * `ReadError::InvalidTombstoneMagic` if the tombstone file is invalid
+    /// * `ReadError::StaleTombstones` if the file changed since the tombstones were saved
+    /// * I/O errors if the files cannot be read
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let file_size = std::fs::metadata(&path)?.len();
+        let tombstone_path = tombstone_path(&path);
+        if !tombstone_path.exists() {
+            return Ok(Self {
+                file_size,
+                deleted: BTreeSet::new(),
+            });
+        }
+        let bytes = std::fs::read(tombstone_path)?;
+        if bytes.len() < SIZE_TOMBSTONE_HEADER || bytes.len() % 8 != 0 {
+            return Err(ReadError::UnexpectedEndOfFile(bytes.len()).into());
+        }
+        let magic = LittleEndian::read_u64(&bytes[0..8]);
+        if magic != TOMBSTONE_MAGIC {
+            return Err(ReadError::InvalidTombstoneMagic(magic).into());
+        }
+        let recorded_size = LittleEndian::read_u64(&bytes[8..16]);
+        if recorded_size != file_size {
+            return Err(ReadError::StaleTombstones(file_size, recorded_size).into());
+        }
+        Ok(Self {
+            file_size,
+            deleted: bytes[SIZE_TOMBSTONE_HEADER..]
+                .chunks_exact(8)
+                .map(LittleEndian::read_u64)
+                .collect(),
+        })
+    }
+
+    /// Saves the tombstones next to a VBINSEQ file
+    ///
+    /// The tombstone file is removed if no record is deleted.
+    ///
+    /// # Parameters
+    ///
+    /// * `path` - Path to the VBINSEQ file the tombstones were loaded for
+    ///
+    /// # Errors
+    ///
+    /// * I/O errors if the tombstone file cannot be written
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let tombstone_path = tombstone_path(path);
+        if self.deleted.is_empty() {
+            if tombstone_path.exists() {
+                std::fs::remove_file(tombstone_path)?;
+            }
+            return Ok(());
+        }
+        let mut writer = File::create(tombstone_path).map(BufWriter::new)?;
+        let mut buffer = [0u8; 8];
+        for value in [TOMBSTONE_MAGIC, self.file_size]
+            .into_iter()
+            .chain(self.deleted.iter().copied())
+        {
+            LittleEndian::write_u64(&mut buffer, value);
+            writer.write_all(&buffer)?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Marks a record as deleted
+    ///
+    /// Returns `false` if the record was already deleted.
+    pub fn delete(&mut self, index: u64) -> bool {
+        self.deleted.insert(index)
+    }
+
+    /// Restores a deleted record
+    ///
+    /// Returns `false` if the record was not deleted.
+    pub fn restore(&mut self, index: u64) -> bool {
+        self.deleted.remove(&index)
+    }
+
+    /// Returns whether a record is deleted
+    pub fn contains(&self, index: u64) -> bool {
+        self.deleted.contains(&index)
+    }
+
+    /// Returns the number of deleted records
+    pub fn len(&self) -> usize {
+        self.deleted.len()
+    }
+
+    /// Returns whether no record is deleted
+    pub fn is_empty(&self) -> bool {
+        self.deleted.is_empty()
+    }
+
+    /// Iterates over the indices of the deleted records in ascending order
+    pub fn iter(&self) -> impl Iterator<Item = u64> + '_ {
+        self.deleted.iter().copied()
+    }
+}
+
+/// Marks records of a VBINSEQ file as deleted
+///
+/// The indices are added to the tombstone file of the file, which is created if needed.
+///
+/// # Parameters
+///
+/// * `path` - Path to the VBINSEQ file
+/// * `indices` - Global indices of the records to delete
+///
+/// # Returns
+///
+/// The number of newly deleted records (records deleted before are not counted)
+///
+/// # Errors
+///
+/// * `ReadError::RecordOutOfRange` if an index is past the last record of the file (no
+///   record is deleted then)
+/// * Any error of `Tombstones::load` or `Tombstones::save`
+pub fn delete<P, I>(path: P, indices: I) -> Result<usize>
+where
+    P: AsRef<Path>,
+    I: IntoIterator<Item = u64>,
+{
+    let mut tombstones = Tombstones::load(&path)?;
+    let n_records = BlockIndex::from_vbq(&path)?.n_records();
+    let mut n_deleted = 0;
+    for index in indices {
+        if index >= n_records {
+            return Err(ReadError::RecordOutOfRange(index, n_records).into());
+        }
+        n_deleted += usize::from(tombstones.delete(index));
+    }
+    tombstones.save(&path)?;
+    Ok(n_deleted)
+}
+
+/// Writes the records of a file that are not deleted into a new file
+///
+/// The output holds the remaining records packed into full blocks (see `repack`), with
+/// the header and header sections of the input. It has no tombstone file, and its records
+/// are indexed from zero without gaps. The input and its tombstones are left unchanged.
+///
+/// # Parameters
+///
+/// * `path` - Path to the VBINSEQ file to compact
+/// * `output` - Path of the file to write
+///
+/// # Returns
+///
+/// The number of dropped records
+///
+/// # Errors
+///
+/// * Any error of `Tombstones::load`
+/// * I/O errors from reading the input or writing the output
+/// * Parsing errors if the input has an invalid format
+pub fn compact<P: AsRef<Path>, Q: AsRef<Path>>(path: P, output: Q) -> Result<u64> {
+    let tombstones = Tombstones::load(&path)?;
+    let mut n_dropped = 0;
+    copy_records(&path, &output, &RepackOptions::default(), |record| {
+        let deleted = tombstones.contains(record.index());
+        n_dropped += u64::from(deleted);
+        !deleted
+    })?;
+    Ok(n_dropped)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::SmallRng;
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::testing::{headers, random_records, read_records, stored_records, write_records};
+    use crate::Error;
+
+    #[test]
+    fn test_tombstones() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("vbq_tombstone_{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let (path, output) = (dir.join("reads.vbq"), dir.join("curated.vbq"));
+        let rng = &mut SmallRng::seed_from_u64(20);
+        for header in headers() {
+            let records = random_records(rng, &header, 200);
+            std::fs::write(&path, write_records(header, &records)?)?;
+            assert!(Tombstones::load(&path)?.is_empty());
+
+            // Deletions accumulate in the tombstone file
+            assert_eq!(delete(&path, [3, 50, 50])?, 2);
+            assert_eq!(delete(&path, [50, 199])?, 1);
+            let mut tombstones = Tombstones::load(&path)?;
+            assert_eq!(tombstones.iter().collect::<Vec<_>>(), [3, 50, 199]);
+            assert!(tombstones.contains(50) && !tombstones.contains(51));
+
+            // Indices past the last record are rejected
+            let e = delete(&path, [7, 200]).unwrap_err();
+            assert!(matches!(
+                e,
+                Error::ReadError(ReadError::RecordOutOfRange(200, 200))
+            ));
+            assert_eq!(Tombstones::load(&path)?.len(), 3);
+
+            // Restored records are kept by the compaction
+            assert!(tombstones.restore(199));
+            tombstones.save(&path)?;
+            assert_eq!(compact(&path, &output)?, 2);
+            let stored = stored_records(&header, &records);
+            let expected: Vec<_> = stored
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| ![3, 50].contains(i))
+                .map(|(_, record)| (record.flag(), record.seq(), record.squal()))
+                .collect();
+            let compacted = read_records(std::fs::read(&output)?)?;
+            let compacted: Vec<_> = compacted
+                .iter()
+                .map(|record| (record.flag(), record.seq(), record.squal()))
+                .collect();
+            assert_eq!(compacted, expected);
+            assert!(!tombstone_path(&output).exists());
+
+            // Restoring all records removes the tombstone file
+            tombstones.restore(3);
+            tombstones.restore(50);
+            tombstones.save(&path)?;
+            assert!(!tombstone_path(&path).exists());
+        }
+
+        // Tombstones of another version of the file are rejected
+        delete(&path, [0])?;
+        std::fs::write(
+            &path,
+            write_records(headers()[0], &random_records(rng, &headers()[0], 5))?,
+        )?;
+        assert!(matches!(
+            Tombstones::load(&path),
+            Err(Error::ReadError(ReadError::StaleTombstones(..)))
+        ));
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
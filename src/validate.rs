@@ -0,0 +1,812 @@
+//! # Whole-File Validation
+//!
+//! This module verifies the structural integrity of a VBINSEQ file and reports every
+//! problem it finds in a structured `ValidationReport`.
+//!
+//! Validation checks:
+//!
+//! * The file header (magic number and format version)
+//! * Every block header (magic number, codec, and declared size)
+//! * That every block is complete and its data can be decoded
+//! * That the number of decoded records matches the record count of each block header
+//! * That every block matches its checksum (if the file stores block checksums)
+//! * That every record matches its checksum (if the file stores record checksums)
+//! * That the footer (if present) matches the counts and content digest of the blocks
+//! * That an existing index file (and the embedded index, if any) is consistent with the
+//!   blocks of the file
+//!
+//! Problems are classified as either _fatal_ (the data cannot be read correctly) or
+//! _recoverable_ (the data is intact but auxiliary information, like the index, needs
+//! to be regenerated).
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use vbinseq::validate::check;
+//!
+//! let report = check("example.vbq").unwrap();
+//! if !report.is_valid() {
+//!     for issue in report.issues() {
+//!         eprintln!("{}", issue);
+//!     }
+//! }
+//! ```
+
+use std::fmt;
+use std::path::Path;
+
+use crate::BlockIndex;
+use crate::{
+    checksum, dictionary,
+    error::ReadError,
+    footer::{block_records, compute_footer, data_end, Footer, SIZE_FOOTER},
+    header::{SIZE_BLOCK_HEADER, SIZE_HEADER},
+    reader::{encoded_sequence_len, load_file, RecordBlock, RecordBlockIter},
+    BlockHeader, BlockRange, Codec, Error, RefRecord, Result, VBinseqHeader,
+};
+
+/// Severity of a validation issue
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// The record data cannot be read correctly
+    Fatal,
+
+    /// The record data is intact but auxiliary information is inconsistent
+    Recoverable,
+}
+
+/// Kind of problem found during validation
+#[derive(Debug, Clone, PartialEq)]
+pub enum IssueKind {
+    /// The file header could not be parsed
+    ///
+    /// The parameter describes the parsing error
+    InvalidHeader(String),
+
+    /// The file ends in the middle of a block header
+    ///
+    /// The parameter is the number of trailing bytes
+    TruncatedBlockHeader(usize),
+
+    /// A block header has an invalid magic number
+    ///
+    /// The parameter is the invalid magic number
+    InvalidBlockMagic(u64),
+
+    /// A block header records an unknown codec
+    ///
+    /// The parameter is the unknown codec byte
+    InvalidBlockCodec(u8),
+
+    /// An uncompressed block declares a size other than the block size of the file
+    BlockSizeMismatch {
+        /// Block size declared in the file header
+        expected: u64,
+        /// Size declared in the block header
+        found: u64,
+    },
+
+    /// A block extends past the end of the file
+    TruncatedBlock {
+        /// Size declared in the block header
+        expected: u64,
+        /// Number of bytes remaining in the file
+        available: u64,
+    },
+
+    /// The data of a block could not be decoded
+    ///
+    /// The parameter describes the decoding error
+    CorruptBlockData(String),
+
+    /// The number of decoded records differs from the record count of the block header
+    RecordCountMismatch {
+        /// Record count declared in the block header
+        declared: u32,
+        /// Number of records decoded from the block data
+        found: usize,
+    },
+
+    /// A block contains no records
+    EmptyBlock,
+
+    /// A record does not match its checksum
+    ///
+    /// The parameter is the index of the record in the file
+    RecordChecksumMismatch(u64),
+
+    /// A block does not match the checksum recorded in its block header
+    BlockChecksumMismatch {
+        /// Checksum recorded in the block header
+        expected: u64,
+        /// Checksum of the decompressed block data
+        found: u64,
+    },
+
+    /// The header announces a footer but it could not be parsed
+    ///
+    /// The parameter describes the parsing error
+    InvalidFooter(String),
+
+    /// The footer does not match the counts or content digest of the blocks
+    FooterMismatch,
+
+    /// The index file exists but could not be loaded
+    ///
+    /// The parameter describes the loading error
+    UnreadableIndex(String),
+
+    /// The index file describes a different number of blocks than the file contains
+    IndexBlockCountMismatch {
+        /// Number of blocks in the file
+        expected: usize,
+        /// Number of blocks in the index
+        found: usize,
+    },
+
+    /// An index entry differs from the block it describes
+    IndexRangeMismatch,
+}
+impl IssueKind {
+    /// Returns the severity of this kind of issue
+    pub fn severity(&self) -> Severity {
+        match self {
+            Self::EmptyBlock
+            | Self::UnreadableIndex(_)
+            | Self::IndexBlockCountMismatch { .. }
+            | Self::IndexRangeMismatch => Severity::Recoverable,
+            _ => Severity::Fatal,
+        }
+    }
+}
+impl fmt::Display for IssueKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidHeader(e) => write!(f, "invalid file header: {e}"),
+            Self::TruncatedBlockHeader(n) => {
+                write!(f, "file ends with {n} bytes of a truncated block header")
+            }
+            Self::InvalidBlockMagic(magic) => write!(f, "invalid block magic number: {magic}"),
+            Self::InvalidBlockCodec(byte) => write!(f, "invalid block codec: {byte}"),
+            Self::BlockSizeMismatch { expected, found } => write!(
+                f,
+                "uncompressed block declares size {found} but the block size is {expected}"
+            ),
+            Self::TruncatedBlock {
+                expected,
+                available,
+            } => write!(
+                f,
+                "block declares {expected} bytes but only {available} bytes remain"
+            ),
+            Self::CorruptBlockData(e) => write!(f, "block data could not be decoded: {e}"),
+            Self::RecordCountMismatch { declared, found } => write!(
+                f,
+                "block declares {declared} records but {found} records were decoded"
+            ),
+            Self::EmptyBlock => write!(f, "block contains no records"),
+            Self::RecordChecksumMismatch(index) => {
+                write!(f, "record {index} does not match its checksum")
+            }
+            Self::BlockChecksumMismatch { expected, found } => write!(
+                f,
+                "block checksum {found:#018x} does not match the recorded {expected:#018x}"
+            ),
+            Self::InvalidFooter(e) => write!(f, "invalid footer: {e}"),
+            Self::FooterMismatch => write!(f, "footer does not match the file content"),
+            Self::UnreadableIndex(e) => write!(f, "index could not be loaded: {e}"),
+            Self::IndexBlockCountMismatch { expected, found } => write!(
+                f,
+                "index describes {found} blocks but the file contains {expected} blocks"
+            ),
+            Self::IndexRangeMismatch => write!(f, "index entry differs from the block"),
+        }
+    }
+}
+
+/// A single problem found during validation
+#[derive(Debug, Clone, PartialEq)]
+pub struct Issue {
+    /// Kind of the problem
+    pub kind: IssueKind,
+
+    /// Index of the block the problem was found in (if block-specific)
+    pub block: Option<usize>,
+
+    /// Byte offset in the file where the problem was found (if known)
+    pub offset: Option<u64>,
+}
+impl Issue {
+    /// Returns the severity of the issue
+    pub fn severity(&self) -> Severity {
+        self.kind.severity()
+    }
+}
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.severity() {
+            Severity::Fatal => write!(f, "[fatal]")?,
+            Severity::Recoverable => write!(f, "[recoverable]")?,
+        }
+        if let Some(block) = self.block {
+            write!(f, " block {block}")?;
+        }
+        if let Some(offset) = self.offset {
+            write!(f, " (offset {offset})")?;
+        }
+        write!(f, ": {}", self.kind)
+    }
+}
+
+/// Structured result of validating a VBINSEQ file
+#[derive(Debug, Clone)]
+pub struct ValidationReport {
+    /// Header of the file (`None` if the header could not be parsed)
+    pub header: Option<VBinseqHeader>,
+
+    /// Number of blocks that were checked
+    pub n_blocks: usize,
+
+    /// Number of records decoded across all blocks
+    pub n_records: u64,
+
+    /// Whether an index file (or embedded index) was found and compared against the blocks
+    pub index_checked: bool,
+
+    /// All problems found during validation
+    issues: Vec<Issue>,
+}
+impl ValidationReport {
+    /// Returns all problems found during validation
+    pub fn issues(&self) -> &[Issue] {
+        &self.issues
+    }
+
+    /// Returns `true` if no problems were found
+    pub fn is_valid(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    /// Returns `true` if any problem prevents the data from being read correctly
+    pub fn is_fatal(&self) -> bool {
+        self.fatal().next().is_some()
+    }
+
+    /// Returns an iterator over the fatal problems
+    pub fn fatal(&self) -> impl Iterator<Item = &Issue> {
+        self.issues
+            .iter()
+            .filter(|issue| issue.severity() == Severity::Fatal)
+    }
+
+    /// Returns an iterator over the recoverable problems
+    pub fn recoverable(&self) -> impl Iterator<Item = &Issue> {
+        self.issues
+            .iter()
+            .filter(|issue| issue.severity() == Severity::Recoverable)
+    }
+
+    fn push(&mut self, kind: IssueKind, block: Option<usize>, offset: Option<usize>) {
+        self.issues.push(Issue {
+            kind,
+            block,
+            offset: offset.map(|x| x as u64),
+        });
+    }
+}
+impl fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Blocks checked:  {}", self.n_blocks)?;
+        writeln!(f, "Records:         {}", self.n_records)?;
+        writeln!(f, "Index checked:   {}", self.index_checked)?;
+        write!(f, "Issues:          {}", self.issues.len())?;
+        for issue in &self.issues {
+            write!(f, "\n  {issue}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Validates a VBINSEQ file and reports all problems found
+///
+/// Validation continues past problems whenever the block structure still allows it,
+/// so a single report lists as many issues as possible. Problems with the file's
+/// content are reported as issues rather than errors.
+///
+/// # Parameters
+///
+/// * `path` - Path to the VBINSEQ file to validate
+///
+/// # Returns
+///
+/// * `Ok(ValidationReport)` - The report of the validation
+/// * `Err(_)` - If the file could not be opened or memory-mapped
+pub fn check<P: AsRef<Path>>(path: P) -> Result<ValidationReport> {
+    let mmap = load_file(&path)?;
+
+    let mut report = ValidationReport {
+        header: None,
+        n_blocks: 0,
+        n_records: 0,
+        index_checked: false,
+        issues: Vec::new(),
+    };
+
+    // Validate the file header
+    let header = {
+        let mut header_bytes = [0u8; SIZE_HEADER];
+        if mmap.len() < SIZE_HEADER {
+            let e = format!("file is only {} bytes long", mmap.len());
+            report.push(IssueKind::InvalidHeader(e), None, Some(0));
+            return Ok(report);
+        }
+        header_bytes.copy_from_slice(&mmap[..SIZE_HEADER]);
+        match VBinseqHeader::from_bytes(&header_bytes) {
+            Ok(header) => header,
+            Err(e) => {
+                report.push(IssueKind::InvalidHeader(e.to_string()), None, Some(0));
+                return Ok(report);
+            }
+        }
+    };
+    report.header = Some(header);
+    if mmap.len() < header.data_offset() {
+        let e = format!(
+            "file is only {} bytes long but the header sections end at byte {}",
+            mmap.len(),
+            header.data_offset()
+        );
+        report.push(IssueKind::InvalidHeader(e), None, Some(0));
+        return Ok(report);
+    }
+    let dictionaries = match dictionary::load(&mmap, &header) {
+        Ok(dictionaries) => dictionaries,
+        Err(e) => {
+            report.push(
+                IssueKind::InvalidHeader(e.to_string()),
+                None,
+                Some(SIZE_HEADER),
+            );
+            return Ok(report);
+        }
+    };
+
+    // Locate the footer (if present)
+    let footer = match Footer::from_file_bytes(&mmap, &header) {
+        Ok(footer) => footer,
+        Err(e) => {
+            let offset = Some(mmap.len().saturating_sub(SIZE_FOOTER));
+            report.push(IssueKind::InvalidFooter(e.to_string()), None, offset);
+            return Ok(report);
+        }
+    };
+    let end = match data_end(&mmap, &header) {
+        Ok(end) => end,
+        Err(e) => {
+            let offset = Some(mmap.len().saturating_sub(SIZE_FOOTER));
+            report.push(IssueKind::InvalidFooter(e.to_string()), None, offset);
+            return Ok(report);
+        }
+    };
+
+    // Validate all blocks
+    let mut ranges = Vec::new();
+    let mut record_block = RecordBlock::new(header.block() as usize);
+    record_block.set_dictionaries(&dictionaries);
+    record_block.set_verify_checksums(true);
+    let mut dbuf = Vec::new();
+    let mut pos = header.data_offset();
+    let mut cumulative_records = 0;
+    while pos < end {
+        let block_id = Some(report.n_blocks);
+        if pos + SIZE_BLOCK_HEADER > end {
+            let kind = IssueKind::TruncatedBlockHeader(end - pos);
+            report.push(kind, block_id, Some(pos));
+            break;
+        }
+        let mut header_bytes = [0u8; SIZE_BLOCK_HEADER];
+        header_bytes.copy_from_slice(&mmap[pos..pos + SIZE_BLOCK_HEADER]);
+        let block_header = match BlockHeader::from_bytes(&header_bytes) {
+            Ok(block_header) => block_header,
+            Err(_) => {
+                let magic = u64::from_le_bytes(header_bytes[..8].try_into().unwrap());
+                report.push(IssueKind::InvalidBlockMagic(magic), block_id, Some(pos));
+                break;
+            }
+        };
+        let codec = match block_header.codec() {
+            Ok(codec) => codec.unwrap_or(header.codec()),
+            Err(_) => {
+                let kind = IssueKind::InvalidBlockCodec(block_header.reserved[0]);
+                report.push(kind, block_id, Some(pos));
+                break;
+            }
+        };
+        if codec == Codec::Uncompressed && block_header.size != header.block() {
+            let kind = IssueKind::BlockSizeMismatch {
+                expected: header.block(),
+                found: block_header.size,
+            };
+            report.push(kind, block_id, Some(pos));
+            break;
+        }
+
+        let data_start = pos + SIZE_BLOCK_HEADER;
+        let available = (end - data_start) as u64;
+        if block_header.size > available {
+            let kind = IssueKind::TruncatedBlock {
+                expected: block_header.size,
+                available,
+            };
+            report.push(kind, block_id, Some(pos));
+            break;
+        }
+        let data_end = data_start + block_header.size as usize;
+
+        // Decode the block and cross-check its record count
+        record_block.clear();
+        match record_block.ingest_block(&block_header, &mmap[data_start..data_end], &header) {
+            Ok(()) => {
+                let found = record_block.n_records();
+                if found == 0 {
+                    report.push(IssueKind::EmptyBlock, block_id, Some(pos));
+                }
+                report.n_records += found as u64;
+
+                // Localize corrupt records by their checksums
+                if header.has_record_crc() {
+                    let data = &mmap[data_start..data_end];
+                    match block_records(&block_header, data, &header, &dictionaries, &mut dbuf) {
+                        Ok(records) => {
+                            for (index, record) in (cumulative_records..).zip(records) {
+                                if !checksum::verify(record) {
+                                    let kind = IssueKind::RecordChecksumMismatch(index);
+                                    report.push(kind, block_id, Some(pos));
+                                }
+                            }
+                        }
+                        Err(e) => {
+                            let kind = IssueKind::CorruptBlockData(e.to_string());
+                            report.push(kind, block_id, Some(pos));
+                        }
+                    }
+                }
+            }
+            Err(Error::ReadError(ReadError::BlockChecksumMismatch(expected, found))) => {
+                let kind = IssueKind::BlockChecksumMismatch { expected, found };
+                report.push(kind, block_id, Some(pos));
+            }
+            Err(Error::ReadError(ReadError::RecordCountMismatch(declared, found))) => {
+                let kind = IssueKind::RecordCountMismatch { declared, found };
+                report.push(kind, block_id, Some(pos));
+                report.n_records += found as u64;
+            }
+            Err(e) => {
+                report.push(
+                    IssueKind::CorruptBlockData(e.to_string()),
+                    block_id,
+                    Some(pos),
+                );
+            }
+        }
+
+        ranges.push(BlockRange::new(
+            pos as u64,
+            block_header.size,
+            block_header.records,
+            cumulative_records,
+        ));
+        cumulative_records += u64::from(block_header.records);
+        report.n_blocks += 1;
+        pos = data_end;
+    }
+
+    // Validate the footer against the blocks (only meaningful if the blocks are readable)
+    if let Some(footer) = footer {
+        if !report.is_fatal() {
+            match compute_footer(&mmap, &header) {
+                Ok(computed) if computed.matches(&footer) => {}
+                Ok(_) => report.push(IssueKind::FooterMismatch, None, Some(end)),
+                Err(e) => report.push(IssueKind::CorruptBlockData(e.to_string()), None, None),
+            }
+        }
+    }
+
+    // Validate the embedded index and the index file against the blocks (if present)
+    if header.has_embedded_index() {
+        report.index_checked = true;
+        match BlockIndex::from_embedded(&mmap) {
+            Ok(index) => check_index(&mut report, &index, &ranges),
+            Err(e) => report.push(IssueKind::UnreadableIndex(e.to_string()), None, None),
+        }
+    }
+    #[cfg(feature = "compression")]
+    {
+        let mut index_path = path.as_ref().as_os_str().to_owned();
+        index_path.push(".vqi");
+        if Path::new(&index_path).exists() {
+            report.index_checked = true;
+            match BlockIndex::from_path(&index_path) {
+                Ok(index) => check_index(&mut report, &index, &ranges),
+                Err(e) => report.push(IssueKind::UnreadableIndex(e.to_string()), None, None),
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+/// Compares the ranges of an index against the ranges found in the file
+fn check_index(report: &mut ValidationReport, index: &BlockIndex, ranges: &[BlockRange]) {
+    if index.n_blocks() != ranges.len() {
+        let kind = IssueKind::IndexBlockCountMismatch {
+            expected: ranges.len(),
+            found: index.n_blocks(),
+        };
+        report.push(kind, None, None);
+    }
+    for (block_id, (indexed, found)) in index.ranges().iter().zip(ranges).enumerate() {
+        if indexed.start_offset != found.start_offset
+            || indexed.len != found.len
+            || indexed.block_records != found.block_records
+            || indexed.cumulative_records != found.cumulative_records
+        {
+            let offset = Some(found.start_offset as usize);
+            report.push(IssueKind::IndexRangeMismatch, Some(block_id), offset);
+        }
+    }
+}
+
+/// Iterator adapter validating every record of a block as it is yielded
+///
+/// Each record is checked against the header of its file:
+///
+/// * The encoded sequence buffers match the sequence lengths
+/// * Records have one quality score per nucleotide if the file has quality scores, and none
+///   otherwise
+/// * Records only have an extended sequence if the file is paired
+/// * The flag passes the flag check (if one is set with `with_flag_check`)
+///
+/// Invalid records are yielded as errors, and iteration continues with the next record.
+/// Created by `RecordBlock::iter_checked`.
+pub struct CheckedRecords<'a> {
+    /// The records to validate
+    records: RecordBlockIter<'a>,
+
+    /// The header of the file the records were read from
+    header: VBinseqHeader,
+
+    /// Returns true for valid flags
+    flag_check: Option<Box<dyn Fn(u64) -> bool + 'a>>,
+}
+impl<'a> CheckedRecords<'a> {
+    /// Creates an adapter validating records against the header of their file
+    ///
+    /// # Parameters
+    ///
+    /// * `records` - The records to validate
+    /// * `header` - The header of the file the records were read from
+    pub fn new(records: RecordBlockIter<'a>, header: &VBinseqHeader) -> Self {
+        Self {
+            records,
+            header: *header,
+            flag_check: None,
+        }
+    }
+
+    /// Additionally checks the flag of every record
+    ///
+    /// # Parameters
+    ///
+    /// * `check` - Called with the flag of every record, returns true for valid flags
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use vbinseq::MmapReader;
+    ///
+    /// let mut reader = MmapReader::new("example.vbq").unwrap();
+    /// let header = reader.header();
+    /// let mut block = reader.new_block();
+    /// reader.read_block_into(&mut block).unwrap();
+    ///
+    /// // Only the two lowest flag bits are in use
+    /// let n_invalid = block
+    ///     .iter_checked(&header)
+    ///     .with_flag_check(|flag| flag < 4)
+    ///     .filter(|result| result.is_err())
+    ///     .count();
+    /// ```
+    pub fn with_flag_check<F: Fn(u64) -> bool + 'a>(mut self, check: F) -> Self {
+        self.flag_check = Some(Box::new(check));
+        self
+    }
+
+    /// Validates a record against the header and the flag check
+    fn check(&self, record: &RefRecord<'_>) -> Result<()> {
+        let index = record.index();
+        let n_segments = self.header.segments().max(2);
+        for segment in 0..n_segments {
+            let (len, buf) = (record.segment_len(segment), record.segment_buf(segment));
+            if buf.len() != encoded_sequence_len(len, self.header.is_iupac()) {
+                return Err(ReadError::SequenceBufferMismatch(index, len, buf.len()).into());
+            }
+        }
+        if !self.header.paired() && record.xlen() > 0 {
+            return Err(ReadError::UnexpectedExtendedSequence(index).into());
+        }
+        // Records of files with optional quality scores may have none
+        let has_quality = self.header.has_base_bytes()
+            && !(self.header.has_optional_quality()
+                && (0..n_segments).all(|segment| record.segment_qual(segment).is_empty()));
+        for segment in 0..n_segments {
+            let (len, qual) = (record.segment_len(segment), record.segment_qual(segment));
+            let expected = if has_quality { len } else { 0 };
+            if qual.len() as u64 != expected {
+                return Err(ReadError::QualityLengthMismatch(index, qual.len(), len).into());
+            }
+        }
+        if let Some(check) = &self.flag_check {
+            if !check(record.flag()) {
+                return Err(ReadError::InvalidFlag(index, record.flag()).into());
+            }
+        }
+        Ok(())
+    }
+}
+impl<'a> Iterator for CheckedRecords<'a> {
+    type Item = Result<RefRecord<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let record = self.records.next()?;
+        Some(self.check(&record).map(|()| record))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conformance::TestVector;
+
+    #[test]
+    fn test_check_valid_and_corrupt() -> crate::Result<()> {
+        let path = std::env::temp_dir().join(format!("vbq_validate_{}.vbq", std::process::id()));
+        let vector = TestVector::generate(true, true, true);
+        let mut bytes = Vec::new();
+        vector.write_vbq(&mut bytes)?;
+
+        // An intact file has no issues
+        std::fs::write(&path, &bytes)?;
+        let report = check(&path)?;
+        assert!(report.is_valid(), "{report}");
+        assert_eq!(report.n_records, vector.records.len() as u64);
+
+        // A corrupted record count is fatal
+        bytes[SIZE_HEADER + 16] ^= 0xFF;
+        std::fs::write(&path, &bytes)?;
+        let report = check(&path)?;
+        assert!(report.is_fatal());
+        assert_eq!(report.fatal().next().unwrap().block, Some(0));
+
+        // A truncated file is fatal
+        std::fs::write(&path, &bytes[..bytes.len() - 1])?;
+        let report = check(&path)?;
+        assert!(report.is_fatal());
+
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_record_checksums() -> crate::Result<()> {
+        use crate::header::SIZE_BLOCK_HEADER;
+        use crate::testing::{assert_round_trip, write_records};
+        use crate::OwnedRecord;
+
+        let path = std::env::temp_dir().join(format!("vbq_crc_{}.vbq", std::process::id()));
+        let records: Vec<OwnedRecord> = (0..4)
+            .map(|i| OwnedRecord::new(i, b"ACGT".repeat(10), b"IIII#".repeat(8)))
+            .collect();
+        let mut header = VBinseqHeader::with_capacity(1024, true, false, false);
+        header.set_record_crc(true);
+        header.set_quality_transform(crate::QualityTransform::RunLength);
+        assert_round_trip(header, &records);
+        let mut bytes = write_records(header, &records)?;
+
+        // An intact file has no issues
+        std::fs::write(&path, &bytes)?;
+        assert!(check(&path)?.is_valid());
+
+        // Bit flips are localized to the records they affect
+        let record_size = 24 + 16 + 40 + checksum::SIZE_RECORD_CRC;
+        for record in [1, 3] {
+            bytes[SIZE_HEADER + SIZE_BLOCK_HEADER + record * record_size + 30] ^= 0x04;
+        }
+        std::fs::write(&path, &bytes)?;
+        let report = check(&path)?;
+        let kinds: Vec<_> = report.issues().iter().map(|issue| &issue.kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                &IssueKind::RecordChecksumMismatch(1),
+                &IssueKind::RecordChecksumMismatch(3)
+            ]
+        );
+        assert!(report.is_fatal());
+
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_block_checksums() -> crate::Result<()> {
+        use crate::header::SIZE_BLOCK_HEADER;
+        use crate::testing::write_records;
+        use crate::OwnedRecord;
+
+        let path = std::env::temp_dir().join(format!("vbq_bcrc_{}.vbq", std::process::id()));
+        let records: Vec<OwnedRecord> = (0..4)
+            .map(|i| OwnedRecord::new(i, b"ACGT".repeat(10), b"IIII#".repeat(8)))
+            .collect();
+        let mut header = VBinseqHeader::with_capacity(1024, true, false, false);
+        header.set_block_checksum(true);
+        let mut bytes = write_records(header, &records)?;
+        std::fs::write(&path, &bytes)?;
+        assert!(check(&path)?.is_valid());
+
+        // A flipped quality score passes record parsing but not the block checksum
+        bytes[SIZE_HEADER + SIZE_BLOCK_HEADER + 40] ^= 0x04;
+        std::fs::write(&path, &bytes)?;
+        let report = check(&path)?;
+        assert!(matches!(
+            report.issues()[0].kind,
+            IssueKind::BlockChecksumMismatch { .. }
+        ));
+        assert!(report.is_fatal());
+
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_checked_records() -> crate::Result<()> {
+        let vector = TestVector::generate(true, false, true);
+        let mut bytes = Vec::new();
+        vector.write_vbq(&mut bytes)?;
+        let mut reader = crate::MemoryReader::new(bytes)?;
+        let header = reader.header();
+        let mut block = reader.new_block();
+        assert!(reader.read_block_into(&mut block)?);
+
+        // Records of an intact file are valid
+        assert!(block.iter_checked(&header).all(|result| result.is_ok()));
+
+        // Rejected flags are reported
+        let n_odd = block.iter().filter(|record| record.flag() % 2 == 1).count();
+        let invalid: Vec<_> = block
+            .iter_checked(&header)
+            .with_flag_check(|flag| flag % 2 == 0)
+            .filter_map(|result| result.err())
+            .collect();
+        assert!(n_odd > 0);
+        assert_eq!(invalid.len(), n_odd);
+        assert!(invalid.iter().all(
+            |e| matches!(e, Error::ReadError(ReadError::InvalidFlag(_, flag)) if flag % 2 == 1)
+        ));
+
+        // Records not matching the header are reported
+        let mut unpaired = header;
+        unpaired.set_paired(false);
+        assert!(block.iter_checked(&unpaired).any(|result| matches!(
+            result,
+            Err(Error::ReadError(ReadError::UnexpectedExtendedSequence(_)))
+        )));
+        let mut unqualified = header;
+        unqualified.set_qual(false);
+        assert!(block.iter_checked(&unqualified).any(|result| matches!(
+            result,
+            Err(Error::ReadError(ReadError::QualityLengthMismatch(..)))
+        )));
+        Ok(())
+    }
+}
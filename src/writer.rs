@@ -29,16 +29,42 @@
 //! // Writer will automatically flush when dropped
 //! ```
 
-use std::io::Write;
-
-use byteorder::{LittleEndian, WriteBytesExt};
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::hash::{Hash, Hasher};
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use byteorder::{ByteOrder, LittleEndian, WriteBytesExt};
+#[cfg(feature = "policy-rand")]
 use rand::rngs::SmallRng;
+#[cfg(feature = "policy-rand")]
 use rand::SeedableRng;
-use zstd::Encoder as ZstdEncoder;
-
-use crate::error::{Result, WriteError};
-use crate::header::{BlockHeader, VBinseqHeader};
-use crate::Policy;
+use xxhash_rust::xxh3::xxh3_64;
+#[cfg(feature = "compression")]
+use zstd::bulk::Compressor;
+#[cfg(feature = "compression")]
+use zstd::stream::raw::CParameter;
+
+use crate::checksum::{self, SIZE_RECORD_CRC};
+use crate::codec;
+use crate::columnar::{self, Layout};
+use crate::dictionary::{self, MAX_DICTIONARIES};
+use crate::error::{HeaderError, Result, WriteError};
+use crate::footer::{hash_block, ContentHasher};
+use crate::header::{BlockHeader, Codec, VBinseqHeader, RECORD_NO_QUALITY, SIZE_AUX};
+use crate::homopolymer::collapse;
+use crate::merge::{MergeOptions, Merger};
+use crate::read_group::read_group;
+use crate::reader::{
+    encoded_sequence_len, load_file, parse_file_layout, read_next_raw_block, OwnedRecord, RawBlock,
+    RecordBlock, RefRecord,
+};
+use crate::sections::HeaderSections;
+use crate::{iupac, policy, Policy, QualityBins, QualityTransform};
 
 /// Random number generator seed used for encoding
 ///
@@ -108,6 +134,145 @@ pub fn record_byte_size_quality(schunk: usize, xchunk: usize, slen: usize, xlen:
     record_byte_size(schunk, xchunk) + slen + xlen
 }
 
+/// Why a writer skipped a record
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SkipReason {
+    /// The primary sequence has invalid nucleotides rejected by the policy
+    InvalidPrimary,
+
+    /// The extended sequence has invalid nucleotides rejected by the policy
+    InvalidExtended,
+}
+
+/// Counters of the records skipped by a writer
+///
+/// Records are skipped when the `Policy` of the writer rejects one of their sequences, in
+/// which case the write methods return `Ok(false)`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SkippedRecords {
+    /// Total number of skipped records
+    pub total: u64,
+
+    /// Number of records skipped for invalid nucleotides in the primary sequence
+    pub invalid_primary: u64,
+
+    /// Number of records skipped for invalid nucleotides in the extended sequence
+    pub invalid_extended: u64,
+}
+impl SkippedRecords {
+    /// Returns the number of records skipped for a reason
+    pub fn by_reason(&self, reason: SkipReason) -> u64 {
+        match reason {
+            SkipReason::InvalidPrimary => self.invalid_primary,
+            SkipReason::InvalidExtended => self.invalid_extended,
+        }
+    }
+
+    /// Counts a skipped record
+    fn count(&mut self, reason: SkipReason) {
+        self.total += 1;
+        match reason {
+            SkipReason::InvalidPrimary => self.invalid_primary += 1,
+            SkipReason::InvalidExtended => self.invalid_extended += 1,
+        }
+    }
+
+    /// Adds the counts of another writer
+    fn absorb(&mut self, other: &Self) {
+        self.total += other.total;
+        self.invalid_primary += other.invalid_primary;
+        self.invalid_extended += other.invalid_extended;
+    }
+}
+
+/// A record skipped by a writer, as passed to the skip callback
+#[derive(Debug, Clone, Copy)]
+pub struct SkippedRecord<'a> {
+    /// Flag of the record
+    pub flag: u64,
+
+    /// Primary sequence of the record
+    pub sequence: &'a [u8],
+
+    /// Extended sequence of the record (empty if not paired)
+    pub extended: &'a [u8],
+
+    /// Quality scores of the primary sequence (empty if not present)
+    pub squal: &'a [u8],
+
+    /// Quality scores of the extended sequence (empty if not present)
+    pub xqual: &'a [u8],
+
+    /// Why the record was skipped
+    pub reason: SkipReason,
+}
+
+/// Callback receiving the records skipped by a writer
+///
+/// The callback is shared with the shard writers of a `SyncWriter`, so it must be callable
+/// from multiple threads.
+pub type SkipCallback = Arc<dyn Fn(&SkippedRecord<'_>) + Send + Sync>;
+
+/// A record as passed to the record transform of a writer
+///
+/// The transform may change all fields in place (e.g. trim adapters, clip sequences, or
+/// move a barcode from the sequence into the flag). Quality scores must keep one score per
+/// nucleotide, and fields of features the file does not store (e.g. the extended sequence
+/// of an unpaired file) are ignored.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RecordInput {
+    /// Flag of the record
+    pub flag: u64,
+
+    /// Primary sequence of the record
+    pub sequence: Vec<u8>,
+
+    /// Extended sequence of the record (empty if not paired)
+    pub extended: Vec<u8>,
+
+    /// Quality scores of the primary sequence (empty if not present)
+    pub squal: Vec<u8>,
+
+    /// Quality scores of the extended sequence (empty if not present)
+    pub xqual: Vec<u8>,
+}
+impl RecordInput {
+    /// Replaces the fields with a copy of a record (reusing the buffers)
+    fn set(&mut self, flag: u64, sequence: &[u8], extended: &[u8], squal: &[u8], xqual: &[u8]) {
+        self.flag = flag;
+        for (buffer, bytes) in [
+            (&mut self.sequence, sequence),
+            (&mut self.extended, extended),
+            (&mut self.squal, squal),
+            (&mut self.xqual, xqual),
+        ] {
+            buffer.clear();
+            buffer.extend_from_slice(bytes);
+        }
+    }
+}
+
+/// Transform applied by a writer to every record before encoding
+///
+/// The transform is shared with the shard writers of a `SyncWriter`, so it must be callable
+/// from multiple threads.
+pub type RecordTransform = Arc<dyn Fn(&mut RecordInput) + Send + Sync>;
+
+/// Default zstd compression level of the blocks
+pub const DEFAULT_COMPRESSION_LEVEL: i32 = 3;
+
+/// zstd compression level of the long-read profile
+///
+/// See `VBinseqWriterBuilder::long_read_profile`.
+pub const LONG_READ_COMPRESSION_LEVEL: i32 = 12;
+
+/// Selects the compression dictionary of a block from its uncompressed bytes
+///
+/// Returns the position of the dictionary in the header sections, or `None` to compress
+/// the block without a dictionary. The selector is shared with the shard writers of a
+/// `SyncWriter`, so it must be callable from multiple threads.
+pub type DictionarySelector = Arc<dyn Fn(&[u8]) -> Option<usize> + Send + Sync>;
+
 /// A builder for creating configured VBinseqWriter instances
 ///
 /// This builder provides a fluent interface for configuring and creating a
@@ -138,6 +303,30 @@ pub struct VBinseqWriterBuilder {
     policy: Option<Policy>,
     /// Optional headless mode (used in parallel writing)
     headless: Option<bool>,
+    /// Optional fallback to uncompressed storage for incompressible blocks
+    compression_fallback: Option<bool>,
+    /// Optional pool of block buffers shared with other writers
+    buffer_pool: Option<BufferPool>,
+    /// Optional number of zstd worker threads per block
+    compression_workers: Option<u32>,
+    /// Optional zstd compression level
+    compression_level: Option<i32>,
+    /// Optional maximum time records stay in an unflushed block
+    flush_interval: Option<Duration>,
+    /// Optional maximum number of bytes held in an unflushed block
+    flush_threshold: Option<usize>,
+    /// Optional callback receiving skipped records
+    skip_callback: Option<SkipCallback>,
+    /// Optional merging of overlapping mates
+    merge_overlaps: Option<MergeOptions>,
+    /// Optional transform applied to records before encoding
+    record_transform: Option<RecordTransform>,
+    /// Optional sections written after the file header
+    sections: Option<HeaderSections>,
+    /// Optional selection of the compression dictionary of every block
+    dictionary_selector: Option<DictionarySelector>,
+    /// Optional writing to a temporary file renamed once finished
+    atomic: Option<bool>,
 }
 impl VBinseqWriterBuilder {
     /// Sets the header for the VBINSEQ file
@@ -160,7 +349,7 @@ impl VBinseqWriterBuilder {
     ///
     /// // Create a header with 64KB blocks and quality scores
     /// let mut header = VBinseqHeader::with_capacity(65536, true, true, true);
-    /// header.qual = true;
+    /// header.set_qual(true);
     ///
     /// let builder = VBinseqWriterBuilder::default().header(header);
     /// ```
@@ -222,1315 +411,6561 @@ impl VBinseqWriterBuilder {
         self
     }
 
-    /// Builds a VBinseqWriter with the configured settings
+    /// Sets whether incompressible blocks are stored uncompressed
     ///
-    /// This finalizes the builder and creates a new VBinseqWriter instance using
-    /// the provided writer and the configured settings. If any settings were not
-    /// explicitly set, default values will be used.
+    /// When enabled, blocks of a compressed file whose compressed size would not be
+    /// smaller than the block size are stored as-is instead. The codec of each block
+    /// is recorded in its block header so readers handle such mixed files transparently.
+    /// Enabling the fallback sets the codec fallback flag of the header (see
+    /// `VBinseqHeader::set_codec_fallback`), so readers unaware of mixed files reject them.
+    /// Without this setting, the fallback is enabled if the header has the flag.
+    ///
+    /// This has no effect on files without compression.
     ///
     /// # Parameters
     ///
-    /// * `inner` - The underlying writer where data will be written
+    /// * `fallback` - Whether to store incompressible blocks uncompressed
     ///
     /// # Returns
     ///
-    /// * `Ok(VBinseqWriter)` - A configured VBinseqWriter ready for use
-    /// * `Err(_)` - If an error occurred while initializing the writer
+    /// The builder with the compression fallback configured
     ///
     /// # Examples
     ///
     /// ```rust,no_run
-    /// use vbinseq::VBinseqWriterBuilder;
-    /// use std::fs::File;
+    /// use vbinseq::{VBinseqWriterBuilder, VBinseqHeader};
     ///
-    /// let file = File::create("example.vbq").unwrap();
-    /// let mut writer = VBinseqWriterBuilder::default()
-    ///     .build(file)
-    ///     .unwrap();
+    /// let builder = VBinseqWriterBuilder::default()
+    ///     .header(VBinseqHeader::new(true, true, false))
+    ///     .compression_fallback(true);
     /// ```
-    pub fn build<W: Write>(self, inner: W) -> Result<VBinseqWriter<W>> {
-        VBinseqWriter::new(
-            inner,
-            self.header.unwrap_or_default(),
-            self.policy.unwrap_or_default(),
-            self.headless.unwrap_or(false),
-        )
-    }
-}
-
-/// Writer for VBINSEQ format files
-///
-/// The `VBinseqWriter` handles writing nucleotide sequence data to VBINSEQ files in a
-/// block-based format. It manages the file structure, compression settings, and ensures
-/// data is properly encoded and organized.
-///
-/// ## File Structure
-///
-/// A VBINSEQ file consists of:
-/// 1. A file header that defines parameters like block size and compression settings
-/// 2. A series of blocks, each with:
-///    - A block header with metadata (e.g., record count)
-///    - A collection of encoded records
-///
-/// Each block is filled with records until either the block is full or no more complete
-/// records can fit. The writer automatically handles block boundaries and creates new
-/// blocks as needed.
-///
-/// ## Usage
-///
-/// The writer supports multiple formats:
-/// - Single-end sequences with or without quality scores
-/// - Paired-end sequences with or without quality scores
-///
-/// It's recommended to use the `VBinseqWriterBuilder` to create and configure a writer
-/// instance with the appropriate settings.
-///
-/// ```rust,no_run
-/// use vbinseq::{VBinseqWriterBuilder, VBinseqHeader};
-/// use std::fs::File;
-///
-/// // Create a writer for single-end reads
-/// let file = File::create("example.vbq").unwrap();
-/// let mut writer = VBinseqWriterBuilder::default()
-///     .header(VBinseqHeader::default())
-///     .build(file)
-///     .unwrap();
-///
-/// // Write a sequence
-/// let flag = 0; // No special flags
-/// let sequence = b"ACGTACGTACGT";
-/// writer.write_nucleotides(flag, sequence).unwrap();
-///
-/// // Writer automatically flushes when dropped
-/// ```
-#[derive(Clone)]
-pub struct VBinseqWriter<W: Write> {
-    /// Inner Writer
-    inner: W,
-
-    /// Header of the file
-    header: VBinseqHeader,
-
-    /// Encoder for nucleotide sequences
-    encoder: Encoder,
-
-    /// Pre-initialized writer for compressed blocks
-    cblock: BlockWriter,
-}
-impl<W: Write> VBinseqWriter<W> {
-    pub fn new(inner: W, header: VBinseqHeader, policy: Policy, headless: bool) -> Result<Self> {
-        let mut wtr = Self {
-            inner,
-            header,
-            encoder: Encoder::with_policy(policy),
-            cblock: BlockWriter::new(header.block as usize, header.compressed),
-        };
-        if !headless {
-            wtr.init()?;
-        }
-        Ok(wtr)
+    pub fn compression_fallback(mut self, fallback: bool) -> Self {
+        self.compression_fallback = Some(fallback);
+        self
     }
 
-    /// Initializes the writer by writing the file header
-    ///
-    /// This method is called automatically during creation unless headless mode is enabled.
-    /// It writes the VBinseqHeader to the underlying writer.
+    /// Sets a pool of block buffers shared with other writers
     ///
-    /// # Returns
+    /// By default every writer owns its block buffers for its whole lifetime. With a
+    /// pool, writers only hold a block buffer while they have records pending and only
+    /// borrow a compression buffer while flushing a block, so the memory of many
+    /// simultaneously open writers (e.g. when demultiplexing or sharding) is bounded by
+    /// the number of writers with pending records rather than by the number of writers.
     ///
-    /// * `Ok(())` - If the header was successfully written
-    /// * `Err(_)` - If an error occurred during writing
-    fn init(&mut self) -> Result<()> {
-        self.header.write_bytes(&mut self.inner)?;
-        Ok(())
-    }
-
-    /// Checks if the writer is configured for paired-end reads
+    /// # Parameters
     ///
-    /// This method returns whether the writer expects paired-end reads based on the
-    /// header settings. If true, you should use `write_paired_nucleotides` instead of
-    /// `write_nucleotides` to write sequences.
+    /// * `pool` - The pool to take buffers from and return them to
     ///
     /// # Returns
     ///
-    /// `true` if the writer is configured for paired-end reads, `false` otherwise
+    /// The builder with the buffer pool configured
     ///
     /// # Examples
     ///
     /// ```rust,no_run
-    /// use vbinseq::{VBinseqWriterBuilder, VBinseqHeader};
+    /// use vbinseq::{BufferPool, VBinseqWriterBuilder};
     /// use std::fs::File;
     ///
-    /// // Create a header for paired-end reads
-    /// let mut header = VBinseqHeader::default();
-    /// header.paired = true;
-    ///
-    /// let file = File::create("paired_reads.vbq").unwrap();
-    /// let writer = VBinseqWriterBuilder::default()
-    ///     .header(header)
-    ///     .build(file)
-    ///     .unwrap();
-    ///
-    /// assert!(writer.is_paired());
+    /// let pool = BufferPool::new();
+    /// let writers: Vec<_> = (0..4)
+    ///     .map(|i| {
+    ///         VBinseqWriterBuilder::default()
+    ///             .buffer_pool(pool.clone())
+    ///             .build(File::create(format!("sample_{i}.vbq")).unwrap())
+    ///             .unwrap()
+    ///     })
+    ///     .collect();
     /// ```
-    pub fn is_paired(&self) -> bool {
-        self.header.paired
+    pub fn buffer_pool(mut self, pool: BufferPool) -> Self {
+        self.buffer_pool = Some(pool);
+        self
     }
 
-    /// Checks if the writer is configured for quality scores
+    /// Sets the number of zstd worker threads used to compress each block
     ///
-    /// This method returns whether the writer expects quality scores based on the
-    /// header settings. If true, you should use methods that include quality scores
-    /// (`write_nucleotides_with_quality` or `write_paired_nucleotides_with_quality`)
-    /// to write sequences.
+    /// By default each block is compressed on the calling thread. With workers, zstd
+    /// splits the compression of a block into jobs run on its own background threads,
+    /// so very large blocks (e.g. for long reads) are compressed on multiple cores even
+    /// if the application writes from a single thread. zstd only splits inputs larger
+    /// than its job size (several MB at the default compression level), so this has no
+    /// benefit for blocks of the default size.
+    ///
+    /// A value of 0 compresses on the calling thread. This has no effect on files
+    /// without compression.
+    ///
+    /// # Parameters
+    ///
+    /// * `workers` - The number of zstd worker threads
     ///
     /// # Returns
     ///
-    /// `true` if the writer is configured for quality scores, `false` otherwise
+    /// The builder with the compression workers configured
     ///
     /// # Examples
     ///
     /// ```rust,no_run
     /// use vbinseq::{VBinseqWriterBuilder, VBinseqHeader};
-    /// use std::fs::File;
-    ///
-    /// // Create a header for sequences with quality scores
-    /// let mut header = VBinseqHeader::default();
-    /// header.qual = true;
-    ///
-    /// let file = File::create("reads_with_quality.vbq").unwrap();
-    /// let writer = VBinseqWriterBuilder::default()
-    ///     .header(header)
-    ///     .build(file)
-    ///     .unwrap();
     ///
-    /// assert!(writer.has_quality());
+    /// // 16MB blocks compressed with 8 worker threads
+    /// let builder = VBinseqWriterBuilder::default()
+    ///     .header(VBinseqHeader::with_capacity(16 * 1024 * 1024, true, true, false))
+    ///     .compression_workers(8);
     /// ```
-    pub fn has_quality(&self) -> bool {
-        self.header.qual
+    pub fn compression_workers(mut self, workers: u32) -> Self {
+        self.compression_workers = Some(workers);
+        self
     }
 
-    /// Writes a single nucleotide sequence to the file
+    /// Sets the zstd compression level of the blocks
     ///
-    /// This method encodes and writes a single nucleotide sequence to the VBINSEQ file.
-    /// It automatically handles block boundaries and will create a new block if the
-    /// current one cannot fit the encoded record.
+    /// Higher levels compress better but slower, and decompression speed is hardly
+    /// affected. This has no effect on files without compression. In XZ files the level
+    /// selects the XZ preset (clamped to 0-9), and LZ4 files ignore it.
     ///
     /// # Parameters
     ///
-    /// * `flag` - A 64-bit flag that can store custom metadata about the sequence
-    /// * `sequence` - The nucleotide sequence to write (typically ASCII: A, C, G, T, N)
+    /// * `level` - The zstd compression level (default: `DEFAULT_COMPRESSION_LEVEL`)
     ///
     /// # Returns
     ///
-    /// * `Ok(true)` - If the sequence was successfully encoded and written
-    /// * `Ok(false)` - If the sequence could not be encoded (e.g., invalid characters)
-    /// * `Err(_)` - If an error occurred during writing or if the writer is configured
-    ///   for quality scores or paired-end reads
+    /// The builder with the compression level configured
+    pub fn compression_level(mut self, level: i32) -> Self {
+        self.compression_level = Some(level);
+        self
+    }
+
+    /// Applies the settings suited to long reads (e.g. nanopore or PacBio)
     ///
-    /// # Errors
+    /// This sets the header to `VBinseqHeader::long_read_profile` (multi-megabyte blocks,
+    /// compressed, with run-length transformed quality scores) and the compression level
+    /// to `LONG_READ_COMPRESSION_LEVEL`, as large blocks of long reads are written at a
+    /// low rate of records. Options set afterwards take precedence, e.g. a header without
+    /// quality scores for FASTA input.
     ///
-    /// Returns an error if:
-    /// - The writer is configured for quality scores (`WriteError::QualityFlagSet`)
-    /// - The writer is configured for paired-end reads (`WriteError::PairedFlagSet`)
-    /// - An I/O error occurred while writing
+    /// Records must still fit in a single block, so reads longer than about 13 megabases
+    /// (with quality scores) require a header with larger blocks.
+    ///
+    /// # Returns
+    ///
+    /// The builder with the long-read settings configured
     ///
     /// # Examples
     ///
-    /// ```rust,no_run
-    /// use vbinseq::{VBinseqWriterBuilder, VBinseqHeader};
-    /// use std::fs::File;
+    /// ```rust
+    /// use vbinseq::VBinseqWriterBuilder;
     ///
-    /// let file = File::create("example.vbq").unwrap();
     /// let mut writer = VBinseqWriterBuilder::default()
-    ///     .build(file)
+    ///     .long_read_profile()
+    ///     .build(Vec::new())
     ///     .unwrap();
     ///
-    /// // Write a sequence with a custom flag
-    /// let flag = 0x1234; // Some arbitrary metadata
-    /// let sequence = b"ACGTACGTACGT";
-    /// writer.write_nucleotides(flag, sequence).unwrap();
+    /// // A 1 Mbp read exceeds the default block size
+    /// let read = b"ACGT".repeat(250_000);
+    /// let qual = vec![b'5'; read.len()];
+    /// writer.write_nucleotides_quality(0, &read, &qual).unwrap();
+    /// writer.finish().unwrap();
     /// ```
-    pub fn write_nucleotides(&mut self, flag: u64, sequence: &[u8]) -> Result<bool> {
-        // Validate the right write operation is being used
-        if self.header.qual {
-            return Err(WriteError::QualityFlagSet.into());
-        }
-        if self.header.paired {
-            return Err(WriteError::PairedFlagSet.into());
-        }
-
-        // encode the sequence
-        if let Some(sbuffer) = self.encoder.encode_single(sequence)? {
-            let record_size = record_byte_size(sbuffer.len(), 0);
-            if self.cblock.exceeds_block_size(record_size)? {
-                self.cblock.flush(&mut self.inner)?;
-            }
-
-            // Write the flag, length, and sequence to the block
-            self.cblock
-                .write_record(flag, sequence.len() as u64, 0, sbuffer, None, None, None)?;
-
-            // Return true if the sequence was successfully written
-            Ok(true)
-        } else {
-            // Silently ignore sequences that fail encoding
-            Ok(false)
-        }
+    pub fn long_read_profile(mut self) -> Self {
+        self.header = Some(VBinseqHeader::long_read_profile());
+        self.compression_level = Some(LONG_READ_COMPRESSION_LEVEL);
+        self
     }
 
-    /// Writes a paired-end nucleotide sequence to the file
+    /// Sets the maximum time records are held in a partial block
     ///
-    /// This method encodes and writes a paired-end nucleotide sequence (two related sequences)
-    /// to the VBINSEQ file. It automatically handles block boundaries and will create a new
-    /// block if the current one cannot fit the encoded record.
+    /// By default a block is only written once it is full, so a slow producer (e.g. live
+    /// basecalling) may hold records for a long time before they become readable. With an
+    /// interval, the current block is flushed to the inner writer (and the inner writer is
+    /// flushed) as soon as its oldest record is older than the interval.
+    ///
+    /// The interval is checked whenever a record is written and on calls to
+    /// `VBinseqWriter::poll_flush`, which producers can call periodically to flush while no
+    /// records arrive. Flushed partial blocks occupy a full block in uncompressed files.
     ///
     /// # Parameters
     ///
-    /// * `flag` - A 64-bit flag that can store custom metadata about the sequence pair
-    /// * `primary` - The primary nucleotide sequence (typically the forward read)
-    /// * `extended` - The extended nucleotide sequence (typically the reverse read)
+    /// * `interval` - The maximum age of the oldest record of an unflushed block
     ///
     /// # Returns
     ///
-    /// * `Ok(true)` - If the sequence pair was successfully encoded and written
-    /// * `Ok(false)` - If the sequence pair could not be encoded
-    /// * `Err(_)` - If an error occurred during writing or if the writer is not configured
-    ///   for paired-end reads
-    ///
-    /// # Errors
-    ///
-    /// Returns an error if:
-    /// - The writer is configured for quality scores (`WriteError::QualityFlagSet`)
-    /// - The writer is not configured for paired-end reads (`WriteError::PairedFlagNotSet`)
-    /// - An I/O error occurred while writing
+    /// The builder with the flush interval configured
     ///
     /// # Examples
     ///
     /// ```rust,no_run
-    /// use vbinseq::{VBinseqWriterBuilder, VBinseqHeader};
-    /// use std::fs::File;
-    ///
-    /// // Create a header for paired-end reads
-    /// let mut header = VBinseqHeader::default();
-    /// header.paired = true;
-    ///
-    /// let file = File::create("paired_reads.vbq").unwrap();
-    /// let mut writer = VBinseqWriterBuilder::default()
-    ///     .header(header)
-    ///     .build(file)
-    ///     .unwrap();
+    /// use vbinseq::VBinseqWriterBuilder;
+    /// use std::time::Duration;
     ///
-    /// // Write a paired sequence
-    /// let flag = 0;
-    /// let forward_read = b"ACGTACGTACGT";
-    /// let reverse_read = b"TGCATGCATGCA";
-    /// writer.write_nucleotides_paired(flag, forward_read, reverse_read).unwrap();
+    /// // Make records readable at most 5 seconds after they were written
+    /// let builder = VBinseqWriterBuilder::default().flush_interval(Duration::from_secs(5));
     /// ```
-    pub fn write_nucleotides_paired(
-        &mut self,
-        flag: u64,
-        primary: &[u8],
-        extended: &[u8],
-    ) -> Result<bool> {
-        // Validate the right write operation is being used
-        if self.header.qual {
-            return Err(WriteError::QualityFlagSet.into());
-        }
-        if !self.header.paired {
-            return Err(WriteError::PairedFlagNotSet.into());
-        }
-
-        if let Some((sbuffer, xbuffer)) = self.encoder.encode_paired(primary, extended)? {
-            // Check if the current block can handle the next record
-            let record_size = record_byte_size(sbuffer.len(), xbuffer.len());
-            if self.cblock.exceeds_block_size(record_size)? {
-                self.cblock.flush(&mut self.inner)?;
-            }
-
-            // Write the flag, length, and sequence to the block
-            self.cblock.write_record(
-                flag,
-                primary.len() as u64,
-                extended.len() as u64,
-                sbuffer,
-                None,
-                Some(xbuffer),
-                None,
-            )?;
-
-            // Return true if the record was successfully written
-            Ok(true)
-        } else {
-            // Return false if the record was not successfully written
-            Ok(false)
-        }
+    pub fn flush_interval(mut self, interval: Duration) -> Self {
+        self.flush_interval = Some(interval);
+        self
     }
 
-    /// Writes a nucleotide sequence with quality scores to the file
+    /// Sets the number of bytes after which a partial block is flushed
     ///
-    /// This method encodes and writes a single nucleotide sequence with corresponding
-    /// quality scores to the VBINSEQ file. Quality scores are typically in the Phred scale
-    /// (encoded as ASCII characters). It automatically handles block boundaries and will
-    /// create a new block if the current one cannot fit the encoded record.
+    /// Like `flush_interval`, but the current block is flushed as soon as it holds at
+    /// least `threshold` bytes of records. Thresholds at or above the block size have
+    /// no effect.
     ///
     /// # Parameters
     ///
-    /// * `flag` - A 64-bit flag that can store custom metadata about the sequence
-    /// * `sequence` - The nucleotide sequence to write (typically ASCII: A, C, G, T, N)
-    /// * `quality` - The quality scores corresponding to each base in the sequence
+    /// * `threshold` - The number of record bytes that triggers a flush
     ///
     /// # Returns
     ///
-    /// * `Ok(true)` - If the sequence and quality scores were successfully encoded and written
-    /// * `Ok(false)` - If the sequence could not be encoded
-    /// * `Err(_)` - If an error occurred during writing or if the writer is not configured
-    ///   for quality scores
-    ///
-    /// # Errors
-    ///
-    /// Returns an error if:
-    /// - The writer is not configured for quality scores (`WriteError::QualityFlagNotSet`)
-    /// - The writer is configured for paired-end reads (`WriteError::PairedFlagSet`)
-    /// - An I/O error occurred while writing
+    /// The builder with the flush threshold configured
     ///
     /// # Examples
     ///
     /// ```rust,no_run
-    /// use vbinseq::{VBinseqWriterBuilder, VBinseqHeader};
-    /// use std::fs::File;
-    ///
-    /// // Create a header for sequences with quality scores
-    /// let mut header = VBinseqHeader::default();
-    /// header.qual = true;
-    ///
-    /// let file = File::create("reads_with_quality.vbq").unwrap();
-    /// let mut writer = VBinseqWriterBuilder::default()
-    ///     .header(header)
-    ///     .build(file)
-    ///     .unwrap();
+    /// use vbinseq::VBinseqWriterBuilder;
     ///
-    /// // Write a sequence with quality scores
-    /// let flag = 0;
-    /// let sequence = b"ACGTACGTACGT";
-    /// let quality = b"IIIIIIEEEEEE"; // Example quality scores in ASCII format
-    /// writer.write_nucleotides_quality(flag, sequence, quality).unwrap();
+    /// // Flush every 64KB of records
+    /// let builder = VBinseqWriterBuilder::default().flush_threshold(64 * 1024);
     /// ```
-    pub fn write_nucleotides_quality(
-        &mut self,
-        flag: u64,
-        sequence: &[u8],
-        quality: &[u8],
-    ) -> Result<bool> {
-        // Validate the right write operation is being used
-        if !self.header.qual {
-            return Err(WriteError::QualityFlagNotSet.into());
-        }
-        if self.header.paired {
-            return Err(WriteError::PairedFlagSet.into());
-        }
-
-        if let Some(sbuffer) = self.encoder.encode_single(sequence)? {
-            // Check if the current block can handle the next record
-            let record_size = record_byte_size_quality(sbuffer.len(), 0, quality.len(), 0);
-            if self.cblock.exceeds_block_size(record_size)? {
-                self.cblock.flush(&mut self.inner)?;
-            }
-
-            // Write the flag, length, sequence, and quality scores to the block
-            self.cblock.write_record(
-                flag,
-                sequence.len() as u64,
-                0,
-                sbuffer,
-                Some(quality),
-                None,
-                None,
-            )?;
-
-            // Return true if the record was written successfully
-            Ok(true)
-        } else {
-            // Return false if the record was not written successfully
-            Ok(false)
-        }
+    pub fn flush_threshold(mut self, threshold: usize) -> Self {
+        self.flush_threshold = Some(threshold);
+        self
     }
 
-    /// Writes paired-end nucleotide sequences with quality scores to the file
+    /// Sets a callback receiving every record skipped by the policy
     ///
-    /// This method encodes and writes paired-end nucleotide sequences with their corresponding
-    /// quality scores to the VBINSEQ file. It's designed for paired-end sequencing data where
-    /// each fragment is sequenced from both ends. The method automatically handles block
-    /// boundaries and will create a new block if the current one cannot fit the encoded record.
+    /// Without a callback, records rejected by the `Policy` are only counted (see
+    /// `VBinseqWriter::skipped`). With a callback, pipelines can log or divert them.
     ///
     /// # Parameters
     ///
-    /// * `flag` - A 64-bit flag that can store custom metadata about the sequence pair
-    /// * `s_seq` - The primary nucleotide sequence (typically the forward read)
-    /// * `x_seq` - The extended nucleotide sequence (typically the reverse read)
-    /// * `s_qual` - The quality scores corresponding to each base in the primary sequence
-    /// * `x_qual` - The quality scores corresponding to each base in the extended sequence
+    /// * `callback` - Called with every skipped record
     ///
     /// # Returns
     ///
-    /// * `Ok(true)` - If the sequences and quality scores were successfully encoded and written
-    /// * `Ok(false)` - If the sequences could not be encoded
-    /// * `Err(_)` - If an error occurred during writing or if the writer is not configured
-    ///   for quality scores and paired-end reads
-    ///
-    /// # Errors
-    ///
-    /// Returns an error if:
-    /// - The writer is not configured for quality scores (`WriteError::QualityFlagNotSet`)
-    /// - The writer is not configured for paired-end reads (`WriteError::PairedFlagNotSet`)
-    /// - An I/O error occurred while writing
+    /// The builder with the skip callback configured
     ///
     /// # Examples
     ///
     /// ```rust,no_run
-    /// use vbinseq::{VBinseqWriterBuilder, VBinseqHeader};
-    /// use std::fs::File;
-    ///
-    /// // Create a header for paired-end reads with quality scores
-    /// let mut header = VBinseqHeader::default();
-    /// header.qual = true;
-    /// header.paired = true;
-    ///
-    /// let file = File::create("paired_reads_with_quality.vbq").unwrap();
-    /// let mut writer = VBinseqWriterBuilder::default()
-    ///     .header(header)
-    ///     .build(file)
-    ///     .unwrap();
+    /// use vbinseq::VBinseqWriterBuilder;
     ///
-    /// // Write paired sequences with quality scores
-    /// let flag = 0;
-    /// let forward_read = b"ACGTACGTACGT";
-    /// let reverse_read = b"TGCATGCATGCA";
-    /// let forward_quality = b"IIIIIIEEEEEE"; // Example quality scores
-    /// let reverse_quality = b"EEEEEEIIIIEE"; // Example quality scores
-    /// writer.write_nucleotides_quality_paired(
-    ///     flag,
-    ///     forward_read,
-    ///     reverse_read,
-    ///     forward_quality,
-    ///     reverse_quality
-    /// ).unwrap();
+    /// let builder = VBinseqWriterBuilder::default().skip_callback(|record| {
+    ///     eprintln!("Skipped record {} ({:?})", record.flag, record.reason);
+    /// });
     /// ```
-    pub fn write_nucleotides_quality_paired(
-        &mut self,
-        flag: u64,
-        s_seq: &[u8],
-        x_seq: &[u8],
-        s_qual: &[u8],
-        x_qual: &[u8],
-    ) -> Result<bool> {
-        // Validate the right write operation is being used
-        if !self.header.qual {
-            return Err(WriteError::QualityFlagNotSet.into());
-        }
-        if !self.header.paired {
-            return Err(WriteError::PairedFlagNotSet.into());
-        }
-
-        if let Some((sbuffer, xbuffer)) = self.encoder.encode_paired(s_seq, x_seq)? {
-            // Check if the current block can handle the next record
-            let record_size =
-                record_byte_size_quality(sbuffer.len(), xbuffer.len(), s_qual.len(), x_qual.len());
-            if self.cblock.exceeds_block_size(record_size)? {
-                self.cblock.flush(&mut self.inner)?;
-            }
-
-            // Write the flag, length, sequence, and quality scores to the block
-            self.cblock.write_record(
-                flag,
-                s_seq.len() as u64,
-                x_seq.len() as u64,
-                sbuffer,
-                Some(s_qual),
-                Some(xbuffer),
-                Some(x_qual),
-            )?;
-
-            // Return true if the record was successfully written
-            Ok(true)
-        } else {
-            // Return false if the record was not successfully written
-            Ok(false)
-        }
+    pub fn skip_callback<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(&SkippedRecord<'_>) + Send + Sync + 'static,
+    {
+        self.skip_callback = Some(Arc::new(callback));
+        self
     }
 
-    /// Finishes writing and flushes all data to the underlying writer
+    /// Sets the sections written between the file header and the first record block
     ///
-    /// This method should be called when you're done writing to ensure all data
-    /// is properly flushed to the underlying writer. It's automatically called
-    /// when the writer is dropped, but calling it explicitly allows you to handle
-    /// any errors that might occur during flushing.
+    /// The sections hold variable-length file metadata, such as the read-group table
+    /// (see the `sections` and `read_group` modules). The writer records their size in
+    /// the header, replacing the size of the header passed to `header`, so the header of
+    /// another file can be reused without its sections.
+    ///
+    /// # Parameters
+    ///
+    /// * `sections` - The sections to write
     ///
     /// # Returns
     ///
-    /// * `Ok(())` - If all data was successfully flushed
-    /// * `Err(_)` - If an error occurred during flushing
+    /// The builder with the header sections configured
     ///
     /// # Examples
     ///
-    /// ```rust,no_run
-    /// use vbinseq::{VBinseqWriterBuilder, VBinseqHeader};
-    /// use std::fs::File;
+    /// ```rust
+    /// use vbinseq::read_group::ReadGroup;
+    /// use vbinseq::sections::HeaderSections;
+    /// use vbinseq::VBinseqWriterBuilder;
     ///
-    /// let file = File::create("example.vbq").unwrap();
-    /// let mut writer = VBinseqWriterBuilder::default()
-    ///     .build(file)
+    /// let sections = HeaderSections {
+    ///     read_groups: vec![ReadGroup::new("lane1").with_tag("PL", "ILLUMINA")],
+    ///     ..HeaderSections::default()
+    /// };
+    /// let writer = VBinseqWriterBuilder::default()
+    ///     .sections(sections)
+    ///     .build(Vec::new())
     ///     .unwrap();
-    ///
-    /// // Write some sequences...
-    /// let sequence = b"ACGTACGTACGT";
-    /// writer.write_nucleotides(0, sequence).unwrap();
-    ///
-    /// // Manually finish and check for errors
-    /// if let Err(e) = writer.finish() {
-    ///     eprintln!("Error flushing data: {}", e);
-    /// }
+    /// assert!(writer.header().sections_size() > 0);
     /// ```
-    pub fn finish(&mut self) -> Result<()> {
-        self.cblock.flush(&mut self.inner)?;
-        self.inner.flush()?;
-        Ok(())
-    }
-
-    /// Provides a mutable reference to the inner writer
-    fn by_ref(&mut self) -> &mut W {
-        self.inner.by_ref()
-    }
-
-    /// Provides a mutable reference to the BlockWriter
-    fn cblock_mut(&mut self) -> &mut BlockWriter {
-        &mut self.cblock
+    pub fn sections(mut self, sections: HeaderSections) -> Self {
+        self.sections = Some(sections);
+        self
     }
 
-    /// Ingests data from another VBinseqWriter that uses a Vec<u8> as its inner writer
-    ///
-    /// This method is particularly useful for parallel processing, where multiple writers
-    /// might be writing to memory buffers and need to be combined into a single file. It
-    /// transfers all complete blocks and any partial blocks from the other writer into this one.
+    /// Adds a user-defined key/value entry to the metadata of the file
     ///
-    /// The method clears the other writer's buffer after ingestion, allowing it to be reused.
+    /// The metadata is stored in the header sections (see `HeaderSections::metadata`), e.g.
+    /// to keep the sample, instrument, or command line with the data. Entries are kept in
+    /// the order they are added, and are added to the sections set with `sections` if
+    /// that is called first.
     ///
     /// # Parameters
     ///
-    /// * `other` - Another VBinseqWriter whose inner writer is a Vec<u8>
+    /// * `key` - The key of the entry
+    /// * `value` - The value of the entry
     ///
     /// # Returns
     ///
-    /// * `Ok(())` - If ingestion was successful
-    /// * `Err(_)` - If an error occurred during ingestion or if the headers are incompatible
+    /// The builder with the metadata entry added
     ///
-    /// # Errors
+    /// # Examples
     ///
-    /// Returns an error if:
-    /// - The headers of the two writers are not compatible (`WriteError::IncompatibleHeaders`)
-    /// - An I/O error occurred during data transfer
+    /// ```rust
+    /// use vbinseq::{MemoryReader, VBinseqWriterBuilder};
+    ///
+    /// let mut bytes = Vec::new();
+    /// let mut writer = VBinseqWriterBuilder::default()
+    ///     .metadata("sample", "NA12878")
+    ///     .metadata("instrument", "NovaSeq 6000")
+    ///     .build(&mut bytes)
+    ///     .unwrap();
+    /// writer.write_nucleotides(0, b"ACGT").unwrap();
+    /// writer.finish().unwrap();
+    /// drop(writer);
+    ///
+    /// let reader = MemoryReader::new(bytes).unwrap();
+    /// let metadata = reader.metadata().unwrap();
+    /// assert_eq!(metadata[0], ("sample".to_string(), "NA12878".to_string()));
+    /// ```
+    pub fn metadata(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.sections
+            .get_or_insert_with(HeaderSections::default)
+            .metadata
+            .push((key.into(), value.into()));
+        self
+    }
+
+    /// Bins the quality scores of all records before storage
+    ///
+    /// Binning reduces the number of distinct scores, which makes quality scores compress
+    /// much better, but is lossy: readers return the binned scores. The bins are stored in
+    /// the header sections (see `HeaderSections::quality_bins`), so readers know the scores
+    /// are quantized. Binning is applied before the quality transform and is ignored for
+    /// files without quality scores.
+    ///
+    /// # Parameters
+    ///
+    /// * `bins` - The bins to apply (e.g. `QualityBins::illumina`)
+    ///
+    /// # Returns
+    ///
+    /// The builder with quality binning configured
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use vbinseq::{MemoryReader, QualityBins, VBinseqHeader, VBinseqWriterBuilder};
+    ///
+    /// let mut bytes = Vec::new();
+    /// let mut writer = VBinseqWriterBuilder::default()
+    ///     .header(VBinseqHeader::new(true, false, false))
+    ///     .quality_bins(QualityBins::illumina())
+    ///     .build(&mut bytes)
+    ///     .unwrap();
+    /// writer.write_nucleotides_quality(0, b"ACGT", b"#+?I").unwrap();
+    /// writer.finish().unwrap();
+    /// drop(writer);
+    ///
+    /// let reader = MemoryReader::new(bytes).unwrap();
+    /// let sections = reader.sections().unwrap();
+    /// assert_eq!(sections.quality_bins, Some(QualityBins::illumina()));
+    /// ```
+    pub fn quality_bins(mut self, bins: QualityBins) -> Self {
+        self.sections
+            .get_or_insert_with(HeaderSections::default)
+            .quality_bins = Some(bins);
+        self
+    }
+
+    /// Sets a transform applied to every record before it is encoded
+    ///
+    /// This lets preprocessing (e.g. adapter trimming, hard-clipping, or moving barcodes
+    /// into the flag) happen in the same pass as the conversion, without writing an
+    /// intermediate FASTQ file. The transform is applied by the `write_nucleotides*`
+    /// methods (and `write_record`) before overlap merging and the encoding policy, but
+    /// not to pre-encoded records or raw blocks.
+    ///
+    /// # Parameters
+    ///
+    /// * `transform` - Called with every record, which it may change in place
+    ///
+    /// # Returns
+    ///
+    /// The builder with the record transform configured
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use vbinseq::VBinseqWriterBuilder;
+    ///
+    /// // Hard-clip the first 4 nucleotides of every read
+    /// let mut writer = VBinseqWriterBuilder::default()
+    ///     .record_transform(|record| {
+    ///         let clip = record.sequence.len().min(4);
+    ///         record.sequence.drain(..clip);
+    ///     })
+    ///     .build(Vec::new())
+    ///     .unwrap();
+    ///
+    /// writer.write_nucleotides(0, b"NNNNACGT").unwrap();
+    /// ```
+    pub fn record_transform<F>(mut self, transform: F) -> Self
+    where
+        F: Fn(&mut RecordInput) + Send + Sync + 'static,
+    {
+        self.record_transform = Some(Arc::new(transform));
+        self
+    }
+
+    /// Sets how the compression dictionary of every block is selected
+    ///
+    /// Files with compression dictionaries in their header sections (see the `dictionary`
+    /// module) compress every block with the dictionary picked by the selector, which is
+    /// called with the uncompressed bytes of the block. Without a selector, every block is
+    /// compressed with each dictionary and without one, and the smallest result is kept,
+    /// which multiplies the cost of compression by the number of candidates.
+    ///
+    /// # Parameters
+    ///
+    /// * `selector` - Returns the position of the dictionary of a block, or `None` to
+    ///   compress the block without a dictionary
+    ///
+    /// # Returns
+    ///
+    /// The builder with the dictionary selector configured
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use vbinseq::sections::HeaderSections;
+    /// use vbinseq::VBinseqWriterBuilder;
+    ///
+    /// let sections = HeaderSections {
+    ///     dictionaries: vec![b"short reads".to_vec(), b"long reads".to_vec()],
+    ///     ..HeaderSections::default()
+    /// };
+    /// // Blocks of long reads hold few records, so their first record is long
+    /// let writer = VBinseqWriterBuilder::default()
+    ///     .sections(sections)
+    ///     .dictionary_selector(|block| {
+    ///         let slen = u64::from_le_bytes(block[8..16].try_into().unwrap());
+    ///         Some(usize::from(slen > 1000))
+    ///     })
+    ///     .build(Vec::new())
+    ///     .unwrap();
+    /// ```
+    pub fn dictionary_selector<F>(mut self, selector: F) -> Self
+    where
+        F: Fn(&[u8]) -> Option<usize> + Send + Sync + 'static,
+    {
+        self.dictionary_selector = Some(Arc::new(selector));
+        self
+    }
+
+    /// Merges overlapping mates into single consensus records
+    ///
+    /// With merging enabled, every pair written to a paired file is checked for an
+    /// overlap of its mates (see the `merge` module). Overlapping pairs are written as a
+    /// record holding the consensus as its primary sequence, no extended sequence, and the
+    /// flag bits of the options set. All other pairs are written unchanged.
+    ///
+    /// # Parameters
+    ///
+    /// * `options` - The overlap criteria and the flag bits of merged records
+    ///
+    /// # Returns
+    ///
+    /// The builder with overlap merging configured
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use vbinseq::merge::MergeOptions;
+    /// use vbinseq::{VBinseqHeader, VBinseqWriterBuilder};
+    ///
+    /// let mut writer = VBinseqWriterBuilder::default()
+    ///     .header(VBinseqHeader::new(false, false, true))
+    ///     .merge_overlaps(MergeOptions {
+    ///         min_overlap: 4,
+    ///         ..MergeOptions::default()
+    ///     })
+    ///     .build(Vec::new())
+    ///     .unwrap();
+    ///
+    /// writer.write_nucleotides_paired(0, b"ACGTTGCA", b"GCCTTGCA").unwrap();
+    /// assert_eq!(writer.merged_pairs(), 1);
+    /// ```
+    pub fn merge_overlaps(mut self, options: MergeOptions) -> Self {
+        self.merge_overlaps = Some(options);
+        self
+    }
+
+    /// Writes files created with `create` atomically
+    ///
+    /// The writer then writes to a temporary file next to the output (`<path>.tmp`), and
+    /// only an explicit `finish` (or `close`) syncs the file, writes its index, and renames
+    /// both into place. A crashed or abandoned conversion thus never leaves a partial file
+    /// at the output path that downstream tools could mistake for a complete one. Writers
+    /// dropped without being finished remove their temporary file.
+    ///
+    /// The index is written to `<path>.tmp.vqi` and renamed to `<path>.vqi` before the
+    /// file itself, so the index is in place once the file appears (this requires the
+    /// `compression` feature). This option has no effect on writers created with `build`.
+    ///
+    /// # Parameters
+    ///
+    /// * `atomic` - Whether to write files atomically
+    ///
+    /// # Returns
+    ///
+    /// The builder with atomic writing configured
     ///
     /// # Examples
     ///
     /// ```rust,no_run
-    /// use vbinseq::{VBinseqWriterBuilder, VBinseqHeader};
+    /// use vbinseq::VBinseqWriterBuilder;
+    ///
+    /// let mut writer = VBinseqWriterBuilder::default()
+    ///     .atomic(true)
+    ///     .create("reads.vbq")
+    ///     .unwrap();
+    /// writer.write_nucleotides(0, b"ACGTACGT").unwrap();
+    ///
+    /// // reads.vbq and reads.vbq.vqi only appear now
+    /// writer.close().unwrap();
+    /// ```
+    pub fn atomic(mut self, atomic: bool) -> Self {
+        self.atomic = Some(atomic);
+        self
+    }
+
+    /// Builds a VBinseqWriter with the configured settings
+    ///
+    /// This finalizes the builder and creates a new VBinseqWriter instance using
+    /// the provided writer and the configured settings. If any settings were not
+    /// explicitly set, default values will be used.
+    ///
+    /// # Parameters
+    ///
+    /// * `inner` - The underlying writer where data will be written
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(VBinseqWriter)` - A configured VBinseqWriter ready for use
+    /// * `Err(_)` - If an error occurred while initializing the writer
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use vbinseq::VBinseqWriterBuilder;
     /// use std::fs::File;
     ///
-    /// // Create a file writer
-    /// let file = File::create("combined.vbq").unwrap();
-    /// let mut file_writer = VBinseqWriterBuilder::default()
+    /// let file = File::create("example.vbq").unwrap();
+    /// let mut writer = VBinseqWriterBuilder::default()
     ///     .build(file)
     ///     .unwrap();
+    /// ```
+    pub fn build<W: Write>(self, inner: W) -> Result<VBinseqWriter<W>> {
+        let mut header = self.header.unwrap_or_default();
+        if let Some(fallback) = self.compression_fallback {
+            header.set_codec_fallback(fallback && header.compressed());
+        }
+        let mut writer = VBinseqWriter::with_sections(
+            inner,
+            header,
+            self.policy.unwrap_or_default(),
+            self.headless.unwrap_or(false),
+            self.sections.unwrap_or_default(),
+        )?;
+        writer.cblock.fallback = header.has_codec_fallback() && header.compressed();
+        writer.cblock.pool = self.buffer_pool;
+        writer.cblock.workers = self.compression_workers.unwrap_or(0);
+        writer.cblock.level = self.compression_level.unwrap_or(DEFAULT_COMPRESSION_LEVEL);
+        writer.cblock.selector = self.dictionary_selector;
+        writer.flush_interval = self.flush_interval;
+        writer.flush_threshold = self.flush_threshold;
+        writer.skip_callback = self.skip_callback;
+        writer.merger = self.merge_overlaps.map(Merger::new);
+        writer.record_transform = self.record_transform;
+        Ok(writer)
+    }
+
+    /// Creates a file and builds a writer for it
     ///
-    /// // Create a memory writer
-    /// let mut mem_writer = VBinseqWriterBuilder::default()
-    ///     .build(Vec::new())
-    ///     .unwrap();
+    /// This is `build` with a buffered writer of a newly created (or truncated) file. With
+    /// `atomic`, the file is written to a temporary path and only renamed to `path` once
+    /// the writer is finished.
     ///
-    /// // Write some data to the memory writer
-    /// mem_writer.write_nucleotides(0, b"ACGTACGT").unwrap();
+    /// # Parameters
     ///
-    /// // Ingest data from memory writer into file writer
-    /// file_writer.ingest(&mut mem_writer).unwrap();
+    /// * `path` - Path of the file to write
+    ///
+    /// # Errors
+    ///
+    /// * I/O errors if the file cannot be created
+    /// * The errors of `build`
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use vbinseq::VBinseqWriterBuilder;
+    ///
+    /// let mut writer = VBinseqWriterBuilder::default().create("reads.vbq").unwrap();
+    /// writer.write_nucleotides(0, b"ACGTACGT").unwrap();
+    /// writer.close().unwrap();
     /// ```
-    pub fn ingest(&mut self, other: &mut VBinseqWriter<Vec<u8>>) -> Result<()> {
-        if self.header != other.header {
-            return Err(WriteError::IncompatibleHeaders(self.header, other.header).into());
+    pub fn create<P: AsRef<Path>>(self, path: P) -> Result<VBinseqWriter<BufWriter<File>>> {
+        if !self.atomic.unwrap_or(false) {
+            return self.build(BufWriter::new(File::create(path)?));
+        }
+        let path = path.as_ref().to_path_buf();
+        let file = File::create(with_suffix(&path, ".tmp"))?;
+        let output = AtomicOutput {
+            path,
+            file: Arc::new(file.try_clone()?),
+        };
+        match self.build(BufWriter::new(file)) {
+            Ok(mut writer) => {
+                writer.atomic = Some(output);
+                Ok(writer)
+            }
+            Err(e) => {
+                output.discard();
+                Err(e)
+            }
         }
+    }
 
-        // Write complete blocks from other directly
-        // and clear the other (mimics reading)
-        {
-            self.inner.write_all(other.by_ref())?;
-            other.by_ref().clear();
+    /// Opens an existing file and builds a writer appending records to it
+    ///
+    /// The header and header sections of the file are validated and kept, so the records
+    /// are written with the settings of the file; a header or sections configured on the
+    /// builder are ignored. The last block of the file is re-opened: its records are
+    /// written again before the appended records, so appending a few records at a time
+    /// does not leave a trail of partially filled blocks. The footer (if any) is replaced
+    /// by one covering all records at `finish`, which requires hashing the existing
+    /// records once. The index file of the file is removed, as it no longer matches, and
+    /// is rebuilt by readers when needed.
+    ///
+    /// Appending happens in place, so `atomic` does not apply. Until the writer is
+    /// finished, the file lacks its last block (and footer).
+    ///
+    /// # Parameters
+    ///
+    /// * `path` - Path of the file to append to
+    ///
+    /// # Errors
+    ///
+    /// * I/O errors if the file cannot be read or written
+    /// * Header validation errors if the file is not a valid VBINSEQ file
+    /// * Read errors if a block of the file is invalid (e.g. a truncated last block)
+    /// * The errors of `build`
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use vbinseq::VBinseqWriterBuilder;
+    ///
+    /// let mut writer = VBinseqWriterBuilder::default().append("reads.vbq").unwrap();
+    /// writer.write_nucleotides(0, b"ACGTACGT").unwrap();
+    /// writer.close().unwrap();
+    /// ```
+    pub fn append<P: AsRef<Path>>(mut self, path: P) -> Result<VBinseqWriter<BufWriter<File>>> {
+        let path = path.as_ref();
+        let bytes = load_file(path)?;
+        let (header, _, end) = parse_file_layout(&bytes)?;
+        let sections = HeaderSections::from_file_bytes(&bytes, &header)?;
+        let dictionaries = dictionary::load(&bytes, &header)?;
+
+        // Hash all blocks but the last one, which is re-opened
+        let mut digest = ContentHasher::for_header(&header, false);
+        let mut dbuf = Vec::new();
+        let mut last: Option<(usize, RawBlock)> = None;
+        let (mut pos, mut total) = (header.data_offset(), 0);
+        loop {
+            let start = pos;
+            let Some(raw) = read_next_raw_block(&bytes, end, &header, &mut pos, &mut total)? else {
+                break;
+            };
+            if let (Some(digest), Some((_, previous))) = (&mut digest, &last) {
+                hash_block(
+                    digest,
+                    &previous.header,
+                    previous.data,
+                    &header,
+                    &dictionaries,
+                    &mut dbuf,
+                )?;
+            }
+            last = Some((start, raw));
+        }
+        let mut block = RecordBlock::new(header.block() as usize);
+        let truncate = match &last {
+            Some((start, raw)) => {
+                block.set_dictionaries(&dictionaries);
+                block.ingest_block(&raw.header, raw.data, &header)?;
+                total -= u64::from(raw.header.records);
+                *start
+            }
+            None => end,
+        };
+        drop(bytes);
+
+        let mut file = OpenOptions::new().write(true).open(path)?;
+        file.set_len(truncate as u64)?;
+        file.seek(SeekFrom::End(0))?;
+        let index_path = with_suffix(path, ".vqi");
+        if index_path.exists() {
+            std::fs::remove_file(index_path)?;
         }
 
-        // Ingest incomplete block from other
-        {
-            self.cblock.ingest(other.cblock_mut(), &mut self.inner)?;
+        // Build a writer continuing the file without writing its header again
+        self.header = Some(header);
+        self.sections = Some(sections);
+        self.headless = Some(true);
+        let mut writer = self.build(BufWriter::new(file))?;
+        writer.headless = false;
+        writer.cblock.digest = digest;
+        writer.cblock.flushed = total;
+        for record in block.iter() {
+            writer.write_stored(&record)?;
         }
-        Ok(())
+        Ok(writer)
     }
 }
 
-impl<W: Write> Drop for VBinseqWriter<W> {
-    fn drop(&mut self) {
-        self.finish()
-            .expect("VBinseqWriter: Failed to finish writing");
+/// Output written to a temporary file and renamed once finished
+///
+/// See `VBinseqWriterBuilder::atomic`.
+#[derive(Clone)]
+struct AtomicOutput {
+    /// Final path of the file
+    path: PathBuf,
+
+    /// Handle of the temporary file (to sync it before renaming)
+    file: Arc<File>,
+}
+impl AtomicOutput {
+    /// Returns the path of the temporary file (`<path>.tmp`)
+    fn temp_path(&self) -> PathBuf {
+        with_suffix(&self.path, ".tmp")
+    }
+
+    /// Syncs the temporary file and renames it (and its index) into place
+    fn commit(&self, index: bool) -> Result<()> {
+        self.file.sync_all()?;
+        let temp_path = self.temp_path();
+        // Index files are compressed, so they require the `compression` feature
+        #[cfg(not(feature = "compression"))]
+        let _ = index;
+        #[cfg(feature = "compression")]
+        if index {
+            let temp_index = with_suffix(&temp_path, ".vqi");
+            crate::BlockIndex::from_vbq(&temp_path)?.save_to_path(&temp_index)?;
+            std::fs::rename(temp_index, with_suffix(&self.path, ".vqi"))?;
+        }
+        std::fs::rename(temp_path, &self.path)?;
+        Ok(())
+    }
+
+    /// Removes the temporary file of an unfinished output
+    fn discard(&self) {
+        let _ = std::fs::remove_file(self.temp_path());
     }
 }
 
-#[derive(Clone)]
-struct BlockWriter {
-    /// Current position in the block
-    pos: usize,
-    /// Tracks all record start positions in the block
-    starts: Vec<usize>,
-    /// Virtual block size
-    block_size: usize,
-    /// Compression level
-    level: i32,
-    /// Uncompressed buffer
-    ubuf: Vec<u8>,
-    /// Compressed buffer
-    zbuf: Vec<u8>,
-    /// Reusable padding buffer
-    padding: Vec<u8>,
-    /// Compression flag
-    /// If false, the block is written uncompressed
-    compress: bool,
+/// Appends a suffix to a path (e.g. `reads.vbq` to `reads.vbq.tmp`)
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut p = path.as_os_str().to_owned();
+    p.push(suffix);
+    p.into()
 }
-impl BlockWriter {
-    fn new(block_size: usize, compress: bool) -> Self {
-        Self {
-            pos: 0,
-            starts: Vec::default(),
-            block_size,
-            level: 3,
-            ubuf: Vec::with_capacity(block_size),
-            zbuf: Vec::with_capacity(block_size),
-            padding: vec![0; block_size],
-            compress,
-        }
+
+/// Writer for VBINSEQ format files
+///
+/// The `VBinseqWriter` handles writing nucleotide sequence data to VBINSEQ files in a
+/// block-based format. It manages the file structure, compression settings, and ensures
+/// data is properly encoded and organized.
+///
+/// ## File Structure
+///
+/// A VBINSEQ file consists of:
+/// 1. A file header that defines parameters like block size and compression settings
+/// 2. A series of blocks, each with:
+///    - A block header with metadata (e.g., record count)
+///    - A collection of encoded records
+///
+/// Each block is filled with records until either the block is full or no more complete
+/// records can fit. The writer automatically handles block boundaries and creates new
+/// blocks as needed.
+///
+/// ## Usage
+///
+/// The writer supports multiple formats:
+/// - Single-end sequences with or without quality scores
+/// - Paired-end sequences with or without quality scores
+///
+/// It's recommended to use the `VBinseqWriterBuilder` to create and configure a writer
+/// instance with the appropriate settings.
+///
+/// ```rust,no_run
+/// use vbinseq::{VBinseqWriterBuilder, VBinseqHeader};
+/// use std::fs::File;
+///
+/// // Create a writer for single-end reads
+/// let file = File::create("example.vbq").unwrap();
+/// let mut writer = VBinseqWriterBuilder::default()
+///     .header(VBinseqHeader::default())
+///     .build(file)
+///     .unwrap();
+///
+/// // Write a sequence
+/// let flag = 0; // No special flags
+/// let sequence = b"ACGTACGTACGT";
+/// writer.write_nucleotides(flag, sequence).unwrap();
+///
+/// // Writer automatically flushes when dropped (use `close` to handle errors)
+/// ```
+#[derive(Clone)]
+pub struct VBinseqWriter<W: Write> {
+    /// Inner Writer
+    inner: W,
+
+    /// Header of the file
+    header: VBinseqHeader,
+
+    /// Encoder for nucleotide sequences
+    encoder: Encoder,
+
+    /// Pre-initialized writer for compressed blocks
+    cblock: BlockWriter,
+
+    /// Whether the writer operates in headless mode
+    headless: bool,
+
+    /// Whether the footer has already been written
+    footer_written: bool,
+
+    /// Whether the writer has been finished
+    /// Writing to a finished writer is an error
+    finished: bool,
+
+    /// Maximum time records stay in an unflushed block
+    flush_interval: Option<Duration>,
+
+    /// Maximum number of bytes held in an unflushed block
+    flush_threshold: Option<usize>,
+
+    /// Counters of the records skipped by the policy
+    skipped: SkippedRecords,
+
+    /// Callback receiving the records skipped by the policy
+    skip_callback: Option<SkipCallback>,
+
+    /// Reusable buffers for homopolymer-compressed sequences
+    collapsed: Collapsed,
+
+    /// Merger of overlapping mates (if enabled)
+    merger: Option<Merger>,
+
+    /// Number of pairs written as merged records
+    merged: u64,
+
+    /// Transform applied to records before encoding
+    record_transform: Option<RecordTransform>,
+
+    /// Reusable buffers for the records passed to the transform
+    record_input: RecordInput,
+
+    /// Reusable buffers for the lengths and 2-bit words of records of more than two segments
+    segment_lens: Vec<u64>,
+    segment_words: Vec<u64>,
+
+    /// Sections written between the header and the first record block
+    sections: HeaderSections,
+
+    /// Writers ingested ahead of their turn, keyed by their sequence number
+    pending: BTreeMap<u64, VBinseqWriter<Vec<u8>>>,
+
+    /// Sequence number of the next writer ingested in order
+    next_sequence: u64,
+
+    /// Temporary output renamed into place by `finish` (see `VBinseqWriterBuilder::atomic`)
+    atomic: Option<AtomicOutput>,
+}
+impl VBinseqWriter<BufWriter<File>> {
+    /// Opens an existing file and returns a writer appending records to it
+    ///
+    /// This is `VBinseqWriterBuilder::append` with the default builder, so the records
+    /// are written with the header of the file and the default policy.
+    ///
+    /// # Parameters
+    ///
+    /// * `path` - Path of the file to append to
+    ///
+    /// # Errors
+    ///
+    /// See `VBinseqWriterBuilder::append`
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use vbinseq::VBinseqWriter;
+    ///
+    /// let mut writer = VBinseqWriter::append("reads.vbq").unwrap();
+    /// writer.write_nucleotides(0, b"ACGTACGT").unwrap();
+    /// writer.close().unwrap();
+    /// ```
+    pub fn append<P: AsRef<Path>>(path: P) -> Result<Self> {
+        VBinseqWriterBuilder::default().append(path)
+    }
+}
+
+impl<W: Write> VBinseqWriter<W> {
+    pub fn new(inner: W, header: VBinseqHeader, policy: Policy, headless: bool) -> Result<Self> {
+        Self::with_sections(inner, header, policy, headless, HeaderSections::default())
+    }
+
+    /// Creates a writer storing header sections (see `VBinseqWriterBuilder::sections`)
+    fn with_sections(
+        inner: W,
+        mut header: VBinseqHeader,
+        policy: Policy,
+        headless: bool,
+        sections: HeaderSections,
+    ) -> Result<Self> {
+        let cblock = Self::member_block_writer(&mut header, &sections, headless)?;
+        let mut wtr = Self {
+            inner,
+            header,
+            encoder: Encoder::with_policy(policy).with_iupac(header.is_iupac()),
+            cblock,
+            headless,
+            footer_written: false,
+            finished: false,
+            flush_interval: None,
+            flush_threshold: None,
+            skipped: SkippedRecords::default(),
+            skip_callback: None,
+            collapsed: Collapsed::default(),
+            merger: None,
+            merged: 0,
+            record_transform: None,
+            record_input: RecordInput::default(),
+            segment_lens: Vec::new(),
+            segment_words: Vec::new(),
+            sections,
+            pending: BTreeMap::new(),
+            next_sequence: 0,
+            atomic: None,
+        };
+        if !headless {
+            wtr.init()?;
+        }
+        Ok(wtr)
+    }
+
+    /// Checks a header and creates the block writer for the records of a member
+    ///
+    /// The size of the header sections is recorded in the header.
+    fn member_block_writer(
+        header: &mut VBinseqHeader,
+        sections: &HeaderSections,
+        headless: bool,
+    ) -> Result<BlockWriter> {
+        header.set_sections_size(sections.to_bytes().len())?;
+        if !header.codec().is_supported() {
+            return Err(HeaderError::UnsupportedCodec(header.codec()).into());
+        }
+        if header.qual() && header.is_homopolymer() {
+            return Err(HeaderError::HomopolymerWithQuality.into());
+        }
+        if header.is_fixed_length() && !header.supports_fixed_length() {
+            return Err(HeaderError::InvalidFixedLength.into());
+        }
+        if header.has_embedded_index() && !header.has_footer() {
+            return Err(HeaderError::EmbeddedIndexWithoutFooter.into());
+        }
+        if header.segments() > 2 && header.is_homopolymer() {
+            return Err(HeaderError::InvalidSegments(header.segments()).into());
+        }
+        let mut cblock = BlockWriter::new(header.block() as usize, header.codec());
+        if header.qual() {
+            cblock.transform = header.quality_transform();
+            cblock.bins = sections.quality_bins.as_ref().map(QualityBins::table);
+        }
+        cblock.record_crc = header.has_record_crc();
+        cblock.length_fields = header.length_fields();
+        cblock.iupac = header.is_iupac();
+        cblock.columnar = header.is_columnar().then(|| Layout::new(header));
+        cblock.block_checksum = header.has_block_checksum();
+        cblock.has_aux = header.has_aux();
+        cblock.fixed = header.is_fixed_length();
+        cblock.marks_quality = header.marks_quality();
+        cblock.read_groups = sections.read_groups.len();
+        if sections.dictionaries.len() > MAX_DICTIONARIES {
+            return Err(WriteError::TooManyDictionaries(sections.dictionaries.len()).into());
+        }
+        cblock.dictionaries = sections.dictionaries.clone().into();
+        // Headless writers defer hashing to the writer ingesting their blocks
+        cblock.digest = ContentHasher::for_header(header, headless);
+        Ok(cblock)
+    }
+
+    /// Initializes the writer by writing the file header
+    ///
+    /// This method is called automatically during creation unless headless mode is enabled.
+    /// It writes the VBinseqHeader and the header sections to the underlying writer.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the header was successfully written
+    /// * `Err(_)` - If an error occurred during writing
+    fn init(&mut self) -> Result<()> {
+        self.header.write_bytes(&mut self.inner)?;
+        self.inner.write_all(&self.sections.to_bytes())?;
+        Ok(())
+    }
+
+    /// Checks if the writer is configured for paired-end reads
+    ///
+    /// This method returns whether the writer expects paired-end reads based on the
+    /// header settings. If true, you should use `write_paired_nucleotides` instead of
+    /// `write_nucleotides` to write sequences.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the writer is configured for paired-end reads, `false` otherwise
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use vbinseq::{VBinseqWriterBuilder, VBinseqHeader};
+    /// use std::fs::File;
+    ///
+    /// // Create a header for paired-end reads
+    /// let mut header = VBinseqHeader::default();
+    /// header.set_paired(true);
+    ///
+    /// let file = File::create("paired_reads.vbq").unwrap();
+    /// let writer = VBinseqWriterBuilder::default()
+    ///     .header(header)
+    ///     .build(file)
+    ///     .unwrap();
+    ///
+    /// assert!(writer.is_paired());
+    /// ```
+    pub fn is_paired(&self) -> bool {
+        self.header.paired()
+    }
+
+    /// Checks if the writer is configured for quality scores
+    ///
+    /// This method returns whether the writer expects quality scores based on the
+    /// header settings. If true, you should use methods that include quality scores
+    /// (`write_nucleotides_with_quality` or `write_paired_nucleotides_with_quality`)
+    /// to write sequences.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the writer is configured for quality scores, `false` otherwise
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use vbinseq::{VBinseqWriterBuilder, VBinseqHeader};
+    /// use std::fs::File;
+    ///
+    /// // Create a header for sequences with quality scores
+    /// let mut header = VBinseqHeader::default();
+    /// header.set_qual(true);
+    ///
+    /// let file = File::create("reads_with_quality.vbq").unwrap();
+    /// let writer = VBinseqWriterBuilder::default()
+    ///     .header(header)
+    ///     .build(file)
+    ///     .unwrap();
+    ///
+    /// assert!(writer.has_quality());
+    /// ```
+    pub fn has_quality(&self) -> bool {
+        self.header.qual()
+    }
+
+    /// Writes a single nucleotide sequence to the file
+    ///
+    /// This method encodes and writes a single nucleotide sequence to the VBINSEQ file.
+    /// It automatically handles block boundaries and will create a new block if the
+    /// current one cannot fit the encoded record.
+    ///
+    /// # Parameters
+    ///
+    /// * `flag` - A 64-bit flag that can store custom metadata about the sequence
+    /// * `sequence` - The nucleotide sequence to write (typically ASCII: A, C, G, T, N)
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(true)` - If the sequence was successfully encoded and written
+    /// * `Ok(false)` - If the sequence could not be encoded (e.g., invalid characters)
+    /// * `Err(_)` - If an error occurred during writing or if the writer is configured
+    ///   for quality scores or paired-end reads
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if:
+    /// - The writer is configured for quality scores that are not optional
+    ///   (`WriteError::QualityFlagSet`)
+    /// - The writer is configured for paired-end reads (`WriteError::PairedFlagSet`)
+    /// - An I/O error occurred while writing
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use vbinseq::{VBinseqWriterBuilder, VBinseqHeader};
+    /// use std::fs::File;
+    ///
+    /// let file = File::create("example.vbq").unwrap();
+    /// let mut writer = VBinseqWriterBuilder::default()
+    ///     .build(file)
+    ///     .unwrap();
+    ///
+    /// // Write a sequence with a custom flag
+    /// let flag = 0x1234; // Some arbitrary metadata
+    /// let sequence = b"ACGTACGTACGT";
+    /// writer.write_nucleotides(flag, sequence).unwrap();
+    /// ```
+    pub fn write_nucleotides(&mut self, flag: u64, sequence: &[u8]) -> Result<bool> {
+        self.check_open()?;
+        // Validate the right write operation is being used
+        if self.header.qual() && !self.header.has_optional_quality() {
+            return Err(WriteError::QualityFlagSet.into());
+        }
+        if self.header.paired() {
+            return Err(WriteError::PairedFlagSet.into());
+        }
+        if self.record_transform.is_some() {
+            return self.write_transformed(flag, sequence, &[], &[], &[]);
+        }
+        if self.header.is_homopolymer() {
+            return self.write_homopolymer(flag, sequence, None);
+        }
+
+        // encode the sequence
+        if self.encoder.encode_single(sequence)?.is_some() {
+            let (flag, sbuffer) = (flag | self.encoder.flag_bits(), self.encoder.sbuffer());
+            let record_size = record_byte_size(sbuffer.len(), 0);
+            if self.cblock.exceeds_block_size(record_size)? {
+                self.cblock.flush(&mut self.inner)?;
+            }
+
+            // Write the flag, length, and sequence to the block
+            self.cblock
+                .write_record(flag, sequence.len() as u64, 0, sbuffer, None, None, None)?;
+            self.poll_flush()?;
+
+            // Return true if the sequence was successfully written
+            Ok(true)
+        } else {
+            self.skip(flag, sequence, &[], &[], &[])
+        }
+    }
+
+    /// Writes a paired-end nucleotide sequence to the file
+    ///
+    /// This method encodes and writes a paired-end nucleotide sequence (two related sequences)
+    /// to the VBINSEQ file. It automatically handles block boundaries and will create a new
+    /// block if the current one cannot fit the encoded record.
+    ///
+    /// # Parameters
+    ///
+    /// * `flag` - A 64-bit flag that can store custom metadata about the sequence pair
+    /// * `primary` - The primary nucleotide sequence (typically the forward read)
+    /// * `extended` - The extended nucleotide sequence (typically the reverse read)
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(true)` - If the sequence pair was successfully encoded and written
+    /// * `Ok(false)` - If the sequence pair could not be encoded
+    /// * `Err(_)` - If an error occurred during writing or if the writer is not configured
+    ///   for paired-end reads
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if:
+    /// - The writer is configured for quality scores that are not optional
+    ///   (`WriteError::QualityFlagSet`)
+    /// - The writer is not configured for paired-end reads (`WriteError::PairedFlagNotSet`)
+    /// - An I/O error occurred while writing
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use vbinseq::{VBinseqWriterBuilder, VBinseqHeader};
+    /// use std::fs::File;
+    ///
+    /// // Create a header for paired-end reads
+    /// let mut header = VBinseqHeader::default();
+    /// header.set_paired(true);
+    ///
+    /// let file = File::create("paired_reads.vbq").unwrap();
+    /// let mut writer = VBinseqWriterBuilder::default()
+    ///     .header(header)
+    ///     .build(file)
+    ///     .unwrap();
+    ///
+    /// // Write a paired sequence
+    /// let flag = 0;
+    /// let forward_read = b"ACGTACGTACGT";
+    /// let reverse_read = b"TGCATGCATGCA";
+    /// writer.write_nucleotides_paired(flag, forward_read, reverse_read).unwrap();
+    /// ```
+    pub fn write_nucleotides_paired(
+        &mut self,
+        flag: u64,
+        primary: &[u8],
+        extended: &[u8],
+    ) -> Result<bool> {
+        self.check_open()?;
+        // Validate the right write operation is being used
+        if self.header.qual() && !self.header.has_optional_quality() {
+            return Err(WriteError::QualityFlagSet.into());
+        }
+        self.check_paired()?;
+        if self.record_transform.is_some() {
+            return self.write_transformed(flag, primary, extended, &[], &[]);
+        }
+        if let Some(status) = self.try_merge(flag, primary, extended, &[], &[]) {
+            return status;
+        }
+        if self.header.is_homopolymer() {
+            return self.write_homopolymer(flag, primary, Some(extended));
+        }
+
+        if self.encoder.encode_paired(primary, extended)?.is_some() {
+            let flag = flag | self.encoder.flag_bits();
+            let (sbuffer, xbuffer) = (self.encoder.sbuffer(), self.encoder.xbuffer());
+            // Check if the current block can handle the next record
+            let record_size = record_byte_size(sbuffer.len(), xbuffer.len());
+            if self.cblock.exceeds_block_size(record_size)? {
+                self.cblock.flush(&mut self.inner)?;
+            }
+
+            // Write the flag, length, and sequence to the block
+            self.cblock.write_record(
+                flag,
+                primary.len() as u64,
+                extended.len() as u64,
+                sbuffer,
+                None,
+                Some(xbuffer),
+                None,
+            )?;
+            self.poll_flush()?;
+
+            // Return true if the record was successfully written
+            Ok(true)
+        } else {
+            self.skip(flag, primary, extended, &[], &[])
+        }
+    }
+
+    /// Writes a nucleotide sequence with quality scores to the file
+    ///
+    /// This method encodes and writes a single nucleotide sequence with corresponding
+    /// quality scores to the VBINSEQ file. Quality scores are typically in the Phred scale
+    /// (encoded as ASCII characters). It automatically handles block boundaries and will
+    /// create a new block if the current one cannot fit the encoded record.
+    ///
+    /// # Parameters
+    ///
+    /// * `flag` - A 64-bit flag that can store custom metadata about the sequence
+    /// * `sequence` - The nucleotide sequence to write (typically ASCII: A, C, G, T, N)
+    /// * `quality` - The quality scores corresponding to each base in the sequence
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(true)` - If the sequence and quality scores were successfully encoded and written
+    /// * `Ok(false)` - If the sequence could not be encoded
+    /// * `Err(_)` - If an error occurred during writing or if the writer is not configured
+    ///   for quality scores
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if:
+    /// - The writer is not configured for quality scores (`WriteError::QualityFlagNotSet`)
+    /// - The quality scores differ in length from their sequence
+    ///   (`WriteError::QualityLengthMismatch`, naming the index the record would have had)
+    /// - The writer is configured for paired-end reads (`WriteError::PairedFlagSet`)
+    /// - An I/O error occurred while writing
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use vbinseq::{VBinseqWriterBuilder, VBinseqHeader};
+    /// use std::fs::File;
+    ///
+    /// // Create a header for sequences with quality scores
+    /// let mut header = VBinseqHeader::default();
+    /// header.set_qual(true);
+    ///
+    /// let file = File::create("reads_with_quality.vbq").unwrap();
+    /// let mut writer = VBinseqWriterBuilder::default()
+    ///     .header(header)
+    ///     .build(file)
+    ///     .unwrap();
+    ///
+    /// // Write a sequence with quality scores
+    /// let flag = 0;
+    /// let sequence = b"ACGTACGTACGT";
+    /// let quality = b"IIIIIIEEEEEE"; // Example quality scores in ASCII format
+    /// writer.write_nucleotides_quality(flag, sequence, quality).unwrap();
+    /// ```
+    pub fn write_nucleotides_quality(
+        &mut self,
+        flag: u64,
+        sequence: &[u8],
+        quality: &[u8],
+    ) -> Result<bool> {
+        self.check_open()?;
+        // Validate the right write operation is being used
+        if !self.header.qual() {
+            return Err(WriteError::QualityFlagNotSet.into());
+        }
+        if self.header.paired() {
+            return Err(WriteError::PairedFlagSet.into());
+        }
+        if self.record_transform.is_some() {
+            return self.write_transformed(flag, sequence, &[], quality, &[]);
+        }
+        self.check_quality_length(flag, sequence, quality)?;
+
+        if self.encoder.encode_single(sequence)?.is_some() {
+            let (flag, sbuffer) = (flag | self.encoder.flag_bits(), self.encoder.sbuffer());
+            // Check if the current block can handle the next record
+            let record_size = record_byte_size_quality(sbuffer.len(), 0, quality.len(), 0);
+            if self.cblock.exceeds_block_size(record_size)? {
+                self.cblock.flush(&mut self.inner)?;
+            }
+
+            // Write the flag, length, sequence, and quality scores to the block
+            self.cblock.write_record(
+                flag,
+                sequence.len() as u64,
+                0,
+                sbuffer,
+                Some(quality),
+                None,
+                None,
+            )?;
+            self.poll_flush()?;
+
+            // Return true if the record was written successfully
+            Ok(true)
+        } else {
+            self.skip(flag, sequence, &[], quality, &[])
+        }
+    }
+
+    /// Writes paired-end nucleotide sequences with quality scores to the file
+    ///
+    /// This method encodes and writes paired-end nucleotide sequences with their corresponding
+    /// quality scores to the VBINSEQ file. It's designed for paired-end sequencing data where
+    /// each fragment is sequenced from both ends. The method automatically handles block
+    /// boundaries and will create a new block if the current one cannot fit the encoded record.
+    ///
+    /// # Parameters
+    ///
+    /// * `flag` - A 64-bit flag that can store custom metadata about the sequence pair
+    /// * `s_seq` - The primary nucleotide sequence (typically the forward read)
+    /// * `x_seq` - The extended nucleotide sequence (typically the reverse read)
+    /// * `s_qual` - The quality scores corresponding to each base in the primary sequence
+    /// * `x_qual` - The quality scores corresponding to each base in the extended sequence
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(true)` - If the sequences and quality scores were successfully encoded and written
+    /// * `Ok(false)` - If the sequences could not be encoded
+    /// * `Err(_)` - If an error occurred during writing or if the writer is not configured
+    ///   for quality scores and paired-end reads
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if:
+    /// - The writer is not configured for quality scores (`WriteError::QualityFlagNotSet`)
+    /// - The quality scores differ in length from their sequence
+    ///   (`WriteError::QualityLengthMismatch`, naming the index the record would have had)
+    /// - The writer is not configured for paired-end reads (`WriteError::PairedFlagNotSet`)
+    /// - An I/O error occurred while writing
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use vbinseq::{VBinseqWriterBuilder, VBinseqHeader};
+    /// use std::fs::File;
+    ///
+    /// // Create a header for paired-end reads with quality scores
+    /// let mut header = VBinseqHeader::default();
+    /// header.set_qual(true);
+    /// header.set_paired(true);
+    ///
+    /// let file = File::create("paired_reads_with_quality.vbq").unwrap();
+    /// let mut writer = VBinseqWriterBuilder::default()
+    ///     .header(header)
+    ///     .build(file)
+    ///     .unwrap();
+    ///
+    /// // Write paired sequences with quality scores
+    /// let flag = 0;
+    /// let forward_read = b"ACGTACGTACGT";
+    /// let reverse_read = b"TGCATGCATGCA";
+    /// let forward_quality = b"IIIIIIEEEEEE"; // Example quality scores
+    /// let reverse_quality = b"EEEEEEIIIIEE"; // Example quality scores
+    /// writer.write_nucleotides_quality_paired(
+    ///     flag,
+    ///     forward_read,
+    ///     reverse_read,
+    ///     forward_quality,
+    ///     reverse_quality
+    /// ).unwrap();
+    /// ```
+    pub fn write_nucleotides_quality_paired(
+        &mut self,
+        flag: u64,
+        s_seq: &[u8],
+        x_seq: &[u8],
+        s_qual: &[u8],
+        x_qual: &[u8],
+    ) -> Result<bool> {
+        self.check_open()?;
+        // Validate the right write operation is being used
+        if !self.header.qual() {
+            return Err(WriteError::QualityFlagNotSet.into());
+        }
+        self.check_paired()?;
+        if self.record_transform.is_some() {
+            return self.write_transformed(flag, s_seq, x_seq, s_qual, x_qual);
+        }
+        self.check_quality_length(flag, s_seq, s_qual)?;
+        self.check_quality_length(flag, x_seq, x_qual)?;
+        if let Some(status) = self.try_merge(flag, s_seq, x_seq, s_qual, x_qual) {
+            return status;
+        }
+
+        if self.encoder.encode_paired(s_seq, x_seq)?.is_some() {
+            let flag = flag | self.encoder.flag_bits();
+            let (sbuffer, xbuffer) = (self.encoder.sbuffer(), self.encoder.xbuffer());
+            // Check if the current block can handle the next record
+            let record_size =
+                record_byte_size_quality(sbuffer.len(), xbuffer.len(), s_qual.len(), x_qual.len());
+            if self.cblock.exceeds_block_size(record_size)? {
+                self.cblock.flush(&mut self.inner)?;
+            }
+
+            // Write the flag, length, sequence, and quality scores to the block
+            self.cblock.write_record(
+                flag,
+                s_seq.len() as u64,
+                x_seq.len() as u64,
+                sbuffer,
+                Some(s_qual),
+                Some(xbuffer),
+                Some(x_qual),
+            )?;
+            self.poll_flush()?;
+
+            // Return true if the record was successfully written
+            Ok(true)
+        } else {
+            self.skip(flag, s_seq, x_seq, s_qual, x_qual)
+        }
+    }
+
+    /// Writes a record of any number of segments
+    ///
+    /// Records of more than two segments (e.g. R1/R2/I1/I2 reads or linked reads, see
+    /// `VBinseqHeader::set_segments`) can only be written with this method or
+    /// `write_record`. In files of one or two segments, this calls the matching single or
+    /// paired write method.
+    ///
+    /// Records of more than two segments are not merged or transformed. If the policy
+    /// rejects one of their segments, the skip callback receives their first two segments.
+    ///
+    /// # Parameters
+    ///
+    /// * `flag` - A 64-bit flag that can store custom metadata about the record
+    /// * `sequences` - The nucleotide sequence of each segment
+    /// * `qualities` - The quality scores of each segment (empty if the record has none)
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(true)` - If the record was successfully written
+    /// * `Ok(false)` - If the record was skipped due to invalid nucleotides
+    ///
+    /// # Errors
+    ///
+    /// * `WriteError::SegmentCountMismatch` - If the record has a different number of
+    ///   sequences or quality scores than the file has segments
+    /// * `WriteError::QualityFlagSet` - If quality scores are required but not given
+    /// * `WriteError::QualityFlagNotSet` - If quality scores are given but not stored
+    /// * `WriteError::QualityLengthMismatch` - If quality scores differ in length from their
+    ///   sequence (the error names the index the record would have had)
+    /// * An I/O error occurred while writing
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use vbinseq::{MemoryReader, VBinseqHeader, VBinseqWriterBuilder};
+    ///
+    /// // Records of two reads and two index reads
+    /// let mut header = VBinseqHeader::default();
+    /// header.set_segments(4).unwrap();
+    ///
+    /// let mut bytes = Vec::new();
+    /// let mut writer = VBinseqWriterBuilder::default()
+    ///     .header(header)
+    ///     .build(&mut bytes)
+    ///     .unwrap();
+    /// let sequences: [&[u8]; 4] = [b"ACGTACGT", b"TTGCAACG", b"GATTACA", b"CCATGG"];
+    /// writer.write_segments(0, &sequences, &[]).unwrap();
+    /// writer.finish().unwrap();
+    /// drop(writer);
+    ///
+    /// let mut reader = MemoryReader::new(bytes).unwrap();
+    /// let mut block = reader.new_block();
+    /// reader.read_block_into(&mut block).unwrap();
+    /// let record = block.iter().next().unwrap();
+    /// assert_eq!(record.n_segments(), 4);
+    /// assert_eq!(record.segment_len(2), 7);
+    /// ```
+    pub fn write_segments(
+        &mut self,
+        flag: u64,
+        sequences: &[&[u8]],
+        qualities: &[&[u8]],
+    ) -> Result<bool> {
+        self.check_open()?;
+        let n_segments = self.header.segments();
+        if sequences.len() != n_segments {
+            return Err(WriteError::SegmentCountMismatch(n_segments, sequences.len()).into());
+        }
+        if !qualities.is_empty() && qualities.len() != n_segments {
+            return Err(WriteError::SegmentCountMismatch(n_segments, qualities.len()).into());
+        }
+        let (sequence, extended) = (sequences[0], sequences.get(1).copied().unwrap_or_default());
+        let squal = qualities.first().copied().unwrap_or_default();
+        let xqual = qualities.get(1).copied().unwrap_or_default();
+        match (n_segments, qualities.is_empty()) {
+            (1, true) => return self.write_nucleotides(flag, sequence),
+            (1, false) => return self.write_nucleotides_quality(flag, sequence, squal),
+            (2, true) => return self.write_nucleotides_paired(flag, sequence, extended),
+            (2, false) => {
+                return self
+                    .write_nucleotides_quality_paired(flag, sequence, extended, squal, xqual)
+            }
+            _ => {}
+        }
+
+        // Validate the quality scores
+        let quality = !qualities.is_empty();
+        if quality && !self.header.qual() {
+            return Err(WriteError::QualityFlagNotSet.into());
+        }
+        if !quality && self.header.qual() && !self.header.has_optional_quality() {
+            return Err(WriteError::QualityFlagSet.into());
+        }
+        for (seq, qual) in sequences.iter().zip(qualities) {
+            self.check_quality_length(flag, seq, qual)?;
+        }
+
+        // Encode the segments one after another
+        self.segment_lens.clear();
+        self.segment_words.clear();
+        let mut flag_bits = 0;
+        for (i, seq) in sequences.iter().enumerate() {
+            let Some(words) = self.encoder.encode_single(seq)? else {
+                if i > 0 {
+                    self.encoder.skip_reason = SkipReason::InvalidExtended;
+                }
+                return self.skip(flag, sequence, extended, squal, xqual);
+            };
+            self.segment_words.extend_from_slice(words);
+            self.segment_lens.push(seq.len() as u64);
+            flag_bits |= self.encoder.flag_bits();
+        }
+        self.write_encoded_segments(flag | flag_bits, quality.then_some(qualities))?;
+        Ok(true)
+    }
+
+    /// Writes the encoded segments of a record, flushing the block first if it is full
+    ///
+    /// The lengths and words of the segments are taken from the reusable buffers.
+    fn write_encoded_segments(&mut self, flag: u64, quals: Option<&[&[u8]]>) -> Result<()> {
+        let record_size = 8 * (1 + self.segment_lens.len() + self.segment_words.len())
+            + quals.map_or(0, |quals| quals.iter().map(|qual| qual.len()).sum());
+        if self.cblock.exceeds_block_size(record_size)? {
+            self.cblock.flush(&mut self.inner)?;
+        }
+        self.cblock
+            .write_segments(flag, &self.segment_lens, &self.segment_words, quals)?;
+        self.poll_flush()?;
+        Ok(())
+    }
+
+    /// Checks the writer is configured for records of exactly two segments
+    fn check_paired(&self) -> Result<()> {
+        if !self.header.paired() {
+            return Err(WriteError::PairedFlagNotSet.into());
+        }
+        if self.header.segments() > 2 {
+            return Err(WriteError::SegmentCountMismatch(self.header.segments(), 2).into());
+        }
+        Ok(())
+    }
+
+    /// Applies the record transform to a record and writes the transformed record
+    ///
+    /// The transform is taken out of the writer while the record is written, so the
+    /// write methods called for the transformed record do not apply it again.
+    fn write_transformed(
+        &mut self,
+        flag: u64,
+        sequence: &[u8],
+        extended: &[u8],
+        squal: &[u8],
+        xqual: &[u8],
+    ) -> Result<bool> {
+        let Some(transform) = self.record_transform.take() else {
+            return Ok(false);
+        };
+        let mut input = std::mem::take(&mut self.record_input);
+        input.set(flag, sequence, extended, squal, xqual);
+        transform(&mut input);
+        let status = self.write_input(&input);
+        self.record_input = input;
+        self.record_transform = Some(transform);
+        status
+    }
+
+    /// Writes a record as it is stored in a block, e.g. a record of a re-opened block
+    ///
+    /// Unlike `write_encoded`, this keeps empty sequences and homopolymer run lengths and
+    /// bypasses the policy and record transform, so the record is written unchanged.
+    pub(crate) fn write_stored(&mut self, record: &RefRecord) -> Result<()> {
+        if self.header.has_aux() {
+            self.cblock.aux = record.aux();
+        }
+        if self.header.segments() > 2 {
+            let n_segments = self.header.segments();
+            let quals: Vec<&[u8]> = (0..n_segments).map(|i| record.segment_qual(i)).collect();
+            let quality = self.header.qual()
+                && !(self.header.has_optional_quality() && quals.iter().all(|q| q.is_empty()));
+            self.segment_lens.clear();
+            self.segment_words.clear();
+            for i in 0..n_segments {
+                self.segment_lens.push(record.segment_len(i));
+                self.segment_words.extend_from_slice(record.segment_buf(i));
+            }
+            return self.write_encoded_segments(record.flag(), quality.then_some(&quals));
+        }
+        let (squal, xqual) = (record.squal(), record.xqual());
+        let quality = self.header.has_base_bytes()
+            && !(self.header.has_optional_quality() && squal.is_empty() && xqual.is_empty());
+        let paired = self.header.paired();
+        self.write_encoded_record(
+            record.flag(),
+            record.slen(),
+            record.sbuf(),
+            quality.then_some(squal),
+            record.xlen(),
+            paired.then(|| record.xbuf()),
+            (quality && paired).then_some(xqual),
+        )
+    }
+
+    /// Returns whether a record is written with its quality scores
+    ///
+    /// Records without quality scores are written as such (and marked) if quality scores
+    /// are optional.
+    fn stores_quality(&self, squal: &[u8], xqual: &[u8]) -> bool {
+        self.header.qual()
+            && !(self.header.has_optional_quality() && squal.is_empty() && xqual.is_empty())
+    }
+
+    /// Writes a record with the write method matching the writer configuration
+    fn write_input(&mut self, input: &RecordInput) -> Result<bool> {
+        match (
+            self.header.paired(),
+            self.stores_quality(&input.squal, &input.xqual),
+        ) {
+            (false, false) => self.write_nucleotides(input.flag, &input.sequence),
+            (true, false) => {
+                self.write_nucleotides_paired(input.flag, &input.sequence, &input.extended)
+            }
+            (false, true) => {
+                self.write_nucleotides_quality(input.flag, &input.sequence, &input.squal)
+            }
+            (true, true) => self.write_nucleotides_quality_paired(
+                input.flag,
+                &input.sequence,
+                &input.extended,
+                &input.squal,
+                &input.xqual,
+            ),
+        }
+    }
+
+    /// Writes a pair as a merged record if merging is enabled and its mates overlap
+    ///
+    /// Returns `None` if the pair was not merged and must be written as-is.
+    fn try_merge(
+        &mut self,
+        flag: u64,
+        primary: &[u8],
+        extended: &[u8],
+        squal: &[u8],
+        xqual: &[u8],
+    ) -> Option<Result<bool>> {
+        let mut merger = self.merger.take()?;
+        let status = merger
+            .merge(primary, extended, squal, xqual)
+            .then(|| self.write_merged(flag, primary, extended, squal, xqual, &merger));
+        self.merger = Some(merger);
+        status
+    }
+
+    /// Writes the consensus of a merged pair as a record without an extended sequence
+    ///
+    /// The policy applies to the consensus, and skipped records are reported with their
+    /// original mates.
+    fn write_merged(
+        &mut self,
+        flag: u64,
+        primary: &[u8],
+        extended: &[u8],
+        squal: &[u8],
+        xqual: &[u8],
+        merger: &Merger,
+    ) -> Result<bool> {
+        let flag = flag | merger.options().flag;
+        if self.header.is_homopolymer() {
+            let status = self.write_homopolymer(flag, merger.sequence(), Some(&[]))?;
+            self.merged += u64::from(status);
+            return Ok(status);
+        }
+        if self.encoder.encode_single(merger.sequence())?.is_none() {
+            return self.skip(flag, primary, extended, squal, xqual);
+        }
+
+        let flag = flag | self.encoder.flag_bits();
+        let sbuffer = self.encoder.sbuffer();
+        let quality = self.header.qual().then(|| merger.quality());
+        let record_size =
+            record_byte_size_quality(sbuffer.len(), 0, quality.map_or(0, <[u8]>::len), 0);
+        if self.cblock.exceeds_block_size(record_size)? {
+            self.cblock.flush(&mut self.inner)?;
+        }
+        self.cblock.write_record(
+            flag,
+            merger.sequence().len() as u64,
+            0,
+            sbuffer,
+            quality,
+            None,
+            None,
+        )?;
+        self.merged += 1;
+        self.poll_flush()?;
+        Ok(true)
+    }
+
+    /// Homopolymer-compresses a record and writes it with its run lengths
+    ///
+    /// The policy applies to the compressed sequences, and skipped records are reported
+    /// with their original sequences.
+    fn write_homopolymer(
+        &mut self,
+        flag: u64,
+        primary: &[u8],
+        extended: Option<&[u8]>,
+    ) -> Result<bool> {
+        let mut collapsed = std::mem::take(&mut self.collapsed);
+        collapse(primary, &mut collapsed.sseq, &mut collapsed.sruns);
+        collapse(
+            extended.unwrap_or_default(),
+            &mut collapsed.xseq,
+            &mut collapsed.xruns,
+        );
+        let status = self.write_collapsed(flag, primary, extended, &collapsed);
+        self.collapsed = collapsed;
+        status
+    }
+
+    /// Writes the homopolymer-compressed sequences of a record
+    fn write_collapsed(
+        &mut self,
+        flag: u64,
+        primary: &[u8],
+        extended: Option<&[u8]>,
+        collapsed: &Collapsed,
+    ) -> Result<bool> {
+        let encoded = match extended {
+            Some(_) => self
+                .encoder
+                .encode_paired(&collapsed.sseq, &collapsed.xseq)?
+                .is_some(),
+            None => self.encoder.encode_single(&collapsed.sseq)?.is_some(),
+        };
+        if !encoded {
+            return self.skip(flag, primary, extended.unwrap_or_default(), &[], &[]);
+        }
+
+        let flag = flag | self.encoder.flag_bits();
+        let sbuffer = self.encoder.sbuffer();
+        let xbuffer = extended.map(|_| self.encoder.xbuffer());
+        let xruns = extended.map(|_| collapsed.xruns.as_slice());
+        let record_size = record_byte_size_quality(
+            sbuffer.len(),
+            xbuffer.map_or(0, <[u64]>::len),
+            collapsed.sruns.len(),
+            collapsed.xruns.len(),
+        );
+        if self.cblock.exceeds_block_size(record_size)? {
+            self.cblock.flush(&mut self.inner)?;
+        }
+        self.cblock.write_record(
+            flag,
+            collapsed.sseq.len() as u64,
+            collapsed.xseq.len() as u64,
+            sbuffer,
+            Some(&collapsed.sruns),
+            xbuffer,
+            xruns,
+        )?;
+        self.poll_flush()?;
+        Ok(true)
+    }
+
+    /// Writes a decoded record to the file
+    ///
+    /// This method dispatches to the write method matching the writer configuration:
+    /// the extended sequence is written if the writer is configured for paired-end
+    /// reads, and the quality scores are written if it is configured for quality scores.
+    /// In files with auxiliary values, the auxiliary value of the record is written and
+    /// also applies to the following records (see `set_record_aux`).
+    ///
+    /// # Parameters
+    ///
+    /// * `record` - The record to write
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(true)` - If the record was successfully encoded and written
+    /// * `Ok(false)` - If the record could not be encoded
+    /// * `Err(_)` - If an error occurred during writing
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use vbinseq::{OwnedRecord, VBinseqWriterBuilder};
+    /// use std::fs::File;
+    ///
+    /// let file = File::create("example.vbq").unwrap();
+    /// let mut writer = VBinseqWriterBuilder::default().build(file).unwrap();
+    ///
+    /// let record = OwnedRecord::new(0, b"ACGTACGTACGT".to_vec(), Vec::new());
+    /// writer.write_record(&record).unwrap();
+    /// ```
+    pub fn write_record(&mut self, record: &OwnedRecord) -> Result<bool> {
+        if self.header.has_aux() {
+            self.cblock.aux = record.aux();
+        }
+        if self.header.segments() > 2 {
+            let n_segments = record.n_segments();
+            let sequences: Vec<&[u8]> = (0..n_segments).map(|i| record.segment(i)).collect();
+            let mut qualities: Vec<&[u8]> =
+                (0..n_segments).map(|i| record.segment_qual(i)).collect();
+            if !self.header.qual()
+                || (self.header.has_optional_quality() && qualities.iter().all(|q| q.is_empty()))
+            {
+                qualities.clear();
+            }
+            return self.write_segments(record.flag(), &sequences, &qualities);
+        }
+        match (
+            self.header.paired(),
+            self.stores_quality(record.squal(), record.xqual()),
+        ) {
+            (false, false) => self.write_nucleotides(record.flag(), record.seq()),
+            (true, false) => {
+                self.write_nucleotides_paired(record.flag(), record.seq(), record.xseq())
+            }
+            (false, true) => {
+                self.write_nucleotides_quality(record.flag(), record.seq(), record.squal())
+            }
+            (true, true) => self.write_nucleotides_quality_paired(
+                record.flag(),
+                record.seq(),
+                record.xseq(),
+                record.squal(),
+                record.xqual(),
+            ),
+        }
+    }
+
+    /// Writes a record from a pre-encoded 2-bit sequence
+    ///
+    /// This skips the nucleotide encoding step for tools that already hold 2-bit data, e.g.
+    /// when copying `RefRecord`s between files (see `RefRecord::sbuf`) or converting from
+    /// other 2-bit formats. The words must use the VBINSEQ packing (A=0, C=1, G=2, T=3,
+    /// first nucleotide in the lowest bits), or the 4-bit packing of the `iupac` module in
+    /// IUPAC files. Since the sequence is not decoded, the encoding policy does not apply.
+    /// In homopolymer-compressed files, the sequence must
+    /// already be compressed and `squal` holds its run lengths.
+    ///
+    /// # Parameters
+    ///
+    /// * `flag` - A 64-bit flag that can store custom metadata about the sequence
+    /// * `slen` - The number of nucleotides of the sequence
+    /// * `sbuf` - The 2-bit encoded sequence (`slen.div_ceil(32)` words, or
+    ///   `slen.div_ceil(16)` in IUPAC files)
+    /// * `squal` - The quality scores of the sequence (empty if the file has none)
+    ///
+    /// # Errors
+    ///
+    /// * `WriteError::PairedFlagSet` - If the writer is configured for paired-end reads
+    /// * `WriteError::QualityFlagSet` - If quality scores are required but `squal` is empty
+    /// * `WriteError::QualityFlagNotSet` - If quality scores are given but not stored
+    /// * `WriteError::QualityLengthMismatch` - If quality scores differ in length from their
+    ///   sequence (the error names the index the record would have had)
+    /// * `WriteError::InvalidEncodedLength` - If `slen` is zero or `sbuf` has the wrong length
+    /// * `WriteError::FixedLengthMismatch` - If the file has fixed-length records of another
+    ///   length
+    /// * An I/O error occurred while writing
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use vbinseq::{MmapReader, VBinseqWriterBuilder};
+    /// use std::fs::File;
+    ///
+    /// // Copy the records with an even flag without decoding them
+    /// let mut reader = MmapReader::new("input.vbq").unwrap();
+    /// let mut writer = VBinseqWriterBuilder::default()
+    ///     .header(reader.header())
+    ///     .build(File::create("even.vbq").unwrap())
+    ///     .unwrap();
+    /// let mut block = reader.new_block();
+    /// while reader.read_block_into(&mut block).unwrap() {
+    ///     for record in block.iter().filter(|record| record.flag() % 2 == 0) {
+    ///         writer
+    ///             .write_encoded(record.flag(), record.slen(), record.sbuf(), record.squal())
+    ///             .unwrap();
+    ///     }
+    /// }
+    /// writer.finish().unwrap();
+    /// ```
+    pub fn write_encoded(
+        &mut self,
+        flag: u64,
+        slen: u64,
+        sbuf: &[u64],
+        squal: &[u8],
+    ) -> Result<()> {
+        self.check_open()?;
+        if self.header.paired() {
+            return Err(WriteError::PairedFlagSet.into());
+        }
+        let squal = self.check_encoded(flag, slen, sbuf, squal)?;
+        self.write_encoded_record(flag, slen, sbuf, squal, 0, None, None)
+    }
+
+    /// Writes a paired record from pre-encoded 2-bit sequences
+    ///
+    /// This is the paired counterpart of `write_encoded`.
+    ///
+    /// # Parameters
+    ///
+    /// * `flag` - A 64-bit flag that can store custom metadata about the sequence pair
+    /// * `slen` - The number of nucleotides of the primary sequence
+    /// * `sbuf` - The 2-bit encoded primary sequence (`slen.div_ceil(32)` words, see `write_encoded`)
+    /// * `squal` - The quality scores of the primary sequence (empty if the file has none)
+    /// * `xlen` - The number of nucleotides of the extended sequence
+    /// * `xbuf` - The 2-bit encoded extended sequence (`xlen.div_ceil(32)` words, see `write_encoded`)
+    /// * `xqual` - The quality scores of the extended sequence (empty if the file has none)
+    ///
+    /// # Errors
+    ///
+    /// * `WriteError::PairedFlagNotSet` - If the writer is not configured for paired-end reads
+    /// * `WriteError::QualityFlagSet` - If quality scores are required but not given
+    /// * `WriteError::QualityFlagNotSet` - If quality scores are given but not stored
+    /// * `WriteError::QualityLengthMismatch` - If quality scores differ in length from their
+    ///   sequence (the error names the index the record would have had)
+    /// * `WriteError::InvalidEncodedLength` - If a length is zero or a buffer has the wrong length
+    /// * An I/O error occurred while writing
+    #[allow(clippy::too_many_arguments)]
+    pub fn write_encoded_paired(
+        &mut self,
+        flag: u64,
+        slen: u64,
+        sbuf: &[u64],
+        squal: &[u8],
+        xlen: u64,
+        xbuf: &[u64],
+        xqual: &[u8],
+    ) -> Result<()> {
+        self.check_open()?;
+        self.check_paired()?;
+        let squal = self.check_encoded(flag, slen, sbuf, squal)?;
+        let xqual = self.check_encoded(flag, xlen, xbuf, xqual)?;
+        // Records either have quality scores for both mates or none
+        if squal.is_some() != xqual.is_some() {
+            return Err(WriteError::QualityFlagSet.into());
+        }
+        self.write_encoded_record(flag, slen, sbuf, squal, xlen, Some(xbuf), xqual)
+    }
+
+    /// Checks that a record has one quality score per nucleotide
+    ///
+    /// A mismatch would shift the layout of all following records of the block.
+    fn check_quality_length(&self, flag: u64, sequence: &[u8], quality: &[u8]) -> Result<()> {
+        if sequence.len() != quality.len() {
+            let index = self.cblock.next_index();
+            return Err(WriteError::QualityLengthMismatch(
+                index,
+                flag,
+                sequence.len(),
+                quality.len(),
+            )
+            .into());
+        }
+        Ok(())
+    }
+
+    /// Validates a pre-encoded sequence against its length and the header
+    ///
+    /// Returns the quality scores to write (if the file stores quality scores).
+    fn check_encoded<'a>(
+        &self,
+        flag: u64,
+        len: u64,
+        ebuf: &[u64],
+        qual: &'a [u8],
+    ) -> Result<Option<&'a [u8]>> {
+        if len == 0 || ebuf.len() != encoded_sequence_len(len, self.header.is_iupac()) {
+            return Err(WriteError::InvalidEncodedLength(len, ebuf.len()).into());
+        }
+        match (self.header.has_base_bytes(), qual.is_empty()) {
+            (true, true) if self.header.has_optional_quality() => Ok(None),
+            (true, true) => Err(WriteError::QualityFlagSet.into()),
+            (false, false) => Err(WriteError::QualityFlagNotSet.into()),
+            (true, false) if qual.len() as u64 != len => {
+                let index = self.cblock.next_index();
+                Err(WriteError::QualityLengthMismatch(index, flag, len as usize, qual.len()).into())
+            }
+            (true, false) => Ok(Some(qual)),
+            (false, true) => Ok(None),
+        }
+    }
+
+    /// Writes a validated pre-encoded record, flushing the block first if it is full
+    #[allow(clippy::too_many_arguments)]
+    fn write_encoded_record(
+        &mut self,
+        flag: u64,
+        slen: u64,
+        sbuf: &[u64],
+        squal: Option<&[u8]>,
+        xlen: u64,
+        xbuf: Option<&[u64]>,
+        xqual: Option<&[u8]>,
+    ) -> Result<()> {
+        let record_size = record_byte_size_quality(
+            sbuf.len(),
+            xbuf.map_or(0, <[u64]>::len),
+            squal.map_or(0, <[u8]>::len),
+            xqual.map_or(0, <[u8]>::len),
+        );
+        if self.cblock.exceeds_block_size(record_size)? {
+            self.cblock.flush(&mut self.inner)?;
+        }
+        self.cblock
+            .write_record(flag, slen, xlen, sbuf, squal, xbuf, xqual)?;
+        self.poll_flush()?;
+        Ok(())
+    }
+
+    /// Appends a stored block verbatim
+    ///
+    /// The block is copied without decoding or recompressing its records, which makes
+    /// concatenating or repacking files nearly as fast as copying their bytes. Pending
+    /// records are flushed as a (partial) block first, so the records keep their order.
+    ///
+    /// If the file has a footer, the records of the block are added to its content digest,
+    /// which requires decompressing the block (but not recompressing it). Blocks compressed
+    /// with a dictionary can only be copied to files holding the same dictionary under the
+    /// same number (see `RawBlock::dictionaries`).
+    /// The quality scores of the block are not binned (see
+    /// `VBinseqWriterBuilder::quality_bins`).
+    ///
+    /// # Parameters
+    ///
+    /// * `block` - The block to append (see `MmapReader::next_raw_block`)
+    ///
+    /// # Errors
+    ///
+    /// * `WriteError::IncompatibleHeaders` - If the block size, quality, compression,
+    ///   segment count, homopolymer, record checksum, auxiliary value, fixed-length,
+    ///   optional quality, block checksum, columnar layout, or IUPAC flags or the quality
+    ///   transform of the source file differ from this file
+    /// * `WriteError::CodecMismatch` - If the block was stored with another codec than the
+    ///   codec of this file, unless it is stored uncompressed in a compressed file with the
+    ///   codec fallback flag (see `VBinseqHeader::set_codec_fallback`)
+    /// * `WriteError::UnknownDictionary` - If the block was compressed with a dictionary
+    ///   missing from this file
+    /// * `WriteError::DictionaryMismatch` - If the dictionary of the block differs from the
+    ///   dictionary of the same number in this file
+    /// * An I/O error occurred while writing
+    pub fn write_raw_block(&mut self, block: &RawBlock) -> Result<()> {
+        self.check_open()?;
+        let source = block.file_header;
+        if source.block() != self.header.block()
+            || source.qual() != self.header.qual()
+            || source.compressed() != self.header.compressed()
+            || source.segments() != self.header.segments()
+            || source.quality_transform() != self.header.quality_transform()
+            || source.is_columnar() != self.header.is_columnar()
+            || source.is_iupac() != self.header.is_iupac()
+            || source.is_homopolymer() != self.header.is_homopolymer()
+            || source.has_record_crc() != self.header.has_record_crc()
+            || source.has_aux() != self.header.has_aux()
+            || source.is_fixed_length() != self.header.is_fixed_length()
+            || source.has_optional_quality() != self.header.has_optional_quality()
+            || source.has_block_checksum() != self.header.has_block_checksum()
+        {
+            return Err(WriteError::IncompatibleHeaders(self.header, source).into());
+        }
+        let codec = block.header.codec()?.unwrap_or(source.codec());
+        let fallback = codec == Codec::Uncompressed && self.header.has_codec_fallback();
+        if codec != self.header.codec() && !fallback {
+            return Err(WriteError::CodecMismatch(codec, self.header.codec()).into());
+        }
+        let n_dictionaries = self.cblock.dictionaries.len();
+        if let Some(id) = block.header.dictionary().map(usize::from) {
+            if id >= n_dictionaries {
+                return Err(WriteError::UnknownDictionary(id, n_dictionaries).into());
+            }
+            if block.dictionaries.get(id) != Some(&self.cblock.dictionaries[id]) {
+                return Err(WriteError::DictionaryMismatch(id).into());
+            }
+        }
+
+        self.cblock.flush(&mut self.inner)?;
+        if let Some(digest) = &mut self.cblock.digest {
+            let dictionaries = &self.cblock.dictionaries;
+            hash_block(
+                digest,
+                &block.header,
+                block.data,
+                &source,
+                dictionaries,
+                &mut Vec::new(),
+            )?;
+        }
+        block.header.write_bytes(&mut self.inner)?;
+        self.inner.write_all(block.data)?;
+        self.cblock.flushed += u64::from(block.header.records);
+        Ok(())
+    }
+
+    /// Flushes the current block if the flush interval or threshold is reached
+    ///
+    /// This is called automatically whenever a record is written. Producers writing at a
+    /// low rate can call it periodically (e.g. from their event loop) so that records
+    /// also become readable while no new records arrive. Without a flush interval or
+    /// threshold (see `VBinseqWriterBuilder::flush_interval`) this never flushes.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(true)` - If the block was flushed
+    /// * `Ok(false)` - If no flush was due
+    /// * `Err(_)` - If an error occurred during flushing
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use vbinseq::VBinseqWriterBuilder;
+    /// use std::fs::File;
+    /// use std::time::Duration;
+    ///
+    /// let mut writer = VBinseqWriterBuilder::default()
+    ///     .flush_interval(Duration::from_secs(1))
+    ///     .build(File::create("live.vbq").unwrap())
+    ///     .unwrap();
+    /// writer.write_nucleotides(0, b"ACGTACGT").unwrap();
+    ///
+    /// // Later, while waiting for more records
+    /// std::thread::sleep(Duration::from_secs(1));
+    /// assert!(writer.poll_flush().unwrap());
+    /// ```
+    pub fn poll_flush(&mut self) -> Result<bool> {
+        let Some(opened) = self.cblock.opened else {
+            return Ok(false);
+        };
+        let due = self
+            .flush_threshold
+            .is_some_and(|threshold| self.cblock.pos >= threshold)
+            || self
+                .flush_interval
+                .is_some_and(|interval| opened.elapsed() >= interval);
+        if !due {
+            return Ok(false);
+        }
+        self.cblock.flush(&mut self.inner)?;
+        self.inner.flush()?;
+        Ok(true)
+    }
+
+    /// Finishes writing and flushes all data to the underlying writer
+    ///
+    /// This method should be called when you're done writing to ensure all data
+    /// is properly flushed to the underlying writer. It's automatically called
+    /// when the writer is dropped, but calling it explicitly allows you to handle
+    /// any errors that might occur during flushing.
+    ///
+    /// Once finished, the writer rejects further records with `WriteError::WriterFinished`.
+    /// Calling `finish` again (e.g. when the writer is dropped) has no effect.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If all data was successfully flushed
+    /// * `Err(_)` - If an error occurred during flushing
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use vbinseq::{VBinseqWriterBuilder, VBinseqHeader};
+    /// use std::fs::File;
+    ///
+    /// let file = File::create("example.vbq").unwrap();
+    /// let mut writer = VBinseqWriterBuilder::default()
+    ///     .build(file)
+    ///     .unwrap();
+    ///
+    /// // Write some sequences...
+    /// let sequence = b"ACGTACGTACGT";
+    /// writer.write_nucleotides(0, sequence).unwrap();
+    ///
+    /// // Manually finish and check for errors
+    /// if let Err(e) = writer.finish() {
+    ///     eprintln!("Error flushing data: {}", e);
+    /// }
+    /// ```
+    pub fn finish(&mut self) -> Result<()> {
+        self.finish_blocks()?;
+        if let Some(output) = self.atomic.take() {
+            output.commit(!self.headless)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the last block and the footer and flushes the inner writer
+    fn finish_blocks(&mut self) -> Result<()> {
+        if self.finished {
+            return Ok(());
+        }
+        if !self.pending.is_empty() {
+            return Err(WriteError::MissingSequence(self.next_sequence, self.pending.len()).into());
+        }
+        self.cblock.flush(&mut self.inner)?;
+        if let Some(digest) = &self.cblock.digest {
+            if !self.headless && !self.footer_written {
+                let mut footer = digest.footer();
+                if let Some(blocks) = digest.blocks() {
+                    let data_offset = self.header.data_offset() as u64;
+                    let index = crate::BlockIndex::from_block_sizes(data_offset, blocks);
+                    index.write_embedded(&mut self.inner)?;
+                    footer.set_index_size(index.embedded_size());
+                }
+                footer.write_bytes(&mut self.inner)?;
+                self.footer_written = true;
+            }
+        }
+        self.inner.flush()?;
+        self.finished = true;
+        Ok(())
+    }
+
+    /// Finishes the writer and closes it
+    ///
+    /// This behaves like `finish` but consumes the writer, so no records can be written
+    /// afterwards. Prefer this over relying on the writer being finished when dropped, as
+    /// it reports errors that occur while flushing the last block.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If all data was successfully flushed
+    /// * `Err(_)` - If an error occurred during flushing
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use vbinseq::VBinseqWriterBuilder;
+    /// use std::fs::File;
+    ///
+    /// let mut writer = VBinseqWriterBuilder::default()
+    ///     .build(File::create("example.vbq").unwrap())
+    ///     .unwrap();
+    /// writer.write_nucleotides(0, b"ACGTACGTACGT").unwrap();
+    /// writer.close().unwrap();
+    /// ```
+    pub fn close(mut self) -> Result<()> {
+        self.finish()
+    }
+
+    /// Finishes the current member of a multi-member output
+    ///
+    /// This behaves like `finish`: the last block and the footer of the member (if its
+    /// header has one) are written, after which the member is a self-contained VBINSEQ
+    /// file. A new member can then be started with `start_member`.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If all data of the member was successfully flushed
+    /// * `Err(_)` - If an error occurred during flushing
+    pub fn finish_member(&mut self) -> Result<()> {
+        self.finish_blocks()
+    }
+
+    /// Starts a new member of a multi-member output
+    ///
+    /// Outputs with several members are concatenated VBINSEQ files, which readers consume
+    /// member by member (see `MemoryReader`). This is useful to rotate members, e.g. once
+    /// per hour of a live run, so every member is complete and readable while the next one
+    /// is written.
+    ///
+    /// The current member is finished first (see `finish_member`) unless it already is.
+    /// The new member starts with the given header and the header sections of the writer,
+    /// and keeps the settings of the builder (e.g. the compression level). Compressed
+    /// members get the codec fallback flag if the compression fallback is enabled.
+    ///
+    /// # Parameters
+    ///
+    /// * `header` - The header of the new member
+    ///
+    /// # Errors
+    ///
+    /// * The errors of `finish`
+    /// * The errors of `VBinseqWriterBuilder::build` if the header is invalid
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use vbinseq::{MemoryReader, VBinseqHeader, VBinseqWriterBuilder};
+    ///
+    /// let mut bytes = Vec::new();
+    /// let mut writer = VBinseqWriterBuilder::default().build(&mut bytes).unwrap();
+    /// writer.write_nucleotides(0, b"ACGTACGT").unwrap();
+    ///
+    /// // Continue with a member of paired records
+    /// writer
+    ///     .start_member(VBinseqHeader::new(false, true, true))
+    ///     .unwrap();
+    /// writer.write_nucleotides_paired(1, b"ACGT", b"TTGCA").unwrap();
+    /// writer.finish().unwrap();
+    /// drop(writer);
+    ///
+    /// let mut reader = MemoryReader::new(bytes).unwrap();
+    /// let mut block = reader.new_block();
+    /// let mut n_records = 0;
+    /// while reader.read_block_into(&mut block).unwrap() {
+    ///     n_records += block.n_records();
+    /// }
+    /// assert_eq!(n_records, 2);
+    /// assert!(reader.header().paired());
+    /// ```
+    pub fn start_member(&mut self, mut header: VBinseqHeader) -> Result<()> {
+        self.finish_member()?;
+        if self.cblock.fallback && header.compressed() {
+            header.set_codec_fallback(true);
+        }
+        let mut cblock = Self::member_block_writer(&mut header, &self.sections, self.headless)?;
+
+        // Keep the settings of the builder (and the compression context of the member)
+        self.cblock.release_buffers();
+        #[cfg(feature = "compression")]
+        {
+            cblock.context = std::mem::take(&mut self.cblock.context);
+        }
+        cblock.level = self.cblock.level;
+        cblock.workers = self.cblock.workers;
+        cblock.flushed = self.cblock.flushed;
+        cblock.fallback = header.has_codec_fallback() && header.compressed();
+        cblock.pool = self.cblock.pool.take();
+        cblock.selector = self.cblock.selector.take();
+
+        self.cblock = cblock;
+        self.header = header;
+        self.footer_written = false;
+        self.finished = false;
+        if !self.headless {
+            self.init()?;
+        }
+        Ok(())
+    }
+
+    /// Writes the complete blocks of another writer and clears them from it
+    ///
+    /// The incomplete block of the other writer is left in place.
+    fn ingest_blocks(&mut self, other: &mut VBinseqWriter<Vec<u8>>) -> Result<()> {
+        self.inner.write_all(other.by_ref())?;
+        other.by_ref().clear();
+        self.cblock.flushed += std::mem::take(&mut other.cblock.flushed);
+        if let (Some(digest), Some(other_digest)) =
+            (&mut self.cblock.digest, &mut other.cblock.digest)
+        {
+            digest.absorb(other_digest);
+        }
+        Ok(())
+    }
+
+    /// Creates an empty headless writer with the settings of this writer
+    fn headless_shard(&self) -> Result<VBinseqWriter<Vec<u8>>> {
+        let mut shard = VBinseqWriter::with_sections(
+            Vec::new(),
+            self.header,
+            self.encoder.policy,
+            true,
+            self.sections.clone(),
+        )?;
+        shard.cblock.level = self.cblock.level;
+        shard.cblock.workers = self.cblock.workers;
+        shard.cblock.fallback = self.cblock.fallback;
+        shard.cblock.pool = self.cblock.pool.clone();
+        shard.cblock.selector = self.cblock.selector.clone();
+        shard.skip_callback = self.skip_callback.clone();
+        shard.merger = self.merger.clone();
+        shard.record_transform = self.record_transform.clone();
+        Ok(shard)
+    }
+
+    /// Counts a record rejected by the policy and passes it to the skip callback
+    ///
+    /// Always returns `Ok(false)`, the status of write methods for skipped records.
+    fn skip(
+        &mut self,
+        flag: u64,
+        sequence: &[u8],
+        extended: &[u8],
+        squal: &[u8],
+        xqual: &[u8],
+    ) -> Result<bool> {
+        let reason = self.encoder.skip_reason();
+        self.skipped.count(reason);
+        if let Some(callback) = &self.skip_callback {
+            callback(&SkippedRecord {
+                flag,
+                sequence,
+                extended,
+                squal,
+                xqual,
+                reason,
+            });
+        }
+        Ok(false)
+    }
+
+    /// Returns the counts of the records skipped by the policy so far
+    ///
+    /// This includes the counts of writers ingested into this writer.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use vbinseq::{Policy, VBinseqWriterBuilder};
+    ///
+    /// let mut writer = VBinseqWriterBuilder::default()
+    ///     .policy(Policy::IgnoreSequence)
+    ///     .build(Vec::new())
+    ///     .unwrap();
+    /// assert!(writer.write_nucleotides(0, b"ACGT").unwrap());
+    /// assert!(!writer.write_nucleotides(1, b"ACNT").unwrap());
+    ///
+    /// assert_eq!(writer.skipped().total, 1);
+    /// assert_eq!(writer.skipped().invalid_primary, 1);
+    /// ```
+    pub fn skipped(&self) -> SkippedRecords {
+        self.skipped
+    }
+
+    /// Returns the number of pairs written as merged records so far
+    ///
+    /// This is always 0 unless overlap merging is enabled (see
+    /// `VBinseqWriterBuilder::merge_overlaps`), and includes the merged pairs of writers
+    /// ingested into this writer.
+    pub fn merged_pairs(&self) -> u64 {
+        self.merged
+    }
+
+    /// Returns the header of the file
+    ///
+    /// The size of the header sections is set by the writer (see
+    /// `VBinseqWriterBuilder::sections`).
+    pub fn header(&self) -> VBinseqHeader {
+        self.header
+    }
+
+    /// Returns the user-defined key/value metadata written to the file
+    ///
+    /// See `VBinseqWriterBuilder::metadata`.
+    pub fn metadata(&self) -> &[(String, String)] {
+        &self.sections.metadata
+    }
+
+    /// Sets the auxiliary value stored with the following records
+    ///
+    /// Files with auxiliary values (see `VBinseqHeader::set_aux`) store a u64 with every
+    /// record, e.g. a timestamp or the channel id of nanopore reads. The value applies to
+    /// all records written until it is set again, and is 0 initially.
+    ///
+    /// # Parameters
+    ///
+    /// * `aux` - The auxiliary value
+    ///
+    /// # Errors
+    ///
+    /// * `WriteError::AuxFlagNotSet` - If the header does not store auxiliary values
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use vbinseq::{MemoryReader, VBinseqHeader, VBinseqWriterBuilder};
+    ///
+    /// let mut header = VBinseqHeader::new(false, false, false);
+    /// header.set_aux(true);
+    /// let mut bytes = Vec::new();
+    /// let mut writer = VBinseqWriterBuilder::default()
+    ///     .header(header)
+    ///     .build(&mut bytes)
+    ///     .unwrap();
+    /// writer.set_record_aux(1_700_000_000).unwrap();
+    /// writer.write_nucleotides(0, b"ACGTACGT").unwrap();
+    /// writer.close().unwrap();
+    ///
+    /// let mut reader = MemoryReader::new(bytes).unwrap();
+    /// let mut block = reader.new_block();
+    /// reader.read_block_into(&mut block).unwrap();
+    /// assert_eq!(block.iter().next().unwrap().aux(), 1_700_000_000);
+    /// ```
+    pub fn set_record_aux(&mut self, aux: u64) -> Result<()> {
+        if !self.header.has_aux() {
+            return Err(WriteError::AuxFlagNotSet.into());
+        }
+        self.cblock.aux = aux;
+        Ok(())
+    }
+
+    /// Returns an error if the writer has been finished
+    fn check_open(&self) -> Result<()> {
+        if self.finished {
+            return Err(WriteError::WriterFinished.into());
+        }
+        Ok(())
+    }
+
+    /// Provides a mutable reference to the inner writer
+    fn by_ref(&mut self) -> &mut W {
+        self.inner.by_ref()
+    }
+
+    /// Provides a mutable reference to the BlockWriter
+    fn cblock_mut(&mut self) -> &mut BlockWriter {
+        &mut self.cblock
+    }
+
+    /// Ingests data from another VBinseqWriter that uses a Vec<u8> as its inner writer
+    ///
+    /// This method is particularly useful for parallel processing, where multiple writers
+    /// might be writing to memory buffers and need to be combined into a single file. It
+    /// transfers all complete blocks and any partial blocks from the other writer into this one.
+    ///
+    /// The method clears the other writer's buffer after ingestion, allowing it to be reused.
+    ///
+    /// # Parameters
+    ///
+    /// * `other` - Another VBinseqWriter whose inner writer is a Vec<u8>
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If ingestion was successful
+    /// * `Err(_)` - If an error occurred during ingestion or if the headers are incompatible
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if:
+    /// - The headers of the two writers are not compatible (`WriteError::IncompatibleHeaders`)
+    /// - An I/O error occurred during data transfer
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use vbinseq::{VBinseqWriterBuilder, VBinseqHeader};
+    /// use std::fs::File;
+    ///
+    /// // Create a file writer
+    /// let file = File::create("combined.vbq").unwrap();
+    /// let mut file_writer = VBinseqWriterBuilder::default()
+    ///     .build(file)
+    ///     .unwrap();
+    ///
+    /// // Create a memory writer
+    /// let mut mem_writer = VBinseqWriterBuilder::default()
+    ///     .build(Vec::new())
+    ///     .unwrap();
+    ///
+    /// // Write some data to the memory writer
+    /// mem_writer.write_nucleotides(0, b"ACGTACGT").unwrap();
+    ///
+    /// // Ingest data from memory writer into file writer
+    /// file_writer.ingest(&mut mem_writer).unwrap();
+    /// ```
+    pub fn ingest(&mut self, other: &mut VBinseqWriter<Vec<u8>>) -> Result<()> {
+        self.check_open()?;
+        if self.header != other.header {
+            return Err(WriteError::IncompatibleHeaders(self.header, other.header).into());
+        }
+
+        // Write complete blocks from other directly
+        // and clear the other (mimics reading)
+        self.ingest_blocks(other)?;
+
+        // Ingest incomplete block from other
+        {
+            self.cblock.ingest(other.cblock_mut(), &mut self.inner)?;
+        }
+
+        // Take over the skip counts of other (so they are not counted twice)
+        self.skipped.absorb(&std::mem::take(&mut other.skipped));
+        self.merged += std::mem::take(&mut other.merged);
+        Ok(())
+    }
+
+    /// Ingests another writer in the order of its sequence number
+    ///
+    /// This is the order-preserving variant of `ingest` for parallel encoders: the input is
+    /// split into batches numbered from 0, every batch is written by a worker into its own
+    /// headless writer, and the workers submit their writers tagged with the sequence
+    /// number of their batch in any order. The records of the batches are written in
+    /// order of their sequence numbers, so the input record order is preserved end-to-end.
+    ///
+    /// A writer submitted ahead of its turn is kept until all preceding sequence numbers
+    /// were ingested: its contents are moved out and `other` is replaced with an empty
+    /// writer with the same settings, so workers can keep writing to it right away. Unlike
+    /// `ingest`, the incomplete block of this writer is flushed before the complete blocks
+    /// of `other` are written, so ordered ingestion can produce more partial blocks.
+    ///
+    /// # Parameters
+    ///
+    /// * `sequence` - The sequence number of the batch written to `other`
+    /// * `other` - The writer holding the batch
+    ///
+    /// # Errors
+    ///
+    /// * `WriteError::IncompatibleHeaders` - If the headers of the writers differ
+    /// * `WriteError::DuplicateSequence` - If the sequence number was already submitted
+    /// * An I/O error occurred while writing
+    ///
+    /// Finishing the writer while writers are pending is an error
+    /// (`WriteError::MissingSequence`).
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use vbinseq::VBinseqWriterBuilder;
+    ///
+    /// let mut writer = VBinseqWriterBuilder::default().build(Vec::new()).unwrap();
+    /// let worker = || VBinseqWriterBuilder::default().headless(true).build(Vec::new());
+    /// let (mut first, mut second) = (worker().unwrap(), worker().unwrap());
+    /// first.write_nucleotides(0, b"ACGT").unwrap();
+    /// second.write_nucleotides(1, b"TTGA").unwrap();
+    ///
+    /// // The second batch waits for the first one
+    /// writer.ingest_ordered(1, &mut second).unwrap();
+    /// assert_eq!(writer.pending_ingests(), 1);
+    /// writer.ingest_ordered(0, &mut first).unwrap();
+    /// assert_eq!(writer.pending_ingests(), 0);
+    /// writer.finish().unwrap();
+    /// ```
+    pub fn ingest_ordered(
+        &mut self,
+        sequence: u64,
+        other: &mut VBinseqWriter<Vec<u8>>,
+    ) -> Result<()> {
+        self.check_open()?;
+        if self.header != other.header {
+            return Err(WriteError::IncompatibleHeaders(self.header, other.header).into());
+        }
+        if sequence < self.next_sequence || self.pending.contains_key(&sequence) {
+            return Err(WriteError::DuplicateSequence(sequence).into());
+        }
+
+        // Keep the contents of writers submitted ahead of their turn
+        if sequence > self.next_sequence {
+            let empty = other.headless_shard()?;
+            self.pending
+                .insert(sequence, std::mem::replace(other, empty));
+            return Ok(());
+        }
+
+        self.ingest_in_order(other)?;
+        self.next_sequence += 1;
+        while let Some(mut pending) = self.pending.remove(&self.next_sequence) {
+            self.ingest_in_order(&mut pending)?;
+            pending.finished = true;
+            self.next_sequence += 1;
+        }
+        Ok(())
+    }
+
+    /// Returns the number of writers ingested ahead of their turn (see `ingest_ordered`)
+    pub fn pending_ingests(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Ingests another writer after all records written to this writer
+    fn ingest_in_order(&mut self, other: &mut VBinseqWriter<Vec<u8>>) -> Result<()> {
+        // The buffered records precede the complete blocks of other
+        if !other.inner.is_empty() {
+            self.cblock.flush(&mut self.inner)?;
+        }
+        self.ingest(other)
+    }
+}
+
+/// Finishes the writer on a best-effort basis
+///
+/// Like `std::io::BufWriter`, errors while flushing cannot be reported from `drop` and are
+/// ignored. Call `finish` or `close` to handle them.
+impl<W: Write> Drop for VBinseqWriter<W> {
+    fn drop(&mut self) {
+        // Atomic outputs are only committed by an explicit `finish`
+        match self.atomic.take() {
+            Some(output) => output.discard(),
+            None => {
+                let _ = self.finish();
+            }
+        }
+    }
+}
+
+/// Writer handle that can be shared between threads
+///
+/// All methods of a `SyncWriter` take `&self`, so producer threads can write into a single
+/// output through a shared reference (e.g. from `std::thread::scope` or behind an `Arc`).
+///
+/// Records are written into a fixed number of headless shard writers, so threads mostly
+/// encode and compress blocks concurrently. Every thread writes to the same shard, whose
+/// complete blocks are moved to the output as soon as they are flushed. The incomplete
+/// blocks of the shards are merged into the output when the writer is finished.
+///
+/// Records written by a single thread stay in order, while the records of different
+/// threads are interleaved block by block.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use vbinseq::{SyncWriter, VBinseqWriterBuilder};
+/// use std::fs::File;
+///
+/// let writer = VBinseqWriterBuilder::default()
+///     .build(File::create("example.vbq").unwrap())
+///     .unwrap();
+/// let writer = SyncWriter::new(writer, 4).unwrap();
+///
+/// std::thread::scope(|scope| {
+///     for tid in 0..4 {
+///         let writer = &writer;
+///         scope.spawn(move || {
+///             for _ in 0..1000 {
+///                 writer.write_nucleotides(tid, b"ACGTACGTACGT").unwrap();
+///             }
+///         });
+///     }
+/// });
+/// writer.close().unwrap();
+/// ```
+pub struct SyncWriter<W: Write> {
+    /// The writer of the output
+    inner: Mutex<VBinseqWriter<W>>,
+
+    /// Headless writers the records are written to
+    shards: Vec<Mutex<VBinseqWriter<Vec<u8>>>>,
+}
+impl<W: Write> SyncWriter<W> {
+    /// Wraps a writer to share it between threads
+    ///
+    /// # Parameters
+    ///
+    /// * `writer` - The writer of the output (its header, policy, and compression settings
+    ///   are used for all records)
+    /// * `n_shards` - The number of shard writers (at least one), typically the number of
+    ///   producer threads
+    ///
+    /// # Errors
+    ///
+    /// * `WriteError::WriterFinished` - If the writer has already been finished
+    pub fn new(writer: VBinseqWriter<W>, n_shards: usize) -> Result<Self> {
+        writer.check_open()?;
+        let shards = (0..n_shards.max(1))
+            .map(|_| writer.headless_shard().map(Mutex::new))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self {
+            inner: Mutex::new(writer),
+            shards,
+        })
+    }
+
+    /// Returns the header of the file
+    pub fn header(&self) -> VBinseqHeader {
+        lock(&self.inner).header
+    }
+
+    /// Writes a nucleotide sequence (see `VBinseqWriter::write_nucleotides`)
+    pub fn write_nucleotides(&self, flag: u64, sequence: &[u8]) -> Result<bool> {
+        self.with_shard(|shard| shard.write_nucleotides(flag, sequence))
+    }
+
+    /// Writes a paired-end nucleotide sequence (see `VBinseqWriter::write_nucleotides_paired`)
+    pub fn write_nucleotides_paired(
+        &self,
+        flag: u64,
+        primary: &[u8],
+        extended: &[u8],
+    ) -> Result<bool> {
+        self.with_shard(|shard| shard.write_nucleotides_paired(flag, primary, extended))
+    }
+
+    /// Writes a nucleotide sequence with quality scores
+    /// (see `VBinseqWriter::write_nucleotides_quality`)
+    pub fn write_nucleotides_quality(
+        &self,
+        flag: u64,
+        sequence: &[u8],
+        quality: &[u8],
+    ) -> Result<bool> {
+        self.with_shard(|shard| shard.write_nucleotides_quality(flag, sequence, quality))
+    }
+
+    /// Writes a paired-end nucleotide sequence with quality scores
+    /// (see `VBinseqWriter::write_nucleotides_quality_paired`)
+    pub fn write_nucleotides_quality_paired(
+        &self,
+        flag: u64,
+        s_seq: &[u8],
+        x_seq: &[u8],
+        s_qual: &[u8],
+        x_qual: &[u8],
+    ) -> Result<bool> {
+        self.with_shard(|shard| {
+            shard.write_nucleotides_quality_paired(flag, s_seq, x_seq, s_qual, x_qual)
+        })
+    }
+
+    /// Writes an owned record (see `VBinseqWriter::write_record`)
+    pub fn write_record(&self, record: &OwnedRecord) -> Result<bool> {
+        self.with_shard(|shard| shard.write_record(record))
+    }
+
+    /// Writes a record of any number of segments (see `VBinseqWriter::write_segments`)
+    pub fn write_segments(
+        &self,
+        flag: u64,
+        sequences: &[&[u8]],
+        qualities: &[&[u8]],
+    ) -> Result<bool> {
+        self.with_shard(|shard| shard.write_segments(flag, sequences, qualities))
+    }
+
+    /// Writes a record from a pre-encoded 2-bit sequence (see `VBinseqWriter::write_encoded`)
+    pub fn write_encoded(&self, flag: u64, slen: u64, sbuf: &[u64], squal: &[u8]) -> Result<()> {
+        self.with_shard(|shard| shard.write_encoded(flag, slen, sbuf, squal))
+    }
+
+    /// Writes a paired record from pre-encoded 2-bit sequences
+    /// (see `VBinseqWriter::write_encoded_paired`)
+    #[allow(clippy::too_many_arguments)]
+    pub fn write_encoded_paired(
+        &self,
+        flag: u64,
+        slen: u64,
+        sbuf: &[u64],
+        squal: &[u8],
+        xlen: u64,
+        xbuf: &[u64],
+        xqual: &[u8],
+    ) -> Result<()> {
+        self.with_shard(|shard| {
+            shard.write_encoded_paired(flag, slen, sbuf, squal, xlen, xbuf, xqual)
+        })
+    }
+
+    /// Merges the shards into the output and finishes it
+    ///
+    /// This behaves like `VBinseqWriter::finish`: it is called when the writer is dropped
+    /// (ignoring errors), calling it again has no effect, and writing afterwards is an
+    /// error.
+    ///
+    /// # Errors
+    ///
+    /// * An I/O error occurred while writing
+    pub fn finish(&self) -> Result<()> {
+        // Writes lock their shard before the output, so lock in the same order
+        let mut shards: Vec<_> = self.shards.iter().map(lock).collect();
+        let mut inner = lock(&self.inner);
+        if inner.finished {
+            return Ok(());
+        }
+        for shard in &mut shards {
+            inner.ingest(shard)?;
+            shard.finished = true;
+        }
+        inner.finish()
+    }
+
+    /// Finishes the writer and closes it (see `VBinseqWriter::close`)
+    ///
+    /// # Errors
+    ///
+    /// * An I/O error occurred while writing
+    pub fn close(self) -> Result<()> {
+        self.finish()
+    }
+
+    /// Returns the counts of the records skipped by the policy so far (across all threads)
+    pub fn skipped(&self) -> SkippedRecords {
+        let mut skipped = SkippedRecords::default();
+        for shard in &self.shards {
+            skipped.absorb(&lock(shard).skipped);
+        }
+        skipped.absorb(&lock(&self.inner).skipped);
+        skipped
+    }
+
+    /// Returns the number of pairs written as merged records so far (across all threads)
+    pub fn merged_pairs(&self) -> u64 {
+        let shards: u64 = self.shards.iter().map(|shard| lock(shard).merged).sum();
+        shards + lock(&self.inner).merged
+    }
+
+    /// Runs a write on the shard of the calling thread and moves its complete blocks
+    fn with_shard<T>(
+        &self,
+        write: impl FnOnce(&mut VBinseqWriter<Vec<u8>>) -> Result<T>,
+    ) -> Result<T> {
+        let mut hasher = std::hash::DefaultHasher::new();
+        std::thread::current().id().hash(&mut hasher);
+        let index = hasher.finish() as usize % self.shards.len();
+
+        let mut shard = lock(&self.shards[index]);
+        let status = write(&mut shard)?;
+        if !shard.inner.is_empty() {
+            lock(&self.inner).ingest_blocks(&mut shard)?;
+        }
+        Ok(status)
+    }
+}
+
+/// Finishes the writer on a best-effort basis (see `VBinseqWriter`)
+impl<W: Write> Drop for SyncWriter<W> {
+    fn drop(&mut self) {
+        // Atomic outputs are discarded when the inner writer is dropped
+        if lock(&self.inner).atomic.is_none() {
+            let _ = self.finish();
+        }
+    }
+}
+
+/// Number of blocks of records handed to a worker of a `ParallelVBinseqWriter` at once
+///
+/// Every batch ends with a partial block, which is completed with the records of the next
+/// batch on the calling thread, so larger batches compress more of the blocks in parallel.
+pub const PARALLEL_BATCH_BLOCKS: usize = 8;
+
+/// Writer encoding and compressing blocks on a pool of worker threads
+///
+/// Writing is usually limited by the compression of the blocks, which a single
+/// `VBinseqWriter` runs on the calling thread. A `ParallelVBinseqWriter` buffers the
+/// records written to it into batches of about `PARALLEL_BATCH_BLOCKS` blocks, and its
+/// workers encode and compress every batch into a headless writer. The batches are
+/// ingested into the output in the order they were written (see
+/// `VBinseqWriter::ingest_ordered`), so the file holds the records in write order, just
+/// like a file written by a single writer.
+///
+/// Since records are encoded later on a worker, the write methods do not report whether a
+/// record was skipped by the policy (see `skipped`), and errors of a record (e.g. invalid
+/// nucleotides with `Policy::BreakOnInvalid`) are returned by a later write or by
+/// `finish`. The writer cannot be used after an error.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use vbinseq::{ParallelVBinseqWriter, VBinseqWriterBuilder};
+/// use std::fs::File;
+///
+/// let writer = VBinseqWriterBuilder::default()
+///     .build(File::create("example.vbq").unwrap())
+///     .unwrap();
+/// let mut writer = ParallelVBinseqWriter::new(writer, 8).unwrap();
+/// for flag in 0..1_000_000 {
+///     writer.write_nucleotides(flag, b"ACGTACGTACGT").unwrap();
+/// }
+/// writer.close().unwrap();
+/// ```
+pub struct ParallelVBinseqWriter<W: Write> {
+    /// The writer of the output
+    inner: VBinseqWriter<W>,
+
+    /// The batch records are buffered into
+    batch: Batch,
+
+    /// Size of a batch in (estimated) encoded bytes
+    batch_size: usize,
+
+    /// Sequence number of the next batch
+    next_batch: u64,
+
+    /// Number of batches handed to the workers and not yet ingested
+    in_flight: usize,
+
+    /// Maximum number of batches handed to the workers at once
+    max_in_flight: usize,
+
+    /// Auxiliary value of the following records (see `set_record_aux`)
+    aux: u64,
+
+    /// Idle batches and headless writers (kept for reuse)
+    idle_batches: Vec<Batch>,
+    idle_shards: Vec<VBinseqWriter<Vec<u8>>>,
+
+    /// Channels to and from the workers (the sender is dropped to stop them)
+    jobs: Option<mpsc::Sender<BatchJob>>,
+    done: mpsc::Receiver<(BatchJob, Result<()>)>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+/// Records buffered by a `ParallelVBinseqWriter`
+#[derive(Default)]
+struct Batch {
+    /// Records of the batch (the buffers of unused records are kept for reuse)
+    records: Vec<BatchRecord>,
+
+    /// Number of records in use
+    len: usize,
+
+    /// Estimated encoded size of the records
+    size: usize,
+}
+
+/// A record buffered by a `ParallelVBinseqWriter` along with how it was written
+#[derive(Default)]
+struct BatchRecord {
+    input: RecordInput,
+    aux: u64,
+    paired: bool,
+    quality: bool,
+
+    /// Record of more than two segments (written with `write_record` instead of `input`)
+    segments: Option<OwnedRecord>,
+}
+
+/// A batch handed to a worker, and the headless writer it is written to
+struct BatchJob {
+    sequence: u64,
+    batch: Batch,
+    shard: VBinseqWriter<Vec<u8>>,
+}
+
+impl Batch {
+    /// Writes the records with the write methods they were passed to
+    fn write_into(&self, shard: &mut VBinseqWriter<Vec<u8>>) -> Result<()> {
+        for record in &self.records[..self.len] {
+            if shard.header.has_aux() {
+                shard.set_record_aux(record.aux)?;
+            }
+            if let Some(segments) = &record.segments {
+                shard.write_record(segments)?;
+                continue;
+            }
+            let input = &record.input;
+            match (record.paired, record.quality) {
+                (false, false) => shard.write_nucleotides(input.flag, &input.sequence),
+                (true, false) => {
+                    shard.write_nucleotides_paired(input.flag, &input.sequence, &input.extended)
+                }
+                (false, true) => {
+                    shard.write_nucleotides_quality(input.flag, &input.sequence, &input.squal)
+                }
+                (true, true) => shard.write_nucleotides_quality_paired(
+                    input.flag,
+                    &input.sequence,
+                    &input.extended,
+                    &input.squal,
+                    &input.xqual,
+                ),
+            }?;
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write> ParallelVBinseqWriter<W> {
+    /// Wraps a writer to encode and compress its blocks on worker threads
+    ///
+    /// # Parameters
+    ///
+    /// * `writer` - The writer of the output (its header, policy, and compression settings
+    ///   are used for all records)
+    /// * `n_threads` - The number of worker threads (at least one)
+    ///
+    /// # Errors
+    ///
+    /// * `WriteError::WriterFinished` - If the writer has already been finished
+    /// * An I/O error if a worker thread cannot be spawned
+    pub fn new(writer: VBinseqWriter<W>, n_threads: usize) -> Result<Self> {
+        writer.check_open()?;
+        let n_threads = n_threads.max(1);
+        let (jobs, receiver) = mpsc::channel::<BatchJob>();
+        let (sender, done) = mpsc::channel();
+        let receiver = Arc::new(Mutex::new(receiver));
+        let workers = (0..n_threads)
+            .map(|index| {
+                let (receiver, sender) = (receiver.clone(), sender.clone());
+                std::thread::Builder::new()
+                    .name(format!("vbq-writer-{index}"))
+                    .spawn(move || {
+                        loop {
+                            // The lock is released before the batch is written
+                            let job = lock(&receiver).recv();
+                            let Ok(mut job) = job else {
+                                break;
+                            };
+                            let status = job.batch.write_into(&mut job.shard);
+                            if sender.send((job, status)).is_err() {
+                                break;
+                            }
+                        }
+                    })
+            })
+            .collect::<std::io::Result<Vec<_>>>()?;
+        Ok(Self {
+            batch_size: PARALLEL_BATCH_BLOCKS * writer.header.block() as usize,
+            next_batch: writer.next_sequence,
+            inner: writer,
+            batch: Batch::default(),
+            in_flight: 0,
+            max_in_flight: 2 * n_threads,
+            aux: 0,
+            idle_batches: Vec::new(),
+            idle_shards: Vec::new(),
+            jobs: Some(jobs),
+            done,
+            workers,
+        })
+    }
+
+    /// Returns the header of the file
+    pub fn header(&self) -> VBinseqHeader {
+        self.inner.header
+    }
+
+    /// Writes a nucleotide sequence (see `VBinseqWriter::write_nucleotides`)
+    pub fn write_nucleotides(&mut self, flag: u64, sequence: &[u8]) -> Result<()> {
+        self.push(flag, sequence, &[], &[], &[], false, false)
+    }
+
+    /// Writes a paired-end nucleotide sequence (see `VBinseqWriter::write_nucleotides_paired`)
+    pub fn write_nucleotides_paired(
+        &mut self,
+        flag: u64,
+        primary: &[u8],
+        extended: &[u8],
+    ) -> Result<()> {
+        self.push(flag, primary, extended, &[], &[], true, false)
+    }
+
+    /// Writes a nucleotide sequence with quality scores
+    /// (see `VBinseqWriter::write_nucleotides_quality`)
+    pub fn write_nucleotides_quality(
+        &mut self,
+        flag: u64,
+        sequence: &[u8],
+        quality: &[u8],
+    ) -> Result<()> {
+        self.push(flag, sequence, &[], quality, &[], false, true)
+    }
+
+    /// Writes a paired-end nucleotide sequence with quality scores
+    /// (see `VBinseqWriter::write_nucleotides_quality_paired`)
+    pub fn write_nucleotides_quality_paired(
+        &mut self,
+        flag: u64,
+        s_seq: &[u8],
+        x_seq: &[u8],
+        s_qual: &[u8],
+        x_qual: &[u8],
+    ) -> Result<()> {
+        self.push(flag, s_seq, x_seq, s_qual, x_qual, true, true)
+    }
+
+    /// Writes an owned record (see `VBinseqWriter::write_record`)
+    ///
+    /// In files with auxiliary values, the auxiliary value of the record is written and
+    /// also applies to the following records.
+    pub fn write_record(&mut self, record: &OwnedRecord) -> Result<()> {
+        if self.inner.header.has_aux() {
+            self.aux = record.aux();
+        }
+        if self.inner.header.segments() > 2 {
+            return self.push_segments(record);
+        }
+        self.push(
+            record.flag(),
+            record.seq(),
+            record.xseq(),
+            record.squal(),
+            record.xqual(),
+            self.inner.header.paired(),
+            self.inner.stores_quality(record.squal(), record.xqual()),
+        )
+    }
+
+    /// Sets the auxiliary value of the following records (see `VBinseqWriter::set_record_aux`)
+    ///
+    /// # Errors
+    ///
+    /// * `WriteError::AuxFlagNotSet` - If the file has no auxiliary values
+    pub fn set_record_aux(&mut self, aux: u64) -> Result<()> {
+        if !self.inner.header.has_aux() {
+            return Err(WriteError::AuxFlagNotSet.into());
+        }
+        self.aux = aux;
+        Ok(())
+    }
+
+    /// Writes all buffered records, stops the workers, and finishes the output
+    ///
+    /// This behaves like `VBinseqWriter::finish`: it is called when the writer is dropped
+    /// (ignoring errors), calling it again has no effect, and writing afterwards is an
+    /// error.
+    ///
+    /// # Errors
+    ///
+    /// * The first error of a record written by the workers
+    /// * An I/O error occurred while writing
+    pub fn finish(&mut self) -> Result<()> {
+        if self.inner.finished {
+            return Ok(());
+        }
+        if self.batch.len > 0 {
+            self.submit()?;
+        }
+        while self.in_flight > 0 {
+            self.ingest_next()?;
+        }
+        self.stop_workers();
+        self.inner.finish()
+    }
+
+    /// Finishes the writer and closes it (see `VBinseqWriter::close`)
+    ///
+    /// # Errors
+    ///
+    /// * The errors of `finish`
+    pub fn close(mut self) -> Result<()> {
+        self.finish()
+    }
+
+    /// Returns the counts of the records skipped by the policy so far
+    ///
+    /// This only counts the records of the batches written to the output, so the counts are
+    /// complete once the writer is finished.
+    pub fn skipped(&self) -> SkippedRecords {
+        self.inner.skipped
+    }
+
+    /// Returns the number of pairs written as merged records so far (see `skipped`)
+    pub fn merged_pairs(&self) -> u64 {
+        self.inner.merged
+    }
+
+    /// Buffers a record, handing the batch to the workers once it is full
+    #[allow(clippy::too_many_arguments)]
+    fn push(
+        &mut self,
+        flag: u64,
+        sequence: &[u8],
+        extended: &[u8],
+        squal: &[u8],
+        xqual: &[u8],
+        paired: bool,
+        quality: bool,
+    ) -> Result<()> {
+        self.inner.check_open()?;
+        if self.batch.len == self.batch.records.len() {
+            self.batch.records.push(BatchRecord::default());
+        }
+        let record = &mut self.batch.records[self.batch.len];
+        record.input.set(flag, sequence, extended, squal, xqual);
+        record.aux = self.aux;
+        record.paired = paired;
+        record.quality = quality;
+        record.segments = None;
+        self.batch.len += 1;
+        self.batch.size += 24
+            + sequence.len().div_ceil(4)
+            + extended.len().div_ceil(4)
+            + squal.len()
+            + xqual.len();
+        if self.batch.size >= self.batch_size {
+            self.submit()?;
+        }
+        Ok(())
+    }
+
+    /// Adds a record of more than two segments to the current batch
+    fn push_segments(&mut self, record: &OwnedRecord) -> Result<()> {
+        self.inner.check_open()?;
+        if self.batch.len == self.batch.records.len() {
+            self.batch.records.push(BatchRecord::default());
+        }
+        let owned = record.clone().with_aux(self.aux);
+        self.batch.records[self.batch.len].segments = Some(owned);
+        self.batch.len += 1;
+        self.batch.size += 8
+            + (0..record.n_segments())
+                .map(|i| 8 + record.segment(i).len().div_ceil(4) + record.segment_qual(i).len())
+                .sum::<usize>();
+        if self.batch.size >= self.batch_size {
+            self.submit()?;
+        }
+        Ok(())
+    }
+
+    /// Hands the current batch to the workers
+    ///
+    /// If the workers are busy, this waits for the next batch to be written first.
+    fn submit(&mut self) -> Result<()> {
+        while self.in_flight >= self.max_in_flight {
+            self.ingest_next()?;
+        }
+        // Ingest the batches written in the meantime, so the output keeps up
+        while let Ok((job, status)) = self.done.try_recv() {
+            self.ingest(job, status)?;
+        }
+        let shard = match self.idle_shards.pop() {
+            Some(shard) => shard,
+            None => self.inner.headless_shard()?,
+        };
+        let mut batch = self.idle_batches.pop().unwrap_or_default();
+        std::mem::swap(&mut batch, &mut self.batch);
+        let job = BatchJob {
+            sequence: self.next_batch,
+            batch,
+            shard,
+        };
+        let Some(jobs) = &self.jobs else {
+            return Err(WriteError::WriterFinished.into());
+        };
+        if jobs.send(job).is_err() {
+            return Err(std::io::Error::other("writer thread stopped").into());
+        }
+        self.next_batch += 1;
+        self.in_flight += 1;
+        Ok(())
+    }
+
+    /// Waits for the next written batch and ingests it
+    fn ingest_next(&mut self) -> Result<()> {
+        let Ok((job, status)) = self.done.recv() else {
+            return Err(std::io::Error::other("writer thread stopped").into());
+        };
+        self.ingest(job, status)
+    }
+
+    /// Ingests a written batch in order and keeps its buffers for reuse
+    fn ingest(&mut self, mut job: BatchJob, status: Result<()>) -> Result<()> {
+        self.in_flight -= 1;
+        status?;
+        self.inner.ingest_ordered(job.sequence, &mut job.shard)?;
+        job.batch.len = 0;
+        job.batch.size = 0;
+        self.idle_batches.push(job.batch);
+        self.idle_shards.push(job.shard);
+        Ok(())
+    }
+
+    /// Stops the workers and waits for them to exit
+    fn stop_workers(&mut self) {
+        self.jobs = None;
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+/// Finishes the writer on a best-effort basis (see `VBinseqWriter`)
+impl<W: Write> Drop for ParallelVBinseqWriter<W> {
+    fn drop(&mut self) {
+        // Atomic outputs are discarded when the inner writer is dropped
+        if self.inner.atomic.is_none() {
+            let _ = self.finish();
+        }
+        self.stop_workers();
+    }
+}
+
+/// Locks a mutex, recovering the data if another thread panicked while holding it
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Pool of block buffers shared by writers
+///
+/// Cloning a pool is cheap and yields a handle to the same pool, so a single pool can be
+/// passed to many writers (see `VBinseqWriterBuilder::buffer_pool`), including writers on
+/// other threads. Buffers returned to the pool are kept for reuse, up to the maximum
+/// number of idle buffers.
+#[derive(Clone, Debug)]
+pub struct BufferPool {
+    /// Idle buffers available for reuse
+    idle: Arc<Mutex<Vec<Vec<u8>>>>,
+    /// Maximum number of idle buffers kept
+    max_idle: usize,
+}
+impl Default for BufferPool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl BufferPool {
+    /// Creates a pool that keeps every returned buffer for reuse
+    pub fn new() -> Self {
+        Self::with_max_idle(usize::MAX)
+    }
+
+    /// Creates a pool that keeps at most `max_idle` returned buffers
+    ///
+    /// Buffers returned to a full pool are freed.
+    pub fn with_max_idle(max_idle: usize) -> Self {
+        Self {
+            idle: Arc::new(Mutex::new(Vec::new())),
+            max_idle,
+        }
+    }
+
+    /// Returns the number of idle buffers in the pool
+    pub fn n_idle(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Takes an empty buffer with at least the given capacity from the pool
+    fn take(&self, capacity: usize) -> Vec<u8> {
+        let mut buffer = self.lock().pop().unwrap_or_default();
+        buffer.reserve(capacity);
+        buffer
+    }
+
+    /// Returns a buffer to the pool
+    fn give(&self, mut buffer: Vec<u8>) {
+        if buffer.capacity() == 0 {
+            return;
+        }
+        buffer.clear();
+        let mut idle = self.lock();
+        if idle.len() < self.max_idle {
+            idle.push(buffer);
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<Vec<u8>>> {
+        // The idle list stays consistent even if another thread panicked while holding it
+        self.idle.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Lazily created zstd compression context
+///
+/// The context is created on the first compressed block and reused for all following
+/// blocks. Cloning yields an empty context, which is created again on first use.
+#[cfg(feature = "compression")]
+#[derive(Default)]
+struct CompressionContext {
+    compressor: Option<Compressor<'static>>,
+    /// Dictionary loaded into the compressor (if any)
+    dictionary: Option<usize>,
+}
+#[cfg(feature = "compression")]
+impl Clone for CompressionContext {
+    fn clone(&self) -> Self {
+        Self::default()
+    }
+}
+#[cfg(feature = "compression")]
+impl CompressionContext {
+    /// Returns the compressor with a dictionary loaded, creating it on first use
+    ///
+    /// The dictionary is given with its position in the header sections, so it is only
+    /// loaded when it differs from the one of the previous block.
+    fn get(
+        &mut self,
+        level: i32,
+        workers: u32,
+        dictionary: Option<(usize, &[u8])>,
+    ) -> Result<&mut Compressor<'static>> {
+        if self.compressor.is_none() {
+            let mut compressor = Compressor::new(level)?;
+            if workers > 0 {
+                compressor.set_parameter(CParameter::NbWorkers(workers))?;
+            }
+            self.compressor = Some(compressor);
+            self.dictionary = None;
+        }
+        let compressor = self.compressor.as_mut().expect("compressor is initialized");
+        let id = dictionary.map(|(id, _)| id);
+        if id != self.dictionary {
+            // Loading an empty dictionary removes the previous one
+            compressor.set_dictionary(level, dictionary.map_or(&[], |(_, bytes)| bytes))?;
+            self.dictionary = id;
+        }
+        Ok(compressor)
+    }
+}
+
+/// Homopolymer-compressed sequences of a record and their run lengths
+#[derive(Clone, Default)]
+struct Collapsed {
+    sseq: Vec<u8>,
+    sruns: Vec<u8>,
+    xseq: Vec<u8>,
+    xruns: Vec<u8>,
+}
+
+#[derive(Clone)]
+struct BlockWriter {
+    /// Current position in the block
+    pos: usize,
+    /// Tracks all record start positions in the block
+    starts: Vec<usize>,
+    /// Number of records of the blocks written before the current block
+    flushed: u64,
+    /// Virtual block size
+    block_size: usize,
+    /// Compression level
+    level: i32,
+    /// Number of zstd worker threads
+    /// If 0, blocks are compressed on the calling thread
+    workers: u32,
+    /// Compression context reused across blocks
+    #[cfg(feature = "compression")]
+    context: CompressionContext,
+    /// Uncompressed buffer (allocated on first use)
+    ubuf: Vec<u8>,
+    /// Compressed buffer (allocated on first use)
+    zbuf: Vec<u8>,
+    /// Buffer the block is rearranged into in columnar files (allocated on first use)
+    columns: Vec<u8>,
+    /// Buffer for compressing with the other candidate dictionaries (allocated on first use)
+    #[cfg(feature = "compression")]
+    trial: Vec<u8>,
+    /// Compression dictionaries of the file
+    dictionaries: Arc<[Vec<u8>]>,
+    /// Selection of the dictionary of every block
+    /// If None, the smallest result of all dictionaries is kept
+    selector: Option<DictionarySelector>,
+    /// Codec of the blocks
+    /// Blocks are written uncompressed with `Codec::Uncompressed`
+    codec: Codec,
+    /// Compression fallback flag
+    /// If true, incompressible blocks are written uncompressed
+    fallback: bool,
+    /// Content digest of the flushed blocks
+    /// Only tracked if the file has a footer
+    digest: Option<ContentHasher>,
+    /// Pool the buffers are borrowed from
+    /// If set, buffers are only held while they are in use
+    pool: Option<BufferPool>,
+    /// Transform applied to the quality scores of the records at flush
+    transform: QualityTransform,
+    /// Binned score of every byte value, applied before the quality transform
+    /// Only set if the quality scores are binned
+    bins: Option<[u8; 256]>,
+    /// Whether every record ends with a checksum
+    /// The checksums are computed at flush, after the quality transform
+    record_crc: bool,
+    /// Number of lengths in the preamble of every record (see `VBinseqHeader::segments`)
+    length_fields: usize,
+    /// Whether sequences are 4-bit encoded (see the `iupac` module)
+    iupac: bool,
+    /// Layout of the records if blocks use the columnar layout
+    columnar: Option<Layout>,
+    /// Whether every block header stores a checksum of the uncompressed block
+    block_checksum: bool,
+    /// Checksum of the block being flushed
+    /// Only computed if the file has block checksums
+    checksum: Option<u64>,
+    /// Number of read groups in the read-group table
+    /// If 0, the read-group bits of the flags are not checked
+    read_groups: usize,
+    /// Whether every record stores an auxiliary value
+    has_aux: bool,
+    /// Auxiliary value written with the following records
+    aux: u64,
+    /// Whether records omit their lengths (fixed-length files)
+    fixed: bool,
+    /// Length of all records of a fixed-length file
+    /// None until the first record is written
+    length: Option<u64>,
+    /// Whether records without quality scores are marked (optional quality scores)
+    marks_quality: bool,
+    /// Time the first record was written to the block
+    /// None if the block is empty
+    opened: Option<Instant>,
+}
+impl BlockWriter {
+    fn new(block_size: usize, codec: Codec) -> Self {
+        Self {
+            pos: 0,
+            starts: Vec::default(),
+            flushed: 0,
+            block_size,
+            level: DEFAULT_COMPRESSION_LEVEL,
+            workers: 0,
+            #[cfg(feature = "compression")]
+            context: CompressionContext::default(),
+            ubuf: Vec::new(),
+            zbuf: Vec::new(),
+            columns: Vec::new(),
+            #[cfg(feature = "compression")]
+            trial: Vec::new(),
+            dictionaries: Arc::from([]),
+            selector: None,
+            codec,
+            fallback: false,
+            digest: None,
+            pool: None,
+            transform: QualityTransform::None,
+            bins: None,
+            record_crc: false,
+            length_fields: 2,
+            iupac: false,
+            columnar: None,
+            block_checksum: false,
+            checksum: None,
+            read_groups: 0,
+            has_aux: false,
+            aux: 0,
+            fixed: false,
+            length: None,
+            marks_quality: false,
+            opened: None,
+        }
+    }
+
+    /// Allocates a buffer for a block (or takes one from the pool)
+    fn allocate(&self) -> Vec<u8> {
+        match &self.pool {
+            Some(pool) => pool.take(self.block_size),
+            None => Vec::with_capacity(self.block_size),
+        }
+    }
+
+    /// Allocates the block buffer if none is held
+    fn acquire_buffer(&mut self) {
+        if self.ubuf.capacity() == 0 {
+            self.ubuf = self.allocate();
+        }
+    }
+
+    /// Returns the empty buffers to the pool
+    fn release_buffers(&mut self) {
+        if let Some(pool) = &self.pool {
+            if self.ubuf.is_empty() {
+                pool.give(std::mem::take(&mut self.ubuf));
+            }
+            pool.give(std::mem::take(&mut self.zbuf));
+            pool.give(std::mem::take(&mut self.columns));
+        }
+    }
+
+    fn exceeds_block_size(&self, record_size: usize) -> Result<bool> {
+        let record_size = record_size + self.trailer_size() - self.omitted_size();
+        if record_size > self.block_size {
+            return Err(WriteError::RecordSizeExceedsMaximumBlockSize(
+                record_size,
+                self.block_size,
+            )
+            .into());
+        }
+        Ok(self.pos + record_size > self.block_size)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn write_record(
+        &mut self,
+        flag: u64,
+        slen: u64,
+        xlen: u64,
+        sbuf: &[u64],
+        squal: Option<&[u8]>,
+        xbuf: Option<&[u64]>,
+        xqual: Option<&[u8]>,
+    ) -> Result<()> {
+        self.check_read_group(flag)?;
+
+        // Records of fixed-length files all have the length of the first record
+        if self.fixed {
+            self.check_fixed_length(slen + xlen)?;
+        }
+        self.begin_record(flag)?;
+
+        // Write the lengths (omitted in fixed-length files)
+        if !self.fixed {
+            let marked = self.marks_quality && squal.is_none();
+            self.write_length(if marked {
+                slen | RECORD_NO_QUALITY
+            } else {
+                slen
+            })?;
+            self.write_length(xlen)?;
+        }
+
+        // Write the primary sequence and optional quality
+        self.write_buffer(sbuf)?;
+        if let Some(qual) = squal {
+            self.write_quality(qual)?;
+        }
+
+        // Write the optional extended sequence and optional quality
+        if let Some(xbuf) = xbuf {
+            self.write_buffer(xbuf)?;
+        }
+        if let Some(qual) = xqual {
+            self.write_quality(qual)?;
+        }
+        self.end_record()
+    }
+
+    /// Writes a record of more than two segments
+    ///
+    /// The lengths of all segments precede their sequences, which are each followed by
+    /// their optional quality scores. Files with such records never have fixed lengths.
+    ///
+    /// # Parameters
+    ///
+    /// * `flag` - The flag of the record
+    /// * `lens` - The number of nucleotides of each segment
+    /// * `words` - The 2-bit encoded segments, one after another
+    /// * `quals` - The quality scores of each segment (if the record has any)
+    fn write_segments(
+        &mut self,
+        flag: u64,
+        lens: &[u64],
+        words: &[u64],
+        quals: Option<&[&[u8]]>,
+    ) -> Result<()> {
+        self.check_read_group(flag)?;
+        self.begin_record(flag)?;
+
+        // Write the lengths
+        let marked = self.marks_quality && quals.is_none();
+        for (i, &len) in lens.iter().enumerate() {
+            self.write_length(if marked && i == 0 {
+                len | RECORD_NO_QUALITY
+            } else {
+                len
+            })?;
+        }
+
+        // Write each sequence and its optional quality
+        let mut start = 0;
+        for (i, len) in lens.iter().enumerate() {
+            let end = start + encoded_sequence_len(*len, self.iupac);
+            self.write_buffer(&words[start..end])?;
+            if let Some(quals) = quals {
+                self.write_quality(quals[i])?;
+            }
+            start = end;
+        }
+        self.end_record()
+    }
+
+    /// Checks a record references a read group of the table (if the file has one)
+    fn check_read_group(&self, flag: u64) -> Result<()> {
+        let group = read_group(flag);
+        if self.read_groups > 0 && usize::from(group) >= self.read_groups {
+            return Err(WriteError::UnknownReadGroup(group, self.read_groups).into());
+        }
+        Ok(())
+    }
+
+    /// Starts a record by tracking its start position and writing its flag
+    fn begin_record(&mut self, flag: u64) -> Result<()> {
+        self.acquire_buffer();
+        self.opened.get_or_insert_with(Instant::now);
+        self.starts.push(self.pos);
+        self.write_flag(flag)
+    }
+
+    /// Ends a record by writing its optional auxiliary value and checksum
+    fn end_record(&mut self) -> Result<()> {
+        // Write the optional auxiliary value
+        if self.has_aux {
+            self.write_buffer(&[self.aux])?;
+        }
+
+        // Reserve space for the checksum (computed at flush)
+        if self.record_crc {
+            self.write_quality(&[0; SIZE_RECORD_CRC])?;
+        }
+
+        Ok(())
+    }
+
+    /// Checks a record has the length of all records of a fixed-length file
+    ///
+    /// The first record sets the length of the file.
+    fn check_fixed_length(&mut self, length: u64) -> Result<()> {
+        match self.length {
+            Some(fixed) if fixed != length => {
+                Err(WriteError::FixedLengthMismatch(fixed, length).into())
+            }
+            _ => {
+                self.length = Some(length);
+                Ok(())
+            }
+        }
+    }
+
+    /// Returns the number of bytes of the lengths omitted from every record
+    ///
+    /// Record sizes include the lengths, which fixed-length files do not store.
+    fn omitted_size(&self) -> usize {
+        if self.fixed {
+            16
+        } else {
+            0
+        }
+    }
+
+    /// Returns the number of bytes written after the data of every record
+    fn trailer_size(&self) -> usize {
+        let aux = if self.has_aux { SIZE_AUX } else { 0 };
+        let crc = if self.record_crc { SIZE_RECORD_CRC } else { 0 };
+        aux + crc
+    }
+
+    fn write_flag(&mut self, flag: u64) -> Result<()> {
+        self.ubuf.write_u64::<LittleEndian>(flag)?;
+        self.pos += 8;
+        Ok(())
+    }
+
+    fn write_length(&mut self, length: u64) -> Result<()> {
+        self.ubuf.write_u64::<LittleEndian>(length)?;
+        self.pos += 8;
+        Ok(())
+    }
+
+    fn write_buffer(&mut self, ebuf: &[u64]) -> Result<()> {
+        ebuf.iter()
+            .try_for_each(|&x| self.ubuf.write_u64::<LittleEndian>(x))?;
+        self.pos += 8 * ebuf.len();
+        Ok(())
+    }
+
+    fn write_quality(&mut self, quality: &[u8]) -> Result<()> {
+        self.ubuf.write_all(quality)?;
+        self.pos += quality.len();
+        Ok(())
+    }
+
+    /// Compresses the block with a codec without dictionary support (see the `codec` module)
+    ///
+    /// Writers are only created for supported codecs, so this only fails on IO errors.
+    fn flush_compressed<W: Write>(&mut self, inner: &mut W, codec: Codec) -> Result<BlockHeader> {
+        if self.zbuf.capacity() == 0 {
+            self.zbuf = self.allocate();
+        }
+        codec::compress(codec, self.level, &self.ubuf, &mut self.zbuf)?;
+
+        // Store the block as-is if compression does not pay off
+        if self.fallback && self.zbuf.len() >= self.ubuf.len() {
+            return self.flush_uncompressed(inner);
+        }
+
+        let header = self.block_header(self.zbuf.len() as u64, codec);
+        header.write_bytes(inner)?;
+        inner.write_all(&self.zbuf)?;
+
+        Ok(header)
+    }
+
+    /// Compresses the block with zstd, using the reusable context and the dictionaries
+    #[cfg(feature = "compression")]
+    fn flush_zstd<W: Write>(&mut self, inner: &mut W) -> Result<BlockHeader> {
+        if self.zbuf.capacity() == 0 {
+            self.zbuf = self.allocate();
+        }
+
+        // Select the dictionary (all candidates are tried without a selector)
+        let n_dictionaries = self.dictionaries.len();
+        let selected = match &self.selector {
+            Some(selector) if n_dictionaries > 0 => match selector(&self.ubuf) {
+                Some(id) if id >= n_dictionaries => {
+                    return Err(WriteError::UnknownDictionary(id, n_dictionaries).into());
+                }
+                id => Some(id),
+            },
+            _ => None,
+        };
+
+        // Encode the block
+        let bound = zstd::zstd_safe::compress_bound(self.ubuf.len());
+        let mut dictionary = selected.flatten();
+        let candidate = dictionary.map(|id| (id, self.dictionaries[id].as_slice()));
+        self.zbuf.reserve(bound);
+        self.context
+            .get(self.level, self.workers, candidate)?
+            .compress_to_buffer(&self.ubuf, &mut self.zbuf)?;
+        if selected.is_none() {
+            // Keep the smallest encoding
+            for id in 0..n_dictionaries {
+                let candidate = Some((id, self.dictionaries[id].as_slice()));
+                self.trial.reserve(bound);
+                self.context
+                    .get(self.level, self.workers, candidate)?
+                    .compress_to_buffer(&self.ubuf, &mut self.trial)?;
+                if self.trial.len() < self.zbuf.len() {
+                    std::mem::swap(&mut self.trial, &mut self.zbuf);
+                    dictionary = Some(id);
+                }
+            }
+        }
+
+        // Store the block as-is if compression does not pay off
+        if self.fallback && self.zbuf.len() >= self.ubuf.len() {
+            return self.flush_uncompressed(inner);
+        }
+
+        // Build a block header (this is variably sized in the compressed case)
+        let mut header = self.block_header(self.zbuf.len() as u64, Codec::Zstd);
+        header.set_dictionary(dictionary.map(|id| id as u8));
+
+        // Write the block header and compressed block
+        header.write_bytes(inner)?;
+        inner.write_all(&self.zbuf)?;
+
+        Ok(header)
+    }
+
+    fn flush_uncompressed<W: Write>(&mut self, inner: &mut W) -> Result<BlockHeader> {
+        // Build a block header (this is static in size in the uncompressed case)
+        let header = self.block_header(self.block_size as u64, Codec::Uncompressed);
+
+        // Write the block header and uncompressed block
+        header.write_bytes(inner)?;
+        inner.write_all(&self.ubuf)?;
+
+        Ok(header)
+    }
+
+    /// Builds the header of the current block
+    fn block_header(&self, size: u64, codec: Codec) -> BlockHeader {
+        let mut header = BlockHeader::new(size, self.starts.len() as u32);
+        header.set_codec(codec);
+
+        // Records with an empty primary sequence look like the padding after the last record
+        let has_empty = if self.fixed {
+            header.set_fixed_length(self.length.unwrap_or(0));
+            self.length == Some(0)
+        } else {
+            // The preambles of columnar blocks are grouped at the start of the block
+            let preamble = self.columnar.map(|layout| layout.preamble());
+            self.starts.iter().enumerate().any(|(i, &start)| {
+                let start = preamble.map_or(start, |preamble| i * preamble);
+                LittleEndian::read_u64(&self.ubuf[start + 8..start + 16]) & !RECORD_NO_QUALITY == 0
+            })
+        };
+        header.set_empty_records(has_empty);
+        if let Some(checksum) = self.checksum {
+            header.set_checksum(checksum);
+        }
+        header
+    }
+
+    /// Applies the quality bins and transform to the scores of all records in the block
+    ///
+    /// This must only be called once per block, right before it is flushed.
+    fn transform_qualities(&mut self) {
+        if self.transform == QualityTransform::None && self.bins.is_none() {
+            return;
+        }
+        for &start in &self.starts {
+            let (n_lengths, mut pos) = if self.fixed {
+                (1, start + 8)
+            } else {
+                let slen = LittleEndian::read_u64(&self.ubuf[start + 8..start + 16]);
+                if slen & RECORD_NO_QUALITY != 0 {
+                    continue;
+                }
+                (self.length_fields, start + 8 + 8 * self.length_fields)
+            };
+            for i in 0..n_lengths {
+                let len = match self.length {
+                    Some(length) if self.fixed => length,
+                    _ => {
+                        LittleEndian::read_u64(&self.ubuf[start + 8 * (i + 1)..start + 8 * (i + 2)])
+                    }
+                };
+                pos += 8 * encoded_sequence_len(len, self.iupac);
+                let scores = &mut self.ubuf[pos..pos + len as usize];
+                if let Some(bins) = &self.bins {
+                    scores
+                        .iter_mut()
+                        .for_each(|score| *score = bins[*score as usize]);
+                }
+                self.transform.encode(scores);
+                pos += len as usize;
+            }
+        }
+    }
+
+    /// Writes the checksums of all records in the block
+    ///
+    /// This must be called after the quality transform, so the checksums cover the
+    /// stored record bytes.
+    fn seal_records(&mut self) {
+        if !self.record_crc {
+            return;
+        }
+        let ends = self.starts.iter().skip(1).copied().chain([self.pos]);
+        for (&start, end) in self.starts.iter().zip(ends) {
+            checksum::seal(&mut self.ubuf[start..end]);
+        }
+    }
+
+    fn flush<W: Write>(&mut self, inner: &mut W) -> Result<()> {
+        // Skip if the block is empty
+        if self.pos == 0 {
+            return Ok(());
+        }
+
+        // Transform the quality scores as they are stored (and digested)
+        self.transform_qualities();
+        self.seal_records();
+
+        // Add the records to the content digest
+        if let Some(digest) = &mut self.digest {
+            let ends = self.starts.iter().skip(1).copied().chain([self.pos]);
+            digest.update_block(
+                self.starts
+                    .iter()
+                    .zip(ends)
+                    .map(|(&start, end)| &self.ubuf[start..end]),
+            );
+        }
+
+        // Finish out the block with padding
+        self.ubuf.resize(self.block_size, 0);
+
+        // Group the parts of the records into regions (see the `columnar` module)
+        if let Some(layout) = self.columnar {
+            let layout = layout.with_fixed_length(self.length.unwrap_or_default());
+            columnar::convert(
+                &self.ubuf,
+                self.starts.len(),
+                &layout,
+                false,
+                &mut self.columns,
+            )?;
+            std::mem::swap(&mut self.ubuf, &mut self.columns);
+        }
+        self.checksum = self.block_checksum.then(|| xxh3_64(&self.ubuf));
+
+        // Flush the block (implemented differently based on compression)
+        let header = match self.codec {
+            Codec::Uncompressed => self.flush_uncompressed(inner)?,
+            #[cfg(feature = "compression")]
+            Codec::Zstd => self.flush_zstd(inner)?,
+            codec => self.flush_compressed(inner, codec)?,
+        };
+        if let Some(digest) = &mut self.digest {
+            digest.set_block_size(header.size);
+        }
+
+        // Reset the position and buffers
+        self.flushed += self.starts.len() as u64;
+        self.clear();
+
+        Ok(())
+    }
+
+    /// Returns the index of the next record written to the file
+    fn next_index(&self) -> u64 {
+        self.flushed + self.starts.len() as u64
+    }
+
+    fn clear(&mut self) {
+        self.pos = 0;
+        self.opened = None;
+        self.starts.clear();
+        self.ubuf.clear();
+        self.zbuf.clear();
+        self.release_buffers();
+    }
+
+    /// Ingests *all* bytes from another BlockWriter.
+    ///
+    /// Because both block sizes should be equivalent the process should take
+    /// at most two steps.
+    ///
+    /// I.e. the bytes can either all fit directly into self.ubuf or an intermediate
+    /// flush step is required.
+    fn ingest<W: Write>(&mut self, other: &mut Self, inner: &mut W) -> Result<()> {
+        if self.block_size != other.block_size {
+            return Err(
+                WriteError::IncompatibleBlockSizes(self.block_size, other.block_size).into(),
+            );
+        }
+        // Records of fixed-length files must all have the same length
+        if let Some(length) = other.length.filter(|_| self.fixed) {
+            self.check_fixed_length(length)?;
+        }
+        // Number of available bytes in buffer (self)
+        self.acquire_buffer();
+        let remaining = self.block_size - self.pos;
+
+        // Quick ingestion (take all without flush)
+        if other.pos <= remaining {
+            self.ingest_all(other)
+        } else {
+            self.ingest_subset(other)?;
+            self.flush(inner)?;
+            self.ingest_all(other)
+        }
+    }
+
+    /// Takes all bytes from the other into self
+    ///
+    /// Do not call this directly - always go through `ingest`
+    fn ingest_all(&mut self, other: &mut Self) -> Result<()> {
+        let n_bytes = other.pos;
+
+        // Drain bounded bytes from other (clearing them in the process)
+        self.ubuf.write_all(other.ubuf.drain(..).as_slice())?;
+
+        // Take starts from other (shifting them in the process)
+        other
+            .starts
+            .drain(..)
+            .for_each(|start| self.starts.push(start + self.pos));
+
+        // Left shift all remaining starts in other
+        other.starts.iter_mut().for_each(|x| {
+            *x -= n_bytes;
+        });
+
+        // Shift position cursors
+        self.pos += n_bytes;
+        if n_bytes > 0 {
+            self.opened = self.opened.or(other.opened);
+        }
+
+        // Clear the other for good measure
+        other.clear();
+
+        Ok(())
+    }
+
+    /// Takes as many bytes as possible from the other into self
+    ///
+    /// Do not call this directly - always go through `ingest
+    fn ingest_subset(&mut self, other: &mut Self) -> Result<()> {
+        let remaining = self.block_size - self.pos;
+        let (start_index, end_byte) = other
+            .starts
+            .iter()
+            .enumerate()
+            .take_while(|(_idx, x)| **x <= remaining)
+            .last()
+            .map(|(idx, x)| (idx, *x))
+            .unwrap();
+
+        // Drain bounded bytes from other (clearing them in the process)
+        self.ubuf
+            .write_all(other.ubuf.drain(0..end_byte).as_slice())?;
+
+        // Take starts from other (shifting them in the process)
+        other
+            .starts
+            .drain(0..start_index)
+            .for_each(|start| self.starts.push(start + self.pos));
+
+        // Left shift all remaining starts in other
+        other.starts.iter_mut().for_each(|x| {
+            *x -= end_byte;
+        });
+
+        // Shift position cursors
+        self.pos += end_byte;
+        other.pos -= end_byte;
+
+        Ok(())
+    }
+}
+
+/// Encapsulates the logic for encoding sequences into a binary format.
+///
+/// Nucleotides are packed 32 per `u64` word by `bitnuc`, which selects a vectorized
+/// implementation at runtime (AVX2 or SSE2 on x86_64, NEON on aarch64) and falls back to
+/// a scalar implementation on other targets. Upper- and lowercase `ACGT` are accepted.
+/// Sequences with any other byte fail the fast path as a whole and are then rewritten
+/// according to the `Policy` before being packed again. Encoders for IUPAC files (see
+/// `with_iupac`) pack 16 nucleotides per word and accept all IUPAC nucleotides instead.
+#[derive(Clone)]
+pub struct Encoder {
+    /// Reusable buffers for all nucleotides (written as 2-bit after conversion)
+    sbuffer: Vec<u64>,
+    xbuffer: Vec<u64>,
+
+    /// Reusable buffers for invalid nucleotide sequences
+    s_ibuf: Vec<u8>,
+    x_ibuf: Vec<u8>,
+
+    /// Invalid Nucleotide Policy
+    policy: Policy,
+
+    /// Bits the policy sets in the flag of the last encoded record
+    flag_bits: u64,
+
+    /// Why the last rejected record was rejected
+    skip_reason: SkipReason,
+
+    /// Whether sequences are 4-bit encoded (see the `iupac` module)
+    iupac: bool,
+
+    /// Random Number Generator
+    #[cfg(feature = "policy-rand")]
+    rng: SmallRng,
+}
+
+impl Default for Encoder {
+    fn default() -> Self {
+        Self::with_policy(Policy::default())
+    }
+}
+
+impl Encoder {
+    pub fn new() -> Self {
+        Self::with_policy(Policy::default())
+    }
+
+    /// Initialize a new encoder with the given policy.
+    pub fn with_policy(policy: Policy) -> Self {
+        Self {
+            policy,
+            flag_bits: 0,
+            skip_reason: SkipReason::InvalidPrimary,
+            iupac: false,
+            sbuffer: Vec::default(),
+            xbuffer: Vec::default(),
+            s_ibuf: Vec::default(),
+            x_ibuf: Vec::default(),
+            #[cfg(feature = "policy-rand")]
+            rng: SmallRng::seed_from_u64(RNG_SEED),
+        }
+    }
+
+    /// Sets whether sequences are encoded with 4 bits per nucleotide (see the `iupac` module)
+    pub fn with_iupac(mut self, iupac: bool) -> Self {
+        self.iupac = iupac;
+        self
+    }
+
+    /// Encodes a single sequence as 2-bit (or 4-bit for IUPAC files).
+    ///
+    /// Will return `None` if the sequence is invalid and the policy does not allow correction.
+    pub fn encode_single(&mut self, primary: &[u8]) -> Result<Option<&[u64]>> {
+        // Fill the buffer with the 2-bit representation of the nucleotides
+        self.clear();
+        if encode_nucleotides(primary, &mut self.sbuffer, self.iupac).is_err() {
+            self.clear();
+            if self.handle_invalid(primary, false)? {
+                encode_nucleotides(&self.s_ibuf, &mut self.sbuffer, self.iupac)?;
+            } else {
+                self.skip_reason = SkipReason::InvalidPrimary;
+                return Ok(None);
+            }
+        }
+        self.flag_bits = self.policy.flag_bits_with(primary, self.valid());
+        Ok(Some(&self.sbuffer))
+    }
+
+    /// Encodes a pair of sequences as 2-bit.
+    ///
+    /// Will return `None` if either sequence is invalid and the policy does not allow correction.
+    pub fn encode_paired(
+        &mut self,
+        primary: &[u8],
+        extended: &[u8],
+    ) -> Result<Option<(&[u64], &[u64])>> {
+        self.clear();
+        let primary_valid = encode_nucleotides(primary, &mut self.sbuffer, self.iupac).is_ok();
+        if !primary_valid || encode_nucleotides(extended, &mut self.xbuffer, self.iupac).is_err() {
+            self.clear();
+            if self.handle_invalid(primary, false)? && self.handle_invalid(extended, true)? {
+                encode_nucleotides(&self.s_ibuf, &mut self.sbuffer, self.iupac)?;
+                encode_nucleotides(&self.x_ibuf, &mut self.xbuffer, self.iupac)?;
+            } else {
+                self.skip_reason = if primary_valid {
+                    SkipReason::InvalidExtended
+                } else {
+                    SkipReason::InvalidPrimary
+                };
+                return Ok(None);
+            }
+        }
+        let valid = self.valid();
+        self.flag_bits = self.policy.flag_bits_with(primary, valid)
+            | self.policy.flag_bits_with(extended, valid);
+        Ok(Some((&self.sbuffer, &self.xbuffer)))
+    }
+
+    /// Converts a sequence with invalid nucleotides according to the policy
+    ///
+    /// The converted sequence is written to the buffer of the primary or extended sequence.
+    fn handle_invalid(&mut self, sequence: &[u8], extended: bool) -> Result<bool> {
+        let valid = self.valid();
+        let ibuf = if extended {
+            &mut self.x_ibuf
+        } else {
+            &mut self.s_ibuf
+        };
+        #[cfg(feature = "policy-rand")]
+        let status = self
+            .policy
+            .handle_with(sequence, ibuf, valid, &mut self.rng);
+        #[cfg(not(feature = "policy-rand"))]
+        let status = self.policy.handle_with(sequence, ibuf, valid);
+        status
+    }
+
+    /// Returns the check of the bytes the encoding stores
+    fn valid(&self) -> fn(&u8) -> bool {
+        if self.iupac {
+            iupac::is_iupac
+        } else {
+            policy::is_nucleotide
+        }
+    }
+
+    /// Returns the bits the policy sets in the flag of the last encoded record
+    ///
+    /// This is 0 unless the policy carries flag bits (e.g. `Policy::SetToAWithFlag`).
+    pub fn flag_bits(&self) -> u64 {
+        self.flag_bits
+    }
+
+    /// Returns why the last record was rejected (if `encode_single` or `encode_paired`
+    /// returned `None`)
+    pub fn skip_reason(&self) -> SkipReason {
+        self.skip_reason
+    }
+
+    /// Returns the 2-bit encoded primary sequence of the last encoded record
+    pub fn sbuffer(&self) -> &[u64] {
+        &self.sbuffer
+    }
+
+    /// Returns the 2-bit encoded extended sequence of the last encoded record
+    pub fn xbuffer(&self) -> &[u64] {
+        &self.xbuffer
+    }
+
+    /// Clear all buffers and reset the encoder.
+    pub fn clear(&mut self) {
+        self.flag_bits = 0;
+        self.sbuffer.clear();
+        self.xbuffer.clear();
+        self.s_ibuf.clear();
+        self.x_ibuf.clear();
+    }
+}
+
+/// Encodes nucleotides as 2-bit (or 4-bit), accepting empty sequences (which encode to no
+/// words)
+fn encode_nucleotides(sequence: &[u8], ebuf: &mut Vec<u64>, iupac: bool) -> Result<()> {
+    if iupac {
+        iupac::encode(sequence, ebuf)?;
+    } else if !sequence.is_empty() {
+        bitnuc::encode(sequence, ebuf)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use super::{with_suffix, BlockWriter};
+    use crate::{
+        header::{SIZE_BLOCK_HEADER, SIZE_HEADER},
+        *,
+    };
+
+    #[test]
+    fn test_headless_writer() -> crate::Result<()> {
+        let writer = VBinseqWriterBuilder::default()
+            .headless(true)
+            .build(Vec::new())?;
+        assert_eq!(writer.inner.len(), 0);
+
+        let writer = VBinseqWriterBuilder::default()
+            .headless(false)
+            .build(Vec::new())?;
+        assert_eq!(writer.inner.len(), SIZE_HEADER);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ingest_empty_writer() -> crate::Result<()> {
+        // Test ingesting from an empty writer
+        let header = VBinseqHeader::new(false, false, false);
+
+        // Create a source writer that's empty
+        let mut source = VBinseqWriterBuilder::default()
+            .header(header)
+            .headless(true)
+            .build(Vec::new())?;
+
+        // Create a destination writer
+        let mut dest = VBinseqWriterBuilder::default()
+            .header(header)
+            .headless(true)
+            .build(Vec::new())?;
+
+        // Ingest from source to dest
+        dest.ingest(&mut source)?;
+
+        // Both writers should be empty
+        let source_vec = source.by_ref();
+        let dest_vec = dest.by_ref();
+
+        assert_eq!(source_vec.len(), 0);
+        assert_eq!(dest_vec.len(), 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ingest_single_record() -> crate::Result<()> {
+        // Test ingesting a single record
+        let header = VBinseqHeader::new(false, false, false);
+
+        // Create a source writer with a single record
+        let mut source = VBinseqWriterBuilder::default()
+            .header(header)
+            .headless(true)
+            .build(Vec::new())?;
+
+        // Write a single sequence
+        let seq = b"ACGTACGTACGT";
+        source.write_nucleotides(1, seq)?;
+
+        // We have not crossed a boundary
+        assert!(source.by_ref().is_empty());
+
+        // Create a destination writer
+        let mut dest = VBinseqWriterBuilder::default()
+            .header(header)
+            .headless(true)
+            .build(Vec::new())?;
+
+        // Ingest from source to dest
+        dest.ingest(&mut source)?;
+
+        // Source should be empty, dest should have content
+        let source_vec = source.by_ref();
+        assert_eq!(source_vec.len(), 0);
+
+        // Source ubuffer should be empty as well
+        let source_ubuf = &source.cblock.ubuf;
+        assert!(source_ubuf.is_empty());
+
+        // The destination vec will be empty because we haven't hit a buffer limit
+        let dest_vec = dest.by_ref();
+        assert!(dest_vec.is_empty());
+
+        // The destination ubuffer should have some data however
+        let dest_ubuf = &dest.cblock.ubuf;
+        assert!(!dest_ubuf.is_empty());
+
+        Ok(())
     }
 
-    fn exceeds_block_size(&self, record_size: usize) -> Result<bool> {
-        if record_size > self.block_size {
-            return Err(WriteError::RecordSizeExceedsMaximumBlockSize(
-                record_size,
-                self.block_size,
-            )
-            .into());
+    #[test]
+    fn test_ingest_multi_record() -> crate::Result<()> {
+        // Test ingesting a single record
+        let header = VBinseqHeader::new(false, false, false);
+
+        // Create a source writer with a single record
+        let mut source = VBinseqWriterBuilder::default()
+            .header(header)
+            .headless(true)
+            .build(Vec::new())?;
+
+        // Write multiple sequences
+        for _ in 0..30 {
+            let seq = b"ACGTACGTACGT";
+            source.write_nucleotides(1, seq)?;
         }
-        Ok(self.pos + record_size > self.block_size)
-    }
+        // We have not crossed a boundary
+        assert!(source.by_ref().is_empty());
 
-    #[allow(clippy::too_many_arguments)]
-    fn write_record(
-        &mut self,
-        flag: u64,
-        slen: u64,
-        xlen: u64,
-        sbuf: &[u64],
-        squal: Option<&[u8]>,
-        xbuf: Option<&[u64]>,
-        xqual: Option<&[u8]>,
-    ) -> Result<()> {
-        // Tracks the record start position
-        self.starts.push(self.pos);
+        // Create a destination writer
+        let mut dest = VBinseqWriterBuilder::default()
+            .header(header)
+            .headless(true)
+            .build(Vec::new())?;
 
-        // Write the flag
-        self.write_flag(flag)?;
+        // Ingest from source to dest
+        dest.ingest(&mut source)?;
 
-        // Write the lengths
-        self.write_length(slen)?;
-        self.write_length(xlen)?;
+        // Source should be empty, dest should have content
+        let source_vec = source.by_ref();
+        assert_eq!(source_vec.len(), 0);
 
-        // Write the primary sequence and optional quality
-        self.write_buffer(sbuf)?;
-        if let Some(qual) = squal {
-            self.write_quality(qual)?;
-        }
+        // Source ubuffer should be empty as well
+        let source_ubuf = &source.cblock.ubuf;
+        assert!(source_ubuf.is_empty());
 
-        // Write the optional extended sequence and optional quality
-        if let Some(xbuf) = xbuf {
-            self.write_buffer(xbuf)?;
-        }
-        if let Some(qual) = xqual {
-            self.write_quality(qual)?;
-        }
+        // The destination vec will be empty because we haven't hit a buffer limit
+        let dest_vec = dest.by_ref();
+        assert!(dest_vec.is_empty());
 
-        Ok(())
-    }
+        // The destination ubuffer should have some data however
+        let dest_ubuf = &dest.cblock.ubuf;
+        assert!(!dest_ubuf.is_empty());
 
-    fn write_flag(&mut self, flag: u64) -> Result<()> {
-        self.ubuf.write_u64::<LittleEndian>(flag)?;
-        self.pos += 8;
         Ok(())
     }
 
-    fn write_length(&mut self, length: u64) -> Result<()> {
-        self.ubuf.write_u64::<LittleEndian>(length)?;
-        self.pos += 8;
-        Ok(())
-    }
+    #[test]
+    fn test_ingest_block_boundary() -> crate::Result<()> {
+        // Test ingesting a single record
+        let header = VBinseqHeader::new(false, false, false);
 
-    fn write_buffer(&mut self, ebuf: &[u64]) -> Result<()> {
-        ebuf.iter()
-            .try_for_each(|&x| self.ubuf.write_u64::<LittleEndian>(x))?;
-        self.pos += 8 * ebuf.len();
-        Ok(())
-    }
+        // Create a source writer with a single record
+        let mut source = VBinseqWriterBuilder::default()
+            .header(header)
+            .headless(true)
+            .build(Vec::new())?;
 
-    fn write_quality(&mut self, quality: &[u8]) -> Result<()> {
-        self.ubuf.write_all(quality)?;
-        self.pos += quality.len();
-        Ok(())
-    }
+        // Write multiple sequences (will cross boundary)
+        for _ in 0..30000 {
+            let seq = b"ACGTACGTACGT";
+            source.write_nucleotides(1, seq)?;
+        }
 
-    fn flush_compressed<W: Write>(&mut self, inner: &mut W) -> Result<()> {
-        // Encode the block
-        let mut encoder = ZstdEncoder::new(&mut self.zbuf, self.level)?;
-        encoder.write_all(&self.ubuf)?;
-        encoder.finish()?;
+        // We have crossed a boundary
+        assert!(!source.by_ref().is_empty());
 
-        // Build a block header (this is variably sized in the compressed case)
-        let header = BlockHeader::new(self.zbuf.len() as u64, self.starts.len() as u32);
+        // Create a destination writer
+        let mut dest = VBinseqWriterBuilder::default()
+            .header(header)
+            .headless(true)
+            .build(Vec::new())?;
 
-        // Write the block header and compressed block
-        header.write_bytes(inner)?;
-        inner.write_all(&self.zbuf)?;
+        // Ingest from source to dest
+        dest.ingest(&mut source)?;
 
-        Ok(())
-    }
+        // Source should be empty, dest should have content
+        let source_vec = source.by_ref();
+        assert_eq!(source_vec.len(), 0);
 
-    fn flush_uncompressed<W: Write>(&mut self, inner: &mut W) -> Result<()> {
-        // Build a block header (this is static in size in the uncompressed case)
-        let header = BlockHeader::new(self.block_size as u64, self.starts.len() as u32);
+        // Source ubuffer should be empty as well
+        let source_ubuf = &source.cblock.ubuf;
+        assert!(source_ubuf.is_empty());
 
-        // Write the block header and uncompressed block
-        header.write_bytes(inner)?;
-        inner.write_all(&self.ubuf)?;
+        // The destination vec will not be empty because we hit a buffer limit
+        let dest_vec = dest.by_ref();
+        assert!(!dest_vec.is_empty());
+
+        // The destination ubuffer should have some data however
+        let dest_ubuf = &dest.cblock.ubuf;
+        assert!(!dest_ubuf.is_empty());
 
         Ok(())
     }
 
-    fn flush<W: Write>(&mut self, inner: &mut W) -> Result<()> {
-        // Skip if the block is empty
-        if self.pos == 0 {
-            return Ok(());
-        }
+    #[test]
+    fn test_ingest_with_quality_scores() -> crate::Result<()> {
+        // Test ingesting records with quality scores
+        let source_header = VBinseqHeader::new(true, false, false); // with quality
+        let dest_header = VBinseqHeader::new(true, false, false); // with quality
 
-        // Finish out the block with padding
-        let bytes_to_next_start = self.block_size - self.pos;
-        self.ubuf.write_all(&self.padding[..bytes_to_next_start])?;
+        // Create a source writer with quality scores
+        let mut source = VBinseqWriterBuilder::default()
+            .header(source_header)
+            .headless(true)
+            .build(Vec::new())?;
 
-        // Flush the block (implemented differently based on compression)
-        if self.compress {
-            self.flush_compressed(inner)?;
-        } else {
-            self.flush_uncompressed(inner)?;
+        // Write sequences with quality scores
+        for i in 0..5 {
+            let seq = b"ACGTACGTACGT";
+            // Simple quality scores (all the same for this test)
+            let qual = vec![40; seq.len()];
+            source.write_nucleotides_quality(i, seq, &qual)?;
         }
 
-        // Reset the position and buffers
-        self.clear();
+        // Create a destination writer
+        let mut dest = VBinseqWriterBuilder::default()
+            .header(dest_header)
+            .headless(true)
+            .build(Vec::new())?;
+
+        // Ingest from source to dest
+        dest.ingest(&mut source)?;
+
+        // Verify source is cleared
+        let source_vec = source.by_ref();
+        assert_eq!(source_vec.len(), 0);
+
+        // Verify destination has content in ubuf
+        let dest_ubuf = &dest.cblock.ubuf;
+        assert!(!dest_ubuf.is_empty());
 
         Ok(())
     }
 
-    fn clear(&mut self) {
-        self.pos = 0;
-        self.starts.clear();
-        self.ubuf.clear();
-        self.zbuf.clear();
-    }
+    #[test]
+    fn test_ingest_with_compression() -> crate::Result<()> {
+        // Test ingesting a single record
+        let header = VBinseqHeader::new(false, true, false);
 
-    /// Ingests *all* bytes from another BlockWriter.
-    ///
-    /// Because both block sizes should be equivalent the process should take
-    /// at most two steps.
-    ///
-    /// I.e. the bytes can either all fit directly into self.ubuf or an intermediate
-    /// flush step is required.
-    fn ingest<W: Write>(&mut self, other: &mut Self, inner: &mut W) -> Result<()> {
-        if self.block_size != other.block_size {
-            return Err(
-                WriteError::IncompatibleBlockSizes(self.block_size, other.block_size).into(),
-            );
-        }
-        // Number of available bytes in buffer (self)
-        let remaining = self.block_size - self.pos;
+        // Create a source writer with a single record
+        let mut source = VBinseqWriterBuilder::default()
+            .header(header)
+            .headless(true)
+            .build(Vec::new())?;
 
-        // Quick ingestion (take all without flush)
-        if other.pos <= remaining {
-            self.ingest_all(other)
-        } else {
-            self.ingest_subset(other)?;
-            self.flush(inner)?;
-            self.ingest_all(other)
+        // Write multiple sequences (will cross boundary)
+        for _ in 0..30000 {
+            let seq = b"ACGTACGTACGT";
+            source.write_nucleotides(1, seq)?;
         }
-    }
-
-    /// Takes all bytes from the other into self
-    ///
-    /// Do not call this directly - always go through `ingest`
-    fn ingest_all(&mut self, other: &mut Self) -> Result<()> {
-        let n_bytes = other.pos;
 
-        // Drain bounded bytes from other (clearing them in the process)
-        self.ubuf.write_all(other.ubuf.drain(..).as_slice())?;
+        // Create a destination writer
+        let mut dest = VBinseqWriterBuilder::default()
+            .header(header)
+            .headless(true)
+            .build(Vec::new())?;
 
-        // Take starts from other (shifting them in the process)
-        other
-            .starts
-            .drain(..)
-            .for_each(|start| self.starts.push(start + self.pos));
+        // Ingest from source to dest
+        dest.ingest(&mut source)?;
 
-        // Left shift all remaining starts in other
-        other.starts.iter_mut().for_each(|x| {
-            *x -= n_bytes;
-        });
+        // Source should be empty, dest should have content
+        let source_vec = source.by_ref();
+        assert_eq!(source_vec.len(), 0);
 
-        // Shift position cursors
-        self.pos += n_bytes;
+        // Source ubuffer should be empty as well
+        let source_ubuf = &source.cblock.ubuf;
+        assert!(source_ubuf.is_empty());
 
-        // Clear the other for good measure
-        other.clear();
+        // The destination vec will not be empty because we hit a buffer limit
+        let dest_vec = dest.by_ref();
+        assert!(!dest_vec.is_empty());
+
+        // The destination ubuffer should have some data however
+        let dest_ubuf = &dest.cblock.ubuf;
+        assert!(!dest_ubuf.is_empty());
 
         Ok(())
     }
 
-    /// Takes as many bytes as possible from the other into self
-    ///
-    /// Do not call this directly - always go through `ingest
-    fn ingest_subset(&mut self, other: &mut Self) -> Result<()> {
-        let remaining = self.block_size - self.pos;
-        let (start_index, end_byte) = other
-            .starts
-            .iter()
-            .enumerate()
-            .take_while(|(_idx, x)| **x <= remaining)
-            .last()
-            .map(|(idx, x)| (idx, *x))
-            .unwrap();
+    #[test]
+    fn test_compression_fallback() -> crate::Result<()> {
+        use rand::RngCore;
+
+        // A block of random bytes is incompressible
+        let mut rng = rand::rngs::SmallRng::seed_from_u64(0);
+        let mut payload = vec![0u8; 1024];
+        rng.fill_bytes(&mut payload);
+
+        for (fallback, expected) in [(false, Codec::Zstd), (true, Codec::Uncompressed)] {
+            let mut cblock = BlockWriter::new(payload.len(), Codec::Zstd);
+            cblock.fallback = fallback;
+            cblock.starts.push(0);
+            cblock.write_quality(&payload)?;
+
+            let mut inner = Vec::new();
+            cblock.flush(&mut inner)?;
+
+            let mut block_header = [0u8; SIZE_BLOCK_HEADER];
+            block_header.copy_from_slice(&inner[..SIZE_BLOCK_HEADER]);
+            let block_header = BlockHeader::from_bytes(&block_header)?;
+            assert_eq!(block_header.codec()?, Some(expected));
+            assert_eq!(block_header.size as usize, inner.len() - SIZE_BLOCK_HEADER);
+        }
 
-        // Drain bounded bytes from other (clearing them in the process)
-        self.ubuf
-            .write_all(other.ubuf.drain(0..end_byte).as_slice())?;
+        // Files that may hold uncompressed blocks are flagged, so format 1 readers reject them
+        for (compressed, fallback, flagged) in [
+            (true, Some(true), true),
+            (true, Some(false), false),
+            (true, None, false),
+            (false, Some(true), false),
+        ] {
+            let header = VBinseqHeader::with_capacity(1024, true, compressed, false);
+            let mut builder = VBinseqWriterBuilder::default().header(header);
+            if let Some(fallback) = fallback {
+                builder = builder.compression_fallback(fallback);
+            }
+            let writer = builder.build(Vec::new())?;
+            assert_eq!(writer.header().has_codec_fallback(), flagged);
+            assert_eq!(writer.header().format(), if flagged { 2 } else { 1 });
+            assert_eq!(writer.cblock.fallback, flagged);
+        }
 
-        // Take starts from other (shifting them in the process)
-        other
-            .starts
-            .drain(0..start_index)
-            .for_each(|start| self.starts.push(start + self.pos));
+        // Headers with the flag enable the fallback
+        let mut header = VBinseqHeader::with_capacity(1024, true, true, false);
+        header.set_codec_fallback(true);
+        let writer = VBinseqWriterBuilder::default()
+            .header(header)
+            .build(Vec::new())?;
+        assert!(writer.cblock.fallback);
 
-        // Left shift all remaining starts in other
-        other.starts.iter_mut().for_each(|x| {
-            *x -= end_byte;
-        });
+        Ok(())
+    }
 
-        // Shift position cursors
-        self.pos += end_byte;
-        other.pos -= end_byte;
+    #[test]
+    fn test_codecs() -> crate::Result<()> {
+        use crate::testing::{random_records, read_records, write_records};
+
+        let rng = &mut rand::rngs::SmallRng::seed_from_u64(11);
+        for codec in [Codec::Uncompressed, Codec::Zstd, Codec::Lz4, Codec::Xz] {
+            let mut header = VBinseqHeader::with_capacity(4096, true, false, true);
+            header.set_codec(codec);
+            let records = random_records(rng, &header, 200);
+            if !codec.is_supported() {
+                assert!(write_records(header, &records).is_err());
+                continue;
+            }
+            let bytes = write_records(header, &records)?;
 
+            // Every block records the codec of the file
+            let mut reader = crate::MemoryReader::new(bytes.clone())?;
+            assert_eq!(reader.header().codec(), codec);
+            while let Some(block) = reader.next_raw_block()? {
+                assert_eq!(block.header.codec()?, Some(codec));
+            }
+
+            let read = read_records(bytes)?;
+            assert_eq!(read.len(), records.len());
+            for (read, written) in read.iter().zip(&records) {
+                assert_eq!((read.seq(), read.xseq()), (written.seq(), written.xseq()));
+                assert_eq!(
+                    (read.squal(), read.xqual()),
+                    (written.squal(), written.xqual())
+                );
+            }
+        }
         Ok(())
     }
-}
 
-/// Encapsulates the logic for encoding sequences into a binary format.
-#[derive(Clone)]
-pub struct Encoder {
-    /// Reusable buffers for all nucleotides (written as 2-bit after conversion)
-    sbuffer: Vec<u64>,
-    xbuffer: Vec<u64>,
+    #[test]
+    fn test_ingest_incompatible_headers() -> crate::Result<()> {
+        let source_header = VBinseqHeader::new(false, false, false);
+        let dest_header = VBinseqHeader::new(true, false, false);
 
-    /// Reusable buffers for invalid nucleotide sequences
-    s_ibuf: Vec<u8>,
-    x_ibuf: Vec<u8>,
+        // Create a source writer with quality scores
+        let mut source = VBinseqWriterBuilder::default()
+            .header(source_header)
+            .headless(true)
+            .build(Vec::new())?;
 
-    /// Invalid Nucleotide Policy
-    policy: Policy,
+        // Create a destination writer
+        let mut dest = VBinseqWriterBuilder::default()
+            .header(dest_header)
+            .headless(true)
+            .build(Vec::new())?;
 
-    /// Random Number Generator
-    rng: SmallRng,
-}
+        // Ingest from source to dest (will error)
+        assert!(dest.ingest(&mut source).is_err());
 
-impl Default for Encoder {
-    fn default() -> Self {
-        Self::with_policy(Policy::default())
+        Ok(())
     }
-}
 
-impl Encoder {
-    pub fn new() -> Self {
-        Self::with_policy(Policy::default())
+    /// Scalar reference of the 2-bit packing (A=0, C=1, G=2, T=3, little end first)
+    fn reference_encode(sequence: &[u8]) -> Vec<u64> {
+        sequence
+            .chunks(32)
+            .map(|chunk| {
+                chunk.iter().enumerate().fold(0u64, |word, (i, base)| {
+                    let bits = match base.to_ascii_uppercase() {
+                        b'A' => 0,
+                        b'C' => 1,
+                        b'G' => 2,
+                        b'T' => 3,
+                        _ => panic!("invalid nucleotide in reference"),
+                    };
+                    word | (bits << (2 * i))
+                })
+            })
+            .collect()
     }
 
-    /// Initialize a new encoder with the given policy.
-    pub fn with_policy(policy: Policy) -> Self {
-        Self {
-            policy,
-            sbuffer: Vec::default(),
-            xbuffer: Vec::default(),
-            s_ibuf: Vec::default(),
-            x_ibuf: Vec::default(),
-            rng: SmallRng::seed_from_u64(RNG_SEED),
+    #[test]
+    fn test_encoder_matches_scalar_reference() -> crate::Result<()> {
+        use rand::Rng;
+
+        let mut rng = rand::rngs::SmallRng::seed_from_u64(0);
+        let mut encoder = super::Encoder::with_policy(Policy::SetToA);
+        let mut xencoder = super::Encoder::with_policy(Policy::IgnoreSequence);
+
+        // Cover every tail length around the vector widths, with and without invalid bytes
+        for len in 1..=200 {
+            for invalid in [false, true] {
+                let mut sequence: Vec<u8> =
+                    (0..len).map(|_| b"ACGTacgt"[rng.gen_range(0..8)]).collect();
+                if invalid {
+                    let pos = rng.gen_range(0..len);
+                    sequence[pos] = b'N';
+                }
+                let expected: Vec<u8> = sequence
+                    .iter()
+                    .map(|&base| if base == b'N' { b'A' } else { base })
+                    .collect();
+                let expected = reference_encode(&expected);
+
+                let encoded = encoder.encode_single(&sequence)?.expect("policy corrects");
+                assert_eq!(encoded, expected.as_slice(), "length {len}");
+
+                let (primary, extended) = encoder
+                    .encode_paired(&sequence, b"ACGT")?
+                    .expect("policy corrects");
+                assert_eq!(primary, expected.as_slice(), "length {len}");
+                assert_eq!(extended, reference_encode(b"ACGT").as_slice());
+
+                let skipped = xencoder.encode_single(&sequence)?.is_none();
+                assert_eq!(skipped, invalid, "length {len}");
+            }
         }
+        Ok(())
     }
 
-    /// Encodes a single sequence as 2-bit.
-    ///
-    /// Will return `None` if the sequence is invalid and the policy does not allow correction.
-    pub fn encode_single(&mut self, primary: &[u8]) -> Result<Option<&[u64]>> {
-        // Fill the buffer with the 2-bit representation of the nucleotides
-        self.clear();
-        if bitnuc::encode(primary, &mut self.sbuffer).is_err() {
-            self.clear();
-            if self
-                .policy
-                .handle(primary, &mut self.s_ibuf, &mut self.rng)?
-            {
-                bitnuc::encode(&self.s_ibuf, &mut self.sbuffer)?;
-            } else {
-                return Ok(None);
+    #[test]
+    fn test_buffer_pool() -> crate::Result<()> {
+        let header = VBinseqHeader::with_capacity(256, true, true, false);
+        let write = |pool: Option<&BufferPool>| -> crate::Result<Vec<u8>> {
+            let mut builder = VBinseqWriterBuilder::default().header(header);
+            if let Some(pool) = pool {
+                builder = builder.buffer_pool(pool.clone());
+            }
+            let mut writer = builder.build(Vec::new())?;
+            for i in 0..100u64 {
+                writer.write_nucleotides_quality(i, b"ACGTACGTAC", b"IIIIIIIIII")?;
             }
+            writer.finish()?;
+            Ok(writer.by_ref().clone())
+        };
+
+        let pool = BufferPool::new();
+        let expected = write(None)?;
+        assert_eq!(write(Some(&pool))?, expected);
+
+        // Both buffers were returned and are reused by the next writer
+        assert_eq!(pool.n_idle(), 2);
+        assert_eq!(write(Some(&pool))?, expected);
+        assert_eq!(pool.n_idle(), 2);
+
+        // Idle writers do not hold any buffers and the pool keeps at most `max_idle`
+        let bounded = BufferPool::with_max_idle(1);
+        let mut writers = (0..10)
+            .map(|_| {
+                VBinseqWriterBuilder::default()
+                    .header(header)
+                    .buffer_pool(bounded.clone())
+                    .build(Vec::new())
+            })
+            .collect::<crate::Result<Vec<_>>>()?;
+        assert!(writers.iter().all(|w| w.cblock.ubuf.capacity() == 0));
+        for writer in &mut writers {
+            writer.write_nucleotides_quality(0, b"ACGT", b"IIII")?;
+            assert!(writer.cblock.ubuf.capacity() > 0);
         }
-        Ok(Some(&self.sbuffer))
+        for writer in &mut writers {
+            writer.finish()?;
+            assert_eq!(writer.cblock.ubuf.capacity(), 0);
+        }
+        assert_eq!(bounded.n_idle(), 1);
+        Ok(())
     }
 
-    /// Encodes a pair of sequences as 2-bit.
-    ///
-    /// Will return `None` if either sequence is invalid and the policy does not allow correction.
-    pub fn encode_paired(
-        &mut self,
-        primary: &[u8],
-        extended: &[u8],
-    ) -> Result<Option<(&[u64], &[u64])>> {
-        self.clear();
-        if bitnuc::encode(primary, &mut self.sbuffer).is_err()
-            || bitnuc::encode(extended, &mut self.xbuffer).is_err()
-        {
-            self.clear();
-            if self
-                .policy
-                .handle(primary, &mut self.s_ibuf, &mut self.rng)?
-                && self
-                    .policy
-                    .handle(extended, &mut self.x_ibuf, &mut self.rng)?
-            {
-                bitnuc::encode(&self.s_ibuf, &mut self.sbuffer)?;
-                bitnuc::encode(&self.x_ibuf, &mut self.xbuffer)?;
-            } else {
-                return Ok(None);
+    #[test]
+    fn test_compression_workers() -> crate::Result<()> {
+        // Large blocks compressed through the multithreaded zstd API
+        let header = VBinseqHeader::with_capacity(4 * 1024 * 1024, false, true, false);
+        let sequences: Vec<Vec<u8>> = (0..2000).map(|i| b"ACGTTGCA".repeat(i % 500 + 1)).collect();
+
+        let mut bytes = Vec::new();
+        let mut writer = VBinseqWriterBuilder::default()
+            .header(header)
+            .compression_workers(4)
+            .build(&mut bytes)?;
+        for (flag, sequence) in sequences.iter().enumerate() {
+            writer.write_nucleotides(flag as u64, sequence)?;
+        }
+        writer.finish()?;
+        drop(writer);
+
+        let mut reader = crate::MemoryReader::new(bytes)?;
+        let mut block = reader.new_block();
+        let mut dbuf = Vec::new();
+        let mut n_records = 0;
+        while reader.read_block_into(&mut block)? {
+            for record in block.iter() {
+                dbuf.clear();
+                record.decode_s(&mut dbuf)?;
+                assert_eq!(dbuf, sequences[record.flag() as usize]);
+                n_records += 1;
             }
         }
-        Ok(Some((&self.sbuffer, &self.xbuffer)))
+        assert_eq!(n_records, sequences.len());
+        Ok(())
     }
+    #[test]
+    fn test_write_encoded() -> crate::Result<()> {
+        use crate::MemoryReader;
+
+        for paired in [false, true] {
+            let header = VBinseqHeader::with_capacity(512, true, false, paired);
+            let mut source = Vec::new();
+            let mut writer = VBinseqWriterBuilder::default()
+                .header(header)
+                .build(&mut source)?;
+            for flag in 0..100u64 {
+                let sequence = b"ACGTTGCAA".repeat(1 + flag as usize % 7);
+                let quality = vec![b'!' + flag as u8 % 40; sequence.len()];
+                writer.write_record(&OwnedRecord::new_paired(
+                    flag,
+                    sequence.clone(),
+                    sequence[3..].to_vec(),
+                    quality.clone(),
+                    quality[3..].to_vec(),
+                ))?;
+            }
+            writer.finish()?;
+            drop(writer);
+
+            // Copying the encoded records reproduces the file
+            let mut copy = Vec::new();
+            let mut writer = VBinseqWriterBuilder::default()
+                .header(header)
+                .build(&mut copy)?;
+            let mut reader = MemoryReader::new(source.clone())?;
+            let mut block = reader.new_block();
+            while reader.read_block_into(&mut block)? {
+                for record in block.iter() {
+                    if paired {
+                        writer.write_encoded_paired(
+                            record.flag(),
+                            record.slen(),
+                            record.sbuf(),
+                            record.squal(),
+                            record.xlen(),
+                            record.xbuf(),
+                            record.xqual(),
+                        )?;
+                    } else {
+                        writer.write_encoded(
+                            record.flag(),
+                            record.slen(),
+                            record.sbuf(),
+                            record.squal(),
+                        )?;
+                    }
+                }
+            }
 
-    /// Clear all buffers and reset the encoder.
-    pub fn clear(&mut self) {
-        self.sbuffer.clear();
-        self.xbuffer.clear();
-        self.s_ibuf.clear();
-        self.x_ibuf.clear();
+            // Mismatched words, missing quality scores, and the wrong pairing are rejected
+            assert!(writer.write_encoded(0, 33, &[0], b"I").is_err());
+            assert!(writer.write_encoded(0, 0, &[], b"").is_err());
+            if paired {
+                assert!(writer.write_encoded(0, 1, &[0], b"I").is_err());
+                assert!(writer
+                    .write_encoded_paired(0, 1, &[0], b"", 1, &[0], b"I")
+                    .is_err());
+            } else {
+                assert!(writer.write_encoded(0, 1, &[0], b"").is_err());
+                assert!(writer
+                    .write_encoded_paired(0, 1, &[0], b"I", 1, &[0], b"I")
+                    .is_err());
+            }
+            writer.finish()?;
+            drop(writer);
+            assert_eq!(copy, source);
+        }
+        Ok(())
     }
-}
+    #[test]
+    fn test_write_raw_block() -> crate::Result<()> {
+        use crate::error::WriteError;
+        use crate::MemoryReader;
+
+        let mut header = VBinseqHeader::with_capacity(256, false, true, false);
+        header.set_footer(true);
+        let mut source = Vec::new();
+        let mut writer = VBinseqWriterBuilder::default()
+            .header(header)
+            .build(&mut source)?;
+        for flag in 0..100 {
+            writer.write_nucleotides(flag, &b"ACGTTGCA".repeat(1 + flag as usize % 5))?;
+        }
+        writer.finish()?;
+        drop(writer);
+
+        // Copying all blocks reproduces the file, with records written in between kept
+        // in their own block
+        let copy = |extra: bool| -> crate::Result<Vec<u8>> {
+            let mut copy = Vec::new();
+            let mut writer = VBinseqWriterBuilder::default()
+                .header(header)
+                .build(&mut copy)?;
+            let mut reader = MemoryReader::new(source.clone())?;
+            while let Some(block) = reader.next_raw_block()? {
+                if extra {
+                    writer.write_nucleotides(0, b"TTTT")?;
+                }
+                writer.write_raw_block(&block)?;
+            }
+            writer.finish()?;
+            drop(writer);
+            Ok(copy)
+        };
+        assert_eq!(copy(false)?, source);
 
-#[cfg(test)]
-mod tests {
-    use crate::{header::SIZE_HEADER, *};
+        let copy = copy(true)?;
+        let reader = MemoryReader::new(copy.clone())?;
+        let footer = reader.footer().expect("file has a footer");
+        assert!(footer.matches(&crate::footer::compute_footer(&copy, &header)?));
+        assert_eq!(footer.n_records, 100 + footer.n_blocks / 2);
 
-    #[test]
-    fn test_headless_writer() -> crate::Result<()> {
-        let writer = VBinseqWriterBuilder::default()
-            .headless(true)
+        // Blocks of files with a different layout are rejected
+        let mut other = VBinseqWriterBuilder::default()
+            .header(VBinseqHeader::with_capacity(512, false, true, false))
             .build(Vec::new())?;
-        assert_eq!(writer.inner.len(), 0);
-
-        let writer = VBinseqWriterBuilder::default()
-            .headless(false)
+        let mut reader = MemoryReader::new(source)?;
+        let block = reader.next_raw_block()?.expect("file has blocks");
+        assert!(other.write_raw_block(&block).is_err());
+
+        // Blocks must match the compression and the codec of the file
+        let mut uncompressed = header;
+        uncompressed.set_compressed(false);
+        let mut other = VBinseqWriterBuilder::default()
+            .header(uncompressed)
             .build(Vec::new())?;
-        assert_eq!(writer.inner.len(), SIZE_HEADER);
-
+        assert!(matches!(
+            other.write_raw_block(&block),
+            Err(crate::Error::WriteError(WriteError::IncompatibleHeaders(
+                ..
+            )))
+        ));
+        let mut stored = block;
+        stored.header.set_codec(Codec::Uncompressed);
+        let mut other = VBinseqWriterBuilder::default()
+            .header(header)
+            .build(Vec::new())?;
+        assert!(matches!(
+            other.write_raw_block(&stored),
+            Err(crate::Error::WriteError(WriteError::CodecMismatch(
+                Codec::Uncompressed,
+                Codec::Zstd
+            )))
+        ));
+        #[cfg(feature = "lz4")]
+        {
+            let mut lz4 = header;
+            lz4.set_codec(Codec::Lz4);
+            let mut other = VBinseqWriterBuilder::default()
+                .header(lz4)
+                .build(Vec::new())?;
+            assert!(matches!(
+                other.write_raw_block(&block),
+                Err(crate::Error::WriteError(WriteError::CodecMismatch(
+                    Codec::Zstd,
+                    Codec::Lz4
+                )))
+            ));
+        }
         Ok(())
     }
 
     #[test]
-    fn test_ingest_empty_writer() -> crate::Result<()> {
-        // Test ingesting from an empty writer
-        let header = VBinseqHeader::new(false, false, false);
+    fn test_auto_flush() -> crate::Result<()> {
+        let header = VBinseqHeader::with_capacity(1024, false, true, false);
 
-        // Create a source writer that's empty
-        let mut source = VBinseqWriterBuilder::default()
+        // Without a threshold or interval records stay in the block
+        let mut writer = VBinseqWriterBuilder::default()
             .header(header)
-            .headless(true)
             .build(Vec::new())?;
+        writer.write_nucleotides(0, b"ACGTACGT")?;
+        assert!(!writer.poll_flush()?);
+        assert_eq!(writer.by_ref().len(), SIZE_HEADER);
 
-        // Create a destination writer
-        let mut dest = VBinseqWriterBuilder::default()
+        // A byte threshold flushes once enough records are buffered
+        let mut writer = VBinseqWriterBuilder::default()
             .header(header)
-            .headless(true)
+            .flush_threshold(100)
             .build(Vec::new())?;
+        let record_size = writer::record_byte_size(1, 0);
+        for flag in 0..100 {
+            writer.write_nucleotides(flag, b"ACGTACGT")?;
+            let n_pending = (flag as usize + 1) % 100usize.div_ceil(record_size);
+            assert_eq!(writer.cblock.pos, n_pending * record_size);
+        }
 
-        // Ingest from source to dest
-        dest.ingest(&mut source)?;
-
-        // Both writers should be empty
-        let source_vec = source.by_ref();
-        let dest_vec = dest.by_ref();
-
-        assert_eq!(source_vec.len(), 0);
-        assert_eq!(dest_vec.len(), 0);
-
+        // An interval flushes on the next write or poll once it has passed
+        let mut writer = VBinseqWriterBuilder::default()
+            .header(header)
+            .flush_interval(std::time::Duration::from_millis(50))
+            .build(Vec::new())?;
+        writer.write_nucleotides(0, b"ACGTACGT")?;
+        assert!(!writer.poll_flush()?);
+        std::thread::sleep(std::time::Duration::from_millis(60));
+        assert!(writer.poll_flush()?);
+        assert!(!writer.poll_flush()?);
+        assert!(writer.by_ref().len() > SIZE_HEADER);
+        writer.finish()?;
+
+        let mut reader = MemoryReader::new(writer.by_ref().clone())?;
+        let mut block = reader.new_block();
+        assert!(reader.read_block_into(&mut block)?);
+        assert_eq!(block.n_records(), 1);
         Ok(())
     }
 
     #[test]
-    fn test_ingest_single_record() -> crate::Result<()> {
-        // Test ingesting a single record
-        let header = VBinseqHeader::new(false, false, false);
-
-        // Create a source writer with a single record
-        let mut source = VBinseqWriterBuilder::default()
+    fn test_write_after_finish() -> crate::Result<()> {
+        let mut header = VBinseqHeader::with_capacity(1024, false, true, false);
+        header.set_footer(true);
+        let mut writer = VBinseqWriterBuilder::default()
             .header(header)
-            .headless(true)
             .build(Vec::new())?;
-
-        // Write a single sequence
-        let seq = b"ACGTACGTACGT";
-        source.write_nucleotides(1, seq)?;
-
-        // We have not crossed a boundary
-        assert!(source.by_ref().is_empty());
-
-        // Create a destination writer
-        let mut dest = VBinseqWriterBuilder::default()
+        writer.write_nucleotides(0, b"ACGTACGT")?;
+        writer.finish()?;
+        let n_bytes = writer.by_ref().len();
+
+        // Finishing is idempotent and writing afterwards is an error
+        writer.finish()?;
+        assert_eq!(writer.by_ref().len(), n_bytes);
+        assert!(matches!(
+            writer.write_nucleotides(1, b"ACGTACGT"),
+            Err(Error::WriteError(error::WriteError::WriterFinished))
+        ));
+        assert!(writer.write_encoded(1, 8, &[0], &[]).is_err());
+        let mut other = VBinseqWriterBuilder::default()
             .header(header)
             .headless(true)
             .build(Vec::new())?;
+        assert!(writer.ingest(&mut other).is_err());
+        assert_eq!(writer.by_ref().len(), n_bytes);
+
+        let mut reader = MemoryReader::new(writer.by_ref().clone())?;
+        assert_eq!(reader.footer().map(|footer| footer.n_records), Some(1));
+        let mut block = reader.new_block();
+        assert!(reader.read_block_into(&mut block)?);
+        assert!(!reader.read_block_into(&mut block)?);
+
+        // Closing consumes the writer, so dropping it afterwards is safe
+        let mut bytes = Vec::new();
+        let mut writer = VBinseqWriterBuilder::default()
+            .header(header)
+            .build(&mut bytes)?;
+        writer.write_nucleotides(0, b"ACGTACGT")?;
+        writer.close()?;
+        assert_eq!(bytes.len(), n_bytes);
+        Ok(())
+    }
 
-        // Ingest from source to dest
-        dest.ingest(&mut source)?;
+    #[test]
+    fn test_drop_ignores_errors() -> crate::Result<()> {
+        /// Writer accepting the file header but failing all later writes
+        struct FullDisk(usize);
+        impl std::io::Write for FullDisk {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                if self.0 + buf.len() > SIZE_HEADER {
+                    return Err(std::io::Error::other("disk full"));
+                }
+                self.0 += buf.len();
+                Ok(buf.len())
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
 
-        // Source should be empty, dest should have content
-        let source_vec = source.by_ref();
-        assert_eq!(source_vec.len(), 0);
+        let mut writer = VBinseqWriterBuilder::default()
+            .header(VBinseqHeader::with_capacity(1024, false, true, false))
+            .build(FullDisk(0))?;
+        writer.write_nucleotides(0, b"ACGTACGT")?;
+        assert!(writer.finish().is_err());
+        drop(writer);
+
+        let mut writer = VBinseqWriterBuilder::default()
+            .header(VBinseqHeader::with_capacity(1024, false, true, false))
+            .build(FullDisk(0))?;
+        writer.write_nucleotides(0, b"ACGTACGT")?;
+        assert!(writer.close().is_err());
+        Ok(())
+    }
 
-        // Source ubuffer should be empty as well
-        let source_ubuf = &source.cblock.ubuf;
-        assert!(source_ubuf.is_empty());
+    #[test]
+    fn test_sync_writer() -> crate::Result<()> {
+        let mut header = VBinseqHeader::with_capacity(512, true, true, false);
+        header.set_footer(true);
+        let mut bytes = Vec::new();
+        let writer = VBinseqWriterBuilder::default()
+            .header(header)
+            .build(&mut bytes)?;
+        let writer = SyncWriter::new(writer, 3)?;
+        std::thread::scope(|scope| {
+            for tid in 0..4u64 {
+                let writer = &writer;
+                scope.spawn(move || -> crate::Result<()> {
+                    for i in 0..500 {
+                        let sequence = b"ACGTTGCA".repeat(1 + i % 5);
+                        let quality = vec![b'I'; sequence.len()];
+                        writer.write_nucleotides_quality(
+                            (tid << 32) | i as u64,
+                            &sequence,
+                            &quality,
+                        )?;
+                    }
+                    Ok(())
+                });
+            }
+        });
+        writer.close()?;
+
+        // All records are present and the records of every thread are in order
+        let mut reader = MemoryReader::new(bytes)?;
+        assert_eq!(reader.footer().map(|footer| footer.n_records), Some(2000));
+        let mut block = reader.new_block();
+        let mut flags = vec![Vec::new(); 4];
+        while reader.read_block_into(&mut block)? {
+            for record in block.iter() {
+                flags[(record.flag() >> 32) as usize].push(record.flag() & u32::MAX as u64);
+            }
+        }
+        for thread_flags in flags {
+            assert_eq!(thread_flags, (0..500).collect::<Vec<u64>>());
+        }
+        Ok(())
+    }
 
-        // The destination vec will be empty because we haven't hit a buffer limit
-        let dest_vec = dest.by_ref();
-        assert!(dest_vec.is_empty());
+    #[test]
+    fn test_parallel_writer() -> crate::Result<()> {
+        let mut rng = rand::rngs::SmallRng::seed_from_u64(12);
+        for mut header in testing::headers() {
+            header.set_aux(header.paired());
+            let records = testing::random_records(&mut rng, &header, 500);
+            let mut bytes = Vec::new();
+            let writer = VBinseqWriterBuilder::default()
+                .header(header)
+                .build(&mut bytes)?;
+            let mut writer = ParallelVBinseqWriter::new(writer, 3)?;
+            for record in &records {
+                writer.write_record(record)?;
+            }
+            writer.close()?;
 
-        // The destination ubuffer should have some data however
-        let dest_ubuf = &dest.cblock.ubuf;
-        assert!(!dest_ubuf.is_empty());
+            // The records are written in order, with the content of a single writer
+            let expected = testing::write_records(header, &records)?;
+            let (reader, expected_reader) = (
+                MemoryReader::new(bytes.clone())?,
+                MemoryReader::new(expected)?,
+            );
+            assert_eq!(
+                reader.footer().map(|footer| footer.digest),
+                expected_reader.footer().map(|footer| footer.digest)
+            );
+            let read = testing::read_records(bytes)?;
+            assert_eq!(read.len(), records.len(), "{}", header.summary());
+            for (read, written) in read.iter().zip(&testing::stored_records(&header, &records)) {
+                assert_eq!(
+                    (
+                        read.flag(),
+                        read.seq(),
+                        read.xseq(),
+                        read.squal(),
+                        read.aux()
+                    ),
+                    (
+                        written.flag(),
+                        written.seq(),
+                        written.xseq(),
+                        written.squal(),
+                        written.aux()
+                    )
+                );
+            }
+        }
 
+        // Skipped records are counted once the writer is finished
+        let mut writer = ParallelVBinseqWriter::new(
+            VBinseqWriterBuilder::default()
+                .policy(Policy::IgnoreSequence)
+                .build(Vec::new())?,
+            2,
+        )?;
+        writer.write_nucleotides(0, b"ACGT")?;
+        writer.write_nucleotides(1, b"ACNT")?;
+        writer.finish()?;
+        assert_eq!(writer.skipped().total, 1);
+        assert!(matches!(
+            writer.write_nucleotides(2, b"ACGT"),
+            Err(Error::WriteError(error::WriteError::WriterFinished))
+        ));
+
+        // Errors of records are reported by finish
+        let mut writer = ParallelVBinseqWriter::new(
+            VBinseqWriterBuilder::default()
+                .policy(Policy::BreakOnInvalid)
+                .build(Vec::new())?,
+            2,
+        )?;
+        writer.write_nucleotides(0, b"ACNT")?;
+        assert!(writer.finish().is_err());
         Ok(())
     }
 
     #[test]
-    fn test_ingest_multi_record() -> crate::Result<()> {
-        // Test ingesting a single record
-        let header = VBinseqHeader::new(false, false, false);
-
-        // Create a source writer with a single record
-        let mut source = VBinseqWriterBuilder::default()
+    fn test_ingest_ordered() -> crate::Result<()> {
+        let mut header = VBinseqHeader::with_capacity(512, true, false, false);
+        header.set_footer(true);
+        let mut bytes = Vec::new();
+        let mut writer = VBinseqWriterBuilder::default()
             .header(header)
-            .headless(true)
-            .build(Vec::new())?;
-
-        // Write multiple sequences
-        for _ in 0..30 {
-            let seq = b"ACGTACGTACGT";
-            source.write_nucleotides(1, seq)?;
+            .build(&mut bytes)?;
+
+        // Workers encode batches of various sizes and submit them as they finish
+        let (batch_tx, batch_rx) = std::sync::mpsc::channel::<(u64, u64)>();
+        let batch_rx = std::sync::Mutex::new(batch_rx);
+        let (done_tx, done_rx) = std::sync::mpsc::channel();
+        std::thread::scope(|scope| -> crate::Result<()> {
+            for _ in 0..4 {
+                let (batch_rx, done_tx) = (&batch_rx, done_tx.clone());
+                scope.spawn(move || -> crate::Result<()> {
+                    loop {
+                        let Ok((sequence, n_records)) = super::lock(batch_rx).recv() else {
+                            return Ok(());
+                        };
+                        let mut worker = VBinseqWriterBuilder::default()
+                            .header(header)
+                            .headless(true)
+                            .build(Vec::new())?;
+                        for i in 0..n_records {
+                            let record = b"ACGTTGCA".repeat(1 + i as usize % 5);
+                            let quality = vec![b'I'; record.len()];
+                            worker.write_nucleotides_quality(
+                                (sequence << 32) | i,
+                                &record,
+                                &quality,
+                            )?;
+                        }
+                        done_tx.send((sequence, worker)).unwrap();
+                    }
+                });
+            }
+            drop(done_tx);
+            for sequence in 0..40u64 {
+                batch_tx.send((sequence, 1 + sequence * 7 % 23)).unwrap();
+            }
+            drop(batch_tx);
+            for (sequence, mut worker) in done_rx {
+                writer.ingest_ordered(sequence, &mut worker)?;
+            }
+            Ok(())
+        })?;
+        assert_eq!(writer.pending_ingests(), 0);
+        assert!(matches!(
+            writer.ingest_ordered(
+                3,
+                &mut VBinseqWriterBuilder::default()
+                    .header(header)
+                    .headless(true)
+                    .build(Vec::new())?
+            ),
+            Err(Error::WriteError(error::WriteError::DuplicateSequence(3)))
+        ));
+        writer.close()?;
+
+        // The records of all batches are in submission order
+        let mut reader = MemoryReader::new(bytes)?;
+        let mut block = reader.new_block();
+        let mut flags = Vec::new();
+        while reader.read_block_into(&mut block)? {
+            flags.extend(block.iter().map(|record| record.flag()));
         }
-        // We have not crossed a boundary
-        assert!(source.by_ref().is_empty());
-
-        // Create a destination writer
-        let mut dest = VBinseqWriterBuilder::default()
+        let expected: Vec<u64> = (0..40u64)
+            .flat_map(|sequence| (0..1 + sequence * 7 % 23).map(move |i| (sequence << 32) | i))
+            .collect();
+        assert_eq!(flags, expected);
+        assert_eq!(
+            reader.footer().map(|footer| footer.n_records),
+            Some(expected.len() as u64)
+        );
+
+        // Finishing with a missing batch is an error
+        let mut writer = VBinseqWriterBuilder::default()
+            .header(header)
+            .build(Vec::new())?;
+        let mut worker = VBinseqWriterBuilder::default()
             .header(header)
             .headless(true)
             .build(Vec::new())?;
+        worker.write_nucleotides_quality(0, b"ACGT", b"IIII")?;
+        writer.ingest_ordered(1, &mut worker)?;
+        assert!(matches!(
+            writer.finish(),
+            Err(Error::WriteError(error::WriteError::MissingSequence(0, 1)))
+        ));
+        Ok(())
+    }
 
-        // Ingest from source to dest
-        dest.ingest(&mut source)?;
-
-        // Source should be empty, dest should have content
-        let source_vec = source.by_ref();
-        assert_eq!(source_vec.len(), 0);
-
-        // Source ubuffer should be empty as well
-        let source_ubuf = &source.cblock.ubuf;
-        assert!(source_ubuf.is_empty());
-
-        // The destination vec will be empty because we haven't hit a buffer limit
-        let dest_vec = dest.by_ref();
-        assert!(dest_vec.is_empty());
+    #[test]
+    fn test_quality_length_mismatch() -> crate::Result<()> {
+        let mut bytes = Vec::new();
+        let mut writer = VBinseqWriterBuilder::default()
+            .header(VBinseqHeader::with_capacity(1024, true, false, false))
+            .build(&mut bytes)?;
+        writer.write_nucleotides_quality(0, b"ACGT", b"IIII")?;
+        assert!(matches!(
+            writer.write_nucleotides_quality(5, b"ACGT", b"III"),
+            Err(Error::WriteError(error::WriteError::QualityLengthMismatch(
+                1, 5, 4, 3
+            )))
+        ));
+        assert!(writer.write_encoded(2, 4, &[0], b"IIIII").is_err());
+        writer.write_nucleotides_quality(3, b"TTGCA", b"!!!!!")?;
+        writer.finish()?;
+        drop(writer);
+
+        // Rejected records leave the block intact
+        let mut reader = MemoryReader::new(bytes)?;
+        let mut block = reader.new_block();
+        assert!(reader.read_block_into(&mut block)?);
+        let flags: Vec<_> = block.iter().map(|record| record.flag()).collect();
+        assert_eq!(flags, [0, 3]);
+
+        // The index counts the records of the flushed blocks
+        let mut writer = VBinseqWriterBuilder::default()
+            .header(VBinseqHeader::with_capacity(1024, true, false, true))
+            .flush_threshold(1)
+            .build(Vec::new())?;
+        writer.write_nucleotides_quality_paired(0, b"ACGT", b"AC", b"IIII", b"II")?;
+        writer.write_nucleotides_quality_paired(0, b"TTGA", b"CA", b"IIII", b"II")?;
+        assert!(matches!(
+            writer.write_nucleotides_quality_paired(0, b"ACGT", b"AC", b"IIII", b"III"),
+            Err(Error::WriteError(error::WriteError::QualityLengthMismatch(
+                2, 0, 2, 3
+            )))
+        ));
+        Ok(())
+    }
 
-        // The destination ubuffer should have some data however
-        let dest_ubuf = &dest.cblock.ubuf;
-        assert!(!dest_ubuf.is_empty());
+    #[test]
+    fn test_flagging_policies() -> crate::Result<()> {
+        /// Writes paired records with a policy and returns the flags and sequences read back
+        fn round_trip(
+            policy: Policy,
+            pairs: &[(&[u8], &[u8])],
+        ) -> crate::Result<Vec<(u64, Vec<u8>)>> {
+            let mut bytes = Vec::new();
+            let mut writer = VBinseqWriterBuilder::default()
+                .header(VBinseqHeader::with_capacity(1024, false, false, true))
+                .policy(policy)
+                .build(&mut bytes)?;
+            for (flag, (primary, extended)) in pairs.iter().enumerate() {
+                writer.write_nucleotides_paired(flag as u64, primary, extended)?;
+            }
+            writer.finish()?;
+            drop(writer);
+            let mut reader = MemoryReader::new(bytes)?;
+            let mut block = reader.new_block();
+            let mut records = Vec::new();
+            while reader.read_block_into(&mut block)? {
+                records.extend(block.iter().map(|record| (record.flag(), record.seq())));
+            }
+            Ok(records)
+        }
 
+        let pairs: [(&[u8], &[u8]); 3] =
+            [(b"ACGT", b"ACGT"), (b"ACNT", b"ACGT"), (b"ACgt", b"ACGT")];
+        let records = round_trip(Policy::SetToAWithFlag(1 << 8), &pairs)?;
+        assert_eq!(
+            records,
+            [
+                (0, b"ACGT".to_vec()),
+                (1 | 1 << 8, b"ACAT".to_vec()),
+                (2, b"ACGT".to_vec())
+            ]
+        );
+        let records = round_trip(Policy::FlagLowercase(1 << 9), &pairs)?;
+        assert_eq!(
+            records,
+            [(0, b"ACGT".to_vec()), (2 | 1 << 9, b"ACGT".to_vec())]
+        );
+
+        let mut writer = VBinseqWriterBuilder::default()
+            .policy(Policy::ReportPositions)
+            .build(Vec::new())?;
+        assert!(matches!(
+            writer.write_nucleotides(0, b"NACGTN"),
+            Err(Error::WriteError(error::WriteError::InvalidNucleotidePositions(positions))) if positions == [0, 5]
+        ));
         Ok(())
     }
 
     #[test]
-    fn test_ingest_block_boundary() -> crate::Result<()> {
-        // Test ingesting a single record
-        let header = VBinseqHeader::new(false, false, false);
+    fn test_skipped_records() -> crate::Result<()> {
+        let skipped = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let diverted = std::sync::Arc::clone(&skipped);
+        let mut writer = VBinseqWriterBuilder::default()
+            .header(VBinseqHeader::with_capacity(1024, true, false, true))
+            .skip_callback(move |record| {
+                let mut diverted = diverted.lock().unwrap();
+                diverted.push((record.flag, record.squal.to_vec(), record.reason));
+            })
+            .build(Vec::new())?;
+        writer.write_nucleotides_quality_paired(0, b"ACGT", b"AC", b"IIII", b"II")?;
+        writer.write_nucleotides_quality_paired(1, b"ACNT", b"AC", b"IIII", b"II")?;
+        writer.write_nucleotides_quality_paired(2, b"ACGT", b"NC", b"IIII", b"II")?;
+        writer.write_nucleotides_quality_paired(3, b"NNNN", b"NN", b"IIII", b"II")?;
+
+        let counts = writer.skipped();
+        assert_eq!(counts.total, 3);
+        assert_eq!(counts.by_reason(SkipReason::InvalidPrimary), 2);
+        assert_eq!(counts.by_reason(SkipReason::InvalidExtended), 1);
+        assert_eq!(
+            *skipped.lock().unwrap(),
+            [
+                (1, b"IIII".to_vec(), SkipReason::InvalidPrimary),
+                (2, b"IIII".to_vec(), SkipReason::InvalidExtended),
+                (3, b"IIII".to_vec(), SkipReason::InvalidPrimary),
+            ]
+        );
+
+        // Skip counts are summed across the shards of a sync writer
+        let writer = SyncWriter::new(VBinseqWriterBuilder::default().build(Vec::new())?, 2)?;
+        std::thread::scope(|scope| {
+            for _ in 0..2 {
+                scope.spawn(|| {
+                    for flag in 0..10 {
+                        writer.write_nucleotides(flag, b"ACGN").unwrap();
+                    }
+                });
+            }
+        });
+        assert_eq!(writer.skipped().total, 20);
+        writer.finish()?;
+        assert_eq!(writer.skipped().total, 20);
+        Ok(())
+    }
+    #[test]
+    fn test_record_transform() -> crate::Result<()> {
+        // Moves a 2-nucleotide barcode of the primary read into the flag
+        let header = VBinseqHeader::with_capacity(1024, true, false, true);
+        let mut bytes = Vec::new();
+        let mut writer = VBinseqWriterBuilder::default()
+            .header(header)
+            .record_transform(|record| {
+                let barcode: Vec<u8> = record.sequence.drain(..2).collect();
+                record.squal.drain(..2);
+                record.flag = u64::from(barcode == b"AC");
+                record.extended.truncate(2);
+                record.xqual.truncate(2);
+            })
+            .build(&mut bytes)?;
+        assert!(writer.write_nucleotides_quality_paired(0, b"ACGTT", b"TTAA", b"IIII#", b"##II")?);
+        assert!(writer.write_nucleotides_quality_paired(0, b"GGCCA", b"CA", b"IIIII", b"II")?);
+
+        // Checks of the write method still apply to the original record
+        assert!(writer.write_nucleotides(0, b"ACGT").is_err());
+        writer.finish()?;
+        drop(writer);
+
+        let mut reader = MemoryReader::new(bytes)?;
+        let mut block = reader.new_block();
+        assert!(reader.read_block_into(&mut block)?);
+        let records: Vec<_> = block
+            .iter()
+            .map(|record| {
+                (
+                    record.flag(),
+                    record.slen(),
+                    record.xlen(),
+                    record.squal().to_vec(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            records,
+            [(1, 3, 2, b"II#".to_vec()), (0, 3, 2, b"III".to_vec())]
+        );
+        Ok(())
+    }
 
-        // Create a source writer with a single record
-        let mut source = VBinseqWriterBuilder::default()
+    #[test]
+    fn test_record_aux() -> crate::Result<()> {
+        let mut header = VBinseqHeader::with_capacity(1024, true, true, false);
+        header.set_aux(true);
+        header.set_record_crc(true);
+        let mut bytes = Vec::new();
+        let mut writer = VBinseqWriterBuilder::default()
             .header(header)
-            .headless(true)
-            .build(Vec::new())?;
+            .build(&mut bytes)?;
+
+        // The auxiliary value applies until it is set again
+        writer.write_nucleotides_quality(0, b"ACGT", b"IIII")?;
+        writer.set_record_aux(u64::MAX)?;
+        writer.write_nucleotides_quality(1, b"ACGTA", b"IIIII")?;
+        writer.write_nucleotides_quality(2, b"ACG", b"III")?;
+        writer
+            .write_record(&OwnedRecord::new(3, b"TTTT".to_vec(), b"####".to_vec()).with_aux(7))?;
+        writer.finish()?;
+        drop(writer);
+
+        let mut reader = MemoryReader::new(bytes)?;
+        let mut block = reader.new_block();
+        assert!(reader.read_block_into(&mut block)?);
+        let records: Vec<_> = block
+            .iter()
+            .map(|record| (record.flag(), record.aux(), record.squal().to_vec()))
+            .collect();
+        assert_eq!(
+            records,
+            [
+                (0, 0, b"IIII".to_vec()),
+                (1, u64::MAX, b"IIIII".to_vec()),
+                (2, u64::MAX, b"III".to_vec()),
+                (3, 7, b"####".to_vec()),
+            ]
+        );
+
+        let mut writer = VBinseqWriterBuilder::default().build(Vec::new())?;
+        assert!(matches!(
+            writer.set_record_aux(1),
+            Err(Error::WriteError(error::WriteError::AuxFlagNotSet))
+        ));
+        Ok(())
+    }
 
-        // Write multiple sequences (will cross boundary)
-        for _ in 0..30000 {
-            let seq = b"ACGTACGTACGT";
-            source.write_nucleotides(1, seq)?;
+    #[test]
+    fn test_long_read_profile() -> crate::Result<()> {
+        let mut rng = rand::rngs::SmallRng::seed_from_u64(5);
+        let read: Vec<u8> = (0..2_000_000)
+            .map(|_| b"ACGT"[rand::Rng::gen_range(&mut rng, 0..4)])
+            .collect();
+        let qual: Vec<u8> = (0..read.len())
+            .map(|i| b'0' + (i / 100 % 10) as u8)
+            .collect();
+
+        // Long reads exceed the default block size
+        let mut writer = VBinseqWriterBuilder::default()
+            .header(VBinseqHeader::new(true, true, false))
+            .build(Vec::new())?;
+        assert!(matches!(
+            writer.write_nucleotides_quality(0, &read, &qual),
+            Err(Error::WriteError(
+                error::WriteError::RecordSizeExceedsMaximumBlockSize(..)
+            ))
+        ));
+
+        let mut bytes = Vec::new();
+        let mut writer = VBinseqWriterBuilder::default()
+            .long_read_profile()
+            .build(&mut bytes)?;
+        assert_eq!(writer.header(), VBinseqHeader::long_read_profile());
+        assert_eq!(writer.cblock.level, super::LONG_READ_COMPRESSION_LEVEL);
+        for flag in 0..3 {
+            writer.write_nucleotides_quality(flag, &read, &qual)?;
         }
+        writer.finish()?;
+        drop(writer);
+
+        // The run-length transformed qualities compress well
+        assert!(bytes.len() < 3 * read.len() / 2);
+        let mut reader = MemoryReader::new(bytes)?;
+        let mut block = reader.new_block();
+        let mut sequence = Vec::new();
+        let mut n_records = 0;
+        while reader.read_block_into(&mut block)? {
+            for record in block.iter() {
+                sequence.clear();
+                record.decode_s(&mut sequence)?;
+                assert_eq!(sequence, read);
+                assert_eq!(record.squal(), qual);
+                n_records += 1;
+            }
+        }
+        assert_eq!(n_records, 3);
+        Ok(())
+    }
 
-        // We have crossed a boundary
-        assert!(!source.by_ref().is_empty());
-
-        // Create a destination writer
-        let mut dest = VBinseqWriterBuilder::default()
-            .header(header)
-            .headless(true)
+    #[test]
+    fn test_fixed_length() -> crate::Result<()> {
+        let mut rng = rand::rngs::SmallRng::seed_from_u64(8);
+        let records: Vec<_> = (0..500)
+            .map(|flag| {
+                let sequence = (0..150)
+                    .map(|_| b"ACGT"[rand::Rng::gen_range(&mut rng, 0..4)])
+                    .collect();
+                OwnedRecord::new(flag, sequence, vec![b'I' - (flag % 20) as u8; 150]).with_aux(flag)
+            })
+            .collect();
+        let mut header = VBinseqHeader::with_capacity(4096, true, false, false);
+        header.set_aux(true);
+        header.set_record_crc(true);
+        header.set_quality_transform(crate::QualityTransform::Delta);
+        let mut fixed = header;
+        fixed.set_fixed_length(true);
+        let plain = testing::write_records(header, &records)?;
+        let bytes = testing::write_records(fixed, &records)?;
+
+        // Records omit their lengths and are read back unchanged
+        assert!(bytes.len() < plain.len());
+        assert_eq!(
+            testing::read_records(bytes.clone())?,
+            testing::read_records(plain)?
+        );
+        let mut reader = MemoryReader::new(bytes)?;
+        while let Some(raw) = reader.next_raw_block()? {
+            assert_eq!(raw.header.fixed_length(), 150);
+        }
+        let dir = std::env::temp_dir().join(format!("vbq_fixed_{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join("reads.vbq");
+        std::fs::write(&path, testing::write_records(fixed, &records)?)?;
+        assert!(crate::validate::check(&path)?.is_valid());
+        std::fs::remove_dir_all(&dir)?;
+
+        // Every record must have the length of the first record
+        let mut writer = VBinseqWriterBuilder::default()
+            .header(fixed)
             .build(Vec::new())?;
+        writer.write_nucleotides_quality(0, b"ACGT", b"IIII")?;
+        assert!(matches!(
+            writer.write_nucleotides_quality(1, b"ACG", b"III"),
+            Err(Error::WriteError(error::WriteError::FixedLengthMismatch(
+                4, 3
+            )))
+        ));
+
+        // Paired records vary in length
+        let mut header = VBinseqHeader::new(false, false, true);
+        header.set_fixed_length(true);
+        let error = VBinseqWriterBuilder::default()
+            .header(header)
+            .build(Vec::new());
+        assert!(matches!(
+            error,
+            Err(Error::HeaderError(error::HeaderError::InvalidFixedLength))
+        ));
+        Ok(())
+    }
 
-        // Ingest from source to dest
-        dest.ingest(&mut source)?;
-
-        // Source should be empty, dest should have content
-        let source_vec = source.by_ref();
-        assert_eq!(source_vec.len(), 0);
+    #[test]
+    fn test_optional_quality() -> crate::Result<()> {
+        for paired in [false, true] {
+            let mut header = VBinseqHeader::with_capacity(256, true, true, paired);
+            header.set_quality_transform(crate::QualityTransform::Delta);
+            header.set_record_crc(true);
+            header.set_footer(true);
+
+            // Quality scores are required unless they are optional
+            let mut writer = VBinseqWriterBuilder::default()
+                .header(header)
+                .build(Vec::new())?;
+            let missing = if paired {
+                writer.write_nucleotides_paired(0, b"ACGT", b"TTGCA")
+            } else {
+                writer.write_nucleotides(0, b"ACGT")
+            };
+            assert!(matches!(
+                missing,
+                Err(Error::WriteError(error::WriteError::QualityFlagSet))
+            ));
+
+            header.set_optional_quality(true);
+            let mut bytes = Vec::new();
+            let mut writer = VBinseqWriterBuilder::default()
+                .header(header)
+                .build(&mut bytes)?;
+            let mut expected = Vec::new();
+            for flag in 0..60u64 {
+                let sequence = b"ACGTTGCAAC".repeat(1 + flag as usize % 3);
+                let mate = if paired {
+                    b"GGCAT".to_vec()
+                } else {
+                    Vec::new()
+                };
+                let (squal, xqual) = if flag % 3 == 0 {
+                    (Vec::new(), Vec::new())
+                } else {
+                    (
+                        vec![b'0' + flag as u8 % 40; sequence.len()],
+                        vec![b'#'; mate.len()],
+                    )
+                };
+                let record = if paired {
+                    OwnedRecord::new_paired(flag, sequence, mate, squal, xqual)
+                } else {
+                    OwnedRecord::new(flag, sequence, squal)
+                };
+                writer.write_record(&record)?;
+                expected.push((flag, record.squal().to_vec(), record.xqual().to_vec()));
+            }
+            writer.finish()?;
+            drop(writer);
+
+            // Records without quality scores are read back without them
+            let records: Vec<_> = testing::read_records(bytes.clone())?
+                .into_iter()
+                .map(|record| {
+                    (
+                        record.flag(),
+                        record.squal().to_vec(),
+                        record.xqual().to_vec(),
+                    )
+                })
+                .collect();
+            assert_eq!(records, expected);
+            let mut reader = MemoryReader::new(bytes.clone())?;
+            let mut block = reader.new_block();
+            while reader.read_block_into(&mut block)? {
+                assert!(block.iter_checked(&header).all(|result| result.is_ok()));
+            }
+            let dir = std::env::temp_dir().join(format!("vbq_optqual_{}", std::process::id()));
+            std::fs::create_dir_all(&dir)?;
+            let path = dir.join("reads.vbq");
+            std::fs::write(&path, &bytes)?;
+            assert!(crate::validate::check(&path)?.is_valid());
+            std::fs::remove_dir_all(&dir)?;
+        }
+        Ok(())
+    }
 
-        // Source ubuffer should be empty as well
-        let source_ubuf = &source.cblock.ubuf;
-        assert!(source_ubuf.is_empty());
+    #[test]
+    fn test_members() -> crate::Result<()> {
+        let mut first = VBinseqHeader::with_capacity(512, true, true, false);
+        first.set_footer(true);
+        let mut second = VBinseqHeader::with_capacity(256, false, false, true);
+        second.set_footer(true);
+
+        let mut bytes = Vec::new();
+        let mut writer = VBinseqWriterBuilder::default()
+            .header(first)
+            .compression_level(5)
+            .build(&mut bytes)?;
+        for flag in 0..50 {
+            writer.write_nucleotides_quality(flag, b"ACGTACGT", b"IIIIIIII")?;
+        }
+        writer.finish_member()?;
+        assert!(writer
+            .write_nucleotides_quality(0, b"ACGT", b"IIII")
+            .is_err());
+        writer.start_member(second)?;
+        assert_eq!(writer.cblock.level, 5);
+        for flag in 50..80 {
+            writer.write_nucleotides_paired(flag, b"ACGT", b"TTGCA")?;
+        }
+        // Starting a member finishes the current one, also if it has no records
+        writer.start_member(first)?;
+        writer.start_member(first)?;
+        writer.write_nucleotides_quality(80, b"GGCC", b"####")?;
+        writer.finish()?;
+        drop(writer);
+
+        let mut reader = MemoryReader::new(bytes)?;
+        let mut block = reader.new_block();
+        let mut flags = Vec::new();
+        while reader.read_block_into(&mut block)? {
+            let paired = reader.header().paired();
+            for record in block.iter() {
+                assert_eq!(record.is_paired(), paired);
+                flags.push(record.flag());
+            }
+        }
+        assert_eq!(flags, (0..81).collect::<Vec<_>>());
+        assert_eq!(reader.header(), first);
+        assert_eq!(reader.footer().map(|footer| footer.n_records), Some(1));
+        Ok(())
+    }
 
-        // The destination vec will not be empty because we hit a buffer limit
-        let dest_vec = dest.by_ref();
-        assert!(!dest_vec.is_empty());
+    #[test]
+    fn test_append() -> crate::Result<()> {
+        let mut rng = rand::rngs::SmallRng::seed_from_u64(11);
+        let path = std::env::temp_dir().join(format!("vbq_append_{}.vbq", std::process::id()));
+        let index = with_suffix(&path, ".vqi");
+        for mut header in testing::headers() {
+            header.set_aux(header.paired());
+            header.set_record_crc(header.qual());
+            // Fixed-length files have no block checksums
+            header.set_block_checksum(header.compressed() && !header.is_fixed_length());
+            let records = testing::random_records(&mut rng, &header, 60);
+            std::fs::write(&path, testing::write_records(header, &records[..25])?)?;
+            std::fs::write(&index, b"stale")?;
+
+            // Records can be appended any number of times
+            for range in [25..40, 40..40, 40..60] {
+                let mut writer = VBinseqWriter::append(&path)?;
+                assert_eq!(writer.header(), header);
+                assert!(!index.exists());
+                assert_eq!(writer.cblock.next_index(), range.start as u64);
+                testing::write_all(&mut writer, &records[range])?;
+                writer.close()?;
+            }
+            let read = testing::read_records(std::fs::read(&path)?)?;
+            assert_eq!(read.len(), records.len(), "{}", header.summary());
+            for (read, written) in read.iter().zip(&testing::stored_records(&header, &records)) {
+                assert_eq!(
+                    (read.flag(), read.seq(), read.xseq(), read.aux()),
+                    (written.flag(), written.seq(), written.xseq(), written.aux())
+                );
+                assert_eq!(
+                    (read.squal(), read.xqual()),
+                    (written.squal(), written.xqual())
+                );
+            }
+            if header.has_footer() {
+                assert!(footer::verify(&path)?, "{}", header.summary());
+            }
+        }
 
-        // The destination ubuffer should have some data however
-        let dest_ubuf = &dest.cblock.ubuf;
-        assert!(!dest_ubuf.is_empty());
+        // The last block is re-opened instead of leaving partial blocks behind
+        let header = VBinseqHeader::with_capacity(1024, false, false, false);
+        std::fs::write(&path, testing::write_records(header, &[])?)?;
+        for flag in 0..10 {
+            let mut writer = VBinseqWriter::append(&path)?;
+            writer.write_nucleotides(flag, b"ACGTACGTTTGCA")?;
+            writer.close()?;
+        }
+        let mut reader = MemoryReader::new(std::fs::read(&path)?)?;
+        let mut block = reader.new_block();
+        assert!(reader.read_block_into(&mut block)?);
+        assert_eq!(block.n_records(), 10);
+        assert!(!reader.read_block_into(&mut block)?);
 
+        // Files with a header written by the builder are not changed by it
+        let writer = VBinseqWriterBuilder::default()
+            .header(VBinseqHeader::new(true, false, false))
+            .append(&path)?;
+        assert_eq!(writer.header(), header);
+        drop(writer);
+
+        // Invalid files are rejected
+        std::fs::write(&path, b"not a vbq file")?;
+        assert!(VBinseqWriter::append(&path).is_err());
+        std::fs::remove_file(&path)?;
         Ok(())
     }
 
     #[test]
-    fn test_ingest_with_quality_scores() -> crate::Result<()> {
-        // Test ingesting records with quality scores
-        let source_header = VBinseqHeader::new(true, false, false); // with quality
-        let dest_header = VBinseqHeader::new(true, false, false); // with quality
-
-        // Create a source writer with quality scores
-        let mut source = VBinseqWriterBuilder::default()
-            .header(source_header)
-            .headless(true)
-            .build(Vec::new())?;
+    fn test_embedded_index() -> crate::Result<()> {
+        use crate::error::{HeaderError, IndexError};
+
+        let mut rng = rand::rngs::SmallRng::seed_from_u64(13);
+        let path = std::env::temp_dir().join(format!("vbq_embedded_{}.vbq", std::process::id()));
+        for mut header in testing::headers() {
+            header.set_embedded_index(true);
+            let records = testing::random_records(&mut rng, &header, 120);
+            let bytes = testing::write_records(header, &records[..80])?;
+            let index = BlockIndex::from_embedded(&bytes)?;
+            assert!(index.n_blocks() > 1);
+            assert_eq!(index.ranges(), BlockIndex::from_bytes(&bytes)?.ranges());
+            assert_eq!(testing::read_records(bytes.clone())?.len(), 80);
+
+            // Appending rewrites the index along with the footer
+            std::fs::write(&path, &bytes)?;
+            let mut writer = VBinseqWriter::append(&path)?;
+            testing::write_all(&mut writer, &records[80..])?;
+            writer.close()?;
+            let bytes = std::fs::read(&path)?;
+            let index = BlockIndex::from_embedded(&bytes)?;
+            assert_eq!(index.ranges(), BlockIndex::from_bytes(&bytes)?.ranges());
+            assert_eq!(index.n_records(), records.len() as u64);
+            assert!(footer::verify(&path)?, "{}", header.summary());
+            let report = validate::check(&path)?;
+            assert!(report.is_valid() && report.index_checked, "{report}");
+
+            // Readers load the index without an index file
+            #[cfg(feature = "mmap")]
+            {
+                let index_path = with_suffix(&path, ".vqi");
+                std::fs::remove_file(&index_path).ok();
+                let mut reader = MmapReader::new(&path)?;
+                reader.set_index_policy(IndexPolicy::RequireValid);
+                assert_eq!(reader.load_index()?.ranges(), index.ranges());
+                assert!(!index_path.exists());
+                let mut block = reader.new_block();
+                assert!(reader.seek_record(&index, 100, &mut block)?);
+                let stored = testing::stored_records(&header, &records[100..101]);
+                assert_eq!(block.iter().next().unwrap().seq(), stored[0].seq());
+            }
 
-        // Write sequences with quality scores
-        for i in 0..5 {
-            let seq = b"ACGTACGTACGT";
-            // Simple quality scores (all the same for this test)
-            let qual = vec![40; seq.len()];
-            source.write_nucleotides_quality(i, seq, &qual)?;
+            // Files with embedded indexes can be concatenated and streamed
+            let both = [bytes.as_slice(), &bytes].concat();
+            assert_eq!(
+                testing::read_records(both.clone())?.len(),
+                2 * records.len()
+            );
+            let mut reader = StreamReader::new(both.as_slice())?;
+            let mut block = reader.new_block();
+            let mut n_records = 0;
+            while reader.read_block_into(&mut block)? {
+                n_records += block.n_records();
+            }
+            assert_eq!(n_records, 2 * records.len());
         }
 
-        // Create a destination writer
-        let mut dest = VBinseqWriterBuilder::default()
-            .header(dest_header)
-            .headless(true)
-            .build(Vec::new())?;
-
-        // Ingest from source to dest
-        dest.ingest(&mut source)?;
+        // Blocks compressed by the parallel writer are indexed in file order
+        let mut header = VBinseqHeader::with_capacity(1024, true, true, false);
+        header.set_embedded_index(true);
+        let records = testing::random_records(&mut rng, &header, 500);
+        let mut bytes = Vec::new();
+        let writer = VBinseqWriterBuilder::default()
+            .header(header)
+            .build(&mut bytes)?;
+        let mut writer = ParallelVBinseqWriter::new(writer, 3)?;
+        for record in &records {
+            writer.write_record(record)?;
+        }
+        writer.close()?;
+        let index = BlockIndex::from_embedded(&bytes)?;
+        assert_eq!(index.ranges(), BlockIndex::from_bytes(&bytes)?.ranges());
+
+        // Corrupt embedded indexes are detected
+        let mut corrupt = bytes.clone();
+        let start = corrupt.len() - footer::SIZE_FOOTER - index.embedded_size() as usize;
+        corrupt[start] ^= 0xFF;
+        assert!(matches!(
+            BlockIndex::from_embedded(&corrupt),
+            Err(Error::IndexError(IndexError::InvalidEmbeddedIndex(_)))
+        ));
+        assert!(matches!(
+            BlockIndex::from_embedded(&testing::write_records(VBinseqHeader::default(), &[])?),
+            Err(Error::IndexError(IndexError::MissingEmbeddedIndex))
+        ));
+
+        // The index is found through the footer
+        header.set_footer(false);
+        assert!(matches!(
+            VBinseqWriterBuilder::default()
+                .header(header)
+                .build(Vec::new()),
+            Err(Error::HeaderError(HeaderError::EmbeddedIndexWithoutFooter))
+        ));
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
 
-        // Verify source is cleared
-        let source_vec = source.by_ref();
-        assert_eq!(source_vec.len(), 0);
+    #[test]
+    fn test_atomic() -> crate::Result<()> {
+        let dir = std::env::temp_dir().join(format!("vbq_atomic_{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join("reads.vbq");
+        let (temp, index) = (with_suffix(&path, ".tmp"), with_suffix(&path, ".vqi"));
+
+        // The file and its index only appear once the writer is finished
+        let mut writer = VBinseqWriterBuilder::default()
+            .header(VBinseqHeader::with_capacity(256, false, false, false))
+            .atomic(true)
+            .create(&path)?;
+        for flag in 0..100 {
+            writer.write_nucleotides(flag, b"ACGTACGTTTGCA")?;
+        }
+        assert!(temp.exists() && !path.exists() && !index.exists());
+        writer.close()?;
+        assert!(!temp.exists() && path.exists());
+        let mut reader = MemoryReader::new(std::fs::read(&path)?)?;
+        let mut block = reader.new_block();
+        let mut n_records = 0;
+        while reader.read_block_into(&mut block)? {
+            n_records += block.n_records();
+        }
+        assert_eq!(n_records, 100);
+        #[cfg(feature = "compression")]
+        assert_eq!(BlockIndex::from_path(&index)?.n_records(), 100);
+
+        // Writers dropped without being finished leave no output behind
+        std::fs::remove_file(&path)?;
+        #[cfg(feature = "compression")]
+        std::fs::remove_file(&index)?;
+        let mut writer = VBinseqWriterBuilder::default().atomic(true).create(&path)?;
+        writer.write_nucleotides(0, b"ACGT")?;
+        drop(writer);
+        assert!(!temp.exists() && !path.exists() && !index.exists());
+
+        // Without the option, files are written in place
+        let mut writer = VBinseqWriterBuilder::default().create(&path)?;
+        writer.write_nucleotides(0, b"ACGT")?;
+        drop(writer);
+        assert!(path.exists() && !temp.exists() && !index.exists());
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
 
-        // Verify destination has content in ubuf
-        let dest_ubuf = &dest.cblock.ubuf;
-        assert!(!dest_ubuf.is_empty());
+    #[test]
+    fn test_metadata() -> crate::Result<()> {
+        use crate::error::HeaderError;
+        use crate::read_group::ReadGroup;
+        use crate::sections::HeaderSections;
+
+        // Files without metadata keep format 1 headers
+        let mut bytes = Vec::new();
+        let mut writer = VBinseqWriterBuilder::default().build(&mut bytes)?;
+        writer.write_nucleotides(0, b"ACGT")?;
+        assert!(writer.metadata().is_empty());
+        writer.finish()?;
+        drop(writer);
+        let reader = MemoryReader::new(bytes)?;
+        assert_eq!(reader.header().format(), 1);
+        assert!(reader.metadata()?.is_empty());
+
+        for header in crate::testing::headers() {
+            let mut bytes = Vec::new();
+            let mut writer = VBinseqWriterBuilder::default()
+                .header(header)
+                .metadata("sample", "NA12878")
+                .metadata("command", "vbq encode -o out.vbq in.fq")
+                .metadata("sample", "")
+                .build(&mut bytes)?;
+            let metadata = writer.metadata().to_vec();
+            assert_eq!(metadata.len(), 3);
+            let record = crate::testing::random_records(
+                &mut rand::rngs::SmallRng::seed_from_u64(3),
+                &header,
+                1,
+            );
+            crate::testing::write_all(&mut writer, &record)?;
+            writer.finish()?;
+            drop(writer);
+
+            let reader = MemoryReader::new(bytes.clone())?;
+            assert_eq!(reader.metadata()?, metadata);
+            let sections = reader.sections()?;
+            assert_eq!(sections.metadata_value("sample"), Some("NA12878"));
+            assert_eq!(sections.metadata_value("instrument"), None);
+            assert_eq!(StreamReader::new(bytes.as_slice())?.metadata()?, metadata);
+            assert_eq!(crate::testing::read_records(bytes)?.len(), 1);
+        }
 
+        // Sections with unknown tags are skipped, and metadata is kept with other sections
+        let sections = HeaderSections {
+            read_groups: vec![ReadGroup::new("lane1")],
+            metadata: vec![("key".into(), "value".into())],
+            ..HeaderSections::default()
+        };
+        let mut bytes = vec![99, 0, 2, 0, 0, 0, 1, 2];
+        bytes.extend_from_slice(&sections.to_bytes());
+        assert_eq!(HeaderSections::from_bytes(&bytes)?, sections);
+
+        // Truncated metadata is rejected
+        let metadata = HeaderSections {
+            metadata: sections.metadata,
+            ..HeaderSections::default()
+        };
+        let offset = bytes.len() - metadata.to_bytes().len();
+        bytes.truncate(bytes.len() - 1);
+        bytes[offset + 2] -= 1;
+        assert!(matches!(
+            HeaderSections::from_bytes(&bytes),
+            Err(crate::Error::HeaderError(HeaderError::InvalidSection(pos))) if pos == offset
+        ));
         Ok(())
     }
 
     #[test]
-    fn test_ingest_with_compression() -> crate::Result<()> {
-        // Test ingesting a single record
-        let header = VBinseqHeader::new(false, true, false);
+    fn test_segments() -> crate::Result<()> {
+        use crate::error::{HeaderError, WriteError};
+
+        let mut rng = rand::rngs::SmallRng::seed_from_u64(21);
+        // Homopolymer and fixed-length files have at most two segments
+        let headers = testing::headers()
+            .into_iter()
+            .filter(|header| !header.is_homopolymer() && !header.is_fixed_length());
+        for mut header in headers {
+            header.set_segments(4)?;
+            header.set_block(4096)?;
+            header.set_aux(header.compressed());
+            header.set_record_crc(header.has_footer());
+            let records = testing::random_records(&mut rng, &header, 500);
+            testing::assert_round_trip(header, &records);
+
+            // Parallel writers and validation handle every segment
+            let mut bytes = Vec::new();
+            let writer = VBinseqWriterBuilder::default()
+                .header(header)
+                .build(&mut bytes)?;
+            let mut writer = ParallelVBinseqWriter::new(writer, 2)?;
+            for record in &records {
+                writer.write_record(record)?;
+            }
+            writer.close()?;
+            assert_eq!(
+                testing::read_records(bytes.clone())?,
+                testing::read_records(testing::write_records(header, &records)?)?
+            );
+            let dir = std::env::temp_dir().join(format!("vbq_segments_{}", std::process::id()));
+            std::fs::create_dir_all(&dir)?;
+            let path = dir.join("reads.vbq");
+            std::fs::write(&path, &bytes)?;
+            assert!(crate::validate::check(&path)?.is_valid());
+
+            // Filtered records keep every segment
+            #[cfg(feature = "mmap")]
+            {
+                let output = dir.join("even.vbq");
+                crate::filter::by_flag(&path, &output, |flag| flag % 2 == 0)?;
+                let even: Vec<_> = records
+                    .iter()
+                    .filter(|record| record.flag() % 2 == 0)
+                    .cloned()
+                    .collect();
+                let filtered = testing::read_records(std::fs::read(&output)?)?;
+                assert_eq!(filtered.len(), even.len());
+                for (filtered, record) in filtered.iter().zip(&even) {
+                    assert_eq!(filtered.segment(3), record.segment(3));
+                    assert_eq!(filtered.segment_qual(3), record.segment_qual(3));
+                }
+            }
+            std::fs::remove_dir_all(&dir)?;
+
+            let mut stream = StreamReader::new(bytes.as_slice())?;
+            let mut block = stream.new_block();
+            let mut n_records = 0;
+            while stream.read_block_into(&mut block)? {
+                for record in block.iter() {
+                    let written = &records[record.index() as usize];
+                    let mut segment = Vec::new();
+                    record.decode_segment(3, &mut segment)?;
+                    assert_eq!(segment, written.segment(3));
+                    n_records += 1;
+                }
+            }
+            assert_eq!(n_records, records.len());
+
+            let mut reader = MemoryReader::new(bytes)?;
+            assert_eq!(reader.header().segments(), 4);
+
+            // Raw blocks are only copied between files with the same number of segments
+            let mut paired = header;
+            paired.set_paired(true);
+            let mut writer = VBinseqWriterBuilder::default()
+                .header(paired)
+                .build(Vec::new())?;
+            let block = reader.next_raw_block()?.unwrap();
+            assert!(matches!(
+                writer.write_raw_block(&block),
+                Err(crate::Error::WriteError(WriteError::IncompatibleHeaders(
+                    ..
+                )))
+            ));
+        }
 
-        // Create a source writer with a single record
-        let mut source = VBinseqWriterBuilder::default()
+        let mut header = VBinseqHeader::with_capacity(1024, true, false, false);
+        header.set_segments(3)?;
+        let mut writer = VBinseqWriterBuilder::default()
             .header(header)
-            .headless(true)
             .build(Vec::new())?;
+        let sequences: [&[u8]; 3] = [b"ACGT", b"", b"GGC"];
+        let qualities: [&[u8]; 3] = [b"IIII", b"", b"III"];
+        assert!(writer.write_segments(0, &sequences, &qualities)?);
+
+        // Records must have a sequence and quality scores for every segment
+        assert!(matches!(
+            writer.write_segments(0, &sequences[..2], &qualities[..2]),
+            Err(crate::Error::WriteError(WriteError::SegmentCountMismatch(
+                3, 2
+            )))
+        ));
+        assert!(matches!(
+            writer.write_segments(0, &sequences, &[]),
+            Err(crate::Error::WriteError(WriteError::QualityFlagSet))
+        ));
+        assert!(matches!(
+            writer.write_segments(0, &sequences, &[b"IIII", b"", b"II"]),
+            Err(crate::Error::WriteError(WriteError::QualityLengthMismatch(
+                1, 0, 3, 2
+            )))
+        ));
+        assert!(matches!(
+            writer.write_nucleotides_quality_paired(0, b"A", b"C", b"I", b"I"),
+            Err(crate::Error::WriteError(WriteError::SegmentCountMismatch(
+                3, 2
+            )))
+        ));
+
+        // Records with a rejected segment are skipped
+        assert!(!writer.write_segments(0, &[b"ACGT", b"", b"GNC"], &qualities)?);
+        assert_eq!(writer.skipped().invalid_extended, 1);
+
+        // Homopolymer files are limited to pairs
+        header.set_homopolymer(true);
+        header.set_qual(false);
+        assert!(matches!(
+            VBinseqWriterBuilder::default()
+                .header(header)
+                .build(Vec::new()),
+            Err(crate::Error::HeaderError(HeaderError::InvalidSegments(3)))
+        ));
+        Ok(())
+    }
 
-        // Write multiple sequences (will cross boundary)
-        for _ in 0..30000 {
-            let seq = b"ACGTACGTACGT";
-            source.write_nucleotides(1, seq)?;
+    #[test]
+    fn test_quality_bins() -> crate::Result<()> {
+        let bins = QualityBins::illumina();
+        let binned = |scores: &[u8]| {
+            let mut scores = scores.to_vec();
+            bins.apply(&mut scores);
+            scores
+        };
+        let mut rng = rand::rngs::SmallRng::seed_from_u64(23);
+        for header in testing::headers().into_iter().filter(VBinseqHeader::qual) {
+            let mut segments = header;
+            segments.set_fixed_length(false);
+            segments.set_segments(3)?;
+            segments.set_block(4096)?;
+            for header in [header, segments] {
+                let records = testing::random_records(&mut rng, &header, 200);
+                let mut bytes = Vec::new();
+                let mut writer = VBinseqWriterBuilder::default()
+                    .header(header)
+                    .quality_bins(bins.clone())
+                    .build(&mut bytes)?;
+                testing::write_all(&mut writer, &records)?;
+                writer.finish()?;
+                drop(writer);
+
+                // Readers find the bins and return the binned scores
+                let reader = MemoryReader::new(bytes.clone())?;
+                assert_eq!(reader.sections()?.quality_bins.as_ref(), Some(&bins));
+                let read = testing::read_records(bytes)?;
+                assert_eq!(read.len(), records.len());
+                for (read, written) in read.iter().zip(&records) {
+                    assert_eq!(read.seq(), written.seq());
+                    assert_eq!(read.squal(), binned(written.squal()));
+                    assert_eq!(read.xqual(), binned(written.xqual()));
+                    for i in 2..written.n_segments() {
+                        assert_eq!(read.segment_qual(i), binned(written.segment_qual(i)));
+                    }
+                }
+            }
         }
 
-        // Create a destination writer
-        let mut dest = VBinseqWriterBuilder::default()
+        // Bins are ignored without quality scores
+        let header = VBinseqHeader::with_capacity(1024, false, false, false);
+        let records = testing::random_records(&mut rng, &header, 10);
+        let mut bytes = Vec::new();
+        let mut writer = VBinseqWriterBuilder::default()
             .header(header)
-            .headless(true)
-            .build(Vec::new())?;
-
-        // Ingest from source to dest
-        dest.ingest(&mut source)?;
-
-        // Source should be empty, dest should have content
-        let source_vec = source.by_ref();
-        assert_eq!(source_vec.len(), 0);
+            .quality_bins(bins.clone())
+            .build(&mut bytes)?;
+        testing::write_all(&mut writer, &records)?;
+        writer.finish()?;
+        drop(writer);
+        let read = testing::read_records(bytes)?;
+        assert!(read.iter().zip(&records).all(|(a, b)| a.seq() == b.seq()));
+        Ok(())
+    }
 
-        // Source ubuffer should be empty as well
-        let source_ubuf = &source.cblock.ubuf;
-        assert!(source_ubuf.is_empty());
+    #[test]
+    fn test_columnar() -> crate::Result<()> {
+        use rand::Rng;
+
+        let mut rng = rand::rngs::SmallRng::seed_from_u64(25);
+        let path = std::env::temp_dir().join(format!("vbq_columnar_{}.vbq", std::process::id()));
+        for mut header in testing::headers() {
+            header.set_columnar(true);
+            header.set_aux(header.paired());
+            header.set_record_crc(header.qual());
+            header.set_block_checksum(header.compressed() && !header.is_fixed_length());
+            let records = testing::random_records(&mut rng, &header, 200);
+            testing::assert_round_trip(header, &records);
+
+            // Single records are located in the row layout, with or without their offsets
+            let bytes = testing::write_records(header, &records)?;
+            let reader = MemoryReader::new(bytes.clone())?;
+            let mut index = BlockIndex::from_bytes(&bytes)?;
+            let mut block = reader.new_block();
+            for offsets in [false, true] {
+                if offsets {
+                    index.add_record_offsets(&bytes)?;
+                }
+                for i in [0, 57, 199] {
+                    assert!(reader.seek_record(&index, i as u64, &mut block)?);
+                    let record = block.iter().next().expect("record was read");
+                    assert_eq!(record.flag(), records[i].flag());
+                    assert_eq!(record.aux(), records[i].aux());
+                }
+            }
 
-        // The destination vec will not be empty because we hit a buffer limit
-        let dest_vec = dest.by_ref();
-        assert!(!dest_vec.is_empty());
+            // Block checksums, record checksums, and the footer digest are verified
+            std::fs::write(&path, &bytes)?;
+            assert!(
+                crate::validate::check(&path)?.is_valid(),
+                "{}",
+                header.summary()
+            );
 
-        // The destination ubuffer should have some data however
-        let dest_ubuf = &dest.cblock.ubuf;
-        assert!(!dest_ubuf.is_empty());
+            // Records of more segments are grouped the same way
+            let mut segments = header;
+            segments.set_homopolymer(false);
+            segments.set_fixed_length(false);
+            segments.set_segments(3)?;
+            segments.set_block(4096)?;
+            segments.set_iupac(true);
+            let records = testing::random_records(&mut rng, &segments, 100);
+            testing::assert_round_trip(segments, &records);
+        }
+        std::fs::remove_file(&path)?;
 
+        // Fixed-length records store only their flag in the preamble region
+        let sequence = |rng: &mut rand::rngs::SmallRng, len: usize| -> Vec<u8> {
+            (0..len).map(|_| b"ACGT"[rng.gen_range(0..4)]).collect()
+        };
+        let records: Vec<_> = (0..300)
+            .map(|flag| {
+                OwnedRecord::new(flag, sequence(&mut rng, 100), vec![b'F'; 100]).with_aux(flag)
+            })
+            .collect();
+        let mut header = VBinseqHeader::with_capacity(4096, true, true, false);
+        header.set_fixed_length(true);
+        header.set_aux(true);
+        header.set_columnar(true);
+        testing::assert_round_trip(header, &records);
+
+        // Grouping the parts of the records improves compression
+        let records: Vec<_> = (0..2000)
+            .map(|flag| {
+                let len = rng.gen_range(100..150);
+                let mut quality = Vec::new();
+                while quality.len() < len {
+                    let run = rng.gen_range(1..20);
+                    quality.extend(std::iter::repeat_n(b"#,:F"[rng.gen_range(0..4)], run));
+                }
+                quality.truncate(len);
+                OwnedRecord::new(flag, sequence(&mut rng, len), quality)
+            })
+            .collect();
+        let mut header = VBinseqHeader::with_capacity(1 << 16, true, true, false);
+        let rows = testing::write_records(header, &records)?;
+        header.set_columnar(true);
+        let columns = testing::write_records(header, &records)?;
+        assert!(
+            columns.len() < rows.len(),
+            "{} >= {}",
+            columns.len(),
+            rows.len()
+        );
+        assert_eq!(
+            testing::read_records(columns)?,
+            testing::read_records(rows)?
+        );
         Ok(())
     }
 
     #[test]
-    fn test_ingest_incompatible_headers() -> crate::Result<()> {
-        let source_header = VBinseqHeader::new(false, false, false);
-        let dest_header = VBinseqHeader::new(true, false, false);
-
-        // Create a source writer with quality scores
-        let mut source = VBinseqWriterBuilder::default()
-            .header(source_header)
-            .headless(true)
-            .build(Vec::new())?;
+    fn test_iupac() -> crate::Result<()> {
+        let mut rng = rand::rngs::SmallRng::seed_from_u64(22);
+        for mut header in testing::headers() {
+            header.set_iupac(true);
+            let records = testing::random_records(&mut rng, &header, 300);
+            assert!(records.iter().any(|record| record.seq().contains(&b'N')));
+            testing::assert_round_trip(header, &records);
+
+            // Files with more segments or fixed lengths store the same codes
+            let mut segments = header;
+            segments.set_homopolymer(false);
+            segments.set_fixed_length(false);
+            segments.set_segments(3)?;
+            segments.set_block(4096)?;
+            let records = testing::random_records(&mut rng, &segments, 100);
+            testing::assert_round_trip(segments, &records);
+            if header.supports_fixed_length() {
+                let mut fixed = header;
+                fixed.set_fixed_length(true);
+                let records: Vec<_> = (0..100)
+                    .map(|i| {
+                        let codes = iupac::ALPHABET[1..].iter().cycle().skip(i);
+                        let quality = vec![b'I'; if header.qual() { 16 } else { 0 }];
+                        OwnedRecord::new(0, codes.take(16).copied().collect(), quality)
+                    })
+                    .collect();
+                testing::assert_round_trip(fixed, &records);
+            }
+        }
 
-        // Create a destination writer
-        let mut dest = VBinseqWriterBuilder::default()
-            .header(dest_header)
-            .headless(true)
+        // IUPAC codes are stored exactly, and only other bytes are handled by the policy
+        let mut header = VBinseqHeader::with_capacity(1024, false, false, false);
+        header.set_iupac(true);
+        let mut bytes = Vec::new();
+        let mut writer = VBinseqWriterBuilder::default()
+            .header(header)
+            .policy(Policy::SetToAWithFlag(1 << 40))
+            .build(&mut bytes)?;
+        assert!(writer.write_nucleotides(0, b"ACGTNNRYacgtn")?);
+        assert!(writer.write_nucleotides(0, b"AC-GN.T")?);
+        writer.finish()?;
+        drop(writer);
+        let records = testing::read_records(bytes)?;
+        assert_eq!(records[0].seq(), b"ACGTNNRYACGTN");
+        assert_eq!(records[0].flag(), 0);
+        assert_eq!(records[1].seq(), b"ACAGNAT");
+        assert_eq!(records[1].flag(), 1 << 40);
+
+        // Pre-encoded sequences use 16 nucleotides per word
+        let mut writer = VBinseqWriterBuilder::default()
+            .header(header)
             .build(Vec::new())?;
-
-        // Ingest from source to dest (will error)
-        assert!(dest.ingest(&mut source).is_err());
-
+        let mut words = Vec::new();
+        iupac::encode(&[b'N'; 20], &mut words)?;
+        assert!(writer.write_encoded(0, 20, &words, &[]).is_ok());
+        assert!(writer.write_encoded(0, 40, &words, &[]).is_err());
+
+        // Blocks are only copied between files with the same encoding
+        let mut two_bit = header;
+        two_bit.set_iupac(false);
+        let source =
+            testing::write_records(two_bit, &[OwnedRecord::new(0, b"ACGT".to_vec(), vec![])])?;
+        let mut reader = MemoryReader::new(source)?;
+        let raw = reader.next_raw_block()?.expect("file has a block");
+        assert!(matches!(
+            writer.write_raw_block(&raw),
+            Err(crate::Error::WriteError(
+                error::WriteError::IncompatibleHeaders(_, _)
+            ))
+        ));
         Ok(())
     }
 }